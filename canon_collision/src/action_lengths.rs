@@ -0,0 +1,114 @@
+use canon_collision_lib::assets::Assets;
+use canon_collision_lib::entity_def::{ActionDef, ActionFrame};
+use canon_collision_lib::package::Package;
+
+use std::collections::HashMap;
+
+use gltf::buffer::Source as BufferSource;
+use gltf::Gltf;
+
+/// Compares the number of frames in every entity action with the length of the animation of the same name in the entity's model,
+/// played at the action's animation_speed.
+/// Mismatches are logged, and when `resize` is set the action is resized to the length of its animation.
+/// Frames are removed from the end or the last frame is repeated, so hitboxes may need to be adjusted afterwards.
+/// Resized actions are only kept once the package is saved.
+pub fn check_package(package: &mut Package, assets: &Assets, resize: bool) {
+    for key in package.entities.keys() {
+        let entity = package.entities.key_to_value_mut(&key).unwrap();
+        let model_name = entity.name.replace(' ', "");
+        let lengths = match assets.read_model(&model_name) {
+            Some(data) => animation_lengths(&data),
+            None => continue,
+        };
+
+        for action_key in entity.actions.keys() {
+            let action = &mut entity.actions[action_key.as_ref()];
+            let animation_len = match lengths.get(&action_key) {
+                Some(len) => action.frames_for_animation(*len),
+                None => continue,
+            };
+            let action_len = action.frames.len();
+            if action_len != animation_len {
+                if resize {
+                    resize_action(action, animation_len);
+                    warn!(
+                        "Resized {} action {} from {} to {} frames to match its animation",
+                        key, action_key, action_len, animation_len
+                    );
+                } else {
+                    warn!(
+                        "{} action {} has {} frames but its animation needs {} frames, run with --resize-actions to match them",
+                        key, action_key, action_len, animation_len
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Returns the number of frames in every named animation of the gltf model
+pub fn animation_lengths(data: &[u8]) -> HashMap<String, usize> {
+    let mut lengths = HashMap::new();
+    let gltf = match Gltf::from_slice(data) {
+        Ok(gltf) => gltf,
+        Err(err) => {
+            error!("Failed to read gltf animations: {}", err);
+            return lengths;
+        }
+    };
+    let blob = match gltf.blob.as_ref() {
+        Some(blob) => blob,
+        None => return lengths,
+    };
+
+    for animation in gltf.animations() {
+        if let Some(name) = animation.name() {
+            let mut last_input: f32 = 0.0;
+            for channel in animation.channels() {
+                let reader = channel.reader(|buffer| match buffer.source() {
+                    BufferSource::Bin => Some(blob),
+                    _ => None,
+                });
+                if let Some(inputs) = reader.read_inputs() {
+                    last_input = inputs.fold(last_input, f32::max);
+                }
+            }
+            lengths.insert(name.to_string(), frames_from_seconds(last_input));
+        }
+    }
+    lengths
+}
+
+/// Animations are played at 60fps with the first keyframe on frame 0
+pub fn frames_from_seconds(seconds: f32) -> usize {
+    (seconds * 60.0).round() as usize + 1
+}
+
+fn resize_action(action: &mut ActionDef, frames: usize) {
+    let frames = frames.max(1);
+    while action.frames.len() > frames {
+        action.frames.pop();
+    }
+    while action.frames.len() < frames {
+        let frame = action
+            .frames
+            .last()
+            .cloned()
+            .unwrap_or_else(ActionFrame::default);
+        action.frames.push(frame);
+    }
+}
+
+#[test]
+fn resize_action_test() {
+    assert_eq!(frames_from_seconds(0.0), 1);
+    assert_eq!(frames_from_seconds(59.0 / 60.0), 60);
+
+    let mut action = ActionDef::default();
+    action.frames[0].ecb.top = 5.0;
+    resize_action(&mut action, 3);
+    assert_eq!(action.frames.len(), 3);
+    assert_eq!(action.frames[2].ecb.top, 5.0);
+    resize_action(&mut action, 0);
+    assert_eq!(action.frames.len(), 1);
+}
@@ -1,31 +1,727 @@
-use crate::game::Game;
+use crate::game::{Game, GameState};
+
+use canon_collision_lib::entity_def::player::PlayerAction;
 use canon_collision_lib::input::state::ControllerInput;
+use canon_collision_lib::stage::Stage;
+
+use rand::Rng;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaChaRng;
+
+use std::collections::VecDeque;
+
+/// How well a CPU player plays, selected per CPU on the character select screen
+pub struct Difficulty {
+    /// Only displayed by the renderer
+    #[cfg_attr(not(feature = "wgpu_renderer"), allow(dead_code))]
+    pub name: &'static str,
+    /// Idle CPUs never press anything, useful as a training dummy
+    idle: bool,
+    /// Number of frames it takes to notice what opponents are doing
+    reaction_frames: usize,
+    /// Chance of acting on an opportunity to attack, jump or grab each frame
+    action_chance: f32,
+    /// Chance of shielding each frame an opponent in range has an active hitbox
+    shield_chance: f32,
+    /// Guard the ledge when an opponent is recovering instead of waiting for them to return
+    edgeguard: bool,
+}
+
+pub const DIFFICULTIES: [Difficulty; 5] = [
+    Difficulty {
+        name: "Idle",
+        idle: true,
+        reaction_frames: 0,
+        action_chance: 0.0,
+        shield_chance: 0.0,
+        edgeguard: false,
+    },
+    Difficulty {
+        name: "Easy",
+        idle: false,
+        reaction_frames: 30,
+        action_chance: 0.05,
+        shield_chance: 0.0,
+        edgeguard: false,
+    },
+    Difficulty {
+        name: "Medium",
+        idle: false,
+        reaction_frames: 20,
+        action_chance: 0.15,
+        shield_chance: 0.1,
+        edgeguard: false,
+    },
+    Difficulty {
+        name: "Hard",
+        idle: false,
+        reaction_frames: 12,
+        action_chance: 0.35,
+        shield_chance: 0.3,
+        edgeguard: true,
+    },
+    Difficulty {
+        name: "Expert",
+        idle: false,
+        reaction_frames: 6,
+        action_chance: 0.7,
+        shield_chance: 0.6,
+        edgeguard: true,
+    },
+];
+
+/// The difficulty of CPUs added on the character select screen before one is chosen
+pub const DEFAULT_DIFFICULTY: usize = 2;
+
+/// Distance from an opponent to start attacking at
+const PRESSURE_RANGE: f32 = 20.0;
+/// Distance from an opponent to stop attacking at, larger than `PRESSURE_RANGE` so the CPU commits to its pressure
+const PRESSURE_EXIT_RANGE: f32 = 30.0;
+/// Distance from the ledge an edgeguarding CPU waits at
+const EDGEGUARD_OFFSET: f32 = 10.0;
+/// Frames spent hanging on the ledge before getting up
+const LEDGE_WAIT_FRAMES: u64 = 10;
+/// Training dummies press shield to tech when they will land within this many frames
+const TECH_LEAD_FRAMES: f32 = 5.0;
+
+/// How a training dummy influences the angle it is launched at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DummyDi {
+    None,
+    /// Towards the nearest opponent
+    In,
+    /// Away from the nearest opponent
+    Out,
+    /// One of the above, chosen again after every hit
+    Random,
+}
+
+impl DummyDi {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DummyDi::None => "None",
+            DummyDi::In => "In",
+            DummyDi::Out => "Out",
+            DummyDi::Random => "Random",
+        }
+    }
+
+    pub fn next(&self) -> DummyDi {
+        match self {
+            DummyDi::None => DummyDi::In,
+            DummyDi::In => DummyDi::Out,
+            DummyDi::Out => DummyDi::Random,
+            DummyDi::Random => DummyDi::None,
+        }
+    }
+}
+
+/// How a training dummy lands after being launched into tumble
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DummyTech {
+    InPlace,
+    /// Rolls away from the nearest opponent
+    Away,
+    Missed,
+    /// One of the above, chosen again after every hit
+    Random,
+}
+
+impl DummyTech {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DummyTech::InPlace => "In place",
+            DummyTech::Away => "Away",
+            DummyTech::Missed => "Missed",
+            DummyTech::Random => "Random",
+        }
+    }
+
+    pub fn next(&self) -> DummyTech {
+        match self {
+            DummyTech::InPlace => DummyTech::Away,
+            DummyTech::Away => DummyTech::Missed,
+            DummyTech::Missed => DummyTech::Random,
+            DummyTech::Random => DummyTech::InPlace,
+        }
+    }
+}
+
+/// How a training dummy shields while it is on the ground
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DummyShield {
+    None,
+    Hold,
+    /// Holds shield and grabs out of it once shield stun ends
+    Grab,
+}
+
+impl DummyShield {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DummyShield::None => "None",
+            DummyShield::Hold => "Hold",
+            DummyShield::Grab => "Shield then grab",
+        }
+    }
+
+    pub fn next(&self) -> DummyShield {
+        match self {
+            DummyShield::None => DummyShield::Hold,
+            DummyShield::Hold => DummyShield::Grab,
+            DummyShield::Grab => DummyShield::None,
+        }
+    }
+}
+
+/// How CPUs with the Idle difficulty behave as a training dummy, selected from the pause menu.
+/// The default never presses anything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DummyBehavior {
+    pub di: DummyDi,
+    pub tech: DummyTech,
+    pub shield: DummyShield,
+}
+
+impl Default for DummyBehavior {
+    fn default() -> DummyBehavior {
+        DummyBehavior {
+            di: DummyDi::None,
+            tech: DummyTech::Missed,
+            shield: DummyShield::None,
+        }
+    }
+}
+
+/// The parts of a fighter's state that CPU players decide their inputs from.
+#[derive(Clone)]
+pub struct FighterState {
+    pub controller: usize,
+    pub team: usize,
+    pub x: f32,
+    pub y: f32,
+    pub y_vel: f32,
+    pub face_right: bool,
+    pub airbourne: bool,
+    pub ledge: bool,
+    pub air_jumps_left: u64,
+    pub action: Option<PlayerAction>,
+    /// The current frame has a hitbox
+    pub attacking: bool,
+    pub intangible: bool,
+    pub hitstun: bool,
+    pub shield_stun: bool,
+}
+
+impl FighterState {
+    fn shielding(&self) -> bool {
+        matches!(
+            self.action,
+            Some(PlayerAction::ShieldOn)
+                | Some(PlayerAction::Shield)
+                | Some(PlayerAction::PowerShield)
+        )
+    }
+
+    fn tumbling(&self) -> bool {
+        matches!(
+            self.action,
+            Some(PlayerAction::DamageFly) | Some(PlayerAction::DamageFall)
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Behavior {
+    /// Move towards the nearest opponent
+    Approach,
+    /// Attack, shield and grab an opponent in range
+    Pressure,
+    /// Return to the stage after being knocked off it
+    Recover,
+    /// Wait at the ledge for an opponent that is recovering
+    Edgeguard,
+}
+
+/// Generates the inputs of every CPU player in the game.
+/// CPU players have the controllers after every human controller, one for each of `Game::selected_ais`.
+pub struct Cpus {
+    cpus: Vec<Cpu>,
+    /// Opponent states from previous frames, newest at the back, used to delay reactions
+    observations: VecDeque<Vec<FighterState>>,
+    init_seed: u64,
+    previous_frame: usize,
+}
+
+struct Cpu {
+    difficulty: usize,
+    controller: usize,
+    behavior: Behavior,
+    /// Frames spent on the ledge, reset after leaving it
+    ledge_frames: u64,
+    /// Used by training dummies set to random DI, chosen again after every hit
+    random_di: DummyDi,
+    /// Used by training dummies set to random techs, chosen again after every hit
+    random_tech: DummyTech,
+    /// The fighter was in hitstun on the previous frame
+    hitstun: bool,
+    /// Set when the fighter enters shield stun, a training dummy grabs once it ends
+    grab_after_shield_stun: bool,
+    rng: ChaChaRng,
+    /// Held until the game steps so that every press is seen by the game
+    input: ControllerInput,
+}
 
-pub fn gen_inputs(game: &Game) -> Vec<ControllerInput> {
-    game.selected_ais
+impl Cpus {
+    pub fn new() -> Cpus {
+        Cpus {
+            cpus: vec![],
+            observations: VecDeque::new(),
+            init_seed: 0,
+            previous_frame: 0,
+        }
+    }
+
+    pub fn gen_inputs(&mut self, game: &Game) -> Vec<ControllerInput> {
+        // CPUs only play while the game is running, replays already have their inputs
+        if !matches!(game.state, GameState::Local | GameState::Netplay) {
+            return game.selected_ais.iter().map(|_| neutral()).collect();
+        }
+
+        // a new game has started
+        if self.cpus.len() != game.selected_ais.len() || self.init_seed != game.init_seed {
+            self.init_seed = game.init_seed;
+            self.observations.clear();
+            let mut controllers = game.selected_controllers.clone();
+            controllers.sort_unstable();
+            let first_cpu = controllers.len().saturating_sub(game.selected_ais.len());
+            self.cpus = game
+                .selected_ais
+                .iter()
+                .zip(controllers[first_cpu..].iter())
+                .map(|(difficulty, controller)| Cpu {
+                    difficulty: (*difficulty).min(DIFFICULTIES.len() - 1),
+                    controller: *controller,
+                    behavior: Behavior::Approach,
+                    ledge_frames: 0,
+                    random_di: DummyDi::None,
+                    random_tech: DummyTech::Missed,
+                    hitstun: false,
+                    grab_after_shield_stun: false,
+                    rng: ChaChaRng::seed_from_u64(game.init_seed.wrapping_add(*controller as u64)),
+                    input: neutral(),
+                })
+                .collect();
+        }
+
+        if game.current_frame != self.previous_frame {
+            // the frame went backwards when rolled back or rewound, so what was observed never happened
+            if game.current_frame < self.previous_frame {
+                self.observations.clear();
+            }
+            self.previous_frame = game.current_frame;
+
+            let fighters = game.ai_states();
+            self.observations.push_back(fighters.clone());
+            let max_reaction = DIFFICULTIES
+                .iter()
+                .map(|x| x.reaction_frames)
+                .max()
+                .unwrap_or(0);
+            while self.observations.len() > max_reaction + 1 {
+                self.observations.pop_front();
+            }
+
+            for cpu in &mut self.cpus {
+                let difficulty = &DIFFICULTIES[cpu.difficulty];
+                let observed_i = self
+                    .observations
+                    .len()
+                    .saturating_sub(difficulty.reaction_frames + 1);
+                let observed = &self.observations[observed_i];
+                cpu.input = match fighters.iter().find(|x| x.controller == cpu.controller) {
+                    Some(me) if difficulty.idle => {
+                        cpu.dummy_step(me, observed, &game.stage, game.dummy())
+                    }
+                    Some(me) => cpu.step(me, observed, &game.stage),
+                    None => neutral(),
+                };
+            }
+        }
+
+        self.cpus.iter().map(|x| x.input).collect()
+    }
+}
+
+impl Cpu {
+    fn step(
+        &mut self,
+        me: &FighterState,
+        observed: &[FighterState],
+        stage: &Stage,
+    ) -> ControllerInput {
+        let difficulty = &DIFFICULTIES[self.difficulty];
+        let previous = self.input;
+        let mut input = neutral();
+
+        let geometry = StageGeometry::new(stage);
+        // the state of opponents is perceived `reaction_frames` late
+        let target = nearest_opponent(me, observed);
+
+        self.behavior = self.next_behavior(me, target, &geometry);
+        if me.ledge {
+            self.ledge_frames += 1;
+        } else {
+            self.ledge_frames = 0;
+        }
+
+        match (self.behavior, target) {
+            (Behavior::Recover, _) => {
+                let (ledge_x, ledge_y) = geometry.nearest_ledge(me.x);
+                if me.ledge {
+                    if self.ledge_frames > LEDGE_WAIT_FRAMES {
+                        input.stick_x = (geometry.centre_x - me.x).signum();
+                    }
+                } else {
+                    input.stick_x = (ledge_x - me.x).signum();
+                    if me.y < ledge_y && me.y_vel <= 0.0 {
+                        if me.air_jumps_left > 0 {
+                            input.y = !previous.y;
+                        } else {
+                            input.stick_y = 1.0;
+                            input.b = !previous.b;
+                        }
+                    }
+                }
+            }
+            (Behavior::Edgeguard, Some(target)) => {
+                let (ledge_x, _) = geometry.nearest_ledge(target.x);
+                let wait_x = ledge_x + (geometry.centre_x - ledge_x).signum() * EDGEGUARD_OFFSET;
+                let distance = target.x - me.x;
+                if distance.abs() < PRESSURE_RANGE
+                    && self.rng.gen::<f32>() < difficulty.action_chance
+                {
+                    // hit the opponent as they reach the ledge
+                    input.stick_x = distance.signum() * 0.5;
+                    input.a = !previous.a;
+                } else if (wait_x - me.x).abs() > 2.0 {
+                    input.stick_x = (wait_x - me.x).signum() * 0.6;
+                }
+            }
+            (Behavior::Pressure, Some(target)) => {
+                let distance_x = target.x - me.x;
+                let distance_y = target.y - me.y;
+                let facing_target = (distance_x > 0.0) == me.face_right;
+                if target.attacking
+                    && !me.airbourne
+                    && self.rng.gen::<f32>() < difficulty.shield_chance
+                {
+                    input.l = true;
+                    input.l_trigger = 1.0;
+                } else if self.rng.gen::<f32>() < difficulty.action_chance {
+                    if target.shielding() && !me.airbourne {
+                        input.z = !previous.z;
+                    } else if !facing_target && !me.airbourne {
+                        // turn around before attacking
+                        input.stick_x = distance_x.signum() * 0.5;
+                    } else {
+                        if distance_y > PRESSURE_RANGE / 2.0 {
+                            input.stick_y = 0.5;
+                        } else if distance_x.abs() > PRESSURE_RANGE / 2.0 {
+                            input.stick_x = distance_x.signum() * 0.5;
+                        }
+                        input.a = !previous.a;
+                    }
+                }
+            }
+            (_, Some(target)) => {
+                let distance_x = target.x - me.x;
+                input.stick_x = distance_x.signum();
+                if target.y - me.y > PRESSURE_RANGE
+                    && !me.airbourne
+                    && self.rng.gen::<f32>() < difficulty.action_chance
+                {
+                    input.y = !previous.y;
+                }
+            }
+            (_, None) => {}
+        }
+
+        input
+    }
+
+    /// Training dummies only react to being hit and shielded according to `dummy`
+    fn dummy_step(
+        &mut self,
+        me: &FighterState,
+        observed: &[FighterState],
+        stage: &Stage,
+        dummy: DummyBehavior,
+    ) -> ControllerInput {
+        let previous = self.input;
+        let mut input = neutral();
+
+        let geometry = StageGeometry::new(stage);
+        let target = nearest_opponent(me, observed);
+        let away_x = match target {
+            Some(target) => (me.x - target.x).signum(),
+            None => (me.x - geometry.centre_x).signum(),
+        };
+        let threatened = target
+            .map(|x| x.attacking && (x.x - me.x).abs() < PRESSURE_RANGE)
+            .unwrap_or(false);
+
+        if me.hitstun && !self.hitstun {
+            self.random_di = [DummyDi::None, DummyDi::In, DummyDi::Out][self.rng.gen_range(0..3)];
+            self.random_tech =
+                [DummyTech::InPlace, DummyTech::Away, DummyTech::Missed][self.rng.gen_range(0..3)];
+        }
+        self.hitstun = me.hitstun;
+        let di = match dummy.di {
+            DummyDi::Random => self.random_di,
+            di => di,
+        };
+        let tech = match dummy.tech {
+            DummyTech::Random => self.random_tech,
+            tech => tech,
+        };
+
+        if me.shield_stun {
+            self.grab_after_shield_stun = true;
+        }
+        let shielding = dummy.shield != DummyShield::None && !me.airbourne && !me.hitstun;
+        if shielding {
+            input.l = true;
+            input.l_trigger = 1.0;
+            if dummy.shield == DummyShield::Grab
+                && self.grab_after_shield_stun
+                && !me.shield_stun
+                && me.shielding()
+            {
+                input.z = !previous.z;
+                self.grab_after_shield_stun = false;
+            }
+        } else if me.hitstun || threatened {
+            // the stick is held before the hit lands, as the launch angle is decided on the frame of the hit
+            match di {
+                DummyDi::In => input.stick_x = -away_x,
+                DummyDi::Out => input.stick_x = away_x,
+                DummyDi::None | DummyDi::Random => {}
+            }
+        }
+        if !me.shielding() {
+            self.grab_after_shield_stun = false;
+        }
+
+        if me.tumbling() && me.airbourne && me.y_vel < 0.0 {
+            if let Some(floor_y) = geometry.floor_below(me.x, me.y) {
+                if me.y - floor_y <= -me.y_vel * TECH_LEAD_FRAMES {
+                    match tech {
+                        DummyTech::InPlace => {
+                            input.stick_x = 0.0;
+                            input.l = !previous.l;
+                        }
+                        DummyTech::Away => {
+                            input.stick_x = away_x;
+                            input.l = !previous.l;
+                        }
+                        DummyTech::Missed | DummyTech::Random => {}
+                    }
+                }
+            }
+        }
+
+        input
+    }
+
+    fn next_behavior(
+        &self,
+        me: &FighterState,
+        target: Option<&FighterState>,
+        geometry: &StageGeometry,
+    ) -> Behavior {
+        let difficulty = &DIFFICULTIES[self.difficulty];
+        if me.ledge || (me.airbourne && geometry.offstage(me)) {
+            return Behavior::Recover;
+        }
+
+        let target = match target {
+            Some(target) => target,
+            None => return Behavior::Approach,
+        };
+        if difficulty.edgeguard && target.airbourne && geometry.offstage(target) {
+            return Behavior::Edgeguard;
+        }
+
+        let distance = ((target.x - me.x).powi(2) + (target.y - me.y).powi(2)).sqrt();
+        let range = if self.behavior == Behavior::Pressure {
+            PRESSURE_EXIT_RANGE
+        } else {
+            PRESSURE_RANGE
+        };
+        if distance < range {
+            Behavior::Pressure
+        } else {
+            Behavior::Approach
+        }
+    }
+}
+
+/// The closest opponent that can be hit
+fn nearest_opponent<'a>(
+    me: &FighterState,
+    observed: &'a [FighterState],
+) -> Option<&'a FighterState> {
+    observed
         .iter()
-        .map(|_| ControllerInput {
-            plugged_in: true,
-
-            up: false,
-            down: false,
-            right: false,
-            left: false,
-            y: false,
-            x: false,
-            b: false,
-            a: false,
-            l: false,
-            r: false,
-            z: false,
-            start: false,
-
-            stick_x: 0.0,
-            stick_y: 0.0,
-            c_stick_x: 0.0,
-            c_stick_y: 0.0,
-            l_trigger: 0.0,
-            r_trigger: 0.0,
+        .filter(|x| x.team != me.team && !x.intangible)
+        .min_by(|a, b| {
+            let distance_a = (a.x - me.x).powi(2) + (a.y - me.y).powi(2);
+            let distance_b = (b.x - me.x).powi(2) + (b.y - me.y).powi(2);
+            distance_a.partial_cmp(&distance_b).unwrap()
         })
-        .collect()
+}
+
+/// The parts of the stage a CPU needs to know to stay on it
+struct StageGeometry {
+    /// (x, y) of every grabbable ledge
+    ledges: Vec<(f32, f32)>,
+    /// ((x1, y1), (x2, y2)) of every floor, including platforms
+    floors: Vec<((f32, f32), (f32, f32))>,
+    /// The area spanned by solid floors and ledges, fighters outside of it are offstage
+    left: f32,
+    right: f32,
+    bottom: f32,
+    centre_x: f32,
+}
+
+impl StageGeometry {
+    fn new(stage: &Stage) -> StageGeometry {
+        let mut ledges = vec![];
+        for surface in stage.surfaces.iter() {
+            if surface.grab1 {
+                ledges.push((surface.x1, surface.y1));
+            }
+            if surface.grab2 {
+                ledges.push((surface.x2, surface.y2));
+            }
+        }
+
+        let floors = stage
+            .surfaces
+            .iter()
+            .filter(|x| x.floor.is_some())
+            .map(|x| ((x.x1, x.y1), (x.x2, x.y2)))
+            .collect();
+
+        let mut points = ledges.clone();
+        for surface in stage.surfaces.iter() {
+            if surface
+                .floor
+                .as_ref()
+                .map(|x| !x.pass_through)
+                .unwrap_or(false)
+            {
+                points.push((surface.x1, surface.y1));
+                points.push((surface.x2, surface.y2));
+            }
+        }
+
+        let left = points.iter().map(|x| x.0).fold(f32::INFINITY, f32::min);
+        let right = points.iter().map(|x| x.0).fold(f32::NEG_INFINITY, f32::max);
+        let bottom = points.iter().map(|x| x.1).fold(f32::INFINITY, f32::min);
+        if points.is_empty() {
+            StageGeometry {
+                ledges,
+                floors,
+                left: f32::NEG_INFINITY,
+                right: f32::INFINITY,
+                bottom: f32::NEG_INFINITY,
+                centre_x: 0.0,
+            }
+        } else {
+            StageGeometry {
+                ledges,
+                floors,
+                left,
+                right,
+                bottom,
+                centre_x: (left + right) / 2.0,
+            }
+        }
+    }
+
+    fn offstage(&self, fighter: &FighterState) -> bool {
+        fighter.x < self.left || fighter.x > self.right || fighter.y < self.bottom
+    }
+
+    /// Returns the height of the highest floor under (`x`, `y`)
+    fn floor_below(&self, x: f32, y: f32) -> Option<f32> {
+        self.floors
+            .iter()
+            .filter(|((x1, _), (x2, _))| x1.min(*x2) <= x && x <= x1.max(*x2))
+            .map(|((x1, y1), (x2, y2))| {
+                if x1 == x2 {
+                    y1.max(*y2)
+                } else {
+                    y1 + (y2 - y1) * (x - x1) / (x2 - x1)
+                }
+            })
+            .filter(|floor_y| *floor_y <= y)
+            .fold(None, |highest: Option<f32>, floor_y| {
+                Some(highest.map_or(floor_y, |x| x.max(floor_y)))
+            })
+    }
+
+    /// Returns the ledge closest to `x`, the edge of the stage is used when there are no ledges
+    fn nearest_ledge(&self, x: f32) -> (f32, f32) {
+        self.ledges
+            .iter()
+            .cloned()
+            .min_by(|a, b| (a.0 - x).abs().partial_cmp(&(b.0 - x).abs()).unwrap())
+            .unwrap_or(if x < self.centre_x {
+                (self.left, self.bottom)
+            } else {
+                (self.right, self.bottom)
+            })
+    }
+}
+
+fn neutral() -> ControllerInput {
+    ControllerInput {
+        plugged_in: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn stage_geometry_test() {
+    let geometry = StageGeometry::new(&Stage::default());
+    let fighter = FighterState {
+        controller: 0,
+        team: 0,
+        x: 0.0,
+        y: 10.0,
+        y_vel: 0.0,
+        face_right: true,
+        airbourne: true,
+        ledge: false,
+        air_jumps_left: 1,
+        action: None,
+        attacking: false,
+        intangible: false,
+        hitstun: false,
+        shield_stun: false,
+    };
+    assert!(!geometry.offstage(&fighter));
+    assert!(geometry.offstage(&FighterState {
+        x: 100.0,
+        ..fighter.clone()
+    }));
+    assert!(geometry.offstage(&FighterState {
+        y: -10.0,
+        ..fighter
+    }));
+    assert!(geometry.floor_below(0.0, 10.0).is_some());
+    assert_eq!(geometry.floor_below(0.0, -1000.0), None);
 }
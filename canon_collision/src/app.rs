@@ -1,41 +1,69 @@
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 
-use crate::ai;
+use crate::action_lengths;
+use crate::ai::Cpus;
 use crate::audio::Audio;
 use crate::camera::Camera;
 use crate::cli::{CLIResults, ContinueFrom};
-use crate::game::{Edit, Game, GameSetup, GameState, PlayerSetup};
+use crate::game::{Edit, Game, GameSetup, GameState, PlayerSetup, ResumeMenu};
 use crate::graphics::GraphicsMessage;
-use crate::menu::{Menu, MenuState, ResumeMenu};
+use crate::haptics::Haptics;
+use crate::hash_trace::HashTrace;
+use crate::menu::{Menu, MenuState};
+use crate::presence::Presence;
 use crate::replays;
 use crate::rules::Rules;
+use crate::session::SessionReport;
+use crate::telemetry::Telemetry;
+use crate::toasts::Toasts;
 use canon_collision_lib::assets::Assets;
 use canon_collision_lib::command_line::CommandLine;
-use canon_collision_lib::config::Config;
+use canon_collision_lib::config::{Config, EffectColors};
 use canon_collision_lib::input::Input;
 use canon_collision_lib::network::{NetCommandLine, Netplay, NetplayState};
 use canon_collision_lib::package::Package;
+#[cfg(feature = "hot_reload")]
+use canon_collision_lib::package::PackageWatcher;
+#[cfg(feature = "websocket")]
+use canon_collision_lib::websocket::WebSocketCommandLine;
 
+use std::fs;
+use std::path::PathBuf;
 use std::sync::mpsc::channel;
 use std::thread;
 use std::time::{Duration, Instant};
-use winit::event::WindowEvent;
+use winit::event::{VirtualKeyCode, WindowEvent};
 use winit_input_helper::WinitInputHelper;
 
+/// `graphics_adapters` are the names of the adapters the renderer can select from
 pub fn run_in_thread(
     cli_results: CLIResults,
+    graphics_adapters: Vec<String>,
 ) -> (Sender<WindowEvent<'static>>, Receiver<GraphicsMessage>) {
     let (render_tx, render_rx) = channel();
     let (event_tx, event_rx) = mpsc::channel();
     thread::spawn(move || {
-        run(cli_results, event_rx, render_tx);
+        run(cli_results, graphics_adapters, event_rx, render_tx);
     });
     (event_tx, render_rx)
 }
 
+/// On failure logs the error, the package is then only reloaded by the reload command
+#[cfg(feature = "hot_reload")]
+fn watch_package(package: &Package) -> Option<PackageWatcher> {
+    match package.watch() {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            error!("{}", err);
+            None
+        }
+    }
+}
+
 fn run(
     mut cli_results: CLIResults,
+    graphics_adapters: Vec<String>,
     event_rx: Receiver<WindowEvent<'static>>,
     render_tx: Sender<GraphicsMessage>,
 ) {
@@ -45,15 +73,27 @@ fn run(
     }
 
     let mut input = Input::new();
-    let mut net_command_line = NetCommandLine::new();
+    let mut net_command_line = NetCommandLine::new(
+        config.command_socket.as_deref(),
+        &config.command_address,
+        config.command_token.clone(),
+    );
+    #[cfg(feature = "websocket")]
+    let mut websocket = config
+        .websocket_address
+        .as_deref()
+        .and_then(|address| WebSocketCommandLine::new(address, config.command_token.clone()));
+    let mut telemetry = config.telemetry_address.as_deref().and_then(Telemetry::new);
     let mut netplay = Netplay::new();
 
     let mut package = if let Some(path) = Package::find_package_in_parent_dirs() {
-        if let Some(package) = Package::open(path) {
-            Some(package)
-        } else {
-            println!("Could not load package");
-            return;
+        match Package::open(path, cli_results.strict_actions, !cli_results.nocache) {
+            Ok(package) => Some(package),
+            Err(err) => {
+                println!("Could not load package: {}", err);
+                println!("If entities have drifted from their action enums, run without --strict-actions to fix them");
+                return;
+            }
         }
     } else {
         println!("Could not find package/ in current directory or any of its parent directories.");
@@ -67,8 +107,26 @@ fn run(
         println!("Could not find assets/ in current directory or any of its parent directories.");
         return;
     };
+    action_lengths::check_package(
+        package.as_mut().unwrap(),
+        &assets,
+        cli_results.resize_actions,
+    );
+    #[cfg(feature = "hot_reload")]
+    let mut package_watcher = watch_package(package.as_ref().unwrap());
 
-    let mut audio = Audio::new(assets);
+    let mut audio = Audio::new(assets, &config.audio);
+    let mut presence = Presence::new();
+    let mut session = SessionReport::new();
+    let mut haptics = Haptics::new();
+    let mut cpus = Cpus::new();
+    let mut toasts = Toasts::new();
+
+    // Every frame of the replay is saved to this directory, then the game exits
+    let mut export_dir: Option<PathBuf> = None;
+    let mut export_requested = false;
+    // Every frame of the replay is hashed into this trace, then the game exits
+    let mut hash_trace: Option<HashTrace> = None;
 
     // CLI options
     let (mut menu, mut game) = {
@@ -76,7 +134,7 @@ fn run(
         match cli_results.continue_from {
             ContinueFrom::Menu => {
                 audio.play_bgm("Menu");
-                (Menu::new(MenuState::GameSelect), None)
+                (Menu::new(MenuState::MainMenu), None)
             }
             ContinueFrom::Game => {
                 // handle issues with package that prevent starting from game
@@ -120,6 +178,10 @@ fn run(
                         fighter: cli_results.fighter_names[i % cli_results.fighter_names.len()]
                             .clone(),
                         team: i,
+                        stocks: None,
+                        effect_colors: config.effect_colors(i),
+                        costume: 0,
+                        damage: 0.0,
                     });
                 }
 
@@ -141,6 +203,10 @@ fn run(
                                 [(players_len + i) % cli_results.fighter_names.len()]
                             .clone(),
                             team: players_len + i,
+                            stocks: None,
+                            effect_colors: EffectColors::default(),
+                            costume: 0,
+                            damage: 0.0,
                         });
                         controllers.push(input_len + i);
                         ais.push(0);
@@ -153,6 +219,7 @@ fn run(
 
                 let rules = Rules {
                     time_limit_seconds: None,
+                    meter: cli_results.meter,
                     ..Default::default()
                 };
 
@@ -200,6 +267,70 @@ fn run(
                     return;
                 }
             },
+            ContinueFrom::Resume(path) => {
+                let replay = match replays::load_replay_path(&path) {
+                    Ok(replay) => replay,
+                    Err(err) => {
+                        println!("Failed to load resume file {:?}, because: {}", path, err);
+                        return;
+                    }
+                };
+                if let Err(err) = replay.check_package(package.as_ref().unwrap()) {
+                    println!("Failed to resume from {:?}\n{}", path, err);
+                    return;
+                }
+                let mut game_setup = replay.into_resume_setup();
+                input.set_history(std::mem::take(&mut game_setup.input_history));
+                (
+                    Menu::new(MenuState::character_select()),
+                    Some(Game::new(package.take().unwrap(), game_setup, &mut audio)),
+                )
+            }
+            ContinueFrom::RenderReplay { replay, out } => {
+                if let Err(err) = fs::create_dir_all(&out) {
+                    println!("Failed to create directory {:?}, because: {}", out, err);
+                    return;
+                }
+                match replays::load_replay_path(&replay) {
+                    Ok(replay) => {
+                        let mut game_setup = replay.into_game_setup(false);
+                        input.set_history(std::mem::take(&mut game_setup.input_history));
+                        export_dir = Some(out);
+                        (
+                            Menu::new(MenuState::character_select()),
+                            Some(Game::new(package.take().unwrap(), game_setup, &mut audio)),
+                        )
+                    }
+                    Err(err) => {
+                        println!("Failed to load replay {:?}, because: {}", replay, err);
+                        return;
+                    }
+                }
+            }
+            ContinueFrom::TraceReplay { replay, trace } => {
+                hash_trace = match HashTrace::create(&trace) {
+                    Ok(trace) => Some(trace),
+                    Err(err) => {
+                        println!("Failed to create {:?}, because: {}", trace, err);
+                        return;
+                    }
+                };
+                match replays::load_replay_path(&replay) {
+                    Ok(replay) => {
+                        let mut game_setup = replay.into_game_setup(false);
+                        game_setup.state = GameState::ReplayForwardsFromInput;
+                        input.set_history(std::mem::take(&mut game_setup.input_history));
+                        let mut game = Game::new(package.take().unwrap(), game_setup, &mut audio);
+                        // resimulate from the first frame in history, the rest of the history is overwritten
+                        game.replay_seek(game.deleted_history_frames);
+                        (Menu::new(MenuState::character_select()), Some(game))
+                    }
+                    Err(err) => {
+                        println!("Failed to load replay {:?}, because: {}", replay, err);
+                        return;
+                    }
+                }
+            }
             ContinueFrom::Netplay => {
                 audio.play_bgm("Menu");
                 netplay.direct_connect(cli_results.address.unwrap());
@@ -209,6 +340,24 @@ fn run(
 
                 (Menu::new(state), None)
             }
+            ContinueFrom::RoomCode(room_code) => {
+                audio.play_bgm("Menu");
+                netplay.connect_room_code(room_code);
+                let state = MenuState::NetplayWait {
+                    message: String::from(""),
+                };
+
+                (Menu::new(state), None)
+            }
+            ContinueFrom::Spectate => {
+                audio.play_bgm("Menu");
+                netplay.spectate(cli_results.address.unwrap());
+                let state = MenuState::NetplayWait {
+                    message: String::from(""),
+                };
+
+                (Menu::new(state), None)
+            }
             ContinueFrom::MatchMaking => {
                 audio.play_bgm("Menu");
                 netplay.connect_match_making(
@@ -235,7 +384,14 @@ fn run(
         debug!("\n\nAPP LOOP START");
         let frame_start = Instant::now();
 
-        netplay.step();
+        netplay.step(&config);
+        if let Some(telemetry) = &mut telemetry {
+            telemetry.step();
+        }
+        #[cfg(feature = "websocket")]
+        if let Some(websocket) = &mut websocket {
+            websocket.step();
+        }
 
         // TODO:
         // *    use 1/60s timer to update current_frame variable
@@ -249,14 +405,93 @@ fn run(
         }
         os_input.step_with_window_events(&events);
 
+        // Netplay games cannot be interrupted, so files dropped onto the window are only used offline
+        if let NetplayState::Offline = netplay.state() {
+            for event in &events {
+                if let WindowEvent::DroppedFile(path) = event {
+                    if path.is_dir() {
+                        if game.is_some() {
+                            toasts.push(String::from("Cannot switch packages during a game"));
+                            continue;
+                        }
+                        match Package::open(
+                            path.clone(),
+                            cli_results.strict_actions,
+                            !cli_results.nocache,
+                        ) {
+                            Ok(mut new_package)
+                                if !new_package.entities.is_empty()
+                                    && !new_package.stages.is_empty() =>
+                            {
+                                if let Some(assets) = Assets::new() {
+                                    action_lengths::check_package(
+                                        &mut new_package,
+                                        &assets,
+                                        cli_results.resize_actions,
+                                    );
+                                }
+                                #[cfg(feature = "hot_reload")]
+                                {
+                                    package_watcher = watch_package(&new_package);
+                                }
+                                package = Some(new_package);
+                                menu.package_changed();
+                                toasts.push(String::from("Package loaded"));
+                            }
+                            _ => toasts.push(String::from("Dropped folder is not a package")),
+                        }
+                    } else {
+                        match replays::load_replay_path(path) {
+                            Ok(replay) => {
+                                let current_package = match &game {
+                                    Some(game) => &game.package,
+                                    None => package.as_ref().unwrap(),
+                                };
+                                if let Err(error) = replay.check_package(current_package) {
+                                    println!("Failed to play replay {:?}\n{}", path, error);
+                                    toasts.push(String::from("Replay does not match the package"));
+                                    continue;
+                                }
+
+                                // the replay replaces any game in progress
+                                if let Some(game) = game.take() {
+                                    package = Some(game.reclaim());
+                                    input.reset_history();
+                                }
+                                let mut game_setup = replay.into_game_setup(false);
+                                input.set_history(std::mem::take(&mut game_setup.input_history));
+                                game = Some(Game::new(
+                                    package.take().unwrap(),
+                                    game_setup,
+                                    &mut audio,
+                                ));
+                            }
+                            Err(error) => {
+                                println!("Failed to load replay {:?}\n{}", path, error);
+                                toasts.push(String::from("Failed to load replay"));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // A spectator's inputs are not used by the game, so the keyboard is the only way to leave.
+        if netplay.is_spectator() && os_input.key_pressed_os(VirtualKeyCode::Escape) {
+            netplay.stop_spectating();
+        }
+
+        input.set_trigger_curves(&config.trigger_curves);
+
         let mut resume_menu: Option<ResumeMenu> = None;
         if let Some(ref mut game) = game {
             if let NetplayState::Disconnected { reason } = netplay.state() {
                 resume_menu = Some(ResumeMenu::NetplayDisconnect { reason });
             } else {
-                let ai_inputs = ai::gen_inputs(game);
+                let ai_inputs = cpus.gen_inputs(game);
                 let reset_deadzones = game.check_reset_deadzones();
                 input.step(&game.tas, &ai_inputs, &mut netplay, reset_deadzones);
+                game.record_telemetry = telemetry.as_ref().map_or(false, |x| x.has_clients());
 
                 if let GameState::Quit(resume_menu_inner) = game.step(
                     &mut config,
@@ -265,26 +500,110 @@ fn run(
                     command_line.block(),
                     &netplay,
                     &mut audio,
+                    &mut toasts,
                 ) {
                     resume_menu = Some(resume_menu_inner)
                 }
-                if let Err(_) = render_tx.send(game.graphics_message(&config, &command_line)) {
+                if let Some(telemetry) = &mut telemetry {
+                    telemetry.publish(game.telemetry_frames());
+                }
+                #[cfg(feature = "websocket")]
+                if let Some(websocket) = &mut websocket {
+                    websocket.send_events(game.events());
+                }
+                if hash_trace.is_some() {
+                    // the replay pauses once it runs out of inputs
+                    if let GameState::ReplayForwardsFromInput = game.state {
+                        if let Err(err) = hash_trace.as_mut().unwrap().record(game) {
+                            println!("Failed to write hash trace: {}", err);
+                            return;
+                        }
+                    } else {
+                        match hash_trace.take().unwrap().finish() {
+                            Ok(()) => println!("Saved hash trace"),
+                            Err(err) => println!("Failed to write hash trace: {}", err),
+                        }
+                        return;
+                    }
+                }
+                let mut message = game.graphics_message(&config, &command_line, &toasts);
+                if let Some(export_dir) = &export_dir {
+                    // the replay pauses once it reaches its last frame, the renderer reports how many frames it saved
+                    match game.state {
+                        GameState::ReplayForwardsFromHistory
+                        | GameState::ReplayForwardsFromInput => {
+                            message.render.export_frame = Some(
+                                export_dir.join(format!("frame_{:06}.png", game.current_frame)),
+                            );
+                            export_requested = true;
+                        }
+                        _ => {
+                            if !export_requested {
+                                println!("Failed to export the replay, it has no frames");
+                            }
+                            return;
+                        }
+                    }
+                }
+                if let Err(_) = render_tx.send(message) {
                     return;
                 }
                 if let NetplayState::Offline = netplay.state() {
                     net_command_line.step(game);
                     command_line.step(&os_input, game);
+                    #[cfg(feature = "websocket")]
+                    if let Some(websocket) = &mut websocket {
+                        websocket.run_commands(game);
+                    }
+                }
+                if let Some(message) = game.package.take_save_result() {
+                    toasts.push(message);
+                }
+                // Files edited outside of the game are hot reloaded, unless that would lose edits made in game.
+                // The package saving itself also changes the files.
+                #[cfg(feature = "hot_reload")]
+                let package_changed = package_watcher.as_ref().map_or(false, |x| x.changed());
+                #[cfg(not(feature = "hot_reload"))]
+                let package_changed = false;
+                if package_changed && !game.package.is_saving() {
+                    if game.package.has_unsaved_changes() {
+                        toasts.push(String::from(
+                            "Package changed on disc, :reload to load it over the unsaved changes",
+                        ));
+                    } else {
+                        match game.package.reload() {
+                            Ok(diff) => {
+                                for line in diff {
+                                    command_line.output_add(line);
+                                }
+                            }
+                            Err(err) => command_line.output_add(err),
+                        }
+                    }
+                }
+                if game.package.take_reloaded() {
+                    toasts.push(String::from("Package reloaded"));
                 }
             }
         } else {
             input.step(&[], &[], &mut netplay, false);
-            if let Some(mut menu_game_setup) = menu.step(
+            let menu_game_setup = menu.step(
                 package.as_ref().unwrap(),
                 &mut config,
                 &mut input,
                 &os_input,
                 &mut netplay,
-            ) {
+                &graphics_adapters,
+                &mut toasts,
+            );
+            for sfx in menu.take_sfx() {
+                audio.play_menu_sfx(sfx);
+            }
+            // the package may still be saving after leaving the game
+            if let Some(message) = package.as_mut().unwrap().take_save_result() {
+                toasts.push(message);
+            }
+            if let Some(mut menu_game_setup) = menu_game_setup {
                 input.set_history(std::mem::take(&mut menu_game_setup.input_history));
                 game = Some(Game::new(
                     package.take().unwrap(),
@@ -295,15 +614,26 @@ fn run(
                 package.as_mut().unwrap(),
                 &config,
                 &command_line,
+                &toasts,
             )) {
                 return;
             }
         }
+        for message in input.connection_messages() {
+            toasts.push(message);
+        }
+        toasts.step();
+
+        session.step(config.show_session_report, game.as_ref());
+        audio.set_volume(&config.audio);
+        haptics.step(&config, game.as_ref(), &mut input, &netplay);
 
         if let Some(resume_menu) = resume_menu {
+            session.game_ended(game.as_ref().unwrap(), &resume_menu);
             package = Some(game.unwrap().reclaim());
 
             input.reset_history();
+            input.mirror_controller = None;
             game = None;
             menu.resume(resume_menu, &mut audio);
 
@@ -314,14 +644,17 @@ fn run(
             // Replay quit     -> replay screen
         }
 
-        if os_input.quit() {
+        presence.step(&config, game.as_ref(), &menu);
+
+        if os_input.quit() || menu.quit() {
             netplay.set_offline(); // tell peer we are quiting
             return;
         }
 
+        // traces are written as fast as possible
         let frame_duration = Duration::from_secs(1) / 60;
         let frame_elapsed = frame_start.elapsed();
-        if frame_elapsed < frame_duration {
+        if frame_elapsed < frame_duration && hash_trace.is_none() {
             spin_sleep::sleep(frame_duration - frame_elapsed);
         }
     }
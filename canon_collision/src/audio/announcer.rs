@@ -0,0 +1,79 @@
+/// Frames between each number of the countdown
+const COUNTDOWN_INTERVAL: usize = 60;
+
+/// Voice clips stored in assets/audio/sfx/Announcer
+pub enum Announcement {
+    Three,
+    Two,
+    One,
+    Go,
+    Game,
+}
+
+impl Announcement {
+    /// The announcement made on `frame` by a countdown that says "GO" on `go_frame`
+    pub fn countdown(frame: usize, go_frame: usize) -> Option<Announcement> {
+        let frames_until_go = go_frame.checked_sub(frame)?;
+        if frames_until_go % COUNTDOWN_INTERVAL != 0 {
+            return None;
+        }
+        match frames_until_go / COUNTDOWN_INTERVAL {
+            0 => Some(Announcement::Go),
+            1 => Some(Announcement::One),
+            2 => Some(Announcement::Two),
+            3 => Some(Announcement::Three),
+            _ => None,
+        }
+    }
+
+    /// The sfx path without the file extension, so any supported format can be used
+    pub fn path(&self) -> &'static str {
+        match self {
+            Announcement::Three => "Announcer/3",
+            Announcement::Two => "Announcer/2",
+            Announcement::One => "Announcer/1",
+            Announcement::Go => "Announcer/go",
+            Announcement::Game => "Announcer/game",
+        }
+    }
+}
+
+/// Sounds stored in assets/audio/sfx/Menu
+#[derive(Clone, Copy, PartialEq)]
+pub enum MenuSfx {
+    /// Entering a menu
+    Select,
+    /// Returning to the previous menu
+    Back,
+    /// Choosing a fighter on the character select screen
+    FighterConfirm,
+}
+
+impl MenuSfx {
+    /// The sfx path without the file extension, so any supported format can be used
+    pub fn path(&self) -> &'static str {
+        match self {
+            MenuSfx::Select => "Menu/select",
+            MenuSfx::Back => "Menu/back",
+            MenuSfx::FighterConfirm => "Menu/fighterConfirm",
+        }
+    }
+}
+
+#[test]
+fn countdown_test() {
+    let path = |frame| Announcement::countdown(frame, 200).map(|x| x.path());
+    assert_eq!(path(19), None);
+    assert_eq!(path(20), Some("Announcer/3"));
+    assert_eq!(path(21), None);
+    assert_eq!(path(80), Some("Announcer/2"));
+    assert_eq!(path(140), Some("Announcer/1"));
+    assert_eq!(path(200), Some("Announcer/go"));
+    assert_eq!(path(260), None);
+
+    // The countdown starts partway through when the go frame is too early for all of it
+    assert_eq!(
+        Announcement::countdown(1, 61).map(|x| x.path()),
+        Some("Announcer/1")
+    );
+}
@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use kira::arrangement::handle::ArrangementHandle;
+use kira::arrangement::{Arrangement, LoopArrangementSettings};
+use kira::instance::handle::InstanceHandle;
+use kira::instance::{InstanceSettings, StopInstanceSettings};
+use kira::manager::AudioManager;
+use kira::sound::handle::SoundHandle;
+use kira::sound::SoundSettings;
+
+/// Frames taken to fade between the main and climax layers
+const CROSSFADE_FRAMES: f64 = 120.0;
+
+/// The currently playing song.
+/// Songs stored as a folder of stems play their loop and climax layers in sync, fading between them as the game intensifies.
+pub struct Bgm {
+    main: InstanceHandle,
+    climax: Option<InstanceHandle>,
+    /// 0.0 only plays the main layer, 1.0 only plays the climax layer
+    climax_mix: f64,
+}
+
+impl Bgm {
+    /// Plays a single file song on loop
+    pub fn play_file(manager: &mut AudioManager, path: &Path, volume: f64) -> Result<Bgm, String> {
+        let basic_loop = SoundSettings::default().default_loop_start(0.0);
+        let mut sound = manager
+            .load_sound(path, basic_loop)
+            .map_err(|x| x.to_string())?;
+        let main = sound
+            .play(InstanceSettings::default().volume(volume))
+            .map_err(|x| x.to_string())?;
+
+        Ok(Bgm {
+            main,
+            climax: None,
+            climax_mix: 0.0,
+        })
+    }
+
+    /// Plays a song from a folder of stems named intro, loop and climax with any file extension.
+    /// Only the loop is required, the climax must be the same length as the loop to stay in sync with it.
+    /// Returns the path of the loop so its metadata can be read.
+    pub fn play_stems(
+        manager: &mut AudioManager,
+        folder: &Path,
+        volume: f64,
+    ) -> Result<(Bgm, PathBuf), String> {
+        let loop_path = find_stem(folder, "loop").ok_or("Stem folder has no loop file")?;
+        let intro = match find_stem(folder, "intro") {
+            Some(path) => Some(load_stem(manager, &path)?),
+            None => None,
+        };
+        let main = load_stem(manager, &loop_path)?;
+        let climax = match find_stem(folder, "climax") {
+            Some(path) => Some(load_stem(manager, &path)?),
+            None => None,
+        };
+
+        // the intro is arranged into both layers so that they stay in sync
+        let mut main = arrange_stem(manager, intro.as_ref(), &main)?
+            .play(InstanceSettings::default().volume(volume))
+            .map_err(|x| x.to_string())?;
+        let climax = match climax {
+            Some(climax) => {
+                let instance = arrange_stem(manager, intro.as_ref(), &climax)?
+                    .play(InstanceSettings::default().volume(0.0));
+                match instance {
+                    Ok(instance) => Some(instance),
+                    Err(err) => {
+                        main.stop(StopInstanceSettings::default()).ok();
+                        return Err(err.to_string());
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let bgm = Bgm {
+            main,
+            climax,
+            climax_mix: 0.0,
+        };
+        Ok((bgm, loop_path))
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        self.main.set_volume(volume * (1.0 - self.climax_mix)).ok();
+        if let Some(climax) = &mut self.climax {
+            climax.set_volume(volume * self.climax_mix).ok();
+        }
+    }
+
+    /// Called once per frame, crossfades towards the climax layer while `climax` is true and back to the main layer otherwise
+    pub fn step(&mut self, climax: bool, volume: f64) {
+        if self.climax.is_none() {
+            return;
+        }
+
+        let target = if climax { 1.0 } else { 0.0 };
+        if self.climax_mix != target {
+            let step = 1.0 / CROSSFADE_FRAMES;
+            self.climax_mix = if climax {
+                (self.climax_mix + step).min(target)
+            } else {
+                (self.climax_mix - step).max(target)
+            };
+            self.set_volume(volume);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.main.stop(StopInstanceSettings::default()).unwrap();
+        if let Some(climax) = &mut self.climax {
+            climax.stop(StopInstanceSettings::default()).unwrap();
+        }
+    }
+}
+
+/// Returns the file in `folder` named `stem` with any file extension
+fn find_stem(folder: &Path, stem: &str) -> Option<PathBuf> {
+    fs::read_dir(folder)
+        .ok()?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .find(|x| {
+            x.file_stem()
+                .and_then(|x| x.to_str())
+                .map(|x| x.eq_ignore_ascii_case(stem))
+                .unwrap_or(false)
+        })
+}
+
+fn load_stem(manager: &mut AudioManager, path: &Path) -> Result<SoundHandle, String> {
+    manager
+        .load_sound(path, SoundSettings::default())
+        .map_err(|x| x.to_string())
+}
+
+fn arrange_stem(
+    manager: &mut AudioManager,
+    intro: Option<&SoundHandle>,
+    layer: &SoundHandle,
+) -> Result<ArrangementHandle, String> {
+    let settings = LoopArrangementSettings::default();
+    let arrangement = match intro {
+        Some(intro) => Arrangement::new_loop_with_intro(intro, layer, settings),
+        None => Arrangement::new_loop(layer, settings),
+    };
+    manager
+        .add_arrangement(arrangement)
+        .map_err(|x| x.to_string())
+}
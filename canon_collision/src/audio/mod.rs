@@ -1,48 +1,141 @@
+#[cfg(feature = "audio")]
 use std::fs;
+#[cfg(feature = "audio")]
 use std::path::PathBuf;
 
+#[cfg(feature = "audio")]
 use audiotags::Tag;
-use kira::instance::handle::InstanceHandle;
-use kira::instance::{InstanceSettings, StopInstanceSettings};
+#[cfg(feature = "audio")]
 use kira::manager::{AudioManager, AudioManagerSettings};
-use kira::sound::SoundSettings;
+#[cfg(feature = "audio")]
 use rand::seq::IteratorRandom;
 
 use treeflection::{Node, NodeRunner, NodeToken};
 
 use canon_collision_lib::assets::Assets;
+use canon_collision_lib::config::AudioConfig;
 use canon_collision_lib::entity_def::EntityDef;
+use canon_collision_lib::geometry::Rect;
 
+pub mod announcer;
+#[cfg(feature = "audio")]
+pub mod bgm;
 pub mod sfx;
 
-use sfx::{Sfx, SfxType};
+use announcer::{Announcement, MenuSfx};
+#[cfg(feature = "audio")]
+use bgm::Bgm;
+#[cfg(feature = "audio")]
+use sfx::Sfx;
+use sfx::SfxType;
 
+#[cfg(feature = "audio")]
 pub struct Audio {
-    manager: AudioManager,
+    /// None when the audio is silent
+    manager: Option<AudioManager>,
     path: PathBuf,
-    bgm: Option<InstanceHandle>,
+    bgm: Option<Bgm>,
     sfx: Sfx,
+    /// The volumes currently applied
+    volume: AudioConfig,
+    /// The area visible to the camera, sound effects are panned and attenuated relative to it
+    listener: Option<Rect>,
 }
 
+/// Without the audio feature every game is silent
+#[cfg(not(feature = "audio"))]
+pub struct Audio;
+
+/// How far sound effects at the edge of the camera are panned, 0.5 would pan them hard left/right
+#[cfg(feature = "audio")]
+const EDGE_PANNING: f64 = 0.3;
+/// Volume lost for each camera half width a sound effect is beyond the edge of the camera
+#[cfg(feature = "audio")]
+const ATTENUATION_RATE: f64 = 0.5;
+#[cfg(feature = "audio")]
+const MIN_ATTENUATION: f64 = 0.25;
+
+#[cfg(feature = "audio")]
 impl Audio {
-    pub fn new(assets: Assets) -> Self {
+    pub fn new(assets: Assets, volume: &AudioConfig) -> Self {
         let mut manager = AudioManager::new(AudioManagerSettings::default()).unwrap();
         let path = assets.path().join("audio");
         let sfx = Sfx::new(&mut manager, &path);
 
         Audio {
-            manager,
+            manager: Some(manager),
             path,
             sfx,
             bgm: None,
+            volume: volume.clone(),
+            listener: None,
         }
     }
 
-    pub fn play_sound_effect(&mut self, entity: &EntityDef, sfx: SfxType) {
-        self.sfx.play_sound_effect(entity, sfx);
+    /// Plays nothing and needs no audio device, for simulating games without a player to hear them
+    pub fn silent() -> Self {
+        Audio {
+            manager: None,
+            path: PathBuf::new(),
+            sfx: Sfx::default(),
+            bgm: None,
+            volume: AudioConfig::default(),
+            listener: None,
+        }
     }
 
-    /// Folders can contain music organized by stage/menu or fighter
+    /// Applies any changes to the volume settings, the music volume is updated immediately while sound effects use the new volume the next time they play
+    pub fn set_volume(&mut self, volume: &AudioConfig) {
+        if self.volume != *volume {
+            if let Some(bgm) = &mut self.bgm {
+                bgm.set_volume(volume.bgm());
+            }
+            self.volume = volume.clone();
+        }
+    }
+
+    /// Sound effects played after this are positioned relative to the camera
+    pub fn set_listener(&mut self, camera: &Rect) {
+        self.listener = Some(camera.clone());
+    }
+
+    /// `x` is where the sound effect occurred in the stage
+    pub fn play_sound_effect(&mut self, entity: &EntityDef, sfx: SfxType, x: f32) {
+        if self.manager.is_none() {
+            return;
+        }
+        let (panning, attenuation) = match &self.listener {
+            Some(listener) => spatialize(listener, x),
+            None => (0.5, 1.0),
+        };
+        self.sfx
+            .play_sound_effect(entity, sfx, self.volume.sfx() * attenuation, panning);
+    }
+
+    pub fn play_announcement(&mut self, announcement: Announcement) {
+        if self.manager.is_none() {
+            return;
+        }
+        self.sfx
+            .play_optional(announcement.path(), self.volume.sfx());
+    }
+
+    pub fn play_menu_sfx(&mut self, sfx: MenuSfx) {
+        if self.manager.is_none() {
+            return;
+        }
+        self.sfx.play_optional(sfx.path(), self.volume.sfx());
+    }
+
+    /// Called once per frame of a game, the music crossfades to its climax layer while `climax` is true
+    pub fn step_bgm(&mut self, climax: bool) {
+        if let Some(bgm) = &mut self.bgm {
+            bgm.step(climax, self.volume.bgm());
+        }
+    }
+
+    /// Folders can contain music organized by stage/menu or fighter.
+    /// A song is either a single file or a folder of stems, see `Bgm::play_stems`
     /// TODO:
     ///     If I need to specify per song looping metadata then add some kind of foo.json for a foo.mp3.
     ///     OR just throw the metadata into the mp3 metadata.
@@ -55,6 +148,7 @@ impl Audio {
     }
 
     fn play_bgm_inner(&mut self, folder: &str) -> Result<BGMMetadata, String> {
+        let manager = self.manager.as_mut().ok_or("Audio is silent")?;
         let folder = folder.replace(' ', "");
         let read_dir =
             fs::read_dir(self.path.join("music").join(&folder)).map_err(|x| x.to_string())?;
@@ -72,23 +166,18 @@ impl Audio {
             .choose(&mut rand::thread_rng())
             .ok_or("No files in folder")?;
 
-        let basic_loop = SoundSettings::default().default_loop_start(0.0);
-        let mut new_sound = self
-            .manager
-            .load_sound(chosen_file.path(), basic_loop)
-            .map_err(|x| x.to_string())?;
+        let (bgm, tag_path) = if chosen_file.path().is_dir() {
+            Bgm::play_stems(manager, &chosen_file.path(), self.volume.bgm())?
+        } else {
+            let bgm = Bgm::play_file(manager, &chosen_file.path(), self.volume.bgm())?;
+            (bgm, chosen_file.path())
+        };
 
-        if let Some(mut instance_id) = self.bgm.take() {
-            instance_id.stop(StopInstanceSettings::default()).unwrap();
+        if let Some(mut old_bgm) = self.bgm.replace(bgm) {
+            old_bgm.stop();
         }
 
-        self.bgm = Some(
-            new_sound
-                .play(InstanceSettings::default())
-                .map_err(|x| x.to_string())?,
-        );
-
-        let tag = Tag::new().read_from_path(chosen_file.path()).unwrap();
+        let tag = Tag::new().read_from_path(tag_path).unwrap();
 
         let title = if let Some(title) = tag.title() {
             title.to_string()
@@ -116,9 +205,73 @@ impl Audio {
     }
 }
 
+#[cfg(not(feature = "audio"))]
+impl Audio {
+    pub fn new(_assets: Assets, _volume: &AudioConfig) -> Self {
+        Audio
+    }
+
+    pub fn silent() -> Self {
+        Audio
+    }
+
+    pub fn set_volume(&mut self, _volume: &AudioConfig) {}
+
+    pub fn set_listener(&mut self, _camera: &Rect) {}
+
+    pub fn play_sound_effect(&mut self, _entity: &EntityDef, _sfx: SfxType, _x: f32) {}
+
+    pub fn play_announcement(&mut self, _announcement: Announcement) {}
+
+    pub fn play_menu_sfx(&mut self, _sfx: MenuSfx) {}
+
+    pub fn step_bgm(&mut self, _climax: bool) {}
+
+    pub fn play_bgm(&mut self, folder: &str) -> BGMMetadata {
+        BGMMetadata {
+            title: format!("Failed to play song from: {}", folder),
+            artist: Some(String::from("Audio is not available in this build")),
+            album: None,
+        }
+    }
+}
+
+/// Returns the panning (0.0 is left, 1.0 is right) and volume multiplier of a sound effect at `x`.
+/// Sound effects within the camera are at full volume, getting quieter the further beyond the edge they are.
+#[cfg(feature = "audio")]
+fn spatialize(listener: &Rect, x: f32) -> (f64, f64) {
+    let center = (listener.x1 + listener.x2) as f64 / 2.0;
+    let half_width = ((listener.x2 - listener.x1).abs() as f64 / 2.0).max(1.0);
+    let offset = (x as f64 - center) / half_width;
+
+    let panning = 0.5 + offset.clamp(-1.0, 1.0) * EDGE_PANNING;
+    let beyond_edge = (offset.abs() - 1.0).max(0.0);
+    let attenuation = (1.0 - beyond_edge * ATTENUATION_RATE).max(MIN_ATTENUATION);
+    (panning, attenuation)
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, Node)]
 pub struct BGMMetadata {
     pub title: String,
     pub artist: Option<String>,
     pub album: Option<String>,
 }
+
+#[cfg(feature = "audio")]
+#[test]
+fn spatialize_test() {
+    let listener = Rect {
+        x1: -50.0,
+        y1: -50.0,
+        x2: 50.0,
+        y2: 50.0,
+    };
+    assert_eq!(spatialize(&listener, 0.0), (0.5, 1.0));
+    assert_eq!(spatialize(&listener, -50.0), (0.5 - EDGE_PANNING, 1.0));
+    assert_eq!(spatialize(&listener, 50.0), (0.5 + EDGE_PANNING, 1.0));
+    assert_eq!(spatialize(&listener, 100.0), (0.5 + EDGE_PANNING, 0.5));
+    assert_eq!(
+        spatialize(&listener, -1000.0),
+        (0.5 - EDGE_PANNING, MIN_ATTENUATION)
+    );
+}
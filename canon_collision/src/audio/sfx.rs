@@ -1,21 +1,22 @@
-use std::collections::HashMap;
+#[cfg(feature = "audio")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "audio")]
 use std::fs;
+#[cfg(feature = "audio")]
 use std::path::{Path, PathBuf, MAIN_SEPARATOR};
 
+#[cfg(feature = "audio")]
 use kira::instance::InstanceSettings;
+#[cfg(feature = "audio")]
 use kira::manager::AudioManager;
+#[cfg(feature = "audio")]
 use kira::sound::handle::SoundHandle;
+#[cfg(feature = "audio")]
 use kira::sound::SoundSettings;
-use kira::Value;
 
+#[cfg(feature = "audio")]
 use canon_collision_lib::entity_def::EntityDef;
-
-// TODO: move into hitbox canon_collision_lib hitbox definition
-pub enum HitBoxSfx {
-    Sword,
-    Punch,
-    //Explode, etc...
-}
+use canon_collision_lib::entity_def::HitSpark;
 
 pub enum SfxType {
     Walk,
@@ -24,7 +25,10 @@ pub enum SfxType {
     Jump,
     Land,
     Die,
-    Hit(HitBoxSfx),
+    /// Each hit spark plays its own sample, see `hit_sample`.
+    /// A spark without a sample falls back to Common/hit.wav pitched differently for each spark.
+    Hit(HitSpark),
+    ShieldHit,
     /// TODO: Dont know if the ergonomics and efficiency of this is a good idea.
     ///       Lets play with it a bit and throw it away if we dont like it.
     Custom {
@@ -34,16 +38,63 @@ pub enum SfxType {
     },
 }
 
+/// The volume or pitch of a sound effect
+#[derive(Clone, Copy)]
+pub enum Value {
+    Fixed(f64),
+    /// A random value from the range is picked each time the sound effect plays
+    Random(f64, f64),
+}
+
+impl Value {
+    #[cfg(feature = "audio")]
+    fn scale(self, scale: f64) -> Value {
+        match self {
+            Value::Fixed(value) => Value::Fixed(value * scale),
+            Value::Random(min, max) => Value::Random(min * scale, max * scale),
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl From<Value> for kira::Value<f64> {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Fixed(value) => kira::Value::Fixed(value),
+            Value::Random(min, max) => kira::Value::Random(min, max),
+        }
+    }
+}
+
+impl SfxType {
+    /// A sound effect from the entity's folder, played at the volume of a hit
+    pub fn custom(filename: String) -> SfxType {
+        SfxType::Custom {
+            filename,
+            volume: Value::Random(0.15, 0.2),
+            pitch: Value::Random(0.95, 1.05),
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+#[derive(Default)]
 pub struct Sfx {
     sfx: HashMap<String, SoundHandle>,
+    /// Optional sound effects that have no file, so they are only warned about once
+    missing: HashSet<String>,
 }
 
+#[cfg(feature = "audio")]
 impl Sfx {
     pub fn new(manager: &mut AudioManager, path: &Path) -> Self {
         let mut sfx = HashMap::new();
         let path = path.join("sfx");
         Sfx::populate_sfx(manager, &path, None, &mut sfx);
-        Sfx { sfx }
+        Sfx {
+            sfx,
+            missing: HashSet::new(),
+        }
     }
 
     fn populate_sfx(
@@ -87,22 +138,34 @@ impl Sfx {
     }
 
     /// TODO: How to handle rollback?
-    pub fn play_sound_effect(&mut self, entity: &EntityDef, sfx: SfxType) {
+    /// The volume of the sound effect is multiplied by `volume_scale`.
+    /// `panning` ranges from 0.0 (left speaker) to 1.0 (right speaker).
+    pub fn play_sound_effect(
+        &mut self,
+        entity: &EntityDef,
+        sfx: SfxType,
+        volume_scale: f64,
+        panning: f64,
+    ) {
         let entity_name = entity.name.replace(' ', "");
 
-        let sfx_id = match (&entity_name, &sfx) {
+        let hit_key = match &sfx {
+            SfxType::Hit(spark) => self.find_key(hit_sample(spark)),
+            _ => None,
+        };
+        let key = match (&entity_name, &sfx) {
             //(_, SFXType::Walk) => ["Common/walk1.ogg", "Common/walk2.ogg"].choose(&mut rand::thread_rng()).unwrap(), // TODO: This is possible
-            (_, SfxType::Walk) => self.sfx.get_mut("Common/walk.ogg"),
-            (_, SfxType::Run) => self.sfx.get_mut("Common/walk.ogg"),
-            (_, SfxType::Dash) => self.sfx.get_mut("Common/dash.ogg"),
-            (_, SfxType::Jump) => self.sfx.get_mut("Common/jump.ogg"),
-            (_, SfxType::Land) => self.sfx.get_mut("Common/land.ogg"),
-            (_, SfxType::Die) => self.sfx.get_mut("Common/die.wav"),
-            (_, SfxType::Hit(HitBoxSfx::Sword)) => self.sfx.get_mut("Common/hit.wav"),
-            (_, SfxType::Hit(HitBoxSfx::Punch)) => self.sfx.get_mut("Common/hit.wav"),
-            (folder, SfxType::Custom { filename, .. }) => {
-                self.sfx.get_mut(&format!("{}/{}", folder, filename))
-            }
+            (_, SfxType::Walk) => String::from("Common/walk.ogg"),
+            (_, SfxType::Run) => String::from("Common/walk.ogg"),
+            (_, SfxType::Dash) => String::from("Common/dash.ogg"),
+            (_, SfxType::Jump) => String::from("Common/jump.ogg"),
+            (_, SfxType::Land) => String::from("Common/land.ogg"),
+            (_, SfxType::Die) => String::from("Common/die.wav"),
+            (_, SfxType::Hit(_)) => hit_key
+                .clone()
+                .unwrap_or_else(|| String::from("Common/hit.wav")),
+            (_, SfxType::ShieldHit) => String::from("Common/hit.wav"),
+            (folder, SfxType::Custom { filename, .. }) => format!("{}/{}", folder, filename),
         };
 
         let (volume, pitch) = match (&entity_name, sfx) {
@@ -112,22 +175,77 @@ impl Sfx {
             (_, SfxType::Jump) => (Value::Random(0.15, 0.2), Value::Random(0.90, 1.1)),
             (_, SfxType::Land) => (Value::Random(0.05, 0.1), Value::Random(0.90, 1.1)),
             (_, SfxType::Die) => (Value::Random(0.30, 0.4), Value::Random(0.90, 1.1)),
-            (_, SfxType::Hit(HitBoxSfx::Sword)) => {
+            (_, SfxType::Hit(_)) if hit_key.is_some() => {
                 (Value::Random(0.15, 0.2), Value::Random(0.95, 1.05))
             }
-            (_, SfxType::Hit(HitBoxSfx::Punch)) => {
+            (_, SfxType::Hit(HitSpark::Blunt)) => {
                 (Value::Random(0.15, 0.2), Value::Random(0.90, 1.1))
             }
+            (_, SfxType::Hit(HitSpark::Slash)) => {
+                (Value::Random(0.15, 0.2), Value::Random(1.2, 1.3))
+            }
+            (_, SfxType::Hit(HitSpark::Electric)) => {
+                (Value::Random(0.15, 0.2), Value::Random(1.6, 1.8))
+            }
+            (_, SfxType::Hit(HitSpark::Flame)) => {
+                (Value::Random(0.2, 0.25), Value::Random(0.6, 0.7))
+            }
+            (_, SfxType::ShieldHit) => (Value::Random(0.15, 0.2), Value::Random(0.95, 1.05)),
             (_, SfxType::Custom { volume, pitch, .. }) => (volume, pitch),
         };
 
         let instance_settings = InstanceSettings::default()
-            .volume(volume)
-            .playback_rate(pitch);
-        sfx_id
-            .unwrap()
-            .play(instance_settings)
-            .map_err(|x| x.to_string())
-            .unwrap();
+            .volume(volume.scale(volume_scale))
+            .playback_rate(pitch)
+            .panning(panning);
+        match self.sfx.get_mut(&key) {
+            Some(handle) => {
+                handle
+                    .play(instance_settings)
+                    .map_err(|x| x.to_string())
+                    .unwrap();
+            }
+            // custom sound effects are named by the package, so a missing file should not crash the game
+            None => {
+                if !self.missing.contains(&key) {
+                    warn!("Sound effect {} does not exist in assets/audio/sfx", key);
+                    self.missing.insert(key);
+                }
+            }
+        }
+    }
+
+    /// The key of the sound effect at `path` with any file extension
+    fn find_key(&self, path: &str) -> Option<String> {
+        self.sfx
+            .keys()
+            .find(|key| key.rsplit_once('.').map(|x| x.0) == Some(path))
+            .cloned()
+    }
+
+    /// Plays the sound effect at `path` with any file extension.
+    /// The file is optional, when it doesnt exist nothing is played.
+    pub fn play_optional(&mut self, path: &str, volume_scale: f64) {
+        let handle = self.find_key(path).and_then(|key| self.sfx.get_mut(&key));
+
+        if let Some(handle) = handle {
+            let instance_settings = InstanceSettings::default().volume(volume_scale);
+            if let Err(err) = handle.play(instance_settings) {
+                error!("Failed to play sound effect {}: {}", path, err);
+            }
+        } else if self.missing.insert(path.to_string()) {
+            warn!("Sound effect {} does not exist in assets/audio/sfx", path);
+        }
+    }
+}
+
+/// The path of the sample played by hits with `spark`, without a file extension
+#[cfg(feature = "audio")]
+fn hit_sample(spark: &HitSpark) -> &'static str {
+    match spark {
+        HitSpark::Blunt => "Common/hit_blunt",
+        HitSpark::Slash => "Common/hit_slash",
+        HitSpark::Electric => "Common/hit_electric",
+        HitSpark::Flame => "Common/hit_flame",
     }
 }
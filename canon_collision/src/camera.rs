@@ -4,10 +4,14 @@ use canon_collision_lib::entity_def::EntityDef;
 use canon_collision_lib::geometry::Rect;
 use canon_collision_lib::stage::Stage;
 
-use cgmath::{Matrix4, Point3, Quaternion, Rad, Transform, Vector3};
+use cgmath::{Matrix4, Point3, Rad, Vector3};
+#[cfg(feature = "window")]
+use cgmath::{Quaternion, Transform};
 use std::f32::consts;
 use treeflection::{KeyedContextVec, Node, NodeRunner, NodeToken};
+#[cfg(feature = "window")]
 use winit::event::VirtualKeyCode;
+#[cfg(feature = "window")]
 use winit_input_helper::WinitInputHelper;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, Node)]
@@ -27,6 +31,54 @@ pub struct Camera {
     freelook_phi: f32,
     /// equator angle around the y (up) axis.
     freelook_theta: f32,
+    pub settings: CameraSettings,
+    /// Distance the camera is currently shaking
+    shake: f32,
+    shake_frame: u64,
+    /// Added to the camera location in TransformMode::Play
+    shake_offset: (f32, f32),
+    punch_in: Option<PunchIn>,
+}
+
+/// Tunes how the automatic camera follows the action, can be modified live with treeflection
+#[derive(Debug, Clone, Serialize, Deserialize, Node)]
+pub struct CameraSettings {
+    /// Fraction of the distance to the target location moved every frame
+    pub pan_rate: f32,
+    /// Fraction of the difference to the target size zoomed every frame
+    pub zoom_rate: f32,
+    /// Shake distance caused by each point of damage dealt by a hit
+    pub shake_per_damage: f32,
+    pub shake_max: f32,
+    /// The shake distance is multiplied by this every frame
+    pub shake_decay: f32,
+    /// Hits that deal at least this much damage punch in on the hit
+    pub punch_in_damage: f32,
+    pub punch_in_frames: u64,
+    /// Fraction of the regular camera width that is visible while punched in
+    pub punch_in_zoom: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            pan_rate: 0.1,
+            zoom_rate: 0.1,
+            shake_per_damage: 0.15,
+            shake_max: 4.0,
+            shake_decay: 0.85,
+            punch_in_damage: 15.0,
+            punch_in_frames: 20,
+            punch_in_zoom: 0.6,
+        }
+    }
+}
+
+/// The camera zooms in on the point of a strong hit for a few frames
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Node)]
+pub struct PunchIn {
+    point: (f32, f32),
+    frames_remaining: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Node)]
@@ -70,6 +122,11 @@ impl Camera {
             freelook_location: (0.0, 0.0, 0.0),
             freelook_phi: 0.0,
             freelook_theta: 0.0,
+            settings: CameraSettings::default(),
+            shake: 0.0,
+            shake_frame: 0,
+            shake_offset: (0.0, 0.0),
+            punch_in: None,
         }
     }
 
@@ -94,13 +151,26 @@ impl Camera {
             freelook_location: (0.0, 0.0, 0.0),
             freelook_phi: 0.0,
             freelook_theta: 0.0,
+            settings: CameraSettings::default(),
+            shake: 0.0,
+            shake_frame: 0,
+            shake_offset: (0.0, 0.0),
+            punch_in: None,
         }
     }
 
-    pub fn update_os_input(&mut self, os_input: &WinitInputHelper) {
+    /// When `allow_zoom` is false the scroll wheel is being used for something else and is ignored.
+    #[cfg(feature = "window")]
+    pub fn update_os_input(&mut self, os_input: &WinitInputHelper, allow_zoom: bool) {
+        let scroll_diff = if allow_zoom {
+            os_input.scroll_diff()
+        } else {
+            0.0
+        };
+
         // set manual/automatic camera control
         if os_input.mouse_pressed(2)
-            || os_input.scroll_diff() != 0.0
+            || scroll_diff != 0.0
             || (!self.dev_mode()
                 && (os_input.key_pressed_os(VirtualKeyCode::W)
                     || os_input.key_pressed_os(VirtualKeyCode::A)
@@ -131,10 +201,10 @@ impl Camera {
                         }
 
                         // zoom camera
-                        self.rect.x1 -= os_input.scroll_diff() * 4.0;
-                        self.rect.x2 += os_input.scroll_diff() * 4.0;
-                        self.rect.y1 -= os_input.scroll_diff() * 4.0;
-                        self.rect.y2 += os_input.scroll_diff() * 4.0;
+                        self.rect.x1 -= scroll_diff * 4.0;
+                        self.rect.x2 += scroll_diff * 4.0;
+                        self.rect.y1 -= scroll_diff * 4.0;
+                        self.rect.y2 += scroll_diff * 4.0;
                     }
                     TransformMode::Play => {
                         // rotate camera
@@ -264,12 +334,52 @@ impl Camera {
         }
     }
 
+    /// Shakes the camera and punches in on strong hits.
+    /// Rerunning a hit during a netplay rollback has no extra effect.
+    pub fn hit(&mut self, damage: f32, point: (f32, f32)) {
+        let shake = (damage * self.settings.shake_per_damage).min(self.settings.shake_max);
+        self.shake = self.shake.max(shake);
+
+        if damage >= self.settings.punch_in_damage {
+            self.punch_in = Some(PunchIn {
+                point,
+                frames_remaining: self.settings.punch_in_frames,
+            });
+        }
+    }
+
+    fn step_effects(&mut self) {
+        self.punch_in = self.punch_in.take().and_then(|punch_in| {
+            punch_in
+                .frames_remaining
+                .checked_sub(1)
+                .map(|frames_remaining| PunchIn {
+                    frames_remaining,
+                    ..punch_in
+                })
+        });
+
+        self.shake *= self.settings.shake_decay;
+        if self.shake < 0.01 {
+            self.shake = 0.0;
+        }
+        self.shake_frame = self.shake_frame.wrapping_add(1);
+        // the two axes use different frequencies so the shake does not just move diagonally
+        let frame = self.shake_frame as f32;
+        self.shake_offset = (
+            self.shake * (frame * 1.7).sin(),
+            self.shake * (frame * 2.3).cos(),
+        );
+    }
+
+    #[cfg(feature = "window")]
     pub fn update(
         &mut self,
         os_input: &WinitInputHelper,
         entities: &Entities,
         entity_defs: &KeyedContextVec<EntityDef>,
         stage: &Stage,
+        fixed: bool,
     ) {
         // process new resolution
         if let Some((width, height)) = os_input.resolution() {
@@ -278,12 +388,15 @@ impl Camera {
             self.aspect_ratio = width as f32 / height as f32;
         }
 
+        self.step_effects();
+
         if let CameraControlState::Auto = self.control_state {
-            // initialise new_rect using only the first player
-            let mut player_iter = entities.values();
-            let new_rect = player_iter
-                .next()
-                .and_then(|x| x.cam_area(&stage.camera, entities, entity_defs, &stage.surfaces));
+            // a fixed camera frames the whole stage instead of the players
+            let new_rect = if fixed {
+                Some(stage.camera.clone())
+            } else {
+                Camera::players_area(entities, entity_defs, stage)
+            };
             let mut new_rect = match new_rect {
                 Some(rect) => rect,
                 None => {
@@ -297,18 +410,6 @@ impl Camera {
                 }
             };
 
-            // grow new_rect to cover all other players
-            for player in player_iter {
-                if let Some(next_area) =
-                    player.cam_area(&stage.camera, entities, entity_defs, &stage.surfaces)
-                {
-                    new_rect.x1 = new_rect.x1.min(next_area.left());
-                    new_rect.x2 = new_rect.x2.max(next_area.right());
-                    new_rect.y1 = new_rect.y1.min(next_area.bot());
-                    new_rect.y2 = new_rect.y2.max(next_area.top());
-                }
-            }
-
             // grow new_rect to fill aspect ratio
             let mut width = (new_rect.x1 - new_rect.x2).abs();
             let mut height = (new_rect.y1 - new_rect.y2).abs();
@@ -327,6 +428,25 @@ impl Camera {
                 new_rect.x1 = avg_horizontal - width / 2.0;
             }
 
+            // keep within the stages zoom limits and punch in on strong hits
+            let mut middle_x = (new_rect.x1 + new_rect.x2) / 2.0;
+            let mut middle_y = (new_rect.y1 + new_rect.y2) / 2.0;
+            width = width
+                .max(stage.camera_min_width)
+                .min(stage.camera_max_width);
+            if let Some(punch_in) = self.punch_in.as_ref().filter(|_| !fixed) {
+                width = (width * self.settings.punch_in_zoom).max(stage.camera_min_width);
+                middle_x = punch_in.point.0;
+                middle_y = punch_in.point.1;
+            }
+            height = width / self.aspect_ratio;
+            new_rect = Rect {
+                x1: middle_x - width / 2.0,
+                x2: middle_x + width / 2.0,
+                y1: middle_y - height / 2.0,
+                y2: middle_y + height / 2.0,
+            };
+
             // push aspect_ratio changes back so it doesnt go past the stage camera area
             let cam_max = &stage.camera;
             if new_rect.x1 < cam_max.left() {
@@ -348,16 +468,46 @@ impl Camera {
                 new_rect.y2 -= diff;
             }
 
-            // set new camera values
-            let diff_x1 = new_rect.x1 - self.rect.x1;
-            let diff_x2 = new_rect.x2 - self.rect.x2;
-            let diff_y1 = new_rect.y1 - self.rect.y1;
-            let diff_y2 = new_rect.y2 - self.rect.y2;
-            self.rect.x1 += diff_x1 / 10.0;
-            self.rect.x2 += diff_x2 / 10.0;
-            self.rect.y1 += diff_y1 / 10.0;
-            self.rect.y2 += diff_y2 / 10.0;
+            // set new camera values, panning and zooming towards new_rect at their own rates
+            let pan_x = (new_rect.x1 + new_rect.x2 - self.rect.x1 - self.rect.x2) / 2.0
+                * self.settings.pan_rate;
+            let pan_y = (new_rect.y1 + new_rect.y2 - self.rect.y1 - self.rect.y2) / 2.0
+                * self.settings.pan_rate;
+            let zoom_x = (new_rect.x2 - new_rect.x1 - self.rect.x2 + self.rect.x1) / 2.0
+                * self.settings.zoom_rate;
+            let zoom_y = (new_rect.y2 - new_rect.y1 - self.rect.y2 + self.rect.y1) / 2.0
+                * self.settings.zoom_rate;
+            self.rect.x1 += pan_x - zoom_x;
+            self.rect.x2 += pan_x + zoom_x;
+            self.rect.y1 += pan_y - zoom_y;
+            self.rect.y2 += pan_y + zoom_y;
+        }
+    }
+
+    /// The area covering the camera areas of every player, None if there are no players
+    fn players_area(
+        entities: &Entities,
+        entity_defs: &KeyedContextVec<EntityDef>,
+        stage: &Stage,
+    ) -> Option<Rect> {
+        // initialise the area using only the first player
+        let mut player_iter = entities.values();
+        let mut area = player_iter
+            .next()
+            .and_then(|x| x.cam_area(&stage.camera, entities, entity_defs, &stage.surfaces))?;
+
+        // grow the area to cover all other players
+        for player in player_iter {
+            if let Some(next_area) =
+                player.cam_area(&stage.camera, entities, entity_defs, &stage.surfaces)
+            {
+                area.x1 = area.x1.min(next_area.left());
+                area.x2 = area.x2.max(next_area.right());
+                area.y1 = area.y1.min(next_area.bot());
+                area.y2 = area.y2.max(next_area.top());
+            }
         }
+        Some(area)
     }
 
     pub fn transform(&self) -> Matrix4<f32> {
@@ -397,8 +547,9 @@ impl Camera {
                 match self.control_state {
                     CameraControlState::Auto => {
                         // camera points
-                        let camera_target = Point3::new(middle_x, middle_y, 0.0);
-                        let camera_location = self.get_camera_location();
+                        let shake = Vector3::new(self.shake_offset.0, self.shake_offset.1, 0.0);
+                        let camera_target = Point3::new(middle_x, middle_y, 0.0) + shake;
+                        let camera_location = self.get_camera_location() + shake;
 
                         // view matrix
                         let view = Matrix4::look_at_rh(
@@ -1,6 +1,7 @@
 use getopts::Options;
 use std::env;
 use std::net::IpAddr;
+use std::path::PathBuf;
 
 fn print_usage(program: &str, opts: Options) {
     let brief = format!("Usage: {} [options] [package_dir]\nIf no arguments are given the GUI menu is used instead. (excluding -g)", program);
@@ -19,10 +20,27 @@ pub fn cli() -> CLIResults {
     opts.optopt("h",  "humanplayers",     "Number of human players in the game", "NUM_HUMAN_PLAYERS");
     opts.optopt("c",  "cpuplayers",       "Number of CPU players in the game", "NUM_CPU_PLAYERS");
     opts.optopt("a",  "address",          "IP Address of other client to start netplay with", "IP_ADDRESS");
+    opts.optopt("",   "room",             "Room code shared with another client to start netplay with, for when neither client can port forward", "ROOM_CODE");
+    opts.optopt("S",  "spectate",         "IP Address of a netplay client to spectate", "IP_ADDRESS");
     opts.optopt("n",  "netplayplayers",   "Search for a netplay game with the specified number of players", "NUM_PLAYERS");
     opts.optopt("r",  "netplayregion",    "Search for a netplay game with the specified region", "REGION");
     opts.optopt("k",  "replay",           "load the replay in the replays folder with the specified filename. Replay additionally loads normally unused data that is kept specifically for hot reloading.", "FILENAME");
+    opts.optopt("",   "resume",           "Continue the game saved by the save_resume command from the frame it was saved on", "PATH");
+    opts.optopt("",   "render-replay",    "Play the replay at the specified path and save every frame it renders as a png to the --out directory, then exit", "PATH");
+    opts.optopt("",   "out",              "Directory that --render-replay saves frames to", "DIR");
+    opts.optopt("",   "trace-replay",     "Resimulate the replay at the specified path from its inputs as fast as possible, writing the hash of every frame to the --hash-trace file, then exit", "PATH");
+    opts.optopt("",   "hash-trace",       "File that --trace-replay writes the hash of every frame to", "PATH");
+    opts.optopt("",   "compare-traces",   "Report the first frame and entity where two --hash-trace files diverge, then exit", "TRACE1,TRACE2");
+    opts.optflag("",  "upgrade-replays",  "Rewrite every replay in the replays directory saved by an older version of Canon Collision in the latest replay version, then exit");
+    opts.optflag("M", "meter",            "Enable the super meter");
     opts.optopt("m",  "maxhistoryframes", "The oldest history frame is removed when number of history frames exceeds this value", "NUM_FRAMES");
+    opts.optflag("",  "safe-mode",        "Start the renderer with MSAA and the large joint buffer disabled, for GPUs that fail to start otherwise");
+    opts.optflag("",  "hot-reload-shaders", "Load the shaders from the source tree and reload them whenever they are modified, requires the hot_reload_shaders feature");
+    opts.optflag("",  "strict-actions",   "Fail to load package entities with missing or unknown actions instead of fixing them");
+    opts.optflag("",  "resize-actions",   "Resize package entity actions to the length of their model's animation when their lengths differ");
+    opts.optopt("",   "adapter",          "Render with the graphics adapter with the specified index or name, instead of the one in the config", "INDEX|NAME");
+    opts.optflag("",  "list-adapters",    "List the available graphics adapters and exit");
+    opts.optflag("",  "nocache",          "Load every package file instead of using the package cache, for when the cache is suspected to be broken");
     opts.optopt("g",  "graphics",         "Graphics backend to use",
         if cfg!(feature = "wgpu_renderer") {
             "[wgpu|none]"
@@ -57,6 +75,44 @@ pub fn cli() -> CLIResults {
         results.debug = true;
     }
 
+    if matches.opt_present("M") {
+        results.meter = true;
+    }
+
+    if matches.opt_present("safe-mode") {
+        results.safe_mode = true;
+    }
+
+    if matches.opt_present("hot-reload-shaders") {
+        results.hot_reload_shaders = true;
+    }
+
+    if matches.opt_present("strict-actions") {
+        results.strict_actions = true;
+    }
+
+    if matches.opt_present("resize-actions") {
+        results.resize_actions = true;
+    }
+
+    if matches.opt_present("nocache") {
+        results.nocache = true;
+    }
+
+    if let Some(adapter) = matches.opt_str("adapter") {
+        results.graphics_adapter = Some(adapter);
+    }
+
+    if matches.opt_present("list-adapters") {
+        results.list_adapters = true;
+        results.continue_from = ContinueFrom::Close;
+    }
+
+    if matches.opt_present("upgrade-replays") {
+        results.upgrade_replays = true;
+        results.continue_from = ContinueFrom::Close;
+    }
+
     if let Some(players) = matches.opt_str("h") {
         if let Ok(players) = players.parse::<usize>() {
             results.continue_from = ContinueFrom::Game;
@@ -117,6 +173,22 @@ pub fn cli() -> CLIResults {
         }
     }
 
+    if let Some(room_code) = matches.opt_str("room") {
+        results.continue_from = ContinueFrom::RoomCode(room_code);
+    }
+
+    if let Some(address) = matches.opt_str("S") {
+        if let Ok(address) = address.parse() {
+            results.address = Some(address);
+            results.continue_from = ContinueFrom::Spectate;
+        }
+        else {
+            print_usage(program, opts);
+            results.continue_from = ContinueFrom::Close;
+            return results;
+        }
+    }
+
     if let Some(backend_string) = matches.opt_str("g") {
         results.graphics_backend = match backend_string.to_lowercase().as_ref() {
             #[cfg(feature = "wgpu_renderer")]
@@ -150,6 +222,52 @@ pub fn cli() -> CLIResults {
         results.continue_from = ContinueFrom::ReplayFile(replay_filename);
     }
 
+    if let Some(resume) = matches.opt_str("resume") {
+        results.continue_from = ContinueFrom::Resume(PathBuf::from(resume));
+    }
+
+    if let Some(replay) = matches.opt_str("render-replay") {
+        let out = match matches.opt_str("out") {
+            Some(out) => out,
+            None => {
+                println!("--render-replay requires an --out directory to save frames to");
+                results.continue_from = ContinueFrom::Close;
+                return results;
+            }
+        };
+        if matches!(results.graphics_backend, GraphicsBackendChoice::Headless) {
+            println!("--render-replay requires the wgpu renderer");
+            results.continue_from = ContinueFrom::Close;
+            return results;
+        }
+        results.continue_from = ContinueFrom::RenderReplay { replay: PathBuf::from(replay), out: PathBuf::from(out) };
+    }
+
+    if let Some(replay) = matches.opt_str("trace-replay") {
+        let trace = match matches.opt_str("hash-trace") {
+            Some(trace) => trace,
+            None => {
+                println!("--trace-replay requires a --hash-trace file to write to");
+                results.continue_from = ContinueFrom::Close;
+                return results;
+            }
+        };
+        results.continue_from = ContinueFrom::TraceReplay { replay: PathBuf::from(replay), trace: PathBuf::from(trace) };
+    }
+
+    if let Some(traces) = matches.opt_str("compare-traces") {
+        let traces: Vec<&str> = traces.split(',').collect();
+        if let [a, b] = traces[..] {
+            results.compare_traces = Some((PathBuf::from(a), PathBuf::from(b)));
+            results.continue_from = ContinueFrom::Close;
+        }
+        else {
+            print_usage(program, opts);
+            results.continue_from = ContinueFrom::Close;
+            return results;
+        }
+    }
+
     results
 }
 
@@ -165,7 +283,25 @@ pub struct CLIResults {
     pub netplay_players: Option<u8>,
     pub netplay_region: Option<String>,
     pub debug: bool,
+    pub meter: bool,
     pub max_history_frames: Option<usize>,
+    /// Only used by the renderer, the game logic is unaffected
+    pub safe_mode: bool,
+    /// Only used by the renderer, the game logic is unaffected
+    pub hot_reload_shaders: bool,
+    /// Fail to load packages with entities whose actions are out of sync with the action enums instead of fixing them
+    pub strict_actions: bool,
+    /// Resize entity actions to the length of their animation when loading packages
+    pub resize_actions: bool,
+    /// Ignore the package cache, always loading every package file
+    pub nocache: bool,
+    /// Only used by the renderer, overrides the graphics adapter in the config
+    pub graphics_adapter: Option<String>,
+    pub list_adapters: bool,
+    /// The two hash traces to compare instead of starting the game
+    pub compare_traces: Option<(PathBuf, PathBuf)>,
+    /// Upgrade the saved replays instead of starting the game
+    pub upgrade_replays: bool,
 }
 
 impl CLIResults {
@@ -182,7 +318,17 @@ impl CLIResults {
             netplay_players: None,
             netplay_region: None,
             debug: false,
+            meter: false,
             max_history_frames: None,
+            safe_mode: false,
+            hot_reload_shaders: false,
+            strict_actions: false,
+            resize_actions: false,
+            nocache: false,
+            graphics_adapter: None,
+            list_adapters: false,
+            compare_traces: None,
+            upgrade_replays: false,
         }
     }
 }
@@ -190,9 +336,23 @@ impl CLIResults {
 pub enum ContinueFrom {
     Menu,
     Netplay,
+    RoomCode(String),
+    Spectate,
     MatchMaking,
     Game,
     ReplayFile(String),
+    /// Continue a game saved by save_resume
+    Resume(PathBuf),
+    /// Export every frame of a replay as pngs
+    RenderReplay {
+        replay: PathBuf,
+        out: PathBuf,
+    },
+    /// Write the hash of every frame of a replay resimulated from its inputs
+    TraceReplay {
+        replay: PathBuf,
+        trace: PathBuf,
+    },
     Close,
 }
 
@@ -1,6 +1,7 @@
 use crate::entity::components::action_state::ActionState;
 use crate::entity::fighters::player::Player;
-use crate::entity::{Entities, EntityKey, EntityType};
+use crate::entity::{Entities, Entity, EntityKey, EntityType};
+use crate::rules::Teams;
 
 use canon_collision_lib::entity_def::{
     CollisionBox, CollisionBoxRole, EntityDef, HitBox, HurtBox, PowerShield,
@@ -11,10 +12,13 @@ use slotmap::SecondaryMap;
 use treeflection::KeyedContextVec;
 
 /// returns a list of hit results for each entity
+/// The colboxes of fighters are scaled by `fighter_scale`
 pub fn collision_check(
     entities: &Entities,
     entity_definitions: &KeyedContextVec<EntityDef>,
     surfaces: &[Surface],
+    teams: &Teams,
+    fighter_scale: f32,
 ) -> SecondaryMap<EntityKey, Vec<CollisionResult>> {
     let mut result = SecondaryMap::<EntityKey, Vec<CollisionResult>>::new();
     for key in entities.keys() {
@@ -24,20 +28,28 @@ pub fn collision_check(
     'entity_atk: for (entity_atk_i, entity_atk) in entities.iter() {
         let entity_atk_xy = entity_atk.public_bps_xy(entities, entity_definitions, surfaces);
         let entity_atk_def = &entity_definitions[entity_atk.state.entity_def_key.as_ref()];
-        let frame_atk = entity_atk.relative_frame(entity_atk_def, surfaces);
+        let frame_atk = entity_atk.scaled_relative_frame(entity_atk_def, surfaces, fighter_scale);
         let colboxes_atk = frame_atk.get_hitboxes();
+        let team_atk = entity_atk.owner_team(entities);
         for (entity_defend_i, entity_defend) in entities.iter() {
             let entity_defend_xy =
                 entity_defend.public_bps_xy(entities, entity_definitions, surfaces);
             if entity_atk_i != entity_defend_i
-                && entity_atk.can_hit(entity_defend)
+                && entity_atk.can_hit(entity_defend, entity_definitions)
                 && entity_atk.hitlist().iter().all(|x| *x != entity_defend_i)
             {
                 let entity_defend_def =
                     &entity_definitions[entity_defend.state.entity_def_key.as_ref()];
-                let frame_defend = entity_defend.relative_frame(entity_defend_def, surfaces);
+                let frame_defend =
+                    entity_defend.scaled_relative_frame(entity_defend_def, surfaces, fighter_scale);
+                let team_defend = entity_defend.owner_team(entities);
+                let hitboxes_atk: &[_] = if teams.can_hit(team_atk, team_defend) {
+                    &colboxes_atk
+                } else {
+                    &[]
+                };
 
-                'hitbox_atk: for colbox_atk in &colboxes_atk {
+                'hitbox_atk: for (hitbox_id, colbox_atk) in hitboxes_atk.iter().enumerate() {
                     if let CollisionBoxRole::Hit(ref hitbox_atk) = colbox_atk.role {
                         if let EntityType::Fighter(fighter) = &entity_defend.ty {
                             let player_defend = fighter.get_player();
@@ -64,7 +76,9 @@ pub fn collision_check(
                         }
 
                         if hitbox_atk.enable_clang {
-                            for colbox_def in frame_defend.colboxes.iter() {
+                            for (hitbox_def_id, colbox_def) in
+                                frame_defend.get_hitboxes().into_iter().enumerate()
+                            {
                                 match &colbox_def.role {
                                     // TODO: How do we only run the clang handler once?
                                     &CollisionBoxRole::Hit(ref hitbox_def) => {
@@ -76,8 +90,11 @@ pub fn collision_check(
                                                 colbox_def,
                                             )
                                         {
-                                            let damage_diff =
-                                                hitbox_atk.damage as i64 - hitbox_def.damage as i64; // TODO: retrieve proper damage with move staling etc
+                                            let damage_atk = hitbox_atk.damage
+                                                * entity_atk.stale_damage_mult(hitbox_id);
+                                            let damage_def = hitbox_def.damage
+                                                * entity_defend.stale_damage_mult(hitbox_def_id);
+                                            let damage_diff = damage_atk as i64 - damage_def as i64;
 
                                             if damage_diff >= 9 {
                                                 result[entity_atk_i].push(CollisionResult::Clang {
@@ -86,6 +103,7 @@ pub fn collision_check(
                                                 result[entity_defend_i].push(
                                                     CollisionResult::HitAtk {
                                                         hitbox: hitbox_atk.clone(),
+                                                        hitbox_id,
                                                         entity_defend_i,
                                                         point,
                                                     },
@@ -94,6 +112,7 @@ pub fn collision_check(
                                                 result[entity_atk_i].push(
                                                     CollisionResult::HitAtk {
                                                         hitbox: hitbox_atk.clone(),
+                                                        hitbox_id,
                                                         entity_defend_i,
                                                         point,
                                                     },
@@ -132,11 +151,13 @@ pub fn collision_check(
                                     &CollisionBoxRole::Hurt(ref hurtbox) => {
                                         result[entity_atk_i].push(CollisionResult::HitAtk {
                                             hitbox: hitbox_atk.clone(),
+                                            hitbox_id,
                                             entity_defend_i,
                                             point,
                                         });
                                         result[entity_defend_i].push(CollisionResult::HitDef {
                                             hitbox: hitbox_atk.clone(),
+                                            hitbox_id,
                                             hurtbox: hurtbox.clone(),
                                             entity_atk_i,
                                         });
@@ -145,6 +166,7 @@ pub fn collision_check(
                                     &CollisionBoxRole::Invincible => {
                                         result[entity_atk_i].push(CollisionResult::HitAtk {
                                             hitbox: hitbox_atk.clone(),
+                                            hitbox_id,
                                             entity_defend_i,
                                             point,
                                         });
@@ -174,7 +196,7 @@ pub fn collision_check(
 
                 for colbox_atk in &colboxes_atk {
                     match &colbox_atk.role {
-                        &CollisionBoxRole::Grab => {
+                        &CollisionBoxRole::Grab if teams.can_grab(team_atk, team_defend) => {
                             for colbox_def in &frame_defend.colboxes[..] {
                                 if let ColBoxCollisionResult::Hit(_) = colbox_collision_check(
                                     entity_atk_xy,
@@ -199,9 +221,119 @@ pub fn collision_check(
             }
         }
     }
+
+    shield_push_check(
+        entities,
+        entity_definitions,
+        surfaces,
+        fighter_scale,
+        &mut result,
+    );
     result
 }
 
+/// Pushes apart fighters whose shield overlaps another fighter's shield or body, so shielding fighters cannot stand inside each other.
+/// The push is scaled by how far they overlap and uses the constants of the overlapping shield.
+fn shield_push_check(
+    entities: &Entities,
+    entity_definitions: &KeyedContextVec<EntityDef>,
+    surfaces: &[Surface],
+    fighter_scale: f32,
+    result: &mut SecondaryMap<EntityKey, Vec<CollisionResult>>,
+) {
+    for (entity_shield_i, entity_shield) in entities.iter() {
+        let entity_shield_def = &entity_definitions[entity_shield.state.entity_def_key.as_ref()];
+        let (x1, y1, r1) = match shield_circle(
+            entity_shield,
+            entity_shield_def,
+            entities,
+            entity_definitions,
+            surfaces,
+        ) {
+            Some(circle) => circle,
+            None => continue,
+        };
+        let shield = entity_shield_def.shield.as_ref().unwrap();
+
+        for (entity_other_i, entity_other) in entities.iter() {
+            if entity_shield_i == entity_other_i
+                || !matches!(entity_other.ty, EntityType::Fighter(_))
+            {
+                continue;
+            }
+            let entity_other_def = &entity_definitions[entity_other.state.entity_def_key.as_ref()];
+            let other_xy = entity_other.public_bps_xy(entities, entity_definitions, surfaces);
+
+            // each shielding fighter pushes itself away from the other shield, so the other shield is handled from the other side
+            let other_shield = shield_circle(
+                entity_other,
+                entity_other_def,
+                entities,
+                entity_definitions,
+                surfaces,
+            );
+            let (overlap, push) = if let Some((x2, y2, r2)) = other_shield {
+                (
+                    r1 + r2 - ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt(),
+                    shield.shield_push,
+                )
+            } else {
+                let overlap = entity_other
+                    .scaled_relative_frame(entity_other_def, surfaces, fighter_scale)
+                    .colboxes
+                    .iter()
+                    .filter(|x| matches!(x.role, CollisionBoxRole::Hurt(_)))
+                    .map(|colbox| {
+                        let x2 = other_xy.0 + colbox.point.0;
+                        let y2 = other_xy.1 + colbox.point.1;
+                        r1 + colbox.radius - ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+                    })
+                    .fold(0.0, f32::max);
+                (overlap, shield.body_push)
+            };
+
+            if overlap > 0.0 {
+                let direction = if x1 != other_xy.0 {
+                    (x1 - other_xy.0).signum()
+                } else if entity_shield_i < entity_other_i {
+                    -1.0
+                } else {
+                    1.0
+                };
+                let x_vel = (overlap * push).min(shield.push_max) * direction;
+                result[entity_shield_i].push(CollisionResult::ShieldPush { x_vel });
+                if other_shield.is_none() {
+                    result[entity_other_i].push(CollisionResult::ShieldPush { x_vel: -x_vel });
+                }
+            }
+        }
+    }
+}
+
+/// Returns the position and radius of the entity's shield, None if it is not shielding
+fn shield_circle(
+    entity: &Entity,
+    entity_def: &EntityDef,
+    entities: &Entities,
+    entity_definitions: &KeyedContextVec<EntityDef>,
+    surfaces: &[Surface],
+) -> Option<(f32, f32, f32)> {
+    let player = match &entity.ty {
+        EntityType::Fighter(fighter) => fighter.get_player(),
+        _ => return None,
+    };
+    let shield = entity_def.shield.as_ref()?;
+    if !player.is_shielding(&entity.state) {
+        return None;
+    }
+    let (x, y) = entity.public_bps_xy(entities, entity_definitions, surfaces);
+    Some((
+        x + player.shield_offset_x + shield.offset_x,
+        y + player.shield_offset_y + shield.offset_y,
+        player.shield_size(shield),
+    ))
+}
+
 fn colbox_collision_check(
     player1_xy: (f32, f32),
     colbox1: &CollisionBox,
@@ -268,13 +400,16 @@ fn colbox_shield_collision_check(
 pub enum CollisionResult {
     PhantomDef(HitBox, HurtBox),
     PhantomAtk(HitBox, EntityKey),
+    /// `hitbox_id` is the index of the hitbox among the attacker's hitboxes, identifying it for staling
     HitDef {
         hitbox: HitBox,
+        hitbox_id: usize,
         hurtbox: HurtBox,
         entity_atk_i: EntityKey,
     },
     HitAtk {
         hitbox: HitBox,
+        hitbox_id: usize,
         entity_defend_i: EntityKey,
         point: (f32, f32),
     },
@@ -300,6 +435,10 @@ pub enum CollisionResult {
     Clang {
         rebound: bool,
     },
+    /// Separates a shielding fighter from an overlapping shield or body
+    ShieldPush {
+        x_vel: f32,
+    },
 }
 
 // Thoughts on special cases
@@ -1,4 +1,4 @@
-use crate::entity::{Entities, EntityKey, EntityType};
+use crate::entity::{held_item, Entities, EntityKey, EntityType};
 
 use canon_collision_lib::entity_def::EntityDef;
 use canon_collision_lib::stage::Surface;
@@ -20,9 +20,8 @@ pub fn collision_check(
     let mut player_grabs_last_len = 0;
     for _ in 0..10 {
         for (player_i, entity_player) in entities.iter() {
-            if let EntityType::Fighter(fighter) = &entity_player.ty {
-                let player = fighter.get_player();
-                if player.get_held_item(entities).is_none() {
+            if let EntityType::Fighter(_) = &entity_player.ty {
+                if held_item(entities, player_i).is_none() {
                     let (player_x, player_y) =
                         entity_player.public_bps_xy(entities, entity_definitions, surfaces);
                     let player_item_grab_box =
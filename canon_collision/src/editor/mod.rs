@@ -0,0 +1,267 @@
+use canon_collision_lib::entity_def::{
+    CollisionBoxRole, HitBox, HitSpark, HitStun, HitboxEffect, StatusEffect,
+};
+use canon_collision_lib::package::Package;
+
+use std::collections::HashSet;
+use std::mem;
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+#[cfg(feature = "window")]
+use winit::event::VirtualKeyCode;
+#[cfg(feature = "window")]
+use winit_input_helper::WinitInputHelper;
+
+/// A hitbox property that can be modified from the frame data panel
+#[derive(Clone, Copy, PartialEq, Debug, EnumIter)]
+pub enum HitboxProperty {
+    Damage,
+    ShieldDamage,
+    BaseKnockback,
+    KnockbackGrowth,
+    Angle,
+    Hitstun,
+    Effect,
+    Spark,
+    Clang,
+    Rebound,
+    ReverseHit,
+}
+
+impl HitboxProperty {
+    fn name(&self) -> &'static str {
+        match self {
+            HitboxProperty::Damage => "Damage",
+            HitboxProperty::ShieldDamage => "Shield damage",
+            HitboxProperty::BaseKnockback => "Base knockback",
+            HitboxProperty::KnockbackGrowth => "Knockback growth",
+            HitboxProperty::Angle => "Angle",
+            HitboxProperty::Hitstun => "Hitstun",
+            HitboxProperty::Effect => "Effect",
+            HitboxProperty::Spark => "Spark",
+            HitboxProperty::Clang => "Clang",
+            HitboxProperty::Rebound => "Rebound",
+            HitboxProperty::ReverseHit => "Reverse hit",
+        }
+    }
+
+    fn value(&self, hitbox: &HitBox) -> String {
+        match self {
+            HitboxProperty::Damage => format!("{:.1}", hitbox.damage),
+            HitboxProperty::ShieldDamage => format!("{:.1}", hitbox.shield_damage),
+            HitboxProperty::BaseKnockback => format!("{:.1}", hitbox.bkb),
+            HitboxProperty::KnockbackGrowth => format!("{:.2}", hitbox.kbg),
+            HitboxProperty::Angle => format!("{:.0}", hitbox.angle),
+            HitboxProperty::Hitstun => match hitbox.hitstun {
+                HitStun::FramesTimesKnockback(value) => format!("{:.2} x knockback", value),
+                HitStun::Frames(value) => format!("{} frames", value),
+            },
+            HitboxProperty::Effect => match &hitbox.effect {
+                HitboxEffect::Status(status) => format!("{:?} status", status.kind),
+                effect => format!("{:?}", effect),
+            },
+            HitboxProperty::Spark => format!("{:?}", hitbox.spark),
+            HitboxProperty::Clang => hitbox.enable_clang.to_string(),
+            HitboxProperty::Rebound => hitbox.enable_rebound.to_string(),
+            HitboxProperty::ReverseHit => hitbox.enable_reverse_hit.to_string(),
+        }
+    }
+
+    /// Increases the property by `steps` increments, a negative value decreases it.
+    /// Booleans are toggled and enums are cycled regardless of the magnitude of `steps`.
+    fn adjust(&self, hitbox: &mut HitBox, steps: f32) {
+        match self {
+            HitboxProperty::Damage => hitbox.damage = (hitbox.damage + steps).max(0.0),
+            HitboxProperty::ShieldDamage => {
+                hitbox.shield_damage = (hitbox.shield_damage + steps).max(0.0)
+            }
+            HitboxProperty::BaseKnockback => hitbox.bkb = (hitbox.bkb + steps).max(0.0),
+            HitboxProperty::KnockbackGrowth => hitbox.kbg = (hitbox.kbg + steps * 0.01).max(0.0),
+            HitboxProperty::Angle => hitbox.angle = (hitbox.angle + steps).rem_euclid(360.0),
+            HitboxProperty::Hitstun => match hitbox.hitstun {
+                HitStun::FramesTimesKnockback(ref mut value) => {
+                    *value = (*value + steps * 0.01).max(0.0)
+                }
+                HitStun::Frames(ref mut value) => {
+                    *value = (*value as i64 + steps as i64).max(0) as u64
+                }
+            },
+            HitboxProperty::Effect => {
+                let effects = [
+                    HitboxEffect::None,
+                    HitboxEffect::Status(StatusEffect::default()),
+                    HitboxEffect::Fire,
+                    HitboxEffect::Electric,
+                    HitboxEffect::Sleep,
+                    HitboxEffect::Reverse,
+                    HitboxEffect::Stun,
+                    HitboxEffect::Freeze,
+                ];
+                let current = effects
+                    .iter()
+                    .position(|x| mem::discriminant(x) == mem::discriminant(&hitbox.effect))
+                    .unwrap_or(0);
+                let next = if steps > 0.0 {
+                    (current + 1) % effects.len()
+                } else {
+                    (current + effects.len() - 1) % effects.len()
+                };
+                hitbox.effect = effects[next].clone();
+            }
+            HitboxProperty::Spark => {
+                let sparks = [
+                    HitSpark::Blunt,
+                    HitSpark::Slash,
+                    HitSpark::Electric,
+                    HitSpark::Flame,
+                ];
+                let current = sparks.iter().position(|x| *x == hitbox.spark).unwrap_or(0);
+                let next = if steps > 0.0 {
+                    (current + 1) % sparks.len()
+                } else {
+                    (current + sparks.len() - 1) % sparks.len()
+                };
+                hitbox.spark = sparks[next];
+            }
+            HitboxProperty::Clang => hitbox.enable_clang = !hitbox.enable_clang,
+            HitboxProperty::Rebound => hitbox.enable_rebound = !hitbox.enable_rebound,
+            HitboxProperty::ReverseHit => hitbox.enable_reverse_hit = !hitbox.enable_reverse_hit,
+        }
+    }
+}
+
+/// State of the frame data editor used while the game is paused in dev mode.
+/// Colbox selection is handled by the games `Selector`, this handles dragging selected colboxes and the hitbox properties panel.
+///
+/// Mouse bindings, when the mouse is over a selected colbox:
+/// *   Left click and drag: move all selected colboxes
+/// *   Scroll: resize all selected colboxes
+///
+/// Panel keybindings:
+/// *   Up/Down: select a property
+/// *   Left/Right: decrease/increase the property of all selected hitboxes, hold shift to change by 10x as much
+#[derive(Clone)]
+pub struct FrameDataEditor {
+    property: HitboxProperty,
+    /// Selected colboxes are following the mouse until the left mouse button is released
+    pub dragging: bool,
+    /// The scroll wheel was used to resize colboxes this frame, so the camera should not zoom
+    pub captured_scroll: bool,
+}
+
+impl Default for FrameDataEditor {
+    fn default() -> FrameDataEditor {
+        FrameDataEditor {
+            property: HitboxProperty::Damage,
+            dragging: false,
+            captured_scroll: false,
+        }
+    }
+}
+
+impl FrameDataEditor {
+    #[cfg(feature = "window")]
+    pub fn step_panel(
+        &mut self,
+        os_input: &WinitInputHelper,
+        package: &mut Package,
+        fighter: &str,
+        action: &str,
+        frame: usize,
+        colboxes: &HashSet<usize>,
+    ) {
+        let properties: Vec<_> = HitboxProperty::iter().collect();
+        let property_i = properties
+            .iter()
+            .position(|x| *x == self.property)
+            .unwrap_or(0);
+        if os_input.key_pressed_os(VirtualKeyCode::Up) {
+            self.property = properties[(property_i + properties.len() - 1) % properties.len()];
+        }
+        if os_input.key_pressed_os(VirtualKeyCode::Down) {
+            self.property = properties[(property_i + 1) % properties.len()];
+        }
+
+        let multiplier = if os_input.held_shift() { 10.0 } else { 1.0 };
+        let steps = if os_input.key_pressed_os(VirtualKeyCode::Left) {
+            -multiplier
+        } else if os_input.key_pressed_os(VirtualKeyCode::Right) {
+            multiplier
+        } else {
+            return;
+        };
+
+        if !selected_hitboxes(package, fighter, action, frame, colboxes).is_empty() {
+            let property = self.property;
+            package.modify_fighter_hitboxes(fighter, action, frame, colboxes, |hitbox| {
+                property.adjust(hitbox, steps)
+            });
+        }
+    }
+
+    /// Returns the lines of the hitbox properties panel, empty when no hitboxes are selected.
+    /// Values are displayed from the lowest indexed selected hitbox.
+    pub fn render(
+        &self,
+        package: &Package,
+        fighter: &str,
+        action: &str,
+        frame: usize,
+        colboxes: &HashSet<usize>,
+    ) -> Vec<String> {
+        let hitboxes = selected_hitboxes(package, fighter, action, frame, colboxes);
+        let hitbox = match hitboxes.first() {
+            Some(hitbox) => hitbox,
+            None => return vec![],
+        };
+
+        let mut lines = vec![format!("Hitboxes selected: {}", hitboxes.len())];
+        for property in HitboxProperty::iter() {
+            let cursor = if property == self.property { ">" } else { " " };
+            lines.push(format!(
+                "{} {}: {}",
+                cursor,
+                property.name(),
+                property.value(hitbox)
+            ));
+        }
+        lines
+    }
+}
+
+fn selected_hitboxes<'a>(
+    package: &'a Package,
+    fighter: &str,
+    action: &str,
+    frame: usize,
+    colboxes: &HashSet<usize>,
+) -> Vec<&'a HitBox> {
+    let mut colboxes: Vec<_> = colboxes.iter().cloned().collect();
+    colboxes.sort_unstable();
+
+    let frame_colboxes = &package.entities[fighter].actions[action].frames[frame].colboxes;
+    colboxes
+        .iter()
+        .filter_map(|i| match frame_colboxes.get(*i).map(|x| &x.role) {
+            Some(CollisionBoxRole::Hit(hitbox)) => Some(hitbox),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn hitbox_property_adjust_test() {
+    let mut hitbox = HitBox::default();
+    HitboxProperty::Angle.adjust(&mut hitbox, -50.0);
+    assert_eq!(hitbox.angle, 355.0);
+
+    HitboxProperty::Damage.adjust(&mut hitbox, -100.0);
+    assert_eq!(hitbox.damage, 0.0);
+
+    HitboxProperty::Effect.adjust(&mut hitbox, -1.0);
+    assert!(matches!(hitbox.effect, HitboxEffect::Freeze));
+
+    HitboxProperty::Clang.adjust(&mut hitbox, 1.0);
+    assert!(!hitbox.enable_clang);
+}
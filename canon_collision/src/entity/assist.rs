@@ -0,0 +1,198 @@
+use crate::collision::collision_box::CollisionResult;
+use crate::entity::components::action_state::ActionState;
+use crate::entity::components::body::{Body, Location, PhysicsResult};
+use crate::entity::{nearest_enemy_xy, ActionResult, Entity, EntityType, StepContext};
+
+use canon_collision_lib::entity_def::assist::AssistAction;
+use canon_collision_lib::entity_def::EntityDefType;
+
+/// A temporary helper summoned by an assist item.
+/// It walks towards the nearest fighter that does not belong to its owner and attacks when in range.
+/// More complex behaviour can be given to an assist via the actions scripts.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Assist {
+    pub owner_id: Option<usize>,
+    pub body: Body,
+    /// The assist despawns when this reaches 0
+    pub frames_remaining: u64,
+}
+
+impl Assist {
+    /// Creates the assist entity `assist_key` at the given location, logs an error if it is not an assist.
+    pub fn summon(
+        context: &mut StepContext,
+        assist_key: &str,
+        owner_id: Option<usize>,
+        x: f32,
+        y: f32,
+        face_right: bool,
+    ) {
+        match context.entity_defs.key_to_value(assist_key).map(|x| &x.ty) {
+            Some(EntityDefType::Assist(assist_def)) => context.new_entities.push(Entity {
+                ty: EntityType::Assist(Assist {
+                    owner_id,
+                    body: Body::new(Location::Airbourne { x, y }, face_right),
+                    frames_remaining: assist_def.duration,
+                }),
+                state: ActionState::new(assist_key.to_string(), AssistAction::Spawn),
+            }),
+            _ => error!("Tried to summon {} which is not an assist", assist_key),
+        }
+    }
+
+    pub fn action_step(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        self.frames_remaining = self.frames_remaining.saturating_sub(1);
+        if self.frames_remaining == 0 && state.get_action() != Some(AssistAction::Despawn) {
+            return ActionResult::set_action(AssistAction::Despawn);
+        }
+
+        let result = match state.get_action() {
+            Some(AssistAction::Spawn) | Some(AssistAction::Fall) => {
+                self.body.y_vel += self.body.gravity(context);
+                if self.body.y_vel < context.entity_def.terminal_vel {
+                    self.body.y_vel = context.entity_def.terminal_vel;
+                }
+                None
+            }
+            Some(AssistAction::Idle) | Some(AssistAction::Walk) => self.chase(context, state),
+            Some(AssistAction::Attack) | Some(AssistAction::Despawn) => {
+                self.body.apply_friction_strong(context.entity_def);
+                None
+            }
+            None => None,
+        };
+
+        let action_frames = context.entity_def.actions[state.action.as_ref()]
+            .frames
+            .len() as i64;
+        if result.is_none() && state.frame + 1 >= action_frames {
+            self.action_expired(context, state)
+        } else {
+            result
+        }
+    }
+
+    /// Walks towards the nearest enemy and attacks once it is in range
+    fn chase(&mut self, context: &mut StepContext, state: &ActionState) -> Option<ActionResult> {
+        let attack_range = match &context.entity_def.ty {
+            EntityDefType::Assist(assist_def) => assist_def.attack_range,
+            _ => 0.0,
+        };
+        let (x, y) = self.bps_xy(context, state);
+        match nearest_enemy_xy(context, self.owner_id, (x, y)) {
+            Some((target_x, _)) => {
+                self.body.face_right = target_x > x;
+                if (target_x - x).abs() < attack_range {
+                    ActionResult::set_action(AssistAction::Attack)
+                } else {
+                    self.body.x_vel = self.body.relative_f(context.entity_def.walk_max_vel);
+                    match state.get_action() {
+                        Some(AssistAction::Walk) => None,
+                        _ => ActionResult::set_action(AssistAction::Walk),
+                    }
+                }
+            }
+            None => {
+                self.body.apply_friction_strong(context.entity_def);
+                match state.get_action() {
+                    Some(AssistAction::Idle) => None,
+                    _ => ActionResult::set_action(AssistAction::Idle),
+                }
+            }
+        }
+    }
+
+    fn action_expired(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        ActionResult::set_action(match state.get_action() {
+            None => panic!("Custom defined action expirations have not been implemented"),
+
+            Some(AssistAction::Spawn) => AssistAction::Fall,
+            Some(AssistAction::Fall) => AssistAction::Fall,
+            Some(AssistAction::Idle) => AssistAction::Idle,
+            Some(AssistAction::Walk) => AssistAction::Walk,
+            Some(AssistAction::Attack) => {
+                if self.body.is_airbourne() {
+                    AssistAction::Fall
+                } else {
+                    AssistAction::Idle
+                }
+            }
+            Some(AssistAction::Despawn) => {
+                context.delete_self = true;
+                AssistAction::Despawn
+            }
+        })
+    }
+
+    pub fn physics_step(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        let assist_frame =
+            &context.entity_def.actions[state.action.as_ref()].frames[state.frame as usize];
+        match self.body.physics_step(context, state, assist_frame) {
+            Some(PhysicsResult::Fall) => ActionResult::set_action(AssistAction::Fall),
+            Some(PhysicsResult::Land) => ActionResult::set_action(AssistAction::Idle),
+            Some(PhysicsResult::OutOfBounds) => {
+                context.delete_self = true;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn step_collision(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+        col_results: &[CollisionResult],
+    ) -> Option<ActionResult> {
+        let mut set_action = None;
+
+        for col_result in col_results {
+            if let &CollisionResult::HitDef {
+                ref hitbox,
+                hitbox_id,
+                ref hurtbox,
+                entity_atk_i,
+            } = col_result
+            {
+                let action_frame =
+                    state.get_entity_frame(&context.entity_defs[state.entity_def_key.as_ref()]);
+                self.body.launch(
+                    context,
+                    state,
+                    action_frame,
+                    hitbox,
+                    Some(hitbox_id),
+                    hurtbox,
+                    entity_atk_i,
+                    1.0,
+                );
+                set_action = ActionResult::set_action(AssistAction::Fall);
+            }
+        }
+        set_action
+    }
+
+    pub fn bps_xy(&self, context: &StepContext, state: &ActionState) -> (f32, f32) {
+        let action_frame =
+            state.get_entity_frame(&context.entity_defs[state.entity_def_key.as_ref()]);
+        self.body.public_bps_xy(
+            context.entities,
+            context.entity_defs,
+            action_frame,
+            context.surfaces,
+            state,
+        )
+    }
+}
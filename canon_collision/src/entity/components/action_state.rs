@@ -17,6 +17,8 @@ pub struct ActionState {
     pub frame_no_restart: i64,
     pub hitlist: Vec<EntityKey>,
     pub hitlag: Hitlag,
+    /// The animation being crossfaded out of, only affects rendering
+    pub blend: Option<ActionBlend>,
 }
 
 impl ActionState {
@@ -28,6 +30,37 @@ impl ActionState {
             frame_no_restart: 0,
             hitlist: vec![],
             hitlag: Hitlag::None,
+            blend: None,
+        }
+    }
+
+    /// Begins crossfading the animation from the current action and frame into `next_action`.
+    /// Changing action mid crossfade restarts it from the current action, dropping the older pose.
+    pub fn start_blend(&mut self, entity_def: &EntityDef, next_action: &str) {
+        let frames = entity_def
+            .actions
+            .key_to_value(next_action)
+            .and_then(|x| x.blend_frames)
+            .unwrap_or(entity_def.animation_blend_frames);
+
+        self.blend = if frames == 0 {
+            None
+        } else {
+            Some(ActionBlend {
+                action: self.action.clone(),
+                frame: self.frame,
+                frames,
+                frames_remaining: frames,
+            })
+        };
+    }
+
+    pub fn blend_step(&mut self) {
+        if let Some(blend) = &mut self.blend {
+            blend.frames_remaining -= 1;
+            if blend.frames_remaining == 0 {
+                self.blend = None;
+            }
         }
     }
 
@@ -41,6 +74,13 @@ impl ActionState {
         None
     }
 
+    /// Returns true when the current frame has the tag
+    pub fn has_tag(&self, entity_def: &EntityDef, tag: &str) -> bool {
+        self.get_entity_frame(entity_def)
+            .map(|x| x.has_tag(tag))
+            .unwrap_or(false)
+    }
+
     pub fn interruptible(&self, entity_def: &EntityDef) -> bool {
         self.frame >= entity_def.actions[self.action.as_ref()].iasa
     }
@@ -66,9 +106,13 @@ impl ActionState {
         let action = &entity_def.actions[self.action.as_ref()];
         let last_action_frame = action.frames.len() as u64 - 1;
         let iasa = action.iasa;
+        let tags = self
+            .get_entity_frame(entity_def)
+            .map(|x| x.tags.join(", "))
+            .unwrap_or_default();
 
-        format!("Entity: {:?}  \"{}\"  hitlag: {:?}  action: {}  frame: {}/{}  frame no restart: {}  IASA: {}",
-            index, self.entity_def_key, self.hitlag, self.action, self.frame, last_action_frame, self.frame_no_restart, iasa)
+        format!("Entity: {:?}  \"{}\"  hitlag: {:?}  action: {}  frame: {}/{}  frame no restart: {}  IASA: {}  tags: [{}]",
+            index, self.entity_def_key, self.hitlag, self.action, self.frame, last_action_frame, self.frame_no_restart, iasa, tags)
     }
 
     pub fn get_action<T: FromStr>(&self) -> Option<T> {
@@ -76,6 +120,22 @@ impl ActionState {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ActionBlend {
+    pub action: String,
+    /// The outgoing animation is held on this frame for the duration of the crossfade
+    pub frame: i64,
+    pub frames: u64,
+    pub frames_remaining: u64,
+}
+
+impl ActionBlend {
+    /// How much of the outgoing animation to show, fading from almost 1.0 towards 0.0
+    pub fn amount(&self) -> f32 {
+        self.frames_remaining as f32 / (self.frames + 1) as f32
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Hitlag {
     Attack { counter: u64 },
@@ -102,4 +162,63 @@ impl Hitlag {
             Hitlag::None => {}
         }
     }
+
+    /// How white the struck entity is drawn, fading out over the last HIT_FLASH_FRAMES of its hitlag
+    pub fn hit_flash(&self) -> f32 {
+        match self {
+            Hitlag::Launch { counter, .. } => {
+                (*counter).min(HIT_FLASH_FRAMES) as f32 / HIT_FLASH_FRAMES as f32
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+const HIT_FLASH_FRAMES: u64 = 4;
+
+#[test]
+fn hit_flash_test() {
+    let launch = |counter| Hitlag::Launch {
+        counter,
+        wobble_x: 0.0,
+    };
+    assert_eq!(launch(10).hit_flash(), 1.0);
+    assert_eq!(launch(2).hit_flash(), 0.5);
+    assert_eq!(Hitlag::Attack { counter: 10 }.hit_flash(), 0.0);
+    assert_eq!(Hitlag::None.hit_flash(), 0.0);
+}
+
+#[test]
+fn blend_test() {
+    use canon_collision_lib::entity_def::ActionDef;
+
+    let mut entity_def = EntityDef {
+        animation_blend_frames: 2,
+        ..EntityDef::default()
+    };
+    entity_def
+        .actions
+        .push(String::from("Idle"), ActionDef::default());
+    let jab = ActionDef {
+        blend_frames: Some(0),
+        ..ActionDef::default()
+    };
+    entity_def.actions.push(String::from("Jab"), jab);
+
+    let mut state = ActionState::new(String::new(), "Idle");
+    state.frame = 3;
+    state.start_blend(&entity_def, "Jab");
+    assert!(state.blend.is_none());
+
+    state.action = String::from("Jab");
+    state.start_blend(&entity_def, "Idle");
+    let blend = state.blend.as_ref().unwrap();
+    assert_eq!(blend.action, "Jab");
+    assert_eq!(blend.frame, 3);
+    assert!((blend.amount() - 2.0 / 3.0).abs() < 0.0001);
+
+    state.blend_step();
+    assert!((state.blend.as_ref().unwrap().amount() - 1.0 / 3.0).abs() < 0.0001);
+    state.blend_step();
+    assert!(state.blend.is_none());
 }
@@ -1,16 +1,21 @@
 use crate::entity::components::action_state::{ActionState, Hitlag};
-use crate::entity::{Entities, EntityKey, StepContext};
+use crate::entity::components::status_effects::StatusEffects;
+use crate::entity::{ledge_occupant, Entities, Entity, EntityKey, StepContext};
+use crate::rules::ONE_HIT_KO_DAMAGE;
 
-use canon_collision_lib::entity_def::{ActionFrame, EntityDef, HitBox, HurtBox, ECB};
+use canon_collision_lib::entity_def::{ActionFrame, EntityDef, HitBox, HitboxEffect, HurtBox, ECB};
 use canon_collision_lib::geometry;
 use canon_collision_lib::geometry::Rect;
 use canon_collision_lib::input::state::PlayerInput;
-use canon_collision_lib::stage::Surface;
+use canon_collision_lib::stage::{Hazard, Surface};
 
 use treeflection::KeyedContextVec;
 
 use std::f32::consts::PI;
 
+/// Frames after letting go of a ledge before that same ledge can be grabbed again
+pub const LEDGE_REGRAB_FRAMES: u64 = 30;
+
 // Describes the player location by offsets from other locations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Location {
@@ -26,20 +31,46 @@ pub enum Location {
     }, // player.face_right determines which edge on the platform
     GrabbedByPlayer(EntityKey),
     ItemHeldByPlayer(EntityKey),
+    /// Follows the parent entity, the entity is despawned when its parent is removed
+    AttachedToEntity {
+        parent: EntityKey,
+        attachment: Attachment,
+    },
     Airbourne {
         x: f32,
         y: f32,
     },
 }
 
+/// Where on the parent entity an attached entity is located
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Attachment {
+    /// Offset from the parents bps, x is mirrored when the parent is facing left
+    Offset { x: f32, y: f32 },
+    /// The item hold point of the parents current frame, this is how an attached entity can follow one of the parents bones
+    ItemHold,
+}
+
 pub enum PhysicsResult {
     Fall,
     Land,
     Teeter,
-    LedgeGrab,
+    /// The entity that was holding the ledge and has been trumped off it
+    LedgeGrab {
+        trumped: Option<EntityKey>,
+    },
     OutOfBounds,
 }
 
+/// Prevents a single ledge from being regrabbed until `frames` reaches 0
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgeRegrabTimer {
+    pub platform_i: usize,
+    /// Matches `Body::face_right` while holding the ledge, so true is the left ledge
+    pub face_right: bool,
+    pub frames: u64,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Body {
     pub ecb: ECB,
@@ -52,7 +83,8 @@ pub struct Body {
     pub kb_y_dec: f32,
     pub location: Location,
     pub face_right: bool,
-    pub frames_since_ledge: u64,
+    pub ledge_regrab_timers: Vec<LedgeRegrabTimer>,
+    pub status_effects: StatusEffects,
 
     // Only use for debug display
     pub frames_since_hit: u64,
@@ -71,7 +103,8 @@ impl Body {
             kb_y_vel: 0.0,
             kb_x_dec: 0.0,
             kb_y_dec: 0.0,
-            frames_since_ledge: 0,
+            ledge_regrab_timers: vec![],
+            status_effects: StatusEffects::default(),
             location,
             face_right,
 
@@ -90,7 +123,6 @@ impl Body {
         }
     }
 
-    #[allow(dead_code)]
     pub fn is_ledge(&self) -> bool {
         matches!(self.location, Location::GrabbedLedge { .. })
     }
@@ -103,20 +135,73 @@ impl Body {
         matches!(self.location, Location::ItemHeldByPlayer(_))
     }
 
+    /// Held items are children of their holder so they are despawned with it
+    pub fn parent(&self) -> Option<EntityKey> {
+        match self.location {
+            Location::AttachedToEntity { parent, .. } | Location::ItemHeldByPlayer(parent) => {
+                Some(parent)
+            }
+            _ => None,
+        }
+    }
+
     pub fn is_airbourne(&self) -> bool {
         matches!(self.location, Location::Airbourne { .. })
     }
 
-    pub fn is_hogging_ledge(&self, check_platform_i: usize, face_right: bool) -> bool {
-        if let Location::GrabbedLedge {
-            platform_i, logic, ..
-        } = &self.location
-        {
-            if let LedgeLogic::Hog = logic {
-                return self.face_right == face_right && check_platform_i == *platform_i;
+    /// The velocity of the surface the body is standing on, used to carry momentum when leaving a moving platform
+    pub fn surface_vel(&self, surfaces: &[Surface]) -> (f32, f32) {
+        match self.location {
+            Location::Surface { platform_i, .. } => surfaces
+                .get(platform_i)
+                .map_or((0.0, 0.0), |x| (x.vel_x, x.vel_y)),
+            _ => (0.0, 0.0),
+        }
+    }
+
+    /// The hazard of the surface the body is standing on
+    pub fn hazard<'a>(&self, surfaces: &'a [Surface]) -> Option<&'a Hazard> {
+        match self.location {
+            Location::Surface { platform_i, .. } => {
+                surfaces.get(platform_i).and_then(|x| x.hazard.as_ref())
             }
+            _ => None,
+        }
+    }
+
+    /// Returns how other entities interact with the ledge if the body is holding it
+    pub fn ledge_held(&self, check_platform_i: usize, face_right: bool) -> Option<&LedgeLogic> {
+        match &self.location {
+            Location::GrabbedLedge {
+                platform_i, logic, ..
+            } if self.face_right == face_right && check_platform_i == *platform_i => Some(logic),
+            _ => None,
+        }
+    }
+
+    pub fn can_regrab_ledge(&self, platform_i: usize, face_right: bool) -> bool {
+        self.ledge_regrab_timers
+            .iter()
+            .all(|x| x.platform_i != platform_i || x.face_right != face_right)
+    }
+
+    /// The held ledge keeps a full timer, so it starts counting down once it is let go
+    fn step_ledge_regrab_timers(&mut self) {
+        for timer in &mut self.ledge_regrab_timers {
+            timer.frames = timer.frames.saturating_sub(1);
+        }
+        self.ledge_regrab_timers.retain(|x| x.frames > 0);
+
+        if let Location::GrabbedLedge { platform_i, .. } = self.location {
+            let face_right = self.face_right;
+            self.ledge_regrab_timers
+                .retain(|x| x.platform_i != platform_i || x.face_right != face_right);
+            self.ledge_regrab_timers.push(LedgeRegrabTimer {
+                platform_i,
+                face_right,
+                frames: LEDGE_REGRAB_FRAMES,
+            });
         }
-        false
     }
 
     fn bps_xy(
@@ -203,6 +288,18 @@ impl Body {
                     (0.0, 0.0)
                 }
             }
+            Location::AttachedToEntity {
+                parent,
+                ref attachment,
+            } => {
+                if let Some(parent) = entities.get(parent) {
+                    let (x, y) = parent.public_bps_xy(entities, entity_defs, surfaces);
+                    let (d_x, d_y, _) = attachment_offset(parent, attachment, entity_defs);
+                    (x + d_x, y + d_y)
+                } else {
+                    (0.0, 0.0)
+                }
+            }
             Location::Airbourne { x, y } => (x, y),
         };
 
@@ -233,6 +330,13 @@ impl Body {
                     0.0
                 }
             }
+            Location::AttachedToEntity {
+                parent,
+                ref attachment,
+            } => entities
+                .get(parent)
+                .map(|parent| attachment_offset(parent, attachment, entity_defs).2)
+                .unwrap_or(0.0),
             _ => 0.0,
         };
         let (x, y) = self.public_bps_xy(entities, entity_defs, action_frame, surfaces, state);
@@ -279,13 +383,10 @@ impl Body {
                 }
             }
 
-            let x_vel = self.x_vel + self.kb_x_vel;
+            let x_vel = self.x_vel * self.status_effects.speed_mult() + self.kb_x_vel;
             let y_vel = self.y_vel + self.kb_y_vel;
 
-            if self.is_ledge() {
-                self.frames_since_ledge = 0;
-            }
-            self.frames_since_ledge += 1;
+            self.step_ledge_regrab_timers();
 
             // update position
             let result = match self.location.clone() {
@@ -315,6 +416,12 @@ impl Body {
                         Some(PhysicsResult::Fall)
                     }
                 }
+                Location::AttachedToEntity { .. } | Location::ItemHeldByPlayer(_) => {
+                    if let Some(parent) = context.parent().and_then(|x| context.entities.get(x)) {
+                        self.face_right = parent.face_right();
+                    }
+                    None
+                }
                 _ => None,
             };
             match result {
@@ -338,10 +445,7 @@ impl Body {
             Some(PhysicsResult::OutOfBounds)
         } else {
             // ledge grabs
-            if self.frames_since_ledge >= 30
-                && self.y_vel < 0.0
-                && context.input.stick_y.value > -0.5
-            {
+            if self.y_vel < 0.0 && context.input.stick_y.value > -0.5 {
                 if let Some(ref ledge_grab_box) = action_frame.ledge_grab_box {
                     self.check_ledge_grab(context, ledge_grab_box)
                 } else {
@@ -361,16 +465,10 @@ impl Body {
         for (platform_i, platform) in context.surfaces.iter().enumerate() {
             let left_grab = platform.left_grab()
                 && self.check_ledge_collision(ledge_grab_box, platform.left_ledge())
-                && context
-                    .entities
-                    .iter()
-                    .all(|(_, x)| !x.is_hogging_ledge(platform_i, true));
+                && self.can_grab_ledge(context.entities, platform_i, true);
             let right_grab = platform.right_grab()
                 && self.check_ledge_collision(ledge_grab_box, platform.right_ledge())
-                && context
-                    .entities
-                    .iter()
-                    .all(|(_, x)| !x.is_hogging_ledge(platform_i, false));
+                && self.can_grab_ledge(context.entities, platform_i, false);
 
             // If both left and right ledges are in range then keep the same direction.
             // This prevents always facing left or right on small surfaces.
@@ -385,19 +483,32 @@ impl Body {
                 self.y_vel = 0.0;
                 let d_x = context.entity_def.ledge_grab_x;
                 let d_y = context.entity_def.ledge_grab_y;
+                let trumped = match ledge_occupant(context.entities, platform_i, self.face_right) {
+                    Some((key, LedgeLogic::Trump)) => Some(key),
+                    _ => None,
+                };
                 self.location = Location::GrabbedLedge {
                     platform_i,
                     d_x,
                     d_y,
-                    logic: LedgeLogic::Hog,
+                    logic: LedgeLogic::Trump,
                 };
-                return Some(PhysicsResult::LedgeGrab);
+                return Some(PhysicsResult::LedgeGrab { trumped });
             }
         }
 
         None
     }
 
+    /// A ledge held with `LedgeLogic::Hog` cannot be grabbed, other occupied ledges can
+    fn can_grab_ledge(&self, entities: &Entities, platform_i: usize, face_right: bool) -> bool {
+        self.can_regrab_ledge(platform_i, face_right)
+            && !matches!(
+                ledge_occupant(entities, platform_i, face_right),
+                Some((_, LedgeLogic::Hog))
+            )
+    }
+
     fn check_ledge_collision(&self, ledge_grab_box: &Rect, ledge: (f32, f32)) -> bool {
         if let Location::Airbourne { x: p_x, y: p_y } = self.location {
             let b_x1 = self
@@ -426,12 +537,11 @@ impl Body {
         old_p: (f32, f32),
         new_p: (f32, f32),
     ) -> Option<usize> {
-        if new_p.1 > old_p.1 {
-            return None;
-        }
-
         for (surface_i, surface) in context.stage.surfaces.iter().enumerate() {
-            if !self.pass_through_platform(context, action_frame, surface)
+            // Check movement relative to the surface so that moving platforms cant pass through the body
+            let old_p = (old_p.0 + surface.vel_x, old_p.1 + surface.vel_y);
+            if new_p.1 <= old_p.1
+                && !self.pass_through_platform(context, action_frame, surface)
                 && surface.floor.is_some()
                 && geometry::segments_intersect(old_p, new_p, surface.p1(), surface.p2())
             {
@@ -506,6 +616,8 @@ impl Body {
             // force set past platform
             let x_offset = if x > 0.0 { 0.000001 } else { -0.000001 }; // just being cautious, probably dont need this
             let (air_x, air_y) = platform.plat_x_to_world_p(x + x_offset);
+            self.x_vel += platform.vel_x;
+            self.y_vel += platform.vel_y;
             self.location = Location::Airbourne { x: air_x, y: air_y };
             Some(PhysicsResult::Fall)
         } else {
@@ -518,6 +630,13 @@ impl Body {
         }
     }
 
+    /// The entity's gravity modified by its status effects and the gravity modifier
+    pub fn gravity(&self, context: &StepContext) -> f32 {
+        context.entity_def.gravity
+            * self.status_effects.gravity_mult()
+            * context.modifiers.gravity.mult()
+    }
+
     pub fn apply_friction_weak(&mut self, fighter: &EntityDef) {
         if self.x_vel > 0.0 {
             self.x_vel -= fighter.friction;
@@ -556,22 +675,32 @@ impl Body {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn launch(
         &mut self,
         context: &mut StepContext,
         state: &ActionState,
         action_frame: Option<&ActionFrame>,
         hitbox: &HitBox,
+        hitbox_id: Option<usize>,
         hurtbox: &HurtBox,
         entity_atk_i: EntityKey,
         kb_vel_mult: f32,
     ) -> f32 {
         let entity_atk = &context.entities[entity_atk_i];
 
-        let damage_done = hitbox.damage * hurtbox.damage_mult; // TODO: apply staling
+        // throws are not hitboxes so they have no hitbox_id and never stale
+        let damage = hitbox.damage * hitbox_id.map_or(1.0, |x| entity_atk.stale_damage_mult(x));
+        let damage_done = damage * hurtbox.damage_mult * self.status_effects.damage_taken_mult();
         self.damage += damage_done;
+        if context.modifiers.one_hit_ko {
+            self.damage = self.damage.max(ONE_HIT_KO_DAMAGE);
+        }
+        if let HitboxEffect::Status(effect) = &hitbox.effect {
+            self.status_effects.apply(effect);
+        }
 
-        let damage_launch = 0.05 * (hitbox.damage * (damage_done + self.damage.floor()))
+        let damage_launch = 0.05 * (damage * (damage_done + self.damage.floor()))
             + (damage_done + self.damage) * 0.1;
         let weight = 2.0 - (2.0 * context.entity_def.weight) / (1.0 + context.entity_def.weight);
         let kbg = hitbox.kbg + hurtbox.kbg_add;
@@ -602,7 +731,7 @@ impl Body {
         };
 
         // convert from degrees to radians
-        let angle_rad = angle_deg.to_radians() + if angle_deg < 0.0 { PI * 2.0 } else { 0.0 };
+        let angle_rad = geometry::positive_angle(angle_deg.to_radians());
 
         // handle reverse hits
         let behind_entity_atk = self.bps_xy(context, action_frame, state).0
@@ -655,6 +784,38 @@ impl Body {
         kb_vel
     }
 
+    /// Launches the body off a hazard surface, returns the knockback velocity
+    pub fn hazard_launch(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+        action_frame: Option<&ActionFrame>,
+        hazard: &Hazard,
+    ) -> f32 {
+        self.damage += hazard.damage * self.status_effects.damage_taken_mult();
+        if context.modifiers.one_hit_ko {
+            self.damage = self.damage.max(ONE_HIT_KO_DAMAGE);
+        }
+
+        let angle = geometry::positive_angle(hazard.angle.to_radians());
+        self.hit_angle_pre_di = Some(angle);
+        self.hit_angle_post_di = Some(angle);
+        self.frames_since_hit = 0;
+
+        let (sin, cos) = angle.sin_cos();
+        self.x_vel = 0.0;
+        self.y_vel = 0.0;
+        self.kb_x_vel = cos * hazard.knockback * 0.03;
+        self.kb_y_vel = sin * hazard.knockback * 0.03;
+        self.kb_x_dec = cos * 0.051;
+        self.kb_y_dec = sin * 0.051;
+
+        let (x, y) = self.bps_xy(context, action_frame, state);
+        self.location = Location::Airbourne { x, y: y + 0.0001 };
+
+        hazard.knockback
+    }
+
     /// 0 < angle < 2pi
     fn di(input: &PlayerInput, angle: f32) -> f32 {
         let range = 18f32.to_radians();
@@ -662,7 +823,7 @@ impl Body {
         let y = input[0].stick_y;
 
         let di_angle = y.atan2(x); // -pi  <= di_angle     <= pi
-        let pos_di_angle = di_angle + if di_angle < 0.0 { PI * 2.0 } else { 0.0 }; // 0    <= pos_di_angle <= 2pi
+        let pos_di_angle = geometry::positive_angle(di_angle); // 0    <= pos_di_angle <= 2pi
         let angle_diff = angle - pos_di_angle; // -2pi <= angle_diff   <= 2pi
 
         let offset_distance = (angle_diff).sin() * (x * x + y * y).sqrt(); // -1     <= offset_distance <= 1
@@ -680,9 +841,54 @@ impl Body {
     }
 }
 
+/// Returns the (x, y, z) offset of an attached entity from its parents bps
+fn attachment_offset(
+    parent: &Entity,
+    attachment: &Attachment,
+    entity_defs: &KeyedContextVec<EntityDef>,
+) -> (f32, f32, f32) {
+    match attachment {
+        Attachment::Offset { x, y } => (parent.relative_f(*x), *y, 0.0),
+        Attachment::ItemHold => parent
+            .get_entity_frame(&entity_defs[parent.state.entity_def_key.as_ref()])
+            .and_then(|action_frame| action_frame.item_hold.as_ref())
+            .map(|item_hold| {
+                (
+                    parent.relative_f(item_hold.translation_x),
+                    item_hold.translation_y,
+                    parent.relative_f(item_hold.translation_z),
+                )
+            })
+            .unwrap_or((0.0, 0.0, 0.0)),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LedgeLogic {
     Hog,
     Share,
     Trump,
 }
+
+#[test]
+fn ledge_regrab_timer_test() {
+    let location = Location::GrabbedLedge {
+        platform_i: 1,
+        d_x: 0.0,
+        d_y: 0.0,
+        logic: LedgeLogic::Trump,
+    };
+    let mut body = Body::new(location, true);
+    body.step_ledge_regrab_timers();
+    assert!(!body.can_regrab_ledge(1, true));
+    assert!(body.can_regrab_ledge(1, false));
+    assert!(body.can_regrab_ledge(0, true));
+
+    body.location = Location::Airbourne { x: 0.0, y: 0.0 };
+    for _ in 0..LEDGE_REGRAB_FRAMES - 1 {
+        body.step_ledge_regrab_timers();
+    }
+    assert!(!body.can_regrab_ledge(1, true));
+    body.step_ledge_regrab_timers();
+    assert!(body.can_regrab_ledge(1, true));
+}
@@ -1,2 +1,4 @@
 pub(crate) mod action_state;
 pub(crate) mod body;
+pub(crate) mod stale_queue;
+pub(crate) mod status_effects;
@@ -0,0 +1,96 @@
+use crate::rules::Staling;
+
+use std::collections::VecDeque;
+
+/// Identifies a hitbox by the action it belongs to and its index among the hitboxes of the action frame
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct StaleHit {
+    pub action: String,
+    pub hitbox_id: usize,
+}
+
+/// The most recent hits landed by a fighter, newest first.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StaleQueue {
+    staling: Staling,
+    hits: VecDeque<StaleHit>,
+    /// Set once the current action has landed a hit
+    action_recorded: bool,
+}
+
+impl StaleQueue {
+    pub fn new(staling: Staling) -> StaleQueue {
+        StaleQueue {
+            staling,
+            hits: VecDeque::new(),
+            action_recorded: false,
+        }
+    }
+
+    /// Multiplier applied to the damage of the hitbox, knockback scales with the damage
+    pub fn damage_mult(&self, action: &str, hitbox_id: usize) -> f32 {
+        let len = self.staling.queue_len;
+        if len == 0 {
+            return 1.0;
+        }
+
+        let mut mult = 1.0;
+        let mut fresh = true;
+        for (i, hit) in self.hits.iter().enumerate() {
+            if hit.action == action && hit.hitbox_id == hitbox_id {
+                mult -= self.staling.decay * (len - i) as f32 / len as f32;
+                fresh = false;
+            }
+        }
+
+        if fresh {
+            self.staling.fresh_bonus
+        } else {
+            mult.max(0.0)
+        }
+    }
+
+    /// Call when a hitbox lands.
+    /// Only the first hit of each action is recorded, so multihits and hitting several fighters at once count as a single use.
+    pub fn push(&mut self, action: &str, hitbox_id: usize) {
+        if self.staling.queue_len == 0 || self.action_recorded {
+            return;
+        }
+        self.action_recorded = true;
+
+        self.hits.push_front(StaleHit {
+            action: action.to_string(),
+            hitbox_id,
+        });
+        self.hits.truncate(self.staling.queue_len);
+    }
+
+    /// Call when a new action starts, allowing its next hit to be recorded
+    pub fn refresh(&mut self) {
+        self.action_recorded = false;
+    }
+
+    pub fn hits(&self) -> &VecDeque<StaleHit> {
+        &self.hits
+    }
+}
+
+#[test]
+fn stale_queue_test() {
+    let mut queue = StaleQueue::new(Staling::default());
+    assert_eq!(queue.damage_mult("Jab", 0), 1.05);
+
+    queue.push("Jab", 0);
+    queue.push("Jab", 0);
+    assert_eq!(queue.hits().len(), 1);
+    assert!((queue.damage_mult("Jab", 0) - 0.91).abs() < 0.0001);
+    assert_eq!(queue.damage_mult("Jab", 1), 1.05);
+
+    for _ in 0..9 {
+        queue.refresh();
+        queue.push("Fair", 0);
+    }
+    assert_eq!(queue.hits().len(), 9);
+    assert_eq!(queue.damage_mult("Jab", 0), 1.05);
+    assert!((queue.damage_mult("Fair", 0) - 0.55).abs() < 0.0001);
+}
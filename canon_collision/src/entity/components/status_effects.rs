@@ -0,0 +1,117 @@
+use canon_collision_lib::entity_def::{StatusEffect, StatusEffectKind};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ActiveStatusEffect {
+    effect: StatusEffect,
+    frames_remaining: u64,
+}
+
+/// The status effects currently applied to an entity, oldest first.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct StatusEffects {
+    effects: Vec<ActiveStatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn apply(&mut self, effect: &StatusEffect) {
+        let stacks = self
+            .effects
+            .iter()
+            .filter(|x| x.effect.kind == effect.kind)
+            .count() as u64;
+        if stacks >= effect.max_stacks {
+            if let Some(oldest) = self
+                .effects
+                .iter()
+                .position(|x| x.effect.kind == effect.kind)
+            {
+                self.effects.remove(oldest);
+            }
+        }
+
+        if effect.max_stacks > 0 {
+            self.effects.push(ActiveStatusEffect {
+                effect: effect.clone(),
+                frames_remaining: effect.frames,
+            });
+        }
+    }
+
+    /// Call this once every frame, returns the damage dealt by the effects this frame
+    pub fn step(&mut self) -> f32 {
+        let damage = self.effects.iter().map(|x| x.effect.damage_per_frame).sum();
+        for active in &mut self.effects {
+            active.frames_remaining = active.frames_remaining.saturating_sub(1);
+        }
+        self.effects.retain(|x| x.frames_remaining > 0);
+        damage
+    }
+
+    pub fn speed_mult(&self) -> f32 {
+        self.effects.iter().map(|x| x.effect.speed_mult).product()
+    }
+
+    pub fn gravity_mult(&self) -> f32 {
+        self.effects.iter().map(|x| x.effect.gravity_mult).product()
+    }
+
+    pub fn damage_taken_mult(&self) -> f32 {
+        self.effects
+            .iter()
+            .map(|x| x.effect.damage_taken_mult)
+            .product()
+    }
+
+    /// One entry per kind of effect, in the order they were first applied
+    pub fn render(&self) -> Vec<RenderStatusEffect> {
+        let mut render: Vec<RenderStatusEffect> = vec![];
+        for active in &self.effects {
+            match render.iter_mut().find(|x| x.kind == active.effect.kind) {
+                Some(existing) => {
+                    existing.stacks += 1;
+                    existing.frames_remaining =
+                        existing.frames_remaining.max(active.frames_remaining);
+                }
+                None => render.push(RenderStatusEffect {
+                    kind: active.effect.kind,
+                    stacks: 1,
+                    frames_remaining: active.frames_remaining,
+                }),
+            }
+        }
+        render
+    }
+}
+
+pub struct RenderStatusEffect {
+    pub kind: StatusEffectKind,
+    pub stacks: usize,
+    pub frames_remaining: u64,
+}
+
+#[test]
+fn status_effects_test() {
+    let slow = StatusEffect {
+        kind: StatusEffectKind::Slow,
+        frames: 2,
+        max_stacks: 2,
+        speed_mult: 0.5,
+        damage_per_frame: 0.0,
+        ..StatusEffect::default()
+    };
+    let poison = StatusEffect::default();
+
+    let mut effects = StatusEffects::default();
+    effects.apply(&slow);
+    effects.apply(&slow);
+    effects.apply(&slow);
+    effects.apply(&poison);
+    assert_eq!(effects.speed_mult(), 0.25);
+    assert_eq!(effects.render().len(), 2);
+    assert_eq!(effects.render()[0].stacks, 2);
+
+    assert_eq!(effects.step(), poison.damage_per_frame);
+    effects.step();
+    assert_eq!(effects.speed_mult(), 1.0);
+    assert_eq!(effects.render().len(), 1);
+}
@@ -57,6 +57,6 @@ impl Fighter {
         context: &mut StepContext,
         state: &ActionState,
     ) -> Option<ActionResult> {
-        self.get_player_mut().action_expired(context, state)
+        self.get_fighter_mut().action_expired(context, state)
     }
 }
@@ -1,20 +1,26 @@
 use crate::audio::sfx::SfxType;
 use crate::collision::collision_box::CollisionResult;
 use crate::entity::components::action_state::ActionState;
-use crate::entity::components::body::{Body, Location, PhysicsResult};
+use crate::entity::components::body::{Body, Location, PhysicsResult, LEDGE_REGRAB_FRAMES};
+use crate::entity::components::stale_queue::StaleQueue;
+use crate::entity::components::status_effects::RenderStatusEffect;
 use crate::entity::item::{Item, MessageItem};
 use crate::entity::{
     ActionResult, DebugEntity, Entities, Entity, EntityKey, EntityType, Message, MessageContents,
     StepContext, VectorArrow,
 };
+use crate::game::{PlayerSetup, RenderRect};
 use crate::graphics;
 use crate::particle::{Particle, ParticleType};
 use crate::results::{DeathRecord, RawPlayerResult};
-use crate::rules::{Goal, Rules};
+use crate::rules::{Assists, Goal, Rules};
 
+use canon_collision_lib::config::{EffectColors, TeamPalette};
 use canon_collision_lib::entity_def::item::ItemAction;
 use canon_collision_lib::entity_def::player::PlayerAction;
-use canon_collision_lib::entity_def::{EntityDef, HitBox, HitStun, HitboxEffect, HurtBox, Shield};
+use canon_collision_lib::entity_def::{
+    EntityDef, HitBox, HitSpark, HitStun, HitboxEffect, HurtBox, Shield, Throw,
+};
 use canon_collision_lib::geometry::Rect;
 use canon_collision_lib::input::state::PlayerInput;
 use canon_collision_lib::package::Package;
@@ -26,6 +32,27 @@ use treeflection::KeyedContextVec;
 use std::f32;
 use std::f32::consts::PI;
 
+/// Speed of a fighter carrying a grabbed fighter relative to their normal walk and air speed
+const CARRY_VEL_MULT: f32 = 0.5;
+/// Frames the grabbed fighter is held for when not mashing
+const GRAB_ESCAPE_FRAMES: u64 = 60;
+/// Frames taken off the grab for each mash input
+const GRAB_MASH_FRAMES: u64 = 4;
+/// Extra frames the grabbed fighter is held for per point of damage they have taken
+const GRAB_ESCAPE_FRAMES_PER_DAMAGE: f32 = 0.5;
+/// Meter required to use the Super action
+const METER_MAX: f32 = 100.0;
+/// Meter gained per point of damage dealt, taking damage gains half as much
+const METER_PER_DAMAGE: f32 = 1.0;
+/// Frames a fighter trumped off a ledge cannot be hit or grabbed
+const LEDGE_TRUMP_INTANGIBLE_FRAMES: u64 = 30;
+/// Frames a fighter can hang on a ledge before letting go
+pub const LEDGE_IDLE_MAX_FRAMES: u64 = 600;
+/// Knockback above which a hit fighter tumbles in DamageFly instead of flinching in Damage
+const DAMAGE_FLY_MIN_KNOCKBACK: f32 = 80.0;
+/// Hitstun frames per point of knockback from a hazard surface, matches the default hitbox hitstun
+const HAZARD_HITSTUN_PER_KNOCKBACK: f32 = 0.5;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum LockTimer {
     Active(u64),
@@ -52,14 +79,23 @@ pub enum MessagePlayer {
     },
     #[allow(dead_code)]
     Released,
+    /// Another fighter grabbed the ledge this fighter is holding
+    LedgeTrumped,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: usize, // unique id among players
     pub team: usize,
+    pub effect_colors: EffectColors,
     pub body: Body,
     pub stocks: Option<u64>,
+    /// None when the super meter is disabled
+    pub meter: Option<f32>,
+    pub assists: Assists,
+    /// Frames the player can wait on the respawn platform before they are dropped from it
+    pub respawn_frames: u64,
+    pub stale_queue: StaleQueue,
     pub ledge_idle_timer: u64,
     pub fastfalled: bool,
     pub air_jumps_left: u64,
@@ -69,6 +105,7 @@ pub struct Player {
     pub shield_offset_x: f32,
     pub shield_offset_y: f32,
     pub stun_timer: u64,
+    pub intangible_timer: u64,
     pub shield_stun_timer: u64,
     pub parry_timer: u64,
     pub tech_timer: LockTimer,
@@ -77,8 +114,12 @@ pub struct Player {
     pub hitstun: f32,
     /// this is only used for end-game statistics so player id is fine
     pub hit_by: Option<usize>,
+    /// The action of the entity that last hit this player, used for the kill feed
+    pub hit_by_action: Option<String>,
     pub particles: Vec<Particle>,
     pub aerial_dodge_frame: Option<u64>,
+    /// Buttons pressed while grabbed, each press brings the escape closer
+    pub grab_mash: u64,
     pub result: RawPlayerResult,
 
     // Only use for debug display
@@ -88,13 +129,13 @@ pub struct Player {
 
 impl Player {
     pub fn new(
-        entity_def_key: &str,
-        team: usize,
+        setup: &PlayerSetup,
         id: usize,
         stage: &Stage,
         package: &Package,
         rules: &Rules,
     ) -> Player {
+        let entity_def_key = setup.fighter.as_str();
         // get the spawn point
         let spawn = if stage.spawn_points.len() == 0 {
             None
@@ -145,8 +186,15 @@ impl Player {
             Location::Airbourne { x: 0.0, y: 0.0 }
         };
 
+        let mut body = Body::new(location, spawn.map(|x| x.face_right).unwrap_or(false));
+        body.damage = setup.damage;
+
         Player {
-            stocks: rules.stock_count,
+            stocks: setup.stocks.or(rules.stock_count),
+            meter: if rules.meter { Some(0.0) } else { None },
+            assists: rules.assists.clone(),
+            respawn_frames: rules.respawn_frames,
+            stale_queue: StaleQueue::new(rules.staling.clone()),
             ledge_idle_timer: 0,
             fastfalled: false,
             air_jumps_left: package.entities[entity_def_key]
@@ -162,6 +210,7 @@ impl Player {
             shield_offset_x: 0.0,
             shield_offset_y: 0.0,
             stun_timer: 0,
+            intangible_timer: 0,
             shield_stun_timer: 0,
             parry_timer: 0,
             tech_timer: LockTimer::Free,
@@ -169,12 +218,15 @@ impl Player {
             land_frame_skip: 0,
             hitstun: 0.0,
             hit_by: None,
+            hit_by_action: None,
             particles: vec![],
             aerial_dodge_frame: None,
+            grab_mash: 0,
             result: RawPlayerResult::default(),
-            body: Body::new(location, spawn.map(|x| x.face_right).unwrap_or(false)),
+            body,
             id,
-            team,
+            team: setup.team,
+            effect_colors: setup.effect_colors.clone(),
 
             // Only use for debug display
             stick: None,
@@ -182,6 +234,22 @@ impl Player {
         }
     }
 
+    /// The body of a player with the given id waiting on its respawn platform
+    pub fn respawn_body(stage: &Stage, id: usize) -> Body {
+        if stage.respawn_points.is_empty() {
+            Body::new(Location::Airbourne { x: 0.0, y: 0.0 }, true)
+        } else {
+            let respawn = &stage.respawn_points[id % stage.respawn_points.len()];
+            Body::new(
+                Location::Airbourne {
+                    x: respawn.x,
+                    y: respawn.y,
+                },
+                respawn.face_right,
+            )
+        }
+    }
+
     pub fn bps_xy(&self, context: &StepContext, state: &ActionState) -> (f32, f32) {
         let action_frame =
             state.get_entity_frame(&context.entity_defs[state.entity_def_key.as_ref()]);
@@ -286,13 +354,13 @@ impl Player {
         context: &mut StepContext,
         state: &ActionState,
         hitbox: &HitBox,
+        hitbox_id: Option<usize>,
         hurtbox: &HurtBox,
         entity_atk_i: EntityKey,
     ) -> Option<ActionResult> {
-        self.hit_by = context
-            .entities
-            .get(entity_atk_i)
-            .and_then(|x| x.player_id());
+        let attacker = context.entities.get(entity_atk_i);
+        self.hit_by = attacker.and_then(|x| x.player_id());
+        self.hit_by_action = attacker.map(|x| x.state.action.clone());
         let kb_vel_mult = if let Some(PlayerAction::Crouch) = state.get_action() {
             0.67
         } else {
@@ -306,6 +374,7 @@ impl Player {
             state,
             action_frame,
             hitbox,
+            hitbox_id,
             hurtbox,
             entity_atk_i,
             kb_vel_mult,
@@ -319,7 +388,7 @@ impl Player {
             };
         }
 
-        if kb_vel > 80.0 {
+        if kb_vel > DAMAGE_FLY_MIN_KNOCKBACK {
             ActionResult::set_action(PlayerAction::DamageFly)
         } else {
             ActionResult::set_action(PlayerAction::Damage)
@@ -338,15 +407,32 @@ impl Player {
         let mut set_action = None;
         for col_result in col_results {
             match col_result {
-                CollisionResult::HitAtk { hitbox, point, .. } => {
-                    self.hit_particles(*point, hitbox);
+                CollisionResult::HitAtk {
+                    hitbox,
+                    hitbox_id,
+                    point,
+                    ..
+                } => {
+                    self.stale_queue.push(&state.action, *hitbox_id);
+                    self.hit_particles(context, *point, hitbox);
+                    self.charge_meter(hitbox.damage * METER_PER_DAMAGE);
                 }
                 CollisionResult::HitDef {
                     hitbox,
+                    hitbox_id,
                     hurtbox,
                     entity_atk_i,
                 } => {
-                    set_action = self.launch(context, state, hitbox, hurtbox, *entity_atk_i);
+                    let damage_before = self.body.damage;
+                    set_action = self.launch(
+                        context,
+                        state,
+                        hitbox,
+                        Some(*hitbox_id),
+                        hurtbox,
+                        *entity_atk_i,
+                    );
+                    self.charge_meter((self.body.damage - damage_before) * METER_PER_DAMAGE * 0.5);
                 }
                 CollisionResult::HitShieldAtk {
                     hitbox,
@@ -409,9 +495,11 @@ impl Player {
                 CollisionResult::GrabAtk(_entity_defend_i) => {
                     set_action = ActionResult::set_action(PlayerAction::GrabbingIdle)
                 }
+                CollisionResult::ShieldPush { x_vel } => self.body.x_vel += x_vel,
                 CollisionResult::GrabDef(entity_atk_i) => {
                     self.body.face_right = !context.entities[*entity_atk_i].face_right();
                     self.body.location = Location::GrabbedByPlayer(*entity_atk_i);
+                    self.grab_mash = 0;
                     set_action = ActionResult::set_action(PlayerAction::GrabbedIdle)
                 }
                 _ => {}
@@ -446,6 +534,10 @@ impl Player {
             self.parry_timer -= 1;
         }
 
+        if self.intangible_timer > 0 {
+            self.intangible_timer -= 1;
+        }
+
         if self.shield_stun_timer > 0 {
             self.shield_stun_timer -= 1;
         }
@@ -512,6 +604,7 @@ impl Player {
         // update ecb
         let prev_bottom = self.body.ecb.bottom;
         self.body.ecb = fighter_frame.ecb.clone();
+        self.body.ecb.scale(context.modifiers.size.scale());
         match state.get_action() {
             Some(PlayerAction::JumpF)
             | Some(PlayerAction::JumpB)
@@ -592,11 +685,17 @@ impl Player {
                 PlayerAction::PowerShield      => self.shield_action(context, state),
                 PlayerAction::Shield           => self.shield_action(context, state),
                 PlayerAction::ShieldOff        => self.shield_off_action(context, state),
-                PlayerAction::ShieldBreakFall  => self.shield_break_fall_action(context.entity_def),
+                PlayerAction::ShieldBreakFall  => self.shield_break_fall_action(context),
                 PlayerAction::ShieldBreakGetup => self.shield_break_getup_action(),
                 PlayerAction::Stun             => self.stun_action(context, state),
                 PlayerAction::GrabbingIdle     => self.grabbing_idle_action(context, state),
                 PlayerAction::GrabbedIdle      => self.grabbed_idle_action(context, state),
+                PlayerAction::GrabbingWalk     => self.grabbing_walk_action(context, state),
+                PlayerAction::GrabbingJump     => self.grabbing_jump_action(context),
+                PlayerAction::Uthrow | PlayerAction::Dthrow |
+                PlayerAction::Fthrow | PlayerAction::Bthrow
+                => self.throw_action(context, state),
+                PlayerAction::Super            => self.super_action(context, state),
                 _ => None,
             }
         } else {
@@ -657,7 +756,7 @@ impl Player {
             } else {
                 ActionResult::set_action(PlayerAction::LedgeRollSlow)
             }
-        } else if self.ledge_idle_timer > 600 {
+        } else if self.ledge_idle_timer > LEDGE_IDLE_MAX_FRAMES {
             self.set_airbourne(context, state);
             ActionResult::set_action(PlayerAction::DamageFall)
         } else {
@@ -720,7 +819,7 @@ impl Player {
             }
         } else {
             if self.body.is_airbourne() {
-                self.fall_action(context.entity_def);
+                self.fall_action(context);
             } else {
                 self.apply_friction(context.entity_def, state);
             }
@@ -730,7 +829,7 @@ impl Player {
 
     fn damage_fly_action(&mut self, context: &mut StepContext) -> Option<ActionResult> {
         self.hitstun -= 1.0;
-        self.fall_action(context.entity_def);
+        self.fall_action(context);
         if self.hitstun <= 0.0 {
             ActionResult::set_action(PlayerAction::DamageFall)
         } else {
@@ -780,7 +879,7 @@ impl Player {
             .or_else(|| {
                 if context.input[0].stick_x.abs() > 0.2
                     || context.input[0].stick_y.abs() > 0.2
-                    || state.frame_no_restart >= 1000
+                    || state.frame_no_restart as u64 >= self.respawn_frames
                 {
                     ActionResult::set_action(PlayerAction::Fall)
                 } else {
@@ -796,6 +895,7 @@ impl Player {
     ) -> Option<ActionResult> {
         if state.interruptible(context.entity_def) {
             None.or_else(|| self.check_attacks_aerial(context))
+                .or_else(|| self.check_super(context))
                 .or_else(|| self.check_special_air(context))
                 .or_else(|| self.check_jump_aerial(context, state))
                 .or_else(|| self.check_aerialdodge(context))
@@ -815,11 +915,17 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         if state.frame == 0 {
+            let x = self.bps_xy(context, state).0;
             context
                 .audio
-                .play_sound_effect(context.entity_def, SfxType::Jump);
+                .play_sound_effect(context.entity_def, SfxType::Jump, x);
+
+            if Player::shoulder_pressed_within(context.input, self.assists.wavedash_buffer) {
+                return self.aerialdodge(context);
+            }
         }
         None.or_else(|| self.check_attacks_aerial(context))
+            .or_else(|| self.check_super(context))
             .or_else(|| self.check_special_air(context))
             .or_else(|| self.check_jump_aerial(context, state))
             .or_else(|| self.check_aerialdodge(context))
@@ -879,7 +985,7 @@ impl Player {
         None.or_else(|| self.check_dash_out_of_tilt_turn(context, state))
             .or_else(|| self.check_jump(context))
             .or_else(|| self.check_shield(context))
-            .or_else(|| self.check_special_ground(context)) // TODO: No neutral special
+            .or_else(|| self.check_special_ground_except(context, &[SpecialDirection::Neutral]))
             .or_else(|| self.check_smash(context))
             .or_else(|| self.check_attacks(context))
             .or_else(|| self.check_grab(context))
@@ -918,7 +1024,7 @@ impl Player {
         None.or_else(|| self.check_dash_out_of_smash_turn(context, state))
             .or_else(|| self.check_jump(context))
             .or_else(|| self.check_shield(context))
-            .or_else(|| self.check_special_ground(context)) // TODO: No neutral special
+            .or_else(|| self.check_special_ground_except(context, &[SpecialDirection::Neutral]))
             .or_else(|| self.check_smash(context))
             .or_else(|| self.check_attacks(context))
             .or_else(|| self.check_grab(context))
@@ -972,7 +1078,12 @@ impl Player {
         if state.interruptible(context.entity_def) {
             None.or_else(|| self.check_pass_platform(context, state))
                 .or_else(|| self.check_shield(context))
-                .or_else(|| self.check_special_ground(context)) // TODO: no neutral/side special
+                .or_else(|| {
+                    self.check_special_ground_except(
+                        context,
+                        &[SpecialDirection::Neutral, SpecialDirection::Side],
+                    )
+                })
                 .or_else(|| self.check_smash(context))
                 .or_else(|| self.check_attacks(context))
                 .or_else(|| self.check_grab(context))
@@ -995,7 +1106,12 @@ impl Player {
         if state.interruptible(context.entity_def) {
             None.or_else(|| self.check_jump(context))
                 .or_else(|| self.check_shield(context))
-                .or_else(|| self.check_special_ground(context)) // TODO: no neutral/side special
+                .or_else(|| {
+                    self.check_special_ground_except(
+                        context,
+                        &[SpecialDirection::Neutral, SpecialDirection::Side],
+                    )
+                })
                 .or_else(|| self.check_smash(context))
                 .or_else(|| self.check_attacks(context))
                 .or_else(|| self.check_grab(context))
@@ -1027,7 +1143,12 @@ impl Player {
         if state.interruptible(context.entity_def) {
             None.or_else(|| self.check_jump(context))
                 .or_else(|| self.check_shield(context))
-                .or_else(|| self.check_special_ground(context)) // TODO: no neutral/side special
+                .or_else(|| {
+                    self.check_special_ground_except(
+                        context,
+                        &[SpecialDirection::Neutral, SpecialDirection::Side],
+                    )
+                })
                 .or_else(|| self.check_smash(context))
                 .or_else(|| self.check_attacks(context))
                 .or_else(|| self.check_grab(context))
@@ -1071,6 +1192,7 @@ impl Player {
         if state.interruptible(context.entity_def) {
             None.or_else(|| self.check_jump(context))
                 .or_else(|| self.check_shield(context))
+                .or_else(|| self.check_super(context))
                 .or_else(|| self.check_special_ground(context))
                 .or_else(|| self.check_smash(context))
                 .or_else(|| self.check_attacks(context))
@@ -1105,7 +1227,7 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         if state.frame == 4 {
-            if let Some(item) = self.get_held_item(context.entities) {
+            if let Some(item) = context.held_item() {
                 let message_item = match state.get_action() {
                     Some(PlayerAction::ItemThrowF) | Some(PlayerAction::ItemThrowAirF) => {
                         MessageItem::Thrown {
@@ -1148,9 +1270,10 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         if state.frame == 0 {
+            let x = self.bps_xy(context, state).0;
             context
                 .audio
-                .play_sound_effect(context.entity_def, SfxType::Land);
+                .play_sound_effect(context.entity_def, SfxType::Land, x);
         }
         let frame = state.frame + self.land_frame_skip as i64 + 1;
 
@@ -1164,9 +1287,10 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         if state.frame == 0 {
+            let x = self.bps_xy(context, state).0;
             context
                 .audio
-                .play_sound_effect(context.entity_def, SfxType::Land);
+                .play_sound_effect(context.entity_def, SfxType::Land, x);
         }
         self.land_particles(context, state);
 
@@ -1229,9 +1353,10 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         if state.frame_no_restart % 20 == 0 {
+            let x = self.bps_xy(context, state).0;
             context
                 .audio
-                .play_sound_effect(context.entity_def, SfxType::Walk);
+                .play_sound_effect(context.entity_def, SfxType::Walk, x);
         }
 
         if context.input[0].stick_x == 0.0 {
@@ -1241,6 +1366,7 @@ impl Player {
         }
         .or_else(|| self.check_jump(context))
         .or_else(|| self.check_shield(context))
+        .or_else(|| self.check_super(context))
         .or_else(|| self.check_special_ground(context))
         .or_else(|| self.check_smash(context))
         .or_else(|| self.check_attacks(context))
@@ -1274,9 +1400,10 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         if state.frame == 0 {
+            let x = self.bps_xy(context, state).0;
             context
                 .audio
-                .play_sound_effect(context.entity_def, SfxType::Dash);
+                .play_sound_effect(context.entity_def, SfxType::Dash, x);
         }
         self.dash_particles(context, state);
         if state.frame == 1 {
@@ -1346,9 +1473,10 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         if state.frame_no_restart % 17 == 0 {
+            let x = self.bps_xy(context, state).0;
             context
                 .audio
-                .play_sound_effect(context.entity_def, SfxType::Run);
+                .play_sound_effect(context.entity_def, SfxType::Run, x);
         }
         None.or_else(|| self.check_jump(context))
             .or_else(|| self.check_shield(context))
@@ -1418,6 +1546,12 @@ impl Player {
         }
     }
 
+    /// Returns true if L or R was pressed on any of the last `frames` frames
+    fn shoulder_pressed_within(input: &PlayerInput, frames: u64) -> bool {
+        let frames = (frames as usize).min(input.history.len() - 1);
+        (0..frames).any(|i| (input[i].l && !input[i + 1].l) || (input[i].r && !input[i + 1].r))
+    }
+
     fn aerialdodge(&mut self, context: &mut StepContext) -> Option<ActionResult> {
         match context.input[0].stick_angle() {
             Some(angle) => {
@@ -1553,8 +1687,8 @@ impl Player {
         }
     }
 
-    fn shield_break_fall_action(&mut self, entity_def: &EntityDef) -> Option<ActionResult> {
-        self.fall_action(entity_def);
+    fn shield_break_fall_action(&mut self, context: &StepContext) -> Option<ActionResult> {
+        self.fall_action(context);
         None
     }
 
@@ -1590,7 +1724,92 @@ impl Player {
         state: &ActionState,
     ) -> Option<ActionResult> {
         self.apply_friction(context.entity_def, state);
-        if (self.relative_f(context.input[0].stick_x) <= -0.66
+        None.or_else(|| self.check_throw(context, true))
+            .or_else(|| self.check_grab_released(context))
+            .or_else(|| self.check_grabbing_jump(context))
+            .or_else(|| {
+                if context.input[0].stick_x.abs() >= 0.3 {
+                    ActionResult::set_action(PlayerAction::GrabbingWalk)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Walking while carrying the grabbed fighter.
+    /// The stick is used for walking so only the c-stick can throw.
+    fn grabbing_walk_action(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        if context.input[0].stick_x.abs() < 0.3 {
+            ActionResult::set_action(PlayerAction::GrabbingIdle)
+        } else {
+            None
+        }
+        .or_else(|| self.check_throw(context, false))
+        .or_else(|| self.check_grab_released(context))
+        .or_else(|| self.check_grabbing_jump(context))
+        .or_else(|| {
+            let vel_max =
+                context.entity_def.walk_max_vel * CARRY_VEL_MULT * context.input[0].stick_x;
+            if self.body.x_vel.abs() > vel_max.abs() {
+                self.apply_friction(context.entity_def, state);
+            } else {
+                self.body.x_vel = vel_max;
+            }
+            None
+        })
+    }
+
+    fn grabbing_jump_action(&mut self, context: &mut StepContext) -> Option<ActionResult> {
+        self.check_grab_released(context).or_else(|| {
+            self.air_drift(context);
+            let term_vel = context.entity_def.air_x_term_vel * CARRY_VEL_MULT;
+            self.body.x_vel = self.body.x_vel.max(-term_vel).min(term_vel);
+            self.fastfall_action(context);
+            None
+        })
+    }
+
+    fn check_grabbing_jump(&mut self, context: &mut StepContext) -> Option<ActionResult> {
+        if self.jump_input(context.input).jump() {
+            ActionResult::set_action(PlayerAction::GrabbingJumpSquat)
+        } else {
+            None
+        }
+    }
+
+    /// The carried jump is always a short hop due to the weight of the grabbed fighter
+    fn grabbing_jump(&mut self, context: &mut StepContext, state: &ActionState) -> PlayerAction {
+        let (surface_vel_x, surface_vel_y) = self.body.surface_vel(context.surfaces);
+        self.set_airbourne(context, state);
+        if let &mut Location::Airbourne { ref mut y, .. } = &mut self.body.location {
+            *y += 0.0001;
+        }
+
+        let term_vel = context.entity_def.air_x_term_vel * CARRY_VEL_MULT;
+        self.body.y_vel = context.entity_def.jump_y_init_vel_short + surface_vel_y;
+        self.body.x_vel = self.body.x_vel.max(-term_vel).min(term_vel) + surface_vel_x;
+        PlayerAction::GrabbingJump
+    }
+
+    /// The grabbed fighter has escaped or been hit out of the grab
+    fn check_grab_released(&mut self, context: &mut StepContext) -> Option<ActionResult> {
+        if self.get_held_fighter(context.entities).is_some() {
+            None
+        } else if self.body.is_airbourne() {
+            ActionResult::set_action(PlayerAction::Fall)
+        } else {
+            ActionResult::set_action(PlayerAction::GrabbingEnd)
+        }
+    }
+
+    /// Throws are triggered by flicking the stick or c-stick, `use_stick` disables the stick
+    fn check_throw(&mut self, context: &mut StepContext, use_stick: bool) -> Option<ActionResult> {
+        if (use_stick
+            && self.relative_f(context.input[0].stick_x) <= -0.66
             && self.relative_f(context.input[1].stick_x) > -0.66
             && context.input[0].stick_x.abs() > context.input[0].stick_y.abs() - 0.1)
             || (self.relative_f(context.input[0].c_stick_x) <= -0.66
@@ -1598,7 +1817,8 @@ impl Player {
                 && context.input[0].c_stick_x.abs() > context.input[0].c_stick_y.abs() - 0.1)
         {
             ActionResult::set_action(PlayerAction::Bthrow)
-        } else if (self.relative_f(context.input[0].stick_x) >= 0.66
+        } else if (use_stick
+            && self.relative_f(context.input[0].stick_x) >= 0.66
             && self.relative_f(context.input[1].stick_x) < 0.66
             && context.input[0].stick_x.abs() > context.input[0].stick_y.abs() - 0.1)
             || (self.relative_f(context.input[0].c_stick_x) >= 0.66
@@ -1606,17 +1826,52 @@ impl Player {
                 && context.input[0].c_stick_x.abs() > context.input[0].c_stick_y.abs() - 0.1)
         {
             ActionResult::set_action(PlayerAction::Fthrow)
-        } else if (context.input[0].stick_y >= 0.66 && context.input[1].stick_y < 0.66)
+        } else if (use_stick && context.input[0].stick_y >= 0.66 && context.input[1].stick_y < 0.66)
             || (context.input[0].c_stick_y >= 0.66 && context.input[1].c_stick_y < 0.66)
         {
             ActionResult::set_action(PlayerAction::Uthrow)
-        } else if (context.input[0].stick_y <= -0.66 && context.input[1].stick_y > -0.66)
+        } else if (use_stick
+            && context.input[0].stick_y <= -0.66
+            && context.input[1].stick_y > -0.66)
             || (context.input[0].c_stick_y <= -0.66 && context.input[1].c_stick_y > -0.66)
         {
             ActionResult::set_action(PlayerAction::Dthrow)
-        } else if state.frame_no_restart > 60 {
-            // TODO: additionally check if grabbed player is still in a grabbed state
-            ActionResult::set_action(PlayerAction::GrabbingEnd)
+        } else {
+            None
+        }
+    }
+
+    /// Applies the throw knockback defined by the fighter to the grabbed fighter on the throw's release frame
+    fn throw_action(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        let throws = match context.entity_def.fighter() {
+            Some(fighter) => &fighter.throws,
+            None => return None,
+        };
+        let throw = match state.get_action() {
+            Some(PlayerAction::Uthrow) => &throws.up,
+            Some(PlayerAction::Dthrow) => &throws.down,
+            Some(PlayerAction::Fthrow) => &throws.forward,
+            Some(PlayerAction::Bthrow) => &throws.back,
+            _ => return None,
+        };
+        if state.frame as u64 == throw.release_frame {
+            let throw = throw.clone();
+            self.send_thrown_message(context, &throw);
+        }
+        None
+    }
+
+    /// The action of the fighter holding this fighter, None if this fighter is not grabbed
+    fn grabber_action(&self, context: &StepContext) -> Option<PlayerAction> {
+        if let Location::GrabbedByPlayer(grabber_i) = self.body.location {
+            context
+                .entities
+                .get(grabber_i)
+                .and_then(|x| x.state.get_action())
         } else {
             None
         }
@@ -1629,26 +1884,71 @@ impl Player {
         context: &mut StepContext,
         state: &ActionState,
     ) -> Option<ActionResult> {
-        if state.frame_no_restart > 60 {
-            // TODO: instead check if grabbing player is still in a grabbing state
-            let bps_xy = self.bps_xy(context, state);
-            if let Some(frame) = state.get_entity_frame(context.entity_def) {
-                // ignore the x offset, we only want to check straight down.
-                let bps_xy_grab_point = (bps_xy.0, bps_xy.1 + frame.grabbed_y);
-                if let Some(platform_i) =
-                    self.body
-                        .land_stage_collision(context, frame, bps_xy_grab_point, bps_xy)
-                {
-                    let x = context.stage.surfaces[platform_i].world_x_to_plat_x(bps_xy.0);
-                    self.body.location = Location::Surface { platform_i, x };
-                    self.land(context, state);
-                    ActionResult::set_action(PlayerAction::GrabbedEnd)
-                } else {
-                    self.set_airbourne(context, state);
-                    ActionResult::set_action(PlayerAction::Fall)
-                }
+        if let Location::GrabbedByPlayer(grabber_i) = self.body.location {
+            if let Some(grabber) = context.entities.get(grabber_i) {
+                // keep the grab points lined up when the grabber turns around e.g. during a back throw
+                self.body.face_right = !grabber.face_right();
+            }
+        }
+
+        if context.input.a.press
+            || context.input.b.press
+            || context.input.x.press
+            || context.input.y.press
+            || (context.input[0].stick_x.abs() > 0.5 && context.input[1].stick_x.abs() <= 0.5)
+            || (context.input[0].stick_y.abs() > 0.5 && context.input[1].stick_y.abs() <= 0.5)
+        {
+            self.grab_mash += 1;
+        }
+
+        let escape_frames =
+            GRAB_ESCAPE_FRAMES + (self.body.damage * GRAB_ESCAPE_FRAMES_PER_DAMAGE) as u64;
+        let released = match self.grabber_action(context) {
+            // once a throw starts it cannot be escaped, the grabbed fighter is released by the throw itself
+            Some(PlayerAction::Uthrow)
+            | Some(PlayerAction::Dthrow)
+            | Some(PlayerAction::Fthrow)
+            | Some(PlayerAction::Bthrow) => false,
+            Some(PlayerAction::Grab)
+            | Some(PlayerAction::DashGrab)
+            | Some(PlayerAction::GrabbingIdle)
+            | Some(PlayerAction::GrabbingWalk)
+            | Some(PlayerAction::GrabbingJumpSquat)
+            | Some(PlayerAction::GrabbingJump) => {
+                state.frame_no_restart as u64 + self.grab_mash * GRAB_MASH_FRAMES > escape_frames
+            }
+            // the grabber was hit or otherwise let go
+            _ => true,
+        };
+
+        if released {
+            self.grab_release(context, state)
+        } else {
+            None
+        }
+    }
+
+    /// Drops the grabbed fighter to the floor beneath them, or into the air if there is none
+    fn grab_release(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        let bps_xy = self.bps_xy(context, state);
+        if let Some(frame) = state.get_entity_frame(context.entity_def) {
+            // ignore the x offset, we only want to check straight down.
+            let bps_xy_grab_point = (bps_xy.0, bps_xy.1 + frame.grabbed_y);
+            if let Some(platform_i) =
+                self.body
+                    .land_stage_collision(context, frame, bps_xy_grab_point, bps_xy)
+            {
+                let x = context.stage.surfaces[platform_i].world_x_to_plat_x(bps_xy.0);
+                self.body.location = Location::Surface { platform_i, x };
+                self.land(context, state);
+                ActionResult::set_action(PlayerAction::GrabbedEnd)
             } else {
-                None
+                self.set_airbourne(context, state);
+                ActionResult::set_action(PlayerAction::Fall)
             }
         } else {
             None
@@ -1812,7 +2112,7 @@ impl Player {
             if self.relative_f(context.input[0].stick_x) > 0.3
                 && context.input[0].stick_x.abs() > context.input[0].stick_y.abs() - 0.1
             {
-                if context.input.z.press && self.get_held_item(context.entities).is_some() {
+                if context.input.z.press && context.held_item().is_some() {
                     ActionResult::set_action(PlayerAction::ItemThrowAirF)
                 } else {
                     ActionResult::set_action(PlayerAction::Fair)
@@ -1820,25 +2120,25 @@ impl Player {
             } else if self.relative_f(context.input[0].stick_x) < -0.3
                 && context.input[0].stick_x.abs() > context.input[0].stick_y.abs() - 0.1
             {
-                if context.input.z.press && self.get_held_item(context.entities).is_some() {
+                if context.input.z.press && context.held_item().is_some() {
                     ActionResult::set_action(PlayerAction::ItemThrowAirB)
                 } else {
                     ActionResult::set_action(PlayerAction::Bair)
                 }
             } else if context.input[0].stick_y < -0.3 {
-                if context.input.z.press && self.get_held_item(context.entities).is_some() {
+                if context.input.z.press && context.held_item().is_some() {
                     ActionResult::set_action(PlayerAction::ItemThrowAirD)
                 } else {
                     ActionResult::set_action(PlayerAction::Dair)
                 }
             } else if context.input[0].stick_y > 0.3 {
-                if context.input.z.press && self.get_held_item(context.entities).is_some() {
+                if context.input.z.press && context.held_item().is_some() {
                     ActionResult::set_action(PlayerAction::ItemThrowAirU)
                 } else {
                     ActionResult::set_action(PlayerAction::Uair)
                 }
-            } else if context.input.z.press && self.get_held_item(context.entities).is_some() {
-                if let Some(item) = self.get_held_item(context.entities) {
+            } else if context.input.z.press && context.held_item().is_some() {
+                if let Some(item) = context.held_item() {
                     context.messages.push(Message {
                         recipient: item,
                         contents: MessageContents::Item(MessageItem::Dropped),
@@ -1852,7 +2152,7 @@ impl Player {
             && self.relative_f(context.input[1].c_stick_x) < 0.3
             && context.input[0].c_stick_x.abs() > context.input[0].c_stick_y.abs() - 0.1
         {
-            if self.get_held_item(context.entities).is_some() {
+            if context.held_item().is_some() {
                 ActionResult::set_action(PlayerAction::ItemThrowAirF)
             } else {
                 ActionResult::set_action(PlayerAction::Fair)
@@ -1861,19 +2161,19 @@ impl Player {
             && self.relative_f(context.input[1].c_stick_x) > -0.3
             && context.input[0].c_stick_x.abs() > context.input[0].c_stick_y.abs() - 0.1
         {
-            if self.get_held_item(context.entities).is_some() {
+            if context.held_item().is_some() {
                 ActionResult::set_action(PlayerAction::ItemThrowAirB)
             } else {
                 ActionResult::set_action(PlayerAction::Bair)
             }
         } else if context.input[0].c_stick_y < -0.3 && context.input[1].c_stick_y > -0.3 {
-            if self.get_held_item(context.entities).is_some() {
+            if context.held_item().is_some() {
                 ActionResult::set_action(PlayerAction::ItemThrowAirD)
             } else {
                 ActionResult::set_action(PlayerAction::Dair)
             }
         } else if context.input[0].c_stick_y >= 0.3 && context.input[1].c_stick_y < 0.3 {
-            if self.get_held_item(context.entities).is_some() {
+            if context.held_item().is_some() {
                 ActionResult::set_action(PlayerAction::ItemThrowAirU)
             } else {
                 ActionResult::set_action(PlayerAction::Uair)
@@ -1888,24 +2188,24 @@ impl Player {
             if self.relative_f(context.input[0].stick_x) > 0.3
                 && context.input[0].stick_x.abs() - context.input[0].stick_y.abs() > -0.05
             {
-                if self.get_held_item(context.entities).is_some() {
+                if context.held_item().is_some() {
                     ActionResult::set_action(PlayerAction::ItemThrowF)
                 } else {
                     ActionResult::set_action(PlayerAction::Ftilt)
                 }
             } else if context.input[0].stick_y < -0.3 {
-                if self.get_held_item(context.entities).is_some() {
+                if context.held_item().is_some() {
                     ActionResult::set_action(PlayerAction::ItemThrowD)
                 } else {
                     ActionResult::set_action(PlayerAction::Dtilt)
                 }
             } else if context.input[0].stick_y > 0.3 {
-                if self.get_held_item(context.entities).is_some() {
+                if context.held_item().is_some() {
                     ActionResult::set_action(PlayerAction::ItemThrowU)
                 } else {
                     ActionResult::set_action(PlayerAction::Utilt)
                 }
-            } else if self.get_held_item(context.entities).is_some() {
+            } else if context.held_item().is_some() {
                 ActionResult::set_action(PlayerAction::ItemThrowF)
             } else {
                 ActionResult::set_action(PlayerAction::Jab)
@@ -1947,37 +2247,98 @@ impl Player {
         }
     }
 
+    /// Pressing A and B together uses the super when the meter is full, emptying it
+    fn check_super(&mut self, context: &StepContext) -> Option<ActionResult> {
+        let has_super = context.entity_def.fighter().map_or(false, |x| x.has_super);
+        let input = &context.input;
+        if has_super
+            && self.meter.map_or(false, |x| x >= METER_MAX)
+            && ((input.a.press && input.b.value) || (input.b.press && input.a.value))
+        {
+            self.meter = Some(0.0);
+            ActionResult::set_action(PlayerAction::Super)
+        } else {
+            None
+        }
+    }
+
+    fn super_action(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        if self.body.is_airbourne() {
+            self.air_drift(context);
+            self.fastfall_action(context);
+        } else {
+            self.apply_friction(context.entity_def, state);
+        }
+        None
+    }
+
+    fn charge_meter(&mut self, amount: f32) {
+        if let Some(meter) = &mut self.meter {
+            *meter = (*meter + amount).min(METER_MAX);
+        }
+    }
+
     fn check_special_ground(&mut self, context: &StepContext) -> Option<ActionResult> {
-        if context.input.b.press {
-            if context.input[0].stick_x.abs() > 0.3 {
-                self.body.face_right = context.input.stick_x.value > 0.0;
-                ActionResult::set_action(PlayerAction::SspecialGroundStart)
-            } else if context.input[0].stick_y < -0.3 {
-                ActionResult::set_action(PlayerAction::DspecialGroundStart)
-            } else if context.input[0].stick_y > 0.3 {
-                ActionResult::set_action(PlayerAction::UspecialGroundStart)
-            } else {
+        self.check_special_ground_except(context, &[])
+    }
+
+    /// Specials in `disallowed` are ignored, leaving the B press to be handled by later checks
+    fn check_special_ground_except(
+        &mut self,
+        context: &StepContext,
+        disallowed: &[SpecialDirection],
+    ) -> Option<ActionResult> {
+        match self.special_direction(context, disallowed)? {
+            SpecialDirection::Side => ActionResult::set_action(PlayerAction::SspecialGroundStart),
+            SpecialDirection::Down => ActionResult::set_action(PlayerAction::DspecialGroundStart),
+            SpecialDirection::Up => ActionResult::set_action(PlayerAction::UspecialGroundStart),
+            SpecialDirection::Neutral => {
                 ActionResult::set_action(PlayerAction::NspecialGroundStart)
             }
-        } else {
-            None
         }
     }
 
     fn check_special_air(&mut self, context: &StepContext) -> Option<ActionResult> {
-        if context.input.b.press {
-            if context.input[0].stick_x.abs() > 0.3 {
-                self.body.face_right = context.input.stick_x.value > 0.0;
-                ActionResult::set_action(PlayerAction::SspecialAirStart)
-            } else if context.input[0].stick_y < -0.3 {
-                ActionResult::set_action(PlayerAction::DspecialAirStart)
-            } else if context.input[0].stick_y > 0.3 {
-                ActionResult::set_action(PlayerAction::UspecialAirStart)
-            } else {
-                ActionResult::set_action(PlayerAction::NspecialAirStart)
-            }
+        match self.special_direction(context, &[])? {
+            SpecialDirection::Side => ActionResult::set_action(PlayerAction::SspecialAirStart),
+            SpecialDirection::Down => ActionResult::set_action(PlayerAction::DspecialAirStart),
+            SpecialDirection::Up => ActionResult::set_action(PlayerAction::UspecialAirStart),
+            SpecialDirection::Neutral => ActionResult::set_action(PlayerAction::NspecialAirStart),
+        }
+    }
+
+    /// Returns the direction of the special the player is inputting.
+    /// A side special also turns the player to face the direction of the stick.
+    fn special_direction(
+        &mut self,
+        context: &StepContext,
+        disallowed: &[SpecialDirection],
+    ) -> Option<SpecialDirection> {
+        if !context.input.b.press {
+            return None;
+        }
+
+        let direction = if context.input[0].stick_x.abs() > 0.3 {
+            SpecialDirection::Side
+        } else if context.input[0].stick_y < -0.3 {
+            SpecialDirection::Down
+        } else if context.input[0].stick_y > 0.3 {
+            SpecialDirection::Up
         } else {
+            SpecialDirection::Neutral
+        };
+
+        if disallowed.contains(&direction) {
             None
+        } else {
+            if let SpecialDirection::Side = direction {
+                self.body.face_right = context.input.stick_x.value > 0.0;
+            }
+            Some(direction)
         }
     }
 
@@ -2131,6 +2492,7 @@ impl Player {
                 PlayerAction::LedgeIdle
             }
             Some(PlayerAction::JumpSquat) => {
+                let (surface_vel_x, surface_vel_y) = self.body.surface_vel(context.surfaces);
                 self.set_airbourne(context, state);
                 if let &mut Location::Airbourne { ref mut y, .. } = &mut self.body.location {
                     *y += 0.0001;
@@ -2154,6 +2516,8 @@ impl Player {
                 if self.body.x_vel.abs() > context.entity_def.jump_x_term_vel {
                     self.body.x_vel = context.entity_def.jump_x_term_vel * self.body.x_vel.signum();
                 }
+                self.body.x_vel += surface_vel_x;
+                self.body.y_vel += surface_vel_y;
 
                 if self.relative_f(context.input[2].stick_x) >= -0.3 {
                     PlayerAction::JumpF
@@ -2216,6 +2580,9 @@ impl Player {
             Some(PlayerAction::GrabbedIdleAir) => PlayerAction::GrabbedIdleAir,
             Some(PlayerAction::GrabbedIdle)    => PlayerAction::GrabbedIdle,
             Some(PlayerAction::GrabbedEnd)     => PlayerAction::Idle,
+            Some(PlayerAction::GrabbingWalk)      => PlayerAction::GrabbingWalk,
+            Some(PlayerAction::GrabbingJumpSquat) => self.grabbing_jump(context, state),
+            Some(PlayerAction::GrabbingJump)      => PlayerAction::GrabbingJump,
 
             // Throws
             Some(PlayerAction::Uthrow) => PlayerAction::Idle,
@@ -2258,6 +2625,9 @@ impl Player {
             Some(PlayerAction::SspecialAirStart) => PlayerAction::Fall,
             Some(PlayerAction::NspecialAirStart) => PlayerAction::Fall,
 
+            // Super
+            Some(PlayerAction::Super) => if self.body.is_airbourne() { PlayerAction::Fall } else { PlayerAction::Idle },
+
             // Taunts
             Some(PlayerAction::TauntUp)    => PlayerAction::Idle,
             Some(PlayerAction::TauntDown)  => PlayerAction::Idle,
@@ -2300,15 +2670,15 @@ impl Player {
     }
 
     fn specialfall_action(&mut self, context: &mut StepContext) -> Option<ActionResult> {
-        self.fall_action(context.entity_def);
+        self.fall_action(context);
         self.air_drift(context);
         None
     }
 
-    fn fall_action(&mut self, entity_def: &EntityDef) {
-        self.body.y_vel += entity_def.gravity;
-        if self.body.y_vel < entity_def.terminal_vel {
-            self.body.y_vel = entity_def.terminal_vel;
+    fn fall_action(&mut self, context: &StepContext) {
+        self.body.y_vel += self.body.gravity(context);
+        if self.body.y_vel < context.entity_def.terminal_vel {
+            self.body.y_vel = context.entity_def.terminal_vel;
         }
     }
 
@@ -2321,7 +2691,7 @@ impl Player {
                 self.fastfalled = true;
                 self.body.y_vel = context.entity_def.fastfall_terminal_vel;
             } else {
-                self.body.y_vel += context.entity_def.gravity;
+                self.body.y_vel += self.body.gravity(context);
                 if self.body.y_vel < context.entity_def.terminal_vel {
                     self.body.y_vel = context.entity_def.terminal_vel;
                 }
@@ -2330,24 +2700,6 @@ impl Player {
         None
     }
 
-    pub fn get_held_item(&self, entities: &Entities) -> Option<EntityKey> {
-        for (key, entity) in entities.iter() {
-            if let EntityType::Item(item) = &entity.ty {
-                if let Location::ItemHeldByPlayer(player_entity_key) = item.body.location {
-                    if let Some(check_entity) = entities.get(player_entity_key) {
-                        if let Some(check_player) = &check_entity.ty.get_player() {
-                            if check_player.id == self.id {
-                                return Some(key);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        None
-    }
-
     pub fn get_held_fighter(&self, entities: &Entities) -> Option<EntityKey> {
         for (key, entity) in entities.iter() {
             if let EntityType::Fighter(fighter) = &entity.ty {
@@ -2393,14 +2745,30 @@ impl Player {
         match self.body.physics_step(context, state, fighter_frame) {
             Some(PhysicsResult::Fall) => {
                 self.fastfalled = false;
-                ActionResult::set_action(PlayerAction::Fall)
+                if let Some(PlayerAction::GrabbingWalk) = state.get_action() {
+                    ActionResult::set_action(PlayerAction::GrabbingJump)
+                } else {
+                    ActionResult::set_action(PlayerAction::Fall)
+                }
             }
             Some(PhysicsResult::Land) => {
                 self.hitstun = 0.0;
                 self.land(context, state)
             }
-            Some(PhysicsResult::Teeter) => ActionResult::set_action(PlayerAction::Teeter),
-            Some(PhysicsResult::LedgeGrab) => {
+            Some(PhysicsResult::Teeter) => {
+                if let Some(PlayerAction::GrabbingWalk) = state.get_action() {
+                    ActionResult::set_action(PlayerAction::GrabbingIdle)
+                } else {
+                    ActionResult::set_action(PlayerAction::Teeter)
+                }
+            }
+            Some(PhysicsResult::LedgeGrab { trumped }) => {
+                if let Some(recipient) = trumped {
+                    context.messages.push(Message {
+                        recipient,
+                        contents: MessageContents::Player(MessagePlayer::LedgeTrumped),
+                    });
+                }
                 self.fastfalled = false;
                 self.air_jumps_left = context
                     .entity_def
@@ -2408,10 +2776,38 @@ impl Player {
                     .map(|x| x.air_jumps)
                     .unwrap_or(1);
                 self.hit_by = None;
+                self.hit_by_action = None;
                 ActionResult::set_action(PlayerAction::LedgeGrab)
             }
-            Some(PhysicsResult::OutOfBounds) => self.die(context, game_frame, goal),
-            None => None,
+            Some(PhysicsResult::OutOfBounds) => self.die(context, state, game_frame, goal),
+            None => self.hazard_step(context, state),
+        }
+    }
+
+    fn hazard_step(
+        &mut self,
+        context: &mut StepContext,
+        state: &ActionState,
+    ) -> Option<ActionResult> {
+        let hazard = self.body.hazard(context.surfaces)?.clone();
+        if context.entities[context.entity_key].is_intangible(context.entity_defs) {
+            return None;
+        }
+
+        let action_frame =
+            state.get_entity_frame(&context.entity_defs[state.entity_def_key.as_ref()]);
+        let kb_vel = self
+            .body
+            .hazard_launch(context, state, action_frame, &hazard);
+        self.fastfalled = false;
+        self.hitstun = kb_vel * HAZARD_HITSTUN_PER_KNOCKBACK;
+        self.hit_by = None;
+        self.hit_by_action = None;
+
+        if kb_vel > DAMAGE_FLY_MIN_KNOCKBACK {
+            ActionResult::set_action(PlayerAction::DamageFly)
+        } else {
+            ActionResult::set_action(PlayerAction::Damage)
         }
     }
 
@@ -2489,7 +2885,7 @@ impl Player {
         self.land_frame_skip = match action {
             Some(_)
                 if action.as_ref().map_or(false, |x| x.is_air_attack())
-                    && self.lcancel_timer > 0 =>
+                    && (self.lcancel_timer > 0 || self.assists.auto_lcancel) =>
             {
                 1
             }
@@ -2511,6 +2907,7 @@ impl Player {
             .map(|x| x.air_jumps)
             .unwrap_or(1);
         self.hit_by = None;
+        self.hit_by_action = None;
 
         ActionResult::set_action(match action {
             Some(PlayerAction::Uair) => PlayerAction::UairLand,
@@ -2519,8 +2916,9 @@ impl Player {
             Some(PlayerAction::Bair) => PlayerAction::BairLand,
             Some(PlayerAction::Nair) => PlayerAction::NairLand,
             Some(PlayerAction::ShieldBreakFall) => PlayerAction::ShieldBreakGetup,
+            Some(PlayerAction::GrabbingJump) => PlayerAction::GrabbingIdle,
             Some(PlayerAction::DamageFly) | Some(PlayerAction::DamageFall) => {
-                if self.tech_timer.is_active() {
+                if self.tech_timer.is_active() || self.assists.auto_tech {
                     if self.relative_f(context.input[0].stick_x) > 0.5 {
                         PlayerAction::TechF
                     } else if self.relative_f(context.input[0].stick_x) < -0.5 {
@@ -2553,25 +2951,22 @@ impl Player {
     fn die(
         &mut self,
         context: &mut StepContext,
+        state: &ActionState,
         game_frame: usize,
         goal: Goal,
     ) -> Option<ActionResult> {
+        let x = self.bps_xy(context, state).0;
         context
             .audio
-            .play_sound_effect(context.entity_def, SfxType::Die);
-        self.body = if context.stage.respawn_points.len() == 0 {
-            Body::new(Location::Airbourne { x: 0.0, y: 0.0 }, true)
-        } else {
-            let respawn =
-                &context.stage.respawn_points[self.id % context.stage.respawn_points.len()];
-            Body::new(
-                Location::Airbourne {
-                    x: respawn.x,
-                    y: respawn.y,
-                },
-                respawn.face_right,
-            )
-        };
+            .play_sound_effect(context.entity_def, SfxType::Die, x);
+        self.result.deaths.push(DeathRecord {
+            player: self.hit_by,
+            action: self.hit_by_action.clone(),
+            damage: self.body.damage,
+            frame: game_frame,
+        });
+
+        self.body = Player::respawn_body(context.stage, self.id);
         self.air_jumps_left = context
             .entity_def
             .fighter()
@@ -2580,11 +2975,6 @@ impl Player {
         self.fastfalled = false;
         self.hitstun = 0.0;
 
-        self.result.deaths.push(DeathRecord {
-            player: self.hit_by,
-            frame: game_frame,
-        });
-
         match goal {
             Goal::LastManStanding => {
                 if let Some(mut stocks) = self.stocks {
@@ -2615,6 +3005,38 @@ impl Player {
         if debug.frame {
             lines.push(format!("Entity: {:?}  shield HP: {:.5}  hitstun: {:.5}  tech timer: {:?}  lcancel timer: {}",
                 index, self.shield_hp, self.hitstun, self.tech_timer, self.lcancel_timer));
+
+            let stale: Vec<String> = self
+                .stale_queue
+                .hits()
+                .iter()
+                .map(|x| format!("{} #{}", x.action, x.hitbox_id))
+                .collect();
+            lines.push(format!(
+                "Entity: {:?}  stale queue: [{}]",
+                index,
+                stale.join(", ")
+            ));
+        }
+
+        if debug.ledge_timers {
+            let regrab: Vec<String> = self
+                .body
+                .ledge_regrab_timers
+                .iter()
+                .map(|x| {
+                    let side = if x.face_right { "left" } else { "right" };
+                    format!("{} {}: {}", x.platform_i, side, x.frames)
+                })
+                .collect();
+            lines.push(format!(
+                "Entity: {:?}  ledge idle: {}/{}  intangible: {}  regrab lockouts: [{}]",
+                index,
+                self.ledge_idle_timer,
+                LEDGE_IDLE_MAX_FRAMES,
+                self.intangible_timer,
+                regrab.join(", ")
+            ));
         }
 
         if debug.input {
@@ -2652,26 +3074,93 @@ impl Player {
         result
     }
 
-    pub fn hit_particles(&mut self, point: (f32, f32), hitbox: &HitBox) {
+    /// The team color unless the player chose their own.
+    /// Particles are simulated with the standard palette so their state is identical for netplay peers using other palettes.
+    /// `Entity::particles` swaps it for the configured palette when rendering.
+    fn particle_color(&self) -> [f32; 3] {
+        self.effect_colors.particles.map_or_else(
+            || graphics::get_team_color3(&TeamPalette::Standard, self.team),
+            |x| x.to_f32(),
+        )
+    }
+
+    fn shield_color(&self, team_palette: &TeamPalette) -> [f32; 3] {
+        self.effect_colors.shield.map_or_else(
+            || graphics::get_team_color3(team_palette, self.team),
+            |x| x.to_f32(),
+        )
+    }
+
+    /// A flash at the point of impact, followed by sparks that depend on the hitbox's `HitSpark`
+    pub fn hit_particles(&mut self, context: &mut StepContext, point: (f32, f32), hitbox: &HitBox) {
+        let angle = hitbox.angle.to_radians();
+        let color = match hitbox.spark {
+            HitSpark::Blunt | HitSpark::Slash => [0.5, 0.5, 0.5],
+            HitSpark::Electric => [1.0, 1.0, 0.3],
+            HitSpark::Flame => [1.0, 0.45, 0.1],
+        };
         self.particles.push(Particle {
-            color: graphics::get_team_color3(self.team),
+            color,
             counter: 0,
             counter_max: 2,
             x: point.0,
             y: point.1,
             z: 0.0,
-            angle: hitbox.angle.to_radians(),
+            angle,
             p_type: ParticleType::Hit {
                 knockback: hitbox.bkb + hitbox.kbg * 70.0, // TODO: get actual knockback
                 damage: hitbox.damage,                     // TODO: get actual damage
+                spark: hitbox.spark,
             },
         });
+
+        // (number of sparks, frames they last, speed)
+        let (num, counter_max, speed) = match hitbox.spark {
+            HitSpark::Blunt => (0, 0, 0.0),
+            HitSpark::Slash => (3, 12, 1.5),
+            HitSpark::Electric => (8, 8, 1.0),
+            HitSpark::Flame => (6, 30, 0.3),
+        };
+        for _ in 0..num {
+            let spark_angle = match hitbox.spark {
+                // slashes follow the direction of the attack
+                HitSpark::Slash => angle + context.rng.gen_range(-0.15..=0.15),
+                _ => context.rng.gen_range(0.0..=2.0 * PI),
+            };
+            let vec_mult = speed * context.rng.gen_range(0.5..=1.0);
+            let y_vel = match hitbox.spark {
+                // flames rise
+                HitSpark::Flame => context.rng.gen_range(0.1..=0.3),
+                _ => spark_angle.sin() * vec_mult,
+            };
+            let z = context.rng.gen_range(-1.0..=1.0);
+            self.particles.push(Particle {
+                color: if let HitSpark::Slash = hitbox.spark {
+                    [1.0, 1.0, 1.0]
+                } else {
+                    color
+                },
+                counter: 0,
+                counter_max,
+                x: point.0,
+                y: point.1,
+                z,
+                angle: spark_angle,
+                p_type: ParticleType::Spark {
+                    x_vel: spark_angle.cos() * vec_mult,
+                    y_vel,
+                    z_vel: context.rng.gen_range(0.0..=0.2) * z.signum(),
+                    size: context.rng.gen_range(0.5..=1.5),
+                    angle_vel: context.rng.gen_range(0.0..=1.0),
+                },
+            });
+        }
     }
 
     pub fn air_jump_particles(&mut self, context: &mut StepContext, state: &ActionState) {
         let (x, y) = self.bps_xy(context, state);
         self.particles.push(Particle {
-            color: graphics::get_team_color3(self.team),
+            color: self.particle_color(),
             counter: 0,
             counter_max: 40,
             x,
@@ -2699,7 +3188,7 @@ impl Player {
         for _ in 0..num {
             let z = context.rng.gen_range(-1.0..=1.0);
             self.particles.push(Particle {
-                color: graphics::get_team_color3(self.team),
+                color: self.particle_color(),
                 counter: 0,
                 counter_max: 30,
                 x,
@@ -2738,7 +3227,7 @@ impl Player {
         {
             [1.0, 1.0, 1.0]
         } else {
-            graphics::get_team_color3(self.team)
+            self.particle_color()
         };
 
         for _ in 0..num {
@@ -2780,7 +3269,7 @@ impl Player {
         for _ in 0..num {
             let z = context.rng.gen_range(-6.0..=6.0);
             self.particles.push(Particle {
-                color: graphics::get_team_color3(self.team),
+                color: self.particle_color(),
                 counter: 0,
                 counter_max: 40,
                 x: x + x_offset,
@@ -2808,9 +3297,10 @@ impl Player {
         fighters: &KeyedContextVec<EntityDef>,
         surfaces: &[Surface],
         state: &ActionState,
+        team_palette: &TeamPalette,
     ) -> RenderPlayer {
         let shield = if self.is_shielding(state) {
-            let fighter_color = graphics::get_team_color3(self.team);
+            let fighter_color = self.shield_color(team_palette);
             let fighter = &fighters[state.entity_def_key.as_ref()];
 
             if let &Some(ref shield) = &fighter.shield {
@@ -2835,11 +3325,68 @@ impl Player {
         };
 
         RenderPlayer {
+            id: self.id,
             team: self.team,
             damage: self.body.damage,
             stocks: self.stocks,
+            meter: self.meter,
             shield,
-        }
+            status_effects: self.body.status_effects.render(),
+            respawn_frames_remaining: match state.get_action() {
+                Some(PlayerAction::ReSpawnIdle) => Some(
+                    self.respawn_frames
+                        .saturating_sub(state.frame_no_restart as u64),
+                ),
+                _ => None,
+            },
+            hit_flash: state.hitlag.hit_flash(),
+        }
+    }
+
+    /// Compact bars stacked above the fighter showing the ledge timers, each bar shrinks as its timer runs out.
+    /// *   yellow: frames left before letting go of the held ledge
+    /// *   white: intangibility remaining
+    /// *   red: one bar for each ledge that cannot be regrabbed yet
+    pub fn ledge_timer_rects(&self, (x, y): (f32, f32)) -> Vec<RenderRect> {
+        let mut bars = vec![];
+        if self.body.is_ledge() {
+            let remaining = LEDGE_IDLE_MAX_FRAMES.saturating_sub(self.ledge_idle_timer);
+            bars.push((
+                remaining as f32 / LEDGE_IDLE_MAX_FRAMES as f32,
+                [1.0, 1.0, 0.0, 1.0],
+            ));
+        }
+        if self.intangible_timer > 0 {
+            bars.push((
+                (self.intangible_timer as f32 / LEDGE_TRUMP_INTANGIBLE_FRAMES as f32).min(1.0),
+                [1.0, 1.0, 1.0, 1.0],
+            ));
+        }
+        for timer in &self.body.ledge_regrab_timers {
+            bars.push((
+                timer.frames as f32 / LEDGE_REGRAB_FRAMES as f32,
+                [1.0, 0.0, 0.0, 1.0],
+            ));
+        }
+
+        let width = 12.0;
+        let height = 1.5;
+        let bottom = y + self.body.ecb.top + 4.0;
+        bars.into_iter()
+            .enumerate()
+            .map(|(i, (fraction, color))| {
+                let y1 = bottom + i as f32 * height * 2.0;
+                RenderRect {
+                    rect: Rect {
+                        x1: x - width / 2.0,
+                        y1,
+                        x2: x - width / 2.0 + width * fraction,
+                        y2: y1 + height,
+                    },
+                    color,
+                }
+            })
+            .collect()
     }
 
     pub fn vector_arrows(&self, debug: &DebugEntity) -> Vec<VectorArrow> {
@@ -2897,6 +3444,10 @@ impl Player {
                 kbg,
                 entity_atk_i,
             } => {
+                // throws always release, regardless of how weak the knockback is
+                if self.body.is_grabbed() {
+                    self.set_airbourne(context, state);
+                }
                 let hitbox = HitBox {
                     shield_damage: 0.0,
                     damage: *damage,
@@ -2907,35 +3458,41 @@ impl Player {
                     enable_clang: false,
                     enable_rebound: false,
                     effect: HitboxEffect::None,
+                    spark: HitSpark::Blunt,
                     enable_reverse_hit: false,
+                    sfx: None,
                 };
 
                 let hurtbox = HurtBox::default();
-                self.launch(context, state, &hitbox, &hurtbox, *entity_atk_i)
+                self.launch(context, state, &hitbox, None, &hurtbox, *entity_atk_i)
             }
             MessagePlayer::Released => None,
+            MessagePlayer::LedgeTrumped => {
+                if self.body.is_ledge() {
+                    self.set_airbourne(context, state);
+                    self.body.x_vel = self.relative_f(-0.5);
+                    self.body.y_vel = 0.5;
+                    self.intangible_timer = LEDGE_TRUMP_INTANGIBLE_FRAMES;
+                    ActionResult::set_action(PlayerAction::DamageFall)
+                } else {
+                    None
+                }
+            }
         }
     }
 
-    pub fn send_thrown_message(
-        &self,
-        context: &mut StepContext,
-        angle: f32,
-        damage: f32,
-        bkb: f32,
-        kbg: f32,
-    ) {
+    pub fn send_thrown_message(&self, context: &mut StepContext, throw: &Throw) {
         if let Some(recipient) = self.get_held_fighter(context.entities) {
             let angle = if !self.body.face_right {
-                180.0 - angle
+                180.0 - throw.angle
             } else {
-                angle
+                throw.angle
             };
             let message = MessagePlayer::Thrown {
                 angle,
-                damage,
-                bkb,
-                kbg,
+                damage: throw.damage,
+                bkb: throw.bkb,
+                kbg: throw.kbg,
                 entity_atk_i: context.entity_key,
             };
 
@@ -2947,6 +3504,14 @@ impl Player {
     }
 }
 
+#[derive(PartialEq)]
+enum SpecialDirection {
+    Neutral,
+    Side,
+    Up,
+    Down,
+}
+
 enum JumpResult {
     Button,
     Stick,
@@ -2963,10 +3528,17 @@ impl JumpResult {
 }
 
 pub struct RenderPlayer {
+    pub id: usize,
     pub team: usize,
     pub damage: f32,
     pub stocks: Option<u64>,
+    pub meter: Option<f32>,
     pub shield: Option<RenderShield>,
+    pub status_effects: Vec<RenderStatusEffect>,
+    /// Only set while waiting on the respawn platform
+    pub respawn_frames_remaining: Option<u64>,
+    /// 0.0 to 1.0, how white the fighter is drawn after being struck
+    pub hit_flash: f32,
 }
 
 pub struct RenderShield {
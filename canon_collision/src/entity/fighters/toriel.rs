@@ -44,12 +44,6 @@ impl FighterTrait for Toriel {
             Some(TorielAction::NspecialGroundStart) => self.n_special_ground_start_action(context, state),
             Some(TorielAction::NspecialAirStart)    => self.n_special_air_start_action(context, state),
 
-            // Throws
-            Some(TorielAction::Uthrow) => self.u_throw_action(context, state),
-            Some(TorielAction::Dthrow) => self.d_throw_action(context, state),
-            Some(TorielAction::Fthrow) => self.f_throw_action(context, state),
-            Some(TorielAction::Bthrow) => self.b_throw_action(context, state),
-
             None => self.player.frame_step(context, state)
         }
     }
@@ -68,12 +62,6 @@ impl FighterTrait for Toriel {
             Some(TorielAction::SspecialAirStart) => ActionResult::set_action(PlayerAction::Fall),
             Some(TorielAction::NspecialAirStart) => ActionResult::set_action(PlayerAction::Fall),
 
-            // Throws
-            Some(TorielAction::Uthrow) => ActionResult::set_action(PlayerAction::Idle),
-            Some(TorielAction::Dthrow) => ActionResult::set_action(PlayerAction::Idle),
-            Some(TorielAction::Fthrow) => ActionResult::set_action(PlayerAction::Idle),
-            Some(TorielAction::Bthrow) => ActionResult::set_action(PlayerAction::Idle),
-
             None => self.player.action_expired(context, state),
         }
     }
@@ -143,7 +131,7 @@ impl Toriel {
                 .get(oven_key)
                 .and_then(|x| x.state.get_action())
             {
-                if self.player.get_held_item(context.entities).is_none() && state.frame == 59 {
+                if context.held_item().is_none() && state.frame == 59 {
                     context.new_entities.push(Entity {
                         ty: EntityType::Item(Item {
                             owner_id: Some(self.player.id),
@@ -251,71 +239,6 @@ impl Toriel {
         None
     }
 
-    fn u_throw_action(
-        &mut self,
-        context: &mut StepContext,
-        state: &ActionState,
-    ) -> Option<ActionResult> {
-        if state.frame == 5 {
-            // TODO: lets make this a struct instead of commenting the args.
-            self.player.send_thrown_message(
-                context, 85.0, // angle
-                5.0,  // damage
-                80.0, // bkb
-                1.1,  // kbg
-            );
-        }
-        None
-    }
-
-    fn d_throw_action(
-        &mut self,
-        context: &mut StepContext,
-        state: &ActionState,
-    ) -> Option<ActionResult> {
-        if state.frame == 5 {
-            self.player.send_thrown_message(
-                context, -90.0, // angle
-                5.0,   // damage
-                80.0,  // bkb
-                1.1,   // kbg
-            );
-        }
-        None
-    }
-
-    fn f_throw_action(
-        &mut self,
-        context: &mut StepContext,
-        state: &ActionState,
-    ) -> Option<ActionResult> {
-        if state.frame == 5 {
-            self.player.send_thrown_message(
-                context, 30.0, // angle
-                5.0,  // damage
-                80.0, // bkb
-                1.1,  // kbg
-            );
-        }
-        None
-    }
-
-    fn b_throw_action(
-        &mut self,
-        context: &mut StepContext,
-        state: &ActionState,
-    ) -> Option<ActionResult> {
-        if state.frame == 5 {
-            self.player.send_thrown_message(
-                context, 170.0, // angle
-                5.0,   // damage
-                80.0,  // bkb
-                1.1,   // kbg
-            );
-        }
-        None
-    }
-
     fn relative_f(&self, input: f32) -> f32 {
         self.player.body.relative_f(input)
     }
@@ -1,10 +1,11 @@
 use crate::collision::collision_box::CollisionResult;
+use crate::entity::assist::Assist;
 use crate::entity::components::action_state::ActionState;
 use crate::entity::components::body::{Body, Location, PhysicsResult};
 use crate::entity::{ActionResult, Entities, EntityKey, StepContext};
 
 use canon_collision_lib::entity_def::item::ItemAction;
-use canon_collision_lib::entity_def::EntityDef;
+use canon_collision_lib::entity_def::{EntityDef, EntityDefType};
 
 use cgmath::{Angle, Quaternion, Rad, Rotation3, Zero};
 use treeflection::KeyedContextVec;
@@ -48,6 +49,24 @@ impl Item {
         context: &mut StepContext,
         state: &ActionState,
     ) -> Option<ActionResult> {
+        // Using an assist item summons its assist in place of the item
+        if let (Some(ItemAction::Thrown), EntityDefType::AssistItem(assist_item)) =
+            (state.get_action(), &context.entity_def.ty)
+        {
+            let (x, y) = self.bps_xy(context, state);
+            let face_right = self.body.x_vel >= 0.0;
+            Assist::summon(
+                context,
+                &assist_item.assist,
+                self.owner_id,
+                x,
+                y,
+                face_right,
+            );
+            context.delete_self = true;
+            return None;
+        }
+
         if let Some(action) = state.get_action() {
             match action {
                 ItemAction::Held => {}
@@ -57,7 +76,7 @@ impl Item {
                 }
 
                 ItemAction::Thrown | ItemAction::Fall | ItemAction::Dropped => {
-                    self.body.y_vel += context.entity_def.gravity;
+                    self.body.y_vel += self.body.gravity(context);
                     if self.body.y_vel < context.entity_def.terminal_vel {
                         self.body.y_vel = context.entity_def.terminal_vel;
                     }
@@ -150,6 +169,7 @@ impl Item {
                 }
                 &CollisionResult::HitDef {
                     ref hitbox,
+                    hitbox_id,
                     ref hurtbox,
                     entity_atk_i,
                 } => {
@@ -161,6 +181,7 @@ impl Item {
                         state,
                         action_frame,
                         hitbox,
+                        Some(hitbox_id),
                         hurtbox,
                         entity_atk_i,
                         kb_vel_mult,
@@ -1,30 +1,36 @@
+pub(crate) mod assist;
 pub(crate) mod components;
 pub(crate) mod fighters;
 pub(crate) mod item;
 pub(crate) mod projectile;
+pub(crate) mod scripting;
 pub(crate) mod toriel_fireball;
 pub(crate) mod toriel_oven;
 
 use std::collections::HashSet;
 use std::f32::consts::PI;
 
+use assist::Assist;
 use components::action_state::{ActionState, Hitlag};
-use components::body::Body;
+use components::body::{Body, LedgeLogic};
 use fighters::player::{MessagePlayer, Player, RenderPlayer};
 use fighters::Fighter;
 use item::{Item, MessageItem};
 use projectile::Projectile;
+use scripting::Scripts;
 use toriel_fireball::TorielFireball;
 use toriel_oven::{MessageTorielOven, TorielOven};
 
-use crate::audio::sfx::{HitBoxSfx, SfxType};
+use crate::audio::sfx::SfxType;
 use crate::audio::Audio;
 use crate::collision::collision_box::CollisionResult;
 use crate::graphics;
 use crate::particle::Particle;
-use crate::rules::Goal;
+use crate::rules::{Goal, Modifiers};
 
-use canon_collision_lib::entity_def::{ActionFrame, CollisionBoxRole, EntityDef, ECB};
+use canon_collision_lib::config::TeamPalette;
+use canon_collision_lib::entity_def::{ActionFrame, CollisionBoxRole, Costume, EntityDef, ECB};
+use canon_collision_lib::geometry;
 use canon_collision_lib::geometry::Rect;
 use canon_collision_lib::input::state::PlayerInput;
 use canon_collision_lib::stage::{Stage, Surface};
@@ -43,6 +49,7 @@ pub enum EntityType {
     Fighter(Fighter),
     Projectile(Projectile),
     Item(Item),
+    Assist(Assist),
     TorielFireball(TorielFireball),
     TorielOven(TorielOven),
 }
@@ -54,6 +61,17 @@ impl EntityType {
             _ => None,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntityType::Fighter(_) => "fighter",
+            EntityType::Item(_) => "item",
+            EntityType::Projectile(_) => "projectile",
+            EntityType::Assist(_) => "assist",
+            EntityType::TorielFireball(_) => "toriel fireball",
+            EntityType::TorielOven(_) => "toriel oven",
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -77,10 +95,15 @@ impl Entity {
         self.process_action_result(context, action_result);
     }
 
-    pub fn is_hogging_ledge(&self, check_platform_i: usize, face_right: bool) -> bool {
-        match self.body() {
-            Some(body) => body.is_hogging_ledge(check_platform_i, face_right),
-            None => false,
+    /// Intangible entities cannot be hit, either from a timer or an "intangible" frame tag
+    pub fn is_intangible(&self, entity_defs: &KeyedContextVec<EntityDef>) -> bool {
+        let tagged = self.state.has_tag(
+            &entity_defs[self.state.entity_def_key.as_ref()],
+            "intangible",
+        );
+        match &self.ty {
+            EntityType::Fighter(fighter) => tagged || fighter.get_player().intangible_timer > 0,
+            _ => tagged,
         }
     }
 
@@ -96,6 +119,7 @@ impl Entity {
             }
             EntityType::TorielFireball (_) => true,
             EntityType::TorielOven (toriel_oven) => toriel_oven.body.face_right,
+            EntityType::Assist     (assist)      => assist.body.face_right,
         }
     }
 
@@ -110,6 +134,7 @@ impl Entity {
             EntityType::Fighter        (fighter)    => fighter.get_player().body.public_bps_xy(entities, entity_defs, action_frame, surfaces, &self.state),
             EntityType::Item           (item)       => item.body.public_bps_xy(entities, entity_defs, action_frame, surfaces, &self.state),
             EntityType::TorielOven     (toriel_oven) => toriel_oven.body.public_bps_xy(entities, entity_defs, action_frame, surfaces, &self.state),
+            EntityType::Assist         (assist)     => assist.body.public_bps_xy(entities, entity_defs, action_frame, surfaces, &self.state),
             EntityType::Projectile     (projectile) => (projectile.x, projectile.y),
             EntityType::TorielFireball (projectile) => (projectile.x, projectile.y),
         }
@@ -123,6 +148,7 @@ impl Entity {
             EntityType::Fighter        (fighter)     => fighter.get_player().body.public_bps_xyz(entities, entity_defs, action_frame, surfaces, &self.state),
             EntityType::Item           (item)        => item.body.public_bps_xyz(entities, entity_defs, action_frame, surfaces, &self.state),
             EntityType::TorielOven     (toriel_oven) => toriel_oven.body.public_bps_xyz(entities, entity_defs, action_frame, surfaces, &self.state),
+            EntityType::Assist         (assist)      => assist.body.public_bps_xyz(entities, entity_defs, action_frame, surfaces, &self.state),
             EntityType::Projectile     (projectile)  => (projectile.x, projectile.y, 0.0),
             EntityType::TorielFireball (projectile)  => (projectile.x, projectile.y, 0.0),
         }
@@ -143,6 +169,7 @@ impl Entity {
         let action_result = match &mut self.ty {
             EntityType::Fighter    (fighter) => fighter.get_player_mut().physics_step(context, &self.state, game_frame, goal),
             EntityType::Item       (item)    => item.physics_step(context, &self.state),
+            EntityType::Assist     (assist)  => assist.physics_step(context, &self.state),
             EntityType::Projectile (_)       => None,
             EntityType::TorielFireball (_)   => None,
             EntityType::TorielOven (_)       => None,
@@ -158,17 +185,23 @@ impl Entity {
             EntityType::Projectile (projectile)     => projectile.step_collision(col_results),
             EntityType::TorielFireball (projectile) => projectile.step_collision(col_results),
             EntityType::TorielOven (_) => None,
+            EntityType::Assist     (assist)         => assist.step_collision(context, &self.state, col_results),
         };
         self.process_action_result(context, action_result);
         for col_result in col_results {
             match col_result {
-                CollisionResult::HitAtk { entity_defend_i, ref hitbox, .. } => {
-                    context.audio.play_sound_effect(context.entity_def, SfxType::Hit(HitBoxSfx::Punch));
+                CollisionResult::HitAtk { entity_defend_i, ref hitbox, point, .. } => {
+                    let sfx = match &hitbox.sfx {
+                        Some(filename) => SfxType::custom(filename.clone()),
+                        None           => SfxType::Hit(hitbox.spark),
+                    };
+                    context.audio.play_sound_effect(context.entity_def, sfx, point.0);
                     self.state.hitlist.push(*entity_defend_i);
                     self.state.hitlag = Hitlag::Attack { counter: (hitbox.damage / 3.0 + 3.0) as u64 };
                 }
                 CollisionResult::HitShieldAtk { entity_defend_i, ref hitbox, .. } => {
-                    context.audio.play_sound_effect(context.entity_def, SfxType::Hit(HitBoxSfx::Sword));
+                    let x = self.bps_xy(context).0;
+                    context.audio.play_sound_effect(context.entity_def, SfxType::ShieldHit, x);
                     self.state.hitlist.push(*entity_defend_i);
                     self.state.hitlag = Hitlag::Attack { counter: (hitbox.damage / 3.0 + 3.0) as u64 };
                 }
@@ -183,6 +216,13 @@ impl Entity {
         }
     }
 
+    /// Counts down the status effects of entities with a body, dealing their damage
+    pub fn status_effects_step(&mut self) {
+        if let Some(body) = self.body_mut() {
+            body.damage += body.status_effects.step();
+        }
+    }
+
     pub fn action_hitlag_step(&mut self, context: &mut StepContext) {
         // If the action or frame is out of bounds jump to a valid one.
         // This is needed because we can continue from any point in a replay and replays may
@@ -211,6 +251,7 @@ impl Entity {
 
         self.state.hitlag.step(context.rng);
         if let Hitlag::None = self.state.hitlag {
+            self.state.blend_step();
             let main_action_result = self.action_step(context).or_else(|| {
                 if self.state.last_frame(context.entity_def) {
                     self.action_expired(context)
@@ -238,16 +279,29 @@ impl Entity {
             self.state.hitlist.clear();
         }
 
-        match &mut self.ty {
+        let action_result = match &mut self.ty {
             EntityType::Fighter(fighter) => fighter.action_step(context, &self.state),
             EntityType::Item(item) => item.action_step(context, &self.state),
             EntityType::Projectile(projectile) => projectile.action_step(context, &self.state),
             EntityType::TorielFireball(projectile) => projectile.action_step(context, &self.state),
             EntityType::TorielOven(toriel_oven) => toriel_oven.action_step(context, &self.state),
+            EntityType::Assist(assist) => assist.action_step(context, &self.state),
+        };
+
+        let entity_def = context.entity_def;
+        match &entity_def.actions[self.state.action.as_ref()].frame_step_script {
+            Some(script) => context.scripts.run(script, self, context).or(action_result),
+            None => action_result,
         }
     }
 
     fn action_expired(&mut self, context: &mut StepContext) -> Option<ActionResult> {
+        let entity_def = context.entity_def;
+        if let Some(script) = &entity_def.actions[self.state.action.as_ref()].action_expired_script
+        {
+            return context.scripts.run(script, self, context);
+        }
+
         match &mut self.ty {
             EntityType::Fighter(fighter) => fighter.action_expired(context, &self.state),
             _ => None,
@@ -290,10 +344,13 @@ impl Entity {
         };
         match action_result {
             Some(ActionResult::SetAction(action)) => {
+                let entity_def = &entity_defs[self.state.entity_def_key.as_ref()];
+                self.state.start_blend(entity_def, &action);
                 self.state.frame_no_restart = 0;
                 self.state.frame = 0;
                 self.state.action = action;
-                self.state.hitlist.clear()
+                self.state.hitlist.clear();
+                self.refresh_stale_queue();
             }
             _ => {}
         }
@@ -309,6 +366,7 @@ impl Entity {
                 EntityType::TorielOven(toriel_oven) => {
                     toriel_oven.body.angle(entity_frame, surfaces)
                 }
+                EntityType::Assist(assist) => assist.body.angle(entity_frame, surfaces),
                 EntityType::Projectile(projectile) => projectile.angle,
                 EntityType::TorielFireball(_) => 0.0,
             }
@@ -334,9 +392,7 @@ impl Entity {
             for colbox in fighter_frame.colboxes.iter_mut() {
                 let (raw_x, y) = colbox.point;
                 let x = self.relative_f(raw_x);
-                let angled_x = x * angle.cos() - y * angle.sin();
-                let angled_y = x * angle.sin() + y * angle.cos();
-                colbox.point = (angled_x, angled_y);
+                colbox.point = geometry::rotate((x, y), angle);
                 if let &mut CollisionBoxRole::Hit(ref mut hitbox) = &mut colbox.role {
                     if !self.face_right() {
                         hitbox.angle = 180.0 - hitbox.angle
@@ -355,8 +411,22 @@ impl Entity {
         }
     }
 
-    pub fn can_hit(&self, other: &Entity) -> bool {
-        self.player_id() != other.player_id()
+    /// Like `relative_frame` but the colboxes and ecb of fighters are scaled by `fighter_scale` e.g. for the giant and mini modifiers
+    pub fn scaled_relative_frame(
+        &self,
+        entity_def: &EntityDef,
+        surfaces: &[Surface],
+        fighter_scale: f32,
+    ) -> ActionFrame {
+        let mut frame = self.relative_frame(entity_def, surfaces);
+        if let EntityType::Fighter(_) = self.ty {
+            frame.scale(fighter_scale);
+        }
+        frame
+    }
+
+    pub fn can_hit(&self, other: &Entity, entity_defs: &KeyedContextVec<EntityDef>) -> bool {
+        self.player_id() != other.player_id() && !other.is_intangible(entity_defs)
     }
 
     /// The players id
@@ -369,6 +439,7 @@ impl Entity {
             EntityType::Projectile(projectile) => projectile.owner_id,
             EntityType::TorielFireball(projectile) => projectile.owner_id,
             EntityType::TorielOven(toriel_oven) => toriel_oven.owner_id,
+            EntityType::Assist(assist) => assist.owner_id,
         }
     }
 
@@ -405,6 +476,21 @@ impl Entity {
         &self.state.hitlist
     }
 
+    /// Damage multiplier of the current action's hitbox due to staling, only fighters have a stale queue
+    pub fn stale_damage_mult(&self, hitbox_id: usize) -> f32 {
+        self.ty.get_player().map_or(1.0, |player| {
+            player
+                .stale_queue
+                .damage_mult(&self.state.action, hitbox_id)
+        })
+    }
+
+    fn refresh_stale_queue(&mut self) {
+        if let EntityType::Fighter(fighter) = &mut self.ty {
+            fighter.get_player_mut().stale_queue.refresh();
+        }
+    }
+
     pub fn debug_print(
         &self,
         entities: &KeyedContextVec<EntityDef>,
@@ -417,6 +503,33 @@ impl Entity {
             lines.push(self.state.debug_string(entities, i));
         }
 
+        if debug.colbox_overlay {
+            let action =
+                &entities[self.state.entity_def_key.as_ref()].actions[self.state.action.as_ref()];
+            let windows: Vec<String> = action
+                .hitbox_windows()
+                .iter()
+                .map(|(colbox_i, ranges)| {
+                    let ranges: Vec<String> = ranges
+                        .iter()
+                        .map(|(start, end)| {
+                            if start == end {
+                                start.to_string()
+                            } else {
+                                format!("{}-{}", start, end)
+                            }
+                        })
+                        .collect();
+                    format!("#{}: {}", colbox_i, ranges.join(", "))
+                })
+                .collect();
+            lines.push(format!(
+                "Entity: {:?}  hitbox frames: [{}]",
+                i,
+                windows.join("  ")
+            ));
+        }
+
         if debug.physics {
             if let Some(body) = self.body() {
                 lines.push(body.debug_string(i));
@@ -438,10 +551,37 @@ impl Entity {
         lines
     }
 
+    /// A single line describing the entity, for when the full debug lines would be too noisy
+    pub fn debug_summary(
+        &self,
+        entities: &Entities,
+        entity_defs: &KeyedContextVec<EntityDef>,
+        surfaces: &[Surface],
+        i: EntityKey,
+    ) -> String {
+        let (x, y) = self.public_bps_xy(entities, entity_defs, surfaces);
+        let damage = match self.body() {
+            Some(body) => format!("  damage: {:.1}", body.damage),
+            None => String::new(),
+        };
+        format!(
+            "Entity: {:?}  {} {}  action: {}  frame: {}  x: {:.2}  y: {:.2}{}",
+            i,
+            self.ty.name(),
+            self.state.entity_def_key,
+            self.state.action,
+            self.state.frame,
+            x,
+            y,
+            damage
+        )
+    }
+
     pub fn body(&self) -> Option<&Body> {
         match &self.ty {
             EntityType::Fighter(fighter) => Some(&fighter.get_player().body),
             EntityType::Item(item) => Some(&item.body),
+            EntityType::Assist(assist) => Some(&assist.body),
             _ => None,
         }
     }
@@ -450,10 +590,23 @@ impl Entity {
         match &mut self.ty {
             EntityType::Fighter(fighter) => Some(&mut fighter.get_player_mut().body),
             EntityType::Item(item) => Some(&mut item.body),
+            EntityType::Assist(assist) => Some(&mut assist.body),
             _ => None,
         }
     }
 
+    /// The entity this entity is attached to
+    pub fn parent(&self) -> Option<EntityKey> {
+        match &self.ty {
+            EntityType::Fighter(fighter) => fighter.get_player().body.parent(),
+            EntityType::Item(item) => item.body.parent(),
+            EntityType::TorielOven(toriel_oven) => toriel_oven.body.parent(),
+            EntityType::Assist(assist) => assist.body.parent(),
+            EntityType::Projectile(_) => None,
+            EntityType::TorielFireball(_) => None,
+        }
+    }
+
     pub fn team(&self) -> usize {
         match &self.ty {
             EntityType::Fighter(fighter) => fighter.get_player().team,
@@ -461,12 +614,46 @@ impl Entity {
             EntityType::Projectile(_) => 0,
             EntityType::TorielFireball(_) => 0,
             EntityType::TorielOven(_) => 0,
+            EntityType::Assist(_) => 0,
+        }
+    }
+
+    /// The team of the fighter or of the fighter that owns this entity
+    /// or none if not owned by a fighter
+    pub fn owner_team(&self, entities: &Entities) -> Option<usize> {
+        match &self.ty {
+            EntityType::Fighter(fighter) => Some(fighter.get_player().team),
+            _ => {
+                let player_id = self.player_id()?;
+                entities.values().find_map(|entity| match &entity.ty {
+                    EntityType::Fighter(fighter) if fighter.get_player().id == player_id => {
+                        Some(fighter.get_player().team)
+                    }
+                    _ => None,
+                })
+            }
         }
     }
 
-    pub fn particles(&self) -> Vec<Particle> {
+    /// Particles are only rendered, so the team color they are simulated with is swapped for the team color of `team_palette`
+    pub fn particles(&self, team_palette: &TeamPalette) -> Vec<Particle> {
         match &self.ty {
-            EntityType::Fighter(fighter) => fighter.get_player().particles.clone(),
+            EntityType::Fighter(fighter) => {
+                let player = fighter.get_player();
+                let simulated = graphics::get_team_color3(&TeamPalette::Standard, player.team);
+                let configured = graphics::get_team_color3(team_palette, player.team);
+                player
+                    .particles
+                    .iter()
+                    .cloned()
+                    .map(|mut particle| {
+                        if particle.color == simulated {
+                            particle.color = configured;
+                        }
+                        particle
+                    })
+                    .collect()
+            }
             _ => vec![],
         }
     }
@@ -481,8 +668,11 @@ impl Entity {
         entities: &Entities,
         entity_defs: &KeyedContextVec<EntityDef>,
         surfaces: &[Surface],
+        fighter_scale: f32,
+        team_palette: &TeamPalette,
+        costume: Option<&Costume>,
     ) -> RenderEntity {
-        let fighter_color = graphics::get_team_color3(self.team());
+        let fighter_color = graphics::get_team_color3(team_palette, self.team());
         let entity_def = &entity_defs[self.state.entity_def_key.as_ref()];
 
         let vector_arrows = if let Some(player) = &self.ty.get_player() {
@@ -491,7 +681,7 @@ impl Entity {
             vec![]
         };
 
-        let mut frames = vec![self.render_frame(entities, entity_defs, surfaces)];
+        let mut frames = vec![self.render_frame(entities, entity_defs, surfaces, costume)];
         let range = entity_history.len().saturating_sub(5)..entity_history.len();
         for entities in entity_history[range].iter().rev() {
             if let Some(entity) = entities.get(entity_i) {
@@ -501,7 +691,7 @@ impl Entity {
                     .len()
                     > entity.state.frame as usize
                 {
-                    frames.push(entity.render_frame(entities, entity_defs, surfaces));
+                    frames.push(entity.render_frame(entities, entity_defs, surfaces, costume));
                 }
             }
         }
@@ -512,11 +702,13 @@ impl Entity {
                 entity_defs,
                 surfaces,
                 &self.state,
+                team_palette,
             )),
             EntityType::Projectile(_) => RenderEntityType::Projectile,
             EntityType::TorielFireball(_) => RenderEntityType::Projectile,
             EntityType::Item(_) => RenderEntityType::Item,
             EntityType::TorielOven(_) => RenderEntityType::Projectile,
+            EntityType::Assist(_) => RenderEntityType::Projectile,
         };
 
         let visible = match &self.ty {
@@ -527,12 +719,17 @@ impl Entity {
         };
 
         RenderEntity {
-            frame_data: self.relative_frame(entity_def, surfaces),
-            particles: self.particles(),
+            frame_data: self.scaled_relative_frame(entity_def, surfaces, fighter_scale),
+            model_scale: match self.ty {
+                EntityType::Fighter(_) => fighter_scale,
+                _ => 1.0,
+            },
+            particles: self.particles(team_palette),
             visible,
             render_type,
             frames,
             fighter_color,
+            intangible: self.is_intangible(entity_defs),
             entity_selected,
             selected_colboxes,
             debug,
@@ -545,20 +742,35 @@ impl Entity {
         entities: &Entities,
         entity_defs: &KeyedContextVec<EntityDef>,
         surfaces: &[Surface],
+        costume: Option<&Costume>,
     ) -> RenderEntityFrame {
         let entity_def = &entity_defs[self.state.entity_def_key.as_ref()];
+        let animation_frame =
+            |action: &str, frame: i64| match entity_def.actions.key_to_value(action) {
+                Some(action_def) => action_def.animation_frame(frame as usize),
+                None => frame as f32,
+            };
         RenderEntityFrame {
             entity_def_key: self.state.entity_def_key.clone(),
-            model_name: entity_def.name.clone(),
+            model_name: costume
+                .and_then(|x| x.model.clone())
+                .unwrap_or_else(|| entity_def.name.clone()),
+            costume_textures: costume.map(|x| x.textures.clone()),
             frame_bps: self.public_bps_xy(entities, entity_defs, surfaces),
             render_bps: self.public_bps_xyz(entities, entity_defs, surfaces),
             ecb: self.body().map(|x| x.ecb.clone()),
             frame: self.state.frame as usize,
+            animation_frame: animation_frame(&self.state.action, self.state.frame),
             frame_no_restart: self.state.frame_no_restart as usize,
             action: self.state.action.clone(),
             face_right: self.face_right(),
             frame_angle: self.frame_angle(entity_def, surfaces),
             render_angle: self.render_angle(entities, entity_defs, surfaces),
+            blend: self.state.blend.as_ref().map(|blend| RenderActionBlend {
+                action: blend.action.clone(),
+                frame: animation_frame(&blend.action, blend.frame),
+                amount: blend.amount(),
+            }),
         }
     }
 
@@ -589,13 +801,15 @@ impl Entity {
         match action_result {
             Some(ActionResult::SetAction(action)) => {
                 if self.state.action != action {
+                    self.state.start_blend(context.entity_def, &action);
                     self.state.frame_no_restart = 0;
                 } else {
                     self.state.frame_no_restart += 1;
                 }
                 self.state.frame = 0;
                 self.state.action = action;
-                self.state.hitlist.clear()
+                self.state.hitlist.clear();
+                self.refresh_stale_queue();
             }
             Some(ActionResult::SetActionKeepFrame(action)) => {
                 self.state.frame_no_restart += 1;
@@ -628,7 +842,10 @@ pub struct RenderEntity {
     /// Gauranteed to have at least one value (the current frame), and can have up to and including 10 values
     pub frames: Vec<RenderEntityFrame>,
     pub frame_data: ActionFrame,
+    /// Scale of the model, fighters are scaled by the fighter size modifier
+    pub model_scale: f32,
     pub fighter_color: [f32; 3],
+    pub intangible: bool,
     pub entity_selected: bool,
     pub selected_colboxes: HashSet<usize>,
     pub vector_arrows: Vec<VectorArrow>,
@@ -709,6 +926,10 @@ pub struct DebugEntity {
     pub ecb: bool,
     pub cam_area: bool,
     pub item_grab_area: bool,
+    pub ledge_timers: bool,
+    /// Label the colboxes with their index, color hitboxes by damage, dim hurtboxes while intangible
+    /// and list the hitbox frames of the current action.
+    pub colbox_overlay: bool,
 }
 
 impl DebugEntity {
@@ -727,6 +948,8 @@ impl DebugEntity {
             ecb: true,
             cam_area: true,
             item_grab_area: true,
+            ledge_timers: true,
+            colbox_overlay: true,
         }
     }
 }
@@ -734,15 +957,28 @@ impl DebugEntity {
 pub struct RenderEntityFrame {
     pub entity_def_key: String,
     pub model_name: String,
+    /// The folder of png textures replacing the textures of the model, None uses the model's own textures
+    pub costume_textures: Option<String>,
     pub frame_bps: (f32, f32),
     pub render_bps: (f32, f32, f32),
     pub ecb: Option<ECB>,
     pub frame: usize,
+    /// The frame of the action's animation to display, scaled by the action's animation_speed
+    pub animation_frame: f32,
     pub frame_no_restart: usize,
     pub action: String,
     pub face_right: bool,
     pub frame_angle: f32,
     pub render_angle: Quaternion<f32>,
+    pub blend: Option<RenderActionBlend>,
+}
+
+pub struct RenderActionBlend {
+    pub action: String,
+    /// The frame of the outgoing action's animation, scaled by its animation_speed
+    pub frame: f32,
+    /// How much of the outgoing animation to show
+    pub amount: f32,
 }
 
 pub struct VectorArrow {
@@ -763,9 +999,106 @@ pub struct StepContext<'a> {
     pub new_entities: &'a mut Vec<Entity>,
     pub messages: &'a mut Vec<Message>,
     pub audio: &'a mut Audio,
+    pub scripts: &'a Scripts,
+    pub modifiers: &'a Modifiers,
     pub delete_self: bool,
 }
 
+impl<'a> StepContext<'a> {
+    /// The entity attached to, send it messages via this key
+    pub fn parent(&self) -> Option<EntityKey> {
+        self.entities.get(self.entity_key).and_then(|x| x.parent())
+    }
+
+    /// The entities attached to this entity, send them messages via these keys
+    pub fn children(&self) -> Vec<EntityKey> {
+        children(self.entities, self.entity_key)
+    }
+
+    /// The item held by this entity
+    pub fn held_item(&self) -> Option<EntityKey> {
+        held_item(self.entities, self.entity_key)
+    }
+}
+
+/// Returns the entity holding the ledge and how it allows other entities to grab the same ledge.
+/// `face_right` is the direction an entity faces while holding the ledge, so true is the left ledge of the platform.
+pub fn ledge_occupant(
+    entities: &Entities,
+    platform_i: usize,
+    face_right: bool,
+) -> Option<(EntityKey, LedgeLogic)> {
+    entities.iter().find_map(|(key, entity)| {
+        entity
+            .body()
+            .and_then(|body| body.ledge_held(platform_i, face_right))
+            .map(|logic| (key, logic.clone()))
+    })
+}
+
+/// Returns the position of the closest fighter that does not belong to `player_id` and can currently be hit
+pub fn nearest_enemy_xy(
+    context: &StepContext,
+    player_id: Option<usize>,
+    (x, y): (f32, f32),
+) -> Option<(f32, f32)> {
+    context
+        .entities
+        .iter()
+        .filter(|(_, entity)| {
+            entity.ty.get_player().is_some()
+                && entity.player_id() != player_id
+                && !entity.is_intangible(context.entity_defs)
+        })
+        .map(|(_, entity)| entity.bps_xy(context))
+        .min_by(|(x1, y1), (x2, y2)| {
+            let distance1 = (x1 - x).powi(2) + (y1 - y).powi(2);
+            let distance2 = (x2 - x).powi(2) + (y2 - y).powi(2);
+            distance1.partial_cmp(&distance2).unwrap()
+        })
+}
+
+/// Returns the entities directly attached to `parent`
+pub fn children(entities: &Entities, parent: EntityKey) -> Vec<EntityKey> {
+    entities
+        .iter()
+        .filter(|(_, entity)| entity.parent() == Some(parent))
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Returns the item held by `holder`
+pub fn held_item(entities: &Entities, holder: EntityKey) -> Option<EntityKey> {
+    children(entities, holder).into_iter().find(|key| {
+        entities[*key]
+            .body()
+            .map_or(false, |body| body.is_item_held())
+    })
+}
+
+/// Removes every entity whose parent no longer exists.
+/// Repeats until no orphans remain so that children of removed children are also removed.
+pub fn despawn_orphans(entities: &mut Entities) {
+    loop {
+        let orphans: Vec<_> = entities
+            .iter()
+            .filter(|(_, entity)| {
+                entity
+                    .parent()
+                    .map_or(false, |parent| !entities.contains_key(parent))
+            })
+            .map(|(key, _)| key)
+            .collect();
+
+        if orphans.is_empty() {
+            return;
+        }
+        for orphan in orphans {
+            entities.remove(orphan);
+        }
+    }
+}
+
 pub struct Message {
     pub recipient: EntityKey,
     pub contents: MessageContents,
@@ -798,3 +1131,110 @@ impl ActionResult {
         Some(ActionResult::SetFrame(action))
     }
 }
+
+#[test]
+fn despawn_orphans_test() {
+    use canon_collision_lib::entity_def::item::ItemAction;
+    use components::body::{Attachment, Location};
+
+    fn attached_item(location: Location) -> Entity {
+        Entity {
+            ty: EntityType::Item(Item {
+                owner_id: None,
+                body: Body::new(location, true),
+            }),
+            state: ActionState::new("Item.cbor".to_string(), ItemAction::Idle),
+        }
+    }
+
+    let mut entities = Entities::with_key();
+    let parent = entities.insert(attached_item(Location::Airbourne { x: 0.0, y: 0.0 }));
+    let child = entities.insert(attached_item(Location::AttachedToEntity {
+        parent,
+        attachment: Attachment::ItemHold,
+    }));
+    entities.insert(attached_item(Location::AttachedToEntity {
+        parent: child,
+        attachment: Attachment::Offset { x: 1.0, y: 0.0 },
+    }));
+    assert_eq!(children(&entities, parent), vec![child]);
+
+    despawn_orphans(&mut entities);
+    assert_eq!(entities.len(), 3);
+
+    entities.remove(parent);
+    despawn_orphans(&mut entities);
+    assert!(entities.is_empty());
+}
+
+#[test]
+fn held_item_step_test() {
+    use canon_collision_lib::entity_def::item::ItemAction;
+    use canon_collision_lib::entity_def::ActionDef;
+    use components::body::Location;
+    use rand::SeedableRng;
+
+    fn item(location: Location, face_right: bool) -> Entity {
+        Entity {
+            ty: EntityType::Item(Item {
+                owner_id: None,
+                body: Body::new(location, face_right),
+            }),
+            state: ActionState::new("Item.cbor".to_string(), ItemAction::Held),
+        }
+    }
+
+    let mut entity_def = EntityDef::default();
+    entity_def
+        .actions
+        .push("Held".to_string(), ActionDef::default());
+    let mut entity_defs = KeyedContextVec::new();
+    entity_defs.push("Item.cbor".to_string(), entity_def);
+
+    let mut entities = Entities::with_key();
+    let holder = entities.insert(item(Location::Airbourne { x: 0.0, y: 0.0 }, false));
+    let held = entities.insert(item(Location::ItemHeldByPlayer(holder), true));
+
+    let stage = Stage::default();
+    let input = PlayerInput::empty();
+    let mut rng = ChaChaRng::seed_from_u64(0);
+    let mut new_entities = vec![];
+    let mut messages = vec![];
+    let mut audio = Audio::silent();
+    let scripts = Scripts::default();
+    let modifiers = Modifiers::default();
+    let mut step = |key: EntityKey, entities: &Entities| {
+        let mut entity = entities[key].clone();
+        let mut context = StepContext {
+            entity_key: key,
+            input: &input,
+            entities,
+            entity_defs: &entity_defs,
+            entity_def: &entity_defs["Item.cbor"],
+            stage: &stage,
+            surfaces: &stage.surfaces,
+            rng: &mut rng,
+            new_entities: &mut new_entities,
+            messages: &mut messages,
+            audio: &mut audio,
+            scripts: &scripts,
+            modifiers: &modifiers,
+            delete_self: false,
+        };
+        let result = (context.parent(), context.held_item());
+        entity.physics_step(&mut context, 0, Goal::default());
+        (entity, result)
+    };
+
+    assert_eq!(step(holder, &entities).1, (None, Some(held)));
+
+    // the held item turns with its holder
+    let (stepped, result) = step(held, &entities);
+    assert_eq!(result, (Some(holder), None));
+    assert!(!stepped.face_right());
+    entities[held] = stepped;
+
+    entities.remove(holder);
+    despawn_orphans(&mut entities);
+    assert!(entities.is_empty());
+}
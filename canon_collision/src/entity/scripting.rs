@@ -0,0 +1,227 @@
+use crate::entity::assist::Assist;
+use crate::entity::components::action_state::ActionState;
+use crate::entity::components::body::{Body, Location};
+use crate::entity::item::Item;
+use crate::entity::projectile::Projectile;
+use crate::entity::{nearest_enemy_xy, ActionResult, Entity, EntityType, StepContext};
+
+use canon_collision_lib::entity_def::item::ItemAction;
+use canon_collision_lib::entity_def::projectile::ProjectileAction;
+use canon_collision_lib::entity_def::EntityDefType;
+
+use rhai::{Array, Engine, Map, Scope, AST};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Stops a buggy script from hanging the game
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Runs the rhai scripts attached to actions in the package.
+///
+/// The following variables are available to a script:
+/// *   `action`, `frame`, `frame_no_restart`: modifying `action` or `frame` changes the action/frame of the entity
+/// *   `x`, `y`: read only position of the entity
+/// *   `tags`: read only array of the tags on the current frame
+/// *   `target_x`, `target_y`: read only position of the nearest fighter the entity can hit, only available if there is one
+/// *   `damage`, `x_vel`, `y_vel`, `kb_x_vel`, `kb_y_vel`, `face_right`: only available if the entity has a body
+/// *   `delete_self`: set to true to delete the entity
+/// *   `spawn`: push maps like `#{ entity: "PerfectlyGenericProjectile.cbor", x: 0.0, y: 0.0, angle: 0.0, speed: 1.0 }` to create new entities.
+///     Only Projectile, Item and Assist entities can be spawned, `angle` and `speed` are only used by projectiles.
+pub struct Scripts {
+    engine: Engine,
+    /// Scripts that failed to compile are stored as None so the error is only logged once
+    compiled: RefCell<HashMap<String, Option<AST>>>,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        Scripts {
+            engine,
+            compiled: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl Clone for Scripts {
+    fn clone(&self) -> Self {
+        Scripts::default()
+    }
+}
+
+impl Scripts {
+    pub fn run(
+        &self,
+        script: &str,
+        entity: &mut Entity,
+        context: &mut StepContext,
+    ) -> Option<ActionResult> {
+        let mut compiled = self.compiled.borrow_mut();
+        let ast = compiled
+            .entry(script.to_string())
+            .or_insert_with(|| match self.engine.compile(script) {
+                Ok(ast) => Some(ast),
+                Err(err) => {
+                    error!(
+                        "Failed to compile script for {}: {}",
+                        entity.state.entity_def_key, err
+                    );
+                    None
+                }
+            })
+            .as_ref()?;
+
+        let (x, y) = entity.bps_xy(context);
+        let mut scope = Scope::new();
+        scope.push("action", entity.state.action.clone());
+        scope.push("frame", entity.state.frame);
+        scope.push_constant("frame_no_restart", entity.state.frame_no_restart);
+        scope.push_constant("x", x);
+        scope.push_constant("y", y);
+        let tags: Array = entity
+            .state
+            .get_entity_frame(&context.entity_defs[entity.state.entity_def_key.as_ref()])
+            .map(|frame| frame.tags.iter().cloned().map(Into::into).collect())
+            .unwrap_or_default();
+        scope.push_constant("tags", tags);
+        if let Some((target_x, target_y)) = nearest_enemy_xy(context, entity.player_id(), (x, y)) {
+            scope.push_constant("target_x", target_x);
+            scope.push_constant("target_y", target_y);
+        }
+        scope.push("delete_self", false);
+        scope.push("spawn", Array::new());
+        if let Some(body) = entity.body() {
+            push_body(&mut scope, body);
+        }
+
+        if let Err(err) = self.engine.run_ast_with_scope(&mut scope, ast) {
+            error!(
+                "Script for {} action {} failed: {}",
+                entity.state.entity_def_key, entity.state.action, err
+            );
+            return None;
+        }
+
+        if let Some(body) = entity.body_mut() {
+            read_body(&scope, body);
+        }
+
+        if scope.get_value("delete_self").unwrap_or(false) {
+            context.delete_self = true;
+        }
+
+        let owner_id = entity.player_id();
+        for spawn in scope.get_value::<Array>("spawn").unwrap_or_default() {
+            match spawn.try_cast::<Map>() {
+                Some(spawn) => spawn_entity(context, &spawn, owner_id),
+                None => error!("Values pushed to spawn must be maps"),
+            }
+        }
+
+        read_action_result(&scope, &entity.state, context)
+    }
+}
+
+fn push_body(scope: &mut Scope, body: &Body) {
+    scope.push("damage", body.damage);
+    scope.push("x_vel", body.x_vel);
+    scope.push("y_vel", body.y_vel);
+    scope.push("kb_x_vel", body.kb_x_vel);
+    scope.push("kb_y_vel", body.kb_y_vel);
+    scope.push("face_right", body.face_right);
+}
+
+fn read_body(scope: &Scope, body: &mut Body) {
+    body.damage = scope.get_value("damage").unwrap_or(body.damage);
+    body.x_vel = scope.get_value("x_vel").unwrap_or(body.x_vel);
+    body.y_vel = scope.get_value("y_vel").unwrap_or(body.y_vel);
+    body.kb_x_vel = scope.get_value("kb_x_vel").unwrap_or(body.kb_x_vel);
+    body.kb_y_vel = scope.get_value("kb_y_vel").unwrap_or(body.kb_y_vel);
+    body.face_right = scope.get_value("face_right").unwrap_or(body.face_right);
+}
+
+fn read_action_result(
+    scope: &Scope,
+    state: &ActionState,
+    context: &StepContext,
+) -> Option<ActionResult> {
+    let action: String = scope.get_value("action")?;
+    if action != state.action {
+        if context.entity_def.actions.contains_key(&action) {
+            return Some(ActionResult::SetAction(action));
+        } else {
+            error!("Script set nonexistent action: {}", action);
+        }
+    }
+
+    let frame: i64 = scope.get_value("frame")?;
+    if frame != state.frame {
+        Some(ActionResult::SetFrame(frame))
+    } else {
+        None
+    }
+}
+
+fn spawn_entity(context: &mut StepContext, spawn: &Map, owner_id: Option<usize>) {
+    let get_float = |key: &str| {
+        spawn
+            .get(key)
+            .and_then(|x| x.as_float().ok())
+            .unwrap_or(0.0)
+    };
+
+    let entity_def_key = match spawn
+        .get("entity")
+        .and_then(|x| x.clone().into_string().ok())
+    {
+        Some(key) => key,
+        None => {
+            error!("Spawned entity is missing the entity field");
+            return;
+        }
+    };
+    let x = get_float("x");
+    let y = get_float("y");
+
+    let entity = match context
+        .entity_defs
+        .key_to_value(&entity_def_key)
+        .map(|x| &x.ty)
+    {
+        Some(EntityDefType::Projectile) => Entity {
+            ty: EntityType::Projectile(Projectile {
+                owner_id,
+                angle: get_float("angle"),
+                speed: get_float("speed"),
+                x,
+                y,
+            }),
+            state: ActionState::new(entity_def_key, ProjectileAction::Spawn),
+        },
+        Some(EntityDefType::Item) | Some(EntityDefType::AssistItem(_)) => Entity {
+            ty: EntityType::Item(Item {
+                owner_id,
+                body: Body::new(Location::Airbourne { x, y }, true),
+            }),
+            state: ActionState::new(entity_def_key, ItemAction::Fall),
+        },
+        Some(EntityDefType::Assist(_)) => {
+            Assist::summon(context, &entity_def_key, owner_id, x, y, true);
+            return;
+        }
+        Some(_) => {
+            error!("Scripts cannot spawn {}", entity_def_key);
+            return;
+        }
+        None => {
+            error!(
+                "Script tried to spawn nonexistent entity: {}",
+                entity_def_key
+            );
+            return;
+        }
+    };
+    context.new_entities.push(entity);
+}
@@ -1,6 +1,4 @@
-use kira::Value;
-
-use crate::audio::sfx::SfxType;
+use crate::audio::sfx::{SfxType, Value};
 use crate::entity::components::action_state::ActionState;
 use crate::entity::components::body::Body;
 use crate::entity::{ActionResult, StepContext};
@@ -70,6 +68,14 @@ impl TorielOven {
             Some(TorielOvenAction::AttackExtended) => None,
             Some(TorielOvenAction::Attack) => {
                 if state.frame == 40 {
+                    let action_frame = state.get_entity_frame(context.entity_def);
+                    let (x, _) = self.body.public_bps_xy(
+                        context.entities,
+                        context.entity_defs,
+                        action_frame,
+                        context.surfaces,
+                        state,
+                    );
                     context.audio.play_sound_effect(
                         context.entity_def,
                         SfxType::Custom {
@@ -77,6 +83,7 @@ impl TorielOven {
                             volume: Value::Fixed(0.3),
                             pitch: Value::Fixed(1.0),
                         },
+                        x,
                     );
                 }
                 None
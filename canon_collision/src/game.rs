@@ -1,52 +1,139 @@
+use crate::ai::{self, DummyBehavior};
+use crate::audio::announcer::Announcement;
+use crate::audio::sfx::SfxType;
 use crate::audio::{Audio, BGMMetadata};
 use crate::camera::Camera;
-use crate::collision::collision_box;
+use crate::collision::collision_box::{self, CollisionResult};
 use crate::collision::item_grab;
+use crate::editor::FrameDataEditor;
 use crate::entity::components::action_state::ActionState;
+use crate::entity::components::body::{Body, Location};
 use crate::entity::fighters::player::Player;
 use crate::entity::fighters::toriel::Toriel;
 use crate::entity::fighters::Fighter;
+use crate::entity::item::Item;
+use crate::entity::projectile::Projectile;
+use crate::entity::scripting::Scripts;
 use crate::entity::{
-    DebugEntities, DebugEntity, Entities, Entity, EntityKey, EntityType, RenderEntity, StepContext,
+    despawn_orphans, DebugEntities, DebugEntity, Entities, Entity, EntityKey, EntityType,
+    RenderDebugType, RenderEntity, StepContext,
 };
-use crate::graphics::{GraphicsMessage, Render, RenderType};
-use crate::menu::ResumeMenu;
+use crate::graphics::{self, GraphicsMessage, Render, RenderType};
+use crate::haptics::HapticState;
+use crate::pause_menu::{EntityAction, EntityOption, PauseAction, PauseMenu, RenderPauseMenu};
+#[cfg(feature = "window")]
+use crate::presence::Activity;
 use crate::replays;
 use crate::replays::Replay;
 use crate::results::{GameResults, PlayerResult, RawPlayerResult};
-use crate::rules::{Goal, Rules};
+#[cfg(feature = "window")]
+use crate::rules::Pause;
+use crate::rules::{Goal, Rules, Teams};
+use crate::telemetry::TelemetryFrame;
+use crate::toasts::Toasts;
+use crate::tutorial::{Tutorial, DI_EVENT};
 
 use canon_collision_lib::command_line::CommandLine;
-use canon_collision_lib::config::Config;
+use canon_collision_lib::config::{Config, EffectColors, HudConfig, Minimap, TeamPalette};
+use canon_collision_lib::entity_def::item::ItemAction;
 use canon_collision_lib::entity_def::player::PlayerAction;
-use canon_collision_lib::entity_def::{ActionFrame, CollisionBox, EntityDefType, FighterType};
+use canon_collision_lib::entity_def::projectile::ProjectileAction;
+use canon_collision_lib::entity_def::{
+    ActionFrame, CollisionBox, CollisionBoxRole, EntityDefType, FighterType, FrameEvent,
+};
+use canon_collision_lib::files;
+#[cfg(feature = "window")]
+use canon_collision_lib::geometry;
 use canon_collision_lib::geometry::Rect;
+#[cfg(feature = "window")]
+use canon_collision_lib::hotkeys::HotkeyAction;
 use canon_collision_lib::input::state::{ControllerInput, PlayerInput};
 use canon_collision_lib::input::Input;
 use canon_collision_lib::network::Netplay;
 use canon_collision_lib::package::Package;
-use canon_collision_lib::stage::{DebugStage, Floor, RenderStageMode, SpawnPoint, Stage, Surface};
+use canon_collision_lib::schema;
+#[cfg(feature = "window")]
+use canon_collision_lib::stage::Floor;
+use canon_collision_lib::stage::{DebugStage, RenderStageMode, SpawnPoint, Stage, Surface};
 
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::path::Path;
+#[cfg(feature = "window")]
 use std::str::FromStr;
 use std::time::Duration;
 
 use byteorder::{LittleEndian, WriteBytesExt};
 use chrono::Local;
+use rand::Rng;
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaChaRng;
+use slotmap::Key;
 use treeflection::{Node, NodeRunner, NodeToken};
+#[cfg(feature = "window")]
 use winit::event::VirtualKeyCode;
+#[cfg(feature = "window")]
 use winit_input_helper::WinitInputHelper;
 
+include!(concat!(env!("OUT_DIR"), "/node_docs.rs"));
+
 #[derive(Clone, Default, Serialize, Deserialize, Node)]
 #[NodeActions(
     NodeAction(function = "save_replay", return_string),
+    NodeAction(
+        function = "dump_last_inputs",
+        args = "1",
+        help = "$SECONDS - save the last $SECONDS of history and inputs to the replays folder for bug reports",
+        return_string
+    ),
+    NodeAction(
+        function = "save_resume",
+        args = "1",
+        help = "$PATH - save the current frame and the inputs leading up to it to $PATH, the game can then be continued from this frame with `--resume $PATH`",
+        return_string
+    ),
     NodeAction(function = "reset_deadzones", return_string),
+    NodeAction(function = "toggle_mute", return_string),
+    NodeAction(
+        function = "set_speed",
+        args = "1",
+        help = "$SPEED - run local games at $SPEED times the normal speed e.g. 0.5 for half speed",
+        return_string
+    ),
+    NodeAction(
+        function = "spawn",
+        args = "4",
+        help = "$KIND $ENTITY $X $Y - spawn the item or projectile $ENTITY at $X $Y, $KIND must be item or projectile",
+        return_string
+    ),
+    NodeAction(
+        function = "step_frames",
+        args = "1",
+        help = "$FRAMES - while paused, advance $FRAMES frames, or step back through the history when negative",
+        return_string
+    ),
+    NodeAction(function = "list_entities", return_string),
+    NodeAction(
+        function = "despawn",
+        args = "1",
+        help = "$KEY - despawn the entity with the key displayed by list_entities, along with everything attached to it",
+        return_string
+    ),
+    NodeAction(
+        function = "debug_port",
+        args = "1",
+        help = "$PORT - toggle displaying the debug lines of the entities belonging to player $PORT, starting from 1. `all` displays every entity again",
+        return_string
+    ),
+    NodeAction(
+        function = "debug_summary",
+        help = "toggle displaying a single line summary of each entity instead of its enabled debug lines",
+        return_string
+    ),
     NodeAction(function = "copy_stage_to_package", return_string),
-    NodeAction(function = "copy_package_to_stage", return_string)
+    NodeAction(function = "copy_package_to_stage", return_string),
+    NodeAction(function = "schema", return_string)
 )]
 pub struct Game {
     pub package: Package,
@@ -58,11 +145,70 @@ pub struct Game {
     pub saved_frame: usize,
     pub deleted_history_frames: usize,
     pub max_history_frames: Option<usize>,
+    pub replay_speed: f32,
+    replay_frame_progress: f32,
+    /// Speed of local games relative to 60 frames per second, useful for training.
+    /// Netplay always runs at full speed so that peers stay in sync.
+    pub time_scale: f32,
+    time_scale_progress: f32,
+    /// Simulated frames of slow motion remaining before the results are displayed, after the final stock is taken
+    final_kill_frames: Option<u64>,
+    /// The countdown announces "GO" on this frame, when the slowest fighter finishes spawning
+    #[serde(skip)]
+    countdown_go_frame: usize,
+    /// The last frame the countdown was announced on, so resimulated frames are not announced again
+    #[serde(skip)]
+    announced_frame: usize,
+    /// How CPUs with the Idle difficulty behave, replays only need the inputs they generate
+    #[serde(skip)]
+    dummy: DummyBehavior,
+    /// Every fighter displays its ledge timers, toggled from the pause menu
+    #[serde(skip)]
+    ledge_timers: bool,
+    /// Text displayed by frame events along with the frames left to display it
+    #[serde(skip)]
+    screen_text: Vec<(String, u64)>,
+    /// Lines describing recent deaths along with the frames left to display them
+    #[serde(skip)]
+    kill_feed: Vec<(String, u64)>,
+    /// Events that occurred during this step, sent to tools connected to the WebSocket command interface
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+    /// The last frame events were reported on, so resimulated frames are not reported again
+    #[serde(skip)]
+    reported_frame: usize,
+    /// Set while a telemetry client is connected
+    #[serde(skip)]
+    pub record_telemetry: bool,
+    /// The state of every frame simulated during this step, when `record_telemetry` is set
+    #[serde(skip)]
+    telemetry: Vec<TelemetryFrame>,
+    #[serde(skip)]
+    tutorial: Option<Tutorial>,
+    /// The rewind hotkey is held
+    #[serde(skip)]
+    rewinding: bool,
+    /// The frame the current rewind started from
+    #[serde(skip)]
+    rewind_start: Option<usize>,
+    /// Exists while the game is paused
+    #[serde(skip)]
+    pause_menu: Option<PauseMenu>,
+    /// Number of times each controller has paused, limits pausing when the rules give out pause tokens
+    pauses_used: HashMap<usize, u64>,
+    /// The minimap zoom each controller's player picked from the pause menu, other players use the configured zoom
+    #[serde(skip)]
+    minimaps: HashMap<usize, Minimap>,
     pub stage: Stage,
     entities: Entities,
     pub debug_stage: DebugStage,
     debug_entities: DebugEntities,
+    /// Only the debug lines of entities belonging to these players are displayed, every entity is displayed when empty
+    pub debug_ports: Vec<usize>,
+    /// Display a single line summary of each entity instead of its enabled debug lines
+    pub debug_summary: bool,
     pub selected_controllers: Vec<usize>,
+    pub selected_players: Vec<PlayerSetup>,
     pub selected_ais: Vec<usize>,
     pub selected_stage: String,
     pub rules: Rules,
@@ -75,10 +221,48 @@ pub struct Game {
     pub tas: Vec<ControllerInput>,
     bgm_metadata: Option<BGMMetadata>,
     save_replay: bool,
+    /// Number of frames and file name of a bug report to save at the next step
+    dump_last_inputs: Option<(usize, String)>,
+    /// Path to save the current frame to at the next step, so the game can be continued from it
+    save_resume: Option<String>,
     reset_deadzones: bool,
+    toggle_mute: bool,
+    /// Frames to step the paused game by at the next step, negative steps backwards
+    paused_frame_steps: i64,
     prev_mouse_point: Option<(f32, f32)>,
+    screenshot: bool,
+    #[serde(skip)]
+    scripts: Scripts,
+    #[serde(skip)]
+    editor: FrameDataEditor,
 }
 
+/// Furthest a single rewind can go back
+const REWIND_MAX_FRAMES: usize = 60 * 5;
+
+/// Playback speeds that can be selected while watching a replay or playing a local game
+const REPLAY_SPEEDS: [f32; 4] = [0.25, 0.5, 1.0, 2.0];
+/// Fastest speed local games can be set to
+const MAX_TIME_SCALE: f32 = 4.0;
+/// Most frames simulated per step, so a high speed cannot stall rendering
+const MAX_FRAMES_PER_STEP: usize = 4;
+/// Local games slow down to this speed after the final stock is taken
+const FINAL_KILL_TIME_SCALE: f32 = 0.25;
+/// Number of simulated frames the final kill is played in slow motion for
+const FINAL_KILL_FRAMES: u64 = 40;
+/// The music switches to its climax layer once this many frames of the time limit remain
+const CLIMAX_FRAMES: u64 = 60 * 30;
+/// Frames that text displayed by a frame event stays on screen
+const SCREEN_TEXT_FRAMES: u64 = 180;
+/// Frames that each line of the kill feed stays on screen
+const KILL_FEED_FRAMES: u64 = 240;
+/// Items stop spawning from the stage's item table while this many items exist
+const MAX_SPAWNED_ITEMS: usize = 4;
+/// Items spawn this far above the floor they are spawned over
+const ITEM_SPAWN_HEIGHT: f32 = 40.0;
+/// Entities within this distance of a fighter are simulated every frame regardless of their simulation_lod
+const SIMULATION_LOD_DISTANCE: f32 = 150.0;
+
 /// Frame 0 refers to the initial state of the game.
 /// Any changes occur in the proceeding frames i.e. frames 1, 2, 3 ...
 
@@ -102,30 +286,12 @@ impl Game {
 
         // generate players
         let mut entities: Entities = Default::default();
-        {
-            for (i, player) in setup.players.iter().enumerate() {
-                // Stage can have less spawn points then players
-                let fighter_key = player.fighter.clone();
-                let entity_def = &package.entities[fighter_key.as_ref()];
-                let team = player.team;
-
-                if let EntityDefType::Fighter(fighter_def) = &entity_def.ty {
-                    let player = Player::new(
-                        fighter_key.as_ref(),
-                        team,
-                        i,
-                        &stage,
-                        &package,
-                        &setup.rules,
-                    );
-                    let fighter = match fighter_def.ty {
-                        FighterType::Toriel => Fighter::Toriel(Toriel::new(player)),
-                        FighterType::Dave => Fighter::Toriel(Toriel::new(player)),
-                    };
-                    let ty = EntityType::Fighter(fighter);
-                    let state = ActionState::new(fighter_key, PlayerAction::Spawn);
-                    entities.insert(Entity { ty, state });
-                }
+        for (i, player) in setup.players.iter().enumerate() {
+            let fighter_key = &player.fighter;
+            let player = Player::new(player, i, &stage, &package, &setup.rules);
+            if let Some(entity) = fighter_entity(player, fighter_key, &package, PlayerAction::Spawn)
+            {
+                entities.insert(entity);
             }
         }
 
@@ -145,7 +311,22 @@ impl Game {
             entities = overwrite;
         }
 
+        let spawn: &str = PlayerAction::Spawn.into();
+        let countdown_go_frame = setup
+            .players
+            .iter()
+            .filter_map(|x| {
+                package.entities[x.fighter.as_ref()]
+                    .actions
+                    .key_to_value(spawn)
+            })
+            .map(|x| x.frames.len())
+            .max()
+            .unwrap_or(0)
+            + 1;
+
         let bgm_metadata = Some(audio.play_bgm(&stage.name));
+        let tutorial = setup.rules.tutorial.map(Tutorial::new);
 
         Game {
             init_seed: setup.init_seed,
@@ -156,7 +337,29 @@ impl Game {
             saved_frame: 0,
             max_history_frames: setup.max_history_frames,
             deleted_history_frames: setup.deleted_history_frames,
+            replay_speed: 1.0,
+            replay_frame_progress: 0.0,
+            time_scale: 1.0,
+            time_scale_progress: 0.0,
+            final_kill_frames: None,
+            countdown_go_frame,
+            announced_frame: 0,
+            dummy: DummyBehavior::default(),
+            ledge_timers: false,
+            screen_text: vec![],
+            kill_feed: vec![],
+            events: vec![],
+            reported_frame: 0,
+            record_telemetry: false,
+            telemetry: vec![],
+            tutorial,
+            rewinding: false,
+            rewind_start: None,
+            pause_menu: None,
+            pauses_used: HashMap::new(),
+            minimaps: HashMap::new(),
             selected_controllers: setup.controllers,
+            selected_players: setup.players,
             selected_ais: setup.ais,
             selected_stage: setup.stage,
             rules: setup.rules,
@@ -168,18 +371,29 @@ impl Game {
             camera: setup.camera,
             tas: vec![],
             save_replay: false,
+            dump_last_inputs: None,
+            save_resume: None,
             reset_deadzones: false,
+            toggle_mute: false,
+            paused_frame_steps: 0,
             prev_mouse_point: None,
+            screenshot: false,
+            scripts: Scripts::default(),
+            editor: FrameDataEditor::default(),
             bgm_metadata,
             package,
             stage,
             entities,
             debug_stage,
             debug_entities,
+            debug_ports: vec![],
+            debug_summary: false,
         }
     }
 
+    #[cfg(feature = "window")]
     #[rustfmt::skip]
+    #[allow(clippy::too_many_arguments)]
     pub fn step(
         &mut self,
         config: &mut Config,
@@ -188,21 +402,56 @@ impl Game {
         os_input_blocked: bool,
         netplay: &Netplay,
         audio: &mut Audio,
+        toasts: &mut Toasts,
     ) -> GameState {
         if os_input.held_alt() && os_input.key_pressed_os(VirtualKeyCode::Return) {
             config.fullscreen = !config.fullscreen;
             config.save();
         }
 
+        let editor_open = matches!(self.state, GameState::Paused) && self.camera.dev_mode();
+        self.rewinding = false;
+        self.events.clear();
+        self.telemetry.clear();
+        if !os_input_blocked && !editor_open {
+            self.step_hotkeys(config, os_input);
+        }
+
         if self.save_replay {
-            replays::save_replay(&Replay::new(self, input));
+            replays::save_replay(&Replay::new(self, input.history()));
+            toasts.push(String::from("Replay saved"));
             self.save_replay = false;
         }
 
+        if self.toggle_mute {
+            config.audio.mute = !config.audio.mute;
+            config.save();
+            toasts.push(String::from(if config.audio.mute {
+                "Audio muted"
+            } else {
+                "Audio unmuted"
+            }));
+            self.toggle_mute = false;
+        }
+
+        if let Some((frames, name)) = self.dump_last_inputs.take() {
+            let mut replay = Replay::new(self, input.history());
+            replay.trim_history(frames);
+            replays::save_named_replay(&replay, &name);
+            toasts.push(format!("Saved \"{}\"", name));
+        }
+
+        if let Some(path) = self.save_resume.take() {
+            match replays::save_replay_path(Path::new(&path), &Replay::new(self, input.history())) {
+                Ok(()) => toasts.push(format!("Saved \"{}\", continue with --resume", path)),
+                Err(err) => toasts.push(format!("Failed to save \"{}\": {}", path, err)),
+            }
+        }
+
         {
             let state = self.state.clone();
             match state {
-                GameState::Local                     => self.step_local(input, netplay, audio),
+                GameState::Local                     => self.step_local_scaled(input, netplay, audio),
                 GameState::Netplay                   => self.step_netplay(input, netplay, audio),
                 GameState::ReplayForwardsFromHistory => self.step_replay_forwards_from_history(input),
                 GameState::ReplayForwardsFromInput   => self.step_replay_forwards_from_input(input, netplay, audio),
@@ -210,11 +459,12 @@ impl Game {
                 GameState::StepThenPause             => { self.step_local(input, netplay, audio); self.state = GameState::Paused; }
                 GameState::StepForwardThenPause      => { self.step_replay_forwards_from_history(input); self.state = GameState::Paused; }
                 GameState::StepBackwardThenPause     => { self.step_replay_backwards(input); self.state = GameState::Paused; }
-                GameState::Paused                    => self.step_pause(input),
+                GameState::Paused                    => self.step_pause(input, netplay, audio, &config.hud.minimap),
                 GameState::Quit (_)                  => unreachable!(),
             }
 
             if !os_input_blocked {
+                self.editor.captured_scroll = false;
                 match state {
                     GameState::Local                     => self.step_local_os_input(os_input),
                     GameState::ReplayForwardsFromHistory => self.step_replay_forwards_os_input(os_input),
@@ -226,7 +476,7 @@ impl Game {
                     GameState::Netplay              | GameState::StepThenPause |
                     GameState::StepForwardThenPause | GameState::StepBackwardThenPause => { }
                 }
-                self.camera.update_os_input(os_input);
+                self.camera.update_os_input(os_input, !self.editor.captured_scroll);
                 self.prev_mouse_point = os_input.mouse();
             }
             self.camera.update(
@@ -234,23 +484,83 @@ impl Game {
                 &self.entities,
                 &self.package.entities,
                 &self.stage,
+                self.rules.modifiers.fixed_camera,
             );
 
             self.generate_debug(input, netplay);
         }
 
+        if !matches!(self.state, GameState::Paused) {
+            self.pause_menu = None;
+        }
+
+        // Also saves a completed tutorial starting over, so the restarted progress is resumed next time.
+        // Replays of tutorials are not the player's own progress.
+        if let (Some(tutorial), GameState::Local) = (&self.tutorial, &self.state) {
+            if tutorial.progress() != config.tutorial_progress {
+                config.tutorial_progress = tutorial.progress();
+                config.save();
+            }
+        }
+
         self.set_context();
 
         debug!("current_frame: {}", self.current_frame);
         self.state.clone()
     }
 
+    #[cfg(feature = "window")]
+    fn step_hotkeys(&mut self, config: &mut Config, os_input: &WinitInputHelper) {
+        if config.hotkeys.pressed(HotkeyAction::Fullscreen, os_input) {
+            config.fullscreen = !config.fullscreen;
+            config.save();
+        }
+
+        if config.hotkeys.pressed(HotkeyAction::Screenshot, os_input) {
+            self.screenshot = true;
+        }
+
+        if config.hotkeys.pressed(HotkeyAction::Minimap, os_input) {
+            config.hud.minimap = config.hud.minimap.next();
+            config.save();
+        }
+
+        if config.hotkeys.pressed(HotkeyAction::Debug, os_input) {
+            if self.debug_entities.is_empty() {
+                for key in self.entities.keys() {
+                    self.debug_entities.insert(key, DebugEntity::all());
+                }
+                self.debug_stage = DebugStage::all();
+            } else {
+                self.debug_entities.clear();
+                self.debug_stage = DebugStage::default();
+            }
+        }
+
+        self.rewinding = self.rules.rewind && config.hotkeys.held(HotkeyAction::Rewind, os_input);
+
+        if config.hotkeys.pressed(HotkeyAction::Pause, os_input) {
+            let pause_locked = !matches!(self.rules.pause, Pause::On | Pause::Hold);
+            match self.state {
+                GameState::Local if pause_locked => {}
+                GameState::Local
+                | GameState::ReplayForwardsFromHistory
+                | GameState::ReplayForwardsFromInput
+                | GameState::ReplayBackwards => self.state = GameState::Paused,
+                GameState::Paused => self.state = GameState::Local,
+                _ => {}
+            }
+        }
+    }
+
+    #[cfg(feature = "window")]
     fn game_mouse(&self, os_input: &WinitInputHelper) -> Option<(f32, f32)> {
         os_input
             .mouse()
             .and_then(|point| self.camera.mouse_to_game(point))
     }
 
+    #[cfg(feature = "window")]
     fn game_mouse_diff(&self, os_input: &WinitInputHelper) -> (f32, f32) {
         if let (Some(cur), Some(prev)) = (os_input.mouse(), self.prev_mouse_point) {
             if let (Some(cur), Some(prev)) = (
@@ -270,21 +580,224 @@ impl Game {
         String::from("Save replay completed")
     }
 
+    /// Bundles the recent history with the inputs and current state needed to reproduce it.
+    /// The bundle is saved as a replay so it can be opened from the replay menu or with --replay.
+    pub fn dump_last_inputs(&mut self, seconds: String) -> String {
+        match seconds.parse::<f32>() {
+            Ok(seconds) if seconds > 0.0 => {
+                let frames = (seconds * 60.0).ceil() as usize;
+                let name = format!("bug report {}", Local::now().format("%Y-%m-%d %H-%M-%S"));
+                let message = format!(
+                    "Saving the last {} frames to the replays folder as \"{}\"",
+                    frames, name
+                );
+                self.dump_last_inputs = Some((frames, name));
+                message
+            }
+            _ => format!(
+                "Expected a positive number of seconds but was '{}'",
+                seconds
+            ),
+        }
+    }
+
+    /// Saved as a replay that boots straight into the game at the current frame, instead of playing from the start.
+    /// The save is delayed until the next step where the inputs are available.
+    pub fn save_resume(&mut self, path: String) -> String {
+        let message = format!("Saving the current frame to \"{}\"", path);
+        self.save_resume = Some(path);
+        message
+    }
+
+    pub fn set_speed(&mut self, speed: String) -> String {
+        match speed.parse::<f32>() {
+            Ok(speed) if speed.is_finite() && speed > 0.0 => {
+                self.time_scale = speed.min(MAX_TIME_SCALE);
+                format!("Local games now run at {}x speed", self.time_scale)
+            }
+            _ => format!(
+                "Expected a positive speed up to {} but was '{}'",
+                MAX_TIME_SCALE, speed
+            ),
+        }
+    }
+
     pub fn reset_deadzones(&mut self) -> String {
         self.reset_deadzones = true;
         String::from("Deadzones reset")
     }
 
+    pub fn toggle_mute(&mut self) -> String {
+        self.toggle_mute = true;
+        String::from("Audio mute toggled")
+    }
+
+    pub fn step_frames(&mut self, frames: String) -> String {
+        match frames.parse::<i64>() {
+            Ok(frames) if matches!(self.state, GameState::Paused) => {
+                self.paused_frame_steps += frames;
+                format!("Stepping {} frames", frames)
+            }
+            Ok(_) => String::from("Frames can only be stepped while the game is paused"),
+            Err(_) => format!("Expected a number of frames but was '{}'", frames),
+        }
+    }
+
+    /// Spawns an entity for testing items and projectiles without needing a fighter to spawn them.
+    /// Spawned projectiles are stationary.
+    pub fn spawn(&mut self, kind: String, entity: String, x: String, y: String) -> String {
+        match (x.parse::<f32>(), y.parse::<f32>()) {
+            (Ok(x), Ok(y)) => self.spawn_entity(&kind, entity, x, y),
+            _ => format!("Expected a position but was '{}' '{}'", x, y),
+        }
+    }
+
+    fn spawn_entity(&mut self, kind: &str, entity: String, x: f32, y: f32) -> String {
+        // the .cbor extension can be left off
+        let entity_def_key = if self.package.entities.contains_key(&entity) {
+            entity
+        } else {
+            format!("{}.cbor", entity)
+        };
+        let ty = match self.package.entities.key_to_value(&entity_def_key) {
+            Some(entity_def) => &entity_def.ty,
+            None => return format!("There is no entity {}", entity_def_key),
+        };
+
+        let new_entity = match (kind, ty) {
+            ("item", EntityDefType::Item) | ("item", EntityDefType::AssistItem(_)) => Entity {
+                ty: EntityType::Item(Item {
+                    owner_id: None,
+                    body: Body::new(Location::Airbourne { x, y }, true),
+                }),
+                state: ActionState::new(entity_def_key.clone(), ItemAction::Fall),
+            },
+            ("projectile", EntityDefType::Projectile) => Entity {
+                ty: EntityType::Projectile(Projectile {
+                    owner_id: None,
+                    angle: 0.0,
+                    speed: 0.0,
+                    x,
+                    y,
+                }),
+                state: ActionState::new(entity_def_key.clone(), ProjectileAction::Spawn),
+            },
+            ("item", _) | ("projectile", _) => {
+                return format!("{} is not a {}", entity_def_key, kind)
+            }
+            _ => return format!("Expected item or projectile but was '{}'", kind),
+        };
+        let key = self.entities.insert(new_entity);
+        format!("Spawned {} as {:?}", entity_def_key, key.data())
+    }
+
+    /// Every live entity with the key used to despawn it
+    pub fn list_entities(&mut self) -> String {
+        let mut lines = vec![];
+        for (key, entity) in &self.entities {
+            let (x, y) =
+                entity.public_bps_xy(&self.entities, &self.package.entities, &self.stage.surfaces);
+            lines.push(format!(
+                "{:?}  {} {}  action: {}  x: {:.2}  y: {:.2}",
+                key.data(),
+                entity.ty.name(),
+                entity.state.entity_def_key,
+                entity.state.action,
+                x,
+                y
+            ));
+        }
+        lines.join("\n")
+    }
+
+    pub fn despawn(&mut self, key: String) -> String {
+        match self
+            .entities
+            .keys()
+            .find(|x| format!("{:?}", x.data()) == key)
+        {
+            Some(entity_key) => self.despawn_entity(entity_key),
+            None => format!("There is no entity {}", key),
+        }
+    }
+
+    fn despawn_entity(&mut self, key: EntityKey) -> String {
+        match self.entities.get(key) {
+            Some(entity) if entity.ty.get_player().is_some() => {
+                String::from("Fighters cannot be despawned")
+            }
+            Some(_) => {
+                self.entities.remove(key);
+                despawn_orphans(&mut self.entities);
+                format!("Despawned {:?}", key.data())
+            }
+            None => format!("There is no entity {:?}", key.data()),
+        }
+    }
+
+    pub fn debug_port(&mut self, port: String) -> String {
+        if port == "all" {
+            self.debug_ports.clear();
+            return String::from("Displaying the debug lines of every entity");
+        }
+        let player_id = match port.parse::<usize>() {
+            Ok(port) if port > 0 => port - 1,
+            _ => {
+                return format!(
+                    "Expected a port starting from 1 or `all` but was '{}'",
+                    port
+                )
+            }
+        };
+
+        if let Some(i) = self.debug_ports.iter().position(|x| *x == player_id) {
+            self.debug_ports.remove(i);
+        } else {
+            self.debug_ports.push(player_id);
+            self.debug_ports.sort_unstable();
+        }
+
+        if self.debug_ports.is_empty() {
+            String::from("Displaying the debug lines of every entity")
+        } else {
+            let ports: Vec<String> = self
+                .debug_ports
+                .iter()
+                .map(|x| (x + 1).to_string())
+                .collect();
+            format!("Displaying the debug lines of ports {}", ports.join(", "))
+        }
+    }
+
+    pub fn debug_summary(&mut self) -> String {
+        self.debug_summary = !self.debug_summary;
+        if self.debug_summary {
+            String::from("Debug lines summarized to one line per entity")
+        } else {
+            String::from("Debug lines no longer summarized")
+        }
+    }
+
     pub fn copy_stage_to_package(&mut self) -> String {
-        self.package.stages[self.selected_stage.as_ref()] = self.stage.clone();
+        let mut stage = self.stage.clone();
+        stage.remove_path_offsets(self.current_frame);
+        self.package.stages[self.selected_stage.as_ref()] = stage;
         String::from("Current stage state copied to package")
     }
 
     pub fn copy_package_to_stage(&mut self) -> String {
         self.stage = self.package.stages[self.selected_stage.as_ref()].clone();
+        self.stage.apply_path_offsets(self.current_frame);
         String::from("Package copied to current stage state")
     }
 
+    /// Every property of the game and package with its type and documentation as JSON
+    pub fn schema(&mut self) -> String {
+        let docs: Vec<(&str, &str, &str)> =
+            NODE_DOCS.iter().chain(schema::NODE_DOCS).cloned().collect();
+        schema::generate(&mut self.clone(), &docs)
+    }
+
     pub fn check_reset_deadzones(&mut self) -> bool {
         let value = self.reset_deadzones;
         self.reset_deadzones = false;
@@ -333,7 +846,96 @@ impl Game {
         }
     }
 
+    /// Steps the local game as many times as the time scale calls for, which is 0 on some steps when slowed down.
+    fn step_local_scaled(&mut self, input: &mut Input, netplay: &Netplay, audio: &mut Audio) {
+        if self.rewinding {
+            self.step_rewind();
+            return;
+        }
+        if self.rewind_start.take().is_some() {
+            // the rewound frames are overwritten, so their events are fired again
+            self.reported_frame = self.current_frame;
+        }
+
+        let time_scale = self.time_scale * self.rules.modifiers.speed.time_scale();
+        let time_scale = if self.final_kill_frames.is_some() {
+            time_scale.min(FINAL_KILL_TIME_SCALE)
+        } else {
+            time_scale
+        };
+        let frames = frames_to_advance(&mut self.time_scale_progress, time_scale);
+        if frames == 0 {
+            input.game_skip();
+        }
+        for _ in 0..frames {
+            if !matches!(self.state, GameState::Local) {
+                break;
+            }
+            self.step_local(input, netplay, audio);
+        }
+    }
+
+    /// Rewinds one frame, up to REWIND_MAX_FRAMES before the frame the rewind started from.
+    /// Once the rewind hotkey is released play resumes from the current frame, overwriting the rewound frames.
+    fn step_rewind(&mut self) {
+        let start = *self.rewind_start.get_or_insert(self.current_frame);
+        let first_frame = start
+            .saturating_sub(REWIND_MAX_FRAMES)
+            .max(self.deleted_history_frames);
+        if self.current_frame > first_frame {
+            self.replay_seek(self.current_frame - 1);
+        }
+    }
+
     fn step_local(&mut self, input: &mut Input, netplay: &Netplay, audio: &mut Audio) {
+        self.advance_history();
+
+        // run game loop
+        input.game_update(self.current_frame);
+        let player_inputs = &input.players(self.current_frame, netplay);
+        // a controller without a player joins the game instead of pausing it
+        let joining = self.rules.late_join
+            && input
+                .start_pressed_controller()
+                .map_or(false, |x| !self.controller_has_player(x));
+        self.step_game(input.history(), player_inputs, audio);
+        self.trim_history();
+
+        // pause game
+        if input.start_pressed() && !joining {
+            let controller = input.start_pressed_controller();
+            let pauses_used = controller
+                .and_then(|x| self.pauses_used.get(&x))
+                .cloned()
+                .unwrap_or(0);
+            if self.rules.pause.allowed(pauses_used) {
+                if let Some(controller) = controller {
+                    *self.pauses_used.entry(controller).or_insert(0) += 1;
+                }
+                self.state = GameState::Paused;
+                self.pause_menu = Some(PauseMenu::new(
+                    controller,
+                    input.mirror_controller.is_some(),
+                    self.dummy,
+                ));
+            } else {
+                self.screen_text
+                    .push((String::from("Pausing is locked"), SCREEN_TEXT_FRAMES));
+            }
+        }
+    }
+
+    /// Steps a single frame using the inputs recorded for it in `input_history`.
+    /// Unlike a local game there is no pausing, hotkeys or controllers, the caller is in full control.
+    pub fn step_simulation(&mut self, input_history: &[Vec<ControllerInput>], audio: &mut Audio) {
+        self.advance_history();
+        let player_inputs = &Input::players_from_history(input_history, self.current_frame);
+        self.step_game(input_history, player_inputs, audio);
+        self.trim_history();
+    }
+
+    /// Saves the current frame to history before moving to the next frame
+    fn advance_history(&mut self) {
         self.entity_history.push(self.entities.clone());
         self.stage_history.push(self.stage.clone());
         self.current_frame += 1;
@@ -345,12 +947,10 @@ impl Game {
         for _ in self.current_history_index()..self.stage_history.len() {
             self.stage_history.pop();
         }
+    }
 
-        // run game loop
-        input.game_update(self.current_frame);
-        let player_inputs = &input.players(self.current_frame, netplay);
-        self.step_game(input, player_inputs, audio);
-
+    /// Deletes the oldest frames of history beyond max_history_frames
+    fn trim_history(&mut self) {
         if let Some(max_history_frames) = self.max_history_frames {
             let extra_frames = self.entity_history.len().saturating_sub(max_history_frames);
             self.deleted_history_frames += extra_frames;
@@ -359,55 +959,204 @@ impl Game {
                 self.stage_history.drain(0..extra_frames);
             }
         }
-
-        // pause game
-        if input.start_pressed() {
-            self.state = GameState::Paused;
-        }
     }
 
+    #[cfg(feature = "window")]
     fn step_local_os_input(&mut self, os_input: &WinitInputHelper) {
         if os_input.key_pressed_os(VirtualKeyCode::Space)
             || os_input.key_pressed_os(VirtualKeyCode::Return)
         {
             self.state = GameState::Paused;
         }
+
+        // Minus/Equals: decrease/increase game speed
+        let speed_i = REPLAY_SPEEDS
+            .iter()
+            .position(|x| *x >= self.time_scale)
+            .unwrap_or(REPLAY_SPEEDS.len() - 1);
+        if os_input.key_pressed_os(VirtualKeyCode::Minus) {
+            self.time_scale = REPLAY_SPEEDS[speed_i.saturating_sub(1)];
+        }
+        if os_input.key_pressed_os(VirtualKeyCode::Equals) {
+            self.time_scale = REPLAY_SPEEDS[(speed_i + 1).min(REPLAY_SPEEDS.len() - 1)];
+        }
     }
 
     fn step_netplay(&mut self, input: &mut Input, netplay: &Netplay, audio: &mut Audio) {
         if !netplay.skip_frame() {
-            self.current_frame += 1;
-
-            let start = self.current_frame - netplay.frames_to_step();
-            let end = self.current_frame;
+            let end = self.current_frame + 1;
 
-            self.entity_history.truncate(start);
-            self.stage_history.truncate(start);
-            if start != 0 {
-                self.entities = self.entity_history.get(start - 1).unwrap().clone();
-                self.stage = self.stage_history.get(start - 1).unwrap().clone();
+            // rollback to the last frame that was stepped with correct inputs
+            let start = input.netplay_update(netplay).min(end);
+            if start < end {
+                self.jump_frame(start - 1);
             }
+            self.entity_history.truncate(self.current_history_index());
+            self.stage_history.truncate(self.current_history_index());
 
-            input.netplay_update();
+            for frame in start..=end {
+                self.entity_history.push(self.entities.clone());
+                self.stage_history.push(self.stage.clone());
+                self.current_frame = frame;
 
-            for frame in start..end {
                 let player_inputs = &input.players(frame, netplay);
-                self.step_game(input, player_inputs, audio);
+                self.step_game(input.history(), player_inputs, audio);
+            }
+        }
+    }
 
-                self.entity_history.push(self.entities.clone());
-                self.stage_history.push(self.stage.clone());
+    /// `minimap` is the configured minimap zoom, used by players that have not picked their own
+    fn step_pause(
+        &mut self,
+        input: &mut Input,
+        netplay: &Netplay,
+        audio: &mut Audio,
+        minimap: &Minimap,
+    ) {
+        let mirrored = input.mirror_controller.is_some();
+        let dummy = self.dummy;
+        let confirming_unsaved = self
+            .pause_menu
+            .as_ref()
+            .map_or(false, |x| x.confirming_unsaved());
+        if input.game_quit_held() && !confirming_unsaved {
+            if self.package.has_unsaved_changes() {
+                self.pause_menu
+                    .get_or_insert_with(|| PauseMenu::new(None, mirrored, dummy))
+                    .confirm_unsaved(false);
+            } else {
+                self.state = GameState::Quit(ResumeMenu::Unchanged);
+            }
+            return;
+        }
+
+        let steps = std::mem::take(&mut self.paused_frame_steps);
+        for _ in 0..steps.max(0) {
+            self.step_paused_forward(input, netplay, audio, None);
+        }
+        for _ in steps.min(0)..0 {
+            self.step_paused_backward();
+        }
+
+        // the editor takes over the paused game in dev mode, unless asking to save before quitting
+        if self.camera.dev_mode() && !confirming_unsaved {
+            if input.start_pressed() {
+                self.state = GameState::Local;
+            }
+            return;
+        }
+
+        let pause_menu = self
+            .pause_menu
+            .get_or_insert_with(|| PauseMenu::new(None, mirrored, dummy));
+        let package = &self.package;
+        let entities = &self.entities;
+        match pause_menu.step(
+            input.paused_players(),
+            || package.has_unsaved_changes(),
+            || entity_options(package, entities),
+        ) {
+            Some(PauseAction::Resume) => self.state = GameState::Local,
+            Some(PauseAction::ToggleHitboxes) => self.toggle_hitboxes(),
+            Some(PauseAction::ToggleLedgeTimers) => self.ledge_timers = !self.ledge_timers,
+            Some(PauseAction::CycleMinimap(controller)) => {
+                let controllers = match controller {
+                    Some(controller) => vec![controller],
+                    None => self.selected_controllers.clone(),
+                };
+                for controller in controllers {
+                    let next = self.minimap(controller, minimap).next();
+                    self.minimaps.insert(controller, next);
+                }
+            }
+            Some(PauseAction::MirrorInputs(controller)) => input.mirror_controller = controller,
+            Some(PauseAction::Dummy(dummy)) => self.dummy = dummy,
+            Some(PauseAction::Entity(EntityAction::Spawn { kind, entity })) => {
+                // spawned in the middle of the screen
+                let (x, y) = self.camera.rect.center();
+                let message = self.spawn_entity(kind, entity, x, y);
+                info!("{}", message);
             }
+            Some(PauseAction::Entity(EntityAction::Despawn(key))) => {
+                let message = self.despawn_entity(key);
+                info!("{}", message);
+            }
+            Some(PauseAction::StepForward { frames, controller }) => {
+                for _ in 0..frames {
+                    self.step_paused_forward(input, netplay, audio, Some(controller));
+                }
+            }
+            Some(PauseAction::StepBackward) => self.step_paused_backward(),
+            Some(PauseAction::Leave {
+                character_select,
+                save,
+            }) => {
+                if save {
+                    self.package.save();
+                }
+                self.state = GameState::Quit(if character_select {
+                    ResumeMenu::CharacterSelect
+                } else {
+                    ResumeMenu::Unchanged
+                });
+            }
+            None => {}
         }
     }
 
-    fn step_pause(&mut self, input: &mut Input) {
-        if input.game_quit_held() {
-            self.state = GameState::Quit(ResumeMenu::Unchanged);
-        } else if input.start_pressed() {
-            self.state = GameState::Local;
+    /// Moves the paused game forward through its history, simulating a new frame once the end of the history is reached.
+    /// The frame control buttons of `controller` are released on the new frame, so its fighter does not also grab or shield.
+    fn step_paused_forward(
+        &mut self,
+        input: &mut Input,
+        netplay: &Netplay,
+        audio: &mut Audio,
+        controller: Option<usize>,
+    ) {
+        match self.replay_last_frame() {
+            Some(last_frame) if self.current_frame < last_frame => {
+                self.replay_seek(self.current_frame + 1)
+            }
+            _ => {
+                self.advance_history();
+                input.game_update(self.current_frame);
+                if let Some(controller) = controller {
+                    input.release_frame_controls(self.current_frame, controller);
+                }
+                let player_inputs = &input.players(self.current_frame, netplay);
+                self.step_game(input.history(), player_inputs, audio);
+                self.trim_history();
+            }
+        }
+    }
+
+    /// Moves the paused game back a frame through its history
+    fn step_paused_backward(&mut self) {
+        if self.current_frame > self.deleted_history_frames {
+            self.replay_seek(self.current_frame - 1);
         }
     }
 
+    /// Hitboxes are displayed when any entity renders its collision boxes
+    fn hitboxes_displayed(&self) -> bool {
+        self.debug_entities.values().any(|x| x.render.debug())
+    }
+
+    fn toggle_hitboxes(&mut self) {
+        let render = if self.hitboxes_displayed() {
+            RenderDebugType::Normal
+        } else {
+            RenderDebugType::NormalAndDebug
+        };
+        for key in self.entities.keys() {
+            if !self.debug_entities.contains_key(key) {
+                self.debug_entities.insert(key, DebugEntity::default());
+            }
+            self.debug_entities[key].render = render;
+        }
+    }
+
+    #[cfg(feature = "window")]
     fn step_pause_os_input(
         &mut self,
         input: &mut Input,
@@ -443,6 +1192,7 @@ impl Game {
         }
     }
 
+    #[cfg(feature = "window")]
     fn step_editor(
         &mut self,
         input: &mut Input,
@@ -500,6 +1250,14 @@ impl Game {
             self.update_frame();
         }
 
+        // Delete: despawn the entity being edited
+        if let Edit::Entity(entity_i) = self.edit {
+            if os_input.key_pressed_os(VirtualKeyCode::Delete) {
+                let message = self.despawn_entity(entity_i);
+                info!("{}", message);
+            }
+        }
+
         match self.edit {
             Edit::Entity(entity_i) => {
                 if self.entities.contains_key(entity_i) {
@@ -523,7 +1281,11 @@ impl Game {
                             debug_entity.physics = !debug_entity.physics;
                         }
                         if os_input.key_pressed_os(VirtualKeyCode::F3) {
-                            debug_entity.frame = !debug_entity.frame;
+                            if os_input.held_shift() {
+                                debug_entity.ledge_timers = !debug_entity.ledge_timers;
+                            } else {
+                                debug_entity.frame = !debug_entity.frame;
+                            }
                         }
                         if os_input.key_pressed_os(VirtualKeyCode::F4) {
                             if os_input.held_shift() {
@@ -540,7 +1302,11 @@ impl Game {
                             debug_entity.di_vector = !debug_entity.di_vector;
                         }
                         if os_input.key_pressed_os(VirtualKeyCode::F7) {
-                            debug_entity.hitbox_vectors = !debug_entity.hitbox_vectors;
+                            if os_input.held_shift() {
+                                debug_entity.colbox_overlay = !debug_entity.colbox_overlay;
+                            } else {
+                                debug_entity.hitbox_vectors = !debug_entity.hitbox_vectors;
+                            }
                         }
                         if os_input.key_pressed_os(VirtualKeyCode::F8) {
                             debug_entity.ecb = !debug_entity.ecb;
@@ -578,28 +1344,57 @@ impl Game {
 
                     // move collisionboxes
                     if self.selector.moving {
-                        // undo the operations used to render the entity
-                        let (raw_d_x, raw_d_y) = self.game_mouse_diff(os_input);
-                        let angle = -self.entities[entity_i].frame_angle(
-                            &self.package.entities[entity_def_key],
-                            &self.stage.surfaces,
-                        ); // rotate by the inverse of the angle
-                        let d_x = raw_d_x * angle.cos() - raw_d_y * angle.sin();
-                        let d_y = raw_d_x * angle.sin() + raw_d_y * angle.cos();
-                        let distance = (self.entities[entity_i].relative_f(d_x), d_y); // *= -1 is its own inverse
-                        self.package.move_fighter_colboxes(
+                        self.move_selected_colboxes(entity_i, os_input);
+
+                        // end move
+                        if os_input.mouse_pressed(0) {
+                            self.update_frame();
+                        }
+                    } else if self.editor.dragging {
+                        if os_input.mouse_held(0) {
+                            self.move_selected_colboxes(entity_i, os_input);
+                        } else {
+                            // end drag
+                            self.editor.dragging = false;
+                            self.update_frame();
+                        }
+                    } else {
+                        self.editor.step_panel(
+                            os_input,
+                            &mut self.package,
                             entity_def_key,
                             action_key,
                             frame,
                             &self.selector.colboxes,
-                            distance,
                         );
 
-                        // end move
-                        if os_input.mouse_pressed(0) {
-                            self.update_frame();
+                        // drag or resize collisionboxes when the mouse is over a selected collisionbox
+                        let mouse_over_selected =
+                            self.game_mouse(os_input).map_or(false, |mouse| {
+                                self.colboxes_at_point(entity_i, mouse)
+                                    .iter()
+                                    .any(|i| self.selector.colboxes.contains(i))
+                            });
+                        if mouse_over_selected {
+                            if os_input.mouse_pressed(0)
+                                && !(os_input.held_shift()
+                                    || os_input.held_alt()
+                                    || os_input.held_control())
+                            {
+                                self.editor.dragging = true;
+                            }
+                            if os_input.scroll_diff() != 0.0 {
+                                self.package.resize_fighter_colboxes(
+                                    entity_def_key,
+                                    action_key,
+                                    frame,
+                                    &self.selector.colboxes,
+                                    os_input.scroll_diff() * 0.1,
+                                );
+                                self.editor.captured_scroll = true;
+                            }
                         }
-                    } else {
+
                         // copy frame
                         if os_input.key_pressed_os(VirtualKeyCode::V) {
                             let frame = self.package.entities[entity_def_key].actions[action_key]
@@ -788,31 +1583,17 @@ impl Game {
                         }
 
                         // handle single selection
-                        if let Some((m_x, m_y)) =
+                        let single_selection = if self.editor.dragging {
+                            None
+                        } else {
                             self.selector.step_single_selection(os_input, &self.camera)
-                        {
-                            let (entity_x, entity_y) = self.entities[entity_i].public_bps_xy(
-                                &self.entities,
-                                &self.package.entities,
-                                &self.stage.surfaces,
-                            );
-                            let frame = self.entities[entity_i].relative_frame(
-                                &self.package.entities[entity_def_key],
-                                &self.stage.surfaces,
-                            );
-
-                            for (i, colbox) in frame.colboxes.iter().enumerate() {
-                                let hit_x = colbox.point.0 + entity_x;
-                                let hit_y = colbox.point.1 + entity_y;
-
-                                let distance =
-                                    ((m_x - hit_x).powi(2) + (m_y - hit_y).powi(2)).sqrt();
-                                if distance < colbox.radius {
-                                    if os_input.held_alt() {
-                                        self.selector.colboxes.remove(&i);
-                                    } else {
-                                        self.selector.colboxes.insert(i);
-                                    }
+                        };
+                        if let Some(mouse) = single_selection {
+                            for i in self.colboxes_at_point(entity_i, mouse) {
+                                if os_input.held_alt() {
+                                    self.selector.colboxes.remove(&i);
+                                } else {
+                                    self.selector.colboxes.insert(i);
                                 }
                             }
 
@@ -943,8 +1724,37 @@ impl Game {
                     if os_input.key_pressed_os(VirtualKeyCode::Q) {
                         self.add_surface(Surface::default(), os_input);
                     }
+                    // toggle flags of selected surfaces
+                    if os_input.held_shift() {
+                        if os_input.key_pressed_os(VirtualKeyCode::W) {
+                            self.toggle_selected_surfaces(|surface| {
+                                surface.ceiling = !surface.ceiling
+                            });
+                        }
+                        if os_input.key_pressed_os(VirtualKeyCode::E) {
+                            self.toggle_selected_surfaces(|surface| surface.wall = !surface.wall);
+                        }
+                        if os_input.key_pressed_os(VirtualKeyCode::R) {
+                            self.toggle_selected_surfaces(|surface| {
+                                surface.floor = match surface.floor {
+                                    Some(_) => None,
+                                    None => Some(Floor {
+                                        traction: 1.0,
+                                        pass_through: false,
+                                    }),
+                                }
+                            });
+                        }
+                        if os_input.key_pressed_os(VirtualKeyCode::F) {
+                            self.toggle_selected_surfaces(|surface| {
+                                if let Some(floor) = &mut surface.floor {
+                                    floor.pass_through = !floor.pass_through;
+                                }
+                            });
+                        }
+                    }
                     // add ceiling surface
-                    if os_input.key_pressed_os(VirtualKeyCode::W) {
+                    if !os_input.held_shift() && os_input.key_pressed_os(VirtualKeyCode::W) {
                         let surface = Surface {
                             ceiling: true,
                             ..Surface::default()
@@ -952,7 +1762,7 @@ impl Game {
                         self.add_surface(surface, os_input);
                     }
                     // add wall surface
-                    if os_input.key_pressed_os(VirtualKeyCode::E) {
+                    if !os_input.held_shift() && os_input.key_pressed_os(VirtualKeyCode::E) {
                         let surface = Surface {
                             wall: true,
                             ..Surface::default()
@@ -960,7 +1770,7 @@ impl Game {
                         self.add_surface(surface, os_input);
                     }
                     // add stage surface
-                    if os_input.key_pressed_os(VirtualKeyCode::R) {
+                    if !os_input.held_shift() && os_input.key_pressed_os(VirtualKeyCode::R) {
                         let surface = Surface {
                             floor: Some(Floor {
                                 traction: 1.0,
@@ -971,7 +1781,7 @@ impl Game {
                         self.add_surface(surface, os_input);
                     }
                     // add platform surface
-                    if os_input.key_pressed_os(VirtualKeyCode::F) {
+                    if !os_input.held_shift() && os_input.key_pressed_os(VirtualKeyCode::F) {
                         let surface = Surface {
                             floor: Some(Floor {
                                 traction: 1.0,
@@ -995,7 +1805,11 @@ impl Game {
                             self.update_frame();
                         }
                     }
-                    if os_input.key_pressed_os(VirtualKeyCode::S) {
+                    // save stage to package
+                    if os_input.held_control() && os_input.key_pressed_os(VirtualKeyCode::S) {
+                        self.copy_stage_to_package();
+                        self.package.save();
+                    } else if os_input.key_pressed_os(VirtualKeyCode::S) {
                         let mut join = false;
                         let mut points: Vec<(f32, f32)> = vec![];
                         fn f32_equal(a: f32, b: f32) -> bool {
@@ -1177,6 +1991,15 @@ impl Game {
         self.selector.mouse = self.game_mouse(os_input); // hack to access mouse during render call, dont use this otherwise
     }
 
+    /// Applies `toggle` once to every surface with at least one point selected
+    fn toggle_selected_surfaces(&mut self, toggle: impl Fn(&mut Surface)) {
+        for i in self.selector.surfaces_vec() {
+            toggle(&mut self.stage.surfaces[i]);
+        }
+        self.update_frame();
+    }
+
+    #[cfg(feature = "window")]
     fn add_surface(&mut self, surface: Surface, os_input: &WinitInputHelper) {
         if let Some((m_x, m_y)) = self.game_mouse(os_input) {
             if self.selector.surfaces.len() == 1 {
@@ -1219,21 +2042,26 @@ impl Game {
         }
     }
 
-    /// next frame is advanced by using the input history on the current frame
+    /// next frame is advanced by using the input history on the current frame.
+    /// The simulated frames replace any future history so they can be seeked to.
     fn step_replay_forwards_from_input(
         &mut self,
         input: &mut Input,
-        netplay: &Netplay,
-        audio: &mut Audio,
-    ) {
-        if self.current_frame <= input.last_frame() {
-            self.current_frame += 1;
-            let player_inputs = &input.players(self.current_frame, netplay);
-            self.step_game(input, player_inputs, audio);
-
-            self.update_frame();
-        } else {
-            self.state = GameState::Paused;
+        netplay: &Netplay,
+        audio: &mut Audio,
+    ) {
+        for _ in 0..self.replay_frames_to_advance() {
+            if self.current_frame <= input.last_frame() {
+                self.advance_history();
+                let player_inputs = &input.players(self.current_frame, netplay);
+                self.step_game(input.history(), player_inputs, audio);
+                self.trim_history();
+
+                self.update_frame();
+            } else {
+                self.state = GameState::Paused;
+                break;
+            }
         }
 
         if input.start_pressed() {
@@ -1243,10 +2071,12 @@ impl Game {
 
     /// next frame is advanced by taking the next frame in history
     fn step_replay_forwards_from_history(&mut self, input: &mut Input) {
-        if self.current_history_index() < self.entity_history.len() {
-            self.jump_frame(self.current_frame + 1);
-        } else {
-            self.state = GameState::Paused;
+        let frames = self.replay_frames_to_advance();
+        match self.replay_last_frame() {
+            Some(last_frame) if self.current_frame < last_frame => {
+                self.replay_seek(self.current_frame + frames)
+            }
+            _ => self.state = GameState::Paused,
         }
 
         if input.start_pressed() {
@@ -1254,6 +2084,7 @@ impl Game {
         }
     }
 
+    #[cfg(feature = "window")]
     fn step_replay_forwards_os_input(&mut self, os_input: &WinitInputHelper) {
         if os_input.key_pressed_os(VirtualKeyCode::H) {
             self.state = GameState::ReplayBackwards;
@@ -1268,12 +2099,15 @@ impl Game {
         {
             self.state = GameState::Paused;
         }
+
+        self.step_replay_timeline_os_input(os_input);
     }
 
     /// Immediately jumps to the previous frame in history
     fn step_replay_backwards(&mut self, input: &mut Input) {
-        if self.current_frame > 0 {
-            self.jump_frame(self.current_frame - 1);
+        let frames = self.replay_frames_to_advance();
+        if self.current_frame > self.deleted_history_frames {
+            self.replay_seek(self.current_frame.saturating_sub(frames));
         } else {
             self.state = GameState::Paused;
         }
@@ -1284,6 +2118,7 @@ impl Game {
         }
     }
 
+    #[cfg(feature = "window")]
     fn step_replay_backwards_os_input(&mut self, os_input: &WinitInputHelper) {
         if os_input.held_shift() && os_input.key_pressed_os(VirtualKeyCode::L) {
             self.state = GameState::ReplayForwardsFromInput;
@@ -1295,6 +2130,132 @@ impl Game {
             self.state = GameState::Paused;
             self.update_frame();
         }
+
+        self.step_replay_timeline_os_input(os_input);
+    }
+
+    /// Keybindings for seeking and changing playback speed, shared by all replay states
+    /// *   Minus/Equals: decrease/increase playback speed
+    /// *   Left/Right: jump back/forward one second
+    /// *   Home/End: jump to the start/end of the replay
+    /// *   0-9: jump to 0%-90% of the way through the replay
+    #[cfg(feature = "window")]
+    fn step_replay_timeline_os_input(&mut self, os_input: &WinitInputHelper) {
+        let speed_i = REPLAY_SPEEDS
+            .iter()
+            .position(|x| *x >= self.replay_speed)
+            .unwrap_or(REPLAY_SPEEDS.len() - 1);
+        if os_input.key_pressed_os(VirtualKeyCode::Minus) {
+            self.replay_speed = REPLAY_SPEEDS[speed_i.saturating_sub(1)];
+        }
+        if os_input.key_pressed_os(VirtualKeyCode::Equals) {
+            self.replay_speed = REPLAY_SPEEDS[(speed_i + 1).min(REPLAY_SPEEDS.len() - 1)];
+        }
+
+        let last_frame = match self.replay_last_frame() {
+            Some(last_frame) => last_frame,
+            None => return,
+        };
+        let first_frame = self.deleted_history_frames;
+
+        if os_input.key_pressed_os(VirtualKeyCode::Left) {
+            self.replay_seek(self.current_frame.saturating_sub(60));
+        }
+        if os_input.key_pressed_os(VirtualKeyCode::Right) {
+            self.replay_seek(self.current_frame + 60);
+        }
+        if os_input.key_pressed_os(VirtualKeyCode::Home) {
+            self.replay_seek(first_frame);
+        }
+        if os_input.key_pressed_os(VirtualKeyCode::End) {
+            self.replay_seek(last_frame);
+        }
+
+        let keys = [
+            VirtualKeyCode::Key0,
+            VirtualKeyCode::Key1,
+            VirtualKeyCode::Key2,
+            VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4,
+            VirtualKeyCode::Key5,
+            VirtualKeyCode::Key6,
+            VirtualKeyCode::Key7,
+            VirtualKeyCode::Key8,
+            VirtualKeyCode::Key9,
+        ];
+        for (i, key) in keys.iter().enumerate() {
+            if os_input.key_pressed_os(*key) {
+                self.replay_seek(first_frame + (last_frame - first_frame) * i / 10);
+            }
+        }
+    }
+
+    /// Returns the number of frames the replay should advance this step, according to the replay_speed.
+    fn replay_frames_to_advance(&mut self) -> usize {
+        frames_to_advance(&mut self.replay_frame_progress, self.replay_speed)
+    }
+
+    /// Returns the last frame that can be jumped to, None if there is no history.
+    /// The first frame that can be jumped to is deleted_history_frames.
+    pub fn replay_last_frame(&self) -> Option<usize> {
+        self.entity_history
+            .len()
+            .checked_sub(1)
+            .map(|x| x + self.deleted_history_frames)
+    }
+
+    /// Jump to any frame in history.
+    /// Frames outside of the history are clamped to the nearest frame in history.
+    pub fn replay_seek(&mut self, frame: usize) {
+        if let Some(last_frame) = self.replay_last_frame() {
+            self.jump_frame(frame.clamp(self.deleted_history_frames, last_frame));
+        }
+    }
+
+    /// A serializable snapshot of every entity on the current frame
+    pub fn telemetry_frame(&self) -> TelemetryFrame {
+        TelemetryFrame::new(
+            self.current_frame,
+            &self.entities,
+            &self.package.entities,
+            &self.stage.surfaces,
+        )
+    }
+
+    /// Hashes of the stage and every entity, used to find where simulations of the same inputs diverge
+    pub fn state_hashes(&self) -> Vec<(String, u64)> {
+        let mut hashes = vec![(
+            String::from("stage"),
+            files::hash_struct_bincode(&self.stage),
+        )];
+        for (key, entity) in &self.entities {
+            let name = match entity.player_id() {
+                Some(player_id) => format!("P{} {}", player_id + 1, entity.state.entity_def_key),
+                None => format!("{:?} {}", key, entity.state.entity_def_key),
+            };
+            hashes.push((name, files::hash_struct_bincode(entity)));
+        }
+        hashes
+    }
+
+    /// The colboxes of every entity on the current frame, positioned in the world.
+    /// Used by renderers that only draw the colboxes e.g. the web replay viewer.
+    pub fn colboxes(&self) -> Vec<CollisionBox> {
+        let fighter_scale = self.rules.modifiers.size.scale();
+        let mut colboxes = vec![];
+        for entity in self.entities.values() {
+            let entity_def = &self.package.entities[entity.state.entity_def_key.as_ref()];
+            let (x, y) =
+                entity.public_bps_xy(&self.entities, &self.package.entities, &self.stage.surfaces);
+            let frame =
+                entity.scaled_relative_frame(entity_def, &self.stage.surfaces, fighter_scale);
+            for colbox in frame.colboxes.iter() {
+                let mut colbox = colbox.clone();
+                colbox.point = (colbox.point.0 + x, colbox.point.1 + y);
+                colboxes.push(colbox);
+            }
+        }
+        colboxes
     }
 
     /// Jump to the saved frame in history
@@ -1305,6 +2266,7 @@ impl Game {
             self.stage = self.stage_history.get(history_index).unwrap().clone();
 
             self.current_frame = to_frame;
+            self.final_kill_frames = None;
             self.update_frame();
         }
     }
@@ -1320,8 +2282,28 @@ impl Game {
         seed
     }
 
-    fn step_game(&mut self, input: &Input, player_inputs: &[PlayerInput], audio: &mut Audio) {
+    fn step_game(
+        &mut self,
+        input_history: &[Vec<ControllerInput>],
+        player_inputs: &[PlayerInput],
+        audio: &mut Audio,
+    ) {
+        audio.set_listener(&self.camera.rect);
+        audio.step_bgm(self.climax());
+        if self.current_frame > self.announced_frame {
+            if let Some(announcement) =
+                Announcement::countdown(self.current_frame, self.countdown_go_frame)
+            {
+                audio.play_announcement(announcement);
+            }
+            self.announced_frame = self.current_frame;
+        }
+        let report_events = self.current_frame > self.reported_frame;
+        self.reported_frame = self.reported_frame.max(self.current_frame);
         let default_input = PlayerInput::empty();
+        self.stage.step_surfaces(self.current_frame);
+        self.step_late_join(player_inputs);
+
         {
             let mut rng = ChaChaRng::from_seed(self.get_seed());
             let mut new_entities = vec![];
@@ -1330,9 +2312,14 @@ impl Game {
             // To synchronize entity stepping, we step through entity logic in stages (item grab logic, action logic, physics logic, collision logic)
             // Modified entities are copied from the previous stage so that every entity perceives themselves as being stepped first, within that stage.
 
+            let sleeping = self.sleeping_entities();
+
             // step each entity action
             let mut action_entities = self.entities.clone();
-            let keys: Vec<_> = action_entities.keys().collect();
+            let keys: Vec<_> = action_entities
+                .keys()
+                .filter(|x| !sleeping.contains(x))
+                .collect();
             for key in keys {
                 let delete_self = {
                     let entity = &mut action_entities[key];
@@ -1354,8 +2341,11 @@ impl Game {
                         messages: &mut messages,
                         delete_self: false,
                         audio,
+                        scripts: &self.scripts,
+                        modifiers: &self.rules.modifiers,
                         input,
                     };
+                    entity.status_effects_step();
                     entity.action_hitlag_step(&mut context);
                     context.delete_self
                 };
@@ -1395,6 +2385,8 @@ impl Game {
                             messages: &mut messages,
                             delete_self: false,
                             audio,
+                            scripts: &self.scripts,
+                            modifiers: &self.rules.modifiers,
                             input,
                         };
                         entity.item_grab(&mut context, hit_key, hit_id);
@@ -1408,7 +2400,10 @@ impl Game {
 
             // step each entity physics
             let mut physics_entities = grab_entities.clone();
-            let keys: Vec<_> = physics_entities.keys().collect();
+            let keys: Vec<_> = physics_entities
+                .keys()
+                .filter(|x| !sleeping.contains(x))
+                .collect();
             for key in keys {
                 let delete_self = {
                     let entity = &mut physics_entities[key];
@@ -1430,6 +2425,8 @@ impl Game {
                         messages: &mut messages,
                         delete_self: false,
                         audio,
+                        scripts: &self.scripts,
+                        modifiers: &self.rules.modifiers,
                         input,
                     };
                     entity.physics_step(&mut context, self.current_frame, self.rules.goal.clone());
@@ -1453,8 +2450,36 @@ impl Game {
                 &physics_entities,
                 &self.package.entities,
                 &self.stage.surfaces,
+                &self.rules.teams,
+                self.rules.modifiers.size.scale(),
             );
-            let keys: Vec<_> = collision_entities.keys().collect();
+            for (key, results) in collision_results.iter() {
+                for result in results {
+                    if let CollisionResult::HitAtk {
+                        hitbox,
+                        point,
+                        entity_defend_i,
+                        ..
+                    } = result
+                    {
+                        self.camera.hit(hitbox.damage, *point);
+                        if report_events {
+                            self.events.push(GameEvent::Hit {
+                                frame: self.current_frame,
+                                attacker: physics_entities[key].player_id(),
+                                defender: physics_entities
+                                    .get(*entity_defend_i)
+                                    .and_then(|x| x.player_id()),
+                                damage: hitbox.damage,
+                            });
+                        }
+                    }
+                }
+            }
+            let keys: Vec<_> = collision_entities
+                .keys()
+                .filter(|x| !sleeping.contains(x))
+                .collect();
             for key in keys {
                 let delete_self = {
                     let entity = &mut collision_entities[key];
@@ -1476,6 +2501,8 @@ impl Game {
                         messages: &mut messages,
                         delete_self: false,
                         audio,
+                        scripts: &self.scripts,
+                        modifiers: &self.rules.modifiers,
                         input,
                     };
                     entity.step_collision(&mut context, &collision_results[key]);
@@ -1506,41 +2533,244 @@ impl Game {
                         messages: &mut vec![],
                         delete_self: false,
                         audio,
+                        scripts: &self.scripts,
+                        modifiers: &self.rules.modifiers,
                         input,
                     };
                     entity.process_message(message, &mut context);
                 }
             }
 
+            self.spawn_items(&mut rng, &mut new_entities);
+
             for entity in new_entities {
                 collision_entities.insert(entity);
             }
+            despawn_orphans(&mut collision_entities);
 
+            self.fire_frame_events(&collision_entities, audio, report_events);
+            self.update_kill_feed(&collision_entities, report_events);
             self.entities = collision_entities;
+
+            if self.record_telemetry {
+                self.telemetry.push(self.telemetry_frame());
+            }
         }
 
-        let players_count = self.players_iter().count();
+        // The game ends when only one side remains, when teams are off every player is their own side
         let eliminated: &str = PlayerAction::Eliminated.into();
-        if self.time_out()
-            || (players_count == 1
-                && self
-                    .players_iter()
-                    .filter(|(_, x)| x.action != eliminated)
-                    .count()
-                    == 0)
-            || (players_count > 1
-                && self
-                    .players_iter()
-                    .filter(|(_, x)| x.action != eliminated)
-                    .count()
-                    == 1)
+        let side = |player: &Player| match self.rules.teams {
+            Teams::On { .. } => player.team,
+            Teams::Off => player.id,
+        };
+        let sides: HashSet<usize> = self.players_iter().map(|(x, _)| side(x)).collect();
+        let remaining_sides: HashSet<usize> = self
+            .players_iter()
+            .filter(|(_, x)| x.action != eliminated)
+            .map(|(x, _)| side(x))
+            .collect();
+        let sides_eliminated = (!sides.is_empty() && remaining_sides.is_empty())
+            || (sides.len() > 1 && remaining_sides.len() == 1);
+
+        // Local games play the final kill in slow motion before displaying the results.
+        // Netplay cant as the slow motion is not rolled back.
+        if sides_eliminated
+            && self.final_kill_frames.is_none()
+            && matches!(self.state, GameState::Local)
         {
-            self.state = self.generate_game_results(input);
+            self.final_kill_frames = Some(FINAL_KILL_FRAMES);
+            audio.play_announcement(Announcement::Game);
+        }
+        let final_kill_over = match &mut self.final_kill_frames {
+            Some(frames) => {
+                *frames = frames.saturating_sub(1);
+                *frames == 0
+            }
+            None => sides_eliminated,
+        };
+
+        if self.time_out() || final_kill_over {
+            if self.final_kill_frames.is_none() {
+                audio.play_announcement(Announcement::Game);
+            }
+            self.state = self.generate_game_results(input_history);
+            if let GameState::Quit(ResumeMenu::Results(results)) = &self.state {
+                if report_events {
+                    self.events.push(GameEvent::GameEnd {
+                        frame: self.current_frame,
+                        player_results: results.player_results.clone(),
+                    });
+                }
+            }
         }
 
         self.update_frame();
     }
 
+    /// Fires the events of every entity that reached a new frame during this step, compared to the current entities.
+    /// Does nothing unless `report_events` is set, so frames resimulated by a rollback do not replay sounds and text.
+    fn fire_frame_events(
+        &mut self,
+        new_entities: &Entities,
+        audio: &mut Audio,
+        report_events: bool,
+    ) {
+        if !report_events {
+            return;
+        }
+
+        for text in &mut self.screen_text {
+            text.1 = text.1.saturating_sub(1);
+        }
+        self.screen_text.retain(|x| x.1 > 0);
+        if let Some(tutorial) = &mut self.tutorial {
+            tutorial.step();
+        }
+
+        for (key, entity) in new_entities.iter() {
+            if let Some(previous) = self.entities.get(key) {
+                // the frame is held during hitlag
+                if previous.state.action == entity.state.action
+                    && previous.state.frame == entity.state.frame
+                {
+                    continue;
+                }
+            }
+
+            let mut events = vec![];
+            if entity.state.frame == 0 {
+                events.push(FrameEvent::Named(entity.state.action.clone()));
+            }
+            if let Some(body) = entity.body() {
+                if body.frames_since_hit == 0 && body.hit_angle_pre_di != body.hit_angle_post_di {
+                    events.push(FrameEvent::Named(DI_EVENT.to_string()));
+                }
+            }
+            let entity_def = &self.package.entities[entity.state.entity_def_key.as_ref()];
+            if let Some(frame) = entity.get_entity_frame(entity_def) {
+                events.extend(frame.events.iter().cloned());
+            }
+
+            for event in events {
+                match event {
+                    FrameEvent::Text(text) => self.screen_text.push((text, SCREEN_TEXT_FRAMES)),
+                    FrameEvent::Named(name) => {
+                        if let (Some(tutorial), Some(0)) = (&mut self.tutorial, entity.player_id())
+                        {
+                            tutorial.event(&name);
+                        }
+                    }
+                    FrameEvent::Sound(filename) => {
+                        let (x, _) = entity.public_bps_xy(
+                            new_entities,
+                            &self.package.entities,
+                            &self.stage.surfaces,
+                        );
+                        audio.play_sound_effect(entity_def, SfxType::custom(filename), x);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds a line and reports a KO event for every player that died during this step, compared to the current entities.
+    /// Does nothing unless `report_events` is set, so frames resimulated by a rollback do not add the same deaths again.
+    fn update_kill_feed(&mut self, new_entities: &Entities, report_events: bool) {
+        if !report_events {
+            return;
+        }
+
+        for line in &mut self.kill_feed {
+            line.1 = line.1.saturating_sub(1);
+        }
+        self.kill_feed.retain(|x| x.1 > 0);
+
+        for (key, entity) in new_entities.iter() {
+            if let Some(player) = entity.ty.get_player() {
+                let previous_deaths = self
+                    .entities
+                    .get(key)
+                    .and_then(|x| x.ty.get_player())
+                    .map(|x| x.result.deaths.len())
+                    .unwrap_or(0);
+                for death in player.result.deaths.iter().skip(previous_deaths) {
+                    self.kill_feed
+                        .push((death.kill_feed_line(player.id), KILL_FEED_FRAMES));
+                    self.events.push(GameEvent::Ko {
+                        frame: self.current_frame,
+                        player: player.id,
+                        killer: death.player,
+                        action: death.action.clone(),
+                        damage: death.damage,
+                        stocks: player.stocks,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Randomly spawns an item from the stage's item table above a random floor, at the frequency set by the rules
+    fn spawn_items(&self, rng: &mut ChaChaRng, new_entities: &mut Vec<Entity>) {
+        let spawn_frames = match self
+            .rules
+            .item_frequency
+            .spawn_frames(self.stage.item_spawn_frames)
+        {
+            Some(spawn_frames) => spawn_frames,
+            None => return,
+        };
+        let items = self
+            .entities
+            .values()
+            .filter(|x| matches!(x.ty, EntityType::Item(_)))
+            .count();
+        if items >= MAX_SPAWNED_ITEMS || rng.gen_range(0..spawn_frames) != 0 {
+            return;
+        }
+
+        let item_spawn = match self.stage.choose_item_spawn(rng.gen()) {
+            Some(item_spawn) => item_spawn,
+            None => return,
+        };
+        match self
+            .package
+            .entities
+            .key_to_value(&item_spawn.entity)
+            .map(|x| &x.ty)
+        {
+            Some(EntityDefType::Item) | Some(EntityDefType::AssistItem(_)) => {}
+            _ => {
+                error!(
+                    "Stage tried to spawn nonexistent item: {}",
+                    item_spawn.entity
+                );
+                return;
+            }
+        }
+
+        let floors: Vec<&Surface> = self
+            .stage
+            .surfaces
+            .iter()
+            .filter(|x| x.floor.is_some())
+            .collect();
+        if floors.is_empty() {
+            return;
+        }
+        let floor = floors[rng.gen_range(0..floors.len())];
+        let along: f32 = rng.gen();
+        let x = floor.x1 + (floor.x2 - floor.x1) * along;
+        let y = floor.y1 + (floor.y2 - floor.y1) * along + ITEM_SPAWN_HEIGHT;
+
+        new_entities.push(Entity {
+            ty: EntityType::Item(Item {
+                owner_id: None,
+                body: Body::new(Location::Airbourne { x, y }, true),
+            }),
+            state: ActionState::new(item_spawn.entity.clone(), ItemAction::Fall),
+        });
+    }
+
     pub fn time_out(&self) -> bool {
         if let Some(time_limit_frames) = self.rules.time_limit_frames() {
             self.current_frame as u64 > time_limit_frames
@@ -1549,15 +2779,128 @@ impl Game {
         }
     }
 
+    /// The music intensifies when a player is on their last stock or the time limit is almost up
+    fn climax(&self) -> bool {
+        let last_stock = self
+            .players_iter()
+            .any(|(player, _)| player.stocks == Some(1));
+        let final_seconds = match self.rules.time_limit_frames() {
+            Some(time_limit_frames) => {
+                time_limit_frames.saturating_sub(self.current_frame as u64) <= CLIMAX_FRAMES
+            }
+            None => false,
+        };
+        last_stock || final_seconds
+    }
+
+    /// Spawns a player on the respawn platform for each human controller without a player that presses start.
+    /// They play the first fighter in the package, starting with the fewest stocks of any remaining player.
+    fn step_late_join(&mut self, player_inputs: &[PlayerInput]) {
+        if !self.rules.late_join {
+            return;
+        }
+
+        let human_controllers = player_inputs.len().saturating_sub(self.selected_ais.len());
+        for (controller, input) in player_inputs.iter().enumerate().take(human_controllers) {
+            if !input.start.press || self.controller_has_player(controller) {
+                continue;
+            }
+
+            // A replay resimulating a late join already has the player in the selected players
+            let id = match self
+                .selected_controllers
+                .iter()
+                .position(|x| *x == controller)
+            {
+                Some(id) => id,
+                None => {
+                    let fighter = match self.package.fighters().first() {
+                        Some((fighter, _)) => fighter.clone(),
+                        None => return,
+                    };
+                    let stocks = self
+                        .players_iter()
+                        .filter_map(|(player, _)| player.stocks)
+                        .filter(|x| *x > 0)
+                        .min();
+                    self.selected_controllers.push(controller);
+                    self.selected_players.push(PlayerSetup {
+                        fighter,
+                        team: self.selected_players.len(),
+                        stocks,
+                        effect_colors: Default::default(),
+                        costume: 0,
+                        damage: 0.0,
+                    });
+                    self.selected_players.len() - 1
+                }
+            };
+
+            let setup = &self.selected_players[id];
+            let mut player = Player::new(setup, id, &self.stage, &self.package, &self.rules);
+            player.body = Player::respawn_body(&self.stage, id);
+            let entity =
+                fighter_entity(player, &setup.fighter, &self.package, PlayerAction::ReSpawn);
+            if let Some(entity) = entity {
+                self.entities.insert(entity);
+                self.screen_text
+                    .push((format!("Player {} joined", id + 1), SCREEN_TEXT_FRAMES));
+            }
+        }
+    }
+
+    fn controller_has_player(&self, controller: usize) -> bool {
+        self.selected_controllers
+            .iter()
+            .position(|x| *x == controller)
+            .map_or(false, |id| {
+                self.players_iter().any(|(player, _)| player.id == id)
+            })
+    }
+
+    /// Entities far from every fighter whose simulation_lod skips simulating them this frame.
+    /// Only the simulated state is used, not the camera, so every netplay peer and replay agrees.
+    fn sleeping_entities(&self) -> HashSet<EntityKey> {
+        let fighters: Vec<(f32, f32)> = self
+            .entities
+            .values()
+            .filter(|entity| entity.ty.get_player().is_some())
+            .map(|entity| {
+                entity.public_bps_xy(&self.entities, &self.package.entities, &self.stage.surfaces)
+            })
+            .collect();
+        self.entities
+            .iter()
+            .filter(|(_, entity)| {
+                let entity_def = &self.package.entities[entity.state.entity_def_key.as_ref()];
+                if entity_def.simulation_lod.distant_step(self.current_frame) {
+                    return false;
+                }
+                let (x, y) = entity.public_bps_xy(
+                    &self.entities,
+                    &self.package.entities,
+                    &self.stage.surfaces,
+                );
+                fighters.iter().all(|(fighter_x, fighter_y)| {
+                    (x - fighter_x).hypot(y - fighter_y) > SIMULATION_LOD_DISTANCE
+                })
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+
     fn players_iter(&self) -> impl Iterator<Item = (&Player, &ActionState)> {
         self.entities
             .values()
             .filter_map(|x| x.ty.get_player().map(|f| (f, &x.state)))
     }
 
-    pub fn generate_game_results(&self, input: &Input) -> GameState {
-        let raw_player_results: Vec<RawPlayerResult> = self
-            .players_iter()
+    pub fn generate_game_results(&self, input_history: &[Vec<ControllerInput>]) -> GameState {
+        // late joining players can be stored before earlier players, so they are sorted to match selected_controllers
+        let mut players: Vec<_> = self.players_iter().collect();
+        players.sort_by_key(|(player, _)| player.id);
+        let raw_player_results: Vec<RawPlayerResult> = players
+            .iter()
             .map(|(player, state)| player.result(state))
             .collect();
         // TODO: Players on the same team score to the same pool and share their place.
@@ -1629,11 +2972,12 @@ impl Game {
                 kills: vec![], // TODO
                 deaths: raw_player_result.deaths.clone(),
                 lcancel_percent,
+                final_damage: raw_player_result.final_damage.unwrap_or(0.0),
             });
         }
         player_results.sort_by_key(|x| x.place);
 
-        let replay = Replay::new(self, input);
+        let replay = Replay::new(self, input_history);
 
         GameState::Quit(ResumeMenu::Results(GameResults {
             player_results,
@@ -1641,6 +2985,17 @@ impl Game {
         }))
     }
 
+    /// Events that occurred during the last step
+    #[cfg_attr(not(feature = "websocket"), allow(dead_code))]
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    /// The state of every frame simulated during the last step
+    pub fn telemetry_frames(&self) -> &[TelemetryFrame] {
+        &self.telemetry
+    }
+
     fn generate_debug(&mut self, input: &Input, netplay: &Netplay) {
         let frame = self.current_frame;
         let player_inputs = &input.players_no_log(frame, netplay);
@@ -1650,6 +3005,25 @@ impl Game {
             .push(format!("Frame: {}    state: {}", frame, self.state));
         for (i, debug_entity) in self.debug_entities.iter() {
             if let Some(entity) = self.entities.get(i) {
+                if !self.debug_ports.is_empty()
+                    && !entity
+                        .player_id()
+                        .map(|x| self.debug_ports.contains(&x))
+                        .unwrap_or(false)
+                {
+                    continue;
+                }
+
+                if self.debug_summary {
+                    self.debug_lines.push(entity.debug_summary(
+                        &self.entities,
+                        &self.package.entities,
+                        &self.stage.surfaces,
+                        i,
+                    ));
+                    continue;
+                }
+
                 let input_i = entity
                     .player_id()
                     .and_then(|x| self.selected_controllers.get(x));
@@ -1671,6 +3045,52 @@ impl Game {
         }
     }
 
+    /// Moves the selected colboxes of the entity by the distance the mouse moved this frame
+    #[cfg(feature = "window")]
+    fn move_selected_colboxes(&mut self, entity_i: EntityKey, os_input: &WinitInputHelper) {
+        let entity = &self.entities[entity_i];
+        let entity_def_key = entity.state.entity_def_key.as_ref();
+        let action_key = entity.state.action.as_ref();
+        let frame = entity.state.frame as usize;
+
+        // undo the operations used to render the entity
+        let (raw_d_x, raw_d_y) = self.game_mouse_diff(os_input);
+        let angle =
+            -entity.frame_angle(&self.package.entities[entity_def_key], &self.stage.surfaces); // rotate by the inverse of the angle
+        let (d_x, d_y) = geometry::rotate((raw_d_x, raw_d_y), angle);
+        let distance = (entity.relative_f(d_x), d_y); // *= -1 is its own inverse
+        self.package.move_fighter_colboxes(
+            entity_def_key,
+            action_key,
+            frame,
+            &self.selector.colboxes,
+            distance,
+        );
+    }
+
+    /// Returns the indexes of the entities colboxes that contain the point
+    fn colboxes_at_point(&self, entity_i: EntityKey, (m_x, m_y): (f32, f32)) -> Vec<usize> {
+        let entity = &self.entities[entity_i];
+        let (entity_x, entity_y) =
+            entity.public_bps_xy(&self.entities, &self.package.entities, &self.stage.surfaces);
+        let frame = entity.relative_frame(
+            &self.package.entities[entity.state.entity_def_key.as_ref()],
+            &self.stage.surfaces,
+        );
+
+        let mut result = vec![];
+        for (i, colbox) in frame.colboxes.iter().enumerate() {
+            let hit_x = colbox.point.0 + entity_x;
+            let hit_y = colbox.point.1 + entity_y;
+
+            let distance = ((m_x - hit_x).powi(2) + (m_y - hit_y).powi(2)).sqrt();
+            if distance < colbox.radius {
+                result.push(i);
+            }
+        }
+        result
+    }
+
     /// Call this whenever an entity's frame is changed, this can be from:
     /// *   the fighter's frame data is changed
     /// *   the entity now refers to a different frame.
@@ -1680,7 +3100,7 @@ impl Game {
     }
 
     #[allow(unused)] // Needed for headless build
-    pub fn render(&self) -> RenderGame {
+    pub fn render(&self, config: &Config) -> RenderGame {
         let mut render_entities = vec![];
 
         let entity_defs = &self.package.entities;
@@ -1700,7 +3120,12 @@ impl Game {
                 }
             }
 
-            let debug = self.debug_entities.get(i).cloned().unwrap_or_default();
+            let mut debug = self.debug_entities.get(i).cloned().unwrap_or_default();
+            if self.rules.modifiers.invisible && entity.ty.get_player().is_some() {
+                if let RenderDebugType::Normal | RenderDebugType::NormalAndDebug = debug.render {
+                    debug.render = RenderDebugType::Debug;
+                }
+            }
             if debug.cam_area {
                 if let Some(cam_area) = entity.cam_area(
                     &self.stage.camera,
@@ -1720,7 +3145,26 @@ impl Game {
                     render_entities.push(RenderObject::rect_outline(item_grab_box, 0.0, 1.0, 0.0));
                 }
             }
+            if debug.ledge_timers || self.ledge_timers {
+                if let Some(player) = entity.ty.get_player() {
+                    let bps = entity.public_bps_xy(
+                        &self.entities,
+                        &self.package.entities,
+                        &self.stage.surfaces,
+                    );
+                    for rect in player.ledge_timer_rects(bps) {
+                        render_entities.push(RenderObject::Rect(rect));
+                    }
+                }
+            }
 
+            // only fighters wear costumes, not the entities they create
+            let costume = entity.ty.get_player().and_then(|player| {
+                let setup = self.selected_players.get(player.id)?;
+                entity_defs[entity.state.entity_def_key.as_ref()]
+                    .fighter()?
+                    .costume(setup.costume)
+            });
             let player_render = entity.render(
                 selected_colboxes,
                 entity_selected,
@@ -1730,6 +3174,9 @@ impl Game {
                 &self.entities,
                 entity_defs,
                 surfaces,
+                self.rules.modifiers.size.scale(),
+                &config.team_palette,
+                costume,
             );
             render_entities.push(RenderObject::Entity(player_render));
         }
@@ -1778,14 +3225,68 @@ impl Game {
             }
         }
 
-        let timer = if let Some(time_limit_frames) = self.rules.time_limit_frames() {
+        let frame_data_panel = match (&self.state, &self.edit) {
+            (GameState::Paused, Edit::Entity(entity_i)) if self.camera.dev_mode() => {
+                match self.entities.get(*entity_i) {
+                    Some(entity) => self.editor.render(
+                        &self.package,
+                        entity.state.entity_def_key.as_ref(),
+                        entity.state.action.as_ref(),
+                        entity.state.frame as usize,
+                        &self.selector.colboxes,
+                    ),
+                    None => vec![],
+                }
+            }
+            _ => vec![],
+        };
+
+        let frame_duration = Duration::new(1, 0) / 60;
+        let timer = if self.rules.game_clock && self.rules.stock_count.is_some() {
+            Some(frame_duration * self.current_frame as u32)
+        } else if let Some(time_limit_frames) = self.rules.time_limit_frames() {
             let frames_remaining = time_limit_frames.saturating_sub(self.current_frame as u64);
-            let frame_duration = Duration::new(1, 0) / 60;
             Some(frame_duration * frames_remaining as u32)
         } else {
             None
         };
 
+        let mut screen_text = self.tutorial.as_ref().map(|x| x.text()).unwrap_or_default();
+        screen_text.extend(self.screen_text.iter().map(|x| x.0.clone()));
+        if self.rewind_start.is_some() && self.rewinding {
+            screen_text.push(String::from("<< Rewinding"));
+        }
+
+        let pause_menu = match (&self.state, &self.pause_menu) {
+            (GameState::Paused, Some(pause_menu))
+                if !self.camera.dev_mode() || pause_menu.confirming_unsaved() =>
+            {
+                // paused from the keyboard, the first player's minimap is displayed
+                let controller = pause_menu
+                    .controller()
+                    .or_else(|| self.selected_controllers.first().cloned());
+                let minimap = match controller {
+                    Some(controller) => self.minimap(controller, &config.hud.minimap),
+                    None => config.hud.minimap.clone(),
+                };
+                Some(pause_menu.render(self.hitboxes_displayed(), self.ledge_timers, &minimap))
+            }
+            _ => None,
+        };
+
+        let replay = match self.state {
+            GameState::ReplayForwardsFromHistory
+            | GameState::ReplayForwardsFromInput
+            | GameState::ReplayBackwards => Some(RenderReplay {
+                frame: self.current_frame,
+                first_frame: self.deleted_history_frames,
+                last_frame: self.replay_last_frame().unwrap_or(self.current_frame),
+                speed: self.replay_speed,
+                assisted: self.rules.assists.any(),
+            }),
+            _ => None,
+        };
+
         RenderGame {
             seed: self.get_seed(),
             current_frame: self.current_frame,
@@ -1797,23 +3298,88 @@ impl Game {
             state: self.state.clone(),
             camera: self.camera.clone(),
             debug_lines: self.debug_lines.clone(),
+            frame_data_panel,
+            saving: self.package.is_saving(),
+            hud: config.hud.clone(),
             timer,
+            replay,
+            screen_text,
+            kill_feed: self.kill_feed.iter().map(|x| x.0.clone()).collect(),
+            pause_menu,
             bgm_metadata: self.bgm_metadata.clone(),
+            minimaps: self.render_minimaps(&config.hud.minimap, &config.team_palette),
         }
     }
 
+    /// The minimap zoom of the player using `controller`, `default` when they have not picked one from the pause menu
+    fn minimap(&self, controller: usize, default: &Minimap) -> Minimap {
+        self.minimaps
+            .get(&controller)
+            .cloned()
+            .unwrap_or_else(|| default.clone())
+    }
+
+    /// A minimap for each zoom level picked by any player, so players that picked the same zoom share a minimap
+    fn render_minimaps(&self, default: &Minimap, team_palette: &TeamPalette) -> Vec<RenderMinimap> {
+        let picked: Vec<Minimap> = if self.selected_controllers.is_empty() {
+            vec![default.clone()]
+        } else {
+            self.selected_controllers
+                .iter()
+                .map(|x| self.minimap(*x, default))
+                .collect()
+        };
+        [Minimap::Stage, Minimap::BlastZone]
+            .iter()
+            .filter(|x| picked.contains(x))
+            .map(|x| self.render_minimap(x, team_palette))
+            .collect()
+    }
+
+    fn render_minimap(&self, minimap: &Minimap, team_palette: &TeamPalette) -> RenderMinimap {
+        let view = match minimap {
+            Minimap::Off => unreachable!("Off minimaps are not rendered"),
+            Minimap::Stage => self.stage.camera.clone(),
+            Minimap::BlastZone => self.stage.blast.clone(),
+        };
+        let players = self
+            .entities
+            .values()
+            .filter(|x| x.ty.get_player().is_some())
+            .map(|entity| {
+                let position = entity.public_bps_xy(
+                    &self.entities,
+                    &self.package.entities,
+                    &self.stage.surfaces,
+                );
+                (
+                    position,
+                    graphics::get_team_color3(team_palette, entity.team()),
+                )
+            })
+            .collect();
+        RenderMinimap { view, players }
+    }
+
     #[allow(unused)] // Needed for headless build
     pub fn graphics_message(
         &mut self,
         config: &Config,
         command_line: &CommandLine,
+        toasts: &Toasts,
     ) -> GraphicsMessage {
         let render = Render {
             command_output: command_line.output(),
-            render_type: RenderType::Game(self.render()),
+            render_type: RenderType::Game(self.render(config)),
             fullscreen: config.fullscreen,
+            frame_limiter: None,
+            screenshot: self.screenshot,
+            export_frame: None,
+            toasts: toasts.messages(),
+            team_palette: config.team_palette.clone(),
         };
         self.bgm_metadata = None;
+        self.screenshot = false;
 
         GraphicsMessage {
             package_updates: self.package.updates(),
@@ -1829,6 +3395,109 @@ impl Game {
         self.package
     }
 
+    /// Returns the display name of the fighter each player is currently using
+    pub fn fighter_names(&self) -> Vec<String> {
+        self.players_iter()
+            .map(|(_, state)| {
+                self.package.entities[state.entity_def_key.as_ref()]
+                    .name
+                    .clone()
+            })
+            .collect()
+    }
+
+    /// How CPUs with the Idle difficulty behave, set from the pause menu
+    pub fn dummy(&self) -> DummyBehavior {
+        self.dummy
+    }
+
+    /// The state of each player that CPU players decide their inputs from
+    pub fn ai_states(&self) -> Vec<ai::FighterState> {
+        self.entities
+            .values()
+            .filter_map(|entity| {
+                let player = entity.ty.get_player()?;
+                let controller = *self.selected_controllers.get(player.id)?;
+                let entity_def = &self.package.entities[entity.state.entity_def_key.as_ref()];
+                let (x, y) = entity.public_bps_xy(
+                    &self.entities,
+                    &self.package.entities,
+                    &self.stage.surfaces,
+                );
+                let attacking = entity
+                    .get_entity_frame(entity_def)
+                    .map(|frame| {
+                        frame
+                            .colboxes
+                            .iter()
+                            .any(|x| matches!(x.role, CollisionBoxRole::Hit(_)))
+                    })
+                    .unwrap_or(false);
+                Some(ai::FighterState {
+                    controller,
+                    team: player.team,
+                    x,
+                    y,
+                    y_vel: player.body.y_vel,
+                    face_right: player.body.face_right,
+                    airbourne: player.body.is_airbourne(),
+                    ledge: player.body.is_ledge(),
+                    air_jumps_left: player.air_jumps_left,
+                    action: entity.state.get_action(),
+                    attacking,
+                    intangible: entity.is_intangible(&self.package.entities),
+                    hitstun: player.hitstun > 0.0,
+                    shield_stun: player.shield_stun_timer > 0,
+                })
+            })
+            .collect()
+    }
+
+    /// The state of each player that haptic cues are triggered from, keyed by controller
+    pub fn haptic_states(&self) -> Vec<(usize, HapticState)> {
+        self.players_iter()
+            .filter_map(|(player, state)| {
+                let controller = *self.selected_controllers.get(player.id)?;
+                Some((
+                    controller,
+                    HapticState {
+                        damage: player.body.damage,
+                        parrying: player.parry_timer > 0,
+                        shield_broken: state.get_action() == Some(PlayerAction::ShieldBreakFall),
+                        deaths: player.result.deaths.len(),
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "window")]
+    pub fn presence_activity(&self) -> Activity {
+        let fighters: Vec<String> = self
+            .players_iter()
+            .map(|(player, state)| {
+                let name = &self.package.entities[state.entity_def_key.as_ref()].name;
+                match player.stocks {
+                    Some(stocks) => format!("{} ({} stocks)", name, stocks),
+                    None => name.clone(),
+                }
+            })
+            .collect();
+
+        let mode = match self.state {
+            GameState::ReplayForwardsFromHistory
+            | GameState::ReplayForwardsFromInput
+            | GameState::ReplayBackwards => "Watching a replay",
+            GameState::Netplay => "Playing online",
+            _ => "Playing locally",
+        };
+
+        Activity {
+            details: fighters.join(" vs "),
+            state: format!("{} on {}", mode, self.stage.name),
+        }
+    }
+
     /// TODO:
     /// hacky...
     /// lets add the ability to skip public fields to treefleciton instead
@@ -1846,6 +3515,76 @@ impl Game {
     }
 }
 
+/// Returns the number of frames to advance this step when running at `speed` and carries the remainder over in `progress`.
+/// Speeds below 1 will return 0 on some steps and speeds are capped at MAX_FRAMES_PER_STEP frames per step.
+fn frames_to_advance(progress: &mut f32, speed: f32) -> usize {
+    // max and min discard a NaN speed
+    *progress += speed.max(0.0).min(MAX_FRAMES_PER_STEP as f32);
+    let frames = progress.floor();
+    *progress -= frames;
+    frames as usize
+}
+
+/// The pause menu options to spawn every item and projectile and to despawn every entity except fighters
+fn entity_options(package: &Package, entities: &Entities) -> Vec<EntityOption> {
+    let mut options = vec![];
+    for (key, entity_def) in package.entities.key_value_iter() {
+        let kind = match entity_def.ty {
+            EntityDefType::Item | EntityDefType::AssistItem(_) => "item",
+            EntityDefType::Projectile => "projectile",
+            _ => continue,
+        };
+        options.push(EntityOption {
+            label: format!("Spawn {}", key.trim_end_matches(".cbor")),
+            action: EntityAction::Spawn {
+                kind,
+                entity: key.clone(),
+            },
+        });
+    }
+    for (key, entity) in entities {
+        if entity.ty.get_player().is_none() {
+            options.push(EntityOption {
+                label: format!(
+                    "Despawn {} {}",
+                    entity.ty.name(),
+                    entity.state.entity_def_key.trim_end_matches(".cbor")
+                ),
+                action: EntityAction::Despawn(key),
+            });
+        }
+    }
+    options
+}
+
+/// Reported to tools connected to the WebSocket command interface.
+/// Players are numbered from 0 and are None when the entity does not belong to a player.
+#[derive(Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum GameEvent {
+    Hit {
+        frame: usize,
+        attacker: Option<usize>,
+        defender: Option<usize>,
+        damage: f32,
+    },
+    Ko {
+        frame: usize,
+        player: usize,
+        /// None when the player self destructed
+        killer: Option<usize>,
+        /// The action of the entity that landed the last hit
+        action: Option<String>,
+        damage: f32,
+        /// Stocks remaining after the KO
+        stocks: Option<u64>,
+    },
+    GameEnd {
+        frame: usize,
+        player_results: Vec<PlayerResult>,
+    },
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub enum GameState {
     Local,
@@ -1885,6 +3624,27 @@ impl Default for GameState {
     }
 }
 
+/// # Game -> Menu Transitions
+/// Results:   Game complete   -> display results -> CSS
+/// Unchanged: Game quit       -> CSS
+/// Results:   Replay complete -> display results -> replay ui
+/// Unchanged: Replay quit     -> replay ui
+/// CharacterSelect: Character select chosen from the pause menu -> CSS
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub enum ResumeMenu {
+    Results(GameResults),
+    Unchanged,
+    CharacterSelect,
+    NetplayDisconnect { reason: String },
+}
+
+impl Default for ResumeMenu {
+    fn default() -> Self {
+        ResumeMenu::Unchanged
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub enum Edit {
     Entity(EntityKey),
@@ -2025,12 +3785,42 @@ pub struct RenderGame {
     pub state: GameState,
     pub camera: Camera,
     pub debug_lines: Vec<String>,
+    pub frame_data_panel: Vec<String>,
+    /// The package is being saved in the background
+    pub saving: bool,
+    pub hud: HudConfig,
     pub timer: Option<Duration>,
+    pub replay: Option<RenderReplay>,
+    /// Tutorial instructions and text displayed by frame events
+    pub screen_text: Vec<String>,
+    /// Recent deaths, oldest first
+    pub kill_feed: Vec<String>,
+    pub pause_menu: Option<RenderPauseMenu>,
     pub bgm_metadata: Option<BGMMetadata>,
+    /// Drawn side by side, zoomed in first
+    pub minimaps: Vec<RenderMinimap>,
+}
+
+/// Drawn in the corner of the screen using the stage surfaces
+pub struct RenderMinimap {
+    /// The area of the stage displayed
+    pub view: Rect,
+    /// The position and team color of each player
+    pub players: Vec<((f32, f32), [f32; 3])>,
+}
+
+pub struct RenderReplay {
+    pub frame: usize,
+    pub first_frame: usize,
+    pub last_frame: usize,
+    pub speed: f32,
+    /// The game was played with assists enabled
+    pub assisted: bool,
 }
 
 pub enum RenderObject {
     Entity(RenderEntity),
+    Rect(RenderRect),
     RectOutline(RenderRect),
     SpawnPoint(RenderSpawnPoint),
 }
@@ -2065,6 +3855,27 @@ pub struct RenderSpawnPoint {
     pub color: [f32; 4],
 }
 
+/// Returns None when the player's fighter is not a fighter
+fn fighter_entity(
+    player: Player,
+    fighter_key: &str,
+    package: &Package,
+    action: PlayerAction,
+) -> Option<Entity> {
+    if let EntityDefType::Fighter(fighter_def) = &package.entities[fighter_key].ty {
+        let fighter = match fighter_def.ty {
+            FighterType::Toriel => Fighter::Toriel(Toriel::new(player)),
+            FighterType::Dave => Fighter::Toriel(Toriel::new(player)),
+        };
+        Some(Entity {
+            ty: EntityType::Fighter(fighter),
+            state: ActionState::new(fighter_key.to_string(), action),
+        })
+    } else {
+        None
+    }
+}
+
 #[derive(Clone)]
 pub struct GameSetup {
     pub init_seed: u64,
@@ -2101,4 +3912,30 @@ impl GameSetup {
 pub struct PlayerSetup {
     pub fighter: String,
     pub team: usize,
+    /// Overrides the stock count of the rules
+    pub stocks: Option<u64>,
+    pub effect_colors: EffectColors,
+    /// Index into the costumes of the fighter, 0 is the default appearance
+    pub costume: usize,
+    /// The damage percent the fighter starts the game with
+    pub damage: f32,
+}
+
+#[test]
+fn frames_to_advance_test() {
+    let mut progress = 0.0;
+    let frames: Vec<usize> = (0..4)
+        .map(|_| frames_to_advance(&mut progress, 0.5))
+        .collect();
+    assert_eq!(frames, vec![0, 1, 0, 1]);
+
+    let mut progress = 0.0;
+    assert_eq!(frames_to_advance(&mut progress, 2.0), 2);
+
+    let mut progress = 0.0;
+    assert_eq!(
+        frames_to_advance(&mut progress, f32::INFINITY),
+        MAX_FRAMES_PER_STEP
+    );
+    assert_eq!(frames_to_advance(&mut progress, f32::NAN), 0);
 }
@@ -1,8 +1,12 @@
 use crate::game::RenderGame;
+#[cfg(feature = "window")]
 use crate::menu::RenderMenu;
+use canon_collision_lib::config::{FrameLimiter, TeamPalette};
 use canon_collision_lib::entity_def::CollisionBoxRole;
 use canon_collision_lib::package::PackageUpdate;
 
+use std::path::PathBuf;
+
 pub struct GraphicsMessage {
     pub render: Render,
     pub package_updates: Vec<PackageUpdate>,
@@ -12,14 +16,37 @@ pub struct Render {
     pub command_output: Vec<String>,
     pub render_type: RenderType,
     pub fullscreen: bool,
+    /// Set when frames can be drawn less often to save power
+    pub frame_limiter: Option<FrameLimiter>,
+    /// Save this frame to the screenshots folder
+    pub screenshot: bool,
+    /// Save this frame as a png to this path, set for every frame while exporting a replay
+    pub export_frame: Option<PathBuf>,
+    /// Notifications to display in the top right corner, newest first
+    pub toasts: Vec<String>,
+    pub team_palette: TeamPalette,
 }
 
 pub enum RenderType {
     Game(RenderGame),
+    #[cfg(feature = "window")]
     #[allow(dead_code)] // Needed for headless build
     Menu(RenderMenu),
 }
 
+/// Finds the adapter selected by its index or by a case insensitive part of its name.
+#[allow(unused)] // Needed for headless build
+pub fn find_adapter<'a>(adapters: &'a [String], choice: &str) -> Option<&'a String> {
+    if let Ok(index) = choice.parse::<usize>() {
+        return adapters.get(index);
+    }
+    let choice = choice.to_lowercase();
+    adapters
+        .iter()
+        .find(|x| x.to_lowercase() == choice)
+        .or_else(|| adapters.iter().find(|x| x.to_lowercase().contains(&choice)))
+}
+
 #[allow(unused)] // Needed for headless build
 pub fn get_render_id(role: &CollisionBoxRole) -> u32 {
     match role {
@@ -32,15 +59,17 @@ pub fn get_render_id(role: &CollisionBoxRole) -> u32 {
     }
 }
 
+/// Every team palette has this many colors, so a team keeps its index when the palette is changed
+pub const TEAM_COUNT: usize = 8;
+
 #[allow(unused)] // Needed for headless build
-pub fn get_team_color4(i: usize) -> [f32; 4] {
-    let colors = get_colors();
-    let color = colors[i % colors.len()].value;
-    [color[0] / 255.0, color[1] / 255.0, color[2] / 255.0, 1.0]
+pub fn get_team_color4(palette: &TeamPalette, i: usize) -> [f32; 4] {
+    let [r, g, b] = get_team_color3(palette, i);
+    [r, g, b, 1.0]
 }
 
-pub fn get_team_color3(i: usize) -> [f32; 3] {
-    let colors = get_colors();
+pub fn get_team_color3(palette: &TeamPalette, i: usize) -> [f32; 3] {
+    let colors = get_colors(palette);
     let color = colors[i % colors.len()].value;
     [color[0] / 255.0, color[1] / 255.0, color[2] / 255.0]
 }
@@ -50,39 +79,62 @@ pub struct Color {
     pub value: [f32; 3],
 }
 
-pub fn get_colors() -> Vec<Color> {
-    vec![
-        Color {
-            name: String::from("Blue"),
-            value: [0.0, 90.0, 224.0],
-        },
-        Color {
-            name: String::from("Orange"),
-            value: [239.0, 100.0, 0.0],
-        },
-        Color {
-            name: String::from("Red"),
-            value: [255.0, 0.0, 40.0],
-        },
-        Color {
-            name: String::from("Green"),
-            value: [10.0, 150.0, 38.0],
-        },
-        Color {
-            name: String::from("Pink"),
-            value: [255.0, 0.0, 163.0],
-        },
-        Color {
-            name: String::from("Green #2"),
-            value: [124.0, 184.0, 0.0],
-        },
-        Color {
-            name: String::from("Purple"),
-            value: [120.0, 46.0, 252.0],
-        },
-        Color {
-            name: String::from("Light Blue"),
-            value: [81.0, 229.0, 237.0],
-        },
-    ]
+pub fn get_colors(palette: &TeamPalette) -> Vec<Color> {
+    let colors: [(&str, [f32; 3]); TEAM_COUNT] = match palette {
+        TeamPalette::Standard => [
+            ("Blue", [0.0, 90.0, 224.0]),
+            ("Orange", [239.0, 100.0, 0.0]),
+            ("Red", [255.0, 0.0, 40.0]),
+            ("Green", [10.0, 150.0, 38.0]),
+            ("Pink", [255.0, 0.0, 163.0]),
+            ("Green #2", [124.0, 184.0, 0.0]),
+            ("Purple", [120.0, 46.0, 252.0]),
+            ("Light Blue", [81.0, 229.0, 237.0]),
+        ],
+        TeamPalette::OkabeIto => [
+            ("Blue", [0.0, 114.0, 178.0]),
+            ("Orange", [230.0, 159.0, 0.0]),
+            ("Vermillion", [213.0, 94.0, 0.0]),
+            ("Bluish Green", [0.0, 158.0, 115.0]),
+            ("Reddish Purple", [204.0, 121.0, 167.0]),
+            ("Yellow", [240.0, 228.0, 66.0]),
+            ("Sky Blue", [86.0, 180.0, 233.0]),
+            ("Grey", [187.0, 187.0, 187.0]),
+        ],
+        TeamPalette::TolVibrant => [
+            ("Blue", [0.0, 119.0, 187.0]),
+            ("Orange", [238.0, 119.0, 51.0]),
+            ("Red", [204.0, 51.0, 17.0]),
+            ("Teal", [0.0, 153.0, 136.0]),
+            ("Magenta", [238.0, 51.0, 119.0]),
+            ("Yellow", [204.0, 187.0, 68.0]),
+            ("Cyan", [51.0, 187.0, 238.0]),
+            ("Grey", [187.0, 187.0, 187.0]),
+        ],
+    };
+    colors
+        .iter()
+        .map(|(name, value)| Color {
+            name: name.to_string(),
+            value: *value,
+        })
+        .collect()
+}
+
+#[test]
+fn find_adapter_test() {
+    let adapters = vec![
+        String::from("Intel(R) UHD Graphics 620"),
+        String::from("NVIDIA GeForce GTX 1050"),
+        String::from("NVIDIA GeForce GTX 1050 Ti"),
+    ];
+    assert_eq!(find_adapter(&adapters, "1"), Some(&adapters[1]));
+    assert_eq!(find_adapter(&adapters, "3"), None);
+    assert_eq!(find_adapter(&adapters, "intel"), Some(&adapters[0]));
+    assert_eq!(find_adapter(&adapters, "nvidia"), Some(&adapters[1]));
+    assert_eq!(
+        find_adapter(&adapters, "nvidia geforce gtx 1050 ti"),
+        Some(&adapters[2])
+    );
+    assert_eq!(find_adapter(&adapters, "AMD"), None);
 }
@@ -0,0 +1,145 @@
+use crate::game::{Game, GameState};
+
+use canon_collision_lib::config::{Config, HapticEvent};
+use canon_collision_lib::input::Input;
+use canon_collision_lib::network::Netplay;
+
+use std::collections::HashMap;
+
+/// The parts of a players state that haptic cues are triggered from.
+#[derive(Clone)]
+pub struct HapticState {
+    pub damage: f32,
+    pub parrying: bool,
+    pub shield_broken: bool,
+    pub deaths: usize,
+}
+
+impl HapticState {
+    /// The events that occurred between the previous state and this one
+    fn events(&self, previous: &HapticState) -> Vec<HapticEvent> {
+        let mut events = vec![];
+        if self.damage > previous.damage {
+            events.push(HapticEvent::Hit);
+        }
+        if self.parrying && !previous.parrying {
+            events.push(HapticEvent::Parry);
+        }
+        if self.shield_broken && !previous.shield_broken {
+            events.push(HapticEvent::ShieldBreak);
+        }
+        if self.deaths > previous.deaths {
+            events.push(HapticEvent::Ko);
+        }
+        events
+    }
+}
+
+#[derive(Clone, Default)]
+struct Rumble {
+    strength: f32,
+    frames: u64,
+}
+
+/// Maps game events to rumble on the controller of the player they occurred to, as configured for that port.
+/// Events are detected by comparing each players state to the previous frame.
+pub struct Haptics {
+    previous: HashMap<usize, HapticState>,
+    rumble: Vec<Rumble>,
+    previous_frame: usize,
+}
+
+impl Haptics {
+    pub fn new() -> Haptics {
+        Haptics {
+            previous: HashMap::new(),
+            rumble: vec![],
+            previous_frame: 0,
+        }
+    }
+
+    /// Call this once every frame
+    pub fn step(
+        &mut self,
+        config: &Config,
+        game: Option<&Game>,
+        input: &mut Input,
+        netplay: &Netplay,
+    ) {
+        // The controllers of replays are not connected to this machine, so only running games rumble.
+        let game = match game {
+            Some(game) if matches!(game.state, GameState::Local | GameState::Netplay) => game,
+            Some(game) if matches!(game.state, GameState::Paused) => {
+                input.set_rumble(&[]);
+                return;
+            }
+            _ => {
+                self.previous.clear();
+                self.rumble.clear();
+                input.set_rumble(&[]);
+                return;
+            }
+        };
+
+        if game.current_frame == self.previous_frame {
+            return;
+        }
+        self.previous_frame = game.current_frame;
+
+        for rumble in &mut self.rumble {
+            rumble.frames = rumble.frames.saturating_sub(1);
+        }
+
+        for (controller, state) in game.haptic_states() {
+            if let Some(previous) = self.previous.get(&controller) {
+                // the controllers of remote peers rumble on their own machine
+                if let Some(local_controller) = input.local_controller(controller, netplay) {
+                    for event in state.events(previous) {
+                        self.trigger(config, local_controller, event);
+                    }
+                }
+            }
+            self.previous.insert(controller, state);
+        }
+
+        let strengths: Vec<f32> = self
+            .rumble
+            .iter()
+            .map(|x| if x.frames > 0 { x.strength } else { 0.0 })
+            .collect();
+        input.set_rumble(&strengths);
+    }
+
+    fn trigger(&mut self, config: &Config, controller: usize, event: HapticEvent) {
+        let haptics = config.haptics(controller);
+        let cue = haptics.cue(event);
+        if cue.frames == 0 || cue.strength <= 0.0 {
+            return;
+        }
+
+        if self.rumble.len() <= controller {
+            self.rumble.resize(controller + 1, Rumble::default());
+        }
+        let rumble = &mut self.rumble[controller];
+        if rumble.frames == 0 || cue.strength >= rumble.strength {
+            rumble.strength = cue.strength;
+        }
+        rumble.frames = rumble.frames.max(cue.frames);
+    }
+}
+
+#[test]
+fn haptic_state_events_test() {
+    let previous = HapticState {
+        damage: 10.0,
+        parrying: false,
+        shield_broken: false,
+        deaths: 0,
+    };
+    let state = HapticState {
+        damage: 0.0,
+        deaths: 1,
+        ..previous.clone()
+    };
+    assert_eq!(state.events(&previous), vec![HapticEvent::Ko]);
+}
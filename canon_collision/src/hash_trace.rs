@@ -0,0 +1,128 @@
+use crate::game::Game;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Writes the hashes of the stage and every entity each frame.
+/// Traces of the same replay simulated on different machines are compared to find the first frame where they diverge.
+/// Each line is a frame number followed by tab separated `name=hash` pairs, starting with the stage.
+pub struct HashTrace {
+    writer: BufWriter<File>,
+    last_frame: Option<usize>,
+}
+
+impl HashTrace {
+    pub fn create(path: &Path) -> Result<HashTrace, String> {
+        let file = File::create(path).map_err(|x| x.to_string())?;
+        Ok(HashTrace {
+            writer: BufWriter::new(file),
+            last_frame: None,
+        })
+    }
+
+    /// Writes the current frame, unless the game has not advanced since the last write
+    pub fn record(&mut self, game: &Game) -> Result<(), String> {
+        if self.last_frame == Some(game.current_frame) {
+            return Ok(());
+        }
+        self.last_frame = Some(game.current_frame);
+
+        let mut line = game.current_frame.to_string();
+        for (name, hash) in game.state_hashes() {
+            line.push_str(&format!("\t{}={:016x}", name, hash));
+        }
+        writeln!(self.writer, "{}", line).map_err(|x| x.to_string())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|x| x.to_string())
+    }
+}
+
+/// The hashes of every frame, keyed by frame number
+type Trace = BTreeMap<usize, Vec<(String, String)>>;
+
+fn parse(text: &str) -> Result<Trace, String> {
+    let mut trace = Trace::new();
+    for (i, line) in text.lines().enumerate() {
+        let mut fields = line.split('\t');
+        let frame = fields
+            .next()
+            .and_then(|x| x.parse().ok())
+            .ok_or_else(|| format!("Line {} does not start with a frame number", i + 1))?;
+        let hashes = fields
+            .map(|x| {
+                x.rsplit_once('=')
+                    .map(|(name, hash)| (name.to_string(), hash.to_string()))
+                    .ok_or_else(|| format!("Line {} has a field without a hash", i + 1))
+            })
+            .collect::<Result<_, _>>()?;
+        trace.insert(frame, hashes);
+    }
+    Ok(trace)
+}
+
+/// Describes the first frame and entity where the two traces differ
+pub fn compare(a: &str, b: &str) -> Result<String, String> {
+    let a = parse(a).map_err(|x| format!("First trace: {}", x))?;
+    let b = parse(b).map_err(|x| format!("Second trace: {}", x))?;
+
+    for (frame, a_hashes) in &a {
+        let b_hashes = match b.get(frame) {
+            Some(hashes) => hashes,
+            None => return Ok(format!("Frame {} is missing from the second trace", frame)),
+        };
+        for (name, a_hash) in a_hashes {
+            match b_hashes.iter().find(|x| &x.0 == name) {
+                Some((_, b_hash)) if a_hash == b_hash => {}
+                Some((_, b_hash)) => {
+                    return Ok(format!(
+                        "Diverged on frame {}: {} is {} in the first trace but {} in the second",
+                        frame, name, a_hash, b_hash
+                    ))
+                }
+                None => {
+                    return Ok(format!(
+                        "Diverged on frame {}: {} only exists in the first trace",
+                        frame, name
+                    ))
+                }
+            }
+        }
+        if let Some((name, _)) = b_hashes
+            .iter()
+            .find(|(name, _)| !a_hashes.iter().any(|x| &x.0 == name))
+        {
+            return Ok(format!(
+                "Diverged on frame {}: {} only exists in the second trace",
+                frame, name
+            ));
+        }
+    }
+
+    match b.keys().find(|x| !a.contains_key(x)) {
+        Some(frame) => Ok(format!("Frame {} is missing from the first trace", frame)),
+        None => Ok(format!("The traces match for all {} frames", a.len())),
+    }
+}
+
+#[test]
+fn compare_test() {
+    let a = "1\tstage=01\tP1 a=02\n2\tstage=01\tP1 a=03\n";
+    assert_eq!(compare(a, a).unwrap(), "The traces match for all 2 frames");
+    assert_eq!(
+        compare(a, "1\tstage=01\tP1 a=02\n2\tstage=01\tP1 a=04\n").unwrap(),
+        "Diverged on frame 2: P1 a is 03 in the first trace but 04 in the second"
+    );
+    assert_eq!(
+        compare(a, "1\tstage=01\tP1 a=02\n").unwrap(),
+        "Frame 2 is missing from the second trace"
+    );
+    assert_eq!(
+        compare("1\tstage=01\n", "1\tstage=01\tP1 a=02\n").unwrap(),
+        "Diverged on frame 1: P1 a only exists in the second trace"
+    );
+    assert!(compare("x", a).is_err());
+}
@@ -0,0 +1,44 @@
+#![allow(clippy::new_without_default)]
+#![allow(clippy::identity_op)]
+// Builds without a window or audio only use part of the game logic
+#![cfg_attr(not(all(feature = "window", feature = "audio")), allow(dead_code))]
+
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate treeflection_derive;
+
+pub(crate) mod action_lengths;
+pub(crate) mod ai;
+#[cfg(feature = "window")]
+pub mod app;
+pub(crate) mod audio;
+pub(crate) mod camera;
+pub mod cli;
+pub(crate) mod collision;
+pub(crate) mod editor;
+pub(crate) mod entity;
+pub(crate) mod game;
+pub(crate) mod graphics;
+pub(crate) mod haptics;
+pub mod hash_trace;
+#[cfg(feature = "window")]
+pub(crate) mod menu;
+pub(crate) mod particle;
+pub(crate) mod pause_menu;
+#[cfg(feature = "window")]
+pub(crate) mod presence;
+pub mod replays;
+pub(crate) mod results;
+pub(crate) mod rules;
+pub(crate) mod series;
+pub(crate) mod session;
+pub mod simulation;
+pub(crate) mod telemetry;
+pub(crate) mod toasts;
+pub(crate) mod tutorial;
+
+#[cfg(feature = "wgpu_renderer")]
+pub mod wgpu;
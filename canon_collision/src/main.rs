@@ -1,55 +1,89 @@
 #![windows_subsystem = "windows"]
-#![allow(clippy::new_without_default)]
-#![allow(clippy::identity_op)]
 
 #[macro_use]
 extern crate log;
-#[macro_use]
-extern crate serde_derive;
-#[macro_use]
-extern crate bytemuck;
-#[macro_use]
-extern crate treeflection_derive;
-
-pub(crate) mod ai;
-pub(crate) mod app;
-pub(crate) mod audio;
-pub(crate) mod camera;
-pub(crate) mod cli;
-pub(crate) mod collision;
-pub(crate) mod entity;
-pub(crate) mod game;
-pub(crate) mod graphics;
-pub(crate) mod menu;
-pub(crate) mod particle;
-pub(crate) mod replays;
-pub(crate) mod results;
-pub(crate) mod rules;
 
+use canon_collision::cli::{self, GraphicsBackendChoice};
 #[cfg(feature = "wgpu_renderer")]
-pub(crate) mod wgpu;
-
-use crate::cli::GraphicsBackendChoice;
+use canon_collision::wgpu::{self, WgpuGraphics};
+use canon_collision::{app, hash_trace, replays};
 #[cfg(feature = "wgpu_renderer")]
-use crate::wgpu::WgpuGraphics;
+use canon_collision_lib::config::Config;
 use canon_collision_lib::logger;
 
 use winit::event_loop::EventLoop;
 
+use std::fs;
+use std::path::PathBuf;
+
 fn main() {
     canon_collision_lib::setup_panic_handler!();
     logger::init();
 
     let cli_results = cli::cli();
+    if let Some((a, b)) = &cli_results.compare_traces {
+        let read = |path: &PathBuf| {
+            fs::read_to_string(path).map_err(|x| format!("Failed to read {:?}: {}", path, x))
+        };
+        match read(a).and_then(|a| read(b).and_then(|b| hash_trace::compare(&a, &b))) {
+            Ok(report) => println!("{}", report),
+            Err(err) => println!("{}", err),
+        }
+        return;
+    }
+    if cli_results.upgrade_replays {
+        for result in replays::upgrade_all_replays() {
+            println!("{}", result);
+        }
+        return;
+    }
     let graphics_backend = cli_results.graphics_backend.clone();
-    let (event_tx, render_rx) = app::run_in_thread(cli_results);
+    #[cfg(feature = "wgpu_renderer")]
+    let safe_mode = cli_results.safe_mode;
+    #[cfg(feature = "wgpu_renderer")]
+    let hot_reload_shaders = cli_results.hot_reload_shaders;
+    #[cfg(feature = "wgpu_renderer")]
+    let hidden = matches!(
+        cli_results.continue_from,
+        cli::ContinueFrom::RenderReplay { .. }
+    );
+    #[cfg(feature = "wgpu_renderer")]
+    let config = Config::load();
+    #[cfg(feature = "wgpu_renderer")]
+    let graphics_adapter = cli_results
+        .graphics_adapter
+        .clone()
+        .or(config.graphics_adapter);
+
+    let graphics_adapters = match graphics_backend {
+        #[cfg(feature = "wgpu_renderer")]
+        GraphicsBackendChoice::Wgpu => wgpu::adapter_names(),
+        GraphicsBackendChoice::Headless => vec![],
+    };
+    if cli_results.list_adapters {
+        for (i, name) in graphics_adapters.iter().enumerate() {
+            println!("{}: {}", i, name);
+        }
+        return;
+    }
+    info!("Graphics adapters: {}", graphics_adapters.join(", "));
+
+    let (event_tx, render_rx) = app::run_in_thread(cli_results, graphics_adapters);
 
     match graphics_backend {
         #[cfg(feature = "wgpu_renderer")]
         GraphicsBackendChoice::Wgpu => {
             let event_loop = EventLoop::new();
-            let mut graphics =
-                futures::executor::block_on(WgpuGraphics::new(&event_loop, event_tx, render_rx));
+            let mut graphics = futures::executor::block_on(WgpuGraphics::new(
+                &event_loop,
+                event_tx,
+                render_rx,
+                graphics_adapter,
+                config.surface_format,
+                safe_mode,
+                hot_reload_shaders,
+                hidden,
+            ));
             event_loop.run(move |event, _, control_flow| {
                 graphics.update(event, control_flow);
             });
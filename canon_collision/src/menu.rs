@@ -1,22 +1,34 @@
+use crate::ai;
+use crate::audio::announcer::MenuSfx;
 use crate::audio::Audio;
 use crate::camera::Camera;
-use crate::game::{Edit, GameSetup, GameState, PlayerSetup};
+use crate::game::{Edit, GameSetup, GameState, PlayerSetup, ResumeMenu};
 use crate::graphics;
 use crate::graphics::{GraphicsMessage, Render, RenderType};
+use crate::presence::Activity;
 use crate::replays;
 use crate::results::{GameResults, PlayerResult};
+use crate::rules::{Assists, ItemFrequency, Modifiers, Pause, Rules, SeriesMode, Teams};
+use crate::series::{Entrant, Series, SeriesState};
+use crate::toasts::Toasts;
 
 use canon_collision_lib::command_line::CommandLine;
-use canon_collision_lib::config::Config;
+use canon_collision_lib::config::{
+    Config, EffectColors, HapticEvent, PercentPrecision, StockDisplay,
+};
+use canon_collision_lib::hotkeys;
+use canon_collision_lib::hotkeys::HotkeyAction;
+use canon_collision_lib::input::rebind::Rebind;
 use canon_collision_lib::input::state::PlayerInput;
 use canon_collision_lib::input::Input;
 use canon_collision_lib::network::{Netplay, NetplayState};
 use canon_collision_lib::package::Package;
 use canon_collision_lib::replays_files;
 
+use strum::IntoEnumIterator;
 use treeflection::{Node, NodeRunner, NodeToken};
 use winit::event::VirtualKeyCode;
-use winit_input_helper::WinitInputHelper;
+use winit_input_helper::{TextChar, WinitInputHelper};
 
 use std::mem;
 
@@ -30,20 +42,55 @@ use std::mem;
 
 pub struct Menu {
     state: MenuState,
-    prev_state: Option<MenuState>, // Only populated when the current state specifically needs to jump back to the previous state i.e we could arrive at the current state via multiple sources.
+    /// The states to return to when backing out of the current state, the last element is returned to first.
+    /// When empty, backing out returns to the main menu.
+    back_stack: Vec<MenuState>,
     fighter_selections: Vec<PlayerSelect>,
+    main_ticker: MenuTicker,
     game_ticker: MenuTicker,
+    settings_ticker: MenuTicker,
+    extras_ticker: MenuTicker,
+    /// The port whose rumble settings are displayed and edited, the last controller to select a setting
+    settings_port: usize,
     stage_ticker: Option<MenuTicker>, // Uses an option because we dont know how many stages there are at Menu creation, but we want to remember which stage was selected
     current_frame: usize,
     back_counter_max: usize,
     game_setup: Option<GameSetup>,
     game_results: Option<GameResults>,
+    /// The kind of game chosen at the game mode select
+    series_mode: SeriesMode,
+    /// The tutorial was chosen at the game mode select
+    tutorial: bool,
+    /// The crew battle or tournament currently in progress
+    series: Option<Series>,
+    /// The item frequency of local games, netplay games always use the default so that peers agree
+    item_frequency: ItemFrequency,
+    /// Whether teammates can hit each other in local games
+    team_attack: bool,
+    /// The assists of local games, netplay games never use assists so that peers agree
+    assists: Assists,
+    /// Whether stock matches display the time played in local games
+    game_clock: bool,
+    /// Frames players can wait on the respawn platform in local games
+    respawn_frames: u64,
+    /// Whether a quick restart from the results starts each player at the damage they ended the previous game with
+    carry_damage: bool,
+    /// Whether players charge a super meter in local games
+    meter: bool,
+    /// The modifiers of local games, netplay games never use modifiers so that peers agree
+    modifiers: Modifiers,
     netplay_history: Vec<NetplayHistory>,
+    screenshot: bool,
+    /// Sounds to play once the current step is finished
+    sfx: Vec<MenuSfx>,
+    /// Set for the current frame when the keyboard back key is pressed.
+    keyboard_back: bool,
+    quit: bool,
 }
 
 pub struct NetplayHistory {
     state: MenuState,
-    prev_state: Option<MenuState>,
+    back_stack: Vec<MenuState>,
     fighter_selections: Vec<PlayerSelect>,
     stage_ticker: Option<MenuTicker>,
 }
@@ -52,18 +99,89 @@ impl Menu {
     pub fn new(state: MenuState) -> Menu {
         Menu {
             state,
-            prev_state: None,
+            back_stack: vec![],
             fighter_selections: vec![],
             stage_ticker: None,
-            game_ticker: MenuTicker::new(3),
+            main_ticker: MenuTicker::new(MAIN_MENU_OPTIONS.len()),
+            game_ticker: MenuTicker::new(GAME_SELECT_OPTIONS.len()),
+            settings_ticker: MenuTicker::new(SETTINGS_OPTIONS.len()),
+            extras_ticker: MenuTicker::new(EXTRAS_OPTIONS.len()),
+            settings_port: 0,
             current_frame: 0,
             back_counter_max: 90,
             game_setup: None,
             game_results: None,
+            series_mode: SeriesMode::Single,
+            tutorial: false,
+            series: None,
+            item_frequency: ItemFrequency::default(),
+            team_attack: false,
+            assists: Assists::default(),
+            game_clock: false,
+            respawn_frames: Rules::default().respawn_frames,
+            carry_damage: false,
+            meter: false,
+            modifiers: Modifiers::default(),
             netplay_history: vec![],
+            screenshot: false,
+            sfx: vec![],
+            keyboard_back: false,
+            quit: false,
         }
     }
 
+    /// The selections refer to the fighters and stages of the previous package, so they are cleared and the main menu is returned to.
+    pub fn package_changed(&mut self) {
+        self.state = MenuState::MainMenu;
+        self.back_stack.clear();
+        self.fighter_selections.clear();
+        self.stage_ticker = None;
+        self.series = None;
+        self.game_results = None;
+    }
+
+    /// Returns true when quit was selected from the main menu
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Moves to the new state, the current state is returned to when backing out of the new state.
+    fn push_state(&mut self, state: MenuState) {
+        let prev_state = mem::replace(&mut self.state, state);
+        self.back_stack.push(prev_state);
+        self.queue_sfx(MenuSfx::Select);
+    }
+
+    /// Returns to the state the current state was entered from.
+    fn pop_state(&mut self) {
+        self.state = self.back_stack.pop().unwrap_or(MenuState::MainMenu);
+        self.queue_sfx(MenuSfx::Back);
+    }
+
+    /// Each sound is only played once per step, even when multiple menus are entered or left
+    fn queue_sfx(&mut self, sfx: MenuSfx) {
+        if !self.sfx.contains(&sfx) {
+            self.sfx.push(sfx);
+        }
+    }
+
+    /// The sounds queued during the last step
+    pub fn take_sfx(&mut self) -> Vec<MenuSfx> {
+        mem::take(&mut self.sfx)
+    }
+
+    /// Netplay can be left from any menu reached after the game mode select, so return all the way to it.
+    fn pop_to_game_select(&mut self) {
+        while !matches!(self.state, MenuState::GameSelect | MenuState::MainMenu) {
+            self.pop_state();
+        }
+    }
+
+    /// Back is only triggered by a press, so a held button cannot back out of multiple menus at once.
+    fn back_pressed(&self, player_inputs: &[PlayerInput]) -> bool {
+        self.keyboard_back || player_inputs.iter().any(|x| x.b.press)
+    }
+
     pub fn resume(&mut self, resume_menu: ResumeMenu, audio: &mut Audio) {
         audio.play_bgm("Menu");
 
@@ -73,10 +191,38 @@ impl Menu {
                 self.state = MenuState::NetplayWait { message };
             }
             ResumeMenu::Results(results) => {
+                if let Some(series) = &mut self.series {
+                    series.record(&results.player_results);
+                }
                 self.game_results = Some(results);
-                self.prev_state = Some(mem::replace(&mut self.state, MenuState::game_results()));
+                self.push_state(MenuState::game_results());
+            }
+            ResumeMenu::Unchanged => self.series = None,
+            ResumeMenu::CharacterSelect => {
+                self.series = None;
+                while !matches!(
+                    self.state,
+                    MenuState::CharacterSelect { .. } | MenuState::MainMenu
+                ) {
+                    self.pop_state();
+                }
+            }
+        }
+    }
+
+    fn step_main_menu(&mut self, player_inputs: &[PlayerInput]) {
+        self.main_ticker.step(player_inputs);
+
+        if player_inputs.iter().any(|x| x.a.press || x.start.press) {
+            match self.main_ticker.cursor {
+                0 => self.push_state(MenuState::GameSelect),
+                1 => self.push_state(MenuState::Settings),
+                2 => self.push_state(MenuState::Extras {
+                    message: String::new(),
+                }),
+                3 => self.quit = true,
+                _ => unreachable!(),
             }
-            ResumeMenu::Unchanged => {}
         }
     }
 
@@ -87,53 +233,499 @@ impl Menu {
         player_inputs: &[PlayerInput],
         netplay: &mut Netplay,
     ) {
-        let ticker = &mut self.game_ticker;
-
-        if player_inputs.iter().any(|x| x[0].stick_y > 0.4 || x[0].up) {
-            ticker.up();
-        } else if player_inputs
-            .iter()
-            .any(|x| x[0].stick_y < -0.4 || x[0].down)
-        {
-            ticker.down();
-        } else {
-            ticker.reset();
-        }
+        self.game_ticker.step(player_inputs);
 
         if (player_inputs.iter().any(|x| x.a.press || x.start.press)) && package.stages.len() > 0 {
-            match ticker.cursor {
-                0 => self.state = MenuState::character_select(),
+            self.tutorial = false;
+            match self.game_ticker.cursor {
+                0 => {
+                    self.series_mode = SeriesMode::Single;
+                    self.push_state(MenuState::character_select());
+                }
                 1 => {
+                    self.series_mode = SeriesMode::Single;
                     netplay.connect_match_making(
                         config.netplay_region.clone().unwrap_or_else(|| "AU".into()), // TODO: set region screen if region.is_none()
                         2,
                     );
-                    self.state = MenuState::NetplayWait {
+                    self.push_state(MenuState::NetplayWait {
                         message: String::from(""),
+                    });
+                }
+                2 => {
+                    self.series_mode = SeriesMode::Single;
+                    let message = match &config.netplay_lobby {
+                        Some(room) => {
+                            netplay.join_lobby(room.clone());
+                            String::from("")
+                        }
+                        None => String::from(
+                            "Set netplay_lobby in the config to the name of the lobby to create or join",
+                        ),
                     };
+                    self.push_state(MenuState::NetplayWait { message });
+                }
+                3 => {
+                    self.series_mode = SeriesMode::CrewBattle;
+                    self.push_state(MenuState::character_select());
+                }
+                4 => {
+                    self.series_mode = SeriesMode::Tournament;
+                    self.push_state(MenuState::character_select());
+                }
+                5 => {
+                    self.series_mode = SeriesMode::Single;
+                    self.push_state(MenuState::SpectateAddress {
+                        address: config.netplay_spectate_address.clone().unwrap_or_default(),
+                        message: String::new(),
+                    });
+                }
+                6 => {
+                    self.series_mode = SeriesMode::Single;
+                    self.tutorial = true;
+                    self.push_state(MenuState::character_select());
+                }
+                _ => unreachable!(),
+            }
+        } else if self.back_pressed(player_inputs) {
+            self.pop_state();
+        }
+    }
+
+    fn step_settings(
+        &mut self,
+        config: &mut Config,
+        input: &Input,
+        player_inputs: &[PlayerInput],
+        graphics_adapters: &[String],
+        toasts: &mut Toasts,
+    ) {
+        self.settings_ticker.step(player_inputs);
+
+        if let Some(port) = player_inputs
+            .iter()
+            .position(|x| x.a.press || x.start.press)
+        {
+            self.settings_port = port;
+            match self.settings_ticker.cursor {
+                0 => self.push_state(MenuState::hotkey_settings()),
+                11 => self.push_state(MenuState::controller_settings(input)),
+                1 => {
+                    config.auto_save_replay = !config.auto_save_replay;
+                    config.save();
                 }
                 2 => {
-                    self.state = MenuState::replay_select();
+                    config.show_session_report = !config.show_session_report;
+                    config.save();
+                }
+                3 => {
+                    config.discord_rich_presence = !config.discord_rich_presence;
+                    config.save();
+                }
+                4 => {
+                    config.hud.percent_precision = match config.hud.percent_precision {
+                        PercentPrecision::Whole => PercentPrecision::Tenths,
+                        PercentPrecision::Tenths => PercentPrecision::Whole,
+                    };
+                    config.save();
+                }
+                5 => {
+                    config.hud.show_internal_damage = !config.hud.show_internal_damage;
+                    config.save();
+                }
+                6 => {
+                    config.hud.stock_display = match config.hud.stock_display {
+                        StockDisplay::Icons => StockDisplay::Count,
+                        StockDisplay::Count => StockDisplay::Icons,
+                    };
+                    config.save();
+                }
+                7..=10 => {
+                    let event = HAPTIC_EVENTS[self.settings_ticker.cursor - 7];
+                    config.haptics_mut(port).cue_mut(event).cycle();
+                    config.save();
+                }
+                12 => self.item_frequency = self.item_frequency.next(),
+                13 => self.team_attack = !self.team_attack,
+                14 => self.assists.auto_lcancel = !self.assists.auto_lcancel,
+                15 => self.assists.auto_tech = !self.assists.auto_tech,
+                16 => {
+                    self.assists.wavedash_buffer =
+                        if self.assists.wavedash_buffer >= WAVEDASH_BUFFER_MAX {
+                            0
+                        } else {
+                            self.assists.wavedash_buffer + WAVEDASH_BUFFER_STEP
+                        }
+                }
+                17 => self.game_clock = !self.game_clock,
+                18 => {
+                    let i = RESPAWN_FRAMES
+                        .iter()
+                        .position(|x| *x == self.respawn_frames)
+                        .map_or(0, |i| (i + 1) % RESPAWN_FRAMES.len());
+                    self.respawn_frames = RESPAWN_FRAMES[i];
+                }
+                19 => self.push_state(MenuState::audio_settings()),
+                20 => {
+                    // cycle from the default adapter through every adapter and back again
+                    config.graphics_adapter = match &config.graphics_adapter {
+                        Some(current) => graphics_adapters
+                            .iter()
+                            .skip_while(|x| *x != current)
+                            .nth(1)
+                            .cloned(),
+                        None => graphics_adapters.first().cloned(),
+                    };
+                    config.save();
+                    toasts.push(String::from(
+                        "Restart the game to use the new graphics adapter",
+                    ));
+                }
+                21 => self.push_state(MenuState::modifiers()),
+                22 => {
+                    config.hud.minimap = config.hud.minimap.next();
+                    config.save();
+                }
+                23 => {
+                    config.hud.mode = config.hud.mode.next();
+                    config.save();
+                }
+                24 => {
+                    let i = HUD_SCALES
+                        .iter()
+                        .position(|x| *x == config.hud.scale)
+                        .map_or(0, |i| (i + 1) % HUD_SCALES.len());
+                    config.hud.scale = HUD_SCALES[i];
+                    config.save();
                 }
+                25 => {
+                    config.hud.player_anchor = config.hud.player_anchor.next();
+                    config.save();
+                }
+                26 => {
+                    config.hud.timer_anchor = config.hud.timer_anchor.next();
+                    config.save();
+                }
+                27 => {
+                    config.team_palette = config.team_palette.next();
+                    config.save();
+                }
+                28 => {
+                    config.hud.player_indicators = !config.hud.player_indicators;
+                    config.save();
+                }
+                29 => self.carry_damage = !self.carry_damage,
+                30 => self.meter = !self.meter,
                 _ => unreachable!(),
             }
+        } else if self.back_pressed(player_inputs) {
+            self.pop_state();
         }
     }
 
-    pub fn step_replay_select(&mut self, player_inputs: &[PlayerInput]) {
-        let back = if let &mut MenuState::ReplaySelect(ref replays, ref mut ticker) =
-            &mut self.state
+    /// The IP address of the player to spectate is typed on the keyboard and confirmed with enter or A
+    fn step_spectate_address(
+        &mut self,
+        config: &mut Config,
+        player_inputs: &[PlayerInput],
+        os_input: &WinitInputHelper,
+        netplay: &mut Netplay,
+    ) {
+        let spectate = if let MenuState::SpectateAddress { address, message } = &mut self.state {
+            for text_char in os_input.text() {
+                match text_char {
+                    TextChar::Char(c) if c.is_ascii_hexdigit() || c == '.' || c == ':' => {
+                        address.push(c)
+                    }
+                    TextChar::Char(_) => {}
+                    TextChar::Back => {
+                        address.pop();
+                    }
+                }
+            }
+
+            if os_input.key_pressed_os(VirtualKeyCode::Return)
+                || player_inputs.iter().any(|x| x.a.press || x.start.press)
+            {
+                match address.parse() {
+                    Ok(ip) => {
+                        config.netplay_spectate_address = Some(address.clone());
+                        config.save();
+                        Some(ip)
+                    }
+                    Err(_) => {
+                        *message = format!("'{}' is not an IP address", address);
+                        None
+                    }
+                }
+            } else {
+                None
+            }
+        } else {
+            unreachable!()
+        };
+
+        if let Some(ip) = spectate {
+            netplay.spectate(ip);
+            self.push_state(MenuState::NetplayWait {
+                message: String::new(),
+            });
+        } else if self.back_pressed(player_inputs) {
+            self.pop_state();
+        }
+    }
+
+    /// A raises the selected volume and X/Y lowers it, wrapping around at either end
+    fn step_audio_settings(&mut self, config: &mut Config, player_inputs: &[PlayerInput]) {
+        let back = if let MenuState::AudioSettings { ticker } = &mut self.state {
+            ticker.step(player_inputs);
+
+            let raise = player_inputs.iter().any(|x| x.a.press || x.start.press);
+            let lower = player_inputs.iter().any(|x| x.x.press || x.y.press);
+            if raise || lower {
+                let volume = match ticker.cursor {
+                    0 => Some(&mut config.audio.master_volume),
+                    1 => Some(&mut config.audio.bgm_volume),
+                    2 => Some(&mut config.audio.sfx_volume),
+                    3 => {
+                        config.audio.mute = !config.audio.mute;
+                        None
+                    }
+                    _ => unreachable!(),
+                };
+                if let Some(volume) = volume {
+                    *volume = step_volume(*volume, raise);
+                }
+                config.save();
+                false
+            } else {
+                self.back_pressed(player_inputs)
+            }
+        } else {
+            unreachable!()
+        };
+
+        if back {
+            self.pop_state();
+        }
+    }
+
+    /// A cycles the selected modifier
+    fn step_modifiers(&mut self, player_inputs: &[PlayerInput]) {
+        let back = if let MenuState::Modifiers { ticker } = &mut self.state {
+            ticker.step(player_inputs);
+
+            if player_inputs.iter().any(|x| x.a.press || x.start.press) {
+                let modifiers = &mut self.modifiers;
+                match ticker.cursor {
+                    0 => modifiers.gravity = modifiers.gravity.next(),
+                    1 => modifiers.speed = modifiers.speed.next(),
+                    2 => modifiers.invisible = !modifiers.invisible,
+                    3 => modifiers.one_hit_ko = !modifiers.one_hit_ko,
+                    4 => modifiers.fixed_camera = !modifiers.fixed_camera,
+                    5 => modifiers.size = modifiers.size.next(),
+                    _ => unreachable!(),
+                }
+                false
+            } else {
+                self.back_pressed(player_inputs)
+            }
+        } else {
+            unreachable!()
+        };
+
+        if back {
+            self.pop_state();
+        }
+    }
+
+    fn step_extras(&mut self, player_inputs: &[PlayerInput]) {
+        self.extras_ticker.step(player_inputs);
+
+        if player_inputs.iter().any(|x| x.a.press || x.start.press) {
+            match self.extras_ticker.cursor {
+                0 => self.push_state(MenuState::replay_select()),
+                1 | 2 => {
+                    self.state = MenuState::Extras {
+                        message: format!(
+                            "{} are not available yet",
+                            EXTRAS_OPTIONS[self.extras_ticker.cursor]
+                        ),
+                    }
+                }
+                _ => unreachable!(),
+            }
+        } else if self.back_pressed(player_inputs) {
+            self.pop_state();
+        }
+    }
+
+    pub fn step_hotkey_settings(
+        &mut self,
+        config: &mut Config,
+        player_inputs: &[PlayerInput],
+        os_input: &WinitInputHelper,
+    ) {
+        let back = if let MenuState::HotkeySettings {
+            ticker,
+            rebinding,
+            message,
+        } = &mut self.state
         {
-            if player_inputs.iter().any(|x| x[0].stick_y > 0.4 || x[0].up) {
-                ticker.up();
-            } else if player_inputs
+            let action = HotkeyAction::iter().nth(ticker.cursor).unwrap();
+            if *rebinding {
+                if let Some(key) = hotkeys::key_pressed(os_input) {
+                    *message = match config.hotkeys.set(action, key) {
+                        Ok(()) => {
+                            config.save();
+                            format!("{:?} is now bound to {}", action, key)
+                        }
+                        Err(err) => err,
+                    };
+                    *rebinding = false;
+                } else if player_inputs.iter().any(|x| x.b.press) || self.keyboard_back {
+                    message.clear();
+                    *rebinding = false;
+                }
+                false
+            } else {
+                ticker.step(player_inputs);
+
+                if player_inputs.iter().any(|x| x.start.press || x.a.press) {
+                    *message = format!("Press a key to bind to {:?}", action);
+                    *rebinding = true;
+                    false
+                } else {
+                    player_inputs.iter().any(|x| x.b.press) || self.keyboard_back
+                }
+            }
+        } else {
+            unreachable!()
+        };
+
+        if back {
+            self.pop_state();
+        }
+    }
+
+    /// Keyboard back or another controller pressing B cancels rebinding,
+    /// the controller being rebound cannot as it is only read through raw events.
+    /// Space skips inputs the controller does not have.
+    pub fn step_controller_settings(
+        &mut self,
+        config: &mut Config,
+        input: &mut Input,
+        player_inputs: &[PlayerInput],
+        os_input: &WinitInputHelper,
+        read_events: bool,
+    ) {
+        let back = if let MenuState::ControllerSettings {
+            ticker,
+            rebind,
+            message,
+            triggers,
+            ..
+        } = &mut self.state
+        {
+            *triggers = player_inputs
                 .iter()
-                .any(|x| x[0].stick_y < -0.4 || x[0].down)
-            {
-                ticker.down();
+                .enumerate()
+                .map(|(i, x)| {
+                    let (raw_l, raw_r) = input.raw_triggers(i);
+                    format!(
+                        "L: {:.2} (raw {:.2})  R: {:.2} (raw {:.2})",
+                        x.l_trigger.value, raw_l, x.r_trigger.value, raw_r
+                    )
+                })
+                .collect();
+
+            if let Some(rebind_inner) = rebind {
+                if self.keyboard_back
+                    || player_inputs
+                        .iter()
+                        .enumerate()
+                        .any(|(i, x)| x.b.press && i != ticker.cursor)
+                {
+                    *message = String::from("Rebinding cancelled");
+                    *rebind = None;
+                } else {
+                    if read_events {
+                        if os_input.key_pressed_os(VirtualKeyCode::Space) {
+                            rebind_inner.skip();
+                        }
+                        rebind_inner.step(input);
+                    }
+                    if rebind_inner.is_complete() {
+                        *message = String::from("Controller map saved");
+                        *rebind = None;
+                    } else {
+                        *message = rebind_inner.prompt().to_string();
+                    }
+                }
+                false
             } else {
-                ticker.reset();
+                ticker.step(player_inputs);
+
+                if player_inputs.iter().any(|x| x.start.press || x.a.press) {
+                    match Rebind::new(input, ticker.cursor) {
+                        Some(new_rebind) => {
+                            *message = new_rebind.prompt().to_string();
+                            *rebind = Some(new_rebind);
+                        }
+                        None => {
+                            *message =
+                                String::from("GC controllers cannot be rebound, only calibrated")
+                        }
+                    }
+                    false
+                } else if player_inputs.iter().any(|x| x.x.press || x.y.press) {
+                    input.reset_deadzone(ticker.cursor);
+                    *message = String::from(
+                        "Calibrated from the current position of the sticks and triggers",
+                    );
+                    false
+                } else if player_inputs.iter().any(|x| x.z.press) {
+                    // Z is pressed with the controller that is being calibrated, so its triggers can be held down at the same time
+                    let (raw_l, raw_r) = input.raw_triggers(ticker.cursor);
+                    let full_press = raw_l.max(raw_r);
+                    let curve = config.trigger_curve_mut(ticker.cursor);
+                    if full_press > TRIGGER_FULL_PRESS_MIN {
+                        curve.full_press = full_press;
+                        *message = format!("Triggers fully press at {:.2}", full_press);
+                    } else {
+                        curve.full_press = 1.0;
+                        *message = String::from("Trigger full press reset");
+                    }
+                    config.save();
+                    false
+                } else if player_inputs.iter().any(|x| x.left.press || x.right.press) {
+                    let curve = config.trigger_curve_mut(ticker.cursor);
+                    let i = TRIGGER_EXPONENTS
+                        .iter()
+                        .position(|x| *x == curve.exponent)
+                        .map_or(0, |i| (i + 1) % TRIGGER_EXPONENTS.len());
+                    curve.exponent = TRIGGER_EXPONENTS[i];
+                    *message = format!("Trigger precision: {}", TRIGGER_PRECISIONS[i]);
+                    config.save();
+                    false
+                } else {
+                    self.back_pressed(player_inputs)
+                }
             }
+        } else {
+            unreachable!()
+        };
+
+        if back {
+            self.pop_state();
+        }
+    }
+
+    pub fn step_replay_select(&mut self, player_inputs: &[PlayerInput]) {
+        let back = if let &mut MenuState::ReplaySelect(ref replays, ref mut ticker) =
+            &mut self.state
+        {
+            ticker.step(player_inputs);
 
             if (player_inputs.iter().any(|x| x.start.press || x.a.press)) && !replays.is_empty() {
                 let name = &replays[ticker.cursor];
@@ -147,14 +739,14 @@ impl Menu {
                 }
                 false
             } else {
-                player_inputs.iter().any(|x| x.b.press)
+                player_inputs.iter().any(|x| x.b.press) || self.keyboard_back
             }
         } else {
             unreachable!()
         };
 
         if back {
-            self.state = MenuState::GameSelect;
+            self.pop_state();
         }
     }
 
@@ -162,7 +754,12 @@ impl Menu {
     /// are going be out of whack so just reset the fighter selection state
     /// If a controller is added on the same frame another is removed, then no reset occurs.
     /// However this is rare and the problem is minor, so ¯\_(ツ)_/¯
-    fn add_remove_fighter_selections(&mut self, package: &Package, player_inputs: &[PlayerInput]) {
+    fn add_remove_fighter_selections(
+        &mut self,
+        package: &Package,
+        input: &Input,
+        player_inputs: &[PlayerInput],
+    ) {
         if self
             .fighter_selections
             .iter()
@@ -171,16 +768,17 @@ impl Menu {
             != player_inputs.len()
         {
             self.fighter_selections.clear();
-            for (i, input) in player_inputs.iter().enumerate() {
-                let ui = if input.plugged_in {
+            for (i, player_input) in player_inputs.iter().enumerate() {
+                let ui = if player_input.plugged_in {
                     PlayerSelectUi::human_fighter(package)
                 } else {
                     PlayerSelectUi::HumanUnplugged
                 };
-                let team = Menu::get_free_team(&self.fighter_selections);
+                let team = Menu::default_team(&self.fighter_selections, input, i);
                 self.fighter_selections.push(PlayerSelect {
                     controller: Some((i, MenuTicker::new(1))),
                     fighter: None,
+                    costume: 0,
                     cpu_ai: None,
                     ui,
                     animation_frame: 0,
@@ -193,13 +791,17 @@ impl Menu {
     fn step_fighter_select(
         &mut self,
         package: &Package,
+        config: &Config,
+        input: &Input,
         player_inputs: &[PlayerInput],
         netplay: &mut Netplay,
     ) {
-        self.add_remove_fighter_selections(package, player_inputs);
+        self.add_remove_fighter_selections(package, input, player_inputs);
         let fighters = package.fighters();
 
-        let mut new_state: Option<MenuState> = None;
+        let mut stage_select = false;
+        let mut back = false;
+        let mut fighter_confirmed = false;
         if let &mut MenuState::CharacterSelect {
             ref mut back_counter,
         } = &mut self.state
@@ -217,13 +819,13 @@ impl Menu {
             }
 
             // plug/unplug humans
-            for (input_i, input) in player_inputs.iter().enumerate() {
-                let free_team = Menu::get_free_team(&self.fighter_selections);
-                if input.plugged_in {
+            for (input_i, player_input) in player_inputs.iter().enumerate() {
+                let team = Menu::default_team(&self.fighter_selections, input, input_i);
+                if player_input.plugged_in {
                     let selection = &mut self.fighter_selections[input_i];
                     if let PlayerSelectUi::HumanUnplugged = selection.ui {
                         selection.ui = PlayerSelectUi::human_fighter(package);
-                        selection.team = free_team;
+                        selection.team = team;
                         selection.controller = Some((input_i, MenuTicker::new(1)));
                     }
                 } else if let PlayerSelectUi::HumanFighter(_) = self.fighter_selections[input_i].ui
@@ -366,8 +968,12 @@ impl Menu {
                         match selection.ui.clone() {
                             PlayerSelectUi::HumanFighter(ticker) => {
                                 if ticker.cursor < fighters.len() {
+                                    if selection.fighter != Some(ticker.cursor) {
+                                        selection.costume = 0;
+                                    }
                                     selection.fighter = Some(ticker.cursor);
                                     selection.animation_frame = 0;
+                                    fighter_confirmed = true;
                                 } else {
                                     match ticker.cursor - fighters.len() {
                                         0 => selection.ui = PlayerSelectUi::human_team(),
@@ -382,12 +988,16 @@ impl Menu {
                             }
                             PlayerSelectUi::CpuFighter(ticker) => {
                                 if ticker.cursor < fighters.len() {
+                                    if selection.fighter != Some(ticker.cursor) {
+                                        selection.costume = 0;
+                                    }
                                     selection.fighter = Some(ticker.cursor);
                                     selection.animation_frame = 0;
+                                    fighter_confirmed = true;
                                 } else {
                                     match ticker.cursor - fighters.len() {
                                         0 => selection.ui = PlayerSelectUi::cpu_team(),
-                                        1 => { /* TODO: selection.ui = PlayerSelectUi::cpu_ai()*/ }
+                                        1 => selection.ui = PlayerSelectUi::cpu_ai(),
                                         2 => {
                                             remove_cpu = Some(selection_i);
                                         }
@@ -398,11 +1008,10 @@ impl Menu {
                                 }
                             }
                             PlayerSelectUi::HumanTeam(ticker) => {
-                                let colors = graphics::get_colors();
-                                if ticker.cursor < colors.len() {
+                                if ticker.cursor < graphics::TEAM_COUNT {
                                     selection.team = ticker.cursor;
                                 } else {
-                                    match ticker.cursor - colors.len() {
+                                    match ticker.cursor - graphics::TEAM_COUNT {
                                         0 => selection.ui = PlayerSelectUi::human_fighter(package),
                                         _ => {
                                             unreachable!()
@@ -411,11 +1020,22 @@ impl Menu {
                                 }
                             }
                             PlayerSelectUi::CpuTeam(ticker) => {
-                                let colors = graphics::get_colors();
-                                if ticker.cursor < colors.len() {
+                                if ticker.cursor < graphics::TEAM_COUNT {
                                     selection.team = ticker.cursor;
                                 } else {
-                                    match ticker.cursor - colors.len() {
+                                    match ticker.cursor - graphics::TEAM_COUNT {
+                                        0 => selection.ui = PlayerSelectUi::cpu_fighter(package),
+                                        _ => {
+                                            unreachable!()
+                                        }
+                                    }
+                                }
+                            }
+                            PlayerSelectUi::CpuAi(ticker) => {
+                                if ticker.cursor < ai::DIFFICULTIES.len() {
+                                    selection.cpu_ai = Some(ticker.cursor);
+                                } else {
+                                    match ticker.cursor - ai::DIFFICULTIES.len() {
                                         0 => selection.ui = PlayerSelectUi::cpu_fighter(package),
                                         _ => {
                                             unreachable!()
@@ -423,9 +1043,19 @@ impl Menu {
                                     }
                                 }
                             }
-                            PlayerSelectUi::CpuAi(_) => {}
                             PlayerSelectUi::HumanUnplugged => unreachable!(),
                         }
+                    } else if input.l.press || input.r.press {
+                        // cycle through the costumes of the selected fighter
+                        let costume_count = selection
+                            .fighter
+                            .and_then(|x| fighters[x].1.fighter())
+                            .map_or(1, |x| x.costume_count());
+                        selection.costume = if input.r.press {
+                            (selection.costume + 1) % costume_count
+                        } else {
+                            (selection.costume + costume_count - 1) % costume_count
+                        };
                     }
 
                     match selection.ui {
@@ -471,6 +1101,7 @@ impl Menu {
                 self.fighter_selections.push(PlayerSelect {
                     controller: None,
                     fighter: None,
+                    costume: 0,
                     cpu_ai: None,
                     ui: PlayerSelectUi::cpu_fighter(package),
                     animation_frame: 0,
@@ -479,14 +1110,13 @@ impl Menu {
             }
 
             if player_inputs.iter().any(|x| x.start.press) && !fighters.is_empty() {
-                new_state = Some(MenuState::StageSelect);
-                if self.stage_ticker.is_none() {
-                    self.stage_ticker = Some(MenuTicker::new(package.stages.len()));
-                }
+                stage_select = true;
+            } else if self.keyboard_back {
+                back = true;
             } else if player_inputs.iter().any(|x| x[0].b) {
+                // B must be held to go back, as it is also used to deselect fighters
                 if *back_counter > self.back_counter_max {
-                    netplay.set_offline();
-                    new_state = Some(MenuState::GameSelect);
+                    back = true;
                 } else {
                     *back_counter += 1;
                 }
@@ -495,11 +1125,37 @@ impl Menu {
             }
         }
 
-        if let Some(state) = new_state {
-            self.state = state;
+        if fighter_confirmed {
+            self.queue_sfx(MenuSfx::FighterConfirm);
+        }
+        if back {
+            netplay.set_offline();
+            self.pop_state();
+        } else if stage_select {
+            let stages = self.rules(config, netplay).legal_stages(&package.stages);
+            // the selected stage is remembered unless the legal stages changed
+            let entries = stage_select_entries(stages.len());
+            if self
+                .stage_ticker
+                .as_ref()
+                .map_or(true, |x| x.cursor_max + 1 != entries)
+            {
+                self.stage_ticker = Some(MenuTicker::new(entries));
+            }
+            self.push_state(MenuState::StageSelect {
+                stages,
+                strike: None,
+            });
         }
     }
 
+    /// In netplay each peer starts as its own team, so the players sharing a couch play doubles together
+    fn default_team(selections: &[PlayerSelect], input: &Input, controller: usize) -> usize {
+        input
+            .netplay_peer(controller)
+            .unwrap_or_else(|| Menu::get_free_team(selections))
+    }
+
     fn get_free_team(selections: &[PlayerSelect]) -> usize {
         let mut team = 0;
         while selections
@@ -514,108 +1170,270 @@ impl Menu {
     fn step_stage_select(
         &mut self,
         package: &Package,
+        config: &Config,
         player_inputs: &[PlayerInput],
         netplay: &Netplay,
     ) {
-        if self.stage_ticker.is_none() {
-            self.stage_ticker = Some(MenuTicker::new(package.stages.len()));
-        }
-
+        let strikers = self.strikers();
+        let back_pressed = self.back_pressed(player_inputs);
         let ticker = self.stage_ticker.as_mut().unwrap();
+        let (stages, strike) = match &mut self.state {
+            MenuState::StageSelect { stages, strike } => (stages, strike),
+            _ => unreachable!(),
+        };
 
-        if player_inputs.iter().any(|x| x[0].stick_y > 0.4 || x[0].up) {
-            ticker.up();
-        } else if player_inputs
-            .iter()
-            .any(|x| x[0].stick_y < -0.4 || x[0].down)
-        {
-            ticker.down();
-        } else {
-            ticker.reset();
+        // None selects a random stage
+        let mut selected: Option<Option<String>> = None;
+        let mut back = false;
+        match strike {
+            Some(current) => {
+                // only the player whose turn it is can move the cursor and strike
+                let i = current.striker();
+                let striker = player_inputs.get(i..=i).unwrap_or(&[]);
+                ticker.step(striker);
+                if striker.iter().any(|x| x.a.press)
+                    && ticker.cursor < stages.len()
+                    && !current.struck.contains(&ticker.cursor)
+                {
+                    current.struck.push(ticker.cursor);
+                    if current.struck.len() + 1 >= stages.len() {
+                        selected = (0..stages.len())
+                            .find(|x| !current.struck.contains(x))
+                            .map(|x| Some(stages[x].clone()));
+                    }
+                } else if back_pressed || player_inputs.iter().any(|x| x.z.press) {
+                    *strike = None;
+                }
+            }
+            None => {
+                ticker.step(player_inputs);
+                if player_inputs.iter().any(|x| x.start.press || x.a.press) && !stages.is_empty() {
+                    selected = Some(stages.get(ticker.cursor).cloned());
+                } else if player_inputs.iter().any(|x| x.z.press) {
+                    // striking needs two players and a stage to strike
+                    if let (Some(strikers), true) = (strikers, stages.len() > 1) {
+                        *strike = Some(StageStrike {
+                            strikers,
+                            struck: vec![],
+                        });
+                    }
+                } else {
+                    back = back_pressed;
+                }
+            }
         }
 
-        if (player_inputs.iter().any(|x| x.start.press || x.a.press)) && package.stages.len() > 0 {
-            self.game_setup(package, netplay);
-        } else if player_inputs.iter().any(|x| x.b.press) {
-            self.state = MenuState::character_select();
+        if let Some(stage) = selected {
+            self.game_setup(package, config, netplay, stage);
+        } else if back {
+            self.pop_state();
         }
     }
 
-    pub fn game_setup(&mut self, package: &Package, netplay: &Netplay) {
-        let mut players: Vec<PlayerSetup> = vec![];
-        let mut controllers: Vec<usize> = vec![];
-        let mut ais: Vec<usize> = vec![];
+    /// The controllers of the two human players, None unless exactly two humans are playing
+    fn strikers(&self) -> Option<[usize; 2]> {
+        let humans: Vec<usize> = self
+            .fighter_selections
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.ui.is_human_plugged_in() && x.fighter.is_some())
+            .map(|(i, _)| i)
+            .collect();
+        match humans[..] {
+            [a, b] => Some([a, b]),
+            _ => None,
+        }
+    }
+
+    /// `stage` is None to play on a random legal stage
+    pub fn game_setup(
+        &mut self,
+        package: &Package,
+        config: &Config,
+        netplay: &Netplay,
+        stage: Option<String>,
+    ) {
+        let state = if netplay.number_of_peers() == 1 {
+            GameState::Local
+        } else {
+            GameState::Netplay
+        };
+
+        let mut entrants: Vec<Entrant> = vec![];
         let mut ais_skipped = 0;
         let fighters = package.fighters();
         for (i, selection) in (&self.fighter_selections).iter().enumerate() {
             // add human players
             if selection.ui.is_human_plugged_in() {
                 if let Some(fighter) = selection.fighter {
-                    players.push(PlayerSetup {
-                        fighter: fighters[fighter].0.clone(),
-                        team: selection.team,
+                    entrants.push(Entrant {
+                        setup: PlayerSetup {
+                            fighter: fighters[fighter].0.clone(),
+                            team: selection.team,
+                            stocks: None,
+                            // every netplay peer needs the same setup, so remote ports cannot be looked up in the config
+                            effect_colors: match state {
+                                GameState::Local => config.effect_colors(i),
+                                _ => EffectColors::default(),
+                            },
+                            costume: selection.costume,
+                            damage: 0.0,
+                        },
+                        name: fighters[fighter].1.name.clone(),
+                        controller: i,
+                        cpu: None,
+                        wins: 0,
+                        losses: 0,
                     });
-                    controllers.push(i);
                 }
             }
 
             // add CPU players
             if selection.ui.is_cpu() {
-                if selection.fighter.is_some()
-                /* && selection.cpu.is_some() TODO */
-                {
+                if selection.fighter.is_some() {
                     let fighter = selection.fighter.unwrap();
-                    players.push(PlayerSetup {
-                        fighter: fighters[fighter].0.clone(),
-                        team: selection.team,
+                    entrants.push(Entrant {
+                        setup: PlayerSetup {
+                            fighter: fighters[fighter].0.clone(),
+                            team: selection.team,
+                            stocks: None,
+                            effect_colors: EffectColors::default(),
+                            costume: selection.costume,
+                            damage: 0.0,
+                        },
+                        name: fighters[fighter].1.name.clone(),
+                        controller: i - ais_skipped,
+                        cpu: Some(selection.cpu_ai.unwrap_or(ai::DEFAULT_DIFFICULTY)),
+                        wins: 0,
+                        losses: 0,
                     });
-                    controllers.push(i - ais_skipped);
-                    ais.push(0); // TODO: delete this
-                                 // ais.push(selection.cpu_ai.unwrap()); TODO: add this
                 } else {
                     ais_skipped += 1;
                 }
             }
         }
 
-        let stage = package
-            .stages
-            .index_to_key(self.stage_ticker.as_ref().unwrap().cursor)
-            .unwrap();
-        let state = if netplay.number_of_peers() == 1 {
-            GameState::Local
-        } else {
-            GameState::Netplay
-        };
         let init_seed = netplay.get_seed().unwrap_or_else(GameSetup::gen_seed);
+        let rules = self.rules(config, netplay);
+        // the seed is shared by netplay peers, so they agree on the random stage
+        let stage = stage.unwrap_or_else(|| {
+            let stages = rules.legal_stages(&package.stages);
+            stages[(init_seed % stages.len() as u64) as usize].clone()
+        });
 
-        self.game_setup = Some(GameSetup {
-            input_history: vec![],
-            entity_history: Default::default(),
-            stage_history: vec![],
-            rules: Default::default(), // TODO: this will be configured by the user in the menu
-            debug: false,
-            max_history_frames: None,
-            current_frame: 0,
-            deleted_history_frames: 0,
-            debug_entities: Default::default(),
-            debug_stage: Default::default(),
-            camera: Camera::new(),
-            edit: Edit::Stage,
-            hot_reload_entities: None,
-            hot_reload_stage: None,
-            init_seed,
-            controllers,
-            ais,
-            players,
-            stage,
-            state,
+        // series are only supported locally
+        if let GameState::Local = state {
+            let stocks_per_player = Rules::default().stock_count.unwrap_or(4);
+            self.series = match self.series_mode {
+                SeriesMode::Single => None,
+                SeriesMode::CrewBattle => {
+                    Series::crew(entrants.clone(), stage.clone(), stocks_per_player)
+                }
+                SeriesMode::Tournament => Series::tournament(entrants.clone(), stage.clone()),
+            };
+            if self.series.is_none() && self.series_mode != SeriesMode::Single {
+                warn!("The selected players cannot form a series, starting a single game instead");
+            }
+        }
+
+        self.game_setup = Some(match self.series {
+            Some(ref series) => series_game_setup(series, init_seed, rules),
+            None => new_game_setup(&entrants, stage, state, init_seed, rules),
         });
     }
 
-    fn step_results(&mut self, config: &Config, player_inputs: &[PlayerInput]) {
-        if player_inputs.iter().any(|x| x.start.press || x.a.press) {
-            self.state = self.prev_state.take().unwrap();
+    /// The rules of the game being set up
+    fn rules(&self, config: &Config, netplay: &Netplay) -> Rules {
+        if netplay.number_of_peers() == 1 {
+            self.local_rules(config)
+        } else {
+            Menu::netplay_rules()
+        }
+    }
+
+    /// Every peer must agree on the rules, so netplay games ignore the settings menu.
+    /// Teams are always enabled so that two couches can play doubles.
+    fn netplay_rules() -> Rules {
+        Rules {
+            teams: Teams::On {
+                team_attack: false,
+                team_grab: true,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// The rules configured in the settings menu.
+    fn local_rules(&self, config: &Config) -> Rules {
+        let rules = Rules {
+            item_frequency: self.item_frequency.clone(),
+            teams: Teams::On {
+                team_attack: self.team_attack,
+                // teammates can always grab each other, e.g. to save a teammate from a ledge
+                team_grab: true,
+            },
+            assists: self.assists.clone(),
+            modifiers: self.modifiers.clone(),
+            rewind: self.series_mode != SeriesMode::Tournament,
+            pause: if self.series_mode == SeriesMode::Tournament {
+                Pause::Tokens(TOURNAMENT_PAUSE_TOKENS)
+            } else {
+                Pause::On
+            },
+            game_clock: self.game_clock,
+            respawn_frames: self.respawn_frames,
+            meter: self.meter,
+            late_join: self.series_mode == SeriesMode::Single,
+            legal_stages: config.legal_stages.clone(),
+            ..Default::default()
+        };
+        if self.tutorial {
+            Rules {
+                stock_count: None,
+                time_limit_seconds: None,
+                tutorial: Some(config.tutorial_progress),
+                late_join: false,
+                ..rules
+            }
+        } else {
+            rules
+        }
+    }
+
+    /// Start or A continues, while holding B + A + Start immediately restarts the game with the same setup.
+    /// Holding B first lets players reach the quick restart without leaving the results.
+    fn step_results(
+        &mut self,
+        config: &Config,
+        player_inputs: &[PlayerInput],
+        netplay: &Netplay,
+        toasts: &mut Toasts,
+    ) {
+        // peers cannot agree on a new seed and series decide their own next match, so only single local games can be restarted
+        let runback = self.series.is_none()
+            && netplay.number_of_peers() == 1
+            && player_inputs
+                .iter()
+                .any(|x| x.b.value && x.a.value && x.start.value);
+        if runback {
+            let results = self.game_results.as_ref().unwrap();
+            self.game_setup = Some(runback_game_setup(results, self.carry_damage));
+            self.pop_state();
+        } else if player_inputs
+            .iter()
+            .any(|x| (x.start.press || x.a.press) && !x.b.value)
+        {
+            self.pop_state();
+            match self.series {
+                Some(ref series) if series.next_match().is_some() => {
+                    self.game_setup = Some(series_game_setup(
+                        series,
+                        GameSetup::gen_seed(),
+                        self.local_rules(config),
+                    ));
+                }
+                _ => self.series = None,
+            }
         }
 
         // TODO:
@@ -632,14 +1450,17 @@ impl Menu {
                 && (config.auto_save_replay || player_inputs.iter().any(|x| x.l.press && x.r.press))
             {
                 replays::save_replay(&self.game_results.as_ref().unwrap().replay);
+                toasts.push(String::from("Replay saved"));
                 *replay_saved = true;
             }
         }
     }
 
     fn step_netplay_wait(&mut self, player_inputs: &[PlayerInput], netplay: &mut Netplay) {
-        if player_inputs.iter().any(|x| x.b.press) {
-            self.state = MenuState::GameSelect;
+        if self.back_pressed(player_inputs) {
+            netplay.set_offline();
+            self.pop_to_game_select();
+            return;
         }
 
         let loading_characters = ["|", "/", "-", "\\"];
@@ -655,41 +1476,78 @@ impl Menu {
                         request.region, load_character
                     ),
                 };
-                if player_inputs.iter().any(|x| x.b.press) {
-                    netplay.set_offline();
-                    self.state = MenuState::GameSelect;
+            }
+            NetplayState::Lobby { request } => {
+                if player_inputs.iter().any(|x| x.a.press) {
+                    netplay.set_lobby_ready(!request.ready);
+                } else if player_inputs.iter().any(|x| x.left.press) {
+                    netplay.set_lobby_players(request.players.saturating_sub(1));
+                } else if player_inputs.iter().any(|x| x.right.press) {
+                    netplay.set_lobby_players(request.players.saturating_add(1));
+                }
+
+                let mut lines = vec![format!(
+                    "Lobby {} {}\nA: Ready up    Left/Right: Number of players    B: Leave\n",
+                    request.room, load_character
+                )];
+                for member in netplay.lobby_members() {
+                    let name = if member.local {
+                        String::from("You")
+                    } else {
+                        member.address.to_string()
+                    };
+                    let ping = match member.ping {
+                        Some(ping) => format!("{}ms", ping.as_millis()),
+                        None if member.local => String::from("-"),
+                        None => String::from("?"),
+                    };
+                    let ready = if member.ready { "Ready" } else { "Not ready" };
+                    lines.push(format!(
+                        "{}    ping: {}    players: {}    {}",
+                        name, ping, member.players, ready
+                    ));
                 }
+                self.state = MenuState::NetplayWait {
+                    message: lines.join("\n"),
+                };
+            }
+            NetplayState::Rendezvous { request } => {
+                self.state = MenuState::NetplayWait {
+                    message: format!(
+                        "Waiting for a peer with room code {} {}",
+                        request.room_code, load_character
+                    ),
+                };
             }
             NetplayState::InitConnection { .. } => {
                 self.state = MenuState::NetplayWait {
                     message: format!("Connecting to peer {}", load_character),
                 };
-                if player_inputs.iter().any(|x| x.b.press) {
-                    netplay.set_offline();
-                    self.state = MenuState::GameSelect;
-                }
             }
             NetplayState::PingTest { .. } => {
                 self.state = MenuState::NetplayWait {
                     message: format!("Testing ping {}", load_character),
                 };
-                if player_inputs.iter().any(|x| x.b.press) {
-                    netplay.set_offline();
-                    self.state = MenuState::GameSelect;
-                }
+            }
+            NetplayState::Spectating { accepted: false } => {
+                self.state = MenuState::NetplayWait {
+                    message: format!("Waiting to spectate {}", load_character),
+                };
             }
             NetplayState::Disconnected { .. } => {
-                if player_inputs.iter().any(|x| x.a.press || x.b.press) {
+                if player_inputs.iter().any(|x| x.a.press) {
                     netplay.set_offline();
-                    self.state = MenuState::GameSelect;
+                    self.pop_to_game_select();
                 }
             }
-            NetplayState::Running { .. } => {
+            NetplayState::Running | NetplayState::Spectating { accepted: true } => {
+                // replace the wait screen so backing out of the CSS skips it
                 self.state = MenuState::character_select();
             }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn step(
         &mut self,
         package: &Package,
@@ -697,60 +1555,111 @@ impl Menu {
         input: &mut Input,
         os_input: &WinitInputHelper,
         netplay: &mut Netplay,
+        graphics_adapters: &[String],
+        toasts: &mut Toasts,
     ) -> Option<GameSetup> {
-        if os_input.held_alt() && os_input.key_pressed_os(VirtualKeyCode::Return) {
+        // Hotkeys are disabled while rebinding them so the new key doesnt trigger its old action
+        let rebinding = matches!(
+            self.state,
+            MenuState::HotkeySettings {
+                rebinding: true,
+                ..
+            }
+        );
+        if !rebinding && config.hotkeys.pressed(HotkeyAction::Screenshot, os_input) {
+            self.screenshot = true;
+        }
+        if (os_input.held_alt() && os_input.key_pressed_os(VirtualKeyCode::Return))
+            || (!rebinding && config.hotkeys.pressed(HotkeyAction::Fullscreen, os_input))
+        {
             config.fullscreen = !config.fullscreen;
             config.save();
         }
 
+        // Keyboard input is not shared with peers so it cannot be used during netplay
+        let keyboard_back =
+            os_input.key_pressed_os(VirtualKeyCode::Escape) && netplay.number_of_peers() == 1;
+
         // skip a frame so the other clients can catch up.
         if !netplay.skip_frame() {
             self.current_frame += 1;
 
-            let start = self.current_frame - netplay.frames_to_step();
+            // rollback to the last frame that was stepped with correct inputs
             let end = self.current_frame;
+            let start = input.netplay_update(netplay).min(end);
 
-            self.netplay_history.truncate(start);
-            if start > 0 {
-                let history = self.netplay_history.get(start - 1).unwrap();
+            self.netplay_history.truncate(start - 1);
+            if start > 1 {
+                let history = self.netplay_history.get(start - 2).unwrap();
                 self.state = history.state.clone();
-                self.prev_state = history.prev_state.clone();
+                self.back_stack = history.back_stack.clone();
                 self.fighter_selections = history.fighter_selections.clone();
                 self.stage_ticker = history.stage_ticker.clone();
             }
 
-            input.netplay_update();
-
-            for frame in start..end {
+            for frame in start..=end {
                 if let NetplayState::Disconnected { reason } = netplay.state() {
                     self.state = MenuState::NetplayWait { message: reason };
                 }
 
                 let player_inputs = input.players(frame, netplay);
+                self.keyboard_back = keyboard_back && frame == end;
 
                 // In order to avoid hitting buttons still held down from the game, dont do anything on the first frame.
                 if frame > 1 {
                     match self.state {
+                        MenuState::MainMenu => self.step_main_menu(&player_inputs),
                         MenuState::GameSelect => {
                             self.step_game_select(package, config, &player_inputs, netplay)
                         }
+                        MenuState::Settings => self.step_settings(
+                            config,
+                            input,
+                            &player_inputs,
+                            graphics_adapters,
+                            toasts,
+                        ),
+                        MenuState::Extras { .. } => self.step_extras(&player_inputs),
                         MenuState::ReplaySelect(_, _) => self.step_replay_select(&player_inputs),
-                        MenuState::CharacterSelect { .. } => {
-                            self.step_fighter_select(package, &player_inputs, netplay)
+                        MenuState::HotkeySettings { .. } => {
+                            self.step_hotkey_settings(config, &player_inputs, os_input)
+                        }
+                        MenuState::AudioSettings { .. } => {
+                            self.step_audio_settings(config, &player_inputs)
                         }
-                        MenuState::StageSelect => {
-                            self.step_stage_select(package, &player_inputs, netplay)
+                        MenuState::Modifiers { .. } => self.step_modifiers(&player_inputs),
+                        MenuState::ControllerSettings { .. } => self.step_controller_settings(
+                            config,
+                            input,
+                            &player_inputs,
+                            os_input,
+                            frame == end,
+                        ),
+                        MenuState::CharacterSelect { .. } => self.step_fighter_select(
+                            package,
+                            config,
+                            input,
+                            &player_inputs,
+                            netplay,
+                        ),
+                        MenuState::StageSelect { .. } => {
+                            self.step_stage_select(package, config, &player_inputs, netplay)
+                        }
+                        MenuState::GameResults { .. } => {
+                            self.step_results(config, &player_inputs, netplay, toasts)
                         }
-                        MenuState::GameResults { .. } => self.step_results(config, &player_inputs),
                         MenuState::NetplayWait { .. } => {
                             self.step_netplay_wait(&player_inputs, netplay)
                         }
+                        MenuState::SpectateAddress { .. } => {
+                            self.step_spectate_address(config, &player_inputs, os_input, netplay)
+                        }
                     };
                 }
 
                 self.netplay_history.push(NetplayHistory {
                     state: self.state.clone(),
-                    prev_state: self.prev_state.clone(),
+                    back_stack: self.back_stack.clone(),
                     fighter_selections: self.fighter_selections.clone(),
                     stage_ticker: self.stage_ticker.clone(),
                 });
@@ -762,12 +1671,16 @@ impl Menu {
     }
 
     #[allow(dead_code)] // Needed for headless build
-    pub fn render(&self) -> RenderMenu {
+    pub fn render(&self, config: &Config) -> RenderMenu {
         RenderMenu {
             state: match self.state {
-                MenuState::GameResults { replay_saved } => RenderMenuState::GameResults {
+                MenuState::GameResults { .. } => RenderMenuState::GameResults {
                     results: self.game_results.as_ref().unwrap().player_results.clone(),
-                    replay_saved,
+                    standings: self
+                        .series
+                        .as_ref()
+                        .map(|x| x.standings())
+                        .unwrap_or_default(),
                 },
                 MenuState::CharacterSelect { back_counter, .. } => {
                     RenderMenuState::CharacterSelect(
@@ -782,28 +1695,216 @@ impl Menu {
                 MenuState::NetplayWait { ref message } => {
                     RenderMenuState::GenericText(message.clone())
                 }
+                MenuState::SpectateAddress {
+                    ref address,
+                    ref message,
+                } => RenderMenuState::GenericText(format!(
+                    "Type the IP address of a player to spectate and press enter\n\n{}■\n\n{}",
+                    address, message
+                )),
+                MenuState::HotkeySettings {
+                    ref ticker,
+                    ref message,
+                    ..
+                } => RenderMenuState::HotkeySettings {
+                    hotkeys: HotkeyAction::iter()
+                        .map(|x| (format!("{:?}", x), config.hotkeys.get(x).to_string()))
+                        .collect(),
+                    selection: ticker.cursor,
+                    message: message.clone(),
+                },
+                MenuState::AudioSettings { ref ticker } => RenderMenuState::AudioSettings {
+                    settings: AUDIO_OPTIONS
+                        .iter()
+                        .enumerate()
+                        .map(|(i, name)| {
+                            let percent = |x: f64| format!("{}: {:.0}%", name, x * 100.0);
+                            match i {
+                                0 => percent(config.audio.master_volume),
+                                1 => percent(config.audio.bgm_volume),
+                                2 => percent(config.audio.sfx_volume),
+                                3 if config.audio.mute => format!("{}: On", name),
+                                3 => format!("{}: Off", name),
+                                _ => unreachable!(),
+                            }
+                        })
+                        .collect(),
+                    selection: ticker.cursor,
+                },
+                MenuState::Modifiers { ref ticker } => RenderMenuState::Modifiers {
+                    settings: MODIFIER_OPTIONS
+                        .iter()
+                        .enumerate()
+                        .map(|(i, name)| {
+                            let on_off = |x| if x { "On" } else { "Off" };
+                            let value = match i {
+                                0 => self.modifiers.gravity.name(),
+                                1 => self.modifiers.speed.name(),
+                                2 => on_off(self.modifiers.invisible),
+                                3 => on_off(self.modifiers.one_hit_ko),
+                                4 => on_off(self.modifiers.fixed_camera),
+                                5 => self.modifiers.size.name(),
+                                _ => unreachable!(),
+                            };
+                            format!("{}: {}", name, value)
+                        })
+                        .collect(),
+                    selection: ticker.cursor,
+                },
+                MenuState::ControllerSettings {
+                    ref controllers,
+                    ref triggers,
+                    ref ticker,
+                    ref message,
+                    ..
+                } => RenderMenuState::ControllerSettings {
+                    controllers: controllers
+                        .iter()
+                        .enumerate()
+                        .map(|(i, name)| match triggers.get(i) {
+                            Some(triggers) => format!("{}  {}", name, triggers),
+                            None => name.clone(),
+                        })
+                        .collect(),
+                    selection: ticker.cursor,
+                    message: message.clone(),
+                },
+                MenuState::MainMenu => RenderMenuState::MainMenu(self.main_ticker.cursor),
                 MenuState::GameSelect => RenderMenuState::GameSelect(self.game_ticker.cursor),
-                MenuState::StageSelect => {
-                    RenderMenuState::StageSelect(self.stage_ticker.as_ref().unwrap().cursor)
-                }
+                MenuState::Settings => RenderMenuState::Settings {
+                    settings: SETTINGS_OPTIONS
+                        .iter()
+                        .enumerate()
+                        .map(|(i, name)| {
+                            let on_off = |x| if x { "On" } else { "Off" };
+                            let value = match i {
+                                1 => on_off(config.auto_save_replay),
+                                2 => on_off(config.show_session_report),
+                                3 if config.discord_client_id.is_none() => "No client id",
+                                3 => on_off(config.discord_rich_presence),
+                                4 => match config.hud.percent_precision {
+                                    PercentPrecision::Whole => "Whole",
+                                    PercentPrecision::Tenths => "Tenths",
+                                },
+                                5 => on_off(config.hud.show_internal_damage),
+                                6 => match config.hud.stock_display {
+                                    StockDisplay::Icons => "Icons",
+                                    StockDisplay::Count => "Count",
+                                },
+                                7..=10 => {
+                                    let haptics = config.haptics(self.settings_port);
+                                    return format!(
+                                        "{} (port {}): {}",
+                                        name,
+                                        self.settings_port + 1,
+                                        haptics.cue(HAPTIC_EVENTS[i - 7]).name()
+                                    );
+                                }
+                                12 => self.item_frequency.name(),
+                                13 => on_off(self.team_attack),
+                                14 => on_off(self.assists.auto_lcancel),
+                                15 => on_off(self.assists.auto_tech),
+                                16 if self.assists.wavedash_buffer > 0 => {
+                                    return format!(
+                                        "{}: {} frames",
+                                        name, self.assists.wavedash_buffer
+                                    );
+                                }
+                                16 => "Off",
+                                17 if self.game_clock => "Count up",
+                                17 => "Count down",
+                                18 => {
+                                    return format!("{}: {}s", name, self.respawn_frames / 60);
+                                }
+                                20 => config.graphics_adapter.as_deref().unwrap_or("Default"),
+                                21 => on_off(self.modifiers.any()),
+                                22 => config.hud.minimap.name(),
+                                23 => config.hud.mode.name(),
+                                24 => return format!("{}: {}x", name, config.hud.scale),
+                                25 => config.hud.player_anchor.name(),
+                                26 => config.hud.timer_anchor.name(),
+                                27 => config.team_palette.name(),
+                                28 => on_off(config.hud.player_indicators),
+                                29 => on_off(self.carry_damage),
+                                30 => on_off(self.meter),
+                                _ => return name.to_string(),
+                            };
+                            format!("{}: {}", name, value)
+                        })
+                        .collect(),
+                    selection: self.settings_ticker.cursor,
+                },
+                MenuState::Extras { ref message } => RenderMenuState::Extras {
+                    selection: self.extras_ticker.cursor,
+                    message: message.clone(),
+                },
+                MenuState::StageSelect {
+                    ref stages,
+                    ref strike,
+                } => RenderMenuState::StageSelect {
+                    stages: stages.clone(),
+                    random: stage_select_entries(stages.len()) > stages.len(),
+                    struck: strike.as_ref().map_or(vec![], |x| x.struck.clone()),
+                    selection: self.stage_ticker.as_ref().unwrap().cursor,
+                    message: match strike {
+                        Some(strike) => format!(
+                            "Port {} strikes a stage, B: stop striking",
+                            strike.striker() + 1
+                        ),
+                        None if self.strikers().is_some() && stages.len() > 1 => {
+                            String::from("Z: stage striking")
+                        }
+                        None => String::new(),
+                    },
+                },
             },
         }
     }
 
+    pub fn presence_activity(&self) -> Activity {
+        let state = match self.state {
+            MenuState::MainMenu => "Main menu",
+            MenuState::GameSelect => "Selecting a game mode",
+            MenuState::Settings => "Changing settings",
+            MenuState::Extras { .. } => "Browsing extras",
+            MenuState::ReplaySelect(..) => "Selecting a replay",
+            MenuState::HotkeySettings { .. }
+            | MenuState::ControllerSettings { .. }
+            | MenuState::AudioSettings { .. }
+            | MenuState::Modifiers { .. } => "Changing settings",
+            MenuState::CharacterSelect { .. } => "Selecting a fighter",
+            MenuState::StageSelect { .. } => "Selecting a stage",
+            MenuState::GameResults { .. } => "Viewing results",
+            MenuState::NetplayWait { .. } => "Waiting for an opponent",
+            MenuState::SpectateAddress { .. } => "Choosing a game to spectate",
+        };
+        Activity {
+            details: "In menus".into(),
+            state: state.into(),
+        }
+    }
+
     #[allow(dead_code)] // Needed for headless build
     pub fn graphics_message(
         &mut self,
         package: &mut Package,
         config: &Config,
         command_line: &CommandLine,
+        toasts: &Toasts,
     ) -> GraphicsMessage {
         let updates = package.updates();
 
         let render = Render {
             command_output: command_line.output(),
-            render_type: RenderType::Menu(self.render()),
+            render_type: RenderType::Menu(self.render(config)),
             fullscreen: config.fullscreen,
+            frame_limiter: Some(config.frame_limiter.clone()).filter(|x| x.enabled),
+            screenshot: self.screenshot,
+            export_frame: None,
+            toasts: toasts.messages(),
+            team_palette: config.team_palette.clone(),
         };
+        self.screenshot = false;
 
         GraphicsMessage {
             package_updates: updates,
@@ -812,14 +1913,249 @@ impl Menu {
     }
 }
 
+/// Moves the volume up or down a step, wrapping from full volume to silent and back
+fn step_volume(volume: f64, raise: bool) -> f64 {
+    let steps = (volume / VOLUME_STEP).round() as i64;
+    let max_steps = (1.0 / VOLUME_STEP).round() as i64;
+    let steps = if raise {
+        if steps >= max_steps {
+            0
+        } else {
+            steps + 1
+        }
+    } else if steps <= 0 {
+        max_steps
+    } else {
+        steps - 1
+    };
+    steps as f64 * VOLUME_STEP
+}
+
+/// Creates the setup for a game between the entrants
+fn new_game_setup(
+    entrants: &[Entrant],
+    stage: String,
+    state: GameState,
+    init_seed: u64,
+    rules: Rules,
+) -> GameSetup {
+    let mut players = vec![];
+    let mut controllers = vec![];
+    let mut ais = vec![];
+    for entrant in entrants {
+        players.push(entrant.setup.clone());
+        controllers.push(entrant.controller);
+        if let Some(difficulty) = entrant.cpu {
+            ais.push(difficulty);
+        }
+    }
+
+    GameSetup {
+        input_history: vec![],
+        entity_history: Default::default(),
+        stage_history: vec![],
+        rules,
+        debug: false,
+        max_history_frames: None,
+        current_frame: 0,
+        deleted_history_frames: 0,
+        debug_entities: Default::default(),
+        debug_stage: Default::default(),
+        camera: Camera::new(),
+        edit: Edit::Stage,
+        hot_reload_entities: None,
+        hot_reload_stage: None,
+        init_seed,
+        controllers,
+        ais,
+        players,
+        stage,
+        state,
+    }
+}
+
+/// Creates the setup for a rematch of the finished game with the same players, stage and rules but a new seed.
+/// When `carry_damage` is set, each player starts at the damage they ended the finished game with.
+fn runback_game_setup(results: &GameResults, carry_damage: bool) -> GameSetup {
+    let replay = &results.replay;
+    let mut players = replay.selected_players.clone();
+    for (player, controller) in players.iter_mut().zip(replay.selected_controllers.iter()) {
+        player.damage = results
+            .player_results
+            .iter()
+            .find(|x| x.controller == *controller)
+            .filter(|_| carry_damage)
+            .map_or(0.0, |x| x.final_damage);
+    }
+
+    let mut setup = new_game_setup(
+        &[],
+        replay.selected_stage.clone(),
+        GameState::Local,
+        GameSetup::gen_seed(),
+        replay.rules.clone(),
+    );
+    setup.players = players;
+    setup.controllers = replay.selected_controllers.clone();
+    setup.ais = replay.selected_ais.clone();
+    setup
+}
+
+/// Creates the setup for the next match of the series, the series must not be over
+fn series_game_setup(series: &Series, init_seed: u64, rules: Rules) -> GameSetup {
+    let series_match = series.next_match().unwrap();
+    let mut entrants = vec![];
+    for (i, stocks) in series_match.entrants.iter().zip(series_match.stocks.iter()) {
+        let mut entrant = series.entrants[*i].clone();
+        entrant.setup.stocks = *stocks;
+        entrants.push(entrant);
+    }
+    let mode = match series.state {
+        SeriesState::Crew { .. } => SeriesMode::CrewBattle,
+        SeriesState::Tournament { .. } => SeriesMode::Tournament,
+    };
+    let rules = Rules {
+        series: mode,
+        ..rules
+    };
+    new_game_setup(
+        &entrants,
+        series.stage.clone(),
+        GameState::Local,
+        init_seed,
+        rules,
+    )
+}
+
+pub const MAIN_MENU_OPTIONS: [&str; 4] = ["Play", "Settings", "Extras", "Quit"];
+pub const GAME_SELECT_OPTIONS: [&str; 7] = [
+    "Local",
+    "Netplay",
+    "Netplay Lobby",
+    "Crew Battle",
+    "Tournament",
+    "Spectate",
+    "Tutorial",
+];
+pub const SETTINGS_OPTIONS: [&str; 31] = [
+    "Hotkeys",
+    "Auto save replays",
+    "Show session report",
+    "Discord rich presence",
+    "Percent display",
+    "Show internal damage",
+    "Stock display",
+    "Hit rumble",
+    "Parry rumble",
+    "Shield break rumble",
+    "KO rumble",
+    "Controllers",
+    "Items",
+    "Team attack",
+    "Auto L-cancel",
+    "Auto tech",
+    "Wavedash buffer",
+    "Stock match clock",
+    "Respawn timer",
+    "Audio",
+    "Graphics adapter",
+    "Modifiers",
+    "Minimap",
+    "HUD",
+    "HUD scale",
+    "HUD position",
+    "Clock position",
+    "Team colors",
+    "Player indicators",
+    "Runback keeps percent",
+    "Super meter",
+];
+pub const AUDIO_OPTIONS: [&str; 4] = [
+    "Master volume",
+    "Music volume",
+    "Sound effect volume",
+    "Mute",
+];
+pub const MODIFIER_OPTIONS: [&str; 6] = [
+    "Gravity",
+    "Game speed",
+    "Invisible fighters",
+    "One hit KO",
+    "Fixed camera",
+    "Fighter size",
+];
+/// Volume settings go up and down in steps of this amount
+const VOLUME_STEP: f64 = 0.1;
+/// The wavedash buffer setting cycles through multiples of this up to WAVEDASH_BUFFER_MAX frames
+const WAVEDASH_BUFFER_STEP: u64 = 2;
+const WAVEDASH_BUFFER_MAX: u64 = 6;
+/// The trigger precision setting cycles through these trigger curve exponents
+const TRIGGER_EXPONENTS: [f32; 3] = [1.0, 1.5, 2.0];
+const TRIGGER_PRECISIONS: [&str; 3] = ["Linear", "Light shield", "Extra light shield"];
+/// Trigger full presses below this are treated as the triggers not being held while setting it
+const TRIGGER_FULL_PRESS_MIN: f32 = 0.3;
+/// The respawn timer setting cycles through these frame counts
+const RESPAWN_FRAMES: [u64; 4] = [180, 300, 600, 1000];
+/// The HUD scale setting cycles through these scales
+const HUD_SCALES: [f32; 5] = [0.75, 1.0, 1.25, 1.5, 2.0];
+/// Pauses each controller gets per tournament game
+const TOURNAMENT_PAUSE_TOKENS: u64 = 1;
+/// The events configured by the rumble settings, in the same order as SETTINGS_OPTIONS
+const HAPTIC_EVENTS: [HapticEvent; 4] = [
+    HapticEvent::Hit,
+    HapticEvent::Parry,
+    HapticEvent::ShieldBreak,
+    HapticEvent::Ko,
+];
+pub const EXTRAS_OPTIONS: [&str; 3] = ["Replays", "Trials", "Stats"];
+
 #[derive(Clone)]
 pub enum MenuState {
+    MainMenu,
     GameSelect,
+    Settings,
+    Extras {
+        message: String,
+    },
     ReplaySelect(Vec<String>, MenuTicker), // MenuTicker must be tied with the Vec<String>, otherwise they may become out of sync
-    CharacterSelect { back_counter: usize },
-    StageSelect,
-    GameResults { replay_saved: bool },
-    NetplayWait { message: String },
+    CharacterSelect {
+        back_counter: usize,
+    },
+    StageSelect {
+        /// The keys of the legal stages, followed by a random entry when there is more than one
+        stages: Vec<String>,
+        strike: Option<StageStrike>,
+    },
+    GameResults {
+        replay_saved: bool,
+    },
+    NetplayWait {
+        message: String,
+    },
+    SpectateAddress {
+        address: String,
+        message: String,
+    },
+    HotkeySettings {
+        ticker: MenuTicker,
+        rebinding: bool,
+        message: String,
+    },
+    AudioSettings {
+        ticker: MenuTicker,
+    },
+    Modifiers {
+        ticker: MenuTicker,
+    },
+    /// The ticker selects a local controller
+    ControllerSettings {
+        controllers: Vec<String>,
+        /// The current trigger values of each controller
+        triggers: Vec<String>,
+        ticker: MenuTicker,
+        rebind: Option<Rebind>,
+        message: String,
+    },
 }
 
 impl MenuState {
@@ -829,6 +2165,39 @@ impl MenuState {
         MenuState::ReplaySelect(replays, ticker)
     }
 
+    pub fn hotkey_settings() -> MenuState {
+        MenuState::HotkeySettings {
+            ticker: MenuTicker::new(HotkeyAction::iter().count()),
+            rebinding: false,
+            message: String::new(),
+        }
+    }
+
+    pub fn audio_settings() -> MenuState {
+        MenuState::AudioSettings {
+            ticker: MenuTicker::new(AUDIO_OPTIONS.len()),
+        }
+    }
+
+    pub fn modifiers() -> MenuState {
+        MenuState::Modifiers {
+            ticker: MenuTicker::new(MODIFIER_OPTIONS.len()),
+        }
+    }
+
+    pub fn controller_settings(input: &Input) -> MenuState {
+        let controllers = input.controller_names();
+        MenuState::ControllerSettings {
+            ticker: MenuTicker::new(controllers.len()),
+            controllers,
+            triggers: vec![],
+            rebind: None,
+            message: String::from(
+                "A: rebind, X/Y: calibrate deadzones, Z: set trigger full press, Left/Right: trigger precision",
+            ),
+        }
+    }
+
     pub fn character_select() -> MenuState {
         MenuState::CharacterSelect { back_counter: 0 }
     }
@@ -841,21 +2210,86 @@ impl MenuState {
 }
 
 pub enum RenderMenuState {
+    MainMenu(usize),
     GameSelect(usize),
+    Settings {
+        settings: Vec<String>,
+        selection: usize,
+    },
+    Extras {
+        selection: usize,
+        message: String,
+    },
     ReplaySelect(Vec<String>, usize),
     CharacterSelect(Vec<PlayerSelect>, usize, usize),
-    StageSelect(usize),
+    StageSelect {
+        /// Keys of the legal stages
+        stages: Vec<String>,
+        /// A random entry follows the stages
+        random: bool,
+        /// Indexes into the stages that have been struck
+        struck: Vec<usize>,
+        selection: usize,
+        message: String,
+    },
     GameResults {
         results: Vec<PlayerResult>,
-        replay_saved: bool,
+        /// Progress of the crew battle or tournament, empty outside of a series
+        standings: Vec<String>,
     },
     GenericText(String),
+    HotkeySettings {
+        hotkeys: Vec<(String, String)>,
+        selection: usize,
+        message: String,
+    },
+    AudioSettings {
+        settings: Vec<String>,
+        selection: usize,
+    },
+    Modifiers {
+        settings: Vec<String>,
+        selection: usize,
+    },
+    ControllerSettings {
+        controllers: Vec<String>,
+        selection: usize,
+        message: String,
+    },
+}
+
+/// Two players take turns removing stages until one remains, which is then played on
+#[derive(Clone)]
+pub struct StageStrike {
+    /// The controllers of the two players
+    strikers: [usize; 2],
+    /// Indexes into the legal stages that have been struck
+    struck: Vec<usize>,
+}
+
+impl StageStrike {
+    /// The controller whose turn it is, players take turns in the order 1, 2, 2, 1, 1, 2...
+    fn striker(&self) -> usize {
+        self.strikers[(self.struck.len() + 1) / 2 % 2]
+    }
+}
+
+/// The number of entries in the stage select, a random entry is added when there is a choice of stages
+fn stage_select_entries(stages: usize) -> usize {
+    if stages > 1 {
+        stages + 1
+    } else {
+        stages
+    }
 }
 
 #[derive(Clone)]
 pub struct PlayerSelect {
     pub controller: Option<(usize, MenuTicker)>, // the cursor of the ticker is ignored
     pub fighter: Option<usize>,
+    /// Index into the costumes of the selected fighter, 0 is the default appearance
+    pub costume: usize,
+    /// The difficulty selected for a CPU, None uses the default difficulty
     pub cpu_ai: Option<usize>,
     pub team: usize,
     pub ui: PlayerSelectUi,
@@ -870,7 +2304,6 @@ impl PlayerSelect {
 }
 
 #[derive(Clone)]
-#[allow(dead_code)]
 pub enum PlayerSelectUi {
     CpuAi(MenuTicker),
     CpuFighter(MenuTicker),
@@ -881,9 +2314,8 @@ pub enum PlayerSelectUi {
 }
 
 impl PlayerSelectUi {
-    #[allow(dead_code)]
     pub fn cpu_ai() -> Self {
-        PlayerSelectUi::CpuAi(MenuTicker::new(/* TODO: number_of_ai + */ 1))
+        PlayerSelectUi::CpuAi(MenuTicker::new(ai::DIFFICULTIES.len() + 1))
     }
 
     pub fn cpu_fighter(package: &Package) -> Self {
@@ -895,11 +2327,11 @@ impl PlayerSelectUi {
     }
 
     pub fn cpu_team() -> Self {
-        PlayerSelectUi::CpuTeam(MenuTicker::new(graphics::get_colors().len() + 1))
+        PlayerSelectUi::CpuTeam(MenuTicker::new(graphics::TEAM_COUNT + 1))
     }
 
     pub fn human_team() -> Self {
-        PlayerSelectUi::HumanTeam(MenuTicker::new(graphics::get_colors().len() + 1))
+        PlayerSelectUi::HumanTeam(MenuTicker::new(graphics::TEAM_COUNT + 1))
     }
 
     pub fn is_visible(&self) -> bool {
@@ -964,7 +2396,7 @@ pub struct MenuTicker {
 }
 
 impl MenuTicker {
-    fn new(item_count: usize) -> MenuTicker {
+    pub fn new(item_count: usize) -> MenuTicker {
         MenuTicker {
             cursor: 0,
             cursor_max: if item_count > 0 { item_count - 1 } else { 0 },
@@ -1019,27 +2451,22 @@ impl MenuTicker {
     fn reset(&mut self) {
         self.reset = true;
     }
+
+    /// Moves the cursor up/down when any player holds up/down
+    pub fn step(&mut self, player_inputs: &[PlayerInput]) {
+        if player_inputs.iter().any(|x| x[0].stick_y > 0.4 || x[0].up) {
+            self.up();
+        } else if player_inputs
+            .iter()
+            .any(|x| x[0].stick_y < -0.4 || x[0].down)
+        {
+            self.down();
+        } else {
+            self.reset();
+        }
+    }
 }
 
 pub struct RenderMenu {
     pub state: RenderMenuState,
 }
-
-/// # Game -> Menu Transitions
-/// Results:   Game complete   -> display results -> CSS
-/// Unchanged: Game quit       -> CSS
-/// Results:   Replay complete -> display results -> replay ui
-/// Unchanged: Replay quit     -> replay ui
-
-#[derive(Clone, Serialize, Deserialize, Node)]
-pub enum ResumeMenu {
-    Results(GameResults),
-    Unchanged,
-    NetplayDisconnect { reason: String },
-}
-
-impl Default for ResumeMenu {
-    fn default() -> Self {
-        ResumeMenu::Unchanged
-    }
-}
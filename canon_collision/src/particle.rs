@@ -1,3 +1,5 @@
+use canon_collision_lib::entity_def::HitSpark;
+
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Particle {
     pub color: [f32; 3],
@@ -16,6 +18,7 @@ pub enum ParticleType {
     Hit {
         knockback: f32,
         damage: f32,
+        spark: HitSpark,
     },
     Spark {
         x_vel: f32,
@@ -0,0 +1,437 @@
+use crate::ai::DummyBehavior;
+use crate::entity::EntityKey;
+use crate::menu::MenuTicker;
+
+use canon_collision_lib::config::Minimap;
+use canon_collision_lib::input::state::PlayerInput;
+
+const PAUSE_OPTIONS: [&str; 10] = [
+    "Resume",
+    "Controls",
+    "Hitboxes",
+    "Ledge timers",
+    "Minimap",
+    "Mirror inputs",
+    "Training dummy",
+    "Entities",
+    "Character Select",
+    "Quit",
+];
+
+const DUMMY_OPTIONS: [&str; 4] = ["DI", "Tech", "Shield", "Back"];
+
+const UNSAVED_OPTIONS: [&str; 3] = ["Save", "Don't save", "Cancel"];
+
+/// Frames simulated each step while R is held on a paused game
+const FAST_FORWARD_FRAMES: usize = 4;
+
+const CONTROLS: [&str; 11] = [
+    "Stick: Move",
+    "A: Attack",
+    "B: Special",
+    "X/Y: Jump",
+    "L/R: Shield",
+    "Z: Grab",
+    "Start: Pause",
+    "A + L + R + Start: Quit",
+    "Z while paused: Next frame",
+    "X while paused: Previous frame",
+    "Hold R while paused: Fast forward",
+];
+
+/// What the game should do in response to the pause menu
+pub enum PauseAction {
+    Resume,
+    ToggleHitboxes,
+    /// Show or hide the bars above each fighter counting down their ledge, intangibility and regrab timers
+    ToggleLedgeTimers,
+    /// Cycle the minimap zoom of this controller's player, None cycles it for every player
+    CycleMinimap(Option<usize>),
+    /// Mirror the inputs of this controller to every other controller, None stops mirroring
+    MirrorInputs(Option<usize>),
+    /// Change how CPUs with the Idle difficulty behave
+    Dummy(DummyBehavior),
+    Entity(EntityAction),
+    /// Advance the paused game by `frames`, the controller pressed the buttons to do so
+    StepForward {
+        frames: usize,
+        controller: usize,
+    },
+    /// Step the paused game back a frame
+    StepBackward,
+    /// Leave the game for the character select screen or the menu it was started from,
+    /// saving the package first when `save` is set
+    Leave {
+        character_select: bool,
+        save: bool,
+    },
+}
+
+/// An option of the entities menu, for testing items and projectiles without a fighter to spawn them
+#[derive(Clone)]
+pub struct EntityOption {
+    pub label: String,
+    pub action: EntityAction,
+}
+
+#[derive(Clone)]
+pub enum EntityAction {
+    /// Spawn the entity with this key, `kind` is the kind used by the spawn command
+    Spawn { kind: &'static str, entity: String },
+    /// Despawn the entity along with everything attached to it
+    Despawn(EntityKey),
+}
+
+/// Displayed over a paused local game or replay.
+/// Only the controller that paused the game can navigate the menu, any controller can when the game was paused from the keyboard.
+#[derive(Clone)]
+pub struct PauseMenu {
+    controller: Option<usize>,
+    ticker: MenuTicker,
+    controls: bool,
+    /// The inputs of a controller are being mirrored to every other controller
+    mirrored: bool,
+    /// Displaying the training dummy options, with their own cursor
+    dummy_ticker: Option<MenuTicker>,
+    dummy: DummyBehavior,
+    /// Displaying the entities that can be spawned or despawned, with their own cursor
+    entities: Option<(MenuTicker, Vec<EntityOption>)>,
+    /// Asking whether to save the package before leaving the game, with its own cursor.
+    /// Also stores whether the game is left for the character select screen.
+    unsaved: Option<(MenuTicker, bool)>,
+}
+
+impl PauseMenu {
+    pub fn new(controller: Option<usize>, mirrored: bool, dummy: DummyBehavior) -> PauseMenu {
+        PauseMenu {
+            controller,
+            ticker: MenuTicker::new(PAUSE_OPTIONS.len()),
+            controls: false,
+            mirrored,
+            dummy_ticker: None,
+            dummy,
+            entities: None,
+            unsaved: None,
+        }
+    }
+
+    /// Asks whether to save the package before leaving the game
+    pub fn confirm_unsaved(&mut self, character_select: bool) {
+        self.controls = false;
+        self.dummy_ticker = None;
+        self.entities = None;
+        self.unsaved = Some((MenuTicker::new(UNSAVED_OPTIONS.len()), character_select));
+    }
+
+    /// The controller that paused the game, None when paused from the keyboard
+    pub fn controller(&self) -> Option<usize> {
+        self.controller
+    }
+
+    /// Waiting for an answer to `confirm_unsaved`
+    pub fn confirming_unsaved(&self) -> bool {
+        self.unsaved.is_some()
+    }
+
+    /// `player_inputs` is indexed by controller.
+    /// `unsaved_changes` is only called when leaving the game, to ask whether to save the package first.
+    /// `entity_options` is called every step the entities menu is open, so it lists the current entities.
+    pub fn step<F, G>(
+        &mut self,
+        player_inputs: Vec<PlayerInput>,
+        unsaved_changes: F,
+        entity_options: G,
+    ) -> Option<PauseAction>
+    where
+        F: Fn() -> bool,
+        G: Fn() -> Vec<EntityOption>,
+    {
+        // the controller of each of the player_inputs
+        let controllers: Vec<usize> = match self.controller {
+            Some(controller) => vec![controller],
+            None => (0..player_inputs.len()).collect(),
+        };
+        let player_inputs: Vec<PlayerInput> = match self.controller {
+            Some(controller) => player_inputs
+                .into_iter()
+                .nth(controller)
+                .into_iter()
+                .collect(),
+            None => player_inputs,
+        };
+
+        if let Some((ticker, character_select)) = &mut self.unsaved {
+            ticker.step(&player_inputs);
+            if player_inputs.iter().any(|x| x.b.press) {
+                self.unsaved = None;
+            } else if player_inputs.iter().any(|x| x.a.press) {
+                let character_select = *character_select;
+                let save = match ticker.cursor {
+                    0 => true,
+                    1 => false,
+                    2 => {
+                        self.unsaved = None;
+                        return None;
+                    }
+                    _ => unreachable!(),
+                };
+                return Some(PauseAction::Leave {
+                    character_select,
+                    save,
+                });
+            }
+            return None;
+        }
+
+        if self.controls {
+            if player_inputs.iter().any(|x| x.a.press || x.b.press) {
+                self.controls = false;
+            }
+            return None;
+        }
+
+        if let Some(ticker) = &mut self.dummy_ticker {
+            ticker.step(&player_inputs);
+            if player_inputs.iter().any(|x| x.b.press) {
+                self.dummy_ticker = None;
+                return None;
+            } else if player_inputs.iter().any(|x| x.a.press) {
+                match ticker.cursor {
+                    0 => self.dummy.di = self.dummy.di.next(),
+                    1 => self.dummy.tech = self.dummy.tech.next(),
+                    2 => self.dummy.shield = self.dummy.shield.next(),
+                    3 => {
+                        self.dummy_ticker = None;
+                        return None;
+                    }
+                    _ => unreachable!(),
+                }
+                return Some(PauseAction::Dummy(self.dummy));
+            }
+            return None;
+        }
+
+        if let Some((ticker, options)) = &mut self.entities {
+            let new_options = entity_options();
+            if new_options.len() != options.len() {
+                let cursor = ticker.cursor.min(new_options.len());
+                *ticker = MenuTicker::new(new_options.len() + 1);
+                ticker.cursor = cursor;
+            }
+            *options = new_options;
+
+            ticker.step(&player_inputs);
+            if player_inputs.iter().any(|x| x.b.press) {
+                self.entities = None;
+            } else if player_inputs.iter().any(|x| x.a.press) {
+                // the last option is Back
+                match options.get(ticker.cursor) {
+                    Some(option) => return Some(PauseAction::Entity(option.action.clone())),
+                    None => self.entities = None,
+                }
+            }
+            return None;
+        }
+
+        self.ticker.step(&player_inputs);
+        if player_inputs.iter().any(|x| x.start.press) {
+            Some(PauseAction::Resume)
+        } else if let Some(i) = player_inputs.iter().position(|x| x.z.press) {
+            Some(PauseAction::StepForward {
+                frames: 1,
+                controller: controllers[i],
+            })
+        } else if player_inputs.iter().any(|x| x.x.press) {
+            Some(PauseAction::StepBackward)
+        } else if let Some(i) = player_inputs.iter().position(|x| x.r.value) {
+            Some(PauseAction::StepForward {
+                frames: FAST_FORWARD_FRAMES,
+                controller: controllers[i],
+            })
+        } else if player_inputs.iter().any(|x| x.a.press) {
+            match self.ticker.cursor {
+                0 => Some(PauseAction::Resume),
+                1 => {
+                    self.controls = true;
+                    None
+                }
+                2 => Some(PauseAction::ToggleHitboxes),
+                3 => Some(PauseAction::ToggleLedgeTimers),
+                4 => Some(PauseAction::CycleMinimap(self.controller)),
+                5 => {
+                    self.mirrored = !self.mirrored;
+                    // the first controller is mirrored when paused from the keyboard
+                    Some(PauseAction::MirrorInputs(if self.mirrored {
+                        Some(self.controller.unwrap_or(0))
+                    } else {
+                        None
+                    }))
+                }
+                6 => {
+                    self.dummy_ticker = Some(MenuTicker::new(DUMMY_OPTIONS.len()));
+                    None
+                }
+                7 => {
+                    let options = entity_options();
+                    self.entities = Some((MenuTicker::new(options.len() + 1), options));
+                    None
+                }
+                8 => self.leave(true, unsaved_changes),
+                9 => self.leave(false, unsaved_changes),
+                _ => unreachable!(),
+            }
+        } else {
+            None
+        }
+    }
+
+    fn leave<F>(&mut self, character_select: bool, unsaved_changes: F) -> Option<PauseAction>
+    where
+        F: Fn() -> bool,
+    {
+        if unsaved_changes() {
+            self.confirm_unsaved(character_select);
+            None
+        } else {
+            Some(PauseAction::Leave {
+                character_select,
+                save: false,
+            })
+        }
+    }
+
+    /// `minimap` is the minimap zoom of the player that paused
+    pub fn render(&self, hitboxes: bool, ledge_timers: bool, minimap: &Minimap) -> RenderPauseMenu {
+        if let Some((ticker, _)) = &self.unsaved {
+            RenderPauseMenu {
+                title: "Save package changes?",
+                options: UNSAVED_OPTIONS.iter().map(|x| x.to_string()).collect(),
+                selection: ticker.cursor,
+            }
+        } else if self.controls {
+            RenderPauseMenu {
+                title: "Controls",
+                options: CONTROLS.iter().map(|x| x.to_string()).collect(),
+                // nothing to select, so nothing is highlighted
+                selection: CONTROLS.len(),
+            }
+        } else if let Some((ticker, options)) = &self.entities {
+            let mut options: Vec<String> = options.iter().map(|x| x.label.clone()).collect();
+            options.push(String::from("Back"));
+            RenderPauseMenu {
+                title: "Entities",
+                options,
+                selection: ticker.cursor,
+            }
+        } else if let Some(ticker) = &self.dummy_ticker {
+            let options = DUMMY_OPTIONS
+                .iter()
+                .map(|x| match *x {
+                    "DI" => format!("DI: {}", self.dummy.di.name()),
+                    "Tech" => format!("Tech: {}", self.dummy.tech.name()),
+                    "Shield" => format!("Shield: {}", self.dummy.shield.name()),
+                    x => x.to_string(),
+                })
+                .collect();
+            RenderPauseMenu {
+                title: "Training dummy",
+                options,
+                selection: ticker.cursor,
+            }
+        } else {
+            let options = PAUSE_OPTIONS
+                .iter()
+                .map(|x| match *x {
+                    "Hitboxes" if hitboxes => String::from("Hitboxes: On"),
+                    "Hitboxes" => String::from("Hitboxes: Off"),
+                    "Ledge timers" if ledge_timers => String::from("Ledge timers: On"),
+                    "Ledge timers" => String::from("Ledge timers: Off"),
+                    "Minimap" => format!("Minimap: {}", minimap.name()),
+                    "Mirror inputs" if self.mirrored => String::from("Mirror inputs: On"),
+                    "Mirror inputs" => String::from("Mirror inputs: Off"),
+                    x => x.to_string(),
+                })
+                .collect();
+            RenderPauseMenu {
+                title: "Paused",
+                options,
+                selection: self.ticker.cursor,
+            }
+        }
+    }
+}
+
+pub struct RenderPauseMenu {
+    pub title: &'static str,
+    pub options: Vec<String>,
+    pub selection: usize,
+}
+
+#[test]
+fn unsaved_test() {
+    let a_press = || {
+        let mut input = PlayerInput::empty();
+        input.a.press = true;
+        input
+    };
+    let mut menu = PauseMenu::new(None, false, DummyBehavior::default());
+
+    // leaving without unsaved changes does not ask
+    menu.ticker.cursor = 9;
+    assert!(matches!(
+        menu.step(vec![a_press()], || false, Vec::new),
+        Some(PauseAction::Leave {
+            character_select: false,
+            save: false
+        })
+    ));
+
+    menu.ticker.cursor = 8;
+    assert!(menu.step(vec![a_press()], || true, Vec::new).is_none());
+    assert!(menu.confirming_unsaved());
+    assert!(matches!(
+        menu.step(vec![a_press()], || true, Vec::new),
+        Some(PauseAction::Leave {
+            character_select: true,
+            save: true
+        })
+    ));
+}
+
+#[test]
+fn entities_test() {
+    let a_press = || {
+        let mut input = PlayerInput::empty();
+        input.a.press = true;
+        input
+    };
+    let options = || {
+        vec![EntityOption {
+            label: String::from("Spawn Bomb"),
+            action: EntityAction::Spawn {
+                kind: "item",
+                entity: String::from("Bomb.cbor"),
+            },
+        }]
+    };
+    let mut menu = PauseMenu::new(None, false, DummyBehavior::default());
+
+    menu.ticker.cursor = 7;
+    assert!(menu.step(vec![a_press()], || false, options).is_none());
+    assert_eq!(
+        menu.render(false, false, &Minimap::Off).options,
+        ["Spawn Bomb", "Back"]
+    );
+    assert!(matches!(
+        menu.step(vec![a_press()], || false, options),
+        Some(PauseAction::Entity(EntityAction::Spawn {
+            kind: "item",
+            ..
+        }))
+    ));
+
+    // Back returns to the pause menu
+    menu.entities.as_mut().unwrap().0.cursor = 1;
+    assert!(menu.step(vec![a_press()], || false, options).is_none());
+    assert_eq!(menu.render(false, false, &Minimap::Off).title, "Paused");
+}
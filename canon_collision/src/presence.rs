@@ -0,0 +1,219 @@
+use crate::game::Game;
+use crate::menu::Menu;
+
+use canon_collision_lib::config::Config;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde_json::{json, Value};
+
+use std::io;
+use std::io::{Read, Write};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Discord may not be running yet, so keep trying to connect, but dont spam it.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(15);
+
+#[cfg(unix)]
+type Socket = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Socket = std::fs::File;
+
+/// What the player is currently doing, as displayed in discord
+#[derive(Clone, PartialEq)]
+pub struct Activity {
+    pub details: String,
+    pub state: String,
+}
+
+/// An activity along with the client id of the discord application it is published as
+type Update = Option<(String, Activity)>;
+
+/// Publishes the current activity to Discord Rich Presence.
+/// All communication with discord occurs on a separate thread so a missing or slow discord client cannot stall the game.
+pub struct Presence {
+    tx: Sender<Update>,
+    prev_update: Update,
+}
+
+impl Presence {
+    pub fn new() -> Presence {
+        let (tx, rx) = channel::<Update>();
+        thread::spawn(move || {
+            let mut connection: Option<DiscordConnection> = None;
+            let mut activity: Option<Activity> = None;
+            let mut client_id = String::new();
+            let mut last_connect_attempt: Option<Instant> = None;
+            let mut dirty = false;
+            loop {
+                match rx.recv_timeout(RECONNECT_INTERVAL) {
+                    Ok(update) => {
+                        activity = update.map(|(new_client_id, new_activity)| {
+                            if new_client_id != client_id {
+                                // the handshake is tied to the client id so the connection has to be remade
+                                client_id = new_client_id;
+                                connection = None;
+                                last_connect_attempt = None;
+                            }
+                            new_activity
+                        });
+                        dirty = true;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+
+                if activity.is_some()
+                    && connection.is_none()
+                    && last_connect_attempt.map_or(true, |x| x.elapsed() > RECONNECT_INTERVAL)
+                {
+                    last_connect_attempt = Some(Instant::now());
+                    match DiscordConnection::connect(&client_id) {
+                        Ok(new_connection) => {
+                            connection = Some(new_connection);
+                            dirty = true;
+                        }
+                        Err(err) => debug!("Failed to connect to discord: {}", err),
+                    }
+                }
+
+                if dirty {
+                    if let Some(inner_connection) = &mut connection {
+                        if let Err(err) = inner_connection.set_activity(activity.as_ref()) {
+                            debug!("Lost connection to discord: {}", err);
+                            connection = None;
+                        }
+                    }
+                    dirty = false;
+                }
+
+                if activity.is_none() {
+                    connection = None;
+                }
+            }
+        });
+
+        Presence {
+            tx,
+            prev_update: None,
+        }
+    }
+
+    /// Presence is skipped unless enabled and a discord application client id is configured
+    pub fn step(&mut self, config: &Config, game: Option<&Game>, menu: &Menu) {
+        let update = match &config.discord_client_id {
+            Some(client_id) if config.discord_rich_presence => {
+                let activity = match game {
+                    Some(game) => game.presence_activity(),
+                    None => menu.presence_activity(),
+                };
+                Some((client_id.clone(), activity))
+            }
+            _ => None,
+        };
+
+        if update != self.prev_update {
+            self.tx.send(update.clone()).ok();
+            self.prev_update = update;
+        }
+    }
+}
+
+struct DiscordConnection {
+    socket: Socket,
+    nonce: u64,
+}
+
+impl DiscordConnection {
+    fn connect(client_id: &str) -> io::Result<DiscordConnection> {
+        let mut connection = DiscordConnection {
+            socket: DiscordConnection::open_socket()?,
+            nonce: 0,
+        };
+        connection.send(Opcode::Handshake, json!({ "v": 1, "client_id": client_id }))?;
+        Ok(connection)
+    }
+
+    /// Discord may be listening on any of discord-ipc-0 to discord-ipc-9
+    fn open_socket() -> io::Result<Socket> {
+        let mut result = Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No discord ipc socket",
+        ));
+        for i in 0..10 {
+            result = DiscordConnection::open_socket_i(i);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    #[cfg(unix)]
+    fn open_socket_i(i: usize) -> io::Result<Socket> {
+        let dir = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+            .iter()
+            .find_map(|x| std::env::var(x).ok())
+            .unwrap_or_else(|| "/tmp".into());
+        let socket = Socket::connect(format!("{}/discord-ipc-{}", dir, i))?;
+        socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+        Ok(socket)
+    }
+
+    #[cfg(windows)]
+    fn open_socket_i(i: usize) -> io::Result<Socket> {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!(r"\\.\pipe\discord-ipc-{}", i))
+    }
+
+    fn set_activity(&mut self, activity: Option<&Activity>) -> io::Result<()> {
+        self.nonce += 1;
+        let activity = activity.map(|activity| {
+            json!({
+                "details": activity.details,
+                "state": activity.state,
+            })
+        });
+        self.send(
+            Opcode::Frame,
+            json!({
+                "cmd": "SET_ACTIVITY",
+                "args": { "pid": std::process::id(), "activity": activity },
+                "nonce": self.nonce.to_string(),
+            }),
+        )
+    }
+
+    /// Sends a message and waits for the response so that responses dont build up in the socket.
+    fn send(&mut self, opcode: Opcode, payload: Value) -> io::Result<()> {
+        let payload = payload.to_string();
+        self.socket.write_u32::<LittleEndian>(opcode as u32)?;
+        self.socket
+            .write_u32::<LittleEndian>(payload.len() as u32)?;
+        self.socket.write_all(payload.as_bytes())?;
+
+        let response_opcode = self.socket.read_u32::<LittleEndian>()?;
+        let response_len = self.socket.read_u32::<LittleEndian>()?;
+        let mut response = vec![0; response_len as usize];
+        self.socket.read_exact(&mut response)?;
+
+        let response: Value = serde_json::from_slice(&response)?;
+        if response_opcode == Opcode::Close as u32 || response["evt"] == "ERROR" {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Discord responded with an error: {}", response),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+enum Opcode {
+    Handshake = 0,
+    Frame = 1,
+    Close = 2,
+}
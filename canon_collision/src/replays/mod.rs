@@ -0,0 +1,771 @@
+use crate::camera::Camera;
+use crate::entity::{DebugEntities, DebugEntity, Entities, EntityKey, RenderDebugType};
+use crate::game::{Edit, Game, GameSetup, GameState, PlayerSetup};
+use crate::rules::{
+    Assists, FighterSize, GameSpeed, Goal, Gravity, ItemFrequency, Modifiers, Pause, Rules,
+    SeriesMode, Staling, Teams,
+};
+
+use canon_collision_lib::config::EffectColors;
+use canon_collision_lib::geometry::Rect;
+use canon_collision_lib::input::state::ControllerInput;
+use canon_collision_lib::package::Package;
+use canon_collision_lib::replays_files;
+use canon_collision_lib::stage::{
+    DebugStage, Floor, ItemSpawn, PathKeyframe, SpawnPoint, Stage, Surface,
+};
+
+use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+use slotmap::SparseSecondaryMap;
+use treeflection::ContextVec;
+
+use std::fs::{self, DirBuilder};
+use std::path::Path;
+
+mod v0;
+
+/// Marks a replay file as starting with a `ReplayHeader`.
+/// Replays saved before the header was added start directly with the `Replay`.
+const REPLAY_MAGIC: [u8; 8] = *b"CCREPLAY";
+
+/// Increment this whenever a change to `Replay` or any struct it contains changes its serialized layout.
+/// Then add an arm to `upgrade_replay` that loads the previous layout.
+pub fn replay_version() -> u64 {
+    8
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplayHeader {
+    magic: [u8; 8],
+    replay_version: u64,
+}
+
+pub fn load_replay(name: &str) -> Result<Replay, String> {
+    let replay_path = replays_files::get_replay_path(name);
+    load_replay_path(&replay_path)
+}
+
+/// Loads a replay from anywhere on disk instead of the replays directory e.g. a replay dropped onto the window
+pub fn load_replay_path(path: &Path) -> Result<Replay, String> {
+    let bytes = fs::read(path).map_err(|x| format!("{:?}", x))?;
+    load_replay_bytes(&bytes)
+}
+
+/// Loads a replay from the contents of a replay file e.g. a replay uploaded to the web build
+pub fn load_replay_bytes(bytes: &[u8]) -> Result<Replay, String> {
+    let (version, body) = split_header(bytes)?;
+    upgrade_replay(version, body)
+}
+
+pub fn save_replay(replay: &Replay) {
+    let replay_path =
+        replays_files::get_replay_path(&format!("{}.zip", replay.timestamp.to_rfc2822())); // TODO: could still collide under strange circumstances: check and handle
+    save_replay_path(&replay_path, replay).unwrap()
+}
+
+pub fn save_named_replay(replay: &Replay, name: &str) {
+    let replay_path = replays_files::get_replay_path(&format!("{}.zip", name));
+    save_replay_path(&replay_path, replay).unwrap()
+}
+
+/// Saves a replay to anywhere on disk instead of the replays directory
+pub fn save_replay_path(path: &Path, replay: &Replay) -> Result<(), String> {
+    let header = ReplayHeader {
+        magic: REPLAY_MAGIC,
+        replay_version: replay_version(),
+    };
+    let mut bytes = bincode::serialize(&header).unwrap();
+    bincode::serialize_into(&mut bytes, replay).unwrap();
+
+    if let Some(parent) = path.parent() {
+        DirBuilder::new()
+            .recursive(true)
+            .create(parent)
+            .map_err(|x| x.to_string())?;
+    }
+    fs::write(path, bytes).map_err(|x| x.to_string())
+}
+
+/// Returns the replay version and the bytes of the replay following the header.
+/// Replays without a header are version 0.
+fn split_header(bytes: &[u8]) -> Result<(u64, &[u8]), String> {
+    if !bytes.starts_with(&REPLAY_MAGIC) {
+        return Ok((0, bytes));
+    }
+    let header: ReplayHeader = bincode::deserialize(bytes).map_err(|x| format!("{:?}", x))?;
+    let header_len = bincode::serialized_size(&header).unwrap() as usize;
+    Ok((header.replay_version, &bytes[header_len..]))
+}
+
+/// Loads a replay saved by any previous replay version.
+/// Like the package_upgrader, each arm handles one past version.
+/// As bincode is not self describing, an arm for a layout that has since gained fields deserializes a copy of the old
+/// struct definitions kept in this module and converts them to the current `Replay`, filling the new fields with their defaults.
+fn upgrade_replay(version: u64, bytes: &[u8]) -> Result<Replay, String> {
+    if version > replay_version() {
+        return Err(format!(
+            "Replay is version {} which is newer than this version of Canon Collision supports ({})",
+            version,
+            replay_version()
+        ));
+    }
+
+    let replay = match version {
+        0 => deserialize::<v0::ReplayV0>(bytes)?.upgrade(),
+        1 => deserialize::<ReplayV1<RulesV1, PlayerSetupV3, DebugEntityV6, StageV7>>(bytes)?
+            .upgrade(),
+        2 => deserialize::<ReplayV1<RulesV2, PlayerSetupV3, DebugEntityV6, StageV7>>(bytes)?
+            .upgrade(),
+        3 => deserialize::<ReplayV1<RulesV4, PlayerSetupV3, DebugEntityV6, StageV7>>(bytes)?
+            .upgrade(),
+        4 => deserialize::<ReplayV1<RulesV4, PlayerSetupV5, DebugEntityV6, StageV7>>(bytes)?
+            .upgrade(),
+        5 => {
+            deserialize::<ReplayV1<Rules, PlayerSetupV5, DebugEntityV6, StageV7>>(bytes)?.upgrade()
+        }
+        6 => deserialize::<ReplayV1<Rules, PlayerSetup, DebugEntityV6, StageV7>>(bytes)?.upgrade(),
+        7 => deserialize::<ReplayV1<Rules, PlayerSetup, DebugEntity, StageV7>>(bytes)?.upgrade(),
+        _ => return deserialize(bytes),
+    };
+    Ok(replay)
+}
+
+fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    bincode::deserialize(bytes).map_err(|x| format!("{:?}", x))
+}
+
+/// Rewrites every replay in the replays directory in the latest replay version.
+/// Returns a line for each replay describing the result.
+pub fn upgrade_all_replays() -> Vec<String> {
+    let mut results = vec![];
+    for name in replays_files::get_replay_names() {
+        let path = replays_files::get_replay_path(&format!("{}.zip", name));
+        let result = fs::read(&path)
+            .map_err(|x| format!("{:?}", x))
+            .and_then(|bytes| {
+                let (version, body) = split_header(&bytes)?;
+                if version == replay_version() {
+                    return Ok(false);
+                }
+                save_replay_path(&path, &upgrade_replay(version, body)?)?;
+                Ok(true)
+            });
+        results.push(match result {
+            Ok(true) => format!("Upgraded {}", name),
+            Ok(false) => format!("{} is already the latest version", name),
+            Err(err) => format!("Failed to upgrade {}: {}", name, err),
+        });
+    }
+    results
+}
+
+/// Replay versions 1 to 7, which only differ in the layout of their rules, player setups, debug entities and stages
+#[derive(Deserialize)]
+struct ReplayV1<R, P, D, S> {
+    init_seed: u64,
+    timestamp: DateTime<Local>,
+    input_history: Vec<Vec<ControllerInput>>,
+    entity_history: Vec<Entities>,
+    stage_history: Vec<S>,
+    selected_controllers: Vec<usize>,
+    selected_players: Vec<P>,
+    selected_ais: Vec<usize>,
+    selected_stage: String,
+    rules: R,
+    max_history_frames: Option<usize>,
+    deleted_history_frames: usize,
+    hot_reload_current_frame: usize,
+    hot_reload_camera: Camera,
+    hot_reload_debug_entities: SparseSecondaryMap<EntityKey, D>,
+    hot_reload_debug_stage: DebugStage,
+    hot_reload_entities: Entities,
+    hot_reload_stage: S,
+    hot_reload_as_running: bool,
+    hot_reload_edit: Edit,
+}
+
+impl<R: Into<Rules>, P: Into<PlayerSetup>, D: Into<DebugEntity>, S: Into<Stage>>
+    ReplayV1<R, P, D, S>
+{
+    fn upgrade(self) -> Replay {
+        Replay {
+            init_seed: self.init_seed,
+            timestamp: self.timestamp,
+            input_history: self.input_history,
+            entity_history: self.entity_history,
+            stage_history: self.stage_history.into_iter().map(Into::into).collect(),
+            selected_controllers: self.selected_controllers,
+            selected_players: self.selected_players.into_iter().map(Into::into).collect(),
+            selected_ais: self.selected_ais,
+            selected_stage: self.selected_stage,
+            rules: self.rules.into(),
+            max_history_frames: self.max_history_frames,
+            deleted_history_frames: self.deleted_history_frames,
+            hot_reload_current_frame: self.hot_reload_current_frame,
+            hot_reload_camera: self.hot_reload_camera,
+            hot_reload_debug_entities: self
+                .hot_reload_debug_entities
+                .into_iter()
+                .map(|(key, debug)| (key, debug.into()))
+                .collect(),
+            hot_reload_debug_stage: self.hot_reload_debug_stage,
+            hot_reload_entities: self.hot_reload_entities,
+            hot_reload_stage: self.hot_reload_stage.into(),
+            hot_reload_as_running: self.hot_reload_as_running,
+            hot_reload_edit: self.hot_reload_edit,
+        }
+    }
+}
+
+/// Replay version 1, before `Rules::late_join` was added
+#[derive(Deserialize)]
+struct RulesV1 {
+    goal: Goal,
+    stock_count: Option<u64>,
+    time_limit_seconds: Option<u64>,
+    game_clock: bool,
+    respawn_frames: u64,
+    best_of: u64,
+    pause: Pause,
+    teams: Teams,
+    grab_clang: bool,
+    meter: bool,
+    series: SeriesMode,
+    item_frequency: ItemFrequency,
+    assists: Assists,
+    tutorial: Option<usize>,
+    rewind: bool,
+    staling: Staling,
+    modifiers: ModifiersV2,
+}
+
+impl From<RulesV1> for Rules {
+    fn from(rules: RulesV1) -> Rules {
+        Rules {
+            goal: rules.goal,
+            stock_count: rules.stock_count,
+            time_limit_seconds: rules.time_limit_seconds,
+            game_clock: rules.game_clock,
+            respawn_frames: rules.respawn_frames,
+            best_of: rules.best_of,
+            pause: rules.pause,
+            teams: rules.teams,
+            grab_clang: rules.grab_clang,
+            meter: rules.meter,
+            series: rules.series,
+            item_frequency: rules.item_frequency,
+            assists: rules.assists,
+            tutorial: rules.tutorial,
+            rewind: rules.rewind,
+            staling: rules.staling,
+            modifiers: rules.modifiers.into(),
+            late_join: false,
+            legal_stages: vec![],
+        }
+    }
+}
+
+/// Replay version 2, before `Modifiers::size` was added
+#[derive(Deserialize)]
+struct RulesV2 {
+    goal: Goal,
+    stock_count: Option<u64>,
+    time_limit_seconds: Option<u64>,
+    game_clock: bool,
+    respawn_frames: u64,
+    best_of: u64,
+    pause: Pause,
+    teams: Teams,
+    grab_clang: bool,
+    meter: bool,
+    series: SeriesMode,
+    item_frequency: ItemFrequency,
+    assists: Assists,
+    tutorial: Option<usize>,
+    rewind: bool,
+    staling: Staling,
+    modifiers: ModifiersV2,
+    late_join: bool,
+}
+
+impl From<RulesV2> for Rules {
+    fn from(rules: RulesV2) -> Rules {
+        Rules {
+            goal: rules.goal,
+            stock_count: rules.stock_count,
+            time_limit_seconds: rules.time_limit_seconds,
+            game_clock: rules.game_clock,
+            respawn_frames: rules.respawn_frames,
+            best_of: rules.best_of,
+            pause: rules.pause,
+            teams: rules.teams,
+            grab_clang: rules.grab_clang,
+            meter: rules.meter,
+            series: rules.series,
+            item_frequency: rules.item_frequency,
+            assists: rules.assists,
+            tutorial: rules.tutorial,
+            rewind: rules.rewind,
+            staling: rules.staling,
+            modifiers: rules.modifiers.into(),
+            late_join: rules.late_join,
+            legal_stages: vec![],
+        }
+    }
+}
+
+/// Replay versions 3 and 4, before `Rules::legal_stages` was added
+#[derive(Deserialize)]
+struct RulesV4 {
+    goal: Goal,
+    stock_count: Option<u64>,
+    time_limit_seconds: Option<u64>,
+    game_clock: bool,
+    respawn_frames: u64,
+    best_of: u64,
+    pause: Pause,
+    teams: Teams,
+    grab_clang: bool,
+    meter: bool,
+    series: SeriesMode,
+    item_frequency: ItemFrequency,
+    assists: Assists,
+    tutorial: Option<usize>,
+    rewind: bool,
+    staling: Staling,
+    modifiers: Modifiers,
+    late_join: bool,
+}
+
+impl From<RulesV4> for Rules {
+    fn from(rules: RulesV4) -> Rules {
+        Rules {
+            goal: rules.goal,
+            stock_count: rules.stock_count,
+            time_limit_seconds: rules.time_limit_seconds,
+            game_clock: rules.game_clock,
+            respawn_frames: rules.respawn_frames,
+            best_of: rules.best_of,
+            pause: rules.pause,
+            teams: rules.teams,
+            grab_clang: rules.grab_clang,
+            meter: rules.meter,
+            series: rules.series,
+            item_frequency: rules.item_frequency,
+            assists: rules.assists,
+            tutorial: rules.tutorial,
+            rewind: rules.rewind,
+            staling: rules.staling,
+            modifiers: rules.modifiers,
+            late_join: rules.late_join,
+            legal_stages: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ModifiersV2 {
+    gravity: Gravity,
+    speed: GameSpeed,
+    invisible: bool,
+    one_hit_ko: bool,
+    fixed_camera: bool,
+}
+
+impl From<ModifiersV2> for Modifiers {
+    fn from(modifiers: ModifiersV2) -> Modifiers {
+        Modifiers {
+            gravity: modifiers.gravity,
+            speed: modifiers.speed,
+            invisible: modifiers.invisible,
+            one_hit_ko: modifiers.one_hit_ko,
+            fixed_camera: modifiers.fixed_camera,
+            size: FighterSize::Normal,
+        }
+    }
+}
+
+/// Replay version 3, before `PlayerSetup::costume` was added
+#[derive(Deserialize)]
+struct PlayerSetupV3 {
+    fighter: String,
+    team: usize,
+    stocks: Option<u64>,
+    effect_colors: EffectColors,
+}
+
+impl From<PlayerSetupV3> for PlayerSetup {
+    fn from(setup: PlayerSetupV3) -> PlayerSetup {
+        PlayerSetup {
+            fighter: setup.fighter,
+            team: setup.team,
+            stocks: setup.stocks,
+            effect_colors: setup.effect_colors,
+            costume: 0,
+            damage: 0.0,
+        }
+    }
+}
+
+/// Replay versions 4 and 5, before `PlayerSetup::damage` was added
+#[derive(Deserialize)]
+struct PlayerSetupV5 {
+    fighter: String,
+    team: usize,
+    stocks: Option<u64>,
+    effect_colors: EffectColors,
+    costume: usize,
+}
+
+impl From<PlayerSetupV5> for PlayerSetup {
+    fn from(setup: PlayerSetupV5) -> PlayerSetup {
+        PlayerSetup {
+            fighter: setup.fighter,
+            team: setup.team,
+            stocks: setup.stocks,
+            effect_colors: setup.effect_colors,
+            costume: setup.costume,
+            damage: 0.0,
+        }
+    }
+}
+
+/// Replay versions 1 to 6, before `DebugEntity::colbox_overlay` was stored
+#[derive(Deserialize)]
+struct DebugEntityV6 {
+    render: RenderDebugType,
+    physics: bool,
+    input: bool,
+    input_diff: bool,
+    action: bool,
+    frame: bool,
+    stick_vector: bool,
+    c_stick_vector: bool,
+    di_vector: bool,
+    hitbox_vectors: bool,
+    ecb: bool,
+    cam_area: bool,
+    item_grab_area: bool,
+    ledge_timers: bool,
+}
+
+impl From<DebugEntityV6> for DebugEntity {
+    fn from(debug: DebugEntityV6) -> DebugEntity {
+        DebugEntity {
+            render: debug.render,
+            physics: debug.physics,
+            input: debug.input,
+            input_diff: debug.input_diff,
+            action: debug.action,
+            frame: debug.frame,
+            stick_vector: debug.stick_vector,
+            c_stick_vector: debug.c_stick_vector,
+            di_vector: debug.di_vector,
+            hitbox_vectors: debug.hitbox_vectors,
+            ecb: debug.ecb,
+            cam_area: debug.cam_area,
+            item_grab_area: debug.item_grab_area,
+            ledge_timers: debug.ledge_timers,
+            colbox_overlay: false,
+        }
+    }
+}
+
+/// Replay versions 1 to 7, before surfaces had hazards and when their position along their path was stored
+#[derive(Deserialize)]
+struct StageV7 {
+    engine_version: u64,
+    name: String,
+    surfaces: ContextVec<SurfaceV7>,
+    blast: Rect,
+    camera: Rect,
+    camera_min_width: f32,
+    camera_max_width: f32,
+    spawn_points: ContextVec<SpawnPoint>,
+    respawn_points: ContextVec<SpawnPoint>,
+    item_spawns: ContextVec<ItemSpawn>,
+    item_spawn_frames: u64,
+}
+
+impl From<StageV7> for Stage {
+    fn from(stage: StageV7) -> Stage {
+        Stage {
+            engine_version: stage.engine_version,
+            name: stage.name,
+            surfaces: ContextVec::from_vec(
+                stage
+                    .surfaces
+                    .into_vector()
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            ),
+            blast: stage.blast,
+            camera: stage.camera,
+            camera_min_width: stage.camera_min_width,
+            camera_max_width: stage.camera_max_width,
+            spawn_points: stage.spawn_points,
+            respawn_points: stage.respawn_points,
+            item_spawns: stage.item_spawns,
+            item_spawn_frames: stage.item_spawn_frames,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)] // the path position is only read by bincode
+struct SurfaceV7 {
+    x1: f32,
+    y1: f32,
+    grab1: bool,
+    x2: f32,
+    y2: f32,
+    grab2: bool,
+    wall: bool,
+    ceiling: bool,
+    floor: Option<Floor>,
+    path: ContextVec<PathKeyframe>,
+    path_x: f32,
+    path_y: f32,
+    vel_x: f32,
+    vel_y: f32,
+}
+
+impl From<SurfaceV7> for Surface {
+    /// The points already include the offset along the path, which is all stepping the path needs
+    fn from(surface: SurfaceV7) -> Surface {
+        Surface {
+            x1: surface.x1,
+            y1: surface.y1,
+            grab1: surface.grab1,
+            x2: surface.x2,
+            y2: surface.y2,
+            grab2: surface.grab2,
+            wall: surface.wall,
+            ceiling: surface.ceiling,
+            floor: surface.floor,
+            path: surface.path,
+            hazard: None,
+            vel_x: surface.vel_x,
+            vel_y: surface.vel_y,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub init_seed: u64,
+    pub timestamp: DateTime<Local>,
+    pub input_history: Vec<Vec<ControllerInput>>,
+    pub entity_history: Vec<Entities>,
+    pub stage_history: Vec<Stage>,
+    pub selected_controllers: Vec<usize>,
+    pub selected_players: Vec<PlayerSetup>,
+    pub selected_ais: Vec<usize>,
+    pub selected_stage: String,
+    pub rules: Rules,
+    pub max_history_frames: Option<usize>,
+    pub deleted_history_frames: usize,
+    pub hot_reload_current_frame: usize,
+    pub hot_reload_camera: Camera,
+    pub hot_reload_debug_entities: DebugEntities,
+    pub hot_reload_debug_stage: DebugStage,
+    pub hot_reload_entities: Entities,
+    pub hot_reload_stage: Stage,
+    pub hot_reload_as_running: bool,
+    pub hot_reload_edit: Edit,
+}
+
+impl Replay {
+    pub fn new(game: &Game, input_history: &[Vec<ControllerInput>]) -> Replay {
+        let hot_reload_as_running = matches!(game.state, GameState::Local);
+
+        Replay {
+            init_seed: game.init_seed,
+            timestamp: Local::now(),
+            input_history: input_history.to_vec(),
+            entity_history: game.entity_history(),
+            stage_history: game.stage_history.clone(),
+            selected_controllers: game.selected_controllers.clone(),
+            selected_players: game.selected_players.clone(),
+            selected_ais: game.selected_ais.clone(),
+            selected_stage: game.selected_stage.clone(),
+            rules: game.rules.clone(),
+            max_history_frames: game.max_history_frames,
+            deleted_history_frames: game.deleted_history_frames,
+            hot_reload_current_frame: game.current_frame,
+            hot_reload_camera: game.camera.clone(),
+            hot_reload_debug_entities: game.debug_entities(),
+            hot_reload_debug_stage: game.debug_stage.clone(),
+            hot_reload_entities: game.entities(),
+            hot_reload_stage: game.stage.clone(),
+            hot_reload_edit: game.edit(),
+            hot_reload_as_running,
+        }
+    }
+
+    /// Returns an error if the replay uses a stage or fighter that is not in the package
+    pub fn check_package(&self, package: &Package) -> Result<(), String> {
+        if !package.stages.contains_key(&self.selected_stage) {
+            return Err(format!(
+                "Package does not contain the replay's stage '{}'",
+                self.selected_stage
+            ));
+        }
+        for player in &self.selected_players {
+            if !package.entities.contains_key(&player.fighter) {
+                return Err(format!(
+                    "Package does not contain the replay's fighter '{}'",
+                    player.fighter
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes all but the last `frames` frames of history, in the same way as max_history_frames.
+    /// The input history is kept as it is small and indexed from the first frame of the game.
+    pub fn trim_history(&mut self, frames: usize) {
+        let extra_frames = self.entity_history.len().saturating_sub(frames);
+        self.deleted_history_frames += extra_frames;
+        self.entity_history.drain(0..extra_frames);
+        self.stage_history.drain(0..extra_frames);
+    }
+
+    /// Continues the game from the frame the replay was saved on, as saved by `:save_resume`.
+    /// The input history leading up to that frame is kept so netplay, rewinding and replays of the resumed game still work.
+    pub fn into_resume_setup(self) -> GameSetup {
+        let mut setup = self.into_game_setup(true);
+        setup.state = GameState::Local;
+        setup
+    }
+
+    // TODO: maybe hotreloading should be its own thing seperate to replays
+    // Its increasing looking like hot reloading wants to serialize EVERYTHING
+    // whereas replays only wants to serialize the bits relevant to gameplay
+    pub fn into_game_setup(self, hot_reload: bool) -> GameSetup {
+        // Replays upgraded from version 0 have no history or hot reload state, they are resimulated from their inputs
+        let from_input = self.entity_history.is_empty();
+        let hot_reload = hot_reload && !from_input;
+
+        let state = if from_input {
+            GameState::ReplayForwardsFromInput
+        } else if !hot_reload {
+            GameState::ReplayForwardsFromHistory
+        } else if self.hot_reload_as_running {
+            GameState::Local
+        } else {
+            GameState::Paused
+        };
+
+        let current_frame = if hot_reload {
+            self.hot_reload_current_frame
+        } else {
+            self.deleted_history_frames
+        };
+
+        let debug_entities = if hot_reload {
+            Some(self.hot_reload_debug_entities)
+        } else {
+            None
+        };
+
+        let debug_stage = if hot_reload {
+            Some(self.hot_reload_debug_stage)
+        } else {
+            None
+        };
+
+        let camera = if hot_reload {
+            self.hot_reload_camera
+        } else {
+            Camera::new()
+        };
+
+        let hot_reload_entities = if hot_reload {
+            Some(self.hot_reload_entities)
+        } else {
+            None
+        };
+
+        let hot_reload_stage = if hot_reload {
+            Some(self.hot_reload_stage)
+        } else {
+            None
+        };
+
+        GameSetup {
+            init_seed: self.init_seed,
+            input_history: self.input_history,
+            entity_history: self.entity_history,
+            stage_history: self.stage_history,
+            controllers: self.selected_controllers,
+            players: self.selected_players,
+            ais: self.selected_ais,
+            stage: self.selected_stage,
+            rules: self.rules,
+            max_history_frames: self.max_history_frames,
+            deleted_history_frames: self.deleted_history_frames,
+            edit: self.hot_reload_edit,
+            current_frame,
+            debug: false,
+            camera,
+            debug_entities,
+            debug_stage,
+            hot_reload_entities,
+            hot_reload_stage,
+            state,
+        }
+    }
+}
+
+#[test]
+fn split_header_test() {
+    let header = ReplayHeader {
+        magic: REPLAY_MAGIC,
+        replay_version: 8,
+    };
+    let mut bytes = bincode::serialize(&header).unwrap();
+    bytes.extend_from_slice(&[1, 2, 3]);
+    assert_eq!(split_header(&bytes).unwrap(), (8, &[1, 2, 3][..]));
+
+    // replays saved before the header was added
+    assert_eq!(split_header(&[1, 2, 3]).unwrap(), (0, &[1, 2, 3][..]));
+
+    assert!(upgrade_replay(replay_version() + 1, &[]).is_err());
+}
+
+#[test]
+fn upgrade_replay_v0_test() {
+    // saved by the version of Canon Collision from before replays had a header
+    let bytes = include_bytes!("replay_v0.bin");
+    let (version, body) = split_header(bytes).unwrap();
+    assert_eq!(version, 0);
+
+    let replay = upgrade_replay(version, body).unwrap();
+    assert_eq!(replay.init_seed, 1234);
+    assert_eq!(replay.timestamp.timestamp(), 1_600_000_000);
+    assert_eq!(replay.input_history.len(), 3);
+    assert!(replay.input_history[0][0].a);
+    assert_eq!(replay.selected_players[0].fighter, "Toriel.cbor");
+    assert_eq!(replay.selected_players[0].team, 1);
+    assert_eq!(replay.rules.stock_count, Some(3));
+    assert!(replay.entity_history.is_empty());
+
+    let setup = replay.clone().into_game_setup(true);
+    assert!(matches!(setup.state, GameState::ReplayForwardsFromInput));
+    assert!(setup.hot_reload_entities.is_none());
+
+    let mut bytes = bincode::serialize(&ReplayHeader {
+        magic: REPLAY_MAGIC,
+        replay_version: replay_version(),
+    })
+    .unwrap();
+    bincode::serialize_into(&mut bytes, &replay).unwrap();
+    let (version, body) = split_header(&bytes).unwrap();
+    let reloaded = upgrade_replay(version, body).unwrap();
+    assert_eq!(reloaded.init_seed, 1234);
+    assert_eq!(reloaded.input_history.len(), 3);
+    assert_eq!(reloaded.selected_players[0].fighter, "Toriel.cbor");
+}
@@ -0,0 +1,376 @@
+//! Copies of the structs that made up a replay before replays had a header.
+//! bincode is not self describing so every field has to be deserialized to reach the fields after it,
+//! even though only the inputs and game setup are kept.
+#![allow(dead_code)] // most fields are only read by bincode
+
+use super::Replay;
+use crate::camera::{Camera, CameraControlState, TransformMode};
+use crate::entity::components::action_state::Hitlag;
+use crate::entity::components::body::LedgeLogic;
+use crate::entity::fighters::player::LockTimer;
+use crate::entity::projectile::Projectile;
+use crate::entity::toriel_fireball::TorielFireball;
+use crate::entity::{EntityKey, RenderDebugType};
+use crate::game::{Edit, PlayerSetup};
+use crate::rules::{Assists, Goal, ItemFrequency, Pause, Rules, Staling, Teams};
+
+use canon_collision_lib::entity_def::ECB;
+use canon_collision_lib::geometry::Rect;
+use canon_collision_lib::input::state::ControllerInput;
+use canon_collision_lib::stage::{DebugStage, Floor, SpawnPoint, Stage};
+
+use chrono::{DateTime, Local};
+use slotmap::{DenseSlotMap, SparseSecondaryMap};
+
+type EntitiesV0 = DenseSlotMap<EntityKey, EntityV0>;
+
+#[derive(Deserialize)]
+pub(super) struct ReplayV0 {
+    init_seed: u64,
+    timestamp: DateTime<Local>,
+    input_history: Vec<Vec<ControllerInput>>,
+    entity_history: Vec<EntitiesV0>,
+    stage_history: Vec<StageV0>,
+    selected_controllers: Vec<usize>,
+    selected_players: Vec<PlayerSetupV0>,
+    selected_ais: Vec<usize>,
+    selected_stage: String,
+    rules: RulesV0,
+    max_history_frames: Option<usize>,
+    deleted_history_frames: usize,
+    hot_reload_current_frame: usize,
+    hot_reload_camera: CameraV0,
+    hot_reload_debug_entities: SparseSecondaryMap<EntityKey, DebugEntityV0>,
+    hot_reload_debug_stage: DebugStage,
+    hot_reload_entities: EntitiesV0,
+    hot_reload_stage: StageV0,
+    hot_reload_as_running: bool,
+    hot_reload_edit: Edit,
+}
+
+impl ReplayV0 {
+    /// The entities of version 0 cannot be converted to the current entities, so only the inputs are kept.
+    /// The input history always starts from the first frame, so the upgraded replay is resimulated from the start.
+    pub(super) fn upgrade(self) -> Replay {
+        Replay {
+            init_seed: self.init_seed,
+            timestamp: self.timestamp,
+            input_history: self.input_history,
+            entity_history: vec![],
+            stage_history: vec![],
+            selected_controllers: self.selected_controllers,
+            selected_players: self.selected_players.into_iter().map(Into::into).collect(),
+            selected_ais: self.selected_ais,
+            selected_stage: self.selected_stage,
+            rules: self.rules.into(),
+            max_history_frames: self.max_history_frames,
+            deleted_history_frames: 0,
+            hot_reload_current_frame: 0,
+            hot_reload_camera: Camera::new(),
+            hot_reload_debug_entities: Default::default(),
+            hot_reload_debug_stage: self.hot_reload_debug_stage,
+            hot_reload_entities: Default::default(),
+            hot_reload_stage: Stage::default(),
+            hot_reload_as_running: false,
+            hot_reload_edit: Edit::Stage,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RulesV0 {
+    goal: Goal,
+    stock_count: Option<u64>,
+    time_limit_seconds: Option<u64>,
+    best_of: u64,
+    pause: PauseV0,
+    teams: TeamsV0,
+    grab_clang: bool,
+}
+
+impl From<RulesV0> for Rules {
+    /// Rules added since version 0 are disabled so the game resimulates the same way
+    fn from(rules: RulesV0) -> Rules {
+        Rules {
+            goal: rules.goal,
+            stock_count: rules.stock_count,
+            time_limit_seconds: rules.time_limit_seconds,
+            best_of: rules.best_of,
+            pause: rules.pause.into(),
+            teams: rules.teams.into(),
+            grab_clang: rules.grab_clang,
+            item_frequency: ItemFrequency::Off,
+            assists: Assists::default(),
+            staling: Staling {
+                queue_len: 0,
+                decay: 0.0,
+                fresh_bonus: 1.0,
+            },
+            ..Rules::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum PauseV0 {
+    On,
+    Off,
+    Hold,
+}
+
+impl From<PauseV0> for Pause {
+    fn from(pause: PauseV0) -> Pause {
+        match pause {
+            PauseV0::On => Pause::On,
+            PauseV0::Off => Pause::Off,
+            PauseV0::Hold => Pause::Hold,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+enum TeamsV0 {
+    On { friendly_fire: bool },
+    Off,
+}
+
+impl From<TeamsV0> for Teams {
+    fn from(teams: TeamsV0) -> Teams {
+        match teams {
+            TeamsV0::On { friendly_fire } => Teams::On {
+                team_attack: friendly_fire,
+                team_grab: true,
+            },
+            TeamsV0::Off => Teams::Off,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PlayerSetupV0 {
+    fighter: String,
+    team: usize,
+}
+
+impl From<PlayerSetupV0> for PlayerSetup {
+    fn from(setup: PlayerSetupV0) -> PlayerSetup {
+        PlayerSetup {
+            fighter: setup.fighter,
+            team: setup.team,
+            ..PlayerSetup::default()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CameraV0 {
+    aspect_ratio: f32,
+    window_width: f32,
+    window_height: f32,
+    rect: Rect,
+    control_state: CameraControlState,
+    transform_mode: TransformMode,
+    freelook_location: (f32, f32, f32),
+    freelook_phi: f32,
+    freelook_theta: f32,
+}
+
+#[derive(Deserialize)]
+struct DebugEntityV0 {
+    render: RenderDebugType,
+    physics: bool,
+    input: bool,
+    input_diff: bool,
+    action: bool,
+    frame: bool,
+    stick_vector: bool,
+    c_stick_vector: bool,
+    di_vector: bool,
+    hitbox_vectors: bool,
+    ecb: bool,
+    cam_area: bool,
+    item_grab_area: bool,
+}
+
+#[derive(Deserialize)]
+struct StageV0 {
+    engine_version: u64,
+    name: String,
+    surfaces: Vec<SurfaceV0>,
+    blast: Rect,
+    camera: Rect,
+    spawn_points: Vec<SpawnPoint>,
+    respawn_points: Vec<SpawnPoint>,
+}
+
+#[derive(Deserialize)]
+struct SurfaceV0 {
+    x1: f32,
+    y1: f32,
+    grab1: bool,
+    x2: f32,
+    y2: f32,
+    grab2: bool,
+    wall: bool,
+    ceiling: bool,
+    floor: Option<Floor>,
+}
+
+#[derive(Deserialize)]
+struct EntityV0 {
+    ty: EntityTypeV0,
+    state: ActionStateV0,
+}
+
+#[derive(Deserialize)]
+enum EntityTypeV0 {
+    Fighter(FighterV0),
+    Projectile(Projectile),
+    Item(ItemV0),
+    TorielFireball(TorielFireball),
+    TorielOven(TorielOvenV0),
+}
+
+#[derive(Deserialize)]
+struct ActionStateV0 {
+    entity_def_key: String,
+    action: String,
+    frame: i64,
+    frame_no_restart: i64,
+    hitlist: Vec<EntityKey>,
+    hitlag: Hitlag,
+}
+
+#[derive(Deserialize)]
+enum FighterV0 {
+    Toriel(TorielV0),
+}
+
+#[derive(Deserialize)]
+struct TorielV0 {
+    player: PlayerV0,
+}
+
+#[derive(Deserialize)]
+struct PlayerV0 {
+    id: usize,
+    team: usize,
+    body: BodyV0,
+    stocks: Option<u64>,
+    ledge_idle_timer: u64,
+    fastfalled: bool,
+    air_jumps_left: u64,
+    jumpsquat_button: bool,
+    shield_hp: f32,
+    shield_analog: f32,
+    shield_offset_x: f32,
+    shield_offset_y: f32,
+    stun_timer: u64,
+    shield_stun_timer: u64,
+    parry_timer: u64,
+    tech_timer: LockTimer,
+    lcancel_timer: u64,
+    land_frame_skip: u8,
+    hitstun: f32,
+    hit_by: Option<usize>,
+    particles: Vec<ParticleV0>,
+    aerial_dodge_frame: Option<u64>,
+    result: RawPlayerResultV0,
+    stick: Option<(f32, f32)>,
+    c_stick: Option<(f32, f32)>,
+}
+
+#[derive(Deserialize)]
+struct ItemV0 {
+    owner_id: Option<usize>,
+    body: BodyV0,
+}
+
+#[derive(Deserialize)]
+struct TorielOvenV0 {
+    owner_id: Option<usize>,
+    body: BodyV0,
+    keep_alive: bool,
+}
+
+#[derive(Deserialize)]
+struct BodyV0 {
+    ecb: ECB,
+    damage: f32,
+    x_vel: f32,
+    y_vel: f32,
+    kb_x_vel: f32,
+    kb_y_vel: f32,
+    kb_x_dec: f32,
+    kb_y_dec: f32,
+    location: LocationV0,
+    face_right: bool,
+    frames_since_ledge: u64,
+    frames_since_hit: u64,
+    hit_angle_pre_di: Option<f32>,
+    hit_angle_post_di: Option<f32>,
+}
+
+#[derive(Deserialize)]
+enum LocationV0 {
+    Surface {
+        platform_i: usize,
+        x: f32,
+    },
+    GrabbedLedge {
+        platform_i: usize,
+        d_x: f32,
+        d_y: f32,
+        logic: LedgeLogic,
+    },
+    GrabbedByPlayer(EntityKey),
+    ItemHeldByPlayer(EntityKey),
+    Airbourne {
+        x: f32,
+        y: f32,
+    },
+}
+
+#[derive(Deserialize)]
+struct ParticleV0 {
+    color: [f32; 3],
+    counter: u32,
+    counter_max: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+    angle: f32,
+    p_type: ParticleTypeV0,
+}
+
+#[derive(Deserialize)]
+enum ParticleTypeV0 {
+    AirJump,
+    Hit {
+        knockback: f32,
+        damage: f32,
+    },
+    Spark {
+        x_vel: f32,
+        y_vel: f32,
+        z_vel: f32,
+        size: f32,
+        angle_vel: f32,
+    },
+}
+
+#[derive(Deserialize)]
+struct RawPlayerResultV0 {
+    team: usize,
+    deaths: Vec<DeathRecordV0>,
+    lcancel_attempts: u64,
+    lcancel_success: u64,
+    final_damage: Option<f32>,
+    ended_as_fighter: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeathRecordV0 {
+    player: Option<usize>,
+    frame: usize,
+}
@@ -30,6 +30,8 @@ pub struct PlayerResult {
     pub kills: Vec<DeathRecord>,
     pub deaths: Vec<DeathRecord>,
     pub lcancel_percent: f32,
+    /// The damage percent the player ended the game with
+    pub final_damage: f32,
 }
 
 /// An individual players results: unprocessed
@@ -46,5 +48,46 @@ pub struct RawPlayerResult {
 #[derive(Debug, Clone, Default, Serialize, Deserialize, Node)]
 pub struct DeathRecord {
     pub player: Option<usize>, // None indicates self-destruct
+    /// The action of the entity that landed the last hit
+    pub action: Option<String>,
+    /// The dying players damage percent
+    pub damage: f32,
     pub frame: usize,
 }
+
+impl DeathRecord {
+    /// Describes the death for the kill feed, players are numbered from 1
+    pub fn kill_feed_line(&self, player_id: usize) -> String {
+        match (self.player, &self.action) {
+            (Some(killer), Some(action)) if killer != player_id => format!(
+                "P{} KO'd P{} with {} at {:.0}%",
+                killer + 1,
+                player_id + 1,
+                action,
+                self.damage
+            ),
+            (Some(killer), None) if killer != player_id => format!(
+                "P{} KO'd P{} at {:.0}%",
+                killer + 1,
+                player_id + 1,
+                self.damage
+            ),
+            _ => format!("P{} self destructed at {:.0}%", player_id + 1, self.damage),
+        }
+    }
+}
+
+#[test]
+fn kill_feed_line_test() {
+    let death = DeathRecord {
+        player: Some(0),
+        action: Some(String::from("Fsmash")),
+        damage: 111.6,
+        frame: 0,
+    };
+    assert_eq!(death.kill_feed_line(1), "P1 KO'd P2 with Fsmash at 112%");
+    assert_eq!(death.kill_feed_line(0), "P1 self destructed at 112%");
+
+    let death = DeathRecord::default();
+    assert_eq!(death.kill_feed_line(3), "P4 self destructed at 0%");
+}
@@ -1,4 +1,6 @@
-use treeflection::{Node, NodeRunner, NodeToken};
+use canon_collision_lib::stage::Stage;
+
+use treeflection::{KeyedContextVec, Node, NodeRunner, NodeToken};
 
 // TODO: remove from package, we can specify a default impl here, will never need to modify it at runtime anyway
 #[derive(Clone, Serialize, Deserialize, Node)]
@@ -6,10 +8,34 @@ pub struct Rules {
     pub goal: Goal,
     pub stock_count: Option<u64>,
     pub time_limit_seconds: Option<u64>,
+    /// Stock matches display the time played counting up, instead of the time limit counting down
+    pub game_clock: bool,
+    /// Frames a player can wait on the respawn platform after losing a stock before they are dropped from it
+    pub respawn_frames: u64,
     pub best_of: u64,
     pub pause: Pause,
     pub teams: Teams,
     pub grab_clang: bool,
+    /// Players charge a super meter by dealing and taking damage
+    pub meter: bool,
+    /// How games are chained together from a single character select
+    pub series: SeriesMode,
+    /// How often the items of the stage's item table spawn
+    pub item_frequency: ItemFrequency,
+    /// Techniques performed automatically for casual games, replays record them so assisted games can be told apart
+    pub assists: Assists,
+    /// Walks the first player through the basic techniques, resuming from the steps already completed
+    pub tutorial: Option<usize>,
+    /// Holding the rewind hotkey rewinds the game, netplay and tournaments never allow it
+    pub rewind: bool,
+    /// How repeatedly landing the same hitbox weakens it
+    pub staling: Staling,
+    /// Special rules for casual games
+    pub modifiers: Modifiers,
+    /// A controller without a player that presses start joins the game on the respawn platform, only casual local games allow it
+    pub late_join: bool,
+    /// Keys of the stages that can be selected, every stage is legal when empty
+    pub legal_stages: Vec<String>,
 }
 
 impl Default for Rules {
@@ -18,10 +44,22 @@ impl Default for Rules {
             goal: Goal::default(),
             stock_count: Some(4),
             time_limit_seconds: Some(480),
+            game_clock: false,
+            respawn_frames: 1000,
             best_of: 1,
             pause: Pause::default(),
             teams: Teams::default(),
             grab_clang: false,
+            meter: false,
+            series: SeriesMode::default(),
+            item_frequency: ItemFrequency::default(),
+            assists: Assists::default(),
+            tutorial: None,
+            rewind: false,
+            staling: Staling::default(),
+            modifiers: Modifiers::default(),
+            late_join: false,
+            legal_stages: vec![],
         }
     }
 }
@@ -30,6 +68,21 @@ impl Rules {
     pub fn time_limit_frames(&self) -> Option<u64> {
         self.time_limit_seconds.map(|x| x * 60)
     }
+
+    /// The keys of the legal stages in the order of the package.
+    /// Every stage is returned when none of the legal stages are in the package.
+    pub fn legal_stages(&self, stages: &KeyedContextVec<Stage>) -> Vec<String> {
+        let legal: Vec<String> = stages
+            .key_iter()
+            .filter(|x| self.legal_stages.contains(x))
+            .cloned()
+            .collect();
+        if legal.is_empty() {
+            stages.keys()
+        } else {
+            legal
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Node)]
@@ -38,25 +91,313 @@ pub enum Goal {
     LastManStanding,
 }
 
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum SeriesMode {
+    /// Every game is standalone
+    Single,
+    /// Two teams send in one player at a time for a 1v1.
+    /// Each team has a pool of `stock_count` stocks per member, shared by all of its players.
+    CrewBattle,
+    /// Single elimination 1v1 bracket
+    Tournament,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum ItemFrequency {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ItemFrequency {
+    /// Average frames between item spawns on a stage that spawns an item every `item_spawn_frames` at medium frequency
+    pub fn spawn_frames(&self, item_spawn_frames: u64) -> Option<u64> {
+        match self {
+            ItemFrequency::Off => None,
+            _ if item_spawn_frames == 0 => None,
+            ItemFrequency::Low => Some(item_spawn_frames * 2),
+            ItemFrequency::Medium => Some(item_spawn_frames),
+            ItemFrequency::High => Some((item_spawn_frames / 2).max(1)),
+        }
+    }
+
+    pub fn next(&self) -> ItemFrequency {
+        match self {
+            ItemFrequency::Off => ItemFrequency::Low,
+            ItemFrequency::Low => ItemFrequency::Medium,
+            ItemFrequency::Medium => ItemFrequency::High,
+            ItemFrequency::High => ItemFrequency::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ItemFrequency::Off => "Off",
+            ItemFrequency::Low => "Low",
+            ItemFrequency::Medium => "Medium",
+            ItemFrequency::High => "High",
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Node)]
+pub struct Assists {
+    /// Every aerial is L-cancelled on landing
+    pub auto_lcancel: bool,
+    /// Every tumbling landing is teched, in the direction the stick is held
+    pub auto_tech: bool,
+    /// An airdodge pressed up to this many frames before leaving jumpsquat is performed on the first airborne frame, a perfect wavedash.
+    /// 0 disables the assist, the input history limits it to 7 frames.
+    pub wavedash_buffer: u64,
+}
+
+impl Assists {
+    pub fn any(&self) -> bool {
+        self.auto_lcancel || self.auto_tech || self.wavedash_buffer > 0
+    }
+}
+
+/// Special rules for casual games, every modifier can be combined with the others
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Node)]
+pub struct Modifiers {
+    pub gravity: Gravity,
+    pub speed: GameSpeed,
+    /// Fighters are not drawn, only their hitboxes and hurtboxes are
+    pub invisible: bool,
+    /// Every hit deals at least ONE_HIT_KO_DAMAGE, launching far enough to KO
+    pub one_hit_ko: bool,
+    /// The camera frames the whole stage instead of following the fighters
+    pub fixed_camera: bool,
+    pub size: FighterSize,
+}
+
+/// Damage a fighter is raised to when hit with the one hit KO modifier
+pub const ONE_HIT_KO_DAMAGE: f32 = 300.0;
+
+impl Modifiers {
+    pub fn any(&self) -> bool {
+        *self != Modifiers::default()
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum Gravity {
+    Normal,
+    Light,
+    Heavy,
+}
+
+impl Gravity {
+    /// Multiplies the gravity of every entity
+    pub fn mult(&self) -> f32 {
+        match self {
+            Gravity::Normal => 1.0,
+            Gravity::Light => 0.5,
+            Gravity::Heavy => 1.6,
+        }
+    }
+
+    pub fn next(&self) -> Gravity {
+        match self {
+            Gravity::Normal => Gravity::Light,
+            Gravity::Light => Gravity::Heavy,
+            Gravity::Heavy => Gravity::Normal,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Gravity::Normal => "Normal",
+            Gravity::Light => "Light",
+            Gravity::Heavy => "Heavy",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum GameSpeed {
+    Normal,
+    Slow,
+    Fast,
+}
+
+impl GameSpeed {
+    /// Frames the game advances per rendered frame
+    pub fn time_scale(&self) -> f32 {
+        match self {
+            GameSpeed::Normal => 1.0,
+            GameSpeed::Slow => 0.5,
+            GameSpeed::Fast => 1.5,
+        }
+    }
+
+    pub fn next(&self) -> GameSpeed {
+        match self {
+            GameSpeed::Normal => GameSpeed::Slow,
+            GameSpeed::Slow => GameSpeed::Fast,
+            GameSpeed::Fast => GameSpeed::Normal,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameSpeed::Normal => "Normal",
+            GameSpeed::Slow => "Slow",
+            GameSpeed::Fast => "Fast",
+        }
+    }
+}
+
+/// Giant and mini modes
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum FighterSize {
+    Normal,
+    Giant,
+    Mini,
+}
+
+impl FighterSize {
+    /// Multiplies the size of each fighters model, ecb and colboxes along with the offset of the colboxes from the fighter
+    pub fn scale(&self) -> f32 {
+        match self {
+            FighterSize::Normal => 1.0,
+            FighterSize::Giant => 1.5,
+            FighterSize::Mini => 0.6,
+        }
+    }
+
+    pub fn next(&self) -> FighterSize {
+        match self {
+            FighterSize::Normal => FighterSize::Giant,
+            FighterSize::Giant => FighterSize::Mini,
+            FighterSize::Mini => FighterSize::Normal,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FighterSize::Normal => "Normal",
+            FighterSize::Giant => "Giant",
+            FighterSize::Mini => "Mini",
+        }
+    }
+}
+
+/// Each fighter remembers the hitboxes of its most recent hits.
+/// Hitboxes in the queue deal less damage, and therefore less knockback, the more recently they landed.
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct Staling {
+    /// How many hits are remembered, 0 disables staling
+    pub queue_len: usize,
+    /// Damage multiplier lost while the hitbox is the most recent hit.
+    /// Older entries lose proportionally less, e.g. the oldest of a 9 long queue loses 1/9th of this.
+    pub decay: f32,
+    /// Damage multiplier for hitboxes that are not in the queue at all
+    pub fresh_bonus: f32,
+}
+
+impl Default for Staling {
+    fn default() -> Self {
+        Staling {
+            queue_len: 9,
+            decay: 0.09,
+            fresh_bonus: 1.05,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub enum Pause {
     On,
     Off,
     Hold,
+    /// Each controller can pause this many times per game, pausing is locked once they are used up
+    Tokens(u64),
+}
+
+impl Pause {
+    /// Returns true if a controller that has already paused `pauses_used` times can pause again
+    pub fn allowed(&self, pauses_used: u64) -> bool {
+        match self {
+            Pause::On | Pause::Hold => true,
+            Pause::Off => false,
+            Pause::Tokens(tokens) => pauses_used < *tokens,
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub enum Teams {
-    On { friendly_fire: bool },
+    On {
+        /// Teammates can hit each other
+        #[serde(alias = "friendly_fire")]
+        team_attack: bool,
+        /// Teammates can grab each other
+        team_grab: bool,
+    },
     Off,
 }
 
+impl Teams {
+    /// Returns true if an entity owned by `team_atk` can hit an entity owned by `team_defend`.
+    /// Entities not owned by any team can always hit and be hit.
+    pub fn can_hit(&self, team_atk: Option<usize>, team_defend: Option<usize>) -> bool {
+        match self {
+            Teams::On { team_attack, .. } => *team_attack || !same_team(team_atk, team_defend),
+            Teams::Off => true,
+        }
+    }
+
+    /// Returns true if an entity owned by `team_atk` can grab an entity owned by `team_defend`.
+    pub fn can_grab(&self, team_atk: Option<usize>, team_defend: Option<usize>) -> bool {
+        match self {
+            Teams::On { team_grab, .. } => *team_grab || !same_team(team_atk, team_defend),
+            Teams::Off => true,
+        }
+    }
+}
+
+fn same_team(a: Option<usize>, b: Option<usize>) -> bool {
+    a.is_some() && a == b
+}
+
 impl Default for Goal {
     fn default() -> Self {
         Goal::LastManStanding
     }
 }
 
+impl Default for SeriesMode {
+    fn default() -> Self {
+        SeriesMode::Single
+    }
+}
+
+impl Default for ItemFrequency {
+    fn default() -> Self {
+        ItemFrequency::Medium
+    }
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity::Normal
+    }
+}
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        GameSpeed::Normal
+    }
+}
+
+impl Default for FighterSize {
+    fn default() -> Self {
+        FighterSize::Normal
+    }
+}
+
 impl Default for Pause {
     fn default() -> Self {
         Pause::On
@@ -68,3 +409,54 @@ impl Default for Teams {
         Teams::Off
     }
 }
+
+#[test]
+fn pause_test() {
+    assert!(Pause::On.allowed(10));
+    assert!(!Pause::Off.allowed(0));
+    assert!(Pause::Tokens(1).allowed(0));
+    assert!(!Pause::Tokens(1).allowed(1));
+    assert!(!Pause::Tokens(0).allowed(0));
+}
+
+#[test]
+fn teams_test() {
+    let teams = Teams::On {
+        team_attack: false,
+        team_grab: true,
+    };
+    assert!(!teams.can_hit(Some(1), Some(1)));
+    assert!(teams.can_hit(Some(1), Some(2)));
+    assert!(teams.can_hit(None, None));
+    assert!(teams.can_grab(Some(1), Some(1)));
+    assert!(Teams::Off.can_hit(Some(1), Some(1)));
+}
+
+#[test]
+fn modifiers_test() {
+    assert!(!Modifiers::default().any());
+    let modifiers = Modifiers {
+        gravity: Gravity::Normal.next(),
+        ..Default::default()
+    };
+    assert!(modifiers.any());
+    assert_eq!(modifiers.gravity.mult(), 0.5);
+    assert_eq!(GameSpeed::Fast.next().time_scale(), 1.0);
+    assert_eq!(FighterSize::Mini.next().scale(), 1.0);
+}
+
+#[test]
+fn legal_stages_test() {
+    let mut stages = KeyedContextVec::new();
+    for key in ["a", "b", "c"] {
+        stages.push(key.to_string(), Stage::default());
+    }
+    let mut rules = Rules::default();
+    assert_eq!(rules.legal_stages(&stages), vec!["a", "b", "c"]);
+
+    rules.legal_stages = vec!["c".into(), "a".into(), "missing".into()];
+    assert_eq!(rules.legal_stages(&stages), vec!["a", "c"]);
+
+    rules.legal_stages = vec!["missing".into()];
+    assert_eq!(rules.legal_stages(&stages).len(), 3);
+}
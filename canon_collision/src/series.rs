@@ -0,0 +1,263 @@
+use crate::game::PlayerSetup;
+use crate::results::PlayerResult;
+
+use std::cmp::Reverse;
+use std::mem;
+
+/// A player taking part in a series, chosen at the character select.
+#[derive(Clone)]
+pub struct Entrant {
+    pub setup: PlayerSetup,
+    pub name: String,
+    pub controller: usize,
+    /// The difficulty of a CPU entrant, None for humans
+    pub cpu: Option<usize>,
+    pub wins: u64,
+    pub losses: u64,
+}
+
+/// A 1v1 between two entrants, indexes into the entrants of the series
+pub struct SeriesMatch {
+    pub entrants: [usize; 2],
+    pub stocks: [Option<u64>; 2],
+}
+
+#[derive(Clone)]
+pub struct CrewTeam {
+    pub team: usize,
+    /// Entrants in the order they are sent in
+    pub members: Vec<usize>,
+    /// Index into `members` of the player currently representing the team
+    pub current: usize,
+    pub stocks: u64,
+}
+
+#[derive(Clone)]
+pub enum SeriesState {
+    Crew {
+        teams: [CrewTeam; 2],
+        stocks_per_player: u64,
+    },
+    /// Winners of `round` are moved into `next_round`, an entrant without an opponent gets a bye.
+    Tournament {
+        round: Vec<usize>,
+        next_round: Vec<usize>,
+    },
+}
+
+/// Tracks the progress of a crew battle or tournament between games.
+#[derive(Clone)]
+pub struct Series {
+    pub entrants: Vec<Entrant>,
+    /// Every game of the series is played on this stage
+    pub stage: String,
+    pub state: SeriesState,
+}
+
+impl Series {
+    /// Returns None unless the entrants are split across exactly two teams
+    pub fn crew(entrants: Vec<Entrant>, stage: String, stocks_per_player: u64) -> Option<Series> {
+        let mut teams: Vec<CrewTeam> = vec![];
+        for (i, entrant) in entrants.iter().enumerate() {
+            match teams.iter_mut().find(|x| x.team == entrant.setup.team) {
+                Some(team) => team.members.push(i),
+                None => teams.push(CrewTeam {
+                    team: entrant.setup.team,
+                    members: vec![i],
+                    current: 0,
+                    stocks: 0,
+                }),
+            }
+        }
+        for team in &mut teams {
+            team.stocks = stocks_per_player * team.members.len() as u64;
+        }
+
+        let teams = match <[CrewTeam; 2]>::try_from(teams) {
+            Ok(teams) => teams,
+            Err(_) => return None,
+        };
+        Some(Series {
+            entrants,
+            stage,
+            state: SeriesState::Crew {
+                teams,
+                stocks_per_player,
+            },
+        })
+    }
+
+    /// Returns None when there are not enough entrants for a match
+    pub fn tournament(entrants: Vec<Entrant>, stage: String) -> Option<Series> {
+        if entrants.len() < 2 {
+            return None;
+        }
+        Some(Series {
+            state: SeriesState::Tournament {
+                round: (0..entrants.len()).collect(),
+                next_round: vec![],
+            },
+            entrants,
+            stage,
+        })
+    }
+
+    /// Returns None when the series is over
+    pub fn next_match(&self) -> Option<SeriesMatch> {
+        match &self.state {
+            SeriesState::Crew {
+                teams,
+                stocks_per_player,
+            } => {
+                if teams
+                    .iter()
+                    .any(|x| x.stocks == 0 || x.current >= x.members.len())
+                {
+                    return None;
+                }
+                Some(SeriesMatch {
+                    entrants: [
+                        teams[0].members[teams[0].current],
+                        teams[1].members[teams[1].current],
+                    ],
+                    stocks: [
+                        Some(teams[0].stocks.min(*stocks_per_player)),
+                        Some(teams[1].stocks.min(*stocks_per_player)),
+                    ],
+                })
+            }
+            SeriesState::Tournament { round, .. } => {
+                if round.len() >= 2 {
+                    Some(SeriesMatch {
+                        entrants: [round[0], round[1]],
+                        stocks: [None, None],
+                    })
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Updates the series with the results of the match returned by `next_match`.
+    /// Players are matched to entrants by controller.
+    pub fn record(&mut self, results: &[PlayerResult]) {
+        let series_match = match self.next_match() {
+            Some(series_match) => series_match,
+            None => return,
+        };
+        let result = |entrant: usize| {
+            let controller = self.entrants[entrant].controller;
+            results.iter().find(|x| x.controller == controller)
+        };
+        let (a, b) = match (
+            result(series_match.entrants[0]),
+            result(series_match.entrants[1]),
+        ) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                error!("Results do not match the current series match");
+                return;
+            }
+        };
+        let (winner, loser) = if a.place <= b.place { (0, 1) } else { (1, 0) };
+        let deaths = [a.deaths.len() as u64, b.deaths.len() as u64];
+
+        if let SeriesState::Crew { teams, .. } = &mut self.state {
+            for (team, deaths) in teams.iter_mut().zip(deaths.iter()) {
+                team.stocks = team.stocks.saturating_sub(*deaths);
+            }
+            teams[loser].current += 1;
+        }
+
+        let winner = series_match.entrants[winner];
+        let loser = series_match.entrants[loser];
+        self.entrants[winner].wins += 1;
+        self.entrants[loser].losses += 1;
+
+        if let SeriesState::Tournament { round, next_round } = &mut self.state {
+            round.drain(..2);
+            next_round.push(winner);
+
+            // give a bye to an entrant left without an opponent and move onto the next round
+            if round.len() == 1 && !next_round.is_empty() {
+                next_round.push(round.remove(0));
+            }
+            if round.is_empty() && next_round.len() > 1 {
+                *round = mem::take(next_round);
+            }
+        }
+    }
+
+    /// Lines describing the progress of the series, displayed on the results screen
+    pub fn standings(&self) -> Vec<String> {
+        let mut lines = vec![];
+        match &self.state {
+            SeriesState::Crew { teams, .. } => {
+                lines.push("Crew Battle".to_string());
+                for team in teams {
+                    let remaining = team.members.len().saturating_sub(team.current);
+                    lines.push(format!(
+                        "Team {}: {} stocks, {} players remaining",
+                        team.team + 1,
+                        team.stocks,
+                        remaining
+                    ));
+                }
+                if self.next_match().is_none() {
+                    if let Some(team) = teams
+                        .iter()
+                        .find(|x| x.stocks > 0 && x.current < x.members.len())
+                    {
+                        lines.push(format!("Team {} wins!", team.team + 1));
+                    }
+                }
+            }
+            SeriesState::Tournament { round, next_round } => {
+                lines.push("Tournament".to_string());
+                let mut entrants: Vec<&Entrant> = self.entrants.iter().collect();
+                entrants.sort_by_key(|x| (Reverse(x.wins), x.losses));
+                for entrant in entrants {
+                    lines.push(format!(
+                        "{}: {} wins, {} losses",
+                        entrant.name, entrant.wins, entrant.losses
+                    ));
+                }
+                if self.next_match().is_none() {
+                    if let Some(winner) = round.first().or_else(|| next_round.first()) {
+                        lines.push(format!("{} wins!", self.entrants[*winner].name));
+                    }
+                }
+            }
+        }
+        lines
+    }
+}
+
+#[test]
+fn tournament_bye_test() {
+    let entrants: Vec<Entrant> = (0..3)
+        .map(|i| Entrant {
+            setup: PlayerSetup::default(),
+            name: i.to_string(),
+            controller: i,
+            cpu: None,
+            wins: 0,
+            losses: 0,
+        })
+        .collect();
+    let mut series = Series::tournament(entrants, String::new()).unwrap();
+
+    let result = |controller, place| PlayerResult {
+        controller,
+        place,
+        ..PlayerResult::default()
+    };
+    series.record(&[result(0, 1), result(1, 0)]);
+    // entrant 2 gets a bye so plays entrant 1 in the final
+    assert_eq!(series.next_match().unwrap().entrants, [1, 2]);
+
+    series.record(&[result(1, 0), result(2, 1)]);
+    assert!(series.next_match().is_none());
+    assert_eq!(series.entrants[1].wins, 2);
+}
@@ -0,0 +1,133 @@
+use crate::game::{Game, GameState};
+use crate::game::ResumeMenu;
+
+use canon_collision_lib::files;
+
+use chrono::{DateTime, Local};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::thread;
+
+/// Summary of everything played since the app was launched.
+/// Written to session_report.json when the app exits, this includes exiting due to a panic.
+#[derive(Serialize)]
+pub struct SessionReport {
+    build_version: String,
+    started: DateTime<Local>,
+    ended: Option<DateTime<Local>>,
+    /// Games that continued until the results screen
+    matches_completed: usize,
+    /// Games that were quit before the results screen
+    matches_quit: usize,
+    netplay_disconnects: usize,
+    replays_watched: usize,
+    /// How many games each fighter was played in
+    fighters_used: BTreeMap<String, usize>,
+    /// Frames stepped across all games and replays
+    total_frames: u64,
+    /// The app exited due to a panic
+    panicked: bool,
+    #[serde(skip)]
+    show_at_exit: bool,
+    #[serde(skip)]
+    current_game_is_replay: Option<bool>,
+}
+
+impl SessionReport {
+    pub fn new() -> SessionReport {
+        SessionReport {
+            build_version: files::build_version(),
+            started: Local::now(),
+            ended: None,
+            matches_completed: 0,
+            matches_quit: 0,
+            netplay_disconnects: 0,
+            replays_watched: 0,
+            fighters_used: BTreeMap::new(),
+            total_frames: 0,
+            panicked: false,
+            show_at_exit: false,
+            current_game_is_replay: None,
+        }
+    }
+
+    /// Call this once every frame
+    pub fn step(&mut self, show_at_exit: bool, game: Option<&Game>) {
+        self.show_at_exit = show_at_exit;
+
+        if let (Some(game), None) = (game, self.current_game_is_replay) {
+            let is_replay = matches!(
+                game.state,
+                GameState::ReplayForwardsFromHistory
+                    | GameState::ReplayForwardsFromInput
+                    | GameState::ReplayBackwards
+            );
+            self.current_game_is_replay = Some(is_replay);
+        }
+    }
+
+    /// Call this when the game returns to the menu
+    pub fn game_ended(&mut self, game: &Game, resume_menu: &ResumeMenu) {
+        self.total_frames += game.current_frame as u64;
+
+        if self.current_game_is_replay.take().unwrap_or(false) {
+            self.replays_watched += 1;
+            return;
+        }
+
+        match resume_menu {
+            ResumeMenu::Results(_) => self.matches_completed += 1,
+            ResumeMenu::Unchanged | ResumeMenu::CharacterSelect => self.matches_quit += 1,
+            ResumeMenu::NetplayDisconnect { .. } => self.netplay_disconnects += 1,
+        }
+
+        for fighter in game.fighter_names() {
+            *self.fighters_used.entry(fighter).or_insert(0) += 1;
+        }
+    }
+
+    /// Avoids files::save_struct_json as it panics on failure and this is called while unwinding
+    fn save(&self) {
+        let mut path = files::get_path();
+        path.push("session_report.json");
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|x| x.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|x| x.to_string()));
+        if let Err(err) = result {
+            error!("Failed to save session report to {:?}: {}", path, err);
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "Session report ({})\n  Matches completed: {}\n  Matches quit: {}\n  Netplay disconnects: {}\n  Replays watched: {}\n  Total frames: {}\n",
+            self.build_version,
+            self.matches_completed,
+            self.matches_quit,
+            self.netplay_disconnects,
+            self.replays_watched,
+            self.total_frames,
+        );
+        for (fighter, count) in &self.fighters_used {
+            summary.push_str(&format!("  {}: {} games\n", fighter, count));
+        }
+        if self.panicked {
+            summary.push_str("  Exited due to a panic\n");
+        }
+        summary
+    }
+}
+
+/// Runs when the app thread returns or unwinds so the report is written however the app exits
+impl Drop for SessionReport {
+    fn drop(&mut self) {
+        self.ended = Some(Local::now());
+        self.panicked = thread::panicking();
+        self.save();
+
+        if self.show_at_exit {
+            println!("{}", self.summary());
+        }
+    }
+}
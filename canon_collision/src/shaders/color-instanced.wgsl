@@ -0,0 +1,40 @@
+// output_color is prepended by wgpu/shaders.rs, as wgsl has no includes
+
+struct VertexOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+[[block]]
+struct Locals {
+    transform: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> locals: Locals;
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec4<f32>,
+    [[location(1)]] color: vec4<f32>,
+    [[location(2)]] instance_transform_0: vec4<f32>,
+    [[location(3)]] instance_transform_1: vec4<f32>,
+    [[location(4)]] instance_transform_2: vec4<f32>,
+    [[location(5)]] instance_transform_3: vec4<f32>,
+    [[location(6)]] instance_color: vec4<f32>,
+) -> VertexOutput {
+    let instance_transform = mat4x4<f32>(
+        instance_transform_0,
+        instance_transform_1,
+        instance_transform_2,
+        instance_transform_3,
+    );
+    var out: VertexOutput;
+    out.color = color * instance_color;
+    out.position = locals.transform * instance_transform * position;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return output_color(in.color);
+}
@@ -1,3 +1,5 @@
+// output_color is prepended by wgpu/shaders.rs, as wgsl has no includes
+
 struct VertexOutput {
     [[location(0)]] color: vec4<f32>;
     [[builtin(position)]] position: vec4<f32>;
@@ -23,5 +25,5 @@ fn vs_main(
 
 [[stage(fragment)]]
 fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return in.color;
+    return output_color(in.color);
 }
@@ -1,3 +1,5 @@
+// output_color is prepended by wgpu/shaders.rs, as wgsl has no includes
+
 struct VertexOutput {
     [[location(0)]] edge: f32;
     [[location(1)]] render_id: u32;
@@ -26,9 +28,7 @@ fn vs_main(
     return out;
 }
 
-[[stage(fragment)]]
-fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let e: f32 = fwidth(in.edge);
+fn hitbox_color(in: VertexOutput, e: f32) -> vec4<f32> {
     if (in.render_id == 0u) {
         return locals.color;
     }
@@ -69,3 +69,8 @@ fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
         return vec4<f32>(1.0, 0.0, 1.0, 1.0);
     }
 }
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return output_color(hitbox_color(in, fwidth(in.edge)));
+}
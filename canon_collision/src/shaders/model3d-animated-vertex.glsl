@@ -1,16 +1,23 @@
 #version 440
 
+// Reduced by the renderer when the adapter cannot bind a uniform buffer this large
+#ifndef MAX_JOINTS
+#define MAX_JOINTS 500
+#endif
+
 layout(location = 0) in vec4  a_position;
 layout(location = 1) in vec2  a_uv;
 layout(location = 2) in uvec4 a_joints;
 layout(location = 3) in vec4  a_weights;
 
 layout(location = 0) out vec2 v_uv;
+layout(location = 1) out float v_flash;
 
 layout(set = 0, binding = 0) uniform Data {
     mat4 u_transform;
-    mat4 u_joint_transforms[500];
+    mat4 u_joint_transforms[MAX_JOINTS];
     float u_frame_count;
+    float u_flash;
 };
 
 void main() {
@@ -23,4 +30,5 @@ void main() {
     gl_Position = u_transform * skin_transform * a_position;
 
     v_uv = a_uv;
+    v_flash = u_flash;
 }
@@ -11,6 +11,19 @@ layout(set = 0, binding = 0) uniform Data {
 layout(set = 0, binding = 1) uniform texture2D u_texture;
 layout(set = 0, binding = 2) uniform sampler u_sampler;
 
+#ifdef SRGB_OUTPUT
+// The surface encodes colors to sRGB, so the sRGB colors we work with are decoded to linear first
+vec4 output_color(vec4 color) {
+    vec3 low = color.rgb / 12.92;
+    vec3 high = pow((color.rgb + 0.055) / 1.055, vec3(2.4));
+    return vec4(mix(high, low, lessThanEqual(color.rgb, vec3(0.04045))), color.a);
+}
+#else
+vec4 output_color(vec4 color) {
+    return color;
+}
+#endif
+
 void main() {
     float flow = u_current_frame / 1700;
     float swirl_x = sin(v_uv.y + u_current_frame / 800) * 0.3;
@@ -33,4 +46,6 @@ void main() {
     if (nice_depth < 0.15) {
         f_color = mix(vec4(0.871, 0.4, 0.2, 1.0), f_color, nice_depth * 7);
     }
+
+    f_color = output_color(f_color);
 }
@@ -1,11 +1,28 @@
 #version 440
 
 layout(location = 0) in vec2 v_uv;
+layout(location = 1) in float v_flash;
 layout(location = 0) out vec4 f_color;
 
 layout(set = 0, binding = 1) uniform texture2D u_texture;
 layout(set = 0, binding = 2) uniform sampler u_sampler;
 
+#ifdef SRGB_OUTPUT
+// The surface encodes colors to sRGB, so the sRGB colors we work with are decoded to linear first
+vec4 output_color(vec4 color) {
+    vec3 low = color.rgb / 12.92;
+    vec3 high = pow((color.rgb + 0.055) / 1.055, vec3(2.4));
+    return vec4(mix(high, low, lessThanEqual(color.rgb, vec3(0.04045))), color.a);
+}
+#else
+vec4 output_color(vec4 color) {
+    return color;
+}
+#endif
+
 void main() {
-    f_color = texture(sampler2D(u_texture, u_sampler), v_uv);
+    vec4 color = texture(sampler2D(u_texture, u_sampler), v_uv);
+    // tints the model white e.g. when a fighter is struck
+    color.rgb = mix(color.rgb, vec3(1.0), v_flash * 0.8);
+    f_color = output_color(color);
 }
@@ -3,6 +3,7 @@
 layout(location = 0) in vec4 a_position;
 layout(location = 1) in vec2 a_uv;
 layout(location = 0) out vec2 v_uv;
+layout(location = 1) out float v_flash;
 
 layout(set = 0, binding = 0) uniform Data {
     mat4 u_transform;
@@ -12,4 +13,6 @@ void main() {
     gl_Position = u_transform * a_position;
 
     v_uv = a_uv;
+    // shares its uniform layout with the lava fragment shader, which has no room for a flash
+    v_flash = 0.0;
 }
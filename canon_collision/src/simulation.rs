@@ -0,0 +1,154 @@
+use crate::audio::Audio;
+use crate::camera::Camera;
+use crate::game::{Edit, Game, GameSetup, GameState};
+use crate::replays::Replay;
+
+pub use crate::game::PlayerSetup;
+pub use crate::rules::Rules;
+pub use crate::telemetry::{EntityTelemetry, TelemetryFrame};
+pub use canon_collision_lib::entity_def::{CollisionBox, CollisionBoxRole};
+pub use canon_collision_lib::input::state::ControllerInput;
+pub use canon_collision_lib::stage::Stage;
+
+use canon_collision_lib::package::Package;
+
+/// A simulation is never rewound, so only the latest frames of history are kept to bound its memory use
+const HISTORY_FRAMES: usize = 60;
+
+/// Runs a game without a window, renderer, audio device or controllers so that other projects can embed it,
+/// e.g. AI research stepping many games at once or tools visualizing the state of each frame.
+///
+/// Each call to `step` simulates a frame with the inputs last passed to `set_inputs`, where controller i controls player i.
+/// Stepping the same package, players, stage, rules and seed with the same inputs always produces the same frames.
+pub struct Simulation {
+    game: Game,
+    audio: Audio,
+    /// The inputs of every frame so far, frame 1 has index 0
+    input_history: Vec<Vec<ControllerInput>>,
+    inputs: Vec<ControllerInput>,
+    /// Inputs recorded by a replay, used instead of `inputs` until they run out
+    replay_inputs: Vec<Vec<ControllerInput>>,
+}
+
+impl Simulation {
+    /// `stage` and the fighter of each player are keys into the package
+    pub fn new(
+        package: Package,
+        players: Vec<PlayerSetup>,
+        stage: String,
+        rules: Rules,
+        seed: u64,
+    ) -> Simulation {
+        let controllers = (0..players.len()).collect();
+        Simulation::from_setup(package, players, controllers, vec![], stage, rules, seed)
+    }
+
+    /// Resimulates a replay from its recorded inputs, e.g. to inspect the state of each frame.
+    /// Once the recorded inputs run out, the inputs passed to `set_inputs` are used.
+    pub fn from_replay(package: Package, replay: &Replay) -> Result<Simulation, String> {
+        replay.check_package(&package)?;
+        let mut simulation = Simulation::from_setup(
+            package,
+            replay.selected_players.clone(),
+            replay.selected_controllers.clone(),
+            replay.selected_ais.clone(),
+            replay.selected_stage.clone(),
+            replay.rules.clone(),
+            replay.init_seed,
+        );
+        simulation.replay_inputs = replay.input_history.clone();
+        Ok(simulation)
+    }
+
+    fn from_setup(
+        package: Package,
+        players: Vec<PlayerSetup>,
+        controllers: Vec<usize>,
+        ais: Vec<usize>,
+        stage: String,
+        rules: Rules,
+        seed: u64,
+    ) -> Simulation {
+        let setup = GameSetup {
+            init_seed: seed,
+            input_history: vec![],
+            entity_history: Default::default(),
+            stage_history: vec![],
+            controllers,
+            players,
+            ais,
+            stage,
+            state: GameState::Local,
+            rules,
+            debug: false,
+            max_history_frames: Some(HISTORY_FRAMES),
+            deleted_history_frames: 0,
+            current_frame: 0,
+            camera: Camera::new(),
+            debug_stage: None,
+            debug_entities: None,
+            hot_reload_entities: None,
+            hot_reload_stage: None,
+            edit: Edit::Stage,
+        };
+        let mut audio = Audio::silent();
+        let game = Game::new(package, setup, &mut audio);
+
+        Simulation {
+            game,
+            audio,
+            input_history: vec![],
+            inputs: vec![],
+            replay_inputs: vec![],
+        }
+    }
+
+    /// Sets the inputs used by every following step, one per controller
+    pub fn set_inputs(&mut self, inputs: Vec<ControllerInput>) {
+        self.inputs = inputs;
+    }
+
+    /// Simulates the next frame, does nothing once the game is finished
+    pub fn step(&mut self) {
+        if self.finished() {
+            return;
+        }
+        let inputs = match self.replay_inputs.get(self.input_history.len()) {
+            Some(inputs) => inputs.clone(),
+            None => self.inputs.clone(),
+        };
+        self.input_history.push(inputs);
+        self.game
+            .step_simulation(&self.input_history, &mut self.audio);
+    }
+
+    /// The number of frames stepped so far
+    pub fn frame(&self) -> usize {
+        self.game.current_frame
+    }
+
+    /// True once the rules have decided the winner
+    pub fn finished(&self) -> bool {
+        matches!(self.game.state, GameState::Quit(_))
+    }
+
+    /// A snapshot of every entity on the current frame
+    pub fn state(&self) -> TelemetryFrame {
+        self.game.telemetry_frame()
+    }
+
+    /// Hashes of the stage and every entity, two simulations have diverged when their hashes differ on the same frame
+    pub fn state_hashes(&self) -> Vec<(String, u64)> {
+        self.game.state_hashes()
+    }
+
+    /// The colboxes of every entity on the current frame, positioned in the world
+    pub fn colboxes(&self) -> Vec<CollisionBox> {
+        self.game.colboxes()
+    }
+
+    /// The stage as of the current frame, its surfaces can move
+    pub fn stage(&self) -> &Stage {
+        &self.game.stage
+    }
+}
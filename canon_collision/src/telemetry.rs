@@ -0,0 +1,153 @@
+use crate::entity::{Entities, EntityType};
+
+use canon_collision_lib::entity_def::EntityDef;
+use canon_collision_lib::stage::Surface;
+
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use treeflection::KeyedContextVec;
+
+/// Streams the state of every simulated frame to connected tools e.g. TAS tools, AI training and stat trackers.
+/// Each frame is sent as a little endian u32 length followed by a CBOR encoded `TelemetryFrame`.
+/// Frames resimulated by rewinding or netplay rollback are sent again, replacing the earlier frame with the same number.
+pub struct Telemetry {
+    listener: TcpListener,
+    clients: Vec<TcpStream>,
+}
+
+impl Telemetry {
+    /// Listens for connections on `address`, returns None when it cant be listened on.
+    pub fn new(address: &str) -> Option<Telemetry> {
+        let address: SocketAddr = match address.parse() {
+            Ok(address) => address,
+            Err(_) => {
+                println!(
+                    "telemetry_address {:?} is not a valid address and port",
+                    address
+                );
+                return None;
+            }
+        };
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                println!("Failed to listen for telemetry on {}: {}", address, e);
+                return None;
+            }
+        };
+        listener.set_nonblocking(true).unwrap();
+
+        Some(Telemetry {
+            listener,
+            clients: vec![],
+        })
+    }
+
+    /// Accepts new connections
+    pub fn step(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            // clients that cant keep up are disconnected instead of stalling the game
+            if stream.set_nonblocking(true).is_ok() {
+                stream.set_nodelay(true).ok();
+                self.clients.push(stream);
+            }
+        }
+    }
+
+    /// Frames only need to be recorded while a client is connected
+    pub fn has_clients(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    /// Sends the frames to every client, disconnecting clients that fail to receive them
+    pub fn publish(&mut self, frames: &[TelemetryFrame]) {
+        for frame in frames {
+            let message = encode(frame);
+            self.clients
+                .retain(|mut client| client.write_all(&message).is_ok());
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TelemetryFrame {
+    pub frame: usize,
+    pub entities: Vec<EntityTelemetry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EntityTelemetry {
+    /// The player the entity belongs to, numbered from 0
+    pub player: Option<usize>,
+    /// fighter, item, projectile etc.
+    pub ty: String,
+    pub entity_def: String,
+    pub action: String,
+    pub action_frame: i64,
+    pub x: f32,
+    pub y: f32,
+    pub face_right: bool,
+    /// None when the entity has no body e.g. projectiles
+    pub x_vel: Option<f32>,
+    pub y_vel: Option<f32>,
+    pub damage: Option<f32>,
+    /// Only set for fighters when the rules limit stocks
+    pub stocks: Option<u64>,
+}
+
+impl TelemetryFrame {
+    pub fn new(
+        frame: usize,
+        entities: &Entities,
+        entity_defs: &KeyedContextVec<EntityDef>,
+        surfaces: &[Surface],
+    ) -> TelemetryFrame {
+        let entities = entities
+            .values()
+            .map(|entity| {
+                let (x, y) = entity.public_bps_xy(entities, entity_defs, surfaces);
+                let body = entity.body();
+                let stocks = match &entity.ty {
+                    EntityType::Fighter(fighter) => fighter.get_player().stocks,
+                    _ => None,
+                };
+                EntityTelemetry {
+                    player: entity.player_id(),
+                    ty: entity.ty.name().to_string(),
+                    entity_def: entity.state.entity_def_key.clone(),
+                    action: entity.state.action.clone(),
+                    action_frame: entity.state.frame,
+                    x,
+                    y,
+                    face_right: entity.face_right(),
+                    x_vel: body.map(|x| x.x_vel),
+                    y_vel: body.map(|x| x.y_vel),
+                    damage: body.map(|x| x.damage),
+                    stocks,
+                }
+            })
+            .collect();
+        TelemetryFrame { frame, entities }
+    }
+}
+
+fn encode(frame: &TelemetryFrame) -> Vec<u8> {
+    let cbor = serde_cbor::to_vec(frame).unwrap();
+    let mut message = (cbor.len() as u32).to_le_bytes().to_vec();
+    message.extend(cbor);
+    message
+}
+
+#[test]
+fn encode_test() {
+    let frame = TelemetryFrame {
+        frame: 42,
+        entities: vec![],
+    };
+    let message = encode(&frame);
+    let len = u32::from_le_bytes(message[..4].try_into().unwrap()) as usize;
+    assert_eq!(len, message.len() - 4);
+    let decoded: TelemetryFrame = serde_cbor::from_slice(&message[4..]).unwrap();
+    assert_eq!(decoded.frame, 42);
+}
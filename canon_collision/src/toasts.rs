@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+/// Number of frames each toast is displayed for
+const TOAST_FRAMES: usize = 180;
+/// The oldest toasts are dropped once there are more than this many
+const MAX_TOASTS: usize = 5;
+
+/// Transient messages displayed in the top right corner for events such as a replay being saved.
+/// Toasts are kept outside of the game and menu so they persist across transitions between them.
+pub struct Toasts {
+    toasts: VecDeque<Toast>,
+}
+
+struct Toast {
+    message: String,
+    frames_remaining: usize,
+}
+
+impl Toasts {
+    pub fn new() -> Toasts {
+        Toasts {
+            toasts: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, message: String) {
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.pop_back();
+        }
+        self.toasts.push_front(Toast {
+            message,
+            frames_remaining: TOAST_FRAMES,
+        });
+    }
+
+    /// Call this once every frame
+    pub fn step(&mut self) {
+        for toast in &mut self.toasts {
+            toast.frames_remaining = toast.frames_remaining.saturating_sub(1);
+        }
+        self.toasts.retain(|x| x.frames_remaining > 0);
+    }
+
+    /// The messages to display, newest first
+    pub fn messages(&self) -> Vec<String> {
+        self.toasts.iter().map(|x| x.message.clone()).collect()
+    }
+}
@@ -0,0 +1,141 @@
+/// Frames the success message is displayed for after completing a step
+const SUCCESS_FRAMES: u64 = 90;
+
+struct TutorialStep {
+    instruction: &'static str,
+    /// The step is complete when the player fires one of these events.
+    /// Every action fires an event named after itself when it starts, so most steps just list actions.
+    events: &'static [&'static str],
+}
+
+/// Fired by a fighter launched while holding the stick away from the deadzone
+pub const DI_EVENT: &str = "DI";
+
+const STEPS: [TutorialStep; 7] = [
+    TutorialStep {
+        instruction: "Tilt the stick all the way to the side to dash, keep holding it to run",
+        events: &["Run"],
+    },
+    TutorialStep {
+        instruction: "Press X or Y to jump, press it again in the air to jump again",
+        events: &["JumpAerialF", "JumpAerialB"],
+    },
+    TutorialStep {
+        instruction: "Press A to attack, tilt the stick while pressing A for different attacks",
+        events: &[
+            "Jab",
+            "Utilt",
+            "Dtilt",
+            "Ftilt",
+            "DashAttack",
+            "Usmash",
+            "Dsmash",
+            "Fsmash",
+            "Uair",
+            "Dair",
+            "Fair",
+            "Bair",
+            "Nair",
+        ],
+    },
+    TutorialStep {
+        instruction: "Hold L or R to shield",
+        events: &["Shield"],
+    },
+    TutorialStep {
+        instruction: "Jump off the stage and drift back towards it to grab the ledge",
+        events: &["LedgeGrab"],
+    },
+    TutorialStep {
+        instruction: "While on the ledge tilt towards the stage, jump, attack or shield to get back up",
+        events: &[
+            "LedgeGetup",
+            "LedgeGetupSlow",
+            "LedgeJump",
+            "LedgeJumpSlow",
+            "LedgeAttack",
+            "LedgeAttackSlow",
+            "LedgeRoll",
+            "LedgeRollSlow",
+        ],
+    },
+    TutorialStep {
+        instruction: "Add a CPU and hold the stick as you are hit to influence the direction you are launched in",
+        events: &[DI_EVENT],
+    },
+];
+
+/// Walks the first player through the basic techniques one step at a time.
+/// Steps are completed by the events fired by the player's actions.
+#[derive(Clone)]
+pub struct Tutorial {
+    step: usize,
+    success_frames: u64,
+}
+
+impl Tutorial {
+    /// Resumes from `progress` completed steps, a completed tutorial starts over
+    pub fn new(progress: usize) -> Tutorial {
+        Tutorial {
+            step: if progress >= STEPS.len() { 0 } else { progress },
+            success_frames: 0,
+        }
+    }
+
+    /// The number of steps completed
+    pub fn progress(&self) -> usize {
+        self.step
+    }
+
+    /// Call this once every frame
+    pub fn step(&mut self) {
+        self.success_frames = self.success_frames.saturating_sub(1);
+    }
+
+    pub fn event(&mut self, name: &str) {
+        if let Some(step) = STEPS.get(self.step) {
+            if step.events.contains(&name) {
+                self.step += 1;
+                self.success_frames = SUCCESS_FRAMES;
+            }
+        }
+    }
+
+    pub fn text(&self) -> Vec<String> {
+        let mut lines = vec![];
+        if self.success_frames > 0 {
+            lines.push(String::from("Nice!"));
+        }
+        match STEPS.get(self.step) {
+            Some(step) => lines.push(format!(
+                "{}/{}: {}",
+                self.step + 1,
+                STEPS.len(),
+                step.instruction
+            )),
+            None => lines.push(String::from(
+                "Tutorial complete! Keep practicing or pause to quit",
+            )),
+        }
+        lines
+    }
+}
+
+#[test]
+fn tutorial_test() {
+    let mut tutorial = Tutorial::new(0);
+    tutorial.event("Shield");
+    assert_eq!(tutorial.step, 0);
+    tutorial.event("Run");
+    assert_eq!(tutorial.step, 1);
+    assert_eq!(tutorial.text()[0], "Nice!");
+    for _ in 0..SUCCESS_FRAMES {
+        tutorial.step();
+    }
+    assert!(tutorial.text()[0].starts_with(&format!("2/{}", STEPS.len())));
+
+    let tutorial = Tutorial::new(STEPS.len() - 1);
+    assert_eq!(tutorial.progress(), STEPS.len() - 1);
+    let tutorial = Tutorial::new(STEPS.len());
+    assert_eq!(tutorial.progress(), 0);
+}
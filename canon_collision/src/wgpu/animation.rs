@@ -1,19 +1,57 @@
 use crate::wgpu::model3d::{Animation, Channel, ChannelOutputs, Joint};
 
 use crate::wgpu::JointTransforms;
-use cgmath::{InnerSpace, Matrix4, VectorSpace};
+use cgmath::{InnerSpace, Matrix4, Quaternion, Vector3, VectorSpace};
 use gltf::animation::Interpolation;
 
 // Cubicspline interpolation implemented as per:
 // https://github.com/KhronosGroup/glTF/blob/master/specification/2.0/README.md#appendix-c-spline-interpolation
 
+/// The animation being crossfaded out of
+pub struct Blend<'a> {
+    pub animation: &'a Animation,
+    pub frame: f32,
+    /// How much of this animation to show, 1.0 is entirely this animation and 0.0 is entirely the incoming animation
+    pub amount: f32,
+}
+
 pub fn generate_joint_transforms(
     animation: &Animation,
     frame: f32,
+    blend: Option<&Blend>,
     root_joint: &Joint,
     parent_transform: Matrix4<f32>,
     buffer: &mut JointTransforms,
 ) {
+    let (mut translation, mut rotation, mut scale) = joint_pose(animation, frame, root_joint);
+    if let Some(blend) = blend {
+        let (blend_translation, blend_rotation, blend_scale) =
+            joint_pose(blend.animation, blend.frame, root_joint);
+        translation = translation.lerp(blend_translation, blend.amount);
+        rotation = rotation.slerp(blend_rotation, blend.amount);
+        scale = scale.lerp(blend_scale, blend.amount);
+    }
+
+    let rotation: Matrix4<f32> = rotation.into();
+    let transform: Matrix4<f32> = parent_transform
+        * Matrix4::from_translation(translation)
+        * rotation
+        * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+
+    let final_transform = transform * root_joint.ibm;
+    buffer[root_joint.index] = final_transform.into();
+
+    for child in &root_joint.children {
+        generate_joint_transforms(animation, frame, blend, child, transform, buffer);
+    }
+}
+
+/// Returns the translation, rotation and scale of the joint relative to its parent at the frame of the animation
+fn joint_pose(
+    animation: &Animation,
+    frame: f32,
+    root_joint: &Joint,
+) -> (Vector3<f32>, Quaternion<f32>, Vector3<f32>) {
     let mut translation = root_joint.translation;
     let mut rotation = root_joint.rotation;
     let mut scale = root_joint.scale;
@@ -128,18 +166,7 @@ pub fn generate_joint_transforms(
         }
     }
 
-    let rotation: Matrix4<f32> = rotation.into();
-    let transform: Matrix4<f32> = parent_transform
-        * Matrix4::from_translation(translation)
-        * rotation
-        * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
-
-    let final_transform = transform * root_joint.ibm;
-    buffer[root_joint.index] = final_transform.into();
-
-    for child in &root_joint.children {
-        generate_joint_transforms(animation, frame, child, transform, buffer);
-    }
+    (translation, rotation, scale)
 }
 
 fn index_step(channel: &Channel, frame: f32) -> usize {
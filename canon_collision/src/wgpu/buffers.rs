@@ -1,5 +1,5 @@
 use crate::entity::fighters::player::RenderShield;
-use crate::game::{RenderRect, SurfaceSelection};
+use crate::game::{RenderMinimap, RenderRect, SurfaceSelection};
 use crate::graphics;
 use canon_collision_lib::entity_def::{CollisionBox, ECB};
 use canon_collision_lib::geometry::Rect;
@@ -34,6 +34,15 @@ pub struct ColorVertex {
     pub color: [f32; 4],
 }
 
+/// Positions and tints one instance of a mesh drawn with the instanced color pipeline
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy, Pod, Zeroable)]
+pub struct ColorInstance {
+    pub transform: [[f32; 4]; 4],
+    /// Multiplied with the color of each vertex
+    pub color: [f32; 4],
+}
+
 fn colorvertex(x: f32, y: f32, color: [f32; 4]) -> ColorVertex {
     ColorVertex {
         position: [x, y, 0.0, 1.0],
@@ -58,6 +67,25 @@ pub struct Buffers {
     pub index_count: u32,
 }
 
+/// White meshes that are drawn many times every frame.
+/// Created once and then positioned and colored by `ColorInstance`s.
+pub struct InstancedMeshes {
+    pub arrow: Rc<Buffers>,
+    pub triangle: Rc<Buffers>,
+    pub circle: Rc<Buffers>,
+}
+
+impl InstancedMeshes {
+    pub fn new(device: &Device) -> InstancedMeshes {
+        let white = [1.0, 1.0, 1.0, 1.0];
+        InstancedMeshes {
+            arrow: Buffers::new_arrow(device, white),
+            triangle: Buffers::new_triangle(device, white),
+            circle: Buffers::new_circle(device, white),
+        }
+    }
+}
+
 impl Buffers {
     pub fn new<T>(device: &Device, vertices: &[T], indices: &[u16]) -> Rc<Buffers>
     where
@@ -114,6 +142,15 @@ impl Buffers {
         *index_count += triangles + 1;
     }
 
+    /// A single colbox filled with the color passed to the hitbox pipeline
+    pub fn new_colbox(device: &Device, colbox: &CollisionBox) -> Rc<Buffers> {
+        let mut vertices: Vec<Vertex> = vec![];
+        let mut indices: Vec<u16> = vec![];
+        let mut index_count = 0;
+        Buffers::gen_colbox(&mut vertices, &mut indices, colbox, &mut index_count, 0);
+        Buffers::new(device, &vertices, &indices)
+    }
+
     pub fn new_fighter_frame_colboxes(
         device: &Device,
         package: &Package,
@@ -239,7 +276,11 @@ impl Buffers {
             };
             let g = if surface.ceiling { 0.5 } else { 0.0 };
             let b = if surface.wall { 0.5 } else { 0.0 };
-            let color = [1.0 - g - b, 1.0 - r - b, 1.0 - r - g, 1.0];
+            let color = if surface.hazard.is_some() {
+                [1.0, 0.0, 0.0, 1.0]
+            } else {
+                [1.0 - g - b, 1.0 - r - b, 1.0 - r - g, 1.0]
+            };
 
             let angle = surface.render_angle() - 90f32.to_radians();
             let d_x = angle.cos() / 4.0;
@@ -489,6 +530,85 @@ impl Buffers {
         Buffers::new(device, &vertices, &indices)
     }
 
+    /// Draws the minimap within `area`, which is in normalized device coordinates.
+    /// `aspect_ratio` is the width of the screen divided by its height, used to keep the player dots square.
+    pub fn new_minimap(
+        device: &Device,
+        minimap: &RenderMinimap,
+        surfaces: &[Surface],
+        area: &Rect,
+        aspect_ratio: f32,
+    ) -> Rc<Buffers> {
+        let mut vertices: Vec<ColorVertex> = vec![];
+        let mut indices: Vec<u16> = vec![];
+        let mut quad = |vertices: &mut Vec<ColorVertex>, corners: [(f32, f32); 4], color| {
+            let start = vertices.len() as u16;
+            for (x, y) in corners {
+                vertices.push(colorvertex(x, y, color));
+            }
+            indices.extend_from_slice(&[start, start + 1, start + 2, start, start + 2, start + 3]);
+        };
+
+        // positions outside of the view are clamped to its edge, so offscreen players are still visible
+        let view = &minimap.view;
+        let to_area = |(x, y): (f32, f32)| {
+            let x = ((x - view.left()) / view.width()).clamp(0.0, 1.0);
+            let y = ((y - view.bot()) / view.height()).clamp(0.0, 1.0);
+            (
+                area.left() + x * area.width(),
+                area.bot() + y * area.height(),
+            )
+        };
+
+        let (left, right, bot, top) = (area.left(), area.right(), area.bot(), area.top());
+        quad(
+            &mut vertices,
+            [(left, bot), (right, bot), (right, top), (left, top)],
+            [0.0, 0.0, 0.0, 0.5],
+        );
+
+        let thickness = 0.004;
+        for surface in surfaces {
+            let (x1, y1) = to_area((surface.x1, surface.y1));
+            let (x2, y2) = to_area((surface.x2, surface.y2));
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
+            if length == 0.0 {
+                continue;
+            }
+            let d_x = -(y2 - y1) / length * thickness;
+            let d_y = (x2 - x1) / length * thickness * aspect_ratio;
+            quad(
+                &mut vertices,
+                [
+                    (x1 + d_x, y1 + d_y),
+                    (x2 + d_x, y2 + d_y),
+                    (x2 - d_x, y2 - d_y),
+                    (x1 - d_x, y1 - d_y),
+                ],
+                [1.0, 1.0, 1.0, 1.0],
+            );
+        }
+
+        let dot_x = 0.012;
+        let dot_y = dot_x * aspect_ratio;
+        for (position, color) in &minimap.players {
+            let (x, y) = to_area(*position);
+            let color = [color[0], color[1], color[2], 1.0];
+            quad(
+                &mut vertices,
+                [
+                    (x - dot_x, y - dot_y),
+                    (x + dot_x, y - dot_y),
+                    (x + dot_x, y + dot_y),
+                    (x - dot_x, y + dot_y),
+                ],
+                color,
+            );
+        }
+
+        Buffers::new(device, &vertices, &indices)
+    }
+
     /// Creates a single triangle with sides of length 1
     pub fn new_triangle(device: &Device, color: [f32; 4]) -> Rc<Buffers> {
         let h = ((3.0 / 4.0) as f32).sqrt();
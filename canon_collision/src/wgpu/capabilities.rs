@@ -0,0 +1,164 @@
+use super::{animated_uniform_size, MAX_JOINTS, SAFE_MAX_JOINTS};
+
+use canon_collision_lib::config::SurfaceFormat;
+
+use wgpu::{Adapter, AdapterInfo, Backend, DeviceType, Features, Limits, Surface, TextureFormat};
+
+/// Samples per pixel used for MSAA when the adapter can handle it
+const SAMPLE_COUNT: u32 = 4;
+
+/// The renderer features enabled for an adapter.
+/// Features are disabled when they exceed the adapter's limits, or in safe mode, instead of failing to create the device.
+pub struct Capabilities {
+    /// 1 disables MSAA
+    pub sample_count: u32,
+    /// Joint transforms available to animated models, models with more joints render incorrectly
+    pub max_joints: usize,
+    /// BCn textures can be sampled, allowing compressed ktx2 textures to replace model textures
+    pub compressed_textures: bool,
+    /// The format of the surface and every render target drawn to it
+    pub surface_format: TextureFormat,
+    info: AdapterInfo,
+    adapter_limits: Limits,
+    /// Each feature that was disabled along with the reason
+    disabled: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn probe(
+        adapter: &Adapter,
+        surface: &Surface,
+        surface_preference: SurfaceFormat,
+        safe_mode: bool,
+    ) -> Capabilities {
+        let info = adapter.get_info();
+        let adapter_limits = adapter.limits();
+        let mut disabled = vec![];
+
+        // wgpu cannot query supported sample counts yet, so MSAA is disabled for the backends and devices that are known to struggle with it
+        let sample_count = if safe_mode {
+            disabled.push(String::from("MSAA: safe mode"));
+            1
+        } else if info.backend == Backend::Gl || info.device_type == DeviceType::Cpu {
+            disabled.push(format!(
+                "MSAA: not reliable on a {:?} device with the {:?} backend",
+                info.device_type, info.backend
+            ));
+            1
+        } else {
+            SAMPLE_COUNT
+        };
+
+        let full_joints_size = animated_uniform_size(MAX_JOINTS);
+        let max_joints = if safe_mode {
+            disabled.push(String::from("Big joint buffer: safe mode"));
+            SAFE_MAX_JOINTS
+        } else if (adapter_limits.max_uniform_buffer_binding_size as usize) < full_joints_size {
+            disabled.push(format!(
+                "Big joint buffer: max_uniform_buffer_binding_size is {} but {} is needed",
+                adapter_limits.max_uniform_buffer_binding_size, full_joints_size
+            ));
+            SAFE_MAX_JOINTS
+        } else {
+            MAX_JOINTS
+        };
+
+        let compressed_textures = if safe_mode {
+            disabled.push(String::from("Compressed textures: safe mode"));
+            false
+        } else if !adapter
+            .features()
+            .contains(Features::TEXTURE_COMPRESSION_BC)
+        {
+            disabled.push(String::from(
+                "Compressed textures: the adapter does not support BCn textures",
+            ));
+            false
+        } else {
+            true
+        };
+
+        // wgpu can only report the best of Bgra8UnormSrgb, Rgba8UnormSrgb, Bgra8Unorm and Rgba8Unorm that the surface supports.
+        // Every surface supporting an sRGB format also supports its Unorm counterpart, but HDR formats cannot be detected yet.
+        let surface_format = match surface.get_preferred_format(adapter) {
+            Some(format) => match surface_preference {
+                SurfaceFormat::Srgb => format,
+                SurfaceFormat::Unorm => unorm_format(format),
+                SurfaceFormat::Hdr => {
+                    disabled.push(String::from(
+                        "HDR output: wgpu cannot detect HDR surface formats yet",
+                    ));
+                    format
+                }
+            },
+            None => {
+                disabled.push(String::from(
+                    "Surface format detection: the surface did not report a supported format",
+                ));
+                TextureFormat::Bgra8Unorm
+            }
+        };
+        if surface_preference != SurfaceFormat::Unorm && !surface_format.describe().srgb {
+            disabled.push(String::from(
+                "sRGB output: the surface does not support sRGB formats",
+            ));
+        }
+
+        Capabilities {
+            sample_count,
+            max_joints,
+            compressed_textures,
+            surface_format,
+            info,
+            adapter_limits,
+            disabled,
+        }
+    }
+
+    /// The limits to request the device with.
+    /// Only what the enabled features need is requested on top of the limits every adapter supports.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_uniform_buffer_binding_size: animated_uniform_size(self.max_joints) as u32,
+            ..Limits::downlevel_defaults().using_resolution(self.adapter_limits.clone())
+        }
+    }
+
+    /// The features to request the device with
+    pub fn features(&self) -> Features {
+        if self.compressed_textures {
+            Features::TEXTURE_COMPRESSION_BC
+        } else {
+            Features::empty()
+        }
+    }
+
+    /// A human readable description of the adapter and which features were enabled
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "Renderer capabilities:\n    Adapter: {} ({:?}, {:?} backend)\n    Max uniform buffer binding size: {}\n    Max texture size: {}\n    Surface format: {:?}\n    MSAA samples: {}\n    Max joints: {}",
+            self.info.name,
+            self.info.device_type,
+            self.info.backend,
+            self.adapter_limits.max_uniform_buffer_binding_size,
+            self.adapter_limits.max_texture_dimension_2d,
+            self.surface_format,
+            self.sample_count,
+            self.max_joints,
+        );
+        for disabled in &self.disabled {
+            report.push_str("\n    Disabled ");
+            report.push_str(disabled);
+        }
+        report
+    }
+}
+
+/// The counterpart of an sRGB format that writes colors as is
+fn unorm_format(format: TextureFormat) -> TextureFormat {
+    match format {
+        TextureFormat::Bgra8UnormSrgb => TextureFormat::Bgra8Unorm,
+        TextureFormat::Rgba8UnormSrgb => TextureFormat::Rgba8Unorm,
+        format => format,
+    }
+}
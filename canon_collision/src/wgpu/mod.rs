@@ -1,25 +1,42 @@
 mod animation;
 mod buffers;
+mod capabilities;
 mod model3d;
+mod pipelines;
+mod screenshot;
+mod shaders;
 
+use crate::ai;
 use crate::audio::BGMMetadata;
 use crate::camera::Camera;
-use crate::entity::{RenderEntityFrame, RenderEntityType};
-use crate::game::{GameState, RenderGame, RenderObject};
+use crate::entity::{RenderActionBlend, RenderEntityFrame, RenderEntityType};
+use crate::game::{GameState, RenderGame, RenderMinimap, RenderObject, RenderReplay};
 use crate::graphics::{self, GraphicsMessage, Render, RenderType};
-use crate::menu::{PlayerSelect, PlayerSelectUi, RenderMenu, RenderMenuState};
+use crate::menu::{
+    PlayerSelect, PlayerSelectUi, RenderMenu, RenderMenuState, EXTRAS_OPTIONS, GAME_SELECT_OPTIONS,
+    MAIN_MENU_OPTIONS,
+};
 use crate::particle::ParticleType;
 use crate::results::PlayerResult;
-use buffers::{Buffers, ColorVertex, Vertex};
+use buffers::{Buffers, ColorInstance, InstancedMeshes};
+use canon_collision_lib::config::{
+    HudAnchor, HudConfig, HudMode, StockDisplay, SurfaceFormat, TeamPalette,
+};
 use canon_collision_lib::entity_def::player::PlayerAction;
-use canon_collision_lib::entity_def::CollisionBoxRole;
+use canon_collision_lib::entity_def::{CollisionBoxRole, HitSpark, StatusEffectKind};
 use canon_collision_lib::geometry::Rect;
 use canon_collision_lib::package::{Package, PackageUpdate};
-use model3d::{
-    Model3D, ModelVertexAnimated, ModelVertexStatic, ModelVertexType, Models, ShaderType,
-};
+use canon_collision_lib::stage;
+use canon_collision_lib::theme::Theme;
+use capabilities::Capabilities;
+use model3d::{Model3D, ModelVertexType, Models, ShaderType};
+use pipelines::Pipelines;
+use screenshot::{capture_texture, Screenshot};
+#[cfg(feature = "hot_reload_shaders")]
+use shaders::ShaderWatcher;
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::num::{NonZeroU64, NonZeroU8};
 use std::rc::Rc;
 use std::str::FromStr;
@@ -30,29 +47,37 @@ use std::{f32, mem};
 use bytemuck::{Pod, Zeroable};
 use cgmath::prelude::*;
 use cgmath::Rad;
-use cgmath::{Matrix4, Vector3};
+use cgmath::{Matrix4, Vector3, Vector4};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use wgpu::util::DeviceExt;
 use wgpu::{
-    BindGroupLayout, Buffer, BufferBinding, Device, Queue, RenderPipeline, Sampler, ShaderSource,
-    Surface, Texture, TextureView,
+    BindGroupLayout, Buffer, BufferBinding, Device, Queue, Sampler, Surface, Texture,
+    TextureFormat, TextureView,
 };
 use wgpu_glyph::ab_glyph::FontArc;
-use wgpu_glyph::{FontId, GlyphBrush, GlyphBrushBuilder, Section, Text};
+use wgpu_glyph::{
+    FontId, GlyphBrush, GlyphBrushBuilder, HorizontalAlign, Layout, Section, Text, VerticalAlign,
+};
 
+use png_decoder::png;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::Fullscreen;
-use winit::window::Window;
+use winit::window::{Icon, Window};
 
 pub struct WgpuGraphics {
     package: Option<Package>,
     models: Models,
+    instanced_meshes: InstancedMeshes,
+    /// The hitbox meshes of every fighter frame rendered since the package last changed
+    frame_buffers: HashMap<(String, String, usize), Option<Rc<Buffers>>>,
     uniforms_buffer: Buffer,
     uniforms_buffer_len: usize,
-    glyph_brush: GlyphBrush<()>,
+    glyph_brush: TextBrush,
     hack_font_id: FontId,
+    /// The font of the package theme that the glyph brush was built with
+    theme_font: Option<String>,
     window: Window,
     event_tx: Sender<WindowEvent<'static>>,
     render_rx: Receiver<GraphicsMessage>,
@@ -61,58 +86,98 @@ pub struct WgpuGraphics {
     surface: Surface,
     wsd: WindowSizeDependent,
     staging_belt: StagingBelt,
-    pipeline_color_2d: RenderPipeline,
-    pipeline_color_3d: RenderPipeline,
-    pipeline_hitbox: RenderPipeline,
-    pipeline_debug: RenderPipeline,
-    pipeline_model3d_static: RenderPipeline,
-    pipeline_model3d_static_lava: RenderPipeline,
-    pipeline_model3d_animated: RenderPipeline,
-    pipeline_model3d_fireball: RenderPipeline,
+    pipelines: Pipelines,
+    /// Only set when hot reloading shaders
+    #[cfg(feature = "hot_reload_shaders")]
+    shader_watcher: Option<ShaderWatcher>,
     bind_group_layout_generic: BindGroupLayout,
     bind_group_layout_model3d: BindGroupLayout,
     sampler: Sampler,
     prev_fullscreen: Option<bool>,
+    screenshot_requested: bool,
+    /// Number of replay frames saved by `--render-replay`, None when no frames have been requested
+    exported_frames: Option<usize>,
     frame_durations: Vec<Duration>,
     fps: String,
     bgm_metadata: Option<(BGMMetadata, Instant)>,
+    /// The damage displayed by the HUD for each player, rolls up towards their actual damage
+    hud_damage: Vec<f32>,
+    team_palette: TeamPalette,
+    /// Set when the frame limiter is active, the next frame is not drawn before this
+    next_frame: Option<Instant>,
+    focused: bool,
     width: u32,
     height: u32,
+    sample_count: u32,
+    max_joints: usize,
+    surface_format: TextureFormat,
 }
 
-const SAMPLE_COUNT: u32 = 4;
+/// The names of every graphics adapter on the system, in the order they can be selected by index
+pub fn adapter_names() -> Vec<String> {
+    let instance = wgpu::Instance::new(wgpu::Backends::all());
+    let adapters: Vec<_> = instance.enumerate_adapters(wgpu::Backends::all()).collect();
+    unique_adapter_names(&adapters)
+}
+
+fn unique_adapter_names(adapters: &[wgpu::Adapter]) -> Vec<String> {
+    let mut names = vec![];
+    for adapter in adapters {
+        let name = adapter.get_info().name;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
 
 impl WgpuGraphics {
+    /// `adapter` selects the graphics adapter by index or name, falling back to the adapter wgpu picks.
+    /// `safe_mode` disables every optional renderer feature, otherwise only the features the adapter cannot handle are disabled.
+    /// `hot_reload_shaders` reloads shaders from the source tree whenever they are modified, requires the hot_reload_shaders feature.
     pub async fn new(
         event_loop: &EventLoop<()>,
         event_tx: Sender<WindowEvent<'static>>,
         render_rx: Receiver<GraphicsMessage>,
+        adapter: Option<String>,
+        surface_format: SurfaceFormat,
+        safe_mode: bool,
+        hot_reload_shaders: bool,
+        hidden: bool,
     ) -> WgpuGraphics {
         let window = Window::new(event_loop).unwrap();
         window.set_title("Canon Collision");
+        // exporting a replay has nothing for the player to see
+        window.set_visible(!hidden);
+        window.set_window_icon(Some(WgpuGraphics::window_icon()));
 
         let size = window.inner_size();
 
-        let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
-        let surface = unsafe { instance.create_surface(&window) };
+        let selected = adapter.and_then(|choice| WgpuGraphics::select_adapter(&window, &choice));
+        let (surface, adapter) = match selected {
+            Some(result) => result,
+            None => match WgpuGraphics::request_adapter(&window, wgpu::Backends::PRIMARY).await {
+                Some(result) => result,
+                None => {
+                    warn!("No adapter supports the primary backends, falling back to any backend");
+                    WgpuGraphics::request_adapter(&window, wgpu::Backends::all())
+                        .await
+                        .expect("No graphics adapter is available")
+                }
+            },
+        };
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
+        let capabilities = Capabilities::probe(&adapter, &surface, surface_format, safe_mode);
+        info!("{}", capabilities.report());
+        let sample_count = capabilities.sample_count;
+        let max_joints = capabilities.max_joints;
+        let surface_format = capabilities.surface_format;
 
-        let (mut device, queue) = adapter
+        let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits {
-                        max_uniform_buffer_binding_size: 32068, // Needed for AnimatedUniform
-                        ..wgpu::Limits::default()
-                    },
+                    features: capabilities.features(),
+                    limits: capabilities.limits(),
                     label: None,
                 },
                 None,
@@ -120,9 +185,6 @@ impl WgpuGraphics {
             .await
             .unwrap();
 
-        let color_module =
-            WgpuGraphics::create_shader(&mut device, include_str!("../shaders/color.wgsl"));
-
         let bind_group_layout_generic =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: None,
@@ -137,196 +199,6 @@ impl WgpuGraphics {
                     count: None,
                 }],
             });
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&bind_group_layout_generic],
-            push_constant_ranges: &[],
-        });
-
-        let primitive_back_face_culling = wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            ..Default::default()
-        };
-
-        let primitive = wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: None,
-            ..Default::default()
-        };
-
-        let targets = [wgpu::ColorTargetState {
-            format: wgpu::TextureFormat::Bgra8Unorm,
-            blend: Some(wgpu::BlendState {
-                color: wgpu::BlendComponent {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-                alpha: wgpu::BlendComponent {
-                    src_factor: wgpu::BlendFactor::SrcAlpha,
-                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                    operation: wgpu::BlendOperation::Add,
-                },
-            }),
-            write_mask: wgpu::ColorWrites::ALL,
-        }];
-        let depth_stencil = Some(wgpu::DepthStencilState {
-            format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::LessEqual,
-            stencil: Default::default(),
-            bias: Default::default(),
-        });
-
-        let multisample = wgpu::MultisampleState {
-            count: SAMPLE_COUNT,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        };
-
-        let pipeline_color_2d = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &color_module,
-                entry_point: "vs_main",
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: mem::size_of::<ColorVertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &wgpu::vertex_attr_array![
-                        0 => Float32x4, // position
-                        1 => Float32x4  // color
-                    ],
-                }],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &color_module,
-                entry_point: "fs_main",
-                targets: &targets,
-            }),
-            primitive,
-            depth_stencil: depth_stencil.clone(),
-            multisample,
-        });
-
-        let pipeline_color_3d = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &color_module,
-                entry_point: "vs_main",
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: mem::size_of::<ColorVertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &wgpu::vertex_attr_array![
-                        0 => Float32x4, // position
-                        1 => Float32x4  // color
-                    ],
-                }],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &color_module,
-                entry_point: "fs_main",
-                targets: &targets,
-            }),
-            primitive: primitive_back_face_culling,
-            depth_stencil: depth_stencil.clone(),
-            multisample,
-        });
-
-        let depth_stencil_disable = Some(wgpu::DepthStencilState {
-            format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Always,
-            stencil: Default::default(),
-            bias: Default::default(),
-        });
-
-        let pipeline_debug = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &color_module,
-                entry_point: "vs_main",
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: mem::size_of::<ColorVertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &wgpu::vertex_attr_array![
-                        0 => Float32x4, // position
-                        1 => Float32x4  // color
-                    ],
-                }],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &color_module,
-                entry_point: "fs_main",
-                targets: &targets,
-            }),
-            primitive,
-            depth_stencil: depth_stencil_disable.clone(),
-            multisample,
-        });
-
-        let hitbox_module =
-            WgpuGraphics::create_shader(&mut device, include_str!("../shaders/hitbox.wgsl"));
-
-        let pipeline_hitbox = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &hitbox_module,
-                entry_point: "vs_main",
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &wgpu::vertex_attr_array![
-                        0 => Float32x2, // position
-                        1 => Float32,   // edge
-                        2 => Uint32     // render_id
-                    ],
-                }],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &hitbox_module,
-                entry_point: "fs_main",
-                targets: &targets,
-            }),
-            primitive,
-            depth_stencil: depth_stencil_disable,
-            multisample,
-        });
-
-        let model3d_standard_fs = vk_shader_macros::include_glsl!(
-            "src/shaders/model3d-standard-fragment.glsl",
-            kind: frag
-        );
-        let model3d_standard_fs_module =
-            WgpuGraphics::create_shader_glsl(&mut device, model3d_standard_fs);
-
-        let model3d_lava_fs =
-            vk_shader_macros::include_glsl!("src/shaders/model3d-lava-fragment.glsl", kind: frag);
-        let model3d_lava_fs_module = WgpuGraphics::create_shader_glsl(&mut device, model3d_lava_fs);
-
-        let model3d_static_vs =
-            vk_shader_macros::include_glsl!("src/shaders/model3d-static-vertex.glsl", kind: vert);
-        let model3d_static_vs_module =
-            WgpuGraphics::create_shader_glsl(&mut device, model3d_static_vs);
-
-        let model3d_animated_vs =
-            vk_shader_macros::include_glsl!("src/shaders/model3d-animated-vertex.glsl", kind: vert);
-        let model3d_animated_vs_module =
-            WgpuGraphics::create_shader_glsl(&mut device, model3d_animated_vs);
-
-        let model3d_fireball_vs =
-            vk_shader_macros::include_glsl!("src/shaders/model3d-fireball-vertex.glsl", kind: vert);
-        let model3d_fireball_vs_module =
-            WgpuGraphics::create_shader_glsl(&mut device, model3d_fireball_vs);
-
-        // TODO: wgsl cant even handle the multiply yet.
-        //let model3d_module = WgpuGraphics::create_shader(&mut device, include_str!("../shaders/model3d.wgsl"));
 
         let bind_group_layout_model3d =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -363,120 +235,31 @@ impl WgpuGraphics {
                     },
                 ],
             });
-        let pipeline_model3d_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: None,
-                bind_group_layouts: &[&bind_group_layout_model3d],
-                push_constant_ranges: &[],
-            });
-
-        let pipeline_model3d_static =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_model3d_layout),
-                vertex: wgpu::VertexState {
-                    module: &model3d_static_vs_module,
-                    entry_point: "main",
-                    buffers: &[wgpu::VertexBufferLayout {
-                        array_stride: mem::size_of::<ModelVertexStatic>() as wgpu::BufferAddress,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![
-                            0 => Float32x4, // position
-                            1 => Float32x2  // uv
-                        ],
-                    }],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &model3d_standard_fs_module,
-                    entry_point: "main",
-                    targets: &targets,
-                }),
-                primitive: primitive_back_face_culling,
-                depth_stencil: depth_stencil.clone(),
-                multisample,
-            });
-
-        let pipeline_model3d_static_lava =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_model3d_layout),
-                vertex: wgpu::VertexState {
-                    module: &model3d_static_vs_module,
-                    entry_point: "main",
-                    buffers: &[wgpu::VertexBufferLayout {
-                        array_stride: mem::size_of::<ModelVertexStatic>() as wgpu::BufferAddress,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![
-                            0 => Float32x4, // position
-                            1 => Float32x2  // uv
-                        ],
-                    }],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &model3d_lava_fs_module,
-                    entry_point: "main",
-                    targets: &targets,
-                }),
-                primitive: primitive_back_face_culling,
-                depth_stencil: depth_stencil.clone(),
-                multisample,
-            });
-
-        let pipeline_model3d_animated =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_model3d_layout),
-                vertex: wgpu::VertexState {
-                    module: &model3d_animated_vs_module,
-                    entry_point: "main",
-                    buffers: &[wgpu::VertexBufferLayout {
-                        array_stride: mem::size_of::<ModelVertexAnimated>() as wgpu::BufferAddress,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![
-                            0 => Float32x4, // position
-                            1 => Float32x2, // uv
-                            2 => Uint32x4,  // joints
-                            3 => Float32x4  // weights
-                        ],
-                    }],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &model3d_standard_fs_module,
-                    entry_point: "main",
-                    targets: &targets,
-                }),
-                primitive: primitive_back_face_culling,
-                depth_stencil: depth_stencil.clone(),
-                multisample,
-            });
+        let pipelines = Pipelines::new(
+            &device,
+            &bind_group_layout_generic,
+            &bind_group_layout_model3d,
+            sample_count,
+            max_joints,
+            surface_format,
+        );
 
-        let pipeline_model3d_fireball =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_model3d_layout),
-                vertex: wgpu::VertexState {
-                    module: &model3d_fireball_vs_module,
-                    entry_point: "main",
-                    buffers: &[wgpu::VertexBufferLayout {
-                        array_stride: mem::size_of::<ModelVertexAnimated>() as wgpu::BufferAddress,
-                        step_mode: wgpu::VertexStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![
-                            0 => Float32x4, // position
-                            1 => Float32x2, // uv
-                            2 => Uint32x4,  // joints
-                            3 => Float32x4  // weights
-                        ],
-                    }],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &model3d_standard_fs_module,
-                    entry_point: "main",
-                    targets: &targets,
-                }),
-                primitive: primitive_back_face_culling,
-                depth_stencil,
-                multisample,
-            });
+        #[cfg(feature = "hot_reload_shaders")]
+        let shader_watcher = if hot_reload_shaders {
+            match ShaderWatcher::new() {
+                Ok(watcher) => Some(watcher),
+                Err(err) => {
+                    error!("Shader hot reloading is disabled: {}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        #[cfg(not(feature = "hot_reload_shaders"))]
+        if hot_reload_shaders {
+            error!("Shader hot reloading is disabled: the game was built without the hot_reload_shaders feature");
+        }
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::Repeat,
@@ -485,24 +268,25 @@ impl WgpuGraphics {
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
             anisotropy_clamp: Some(NonZeroU8::new(16).unwrap()),
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
 
-        let dejavu = FontArc::try_from_slice(include_bytes!("../fonts/DejaVuSans.ttf")).unwrap();
-        let hack = FontArc::try_from_slice(include_bytes!("../fonts/Hack-Regular.ttf")).unwrap();
-
-        let mut glyph_brush_builder = GlyphBrushBuilder::using_font(dejavu);
-        let hack_font_id = glyph_brush_builder.add_font(hack);
-        let glyph_brush = glyph_brush_builder
-            .initial_cache_size((512, 512))
-            .build(&device, wgpu::TextureFormat::Bgra8Unorm);
+        let (glyph_brush, hack_font_id) = build_glyph_brush(&device, surface_format, None);
 
         let width = size.width;
         let height = size.height;
-        let wsd = WindowSizeDependent::new(&device, &surface, width, height);
+        let wsd = WindowSizeDependent::new(
+            &device,
+            &surface,
+            width,
+            height,
+            sample_count,
+            surface_format,
+        );
 
-        let models = Models::new();
+        let models = Models::new(capabilities.compressed_textures);
+        let instanced_meshes = InstancedMeshes::new(&device);
         let uniforms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: &[],
@@ -513,10 +297,13 @@ impl WgpuGraphics {
         WgpuGraphics {
             package: None,
             models,
+            instanced_meshes,
+            frame_buffers: HashMap::new(),
             uniforms_buffer,
             uniforms_buffer_len,
             glyph_brush,
             hack_font_id,
+            theme_font: None,
             window,
             event_tx,
             render_rx,
@@ -525,54 +312,106 @@ impl WgpuGraphics {
             queue,
             wsd,
             staging_belt: StagingBelt::new(),
-            pipeline_color_2d,
-            pipeline_color_3d,
-            pipeline_hitbox,
-            pipeline_debug,
-            pipeline_model3d_static,
-            pipeline_model3d_static_lava,
-            pipeline_model3d_animated,
-            pipeline_model3d_fireball,
+            pipelines,
+            #[cfg(feature = "hot_reload_shaders")]
+            shader_watcher,
             bind_group_layout_generic,
             bind_group_layout_model3d,
             sampler,
             prev_fullscreen: None,
+            screenshot_requested: false,
+            exported_frames: None,
             frame_durations: vec![],
             fps: "".into(),
             bgm_metadata: None,
+            hud_damage: vec![],
+            team_palette: TeamPalette::default(),
+            next_frame: None,
+            focused: true,
             width,
             height,
+            sample_count,
+            max_joints,
+            surface_format,
         }
     }
 
-    fn create_shader_glsl(device: &mut Device, shader: &[u32]) -> wgpu::ShaderModule {
-        device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: ShaderSource::SpirV(Cow::Borrowed(shader)),
-        })
+    /// Returns None if no adapter using one of `backends` can render to the window
+    async fn request_adapter(
+        window: &Window,
+        backends: wgpu::Backends,
+    ) -> Option<(Surface, wgpu::Adapter)> {
+        let instance = wgpu::Instance::new(backends);
+        let surface = unsafe { instance.create_surface(window) };
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            })
+            .await?;
+        Some((surface, adapter))
     }
 
-    fn create_shader(device: &mut Device, shader: &str) -> wgpu::ShaderModule {
-        device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: ShaderSource::Wgsl(Cow::Borrowed(shader)),
-        })
+    /// Returns None if no adapter matches `choice` or the matching adapter cannot render to the window
+    fn select_adapter(window: &Window, choice: &str) -> Option<(Surface, wgpu::Adapter)> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let surface = unsafe { instance.create_surface(window) };
+        let adapters: Vec<_> = instance.enumerate_adapters(wgpu::Backends::all()).collect();
+        let names = unique_adapter_names(&adapters);
+
+        let name = if let Some(name) = graphics::find_adapter(&names, choice) {
+            name
+        } else {
+            warn!(
+                "No graphics adapter matches {:?}, falling back to the default adapter",
+                choice
+            );
+            return None;
+        };
+        // The same adapter can be listed once per backend, the first one found uses the most preferred backend
+        let adapter = adapters
+            .into_iter()
+            .find(|x| &x.get_info().name == name && x.is_surface_supported(&surface));
+        if adapter.is_none() {
+            warn!(
+                "The graphics adapter {} cannot render to the window, falling back to the default adapter",
+                name
+            );
+        }
+        adapter.map(|adapter| (surface, adapter))
+    }
+
+    fn window_icon() -> Icon {
+        let png = png::decode_no_check(include_bytes!("../images/icon.png")).unwrap();
+        Icon::from_rgba(png.data, png.width as u32, png.height as u32).unwrap()
     }
 
     pub fn update(&mut self, event: Event<()>, control_flow: &mut ControlFlow) {
-        *control_flow = ControlFlow::Poll;
+        *control_flow = match self.next_frame {
+            Some(next_frame) => ControlFlow::WaitUntil(next_frame),
+            None => ControlFlow::Poll,
+        };
 
         match event {
             Event::MainEventsCleared => {
                 let frame_start = Instant::now();
+                // window events wake the event loop before the next frame is due
+                if self.next_frame.map_or(false, |x| frame_start < x) {
+                    return;
+                }
 
                 // get the most recent render
                 let mut render = None;
                 loop {
                     match self.render_rx.try_recv() {
                         Ok(message) => {
-                            // we want only the last render message
+                            // we want only the last render message, unless every frame is being exported
+                            let export = message.render.export_frame.is_some();
                             render = Some(self.read_message(message));
+                            if export {
+                                break;
+                            }
                         }
                         Err(TryRecvError::Empty) => {
                             if render.is_none() {
@@ -583,6 +422,14 @@ impl WgpuGraphics {
                             }
                         }
                         Err(TryRecvError::Disconnected) => {
+                            match self.exported_frames {
+                                Some(0) => {
+                                    println!("Failed to export the replay, no frames were saved");
+                                    std::process::exit(1);
+                                }
+                                Some(frames) => println!("Saved {} replay frames", frames),
+                                None => {}
+                            }
                             *control_flow = ControlFlow::Exit;
                             return;
                         }
@@ -590,13 +437,34 @@ impl WgpuGraphics {
                 }
                 let render = render.expect("Guaranteed by logic above");
 
+                #[cfg(feature = "hot_reload_shaders")]
+                if let Some(shader_watcher) = &self.shader_watcher {
+                    let changed = shader_watcher.changed();
+                    if !changed.is_empty() {
+                        self.pipelines.reload(&self.device, &changed);
+                    }
+                }
+
                 let resolution: (u32, u32) = self.window.inner_size().into();
                 self.window_resize(resolution.0, resolution.1);
 
+                let minimized = resolution.0 == 0 || resolution.1 == 0;
+                self.next_frame = render.frame_limiter.as_ref().map(|limiter| {
+                    let fps = if self.focused && !minimized {
+                        limiter.menu_fps
+                    } else {
+                        limiter.background_fps
+                    };
+                    frame_start + Duration::from_secs(1) / fps.max(1)
+                });
+
                 self.render(render);
                 self.frame_durations.push(frame_start.elapsed());
             }
             Event::WindowEvent { event, .. } => {
+                if let WindowEvent::Focused(focused) = event {
+                    self.focused = focused;
+                }
                 if let Some(event) = event.to_static() {
                     if let Err(_) = self.event_tx.send(event) {
                         *control_flow = ControlFlow::Exit;
@@ -607,12 +475,49 @@ impl WgpuGraphics {
         }
     }
 
+    /// Sets the window title and rebuilds the glyph brush when the package theme changes the font
+    fn apply_theme(&mut self) {
+        let theme = &self.package.as_ref().unwrap().theme;
+        self.window.set_title(&theme.title);
+        if theme.font != self.theme_font {
+            let font = theme
+                .font
+                .as_ref()
+                .and_then(|x| self.models.get_font(x))
+                .and_then(|x| match FontArc::try_from_vec(x) {
+                    Ok(font) => Some(font),
+                    Err(err) => {
+                        error!("Failed to load the theme font: {}", err);
+                        None
+                    }
+                });
+            let (glyph_brush, hack_font_id) =
+                build_glyph_brush(&self.device, self.surface_format, font);
+            self.glyph_brush = glyph_brush;
+            self.hack_font_id = hack_font_id;
+            self.theme_font = theme.font.clone();
+        }
+    }
+
+    /// The theme of the current package, the default theme before the package is received
+    fn theme(&self) -> Theme {
+        self.package
+            .as_ref()
+            .map(|x| x.theme.clone())
+            .unwrap_or_default()
+    }
+
     fn read_message(&mut self, message: GraphicsMessage) -> Render {
+        if !message.package_updates.is_empty() {
+            self.frame_buffers.clear();
+        }
+
         // TODO: Refactor out the vec + enum once vulkano backend is removed
         for package_update in message.package_updates {
             match package_update {
                 PackageUpdate::Package(package) => {
                     self.package = Some(package);
+                    self.apply_theme();
                 }
                 PackageUpdate::DeleteFighterFrame {
                     fighter,
@@ -649,6 +554,8 @@ impl WgpuGraphics {
                 }
             }
         }
+        // Skipped renders may have requested a screenshot
+        self.screenshot_requested |= message.render.screenshot;
         message.render
     }
 
@@ -660,7 +567,14 @@ impl WgpuGraphics {
         self.width = width;
         self.height = height;
 
-        self.wsd = WindowSizeDependent::new(&self.device, &self.surface, width, height);
+        self.wsd = WindowSizeDependent::new(
+            &self.device,
+            &self.surface,
+            width,
+            height,
+            self.sample_count,
+            self.surface_format,
+        );
     }
 
     fn render(&mut self, render: Render) {
@@ -693,6 +607,7 @@ impl WgpuGraphics {
         };
         self.window
             .set_cursor_visible(!render.fullscreen || in_game_paused);
+        self.team_palette = render.team_palette.clone();
 
         let mut encoder = self
             .device
@@ -703,11 +618,25 @@ impl WgpuGraphics {
                 self.models.load_game(&self.device, &self.queue, render);
             }
             RenderType::Menu(render) => {
-                let fighters = &self.package.as_ref().unwrap().fighters(); // TODO: avoid recreating multiple times every frame
+                let package = self.package.as_ref().unwrap();
+                let fighters = &package.fighters(); // TODO: avoid recreating multiple times every frame
+                let background = package.theme.css_background_model.as_deref();
                 self.models
-                    .load_menu(&self.device, &self.queue, render, fighters);
+                    .load_menu(&self.device, &self.queue, render, fighters, background);
             }
         }
+        let clear_color = match &render.render_type {
+            RenderType::Game(_) => wgpu::Color::BLACK,
+            RenderType::Menu(_) => {
+                let [r, g, b, a] = self.theme().background_color;
+                wgpu::Color {
+                    r: r as f64,
+                    g: g as f64,
+                    b: b as f64,
+                    a: a as f64,
+                }
+            }
+        };
 
         let frame = self.surface.get_current_texture().unwrap();
 
@@ -715,17 +644,22 @@ impl WgpuGraphics {
             RenderType::Game(game) => self.game_render(game, &render.command_output),
             RenderType::Menu(menu) => self.menu_render(menu, &render.command_output),
         };
+        self.toasts_render(&render.toasts);
 
         let uniforms_bytes = {
-            let uniforms_size = draws.iter().map(|x| x.ty.uniform_size_padded()).sum();
+            let max_joints = self.max_joints;
+            let uniforms_size = draws
+                .iter()
+                .map(|x| x.ty.uniform_size_padded(max_joints))
+                .sum();
             let mut uniforms_bytes = vec![0; uniforms_size];
             let mut uniforms_offset = 0;
             for draw in &draws {
-                let size = draw.ty.uniform_size();
-                let size_padded = draw.ty.uniform_size_padded();
+                let size = draw.ty.uniform_size(max_joints);
+                let size_padded = draw.ty.uniform_size_padded(max_joints);
 
                 uniforms_bytes[uniforms_offset..uniforms_offset + size]
-                    .copy_from_slice(draw.ty.uniform_bytes());
+                    .copy_from_slice(&draw.ty.uniform_bytes(max_joints));
                 uniforms_offset += size_padded;
             }
             uniforms_bytes
@@ -745,17 +679,40 @@ impl WgpuGraphics {
                 .write_buffer(&self.uniforms_buffer, 0, &uniforms_bytes);
         }
 
-        let view = &frame
+        let capture = self.screenshot_requested || render.export_frame.is_some();
+        let capture = capture && self.width > 0 && self.height > 0;
+        // The surface texture cannot be copied from, so without MSAA a captured frame is rendered to a texture that can be instead.
+        // The window then shows a stale frame, which is fine for a screenshot or a replay export.
+        let capture_texture = if capture && self.wsd.multisampled_framebuffer.is_none() {
+            Some(capture_texture(
+                &self.device,
+                self.width,
+                self.height,
+                self.surface_format,
+            ))
+        } else {
+            None
+        };
+        let capture_view = capture_texture
+            .as_ref()
+            .map(|x| x.create_view(&wgpu::TextureViewDescriptor::default()));
+        let surface_view = frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        let view = capture_view.as_ref().unwrap_or(&surface_view);
+        // without MSAA there is nothing to resolve so the frame is rendered to directly
+        let (color_view, resolve_target) = match &self.wsd.multisampled_framebuffer {
+            Some(multisampled_framebuffer) => (multisampled_framebuffer, Some(view)),
+            None => (view, None),
+        };
         let mut bind_groups = vec![];
         {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &self.wsd.multisampled_framebuffer,
-                    resolve_target: Some(view),
+                    view: color_view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        load: wgpu::LoadOp::Clear(clear_color),
                         store: true,
                     },
                 }],
@@ -778,10 +735,10 @@ impl WgpuGraphics {
                 let uniform_resource = wgpu::BindingResource::Buffer(BufferBinding {
                     buffer: &self.uniforms_buffer,
                     offset: uniforms_offset,
-                    size: NonZeroU64::new(draw.ty.uniform_size() as u64),
+                    size: NonZeroU64::new(draw.ty.uniform_size(self.max_joints) as u64),
                 });
                 let bind_group = match &draw.ty {
-                    DrawType::Color { .. } => {
+                    DrawType::Color { .. } | DrawType::ColorInstanced { .. } => {
                         self.device.create_bind_group(&wgpu::BindGroupDescriptor {
                             label: None,
                             layout: &self.bind_group_layout_generic,
@@ -815,7 +772,7 @@ impl WgpuGraphics {
                     }
                 };
                 bind_groups.push(bind_group);
-                uniforms_offset += draw.ty.uniform_size_padded() as u64;
+                uniforms_offset += draw.ty.uniform_size_padded(self.max_joints) as u64;
             }
 
             for (i, draw) in draws.iter().enumerate() {
@@ -824,26 +781,66 @@ impl WgpuGraphics {
                         debug: false,
                         dimension3: false,
                         ..
-                    } => &self.pipeline_color_2d,
+                    } => &self.pipelines.color_2d,
                     DrawType::Color {
                         debug: false,
                         dimension3: true,
                         ..
-                    } => &self.pipeline_color_3d,
-                    DrawType::Color { debug: true, .. } => &self.pipeline_debug,
-                    DrawType::Hitbox { .. } => &self.pipeline_hitbox,
-                    DrawType::ModelAnimated { .. } => &self.pipeline_model3d_animated,
-                    DrawType::ModelStatic { .. } => &self.pipeline_model3d_static,
-                    DrawType::Lava { .. } => &self.pipeline_model3d_static_lava,
-                    DrawType::Fireball { .. } => &self.pipeline_model3d_fireball,
+                    } => &self.pipelines.color_3d,
+                    DrawType::Color { debug: true, .. } => &self.pipelines.debug,
+                    DrawType::ColorInstanced { .. } => &self.pipelines.color_instanced,
+                    DrawType::Hitbox { .. } => &self.pipelines.hitbox,
+                    DrawType::ModelAnimated { .. } => &self.pipelines.model3d_animated,
+                    DrawType::ModelStatic { .. } => &self.pipelines.model3d_static,
+                    DrawType::Lava { .. } => &self.pipelines.model3d_static_lava,
+                    DrawType::Fireball { .. } => &self.pipelines.model3d_fireball,
                 };
                 rpass.set_pipeline(pipeline);
                 rpass.set_bind_group(0, &bind_groups[i], &[]);
                 rpass.set_index_buffer(draw.buffers.index.slice(..), wgpu::IndexFormat::Uint16);
                 rpass.set_vertex_buffer(0, draw.buffers.vertex.slice(..));
-                rpass.draw_indexed(0..draw.buffers.index_count as u32, 0, 0..1);
+                let instance_count = match &draw.ty {
+                    DrawType::ColorInstanced {
+                        instances,
+                        instance_count,
+                        ..
+                    } => {
+                        rpass.set_vertex_buffer(1, instances.slice(..));
+                        *instance_count
+                    }
+                    _ => 1,
+                };
+                rpass.draw_indexed(0..draw.buffers.index_count as u32, 0, 0..instance_count);
             }
         }
+
+        let screenshot = if capture {
+            self.screenshot_requested = false;
+            if let Some(multisampled_framebuffer) = &self.wsd.multisampled_framebuffer {
+                Some(Screenshot::new(
+                    &self.device,
+                    &mut encoder,
+                    multisampled_framebuffer,
+                    self.width,
+                    self.height,
+                    self.surface_format,
+                ))
+            } else {
+                capture_texture.as_ref().map(|texture| {
+                    Screenshot::from_texture(
+                        &self.device,
+                        &mut encoder,
+                        texture,
+                        self.width,
+                        self.height,
+                        self.surface_format,
+                    )
+                })
+            }
+        } else {
+            None
+        };
+
         self.glyph_brush
             .draw_queued(
                 &self.device,
@@ -859,6 +856,17 @@ impl WgpuGraphics {
         self.queue.submit(Some(encoder.finish()));
         frame.present();
         self.staging_belt.recall();
+
+        if let Some(path) = &render.export_frame {
+            let exported_frames = self.exported_frames.get_or_insert(0);
+            if let Some(screenshot) = screenshot {
+                if screenshot.save_png(&self.device, path) {
+                    *exported_frames += 1;
+                }
+            }
+        } else if let Some(screenshot) = screenshot {
+            screenshot.save(&self.device);
+        }
     }
 
     fn create_bind_group_model3d(
@@ -902,21 +910,94 @@ impl WgpuGraphics {
         }
     }
 
-    fn game_timer_render(&mut self, timer: &Option<Duration>) {
+    fn game_timer_render(&mut self, timer: &Option<Duration>, hud: &HudConfig) {
+        if hud.mode == HudMode::Off {
+            return;
+        }
         if let &Some(ref timer) = timer {
             let minutes = timer.as_secs() / 60;
             let seconds = timer.as_secs() % 60;
+            let y = match hud.timer_anchor {
+                HudAnchor::Top => 4.0 * hud.scale,
+                HudAnchor::Bottom => self.height as f32 - 50.0 * hud.scale,
+            };
             self.glyph_brush.queue(Section {
                 text: vec![Text::new(format!("{:02}:{:02}", minutes, seconds).as_ref())
                     .with_color([1.0, 1.0, 1.0, 1.0])
-                    .with_scale(40.0)],
-                screen_position: ((self.width / 2) as f32 - 50.0, 4.0),
+                    .with_scale(40.0 * hud.scale)],
+                screen_position: ((self.width / 2) as f32 - 50.0 * hud.scale, y),
+                ..Section::default()
+            });
+        }
+    }
+
+    fn replay_render(&mut self, replay: &Option<RenderReplay>) {
+        if let Some(replay) = replay {
+            let text = format!(
+                "Replay frame {} of {}-{} ({}x){}",
+                replay.frame,
+                replay.first_frame,
+                replay.last_frame,
+                replay.speed,
+                if replay.assisted { " - assisted" } else { "" }
+            );
+            self.glyph_brush.queue(Section {
+                text: vec![Text::new(&text)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(30.0)],
+                screen_position: ((self.width / 2) as f32 - 200.0, 50.0),
+                ..Section::default()
+            });
+        }
+    }
+
+    fn screen_text_render(&mut self, lines: &[String]) {
+        for (i, line) in lines.iter().enumerate() {
+            self.glyph_brush.queue(Section {
+                text: vec![Text::new(line)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(30.0)],
+                screen_position: ((self.width / 2) as f32, 90.0 + 35.0 * i as f32),
+                layout: Layout::default().h_align(HorizontalAlign::Center),
+                ..Section::default()
+            });
+        }
+    }
+
+    /// Right aligned below the toasts
+    fn kill_feed_render(&mut self, lines: &[String], hud: &HudConfig) {
+        if hud.mode != HudMode::Full {
+            return;
+        }
+        for (i, line) in lines.iter().enumerate() {
+            self.glyph_brush.queue(Section {
+                text: vec![Text::new(line)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(22.0 * hud.scale)],
+                screen_position: (
+                    self.width as f32 - 10.0,
+                    200.0 + 25.0 * hud.scale * i as f32,
+                ),
+                layout: Layout::default().h_align(HorizontalAlign::Right),
                 ..Section::default()
             });
         }
     }
 
-    fn game_hud_render(&mut self, objects: &[RenderObject]) {
+    /// Each players HUD is laid out within a block `HUD_HEIGHT` tall, positioned along the edge set by `hud.player_anchor`
+    fn game_hud_render(&mut self, objects: &[RenderObject], hud: &HudConfig) {
+        if hud.mode == HudMode::Off {
+            return;
+        }
+        let full = hud.mode == HudMode::Full;
+        let scale = hud.scale;
+        let top = match hud.player_anchor {
+            HudAnchor::Top => 0.0,
+            HudAnchor::Bottom => self.height as f32 - HUD_HEIGHT * scale,
+        };
+        // offset from the top of the block
+        let y = |offset: f32| top + offset * scale;
+
         let mut entities = 0;
         for object in objects {
             if let RenderObject::Entity(entity) = object {
@@ -926,12 +1007,16 @@ impl WgpuGraphics {
             }
         }
         let distance = (self.width / (entities + 1)) as f32;
+        self.hud_damage.resize(entities as usize, 0.0);
 
-        let mut location = -100.0;
+        let mut location = -100.0 * scale;
+        let mut player_i = 0;
         for object in objects {
             if let RenderObject::Entity(entity) = object {
-                location += distance;
                 if let RenderEntityType::Player(player) = &entity.render_type {
+                    location += distance;
+                    let shown_damage = roll_damage(&mut self.hud_damage[player_i], player.damage);
+                    player_i += 1;
                     match PlayerAction::from_str(&entity.frames[0].action) {
                         Ok(PlayerAction::Eliminated) => {}
                         _ => {
@@ -939,7 +1024,11 @@ impl WgpuGraphics {
                             let color = [c[0], c[1], c[2], 1.0];
 
                             if let Some(stocks) = player.stocks {
-                                let stocks_string = if stocks > 5 {
+                                let show_count = match hud.stock_display {
+                                    StockDisplay::Icons => stocks > 5,
+                                    StockDisplay::Count => true,
+                                };
+                                let stocks_string = if show_count {
                                     format!("⬤ x {}", stocks)
                                 } else {
                                     let mut stocks_string = String::new();
@@ -952,19 +1041,96 @@ impl WgpuGraphics {
                                 self.glyph_brush.queue(Section {
                                     text: vec![Text::new(stocks_string.as_ref())
                                         .with_color(color)
-                                        .with_scale(22.0)],
-                                    screen_position: (location + 10.0, self.height as f32 - 130.0),
+                                        .with_scale(22.0 * scale)],
+                                    screen_position: (location + 10.0 * scale, y(75.0)),
                                     ..Section::default()
                                 });
                             }
 
-                            self.glyph_brush.queue(Section {
-                                text: vec![Text::new(format!("{}%", player.damage).as_ref())
+                            if let Some(meter) = player.meter.filter(|_| full) {
+                                let filled = (meter / 10.0).floor() as usize;
+                                let mut meter_string = String::new();
+                                for i in 0..10 {
+                                    meter_string.push(if i < filled { '█' } else { '░' });
+                                }
+                                if filled >= 10 {
+                                    meter_string.push_str(" SUPER");
+                                }
+
+                                self.glyph_brush.queue(Section {
+                                    text: vec![Text::new(meter_string.as_ref())
+                                        .with_color(color)
+                                        .with_scale(18.0 * scale)
+                                        .with_font_id(self.hack_font_id)],
+                                    screen_position: (location + 10.0 * scale, y(55.0)),
+                                    ..Section::default()
+                                });
+                            }
+
+                            if let Some(frames) = player.respawn_frames_remaining.filter(|_| full) {
+                                self.glyph_brush.queue(Section {
+                                    text: vec![Text::new(&format!(
+                                        "Respawn {:.1}",
+                                        frames as f32 / 60.0
+                                    ))
                                     .with_color(color)
-                                    .with_scale(110.0)],
-                                screen_position: (location, self.height as f32 - 117.0),
+                                    .with_scale(22.0 * scale)],
+                                    screen_position: (location + 10.0 * scale, y(0.0)),
+                                    ..Section::default()
+                                });
+                            }
+
+                            let status_effects = if full {
+                                &player.status_effects[..]
+                            } else {
+                                &[]
+                            };
+                            for (i, effect) in status_effects.iter().enumerate() {
+                                let (icon, c) = status_effect_icon(effect.kind);
+                                // blink when about to expire
+                                let alpha = if effect.frames_remaining < 60
+                                    && effect.frames_remaining % 16 < 8
+                                {
+                                    0.3
+                                } else {
+                                    1.0
+                                };
+                                let text = if effect.stacks > 1 {
+                                    format!("{}{}", icon, effect.stacks)
+                                } else {
+                                    icon.to_string()
+                                };
+                                self.glyph_brush.queue(Section {
+                                    text: vec![Text::new(&text)
+                                        .with_color([c[0], c[1], c[2], alpha])
+                                        .with_scale(22.0 * scale)],
+                                    screen_position: (
+                                        location + (10.0 + 40.0 * i as f32) * scale,
+                                        y(25.0),
+                                    ),
+                                    ..Section::default()
+                                });
+                            }
+
+                            let percent = hud.percent_precision.format(shown_damage);
+                            self.glyph_brush.queue(Section {
+                                text: vec![Text::new(percent.as_ref())
+                                    .with_color(damage_color(color, shown_damage))
+                                    .with_scale(110.0 * scale)],
+                                screen_position: (location, y(88.0)),
                                 ..Section::default()
                             });
+
+                            if full && hud.show_internal_damage {
+                                self.glyph_brush.queue(Section {
+                                    text: vec![Text::new(&player.damage.to_string())
+                                        .with_color(color)
+                                        .with_scale(20.0 * scale)
+                                        .with_font_id(self.hack_font_id)],
+                                    screen_position: (location + 10.0 * scale, y(180.0)),
+                                    ..Section::default()
+                                });
+                            }
                         }
                     }
                 }
@@ -972,6 +1138,106 @@ impl WgpuGraphics {
         }
     }
 
+    /// An arrow with the player number above each fighter, in their team color
+    fn player_indicators_render(
+        &mut self,
+        objects: &[RenderObject],
+        camera: &Camera,
+        hud: &HudConfig,
+    ) {
+        if !hud.player_indicators || hud.mode == HudMode::Off {
+            return;
+        }
+        let transform = camera.transform();
+        for object in objects {
+            if let RenderObject::Entity(entity) = object {
+                if let RenderEntityType::Player(player) = &entity.render_type {
+                    let frame = &entity.frames[0];
+                    if let Ok(PlayerAction::Eliminated) = PlayerAction::from_str(&frame.action) {
+                        continue;
+                    }
+                    let top = frame.ecb.as_ref().map_or(0.0, |ecb| ecb.top);
+                    let (x, y) = frame.frame_bps;
+                    let clip = transform * Vector4::new(x, y + top + INDICATOR_HEIGHT, 0.0, 1.0);
+                    let screen_x = (clip.x / clip.w + 1.0) / 2.0 * self.width as f32;
+                    let screen_y = (1.0 - clip.y / clip.w) / 2.0 * self.height as f32;
+
+                    let c = entity.fighter_color;
+                    self.glyph_brush.queue(Section {
+                        text: vec![Text::new(&format!("P{}\n▼", player.id + 1))
+                            .with_color([c[0], c[1], c[2], 1.0])
+                            .with_scale(24.0 * hud.scale)],
+                        screen_position: (screen_x, screen_y),
+                        layout: Layout::default()
+                            .h_align(HorizontalAlign::Center)
+                            .v_align(VerticalAlign::Bottom),
+                        ..Section::default()
+                    });
+                }
+            }
+        }
+    }
+
+    /// Labels the colboxes of entities with the colbox overlay enabled with their index, hitboxes also display their damage
+    fn colbox_labels_render(&mut self, objects: &[RenderObject], camera: &Camera) {
+        let transform = camera.transform();
+        for object in objects {
+            if let RenderObject::Entity(entity) = object {
+                if !entity.debug.render.debug() || !entity.debug.colbox_overlay {
+                    continue;
+                }
+                let (x, y) = entity.frames[0].frame_bps;
+                for (i, colbox) in entity.frame_data.colboxes.iter().enumerate() {
+                    let label = match &colbox.role {
+                        CollisionBoxRole::Hit(hitbox) => format!("{} {}%", i, hitbox.damage),
+                        _ => i.to_string(),
+                    };
+                    let (point_x, point_y) = colbox.point;
+                    let clip = transform * Vector4::new(x + point_x, y + point_y, 0.0, 1.0);
+                    let screen_x = (clip.x / clip.w + 1.0) / 2.0 * self.width as f32;
+                    let screen_y = (1.0 - clip.y / clip.w) / 2.0 * self.height as f32;
+
+                    self.glyph_brush.queue(Section {
+                        text: vec![Text::new(&label)
+                            .with_color([1.0, 1.0, 1.0, 1.0])
+                            .with_scale(16.0)],
+                        screen_position: (screen_x, screen_y),
+                        layout: Layout::default()
+                            .h_align(HorizontalAlign::Center)
+                            .v_align(VerticalAlign::Center),
+                        ..Section::default()
+                    });
+                }
+            }
+        }
+    }
+
+    fn saving_render(&mut self, saving: bool) {
+        if saving {
+            self.glyph_brush.queue(Section {
+                text: vec![Text::new("Saving package...")
+                    .with_color([1.0, 1.0, 0.0, 1.0])
+                    .with_scale(20.0)],
+                screen_position: (self.width as f32 - 250.0, 4.0),
+                ..Section::default()
+            });
+        }
+    }
+
+    /// Right aligned below the fps counter, newest first
+    fn toasts_render(&mut self, toasts: &[String]) {
+        for (i, toast) in toasts.iter().enumerate() {
+            self.glyph_brush.queue(Section {
+                text: vec![Text::new(toast)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(25.0)],
+                screen_position: (self.width as f32 - 10.0, 30.0 + 30.0 * i as f32),
+                layout: Layout::default().h_align(HorizontalAlign::Right),
+                ..Section::default()
+            });
+        }
+    }
+
     fn fps_render(&mut self) {
         if self.frame_durations.len() == 60 {
             let total: Duration = self.frame_durations.iter().sum();
@@ -1060,6 +1326,19 @@ impl WgpuGraphics {
         }
     }
 
+    fn frame_data_panel_render(&mut self, lines: &[String]) {
+        for (i, line) in lines.iter().enumerate() {
+            self.glyph_brush.queue(Section {
+                text: vec![Text::new(line)
+                    .with_color([1.0, 1.0, 1.0, 1.0])
+                    .with_scale(20.0)
+                    .with_font_id(self.hack_font_id)],
+                screen_position: (self.width as f32 - 350.0, 100.0 + 20.0 * i as f32),
+                ..Section::default()
+            });
+        }
+    }
+
     fn render_hitbox_buffers(
         &self,
         render: &RenderGame,
@@ -1081,6 +1360,7 @@ impl WgpuGraphics {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn render_model3d(
         &self,
         camera: &Camera,
@@ -1089,6 +1369,8 @@ impl WgpuGraphics {
         animation_name: &str,
         animation_frame: f32,
         animation_frame_no_restart: f32,
+        blend: Option<&RenderActionBlend>,
+        flash: f32,
     ) -> Vec<Draw> {
         let camera = camera.transform();
         let mut draws = vec![];
@@ -1101,12 +1383,22 @@ impl WgpuGraphics {
 
                     let draw = match primitive.vertex_type {
                         ModelVertexType::Animated => {
-                            let mut joint_transforms = [Matrix4::identity().into(); 500];
+                            let mut joint_transforms = [Matrix4::identity().into(); MAX_JOINTS];
+                            let blend = blend.and_then(|blend| {
+                                model.animations.get(&blend.action).map(|animation| {
+                                    animation::Blend {
+                                        animation,
+                                        frame: blend.frame,
+                                        amount: blend.amount,
+                                    }
+                                })
+                            });
                             for root_joint in &mesh.root_joints {
                                 if let Some(animation) = model.animations.get(animation_name) {
                                     animation::generate_joint_transforms(
                                         animation,
                                         animation_frame,
+                                        blend.as_ref(),
                                         root_joint,
                                         Matrix4::identity(),
                                         &mut joint_transforms,
@@ -1118,6 +1410,7 @@ impl WgpuGraphics {
                                 transform,
                                 joint_transforms,
                                 frame_count: animation_frame_no_restart,
+                                flash,
                             };
                             let ty = match primitive.shader_type {
                                 ShaderType::Standard | ShaderType::Lava => {
@@ -1154,6 +1447,55 @@ impl WgpuGraphics {
         draws
     }
 
+    /// Returns None when there are no instances to draw
+    fn render_instanced(
+        &self,
+        render: &RenderGame,
+        buffers: Rc<Buffers>,
+        instances: &[ColorInstance],
+    ) -> Option<Draw> {
+        if instances.is_empty() {
+            return None;
+        }
+
+        let uniform = TransformUniform {
+            transform: render.camera.transform().into(),
+        };
+        let instance_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(instances),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        Some(Draw {
+            ty: DrawType::ColorInstanced {
+                uniform,
+                instances: instance_buffer,
+                instance_count: instances.len() as u32,
+            },
+            buffers,
+        })
+    }
+
+    /// The hitbox mesh of a fighter frame, only created the first time the frame is rendered
+    fn fighter_frame_buffers(
+        &mut self,
+        entity_def_key: &str,
+        action: &str,
+        frame: usize,
+    ) -> Option<Rc<Buffers>> {
+        let device = &self.device;
+        let package = self.package.as_ref().unwrap();
+        self.frame_buffers
+            .entry((entity_def_key.to_string(), action.to_string(), frame))
+            .or_insert_with(|| {
+                Buffers::new_fighter_frame(device, package, entity_def_key, action, frame)
+            })
+            .clone()
+    }
+
     fn render_color_buffers(
         &self,
         render: &RenderGame,
@@ -1182,14 +1524,21 @@ impl WgpuGraphics {
         let mut draws = vec![];
         let mut rng = StdRng::from_seed(render.seed);
         if command_output.is_empty() {
-            self.game_hud_render(&render.entities);
-            self.game_timer_render(&render.timer);
+            self.game_hud_render(&render.entities, &render.hud);
+            self.player_indicators_render(&render.entities, &render.camera, &render.hud);
+            self.colbox_labels_render(&render.entities, &render.camera);
+            self.game_timer_render(&render.timer, &render.hud);
+            self.replay_render(&render.replay);
+            self.screen_text_render(&render.screen_text);
+            self.kill_feed_render(&render.kill_feed, &render.hud);
             self.debug_lines_render(&render.debug_lines);
+            self.frame_data_panel_render(&render.frame_data_panel);
             self.fps_render();
             self.bgm_change(&render);
         } else {
             self.command_render(command_output);
         }
+        self.saving_render(render.saving);
 
         match render.state {
             GameState::Local => {}
@@ -1199,6 +1548,14 @@ impl WgpuGraphics {
                 // configurable size via treeflection
                 // but this might be desirable to have during normal gameplay to, hmmmm....
                 // Just have a 5 second fade out time so it doesnt look clunky and can be used during frame advance
+                if let Some(pause_menu) = &render.pause_menu {
+                    self.draw_option_list(
+                        pause_menu.title,
+                        &pause_menu.options,
+                        pause_menu.selection,
+                        "",
+                    );
+                }
             }
             _ => {}
         }
@@ -1206,13 +1563,16 @@ impl WgpuGraphics {
         let stage_transformation = Matrix4::identity();
         if render.render_stage_mode.normal() {
             if let Some(stage) = self.models.get(&render.stage_model_name) {
+                let animation_len = stage.animations.get("Main").map_or(1, |x| x.len());
                 draws.extend(self.render_model3d(
                     &render.camera,
                     stage,
                     &stage_transformation,
                     "Main",
-                    (render.current_frame % 300) as f32, // TODO: Somehow get the animation length from the gltf
+                    (render.current_frame % animation_len) as f32,
                     render.current_frame as f32,
+                    None,
+                    0.0,
                 ));
             }
         }
@@ -1253,6 +1613,12 @@ impl WgpuGraphics {
             }
         }
 
+        // Arrows and particles are drawn many times per frame, so they are collected into a single instanced draw per mesh
+        let mut arrows = vec![];
+        let mut triangles = vec![];
+        let mut circles = vec![];
+        let mut shadows = vec![];
+
         for entity in render.entities.iter() {
             match entity {
                 RenderObject::Entity(entity) => {
@@ -1291,16 +1657,29 @@ impl WgpuGraphics {
                                     entity.frames[0].render_bps.1,
                                     entity.frames[0].render_bps.2,
                                 ));
-                                let transformation = position * rotate * dir;
-                                if let Some(fighter) = self.models.get(fighter_model_name) {
+                                let scale = Matrix4::from_scale(entity.model_scale);
+                                let transformation = position * rotate * dir * scale;
+                                let flash = match &entity.render_type {
+                                    RenderEntityType::Player(player) => player.hit_flash,
+                                    _ => 0.0,
+                                };
+                                let costume_textures = entity.frames[0].costume_textures.as_deref();
+                                if let Some(fighter) = self
+                                    .models
+                                    .get_costume(fighter_model_name, costume_textures)
+                                {
                                     draws.extend(self.render_model3d(
                                         &render.camera,
                                         fighter,
                                         &transformation,
                                         action,
-                                        entity.frames[0].frame as f32,
+                                        entity.frames[0].animation_frame,
                                         entity.frames[0].frame_no_restart as f32,
+                                        entity.frames[0].blend.as_ref(),
+                                        flash,
                                     ));
+                                    shadows
+                                        .extend(blob_shadow(&render.surfaces, &entity.frames[0]));
                                 }
                             }
                         }
@@ -1341,9 +1720,7 @@ impl WgpuGraphics {
                     if entity.debug.render.debug() {
                         if entity.debug.render.onion_skin() {
                             if let Some(frame) = entity.frames.get(2) {
-                                if let Some(buffers) = Buffers::new_fighter_frame(
-                                    &self.device,
-                                    self.package.as_ref().unwrap(),
+                                if let Some(buffers) = self.fighter_frame_buffers(
                                     &frame.entity_def_key,
                                     &frame.action,
                                     frame.frame,
@@ -1361,9 +1738,7 @@ impl WgpuGraphics {
                             }
 
                             if let Some(frame) = entity.frames.get(1) {
-                                if let Some(buffers) = Buffers::new_fighter_frame(
-                                    &self.device,
-                                    self.package.as_ref().unwrap(),
+                                if let Some(buffers) = self.fighter_frame_buffers(
                                     &frame.entity_def_key,
                                     &frame.action,
                                     frame.frame,
@@ -1382,19 +1757,23 @@ impl WgpuGraphics {
                         }
 
                         // draw entity
-                        if let Some(buffers) = Buffers::new_fighter_frame(
-                            &self.device,
-                            self.package.as_ref().unwrap(),
+                        if let Some(buffers) = self.fighter_frame_buffers(
                             &entity.frames[0].entity_def_key,
                             &entity.frames[0].action,
                             entity.frames[0].frame,
                         ) {
-                            let color = [0.9, 0.9, 0.9, 1.0];
+                            // intangible hurtboxes cannot be hit, so the overlay dims them
+                            let alpha = if entity.debug.colbox_overlay && entity.intangible {
+                                OVERLAY_INTANGIBLE_ALPHA
+                            } else {
+                                1.0
+                            };
+                            let color = [0.9, 0.9, 0.9, alpha];
                             let edge_color = if entity.entity_selected {
-                                [0.0, 1.0, 0.0, 1.0]
+                                [0.0, 1.0, 0.0, alpha]
                             } else {
                                 let c = entity.fighter_color;
-                                [c[0], c[1], c[2], 1.0]
+                                [c[0], c[1], c[2], alpha]
                             };
                             draws.push(self.render_hitbox_buffers(
                                 &render,
@@ -1406,6 +1785,21 @@ impl WgpuGraphics {
                         } else {
                             // TODO: Give some indication that we are rendering a deleted or otherwise nonexistent frame
                         }
+
+                        // color hitboxes by damage, drawing the highest priority hitbox (the lowest index) on top
+                        if entity.debug.colbox_overlay {
+                            let (x, y) = entity.frames[0].frame_bps;
+                            let position = Matrix4::from_translation(Vector3::new(x, y, 0.0));
+                            for colbox in entity.frame_data.colboxes.iter().rev() {
+                                if let CollisionBoxRole::Hit(ref hitbox) = colbox.role {
+                                    let buffers = Buffers::new_colbox(&self.device, colbox);
+                                    let color = overlay_hitbox_color(hitbox.damage);
+                                    draws.push(self.render_hitbox_buffers(
+                                        &render, buffers, &position, color, color,
+                                    ));
+                                }
+                            }
+                        }
                     }
 
                     // draw selected colboxes
@@ -1431,9 +1825,6 @@ impl WgpuGraphics {
                     // draw hitbox debug arrows
                     // TODO: this should be usable for all entities
                     if entity.debug.hitbox_vectors {
-                        // TODO: lets move these to the WgpuGraphics struct
-                        let kbg_arrow = Buffers::new_arrow(&self.device, [1.0, 1.0, 1.0, 1.0]);
-                        let bkb_arrow = Buffers::new_arrow(&self.device, [0.17, 0.17, 1.0, 1.0]);
                         for colbox in entity.frame_data.colboxes.iter() {
                             if let CollisionBoxRole::Hit(ref hitbox) = colbox.role {
                                 let kb_squish = 0.5;
@@ -1455,20 +1846,14 @@ impl WgpuGraphics {
                                 let position = Matrix4::from_translation(Vector3::new(x, y, 0.0));
                                 let transformation_bkb = position * rotate * squish_bkb;
                                 let transformation_kbg = position * rotate * squish_kbg;
-                                draws.push(self.render_color_buffers(
-                                    &render,
-                                    kbg_arrow.clone(),
-                                    &transformation_kbg,
-                                    false,
-                                    false,
-                                ));
-                                draws.push(self.render_color_buffers(
-                                    &render,
-                                    bkb_arrow.clone(),
-                                    &transformation_bkb,
-                                    false,
-                                    false,
-                                ));
+                                arrows.push(ColorInstance {
+                                    transform: transformation_kbg.into(),
+                                    color: [1.0, 1.0, 1.0, 1.0],
+                                });
+                                arrows.push(ColorInstance {
+                                    transform: transformation_bkb.into(),
+                                    color: [0.17, 0.17, 1.0, 1.0],
+                                });
                             }
                         }
                     }
@@ -1476,7 +1861,6 @@ impl WgpuGraphics {
                     // draw debug vector arrows
                     let num_arrows = entity.vector_arrows.len() as f32;
                     for (i, arrow) in entity.vector_arrows.iter().enumerate() {
-                        let arrow_buffers = Buffers::new_arrow(&self.device, arrow.color);
                         let squish = Matrix4::from_nonuniform_scale(
                             (num_arrows - i as f32) / num_arrows,
                             1.0,
@@ -1491,13 +1875,10 @@ impl WgpuGraphics {
                             0.0,
                         ));
                         let transformation = position * rotate * squish;
-                        draws.push(self.render_color_buffers(
-                            &render,
-                            arrow_buffers,
-                            &transformation,
-                            false,
-                            false,
-                        ));
+                        arrows.push(ColorInstance {
+                            transform: transformation.into(),
+                            color: arrow.color,
+                        });
                     }
 
                     // draw particles
@@ -1514,15 +1895,10 @@ impl WgpuGraphics {
                                     particle.x, particle.y, particle.z,
                                 ));
                                 let transformation = position * rotate * size;
-                                let color = [c[0], c[1], c[2], 1.0];
-                                let triangle_buffers = Buffers::new_triangle(&self.device, color);
-                                draws.push(self.render_color_buffers(
-                                    &render,
-                                    triangle_buffers,
-                                    &transformation,
-                                    false,
-                                    false,
-                                ));
+                                triangles.push(ColorInstance {
+                                    transform: transformation.into(),
+                                    color: [c[0], c[1], c[2], 1.0],
+                                });
                             }
                             ParticleType::AirJump => {
                                 let size = Matrix4::from_nonuniform_scale(
@@ -1534,22 +1910,29 @@ impl WgpuGraphics {
                                     particle.x, particle.y, particle.z,
                                 ));
                                 let transformation = position * size;
-                                let color =
-                                    [c[0], c[1], c[2], (1.0 - particle.counter_mult()) * 0.7];
-                                let jump_buffers = Buffers::new_circle(&self.device, color);
-                                draws.push(self.render_color_buffers(
-                                    &render,
-                                    jump_buffers,
-                                    &transformation,
-                                    false,
-                                    false,
-                                ));
+                                circles.push(ColorInstance {
+                                    transform: transformation.into(),
+                                    color: [
+                                        c[0],
+                                        c[1],
+                                        c[2],
+                                        (1.0 - particle.counter_mult()) * 0.7,
+                                    ],
+                                });
                             }
-                            ParticleType::Hit { knockback, damage } => {
-                                // needs to rendered last to ensure we dont have anything drawn on top of the inversion
+                            ParticleType::Hit {
+                                knockback,
+                                damage,
+                                spark,
+                            } => {
+                                // slashes are long and thin
+                                let (length, width) = match spark {
+                                    HitSpark::Slash => (0.3, 0.03),
+                                    _ => (0.2, 0.08),
+                                };
                                 let size = Matrix4::from_nonuniform_scale(
-                                    0.2 * knockback,
-                                    0.08 * damage,
+                                    length * knockback,
+                                    width * damage,
                                     1.0,
                                 );
                                 let rotate = Matrix4::from_angle_z(Rad(
@@ -1559,15 +1942,10 @@ impl WgpuGraphics {
                                     particle.x, particle.y, particle.z,
                                 ));
                                 let transformation = position * rotate * size;
-                                let color = [0.5, 0.5, 0.5, 1.5];
-                                let hit_buffers = Buffers::new_circle(&self.device, color);
-                                draws.push(self.render_color_buffers(
-                                    &render,
-                                    hit_buffers,
-                                    &transformation,
-                                    false,
-                                    false,
-                                )); // TODO: Invert
+                                circles.push(ColorInstance {
+                                    transform: transformation.into(),
+                                    color: [c[0], c[1], c[2], 1.5],
+                                }); // TODO: Invert
                             }
                         }
                     }
@@ -1589,21 +1967,30 @@ impl WgpuGraphics {
                                 let transformation = position * scale;
 
                                 let c = entity.fighter_color;
-                                let color = [c[0], c[1], c[2], 1.0];
-                                let triangle_buffers = Buffers::new_triangle(&self.device, color);
-
-                                draws.push(self.render_color_buffers(
-                                    &render,
-                                    triangle_buffers,
-                                    &transformation,
-                                    false,
-                                    false,
-                                ));
+                                triangles.push(ColorInstance {
+                                    transform: transformation.into(),
+                                    color: [c[0], c[1], c[2], 1.0],
+                                });
                             }
                             _ => {}
                         }
                     }
                 }
+                RenderObject::Rect(render_rect) => {
+                    let transformation = Matrix4::identity();
+                    let buffers = Buffers::rect_buffers(
+                        &self.device,
+                        render_rect.rect.clone(),
+                        render_rect.color,
+                    );
+                    draws.push(self.render_color_buffers(
+                        &render,
+                        buffers,
+                        &transformation,
+                        false,
+                        false,
+                    ));
+                }
                 RenderObject::RectOutline(render_rect) => {
                     let transformation = Matrix4::identity();
                     let buffers = Buffers::rect_outline_buffers(&self.device, render_rect);
@@ -1639,6 +2026,18 @@ impl WgpuGraphics {
             }
         }
 
+        let meshes = [
+            // shadows are drawn first so that they dont cover any transparent particles
+            (self.instanced_meshes.circle.clone(), shadows),
+            (self.instanced_meshes.arrow.clone(), arrows),
+            (self.instanced_meshes.triangle.clone(), triangles),
+            // hit particles are circles, they need to be rendered last to ensure we dont have anything drawn on top of the inversion
+            (self.instanced_meshes.circle.clone(), circles),
+        ];
+        for (mesh, instances) in meshes {
+            draws.extend(self.render_instanced(&render, mesh, &instances));
+        }
+
         // Some things need to be rendered after everything else as they are transparent
         for entity in render.entities.iter() {
             match entity {
@@ -1673,16 +2072,87 @@ impl WgpuGraphics {
             }
         }
 
+        for (i, minimap) in render.minimaps.iter().enumerate() {
+            draws.push(self.minimap_render(minimap, &render.surfaces, i));
+        }
+
         draws
     }
 
+    /// Draws the minimap along the top of the screen from the left corner, `index` minimaps to the right of the corner.
+    /// It is sized to match the aspect ratio of the area it displays.
+    /// It is drawn at the nearest depth so the 2D color pipeline draws it over everything else.
+    fn minimap_render(
+        &self,
+        minimap: &RenderMinimap,
+        surfaces: &[stage::Surface],
+        index: usize,
+    ) -> Draw {
+        let aspect_ratio = self.width as f32 / self.height.max(1) as f32;
+        let view_width = minimap.view.width().max(1.0);
+        let view_height = minimap.view.height().max(1.0);
+        let width = 0.5;
+        let height = width * view_height / view_width * aspect_ratio;
+        let x1 = -0.98 + index as f32 * (width + 0.02);
+        let area = Rect {
+            x1,
+            y1: 0.95 - height,
+            x2: x1 + width,
+            y2: 0.95,
+        };
+        let buffers = Buffers::new_minimap(&self.device, minimap, surfaces, &area, aspect_ratio);
+
+        let transform = Matrix4::identity().into();
+        Draw {
+            ty: DrawType::Color {
+                uniform: TransformUniform { transform },
+                debug: false,
+                dimension3: false,
+            },
+            buffers,
+        }
+    }
+
     fn menu_render(&mut self, render: RenderMenu, command_output: &[String]) -> Vec<Draw> {
         self.fps_render();
         let mut draws = vec![];
 
         match render.state {
+            RenderMenuState::MainMenu(selection) => {
+                let title = self.theme().title;
+                self.draw_option_list(&title, &MAIN_MENU_OPTIONS, selection, "");
+                self.command_render(command_output);
+            }
             RenderMenuState::GameSelect(selection) => {
-                self.draw_game_selector(selection);
+                self.draw_option_list("Select Game Mode", &GAME_SELECT_OPTIONS, selection, "");
+                self.command_render(command_output);
+            }
+            RenderMenuState::Settings {
+                ref settings,
+                selection,
+            } => {
+                self.draw_option_list("Settings", settings, selection, "");
+                self.command_render(command_output);
+            }
+            RenderMenuState::AudioSettings {
+                ref settings,
+                selection,
+            } => {
+                self.draw_option_list("Audio", settings, selection, "A: raise, X/Y: lower");
+                self.command_render(command_output);
+            }
+            RenderMenuState::Modifiers {
+                ref settings,
+                selection,
+            } => {
+                self.draw_option_list("Modifiers", settings, selection, "");
+                self.command_render(command_output);
+            }
+            RenderMenuState::Extras {
+                selection,
+                ref message,
+            } => {
+                self.draw_option_list("Extras", &EXTRAS_OPTIONS, selection, message);
                 self.command_render(command_output);
             }
             RenderMenuState::ReplaySelect(replay_names, selection) => {
@@ -1697,10 +2167,12 @@ impl WgpuGraphics {
                     }
                 }
 
+                let theme = self.theme();
+                draws.extend(self.draw_css_background(&theme));
                 draws.push(self.draw_back_counter(back_counter, back_counter_max));
                 self.glyph_brush.queue(Section {
                     text: vec![Text::new("Select Fighters")
-                        .with_color([1.0, 1.0, 1.0, 1.0])
+                        .with_color(theme.text_color)
                         .with_scale(50.0)],
                     screen_position: (100.0, 4.0),
                     ..Section::default()
@@ -1818,14 +2290,17 @@ impl WgpuGraphics {
                 }
                 self.command_render(command_output);
             }
-            RenderMenuState::StageSelect(selection) => {
-                draws.extend(self.draw_stage_selector(selection));
+            RenderMenuState::StageSelect {
+                ref stages,
+                random,
+                ref struck,
+                selection,
+                ref message,
+            } => {
+                draws.extend(self.draw_stage_selector(stages, random, struck, selection, message));
                 self.command_render(command_output);
             }
-            RenderMenuState::GameResults {
-                results,
-                replay_saved,
-            } => {
+            RenderMenuState::GameResults { results, standings } => {
                 let max = results.len() as f32;
                 for (i, result) in results.iter().enumerate() {
                     let i = i as f32;
@@ -1833,16 +2308,34 @@ impl WgpuGraphics {
                     self.draw_player_result(result, start_x);
                 }
 
-                if replay_saved {
+                for (i, line) in standings.iter().enumerate() {
                     self.glyph_brush.queue(Section {
-                        text: vec![Text::new("Replay saved!")
+                        text: vec![Text::new(line)
                             .with_color([1.0, 1.0, 1.0, 1.0])
-                            .with_scale(30.0)],
-                        screen_position: (30.0, self.height as f32 - 30.0),
+                            .with_scale(25.0)],
+                        screen_position: (
+                            30.0,
+                            self.height as f32 - 90.0 - 25.0 * (standings.len() - i) as f32,
+                        ),
                         ..Section::default()
                     });
                 }
             }
+            RenderMenuState::HotkeySettings {
+                ref hotkeys,
+                selection,
+                ref message,
+            } => {
+                self.draw_hotkey_settings(hotkeys, selection, message);
+            }
+            RenderMenuState::ControllerSettings {
+                ref controllers,
+                selection,
+                ref message,
+            } => {
+                self.draw_option_list("Controllers", controllers, selection, message);
+                self.command_render(command_output);
+            }
             RenderMenuState::GenericText(ref text) => {
                 self.glyph_brush.queue(Section {
                     text: vec![Text::new(text)
@@ -1857,35 +2350,55 @@ impl WgpuGraphics {
         draws
     }
 
-    fn draw_game_selector(&mut self, selection: usize) {
+    fn draw_option_list<S: AsRef<str>>(
+        &mut self,
+        title: &str,
+        options: &[S],
+        selection: usize,
+        message: &str,
+    ) {
+        let theme = self.theme();
         self.glyph_brush.queue(Section {
-            text: vec![Text::new("Select Game Mode")
-                .with_color([1.0, 1.0, 1.0, 1.0])
+            text: vec![Text::new(title)
+                .with_color(theme.text_color)
                 .with_scale(50.0)],
             screen_position: (100.0, 4.0),
             ..Section::default()
         });
 
-        let modes = vec!["Local", "Netplay", "Replays"];
-        for (mode_i, name) in modes.iter().enumerate() {
+        // long lists are squeezed together so they stay on screen
+        let spacing = (self.height as f32 * 0.85 / options.len() as f32).min(50.0);
+        for (option_i, name) in options.iter().enumerate() {
             let size = 26.0; // TODO: determine from width/height of screen and start/end pos
-            let x_offset = if mode_i == selection { 0.1 } else { 0.0 };
+            let x_offset = if option_i == selection { 0.1 } else { 0.0 };
             let x = self.width as f32 * (0.1 + x_offset);
-            let y = self.height as f32 * 0.1 + mode_i as f32 * 50.0;
+            let y = self.height as f32 * 0.1 + option_i as f32 * spacing;
+            let color = if option_i == selection {
+                theme.selected_color
+            } else {
+                theme.text_color
+            };
             self.glyph_brush.queue(Section {
-                text: vec![Text::new(name)
-                    .with_color([1.0, 1.0, 1.0, 1.0])
-                    .with_scale(size)],
+                text: vec![Text::new(name.as_ref()).with_color(color).with_scale(size)],
                 screen_position: (x, y),
                 ..Section::default()
             });
         }
+
+        self.glyph_brush.queue(Section {
+            text: vec![Text::new(message)
+                .with_color([1.0, 1.0, 0.0, 1.0])
+                .with_scale(30.0)],
+            screen_position: (30.0, self.height as f32 - 30.0),
+            ..Section::default()
+        });
     }
 
     fn draw_replay_selector(&mut self, replay_names: &[String], selection: usize) {
+        let theme = self.theme();
         self.glyph_brush.queue(Section {
             text: vec![Text::new("Select Replay")
-                .with_color([1.0, 1.0, 1.0, 1.0])
+                .with_color(theme.text_color)
                 .with_scale(50.0)],
             screen_position: (100.0, 4.0),
             ..Section::default()
@@ -1896,14 +2409,54 @@ impl WgpuGraphics {
             let x_offset = if replay_i == selection { 0.1 } else { 0.0 };
             let x = self.width as f32 * (0.1 + x_offset);
             let y = self.height as f32 * 0.1 + replay_i as f32 * 50.0;
+            let color = if replay_i == selection {
+                theme.selected_color
+            } else {
+                theme.text_color
+            };
+            self.glyph_brush.queue(Section {
+                text: vec![Text::new(name.as_ref()).with_color(color).with_scale(size)],
+                screen_position: (x, y),
+                ..Section::default()
+            });
+        }
+    }
+
+    fn draw_hotkey_settings(
+        &mut self,
+        hotkeys: &[(String, String)],
+        selection: usize,
+        message: &str,
+    ) {
+        self.glyph_brush.queue(Section {
+            text: vec![Text::new("Hotkeys")
+                .with_color([1.0, 1.0, 1.0, 1.0])
+                .with_scale(50.0)],
+            screen_position: (100.0, 4.0),
+            ..Section::default()
+        });
+
+        for (hotkey_i, (action, key)) in hotkeys.iter().enumerate() {
+            let size = 26.0; // TODO: determine from width/height of screen and start/end pos
+            let x_offset = if hotkey_i == selection { 0.1 } else { 0.0 };
+            let x = self.width as f32 * (0.1 + x_offset);
+            let y = self.height as f32 * 0.1 + hotkey_i as f32 * 50.0;
             self.glyph_brush.queue(Section {
-                text: vec![Text::new(name.as_ref())
+                text: vec![Text::new(&format!("{}: {}", action, key))
                     .with_color([1.0, 1.0, 1.0, 1.0])
                     .with_scale(size)],
                 screen_position: (x, y),
                 ..Section::default()
             });
         }
+
+        self.glyph_brush.queue(Section {
+            text: vec![Text::new(message)
+                .with_color([1.0, 1.0, 0.0, 1.0])
+                .with_scale(30.0)],
+            screen_position: (30.0, self.height as f32 - 30.0),
+            ..Section::default()
+        });
     }
 
     // TODO: Rewrite text rendering to be part of scene instead of just plastered on top
@@ -1957,7 +2510,7 @@ impl WgpuGraphics {
                         team = controller_selection.team;
                     }
                 }
-                graphics::get_team_color4(team)
+                graphics::get_team_color4(&self.team_palette, team)
             } else {
                 [0.5, 0.5, 0.5, 1.0]
             };
@@ -1991,14 +2544,23 @@ impl WgpuGraphics {
                 options.push(String::from("Remove CPU"));
             }
             PlayerSelectUi::HumanTeam(_) => {
-                options.extend(graphics::get_colors().iter().map(|x| x.name.clone()));
+                options.extend(
+                    graphics::get_colors(&self.team_palette)
+                        .iter()
+                        .map(|x| x.name.clone()),
+                );
                 options.push(String::from("Return"));
             }
             PlayerSelectUi::CpuTeam(_) => {
-                options.extend(graphics::get_colors().iter().map(|x| x.name.clone()));
+                options.extend(
+                    graphics::get_colors(&self.team_palette)
+                        .iter()
+                        .map(|x| x.name.clone()),
+                );
                 options.push(String::from("Return"));
             }
             PlayerSelectUi::CpuAi(_) => {
+                options.extend(ai::DIFFICULTIES.iter().map(|x| x.name.to_string()));
                 options.push(String::from("Return"));
             }
             PlayerSelectUi::HumanUnplugged => unreachable!(),
@@ -2019,13 +2581,18 @@ impl WgpuGraphics {
                 PlayerSelectUi::HumanFighter(_) | PlayerSelectUi::CpuFighter(_) => {
                     if let Some(selected_option_i) = selection.fighter {
                         if selected_option_i == option_i {
-                            color = graphics::get_team_color4(selection.team);
+                            color = graphics::get_team_color4(&self.team_palette, selection.team);
                         }
                     }
                 }
                 PlayerSelectUi::HumanTeam(_) | PlayerSelectUi::CpuTeam(_) => {
-                    if option_i < graphics::get_colors().len() {
-                        color = graphics::get_team_color4(option_i);
+                    if option_i < graphics::TEAM_COUNT {
+                        color = graphics::get_team_color4(&self.team_palette, option_i);
+                    }
+                }
+                PlayerSelectUi::CpuAi(_) => {
+                    if selection.cpu_ai.unwrap_or(ai::DEFAULT_DIFFICULTY) == option_i {
+                        color = graphics::get_team_color4(&self.team_palette, selection.team);
                     }
                 }
                 _ => {}
@@ -2062,14 +2629,14 @@ impl WgpuGraphics {
             let fighter_y = fighter_y_base * camera_dimension * fighter_y_ar;
             let face_right = start_x < 0.0;
 
-            //let zoom = fighter.css_scale; // TODO
+            let scale = Matrix4::from_scale(fighter.css_scale);
             let dir = Matrix4::from_angle_y(if face_right {
                 Rad::turn_div_4()
             } else {
                 -Rad::turn_div_4()
             });
             let position = Matrix4::from_translation(Vector3::new(fighter_x, fighter_y, 0.0));
-            let transformation = position * dir;
+            let transformation = position * dir * scale;
             let camera = Camera::new_for_menu(
                 self.aspect_ratio(),
                 self.width as f32,
@@ -2077,7 +2644,24 @@ impl WgpuGraphics {
                 camera_dimension,
             );
 
-            if let Some(model) = self.models.get(&fighter.name) {
+            let costume = fighter.fighter().and_then(|x| x.costume(selection.costume));
+            let model_name = costume
+                .and_then(|x| x.model.as_ref())
+                .unwrap_or(&fighter.name);
+            let costume_textures = costume.map(|x| x.textures.as_str());
+            if let Some(costume) = costume {
+                let x = ((start_x + 1.0) / 2.0) * self.width as f32;
+                let y = ((end_y + 1.0) / 2.0) * self.height as f32 - 30.0;
+                self.glyph_brush.queue(Section {
+                    text: vec![Text::new(&costume.name)
+                        .with_color([1.0, 1.0, 1.0, 1.0])
+                        .with_scale(26.0)],
+                    screen_position: (x, y),
+                    ..Section::default()
+                });
+            }
+
+            if let Some(model) = self.models.get_costume(model_name, costume_textures) {
                 // TODO
                 //let action: &str = PlayerAction::from_u64(fighter.css_action)
                 //    .map(|x| x.into())
@@ -2091,6 +2675,8 @@ impl WgpuGraphics {
                     action,
                     frame,
                     frame,
+                    None,
+                    0.0,
                 ));
             }
         }
@@ -2098,65 +2684,120 @@ impl WgpuGraphics {
         draws
     }
 
-    fn draw_stage_selector(&mut self, selection: usize) -> Vec<Draw> {
+    /// Draws the background model of the package theme behind the character select screen
+    fn draw_css_background(&self, theme: &Theme) -> Vec<Draw> {
+        let model = match theme
+            .css_background_model
+            .as_ref()
+            .and_then(|x| self.models.get(x))
+        {
+            Some(model) => model,
+            None => return vec![],
+        };
+        let camera = Camera::new_for_menu(
+            self.aspect_ratio(),
+            self.width as f32,
+            self.height as f32,
+            40.0,
+        );
+        self.render_model3d(
+            &camera,
+            model,
+            &Matrix4::identity(),
+            "Idle",
+            0.0,
+            0.0,
+            None,
+            0.0,
+        )
+    }
+
+    fn draw_stage_selector(
+        &mut self,
+        stages: &[String],
+        random: bool,
+        struck: &[usize],
+        selection: usize,
+        message: &str,
+    ) -> Vec<Draw> {
         let mut draws = vec![];
+        let theme = self.theme();
         self.glyph_brush.queue(Section {
             text: vec![Text::new("Select Stage")
-                .with_color([1.0, 1.0, 1.0, 1.0])
+                .with_color(theme.text_color)
                 .with_scale(50.0)],
             screen_position: (100.0, 4.0),
             ..Section::default()
         });
-        let stages = &self.package.as_ref().unwrap().stages;
-        for (stage_i, stage) in stages.key_value_iter().enumerate() {
-            let (stage_key, stage) = stage;
+        self.glyph_brush.queue(Section {
+            text: vec![Text::new(message)
+                .with_color([1.0, 1.0, 0.0, 1.0])
+                .with_scale(30.0)],
+            screen_position: (30.0, self.height as f32 - 30.0),
+            ..Section::default()
+        });
+
+        let package_stages = &self.package.as_ref().unwrap().stages;
+        let names = stages
+            .iter()
+            .map(|key| {
+                package_stages
+                    .key_to_value(key)
+                    .map_or(key.as_str(), |x| x.name.as_str())
+            })
+            .chain(random.then(|| "Random"));
+        for (stage_i, name) in names.enumerate() {
             let size = 26.0; // TODO: determine from width/height of screen and start/end pos
             let x_offset = if stage_i == selection { 0.05 } else { 0.0 };
             let x = self.width as f32 * (0.1 + x_offset);
             let y = self.height as f32 * 0.1 + stage_i as f32 * 50.0;
+            let color = if struck.contains(&stage_i) {
+                [0.4, 0.4, 0.4, 1.0]
+            } else if stage_i == selection {
+                theme.selected_color
+            } else {
+                theme.text_color
+            };
             self.glyph_brush.queue(Section {
-                text: vec![Text::new(stage.name.as_ref())
-                    .with_color([1.0, 1.0, 1.0, 1.0])
-                    .with_scale(size)],
+                text: vec![Text::new(name).with_color(color).with_scale(size)],
                 screen_position: (x, y),
                 ..Section::default()
             });
+        }
 
-            if stage_i == selection {
-                let zoom_divider = 100.0;
-                let zoom = 1.0 / zoom_divider;
-                let y = -0.2 * zoom_divider;
-
-                let camera = Matrix4::from_nonuniform_scale(zoom, zoom * self.aspect_ratio(), 1.0);
-                let position = Matrix4::from_translation(Vector3::new(1.0, y, 0.0));
-                let transformation = camera * position;
-                let uniform = TransformUniform {
-                    transform: transformation.into(),
-                };
-
-                let stage = &self.package.as_ref().unwrap().stages[stage_key.as_str()];
+        let stage_key = stages.get(selection);
+        if let Some(stage) = stage_key.and_then(|x| package_stages.key_to_value(x)) {
+            let zoom_divider = 100.0;
+            let zoom = 1.0 / zoom_divider;
+            let y = -0.2 * zoom_divider;
+
+            let camera = Matrix4::from_nonuniform_scale(zoom, zoom * self.aspect_ratio(), 1.0);
+            let position = Matrix4::from_translation(Vector3::new(1.0, y, 0.0));
+            let transformation = camera * position;
+            let uniform = TransformUniform {
+                transform: transformation.into(),
+            };
 
-                if let Some(buffers) = Buffers::new_surfaces(&self.device, &stage.surfaces) {
-                    draws.push(Draw {
-                        ty: DrawType::Color {
-                            uniform,
-                            debug: true,
-                            dimension3: false,
-                        },
-                        buffers,
-                    });
-                }
+            if let Some(buffers) = Buffers::new_surfaces(&self.device, &stage.surfaces) {
+                draws.push(Draw {
+                    ty: DrawType::Color {
+                        uniform,
+                        debug: true,
+                        dimension3: false,
+                    },
+                    buffers,
+                });
+            }
 
-                if let Some(buffers) = Buffers::new_surfaces_fill(&self.device, &stage.surfaces) {
-                    draws.push(Draw {
-                        ty: DrawType::Color {
-                            uniform,
-                            debug: true,
-                            dimension3: false,
-                        },
-                        buffers,
-                    });
-                }
+            if let Some(buffers) = Buffers::new_surfaces_fill(&self.device, &stage.surfaces) {
+                draws.push(Draw {
+                    ty: DrawType::Color {
+                        uniform,
+                        debug: true,
+                        dimension3: false,
+                    },
+                    buffers,
+                });
             }
         }
 
@@ -2167,7 +2808,7 @@ impl WgpuGraphics {
         let fighter_name = self.package.as_ref().unwrap().entities[result.fighter.as_ref()]
             .name
             .as_str();
-        let color = graphics::get_team_color4(result.team);
+        let color = graphics::get_team_color4(&self.team_palette, result.team);
         let x = (start_x + 0.05) * self.width as f32;
         let y = 30.0;
         self.glyph_brush.queue(Section {
@@ -2204,18 +2845,27 @@ L-Cancel Success: {}%",
 }
 
 struct WindowSizeDependent {
-    multisampled_framebuffer: TextureView,
+    /// None when MSAA is disabled
+    multisampled_framebuffer: Option<TextureView>,
     depth_stencil: TextureView,
 }
 
 impl WindowSizeDependent {
     /// This method is called once during initialization, then again whenever the window is resized
-    fn new(device: &Device, surface: &Surface, width: u32, height: u32) -> WindowSizeDependent {
+    fn new(
+        device: &Device,
+        surface: &Surface,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        surface_format: TextureFormat,
+    ) -> WindowSizeDependent {
         surface.configure(
             device,
             &wgpu::SurfaceConfiguration {
                 usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format: wgpu::TextureFormat::Bgra8Unorm,
+                format: surface_format,
+                // wgpu cannot query present modes yet, but falls back to Fifo when Mailbox is unsupported
                 present_mode: wgpu::PresentMode::Mailbox,
                 width,
                 height,
@@ -2230,14 +2880,20 @@ impl WindowSizeDependent {
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
-            sample_count: SAMPLE_COUNT,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Bgra8Unorm,
+            format: surface_format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         };
-        let multisampled_framebuffer = device
-            .create_texture(multisampled_frame_descriptor)
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let multisampled_framebuffer = if sample_count > 1 {
+            Some(
+                device
+                    .create_texture(multisampled_frame_descriptor)
+                    .create_view(&wgpu::TextureViewDescriptor::default()),
+            )
+        } else {
+            None
+        };
 
         let depth_stencil_descriptor = &wgpu::TextureDescriptor {
             label: None,
@@ -2247,7 +2903,7 @@ impl WindowSizeDependent {
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
-            sample_count: SAMPLE_COUNT,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Depth32Float,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -2290,8 +2946,135 @@ struct AnimatedUniform {
     transform: [[f32; 4]; 4],
     joint_transforms: JointTransforms,
     frame_count: f32,
+    /// 0.0 to 1.0, how white the model is tinted
+    flash: f32,
+}
+
+/// Height of each players HUD at a scale of 1.0
+const HUD_HEIGHT: f32 = 205.0;
+
+/// Distance between the top of a fighters ECB and the bottom of its player indicator, in world units
+const INDICATOR_HEIGHT: f32 = 4.0;
+
+/// Hitboxes in the colbox overlay are at their reddest at this damage
+const OVERLAY_MAX_DAMAGE: f32 = 20.0;
+/// Opacity of hurtboxes in the colbox overlay while the entity is intangible
+const OVERLAY_INTANGIBLE_ALPHA: f32 = 0.25;
+
+/// Colors hitboxes in the colbox overlay from yellow to red as their damage rises
+fn overlay_hitbox_color(damage: f32) -> [f32; 4] {
+    let amount = (damage / OVERLAY_MAX_DAMAGE).clamp(0.0, 1.0);
+    [1.0, 1.0 - amount, 0.0, 1.0]
+}
+
+/// Fraction of the remaining difference the displayed damage rolls up by each frame
+const DAMAGE_ROLL_RATE: f32 = 0.2;
+const DAMAGE_ROLL_MIN_STEP: f32 = 0.5;
+
+/// Damage at which the percent is at its reddest
+const DAMAGE_RED_PERCENT: f32 = 150.0;
+/// How far the percent is blended from the players color to red, the players color stays visible so players can be told apart
+const DAMAGE_RED_MAX: f32 = 0.75;
+
+/// Rolls the displayed damage up towards `damage`, dropping immediately when the damage is lowered e.g. on respawn
+fn roll_damage(shown: &mut f32, damage: f32) -> f32 {
+    if damage <= *shown {
+        *shown = damage;
+    } else {
+        let step = ((damage - *shown) * DAMAGE_ROLL_RATE).max(DAMAGE_ROLL_MIN_STEP);
+        *shown = (*shown + step).min(damage);
+    }
+    *shown
+}
+
+/// Blends the players color towards red as their damage rises
+fn damage_color(color: [f32; 4], damage: f32) -> [f32; 4] {
+    let red = [1.0, 0.1, 0.1];
+    let amount = (damage / DAMAGE_RED_PERCENT).min(1.0) * DAMAGE_RED_MAX;
+    [
+        color[0] + (red[0] - color[0]) * amount,
+        color[1] + (red[1] - color[1]) * amount,
+        color[2] + (red[2] - color[2]) * amount,
+        color[3],
+    ]
+}
+
+/// Height above the floor at which an entity no longer casts a shadow
+const SHADOW_MAX_HEIGHT: f32 = 80.0;
+
+/// A dark ellipse lying on the highest floor beneath the entity, it shrinks and fades as the entity rises above the floor.
+/// Helps players judge where they will land.
+fn blob_shadow(surfaces: &[stage::Surface], frame: &RenderEntityFrame) -> Option<ColorInstance> {
+    let (x, y) = frame.frame_bps;
+    let (floor, floor_y) = surfaces
+        .iter()
+        .filter(|surface| surface.floor.is_some() && surface.world_x_in_bounds(x))
+        .map(|surface| (surface, surface.world_x_to_world_y(x)))
+        // grounded entities can be slightly below the floor due to floating point error
+        .filter(|(_, floor_y)| *floor_y <= y + 0.1)
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())?;
+
+    let strength = 1.0 - (y - floor_y) / SHADOW_MAX_HEIGHT;
+    if strength <= 0.0 {
+        return None;
+    }
+    let width = frame
+        .ecb
+        .as_ref()
+        .map_or(8.0, |ecb| (ecb.right - ecb.left).abs());
+    let radius = width * (0.4 + 0.4 * strength);
+
+    // lift it slightly off the floor so it isnt hidden by the stage model
+    let position = Matrix4::from_translation(Vector3::new(x, floor_y + 0.1, frame.render_bps.2));
+    let angle = Matrix4::from_angle_z(Rad(floor.floor_angle().unwrap_or(0.0)));
+    let lie_flat = Matrix4::from_angle_x(Rad::turn_div_4());
+    let size = Matrix4::from_nonuniform_scale(radius, radius * 0.5, 1.0);
+    Some(ColorInstance {
+        transform: (position * angle * lie_flat * size).into(),
+        color: [0.0, 0.0, 0.0, 0.5 * strength],
+    })
+}
+
+/// The HUD icon of a status effect and its color
+fn status_effect_icon(kind: StatusEffectKind) -> (&'static str, [f32; 3]) {
+    match kind {
+        StatusEffectKind::Poison => ("☠", [0.6, 0.2, 0.8]),
+        StatusEffectKind::Slow => ("▼", [0.3, 0.5, 1.0]),
+        StatusEffectKind::Haste => ("▲", [1.0, 0.9, 0.2]),
+        StatusEffectKind::Heavy => ("↓", [0.6, 0.6, 0.6]),
+        StatusEffectKind::Floaty => ("↑", [0.6, 1.0, 1.0]),
+        StatusEffectKind::Armor => ("◆", [0.9, 0.9, 0.9]),
+    }
+}
+
+type JointTransforms = [[[f32; 4]; 4]; MAX_JOINTS];
+
+/// Joint transforms in the uniform of animated models
+const MAX_JOINTS: usize = 500;
+/// Joint transforms used when the adapter cannot bind a uniform large enough for MAX_JOINTS.
+/// Keeps the uniform within the 16384 bytes every adapter supports.
+const SAFE_MAX_JOINTS: usize = 250;
+
+/// Size of an `AnimatedUniform` with only the first `max_joints` joint transforms
+fn animated_uniform_size(max_joints: usize) -> usize {
+    mem::size_of::<AnimatedUniform>() - (MAX_JOINTS - max_joints) * mem::size_of::<[[f32; 4]; 4]>()
+}
+
+impl AnimatedUniform {
+    /// The uniform as laid out by shaders compiled with `max_joints` joint transforms
+    fn bytes(&self, max_joints: usize) -> Cow<[u8]> {
+        if max_joints == MAX_JOINTS {
+            Cow::Borrowed(bytemuck::bytes_of(self))
+        } else {
+            let mut bytes = Vec::with_capacity(animated_uniform_size(max_joints));
+            bytes.extend_from_slice(bytemuck::bytes_of(&self.transform));
+            bytes.extend_from_slice(bytemuck::cast_slice(&self.joint_transforms[..max_joints]));
+            bytes.extend_from_slice(bytemuck::bytes_of(&self.frame_count));
+            bytes.extend_from_slice(bytemuck::bytes_of(&self.flash));
+            Cow::Owned(bytes)
+        }
+    }
 }
-type JointTransforms = [[[f32; 4]; 4]; 500];
 
 unsafe impl Pod for AnimatedUniform {}
 unsafe impl Zeroable for AnimatedUniform {}
@@ -2307,6 +3090,12 @@ enum DrawType {
         debug: bool,
         dimension3: bool,
     },
+    /// Draws the mesh once for each instance
+    ColorInstanced {
+        uniform: TransformUniform,
+        instances: Buffer,
+        instance_count: u32,
+    },
     Hitbox {
         uniform: HitboxUniform,
     },
@@ -2329,30 +3118,32 @@ enum DrawType {
 }
 
 impl DrawType {
-    fn uniform_bytes(&self) -> &[u8] {
+    fn uniform_bytes(&self, max_joints: usize) -> Cow<[u8]> {
         match &self {
-            DrawType::Color { uniform, .. } => bytemuck::bytes_of(uniform),
-            DrawType::Hitbox { uniform, .. } => bytemuck::bytes_of(uniform),
-            DrawType::ModelStatic { uniform, .. } => bytemuck::bytes_of(uniform),
-            DrawType::ModelAnimated { uniform, .. } => bytemuck::bytes_of(uniform),
-            DrawType::Fireball { uniform, .. } => bytemuck::bytes_of(uniform),
-            DrawType::Lava { uniform, .. } => bytemuck::bytes_of(uniform),
+            DrawType::Color { uniform, .. } => Cow::Borrowed(bytemuck::bytes_of(uniform)),
+            DrawType::ColorInstanced { uniform, .. } => Cow::Borrowed(bytemuck::bytes_of(uniform)),
+            DrawType::Hitbox { uniform, .. } => Cow::Borrowed(bytemuck::bytes_of(uniform)),
+            DrawType::ModelStatic { uniform, .. } => Cow::Borrowed(bytemuck::bytes_of(uniform)),
+            DrawType::ModelAnimated { uniform, .. } => uniform.bytes(max_joints),
+            DrawType::Fireball { uniform, .. } => uniform.bytes(max_joints),
+            DrawType::Lava { uniform, .. } => Cow::Borrowed(bytemuck::bytes_of(uniform)),
         }
     }
 
-    fn uniform_size(&self) -> usize {
+    fn uniform_size(&self, max_joints: usize) -> usize {
         match &self {
             DrawType::Color { .. } => mem::size_of::<TransformUniform>(),
+            DrawType::ColorInstanced { .. } => mem::size_of::<TransformUniform>(),
             DrawType::Hitbox { .. } => mem::size_of::<HitboxUniform>(),
-            DrawType::ModelAnimated { .. } => mem::size_of::<AnimatedUniform>(),
-            DrawType::Fireball { .. } => mem::size_of::<AnimatedUniform>(),
+            DrawType::ModelAnimated { .. } => animated_uniform_size(max_joints),
+            DrawType::Fireball { .. } => animated_uniform_size(max_joints),
             DrawType::ModelStatic { .. } => mem::size_of::<TransformUniform>(),
             DrawType::Lava { .. } => mem::size_of::<TransformUniformCycle>(),
         }
     }
 
-    fn uniform_size_padded(&self) -> usize {
-        let unpadded_size = self.uniform_size();
+    fn uniform_size_padded(&self, max_joints: usize) -> usize {
+        let unpadded_size = self.uniform_size(max_joints);
         let align = 256;
         let padding = (align - unpadded_size % align) % align;
         unpadded_size + padding
@@ -2392,3 +3183,67 @@ impl StagingBelt {
         self.local_pool.run_until_stalled();
     }
 }
+
+/// A glyph brush that takes text colors in sRGB like the rest of the renderer.
+/// When the surface encodes colors to sRGB the colors are decoded to linear first, matching `output_color` in the shaders.
+struct TextBrush {
+    glyph_brush: GlyphBrush<()>,
+    srgb_output: bool,
+}
+
+impl TextBrush {
+    fn queue(&mut self, mut section: Section) {
+        if self.srgb_output {
+            for text in section.text.iter_mut() {
+                for channel in text.extra.color[..3].iter_mut() {
+                    *channel = srgb_to_linear(*channel);
+                }
+            }
+        }
+        self.glyph_brush.queue(section);
+    }
+
+    fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) -> Result<(), String> {
+        self.glyph_brush
+            .draw_queued(device, staging_belt, encoder, target, width, height)
+    }
+}
+
+fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Builds a glyph brush that renders with `font` by default, or the builtin font when None
+fn build_glyph_brush(
+    device: &Device,
+    surface_format: TextureFormat,
+    font: Option<FontArc>,
+) -> (TextBrush, FontId) {
+    let font = font.unwrap_or_else(|| {
+        FontArc::try_from_slice(include_bytes!("../fonts/DejaVuSans.ttf")).unwrap()
+    });
+    let hack = FontArc::try_from_slice(include_bytes!("../fonts/Hack-Regular.ttf")).unwrap();
+
+    let mut glyph_brush_builder = GlyphBrushBuilder::using_font(font);
+    let hack_font_id = glyph_brush_builder.add_font(hack);
+    let glyph_brush = glyph_brush_builder
+        .initial_cache_size((512, 512))
+        .build(device, surface_format);
+    let text_brush = TextBrush {
+        glyph_brush,
+        srgb_output: surface_format.describe().srgb,
+    };
+    (text_brush, hack_font_id)
+}
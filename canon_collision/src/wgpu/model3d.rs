@@ -1,3 +1,4 @@
+use crate::action_lengths;
 use crate::game::{RenderGame, RenderObject};
 use crate::menu::{RenderMenu, RenderMenuState};
 use crate::wgpu::buffers::Buffers;
@@ -27,32 +28,78 @@ pub struct Models {
     assets: Assets,
     models: HashMap<String, Model3D>,
     stage_model_name: Option<String>,
+    /// The device can sample BCn textures, so ktx2 textures in the assets folder replace the embedded textures
+    compressed_textures: bool,
 }
 
 impl Models {
-    pub fn new() -> Self {
+    pub fn new(compressed_textures: bool) -> Self {
         Models {
             assets: Assets::new().unwrap(),
             models: HashMap::new(),
             stage_model_name: None,
+            compressed_textures,
         }
     }
 
+    /// The assets to load ktx2 textures from, None when they cannot be used
+    fn texture_assets(assets: &Assets, compressed_textures: bool) -> Option<&Assets> {
+        if compressed_textures {
+            Some(assets)
+        } else {
+            None
+        }
+    }
+
+    /// Fonts are loaded from the same assets directory as the models
+    pub fn get_font(&self, file_name: &str) -> Option<Vec<u8>> {
+        self.assets.get_font(file_name)
+    }
+
     pub fn get(&self, key: &str) -> Option<&Model3D> {
         self.models.get(&key.replace(' ', ""))
     }
 
-    pub fn load_game(&mut self, device: &Device, queue: &Queue, render: &RenderGame) {
-        // hotreload current models
+    /// Returns the model with the textures of the costume applied, `costume_textures` is None for the default textures
+    pub fn get_costume(
+        &self,
+        model_name: &str,
+        costume_textures: Option<&str>,
+    ) -> Option<&Model3D> {
+        self.models.get(&Models::costume_key(
+            &model_name.replace(' ', ""),
+            costume_textures,
+        ))
+    }
+
+    /// Each costume is loaded as a separate model, so that players using different costumes of the same model can be drawn
+    fn costume_key(model_name: &str, costume_textures: Option<&str>) -> String {
+        match costume_textures {
+            Some(costume_textures) => format!("{}#{}", model_name, costume_textures),
+            None => model_name.to_string(),
+        }
+    }
+
+    /// Reloads every model in memory that has changed on disk, including each costume of the model
+    fn hot_reload(&mut self, device: &Device, queue: &Queue) {
         for reload in self.assets.models_reloads() {
-            // only reload if its still in memory
-            if self.models.contains_key(&reload.name) {
-                self.models.insert(
-                    reload.name.clone(),
-                    Model3D::from_gltf(device, queue, &reload.data),
-                );
+            let keys: Vec<String> = self
+                .models
+                .keys()
+                .filter(|key| key.split('#').next() == Some(reload.name.as_str()))
+                .cloned()
+                .collect();
+            for key in keys {
+                let textures = Models::texture_assets(&self.assets, self.compressed_textures);
+                let costume = key.split_once('#').map(|x| (&self.assets, x.1));
+                let model = Model3D::from_gltf(device, queue, &reload.data, textures, costume);
+                self.models.insert(key, model);
             }
         }
+    }
+
+    pub fn load_game(&mut self, device: &Device, queue: &Queue, render: &RenderGame) {
+        self.hot_reload(device, queue);
 
         // load current stage
         // if a new stage is used, unload old stage and load new stage
@@ -70,8 +117,9 @@ impl Models {
         for entity in render.entities.iter() {
             if let RenderObject::Entity(entity) = entity {
                 let fighter_model_name = entity.frames[0].model_name.replace(' ', "");
+                let costume_textures = entity.frames[0].costume_textures.as_deref();
                 // TODO: Dont reload every frame if the model doesnt exist, probs just do another hashmap
-                self.load_fighter(device, queue, fighter_model_name);
+                self.load_fighter(device, queue, fighter_model_name, costume_textures);
             }
         }
     }
@@ -84,27 +132,28 @@ impl Models {
         queue: &Queue,
         render: &RenderMenu,
         fighters: &[(String, &EntityDef)],
+        css_background: Option<&str>,
     ) {
-        // hotreload current models
-        for reload in self.assets.models_reloads() {
-            // only reload if its still in memory
-            if self.models.contains_key(&reload.name) {
-                self.models.insert(
-                    reload.name.clone(),
-                    Model3D::from_gltf(device, queue, &reload.data),
-                );
-            }
-        }
+        self.hot_reload(device, queue);
 
         // load selected fighters
         match &render.state {
             RenderMenuState::CharacterSelect(selections, _, _) => {
+                if let Some(background) = css_background {
+                    // any model loads the same way as a fighter
+                    self.load_fighter(device, queue, background.replace(' ', ""), None);
+                }
                 for selection in selections {
                     if let Some(index) = selection.fighter {
                         let fighter = fighters[index].1;
-                        let fighter_model_name = fighter.name.replace(' ', "");
+                        let costume = fighter.fighter().and_then(|x| x.costume(selection.costume));
+                        let fighter_model_name = costume
+                            .and_then(|x| x.model.as_ref())
+                            .unwrap_or(&fighter.name)
+                            .replace(' ', "");
+                        let costume_textures = costume.map(|x| x.textures.as_str());
                         // TODO: Dont reload every frame if the model doesnt exist, probs just do another hashmap
-                        self.load_fighter(device, queue, fighter_model_name);
+                        self.load_fighter(device, queue, fighter_model_name, costume_textures);
                     }
                 }
             }
@@ -112,12 +161,21 @@ impl Models {
         }
     }
 
-    fn load_fighter(&mut self, device: &Device, queue: &Queue, model_name: String) {
-        if !self.models.contains_key(&model_name) {
+    fn load_fighter(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        model_name: String,
+        costume_textures: Option<&str>,
+    ) {
+        let key = Models::costume_key(&model_name, costume_textures);
+        if !self.models.contains_key(&key) {
             if let Some(data) = self.assets.get_model(&model_name) {
+                let textures = Models::texture_assets(&self.assets, self.compressed_textures);
+                let costume = costume_textures.map(|x| (&self.assets, x));
                 self.models.insert(
-                    model_name.to_string(),
-                    Model3D::from_gltf(device, queue, &data),
+                    key,
+                    Model3D::from_gltf(device, queue, &data, textures, costume),
                 );
             }
         }
@@ -125,8 +183,11 @@ impl Models {
 
     fn load_stage(&mut self, device: &Device, queue: &Queue, new_name: String) {
         if let Some(data) = self.assets.get_model(&new_name) {
-            self.models
-                .insert(new_name.clone(), Model3D::from_gltf(device, queue, &data));
+            let textures = Models::texture_assets(&self.assets, self.compressed_textures);
+            self.models.insert(
+                new_name.clone(),
+                Model3D::from_gltf(device, queue, &data, textures, None),
+            );
         }
         self.stage_model_name = Some(new_name);
     }
@@ -182,6 +243,18 @@ pub struct Animation {
     pub channels: Vec<Channel>,
 }
 
+impl Animation {
+    /// The number of frames until the last keyframe of any channel
+    pub fn len(&self) -> usize {
+        let last_input = self
+            .channels
+            .iter()
+            .filter_map(|x| x.inputs.last())
+            .fold(0.0, |a: f32, b| a.max(*b));
+        action_lengths::frames_from_seconds(last_input)
+    }
+}
+
 pub struct Channel {
     pub target_node_index: usize,
     pub inputs: Vec<f32>,
@@ -221,14 +294,61 @@ impl Joint {
 }
 
 impl Model3D {
-    pub fn from_gltf(device: &Device, queue: &Queue, data: &[u8]) -> Model3D {
+    /// Textures named after a ktx2 file in `texture_assets` are loaded from that file instead of the glb.
+    /// Textures named after a png file in the folder of the costume take priority over both.
+    pub fn from_gltf(
+        device: &Device,
+        queue: &Queue,
+        data: &[u8],
+        texture_assets: Option<&Assets>,
+        costume: Option<(&Assets, &str)>,
+    ) -> Model3D {
         let gltf = Gltf::from_slice(data).unwrap();
         let blob = gltf.blob.as_ref().unwrap();
         let scene = gltf.default_scene().unwrap();
 
         let mut textures = vec![];
         for texture in gltf.textures() {
-            match texture.source().source() {
+            let image = texture.source();
+            let costume_texture = match (costume, image.name()) {
+                (Some((assets, costume)), Some(name)) => {
+                    assets.get_costume_texture(costume, name).and_then(|data| {
+                        texture_from_png(device, queue, &data)
+                            .map_err(|err| {
+                                error!(
+                                    "Failed to load '{}/{}.png', using the model's texture instead: {}",
+                                    costume, name, err
+                                )
+                            })
+                            .ok()
+                    })
+                }
+                _ => None,
+            };
+            if let Some(texture) = costume_texture {
+                textures.push(Rc::new(texture));
+                continue;
+            }
+
+            let compressed = match (texture_assets, image.name()) {
+                (Some(assets), Some(name)) => assets.get_texture(name).and_then(|data| {
+                    texture_from_ktx2(device, queue, &data)
+                        .map_err(|err| {
+                            error!(
+                                "Failed to load '{}.ktx2', using the embedded texture instead: {}",
+                                name, err
+                            )
+                        })
+                        .ok()
+                }),
+                _ => None,
+            };
+            if let Some(texture) = compressed {
+                textures.push(Rc::new(texture));
+                continue;
+            }
+
+            match image.source() {
                 ImageSource::View { view, mime_type } => {
                     assert!(
                         view.stride().is_none(),
@@ -239,55 +359,8 @@ impl Model3D {
                         "It is assumed that gltf texture mime_type is image/png."
                     );
 
-                    // read png data
                     let slice = &blob[view.offset()..view.offset() + view.length() - 1];
-                    let png = png::decode_no_check(slice).unwrap();
-                    let data = match png.color_type {
-                        PNGColorType::RGB => {
-                            let mut data = Vec::with_capacity(png.data.len() * 2);
-                            for bytes in png.data.chunks(3) {
-                                data.extend(bytes);
-                                data.push(0xFF);
-                            }
-                            data
-                        }
-                        PNGColorType::RGBA => png.data,
-                        _ => unimplemented!(
-                            "It is assumed that gltf png textures are in RGB or RGBA format."
-                        ),
-                    };
-                    assert_eq!(data.len(), png.width * png.height * 4);
-
-                    // create buffer and texture
-                    let size = wgpu::Extent3d {
-                        width: png.width as u32,
-                        height: png.height as u32,
-                        depth_or_array_layers: 1,
-                    };
-                    let texture = device.create_texture(&wgpu::TextureDescriptor {
-                        label: None,
-                        size,
-                        mip_level_count: 1,
-                        sample_count: 1,
-                        dimension: wgpu::TextureDimension::D2,
-                        format: wgpu::TextureFormat::Rgba8Unorm,
-                        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                    });
-
-                    // copy buffer to texture
-                    let texture_copy_view = wgpu::ImageCopyTextureBase {
-                        texture: &texture,
-                        mip_level: 0,
-                        origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
-                        aspect: wgpu::TextureAspect::All,
-                    };
-                    let texture_data_layout = wgpu::ImageDataLayout {
-                        offset: 0,
-                        bytes_per_row: NonZeroU32::new(png.width as u32 * 4),
-                        rows_per_image: None,
-                    };
-                    queue.write_texture(texture_copy_view, &data, texture_data_layout, size);
-
+                    let texture = texture_from_png(device, queue, slice).unwrap();
                     textures.push(Rc::new(texture));
                 }
                 _ => {
@@ -581,3 +654,170 @@ impl Model3D {
         }
     }
 }
+
+/// Returns the RGBA8 image followed by every smaller mip level down to 1x1, as (width, height, data).
+/// Each level averages 2x2 texels of the previous level, the last row or column is reused for odd sizes.
+fn generate_mipmaps(width: u32, height: u32, data: Vec<u8>) -> Vec<(u32, u32, Vec<u8>)> {
+    let mut levels = vec![(width, height, data)];
+    loop {
+        let (width, height, data) = levels.last().unwrap();
+        let (width, height) = (*width as usize, *height as usize);
+        if width == 1 && height == 1 {
+            return levels;
+        }
+
+        let next_width = (width / 2).max(1);
+        let next_height = (height / 2).max(1);
+        let mut next = Vec::with_capacity(next_width * next_height * 4);
+        for y in 0..next_height {
+            let y0 = (y * 2).min(height - 1);
+            let y1 = (y * 2 + 1).min(height - 1);
+            for x in 0..next_width {
+                let x0 = (x * 2).min(width - 1);
+                let x1 = (x * 2 + 1).min(width - 1);
+                for channel in 0..4 {
+                    let texel = |x: usize, y: usize| data[(y * width + x) * 4 + channel] as u32;
+                    let sum = texel(x0, y0) + texel(x1, y0) + texel(x0, y1) + texel(x1, y1);
+                    next.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        levels.push((next_width as u32, next_height as u32, next));
+    }
+}
+
+/// Creates a 2D texture with generated mip levels from a png in RGB or RGBA format
+fn texture_from_png(device: &Device, queue: &Queue, data: &[u8]) -> Result<Texture, String> {
+    let png = png::decode_no_check(data).map_err(|x| format!("{:?}", x))?;
+    let data = match png.color_type {
+        PNGColorType::RGB => {
+            let mut data = Vec::with_capacity(png.data.len() * 2);
+            for bytes in png.data.chunks(3) {
+                data.extend(bytes);
+                data.push(0xFF);
+            }
+            data
+        }
+        PNGColorType::RGBA => png.data,
+        _ => return Err(String::from("only RGB and RGBA pngs are supported")),
+    };
+    assert_eq!(data.len(), png.width * png.height * 4);
+
+    let mipmaps = generate_mipmaps(png.width as u32, png.height as u32, data);
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width: png.width as u32,
+            height: png.height as u32,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: mipmaps.len() as u32,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+
+    // copy each mip level to the texture
+    for (level, (width, height, data)) in mipmaps.iter().enumerate() {
+        write_mip_level(
+            queue,
+            &texture,
+            level as u32,
+            data,
+            (*width, *height),
+            wgpu::TextureFormat::Rgba8Unorm,
+        );
+    }
+    Ok(texture)
+}
+
+/// Creates a 2D texture from a ktx2 file containing uncompressed RGBA8 or BCn data, along with the mip levels it contains.
+/// Supercompressed files are not supported.
+fn texture_from_ktx2(device: &Device, queue: &Queue, data: &[u8]) -> Result<Texture, String> {
+    let reader = ktx2::Reader::new(data).map_err(|err| err.to_string())?;
+    let header = reader.header();
+    if header.supercompression_scheme.is_some() {
+        return Err(String::from("supercompression is not supported"));
+    }
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count != 1 {
+        return Err(String::from("only 2D textures are supported"));
+    }
+    // The shaders decode sRGB texels themselves when the surface is sRGB, like they do for png textures.
+    // So sRGB textures are uploaded as unorm, otherwise the sampler would decode them a second time.
+    let format = match header.format {
+        Some(ktx2::Format::R8G8B8A8_UNORM | ktx2::Format::R8G8B8A8_SRGB) => {
+            wgpu::TextureFormat::Rgba8Unorm
+        }
+        Some(ktx2::Format::BC1_RGBA_UNORM_BLOCK | ktx2::Format::BC1_RGBA_SRGB_BLOCK) => {
+            wgpu::TextureFormat::Bc1RgbaUnorm
+        }
+        Some(ktx2::Format::BC3_UNORM_BLOCK | ktx2::Format::BC3_SRGB_BLOCK) => {
+            wgpu::TextureFormat::Bc3RgbaUnorm
+        }
+        Some(ktx2::Format::BC4_UNORM_BLOCK) => wgpu::TextureFormat::Bc4RUnorm,
+        Some(ktx2::Format::BC5_UNORM_BLOCK) => wgpu::TextureFormat::Bc5RgUnorm,
+        Some(ktx2::Format::BC7_UNORM_BLOCK | ktx2::Format::BC7_SRGB_BLOCK) => {
+            wgpu::TextureFormat::Bc7RgbaUnorm
+        }
+        format => return Err(format!("format {:?} is not supported", format)),
+    };
+
+    let width = header.pixel_width;
+    let height = header.pixel_height.max(1);
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: header.level_count.max(1),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+    for (level, data) in reader.levels().enumerate() {
+        let level = level as u32;
+        let size = ((width >> level).max(1), (height >> level).max(1));
+        write_mip_level(queue, &texture, level, data, size, format);
+    }
+    Ok(texture)
+}
+
+/// Writes a mip level of `texture`, `size` is in texels and may not be a multiple of the block size of compressed formats
+fn write_mip_level(
+    queue: &Queue,
+    texture: &Texture,
+    level: u32,
+    data: &[u8],
+    (width, height): (u32, u32),
+    format: wgpu::TextureFormat,
+) {
+    let info = format.describe();
+    let block_width = info.block_dimensions.0 as u32;
+    let block_height = info.block_dimensions.1 as u32;
+    let blocks_wide = (width + block_width - 1) / block_width;
+    let blocks_high = (height + block_height - 1) / block_height;
+
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: level,
+            origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(blocks_wide * info.block_size as u32),
+            rows_per_image: None,
+        },
+        wgpu::Extent3d {
+            width: blocks_wide * block_width,
+            height: blocks_high * block_height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
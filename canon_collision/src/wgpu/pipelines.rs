@@ -0,0 +1,374 @@
+use super::buffers::{ColorInstance, ColorVertex, Vertex};
+use super::model3d::{ModelVertexAnimated, ModelVertexStatic};
+use super::shaders::Shader;
+
+use std::collections::HashMap;
+use std::mem;
+
+use wgpu::{
+    BindGroupLayout, Device, PipelineLayout, RenderPipeline, ShaderModule, ShaderSource,
+    TextureFormat,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum PipelineType {
+    Color2D,
+    ColorInstanced,
+    Color3D,
+    Debug,
+    Hitbox,
+    Model3DStatic,
+    Model3DStaticLava,
+    Model3DAnimated,
+    Model3DFireball,
+}
+
+impl PipelineType {
+    #[cfg(feature = "hot_reload_shaders")]
+    const ALL: [PipelineType; 9] = [
+        PipelineType::Color2D,
+        PipelineType::ColorInstanced,
+        PipelineType::Color3D,
+        PipelineType::Debug,
+        PipelineType::Hitbox,
+        PipelineType::Model3DStatic,
+        PipelineType::Model3DStaticLava,
+        PipelineType::Model3DAnimated,
+        PipelineType::Model3DFireball,
+    ];
+
+    /// The vertex and fragment shaders used by the pipeline
+    fn shaders(self) -> (Shader, Shader) {
+        match self {
+            PipelineType::Color2D | PipelineType::Color3D | PipelineType::Debug => {
+                (Shader::Color, Shader::Color)
+            }
+            PipelineType::ColorInstanced => (Shader::ColorInstanced, Shader::ColorInstanced),
+            PipelineType::Hitbox => (Shader::Hitbox, Shader::Hitbox),
+            PipelineType::Model3DStatic => {
+                (Shader::Model3DStaticVertex, Shader::Model3DStandardFragment)
+            }
+            PipelineType::Model3DStaticLava => {
+                (Shader::Model3DStaticVertex, Shader::Model3DLavaFragment)
+            }
+            PipelineType::Model3DAnimated => (
+                Shader::Model3DAnimatedVertex,
+                Shader::Model3DStandardFragment,
+            ),
+            PipelineType::Model3DFireball => (
+                Shader::Model3DFireballVertex,
+                Shader::Model3DStandardFragment,
+            ),
+        }
+    }
+}
+
+/// Everything a pipeline is created from, kept around so pipelines can be recreated when their shaders are hot reloaded
+struct PipelineSources {
+    layout_generic: PipelineLayout,
+    layout_model3d: PipelineLayout,
+    modules: HashMap<Shader, ShaderModule>,
+    sample_count: u32,
+    surface_format: TextureFormat,
+}
+
+impl PipelineSources {
+    fn create(&self, device: &Device, ty: PipelineType) -> RenderPipeline {
+        let primitive_back_face_culling = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        };
+
+        let primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            ..Default::default()
+        };
+
+        let targets = [wgpu::ColorTargetState {
+            format: self.surface_format,
+            blend: Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            write_mask: wgpu::ColorWrites::ALL,
+        }];
+        let depth_stencil = Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        });
+
+        let depth_stencil_disable = Some(wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: Default::default(),
+            bias: Default::default(),
+        });
+
+        let multisample = wgpu::MultisampleState {
+            count: self.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        };
+
+        let color_buffers = [wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ColorVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![
+                0 => Float32x4, // position
+                1 => Float32x4  // color
+            ],
+        }];
+
+        let color_instanced_buffers = [
+            color_buffers[0].clone(),
+            wgpu::VertexBufferLayout {
+                array_stride: mem::size_of::<ColorInstance>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![
+                    2 => Float32x4, // transform
+                    3 => Float32x4,
+                    4 => Float32x4,
+                    5 => Float32x4,
+                    6 => Float32x4  // color
+                ],
+            },
+        ];
+
+        let hitbox_buffers = [wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![
+                0 => Float32x2, // position
+                1 => Float32,   // edge
+                2 => Uint32     // render_id
+            ],
+        }];
+
+        let model3d_static_buffers = [wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ModelVertexStatic>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![
+                0 => Float32x4, // position
+                1 => Float32x2  // uv
+            ],
+        }];
+
+        let model3d_animated_buffers = [wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<ModelVertexAnimated>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![
+                0 => Float32x4, // position
+                1 => Float32x2, // uv
+                2 => Uint32x4,  // joints
+                3 => Float32x4  // weights
+            ],
+        }];
+
+        let (layout, buffers, primitive, depth_stencil) = match ty {
+            PipelineType::Color2D => (
+                &self.layout_generic,
+                &color_buffers[..],
+                primitive,
+                depth_stencil,
+            ),
+            PipelineType::ColorInstanced => (
+                &self.layout_generic,
+                &color_instanced_buffers[..],
+                primitive,
+                depth_stencil,
+            ),
+            PipelineType::Color3D => (
+                &self.layout_generic,
+                &color_buffers[..],
+                primitive_back_face_culling,
+                depth_stencil,
+            ),
+            PipelineType::Debug => (
+                &self.layout_generic,
+                &color_buffers[..],
+                primitive,
+                depth_stencil_disable,
+            ),
+            PipelineType::Hitbox => (
+                &self.layout_generic,
+                &hitbox_buffers[..],
+                primitive,
+                depth_stencil_disable,
+            ),
+            PipelineType::Model3DStatic | PipelineType::Model3DStaticLava => (
+                &self.layout_model3d,
+                &model3d_static_buffers[..],
+                primitive_back_face_culling,
+                depth_stencil,
+            ),
+            PipelineType::Model3DAnimated | PipelineType::Model3DFireball => (
+                &self.layout_model3d,
+                &model3d_animated_buffers[..],
+                primitive_back_face_culling,
+                depth_stencil,
+            ),
+        };
+
+        let (vertex_shader, fragment_shader) = ty.shaders();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &self.modules[&vertex_shader],
+                entry_point: vertex_shader.vertex_entry_point(),
+                buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.modules[&fragment_shader],
+                entry_point: fragment_shader.fragment_entry_point(),
+                targets: &targets,
+            }),
+            primitive,
+            depth_stencil,
+            multisample,
+        })
+    }
+}
+
+/// Every render pipeline used by the renderer
+pub struct Pipelines {
+    #[cfg(feature = "hot_reload_shaders")]
+    sources: PipelineSources,
+    #[cfg(feature = "hot_reload_shaders")]
+    max_joints: usize,
+    #[cfg(feature = "hot_reload_shaders")]
+    srgb_output: bool,
+    pub color_2d: RenderPipeline,
+    /// Draws many instances of a `ColorVertex` mesh, otherwise identical to `color_2d`
+    pub color_instanced: RenderPipeline,
+    pub color_3d: RenderPipeline,
+    pub debug: RenderPipeline,
+    pub hitbox: RenderPipeline,
+    pub model3d_static: RenderPipeline,
+    pub model3d_static_lava: RenderPipeline,
+    pub model3d_animated: RenderPipeline,
+    pub model3d_fireball: RenderPipeline,
+}
+
+impl Pipelines {
+    pub fn new(
+        device: &Device,
+        bind_group_layout_generic: &BindGroupLayout,
+        bind_group_layout_model3d: &BindGroupLayout,
+        sample_count: u32,
+        max_joints: usize,
+        surface_format: TextureFormat,
+    ) -> Pipelines {
+        let srgb_output = surface_format.describe().srgb;
+        let layout_generic = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[bind_group_layout_generic],
+            push_constant_ranges: &[],
+        });
+        let layout_model3d = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[bind_group_layout_model3d],
+            push_constant_ranges: &[],
+        });
+
+        // TODO: wgsl cant even handle the multiply yet, so the model3d shaders are still glsl.
+        //let model3d_module = Pipelines::create_module(device, ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/model3d.wgsl"))));
+        let modules = Shader::ALL
+            .iter()
+            .map(|x| {
+                let source = x.embedded(max_joints, srgb_output);
+                (*x, Pipelines::create_module(device, source))
+            })
+            .collect();
+
+        let sources = PipelineSources {
+            layout_generic,
+            layout_model3d,
+            modules,
+            sample_count,
+            surface_format,
+        };
+
+        Pipelines {
+            color_2d: sources.create(device, PipelineType::Color2D),
+            color_instanced: sources.create(device, PipelineType::ColorInstanced),
+            color_3d: sources.create(device, PipelineType::Color3D),
+            debug: sources.create(device, PipelineType::Debug),
+            hitbox: sources.create(device, PipelineType::Hitbox),
+            model3d_static: sources.create(device, PipelineType::Model3DStatic),
+            model3d_static_lava: sources.create(device, PipelineType::Model3DStaticLava),
+            model3d_animated: sources.create(device, PipelineType::Model3DAnimated),
+            model3d_fireball: sources.create(device, PipelineType::Model3DFireball),
+            #[cfg(feature = "hot_reload_shaders")]
+            sources,
+            #[cfg(feature = "hot_reload_shaders")]
+            max_joints,
+            #[cfg(feature = "hot_reload_shaders")]
+            srgb_output,
+        }
+    }
+
+    fn create_module(device: &Device, source: ShaderSource) -> ShaderModule {
+        device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: None,
+            source,
+        })
+    }
+
+    /// Reloads the shaders from disk and recreates the pipelines that use them.
+    /// A shader that fails to compile is logged and its pipelines are left as they were.
+    #[cfg(feature = "hot_reload_shaders")]
+    pub fn reload(&mut self, device: &Device, shaders: &[Shader]) {
+        let mut reloaded = vec![];
+        for shader in shaders {
+            match shader.load(self.max_joints, self.srgb_output) {
+                Ok(source) => {
+                    let module = Pipelines::create_module(device, source);
+                    self.sources.modules.insert(*shader, module);
+                    reloaded.push(*shader);
+                    info!("Reloaded shader {:?}", shader);
+                }
+                Err(err) => error!("Failed to reload shader {:?}:\n{}", shader, err),
+            }
+        }
+
+        for ty in PipelineType::ALL {
+            let (vertex_shader, fragment_shader) = ty.shaders();
+            if reloaded.contains(&vertex_shader) || reloaded.contains(&fragment_shader) {
+                let pipeline = self.sources.create(device, ty);
+                *self.get_mut(ty) = pipeline;
+            }
+        }
+    }
+
+    #[cfg(feature = "hot_reload_shaders")]
+    fn get_mut(&mut self, ty: PipelineType) -> &mut RenderPipeline {
+        match ty {
+            PipelineType::Color2D => &mut self.color_2d,
+            PipelineType::ColorInstanced => &mut self.color_instanced,
+            PipelineType::Color3D => &mut self.color_3d,
+            PipelineType::Debug => &mut self.debug,
+            PipelineType::Hitbox => &mut self.hitbox,
+            PipelineType::Model3DStatic => &mut self.model3d_static,
+            PipelineType::Model3DStaticLava => &mut self.model3d_static_lava,
+            PipelineType::Model3DAnimated => &mut self.model3d_animated,
+            PipelineType::Model3DFireball => &mut self.model3d_fireball,
+        }
+    }
+}
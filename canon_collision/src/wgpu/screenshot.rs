@@ -0,0 +1,241 @@
+use canon_collision_lib::files;
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+use chrono::Local;
+use wgpu::{Buffer, CommandEncoder, Device, Texture, TextureFormat, TextureView};
+
+/// Copies a frame from the gpu so it can be saved to the screenshots folder.
+/// With MSAA, text is drawn directly to the surface, so the command line and fps counter are not included.
+pub struct Screenshot {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    rgba: bool,
+}
+
+impl Screenshot {
+    /// Resolves the multisampled framebuffer into a texture and copies it into a buffer readable by the cpu.
+    pub fn new(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        multisampled_framebuffer: &TextureView,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Screenshot {
+        let texture = capture_texture(device, width, height, format);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // An empty render pass that just resolves the framebuffer into our texture
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: multisampled_framebuffer,
+                resolve_target: Some(&view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+            label: None,
+        });
+
+        Screenshot::from_texture(device, encoder, &texture, width, height, format)
+    }
+
+    /// Copies a texture created by `capture_texture` into a buffer readable by the cpu.
+    /// Used without MSAA, where the frame is rendered to that texture instead of the surface.
+    pub fn from_texture(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Screenshot {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (width * 4 + align - 1) / align * align;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+
+        Screenshot {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            rgba: matches!(
+                format,
+                TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+            ),
+        }
+    }
+
+    /// Saves to the screenshots folder.
+    /// Must only be called after the encoder passed to `Screenshot::new` has been submitted.
+    pub fn save(self, device: &Device) {
+        let (width, height) = (self.width, self.height);
+        let pixels = match self.read_bgra(device) {
+            Some(pixels) => pixels,
+            None => return,
+        };
+
+        let mut path = files::get_path();
+        path.push("screenshots");
+        if let Err(err) = fs::create_dir_all(&path) {
+            error!("Failed to create screenshots directory: {}", err);
+            return;
+        }
+        path.push(format!(
+            "{}.bmp",
+            Local::now().format("%Y-%m-%d_%H-%M-%S%.3f")
+        ));
+
+        match fs::write(&path, bmp(width, height, &pixels)) {
+            Ok(()) => info!("Saved screenshot to {}", path.to_string_lossy()),
+            Err(err) => error!("Failed to save screenshot: {}", err),
+        }
+    }
+
+    /// Saves as a png to `path`, used when exporting replays frame by frame.
+    /// Must only be called after the encoder passed to `Screenshot::new` has been submitted.
+    /// Returns true if the frame was saved.
+    pub fn save_png(self, device: &Device, path: &Path) -> bool {
+        let (width, height) = (self.width, self.height);
+        let mut pixels = match self.read_bgra(device) {
+            Some(pixels) => pixels,
+            None => return false,
+        };
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+
+        match png(path, width, height, &pixels) {
+            Ok(()) => true,
+            Err(err) => {
+                error!(
+                    "Failed to save frame to {}: {}",
+                    path.to_string_lossy(),
+                    err
+                );
+                false
+            }
+        }
+    }
+
+    /// Returns the top to bottom rows of bgra pixels
+    fn read_bgra(self, device: &Device) -> Option<Vec<u8>> {
+        let slice = self.buffer.slice(..);
+        let map = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        if let Err(err) = futures::executor::block_on(map) {
+            error!("Failed to read screenshot from the gpu: {:?}", err);
+            return None;
+        }
+
+        // Bgra8 is also the pixel layout of a 32 bit bmp, so we just need to remove the padding.
+        // Rgba8 surfaces additionally need their red and blue channels swapped.
+        let row_len = self.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_len * self.height as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(self.padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..row_len]);
+        }
+        if self.rgba {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Some(pixels)
+    }
+}
+
+/// A texture the size of the surface that frames can be rendered or resolved to and then copied from
+pub fn capture_texture(device: &Device, width: u32, height: u32, format: TextureFormat) -> Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    })
+}
+
+/// Writes a png file from top to bottom rows of rgba pixels.
+/// Fast compression is used as exporting a replay writes a file every frame.
+fn png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|x| x.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+    let mut writer = encoder.write_header().map_err(|x| x.to_string())?;
+    writer.write_image_data(pixels).map_err(|x| x.to_string())
+}
+
+/// Creates an uncompressed 32 bit bmp file from top to bottom rows of bgra pixels.
+fn bmp(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    const HEADER_LEN: u32 = 14 + 40;
+    let mut bmp = Vec::with_capacity(HEADER_LEN as usize + pixels.len());
+
+    // file header
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&(HEADER_LEN + pixels.len() as u32).to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // reserved
+    bmp.extend_from_slice(&HEADER_LEN.to_le_bytes()); // pixel data offset
+
+    // info header
+    bmp.extend_from_slice(&40u32.to_le_bytes()); // info header size
+    bmp.extend_from_slice(&(width as i32).to_le_bytes());
+    bmp.extend_from_slice(&(-(height as i32)).to_le_bytes()); // negative height means rows are top to bottom
+    bmp.extend_from_slice(&1u16.to_le_bytes()); // planes
+    bmp.extend_from_slice(&32u16.to_le_bytes()); // bits per pixel
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // no compression
+    bmp.extend_from_slice(&(pixels.len() as u32).to_le_bytes());
+    bmp.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI horizontal
+    bmp.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI vertical
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // colors in palette
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // important colors
+
+    bmp.extend_from_slice(pixels);
+    bmp
+}
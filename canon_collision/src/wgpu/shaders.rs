@@ -0,0 +1,278 @@
+use super::MAX_JOINTS;
+
+use std::borrow::Cow;
+#[cfg(feature = "hot_reload_shaders")]
+use std::path::Path;
+#[cfg(feature = "hot_reload_shaders")]
+use std::sync::mpsc::{self, Receiver};
+#[cfg(feature = "hot_reload_shaders")]
+use std::time::Duration;
+
+#[cfg(feature = "hot_reload_shaders")]
+use hotwatch::{Event, Hotwatch};
+use wgpu::ShaderSource;
+
+/// Shaders are only read from here when hot reloading, otherwise they are compiled into the binary
+#[cfg(feature = "hot_reload_shaders")]
+const SHADERS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Shader {
+    Color,
+    ColorInstanced,
+    Hitbox,
+    Model3DStandardFragment,
+    Model3DLavaFragment,
+    Model3DStaticVertex,
+    Model3DAnimatedVertex,
+    Model3DFireballVertex,
+}
+
+impl Shader {
+    pub const ALL: [Shader; 8] = [
+        Shader::Color,
+        Shader::ColorInstanced,
+        Shader::Hitbox,
+        Shader::Model3DStandardFragment,
+        Shader::Model3DLavaFragment,
+        Shader::Model3DStaticVertex,
+        Shader::Model3DAnimatedVertex,
+        Shader::Model3DFireballVertex,
+    ];
+
+    #[cfg(feature = "hot_reload_shaders")]
+    fn file_name(self) -> &'static str {
+        match self {
+            Shader::Color => "color.wgsl",
+            Shader::ColorInstanced => "color-instanced.wgsl",
+            Shader::Hitbox => "hitbox.wgsl",
+            Shader::Model3DStandardFragment => "model3d-standard-fragment.glsl",
+            Shader::Model3DLavaFragment => "model3d-lava-fragment.glsl",
+            Shader::Model3DStaticVertex => "model3d-static-vertex.glsl",
+            Shader::Model3DAnimatedVertex => "model3d-animated-vertex.glsl",
+            Shader::Model3DFireballVertex => "model3d-fireball-vertex.glsl",
+        }
+    }
+
+    #[cfg(feature = "hot_reload_shaders")]
+    fn from_path(path: &Path) -> Option<Shader> {
+        let file_name = path.file_name()?.to_str()?;
+        Shader::ALL
+            .iter()
+            .cloned()
+            .find(|x| x.file_name() == file_name)
+    }
+
+    /// Wgsl shaders contain both stages, glsl shaders contain one
+    fn is_wgsl(self) -> bool {
+        matches!(
+            self,
+            Shader::Color | Shader::ColorInstanced | Shader::Hitbox
+        )
+    }
+
+    /// None for wgsl shaders, which contain both stages
+    #[cfg(feature = "hot_reload_shaders")]
+    fn kind(self) -> Option<shaderc::ShaderKind> {
+        match self {
+            Shader::Color | Shader::ColorInstanced | Shader::Hitbox => None,
+            Shader::Model3DStandardFragment | Shader::Model3DLavaFragment => {
+                Some(shaderc::ShaderKind::Fragment)
+            }
+            Shader::Model3DStaticVertex
+            | Shader::Model3DAnimatedVertex
+            | Shader::Model3DFireballVertex => Some(shaderc::ShaderKind::Vertex),
+        }
+    }
+
+    pub fn vertex_entry_point(self) -> &'static str {
+        if self.is_wgsl() {
+            "vs_main"
+        } else {
+            "main"
+        }
+    }
+
+    pub fn fragment_entry_point(self) -> &'static str {
+        if self.is_wgsl() {
+            "fs_main"
+        } else {
+            "main"
+        }
+    }
+
+    /// The shader as it was when the game was compiled.
+    /// `srgb_output` is set when the surface format is sRGB, so shaders output linear colors.
+    pub fn embedded(self, max_joints: usize, srgb_output: bool) -> ShaderSource<'static> {
+        // The joint count is compiled into the shaders, the defined MAX_JOINTS must match SAFE_MAX_JOINTS
+        let spirv: &'static [u32] = match self {
+            Shader::Color => return wgsl(include_str!("../shaders/color.wgsl"), srgb_output),
+            Shader::ColorInstanced => {
+                return wgsl(include_str!("../shaders/color-instanced.wgsl"), srgb_output)
+            }
+            Shader::Hitbox => return wgsl(include_str!("../shaders/hitbox.wgsl"), srgb_output),
+            Shader::Model3DStandardFragment if srgb_output => vk_shader_macros::include_glsl!(
+                "src/shaders/model3d-standard-fragment.glsl",
+                kind: frag,
+                define: SRGB_OUTPUT
+            ),
+            Shader::Model3DStandardFragment => vk_shader_macros::include_glsl!(
+                "src/shaders/model3d-standard-fragment.glsl",
+                kind: frag
+            ),
+            Shader::Model3DLavaFragment if srgb_output => vk_shader_macros::include_glsl!(
+                "src/shaders/model3d-lava-fragment.glsl",
+                kind: frag,
+                define: SRGB_OUTPUT
+            ),
+            Shader::Model3DLavaFragment => vk_shader_macros::include_glsl!(
+                "src/shaders/model3d-lava-fragment.glsl",
+                kind: frag
+            ),
+            Shader::Model3DStaticVertex => vk_shader_macros::include_glsl!(
+                "src/shaders/model3d-static-vertex.glsl",
+                kind: vert
+            ),
+            Shader::Model3DAnimatedVertex if max_joints == MAX_JOINTS => {
+                vk_shader_macros::include_glsl!(
+                    "src/shaders/model3d-animated-vertex.glsl",
+                    kind: vert
+                )
+            }
+            Shader::Model3DAnimatedVertex => vk_shader_macros::include_glsl!(
+                "src/shaders/model3d-animated-vertex.glsl",
+                kind: vert,
+                define: MAX_JOINTS "250"
+            ),
+            Shader::Model3DFireballVertex if max_joints == MAX_JOINTS => {
+                vk_shader_macros::include_glsl!(
+                    "src/shaders/model3d-fireball-vertex.glsl",
+                    kind: vert
+                )
+            }
+            Shader::Model3DFireballVertex => vk_shader_macros::include_glsl!(
+                "src/shaders/model3d-fireball-vertex.glsl",
+                kind: vert,
+                define: MAX_JOINTS "250"
+            ),
+        };
+        ShaderSource::SpirV(Cow::Borrowed(spirv))
+    }
+
+    /// Reads the shader from the source tree and checks that it compiles.
+    /// Invalid shaders are rejected here because wgpu panics on them.
+    #[cfg(feature = "hot_reload_shaders")]
+    pub fn load(
+        self,
+        max_joints: usize,
+        srgb_output: bool,
+    ) -> Result<ShaderSource<'static>, String> {
+        let path = Path::new(SHADERS_DIR).join(self.file_name());
+        let source = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read '{}': {}", path.display(), err))?;
+
+        match self.kind() {
+            Some(kind) => {
+                let mut compiler =
+                    shaderc::Compiler::new().ok_or("Failed to create the shader compiler")?;
+                let mut options = shaderc::CompileOptions::new()
+                    .ok_or("Failed to create the shader compiler options")?;
+                if max_joints != MAX_JOINTS {
+                    options.add_macro_definition("MAX_JOINTS", Some(&max_joints.to_string()));
+                }
+                if srgb_output {
+                    options.add_macro_definition("SRGB_OUTPUT", None);
+                }
+                let artifact = compiler
+                    .compile_into_spirv(&source, kind, self.file_name(), "main", Some(&options))
+                    .map_err(|err| err.to_string())?;
+                Ok(ShaderSource::SpirV(Cow::Owned(
+                    artifact.as_binary().to_vec(),
+                )))
+            }
+            None => {
+                let source = format!("{}{}", wgsl_prelude(srgb_output), source);
+                let module = naga::front::wgsl::parse_str(&source)
+                    .map_err(|err| err.emit_to_string(&source))?;
+                naga::valid::Validator::new(
+                    naga::valid::ValidationFlags::all(),
+                    naga::valid::Capabilities::empty(),
+                )
+                .validate(&module)
+                .map_err(|err| err.to_string())?;
+                Ok(ShaderSource::Wgsl(Cow::Owned(source)))
+            }
+        }
+    }
+}
+
+/// Defines `output_color`, which every wgsl fragment shader passes its result through.
+/// Wgsl has no preprocessor, so this is prepended to the shader source instead.
+fn wgsl_prelude(srgb_output: bool) -> &'static str {
+    if srgb_output {
+        "// The surface encodes colors to sRGB, so the sRGB colors we work with are decoded to linear first
+fn output_color(color: vec4<f32>) -> vec4<f32> {
+    let low = color.rgb / 12.92;
+    let high = pow((color.rgb + vec3<f32>(0.055)) / 1.055, vec3<f32>(2.4));
+    return vec4<f32>(select(high, low, color.rgb <= vec3<f32>(0.04045)), color.a);
+}
+"
+    } else {
+        "fn output_color(color: vec4<f32>) -> vec4<f32> {
+    return color;
+}
+"
+    }
+}
+
+fn wgsl(source: &str, srgb_output: bool) -> ShaderSource<'static> {
+    ShaderSource::Wgsl(Cow::Owned(format!(
+        "{}{}",
+        wgsl_prelude(srgb_output),
+        source
+    )))
+}
+
+/// Watches the shaders in the source tree for changes
+#[cfg(feature = "hot_reload_shaders")]
+pub struct ShaderWatcher {
+    rx: Receiver<Shader>,
+    _hotwatch: Hotwatch,
+}
+
+#[cfg(feature = "hot_reload_shaders")]
+impl ShaderWatcher {
+    pub fn new() -> Result<ShaderWatcher, String> {
+        let (tx, rx) = mpsc::channel();
+        let mut hotwatch = Hotwatch::new_with_custom_delay(Duration::from_millis(100))
+            .map_err(|x| x.to_string())?;
+        hotwatch
+            .watch(SHADERS_DIR, move |event: Event| {
+                // editors often save by writing a new file and renaming it over the old one
+                let path = match event {
+                    Event::Write(path) | Event::Create(path) | Event::Rename(_, path) => path,
+                    _ => return,
+                };
+                if let Some(shader) = Shader::from_path(&path) {
+                    tx.send(shader).ok();
+                }
+            })
+            .map_err(|x| format!("Failed to watch '{}': {}", SHADERS_DIR, x))?;
+
+        Ok(ShaderWatcher {
+            rx,
+            _hotwatch: hotwatch,
+        })
+    }
+
+    /// Returns each shader modified since the last call
+    pub fn changed(&self) -> Vec<Shader> {
+        let mut changed = vec![];
+        while let Ok(shader) = self.rx.try_recv() {
+            if !changed.contains(&shader) {
+                changed.push(shader);
+            }
+        }
+        changed
+    }
+}
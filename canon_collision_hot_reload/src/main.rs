@@ -8,6 +8,8 @@ use std::sync::mpsc::Receiver;
 
 use hotwatch::{Event, Hotwatch};
 
+use canon_collision_lib::config::Config;
+use canon_collision_lib::network::{command_connect_address, command_message};
 use canon_collision_lib::replays_files;
 
 fn main() {
@@ -74,7 +76,9 @@ fn run(rx: Receiver<Event>) {
                     let latest_replay = &replays[0];
                     let latest_replay_filename = format!("{}.zip", latest_replay);
 
-                    if let Some(mut x) = process.take() { x.kill().unwrap() }
+                    if let Some(mut x) = process.take() {
+                        x.kill().unwrap()
+                    }
 
                     // relaunch
                     process = launch(profile_arg, &["--replay", &latest_replay_filename]);
@@ -120,10 +124,11 @@ fn launch(profile_arg: &str, pass_through_args: &[&str]) -> Option<Child> {
 
 /// returns true on success
 fn send_to_cc(message: &str) -> bool {
-    match TcpStream::connect("127.0.0.1:1613") {
+    let config = Config::load();
+    match TcpStream::connect(command_connect_address(&config.command_address)) {
         Ok(mut stream) => {
             stream
-                .write_all(format!("C{}", message).as_bytes())
+                .write_all(command_message(message, config.command_token.as_deref()).as_bytes())
                 .unwrap();
 
             // We need to receive to ensure we block, but we dont really care what the response is.
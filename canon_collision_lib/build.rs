@@ -1,5 +1,8 @@
 use std::process::Command;
 
+#[path = "build_docs.rs"]
+mod build_docs;
+
 fn main() {
     let mut cmd = Command::new("git");
     cmd.args(&["describe", "--always", "--long", "--dirty"]);
@@ -14,4 +17,6 @@ fn main() {
         String::from("NO GIT")
     };
     println!("cargo:rustc-env=BUILD_VERSION={}", version.trim());
+
+    build_docs::write_node_docs();
 }
@@ -0,0 +1,85 @@
+//! Shared by the build scripts of canon_collision and canon_collision_lib, included by each with `#[path]`
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes the doc comments of the crate to `node_docs.rs` in OUT_DIR, as the NODE_DOCS constant
+pub fn write_node_docs() {
+    let mut docs = vec![];
+    collect_docs(Path::new("src"), &mut docs);
+    let mut out = String::from("pub const NODE_DOCS: &[(&str, &str, &str)] = &[\n");
+    for (ty, member, doc) in docs {
+        out.push_str(&format!("    ({:?}, {:?}, {:?}),\n", ty, member, doc));
+    }
+    out.push_str("];\n");
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("node_docs.rs");
+    fs::write(out_path, out).unwrap();
+}
+
+/// Collects the doc comments of every struct, enum, field and variant so the :schema command can display them.
+/// Stored as (type, member, doc) where member is empty for the doc comment of the type itself.
+fn collect_docs(dir: &Path, docs: &mut Vec<(String, String, String)>) {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.flatten().map(|x| x.path()).collect(),
+        Err(_) => return,
+    };
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_docs(&path, docs);
+        } else if path.extension().map_or(false, |x| x == "rs") {
+            if let Ok(source) = fs::read_to_string(&path) {
+                collect_file_docs(&source, docs);
+            }
+        }
+    }
+}
+
+fn collect_file_docs(source: &str, docs: &mut Vec<(String, String, String)>) {
+    let mut current_type: Option<String> = None;
+    let mut doc: Vec<&str> = vec![];
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if let Some(doc_line) = trimmed.strip_prefix("///") {
+            doc.push(doc_line.strip_prefix(' ').unwrap_or(doc_line));
+            continue;
+        }
+        if trimmed.starts_with("#[") {
+            continue;
+        }
+
+        let item = trimmed
+            .trim_start_matches("pub(crate) ")
+            .trim_start_matches("pub ");
+        let type_name = item
+            .strip_prefix("struct ")
+            .or_else(|| item.strip_prefix("enum "))
+            .map(identifier);
+        if let Some(type_name) = type_name {
+            if !doc.is_empty() {
+                docs.push((type_name.clone(), String::new(), doc.join("\n")));
+            }
+            current_type = if item.ends_with('{') {
+                Some(type_name)
+            } else {
+                None
+            };
+        } else if line.starts_with('}') {
+            current_type = None;
+        } else if let Some(current_type) = &current_type {
+            let member = identifier(item);
+            if !doc.is_empty() && !member.is_empty() {
+                docs.push((current_type.clone(), member, doc.join("\n")));
+            }
+        }
+        doc.clear();
+    }
+}
+
+fn identifier(text: &str) -> String {
+    text.chars()
+        .take_while(|x| x.is_alphanumeric() || *x == '_')
+        .collect()
+}
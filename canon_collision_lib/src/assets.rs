@@ -1,26 +1,35 @@
 use std::fs;
 use std::path::{Path, PathBuf};
+#[cfg(feature = "hot_reload")]
 use std::sync::mpsc::{self, Receiver, Sender};
 
+#[cfg(feature = "hot_reload")]
 use hotwatch::{Event, Hotwatch};
 
 /// Hot reloadable assets
 pub struct Assets {
     path: PathBuf,
+    #[cfg(feature = "hot_reload")]
     models_reload_rx: Receiver<Reload>,
+    #[cfg(feature = "hot_reload")]
     models_reload_tx: Sender<Reload>,
+    #[cfg(feature = "hot_reload")]
     hotwatch: Hotwatch,
 }
 
 impl Assets {
     pub fn new() -> Option<Self> {
+        #[cfg(feature = "hot_reload")]
         let (models_reload_tx, models_reload_rx) = mpsc::channel();
 
         let current_dir = std::env::current_dir().unwrap();
         Assets::find_assets_in_parent_dirs_core(&current_dir).map(|path| Assets {
             path,
+            #[cfg(feature = "hot_reload")]
             models_reload_rx,
+            #[cfg(feature = "hot_reload")]
             models_reload_tx,
+            #[cfg(feature = "hot_reload")]
             hotwatch: Hotwatch::new().unwrap(),
         })
     }
@@ -33,9 +42,12 @@ impl Assets {
         }
     }
 
+    /// Always empty without the hot_reload feature
     pub fn models_reloads(&self) -> Vec<Reload> {
+        #[allow(unused_mut)] // Needed for builds without hot reloading
         let mut reloads = vec![];
 
+        #[cfg(feature = "hot_reload")]
         while let Ok(reload) = self.models_reload_rx.try_recv() {
             reloads.push(reload);
         }
@@ -46,11 +58,24 @@ impl Assets {
     /// On failure to read from disk, logs the error and returns None
     pub fn get_model(&mut self, name: &str) -> Option<Vec<u8>> {
         let path = self.path.join("models").join(format!("{}.glb", name));
+
+        #[cfg(feature = "hot_reload")]
+        if let Err(err) = self.watch_model(name, &path) {
+            error!("Failed to load or setup hotreloading for '{}'. You will need to restart the game to reattempt loading this file. error: {}", path.to_str().unwrap(), err);
+            return None;
+        }
+
+        Assets::load_file(path)
+    }
+
+    /// Sends the model to `models_reloads` whenever its file is written to
+    #[cfg(feature = "hot_reload")]
+    fn watch_model(&mut self, name: &str, path: &Path) -> Result<(), hotwatch::Error> {
         let tx = self.models_reload_tx.clone();
         let reload_name = name.to_string();
-        let reload_path = path.clone();
+        let reload_path = path.to_path_buf();
 
-        let result = self.hotwatch.watch(&path, move |event: Event| {
+        self.hotwatch.watch(path, move |event: Event| {
             let path = reload_path.clone();
             let name = reload_name.clone();
             if let Event::Write(_) = event {
@@ -58,14 +83,47 @@ impl Assets {
                     tx.send(Reload { name, data }).unwrap();
                 }
             }
-        });
+        })
+    }
 
-        match result {
-            Ok(_) => Assets::load_file(path),
-            Err(err) => {
-                error!("Failed to load or setup hotreloading for '{}'. You will need to restart the game to reattempt loading this file. error: {}", path.to_str().unwrap(), err);
-                None
-            }
+    /// Reads the model without hot reloading it, returns None when the model does not exist
+    pub fn read_model(&self, name: &str) -> Option<Vec<u8>> {
+        let path = self.path.join("models").join(format!("{}.glb", name));
+        if path.exists() {
+            Assets::load_file(path)
+        } else {
+            None
+        }
+    }
+
+    /// On failure to read from disk, logs the error and returns None
+    pub fn get_font(&self, file_name: &str) -> Option<Vec<u8>> {
+        Assets::load_file(self.path.join("fonts").join(file_name))
+    }
+
+    /// Returns the ktx2 texture replacing the model texture of the same name.
+    /// Returns None without logging when there is no replacement, as most textures are only embedded in their model.
+    pub fn get_texture(&self, name: &str) -> Option<Vec<u8>> {
+        let path = self.path.join("textures").join(format!("{}.ktx2", name));
+        if path.exists() {
+            Assets::load_file(path)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the png texture of the costume replacing the model texture of the same name.
+    /// Returns None without logging when there is no replacement, as costumes only need to replace some textures.
+    pub fn get_costume_texture(&self, costume: &str, name: &str) -> Option<Vec<u8>> {
+        let path = self
+            .path
+            .join("textures")
+            .join(costume)
+            .join(format!("{}.png", name));
+        if path.exists() {
+            Assets::load_file(path)
+        } else {
+            None
         }
     }
 
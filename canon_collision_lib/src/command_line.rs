@@ -1,8 +1,10 @@
+#[cfg(feature = "window")]
 use winit_input_helper::{TextChar, WinitInputHelper};
 
 use std::collections::VecDeque;
 
 use treeflection::{Node, NodeRunner, NodeToken};
+#[cfg(feature = "window")]
 use winit::event::VirtualKeyCode;
 
 #[derive(Clone, Default, Serialize, Deserialize, Node)]
@@ -27,6 +29,7 @@ impl CommandLine {
         }
     }
 
+    #[cfg(feature = "window")]
     pub fn step<T>(&mut self, os_input: &WinitInputHelper, root_node: &mut T)
     where
         T: Node,
@@ -128,7 +131,7 @@ impl CommandLine {
         }
     }
 
-    fn output_add(&mut self, line: String) {
+    pub fn output_add(&mut self, line: String) {
         if self.output.len() >= 100 {
             self.output.pop_back();
         }
@@ -1,4 +1,6 @@
 use crate::files;
+use crate::hotkeys::Hotkeys;
+use crate::network::DEFAULT_COMMAND_ADDRESS;
 
 use std::path::PathBuf;
 
@@ -6,11 +8,57 @@ use serde_json;
 use treeflection::{Node, NodeRunner, NodeToken};
 
 #[derive(Clone, Serialize, Deserialize, Node)]
+#[serde(default)]
 pub struct Config {
     pub netplay_region: Option<String>,
+    /// IP address of the peer last spectated from the game mode select, entered again the next time Spectate is chosen
+    pub netplay_spectate_address: Option<String>,
+    /// Name of the lobby to create or join when Netplay Lobby is chosen from the game mode select
+    pub netplay_lobby: Option<String>,
+    pub netplay_input_delay: usize,
+    pub netplay_max_rollback_frames: usize,
     pub auto_save_replay: bool,
     pub verify_package_hashes: bool,
     pub fullscreen: bool,
+    /// Only read when the game starts
+    pub surface_format: SurfaceFormat,
+    /// Name of the graphics adapter to render with, wgpu picks one when this is None or no adapter has this name.
+    /// Only read when the game starts
+    pub graphics_adapter: Option<String>,
+    pub frame_limiter: FrameLimiter,
+    pub discord_rich_presence: bool,
+    /// Client id of the discord application that rich presence is published as, rich presence is disabled when unset
+    pub discord_client_id: Option<String>,
+    /// Path of a Unix domain socket to listen for commands on instead of TCP port 1613, only supported on Unix.
+    /// Only read when the game starts
+    pub command_socket: Option<String>,
+    /// Address and port to listen for commands on, e.g. `0.0.0.0:1613` accepts commands from other machines on the LAN.
+    /// Only read when the game starts
+    pub command_address: String,
+    /// When set, only commands sent with this token are accepted e.g. `cc_cli --token TOKEN`
+    pub command_token: Option<String>,
+    /// Address and port to accept WebSocket connections on, e.g. `127.0.0.1:1614`, the WebSocket interface is disabled when unset.
+    /// Only read when the game starts and only used when built with the websocket feature
+    pub websocket_address: Option<String>,
+    /// Address and port to stream the state of every frame to connected tools on, e.g. `127.0.0.1:1615`, telemetry is disabled when unset.
+    /// Only read when the game starts
+    pub telemetry_address: Option<String>,
+    pub show_session_report: bool,
+    pub hotkeys: Hotkeys,
+    pub hud: HudConfig,
+    /// Colors used for each team, particles always use the standard palette as they are part of the simulated game
+    pub team_palette: TeamPalette,
+    pub audio: AudioConfig,
+    /// Rumble settings of each port, ports without an entry use the default settings
+    pub haptics: Vec<HapticsConfig>,
+    /// Effect colors of each port, ports without an entry use their team color
+    pub effect_colors: Vec<EffectColors>,
+    /// Trigger curves of each local controller, controllers without an entry use the default curve
+    pub trigger_curves: Vec<TriggerCurve>,
+    /// Tutorial steps completed, the tutorial resumes from here
+    pub tutorial_progress: usize,
+    /// Keys of the stages that local games can be played on, every stage is legal when empty
+    pub legal_stages: Vec<String>,
 }
 
 impl Config {
@@ -23,6 +71,9 @@ impl Config {
     pub fn load() -> Config {
         if let Ok(json) = files::load_json(&Config::get_path()) {
             if let Ok(config) = serde_json::from_value::<Config>(json) {
+                for conflict in config.hotkeys.conflicts() {
+                    warn!("Hotkey conflict: {}", conflict);
+                }
                 return config;
             }
         }
@@ -36,15 +87,535 @@ impl Config {
     pub fn save(&self) {
         files::save_struct_json(&Config::get_path(), self);
     }
+
+    pub fn haptics(&self, port: usize) -> HapticsConfig {
+        self.haptics.get(port).cloned().unwrap_or_default()
+    }
+
+    pub fn haptics_mut(&mut self, port: usize) -> &mut HapticsConfig {
+        if self.haptics.len() <= port {
+            self.haptics.resize(port + 1, HapticsConfig::default());
+        }
+        &mut self.haptics[port]
+    }
+
+    pub fn trigger_curve(&self, controller: usize) -> TriggerCurve {
+        self.trigger_curves
+            .get(controller)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn trigger_curve_mut(&mut self, controller: usize) -> &mut TriggerCurve {
+        if self.trigger_curves.len() <= controller {
+            self.trigger_curves
+                .resize(controller + 1, TriggerCurve::default());
+        }
+        &mut self.trigger_curves[controller]
+    }
+
+    pub fn effect_colors(&self, port: usize) -> EffectColors {
+        self.effect_colors.get(port).cloned().unwrap_or_default()
+    }
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
             netplay_region: None,
+            netplay_spectate_address: None,
+            netplay_lobby: None,
+            netplay_input_delay: 2,
+            netplay_max_rollback_frames: 8,
             auto_save_replay: false,
             verify_package_hashes: true,
             fullscreen: false,
+            surface_format: SurfaceFormat::default(),
+            graphics_adapter: None,
+            frame_limiter: FrameLimiter::default(),
+            discord_rich_presence: false,
+            discord_client_id: None,
+            command_socket: None,
+            command_address: String::from(DEFAULT_COMMAND_ADDRESS),
+            command_token: None,
+            websocket_address: None,
+            telemetry_address: None,
+            show_session_report: false,
+            hotkeys: Hotkeys::default(),
+            hud: HudConfig::default(),
+            team_palette: TeamPalette::default(),
+            audio: AudioConfig::default(),
+            haptics: vec![],
+            effect_colors: vec![],
+            trigger_curves: vec![],
+            tutorial_progress: 0,
+            legal_stages: vec![],
+        }
+    }
+}
+
+/// How each players damage and stocks are displayed in the HUD
+#[derive(Clone, Serialize, Deserialize, Node)]
+#[serde(default)]
+pub struct HudConfig {
+    pub percent_precision: PercentPrecision,
+    /// Display the exact internal damage below the percent, useful when training
+    pub show_internal_damage: bool,
+    pub stock_display: StockDisplay,
+    /// Zoom level of the minimap, for stages too large to follow every player on screen.
+    /// Each player can pick their own zoom level from the pause menu.
+    pub minimap: Minimap,
+    pub mode: HudMode,
+    /// Multiplies the size and offsets of the HUD e.g. 2.0 on high DPI monitors
+    pub scale: f32,
+    /// Screen edge each players damage and stocks are displayed along
+    pub player_anchor: HudAnchor,
+    /// Screen edge the game clock is displayed at the center of
+    pub timer_anchor: HudAnchor,
+    /// Display an arrow with the player number above each fighter
+    pub player_indicators: bool,
+}
+
+impl Default for HudConfig {
+    fn default() -> HudConfig {
+        HudConfig {
+            percent_precision: PercentPrecision::default(),
+            show_internal_damage: false,
+            stock_display: StockDisplay::default(),
+            minimap: Minimap::default(),
+            mode: HudMode::default(),
+            scale: 1.0,
+            player_anchor: HudAnchor::Bottom,
+            timer_anchor: HudAnchor::Top,
+            player_indicators: true,
+        }
+    }
+}
+
+/// Which parts of the HUD are displayed
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum HudMode {
+    Full,
+    /// Only damage, stocks and the game clock
+    Minimal,
+    /// Hides every part of the HUD, for recording footage
+    Off,
+}
+
+impl HudMode {
+    pub fn next(&self) -> HudMode {
+        match self {
+            HudMode::Full => HudMode::Minimal,
+            HudMode::Minimal => HudMode::Off,
+            HudMode::Off => HudMode::Full,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HudMode::Full => "Full",
+            HudMode::Minimal => "Minimal",
+            HudMode::Off => "Off",
+        }
+    }
+}
+
+impl Default for HudMode {
+    fn default() -> HudMode {
+        HudMode::Full
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum HudAnchor {
+    Top,
+    Bottom,
+}
+
+impl HudAnchor {
+    pub fn next(&self) -> HudAnchor {
+        match self {
+            HudAnchor::Top => HudAnchor::Bottom,
+            HudAnchor::Bottom => HudAnchor::Top,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HudAnchor::Top => "Top",
+            HudAnchor::Bottom => "Bottom",
+        }
+    }
+}
+
+impl Default for HudAnchor {
+    fn default() -> HudAnchor {
+        HudAnchor::Bottom
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub enum PercentPrecision {
+    /// 12%
+    Whole,
+    /// 12.3%
+    Tenths,
+}
+
+impl PercentPrecision {
+    pub fn format(&self, damage: f32) -> String {
+        match self {
+            PercentPrecision::Whole => format!("{}%", damage.floor()),
+            PercentPrecision::Tenths => format!("{:.1}%", (damage * 10.0).floor() / 10.0),
+        }
+    }
+}
+
+impl Default for PercentPrecision {
+    fn default() -> PercentPrecision {
+        PercentPrecision::Whole
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub enum StockDisplay {
+    /// An icon per stock, falls back to a count when there are too many icons to fit
+    Icons,
+    /// An icon followed by the number of stocks
+    Count,
+}
+
+impl Default for StockDisplay {
+    fn default() -> StockDisplay {
+        StockDisplay::Icons
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum TeamPalette {
+    Standard,
+    /// Distinguishable with any form of color blindness, from Okabe and Ito's Color Universal Design
+    OkabeIto,
+    /// Distinguishable with red-green color blindness, from Paul Tol's vibrant scheme
+    TolVibrant,
+}
+
+impl TeamPalette {
+    pub fn next(&self) -> TeamPalette {
+        match self {
+            TeamPalette::Standard => TeamPalette::OkabeIto,
+            TeamPalette::OkabeIto => TeamPalette::TolVibrant,
+            TeamPalette::TolVibrant => TeamPalette::Standard,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TeamPalette::Standard => "Standard",
+            TeamPalette::OkabeIto => "Colorblind (Okabe-Ito)",
+            TeamPalette::TolVibrant => "Colorblind (Tol vibrant)",
+        }
+    }
+}
+
+impl Default for TeamPalette {
+    fn default() -> TeamPalette {
+        TeamPalette::Standard
+    }
+}
+
+/// A view of the whole stage drawn in the corner of the screen, with a dot for each player
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum Minimap {
+    Off,
+    /// Displays the area the camera can move within
+    Stage,
+    /// Displays everything up to the blast zones, so players that are far offstage are still visible
+    BlastZone,
+}
+
+impl Minimap {
+    /// Cycles through each zoom level, zooming out
+    pub fn next(&self) -> Minimap {
+        match self {
+            Minimap::Off => Minimap::Stage,
+            Minimap::Stage => Minimap::BlastZone,
+            Minimap::BlastZone => Minimap::Off,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Minimap::Off => "Off",
+            Minimap::Stage => "Stage",
+            Minimap::BlastZone => "Blast zone",
+        }
+    }
+}
+
+impl Default for Minimap {
+    fn default() -> Minimap {
+        Minimap::Off
+    }
+}
+
+/// The kind of surface the game is rendered to, falls back to the next best kind when the display does not support it
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Node)]
+pub enum SurfaceFormat {
+    /// The display decodes the output as sRGB, blending and color conversions are done in linear space
+    Srgb,
+    /// Colors are written to the display as is, blending is done on the sRGB colors
+    Unorm,
+    /// High dynamic range output, falls back to Srgb
+    Hdr,
+}
+
+impl Default for SurfaceFormat {
+    fn default() -> SurfaceFormat {
+        SurfaceFormat::Srgb
+    }
+}
+
+/// Limits how often menus are drawn to save power, matches are always drawn as fast as possible
+#[derive(Clone, Serialize, Deserialize, Node)]
+#[serde(default)]
+pub struct FrameLimiter {
+    pub enabled: bool,
+    /// Frames per second drawn in menus
+    pub menu_fps: u32,
+    /// Frames per second drawn in menus while the window is unfocused or minimized
+    pub background_fps: u32,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> FrameLimiter {
+        FrameLimiter {
+            enabled: true,
+            menu_fps: 60,
+            background_fps: 10,
+        }
+    }
+}
+
+/// Volumes range from 0.0 (silent) to 1.0 (full volume)
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Scales both the music and sound effect volumes
+    pub master_volume: f64,
+    pub bgm_volume: f64,
+    pub sfx_volume: f64,
+    /// Silences all audio without losing the volume settings
+    pub mute: bool,
+}
+
+impl AudioConfig {
+    /// The volume music is played at
+    pub fn bgm(&self) -> f64 {
+        if self.mute {
+            0.0
+        } else {
+            self.master_volume * self.bgm_volume
+        }
+    }
+
+    /// The volume sound effects are scaled by
+    pub fn sfx(&self) -> f64 {
+        if self.mute {
+            0.0
+        } else {
+            self.master_volume * self.sfx_volume
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            master_volume: 1.0,
+            bgm_volume: 1.0,
+            sfx_volume: 1.0,
+            mute: false,
+        }
+    }
+}
+
+/// Colors used for a fighter's visual effects instead of their team color
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+#[serde(default)]
+pub struct EffectColors {
+    pub shield: Option<Rgb>,
+    /// Tint of the particles created by the fighter e.g. hit sparks and dust
+    pub particles: Option<Rgb>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize, Node)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    /// Each component from 0.0 to 1.0
+    pub fn to_f32(self) -> [f32; 3] {
+        [
+            self.r as f32 / 255.0,
+            self.g as f32 / 255.0,
+            self.b as f32 / 255.0,
+        ]
+    }
+}
+
+/// Reshapes the analog trigger values of a controller, for pads whose triggers do not match a GC controller's range
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+#[serde(default)]
+pub struct TriggerCurve {
+    /// The trigger value reached when fully pressed, some pads never reach 1.0
+    pub full_press: f32,
+    /// Values are raised to this power after scaling, above 1.0 gives more precision to light presses
+    pub exponent: f32,
+}
+
+impl TriggerCurve {
+    pub fn apply(&self, value: f32) -> f32 {
+        (value / self.full_press.max(0.01))
+            .min(1.0)
+            .powf(self.exponent)
+    }
+}
+
+impl Default for TriggerCurve {
+    fn default() -> TriggerCurve {
+        TriggerCurve {
+            full_press: 1.0,
+            exponent: 1.0,
+        }
+    }
+}
+
+/// How the controller of a port rumbles in response to game events
+#[derive(Clone, Serialize, Deserialize, Node)]
+#[serde(default)]
+pub struct HapticsConfig {
+    /// Taking damage
+    pub hit: HapticCue,
+    /// Parrying an attack with a power shield
+    pub parry: HapticCue,
+    pub shield_break: HapticCue,
+    /// Losing a stock
+    pub ko: HapticCue,
+}
+
+impl HapticsConfig {
+    pub fn cue(&self, event: HapticEvent) -> &HapticCue {
+        match event {
+            HapticEvent::Hit => &self.hit,
+            HapticEvent::Parry => &self.parry,
+            HapticEvent::ShieldBreak => &self.shield_break,
+            HapticEvent::Ko => &self.ko,
+        }
+    }
+
+    pub fn cue_mut(&mut self, event: HapticEvent) -> &mut HapticCue {
+        match event {
+            HapticEvent::Hit => &mut self.hit,
+            HapticEvent::Parry => &mut self.parry,
+            HapticEvent::ShieldBreak => &mut self.shield_break,
+            HapticEvent::Ko => &mut self.ko,
+        }
+    }
+}
+
+impl Default for HapticsConfig {
+    fn default() -> HapticsConfig {
+        HapticsConfig {
+            hit: HapticCue::LIGHT,
+            parry: HapticCue::LIGHT,
+            shield_break: HapticCue::STRONG,
+            ko: HapticCue::STRONG,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HapticEvent {
+    Hit,
+    Parry,
+    ShieldBreak,
+    Ko,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub struct HapticCue {
+    /// From 0.0 (no rumble) to 1.0 (full rumble)
+    pub strength: f32,
+    pub frames: u64,
+}
+
+impl HapticCue {
+    pub const OFF: HapticCue = HapticCue {
+        strength: 0.0,
+        frames: 0,
+    };
+    pub const LIGHT: HapticCue = HapticCue {
+        strength: 0.4,
+        frames: 8,
+    };
+    pub const MEDIUM: HapticCue = HapticCue {
+        strength: 0.7,
+        frames: 14,
+    };
+    pub const STRONG: HapticCue = HapticCue {
+        strength: 1.0,
+        frames: 24,
+    };
+    const PRESETS: [(&'static str, HapticCue); 4] = [
+        ("Off", HapticCue::OFF),
+        ("Light", HapticCue::LIGHT),
+        ("Medium", HapticCue::MEDIUM),
+        ("Strong", HapticCue::STRONG),
+    ];
+
+    /// The preset name, values edited in config.json are displayed as "Custom"
+    pub fn name(&self) -> &'static str {
+        HapticCue::PRESETS
+            .iter()
+            .find(|x| x.1 == *self)
+            .map(|x| x.0)
+            .unwrap_or("Custom")
+    }
+
+    /// Moves onto the next preset, a custom cue moves onto the first preset
+    pub fn cycle(&mut self) {
+        let next = HapticCue::PRESETS
+            .iter()
+            .position(|x| x.1 == *self)
+            .map(|i| (i + 1) % HapticCue::PRESETS.len())
+            .unwrap_or(0);
+        *self = HapticCue::PRESETS[next].1.clone();
+    }
+}
+
+impl Default for HapticCue {
+    fn default() -> HapticCue {
+        HapticCue::OFF
+    }
+}
+
+#[test]
+fn trigger_curve_test() {
+    let default = TriggerCurve::default();
+    assert_eq!(default.apply(0.0), 0.0);
+    assert_eq!(default.apply(0.5), 0.5);
+    assert_eq!(default.apply(1.0), 1.0);
+
+    let curve = TriggerCurve {
+        full_press: 0.5,
+        exponent: 2.0,
+    };
+    assert_eq!(curve.apply(0.0), 0.0);
+    assert_eq!(curve.apply(0.25), 0.25);
+    assert_eq!(curve.apply(0.5), 1.0);
+    assert_eq!(curve.apply(0.8), 1.0);
 }
@@ -0,0 +1,10 @@
+#[repr(u64)]
+#[derive(Clone, PartialEq, Debug, EnumString, IntoStaticStr, EnumIter, Serialize, Deserialize)]
+pub enum AssistAction {
+    Spawn,
+    Idle,
+    Walk,
+    Attack,
+    Fall,
+    Despawn,
+}
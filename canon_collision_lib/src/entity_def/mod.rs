@@ -1,3 +1,4 @@
+pub mod assist;
 pub mod dave;
 pub mod item;
 pub mod player;
@@ -6,12 +7,15 @@ pub mod toriel;
 pub mod toriel_fireball;
 pub mod toriel_oven;
 
+use std::collections::BTreeMap;
+
 use strum::IntoEnumIterator;
 use treeflection::{ContextVec, KeyedContextVec, Node, NodeRunner, NodeToken};
 
 use crate::files::engine_version;
 use crate::geometry::Rect;
 
+use assist::AssistAction;
 use dave::DaveAction;
 use item::ItemAction;
 use player::PlayerAction;
@@ -75,6 +79,8 @@ impl Default for EntityDef {
             run_turn_flip_dir_frame: 30,
             tilt_turn_flip_dir_frame: 5,
             tilt_turn_into_dash_iasa: 5,
+            animation_blend_frames: 4,
+            simulation_lod: SimulationLod::default(),
             actions: KeyedContextVec::new(),
         }
     }
@@ -132,6 +138,10 @@ pub struct EntityDef {
     pub run_turn_flip_dir_frame: u64,
     pub tilt_turn_flip_dir_frame: u64,
     pub tilt_turn_into_dash_iasa: u64,
+    /// Number of frames the model crossfades from the previous action's animation when the action changes
+    pub animation_blend_frames: u64,
+    /// Purely cosmetic entities can be simulated less often while far from every fighter to keep large stages cheap
+    pub simulation_lod: SimulationLod,
     pub actions: KeyedContextVec<ActionDef>,
 }
 
@@ -144,20 +154,88 @@ impl EntityDef {
         }
     }
 
-    pub fn cleanup(&mut self) {
-        for action_name in self.ty.get_action_names() {
-            let action_name = action_name.to_string();
-            if !self.actions.contains_key(&action_name) {
-                self.actions.push(action_name, ActionDef::default());
-            }
+    /// Compares the actions in the package against the action enums of the entity's type
+    pub fn action_drift(&self) -> ActionDrift {
+        let expected_action_names: Vec<_> = self.ty.get_action_names().collect();
+        ActionDrift {
+            missing: expected_action_names
+                .iter()
+                .filter(|x| !self.actions.contains_key(&x.to_string()))
+                .map(|x| x.to_string())
+                .collect(),
+            unknown: self
+                .actions
+                .key_iter()
+                .filter(|x| !expected_action_names.contains(&x.as_str()))
+                .cloned()
+                .collect(),
         }
+    }
 
-        let expected_action_names: Vec<_> = self.ty.get_action_names().collect();
-        let check_action_names: Vec<_> = self.actions.key_iter().cloned().collect();
-        for action_name in check_action_names {
-            if !expected_action_names.contains(&action_name.as_str()) {
-                self.actions.remove_by_key(action_name.as_str());
-            }
+    /// Adds the missing actions and removes the unknown actions, returning what was changed
+    pub fn cleanup(&mut self) -> ActionDrift {
+        let drift = self.action_drift();
+        for action_name in &drift.missing {
+            self.actions.push(action_name.clone(), ActionDef::default());
+        }
+        for action_name in &drift.unknown {
+            self.actions.remove_by_key(action_name.as_str());
+        }
+        drift
+    }
+}
+
+/// Actions that are out of sync between a package and the action enums, usually caused by adding or renaming an action.
+#[derive(Debug, Default, PartialEq)]
+pub struct ActionDrift {
+    /// Actions of the entity type that are not in the package
+    pub missing: Vec<String>,
+    /// Actions in the package that the entity type does not have
+    pub unknown: Vec<String>,
+}
+
+impl ActionDrift {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.unknown.is_empty()
+    }
+
+    pub fn report(&self, entity_key: &str) -> String {
+        let mut lines = vec![format!("{} has drifted from its action enums", entity_key)];
+        if !self.missing.is_empty() {
+            lines.push(format!("    missing actions: {}", self.missing.join(", ")));
+        }
+        if !self.unknown.is_empty() {
+            lines.push(format!("    unknown actions: {}", self.unknown.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// How often an entity is simulated while it is far from every fighter, it is always simulated every frame while near one.
+/// Only purely cosmetic entities should use anything other than `Full`, as a sleeping entity cannot react to anything.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Node)]
+pub enum SimulationLod {
+    /// Simulated every frame
+    Full,
+    /// Simulated once every this many frames
+    Reduced(u64),
+    /// Not simulated at all
+    Sleep,
+}
+
+impl Default for SimulationLod {
+    fn default() -> Self {
+        SimulationLod::Full
+    }
+}
+
+impl SimulationLod {
+    /// Returns true if an entity far from every fighter is simulated on this frame
+    pub fn distant_step(&self, frame: usize) -> bool {
+        match self {
+            SimulationLod::Full => true,
+            SimulationLod::Reduced(interval) => frame as u64 % (*interval).max(1) == 0,
+            SimulationLod::Sleep => false,
         }
     }
 }
@@ -166,6 +244,10 @@ impl EntityDef {
 pub enum EntityDefType {
     Fighter(Fighter),
     Item,
+    /// An item that summons an assist when thrown
+    AssistItem(AssistItem),
+    /// A temporary helper that fights for the player that summoned it
+    Assist(Assist),
     Projectile,
     TorielFireball,
     TorielOven,
@@ -180,6 +262,8 @@ impl EntityDefType {
                     .chain(fighter.ty.get_action_names()),
             ),
             EntityDefType::Item => Box::new(ItemAction::iter().map(|x| x.into())),
+            EntityDefType::AssistItem(_) => Box::new(ItemAction::iter().map(|x| x.into())),
+            EntityDefType::Assist(_) => Box::new(AssistAction::iter().map(|x| x.into())),
             EntityDefType::Projectile => Box::new(ProjectileAction::iter().map(|x| x.into())),
             EntityDefType::TorielFireball => {
                 Box::new(TorielFireballAction::iter().map(|x| x.into()))
@@ -195,10 +279,38 @@ impl Default for EntityDefType {
     }
 }
 
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct AssistItem {
+    /// Key of the Assist entity summoned by this item
+    pub assist: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct Assist {
+    /// Frames the assist remains for before despawning
+    pub duration: u64,
+    /// The assist attacks when the nearest enemy is within this horizontal distance
+    pub attack_range: f32,
+}
+
+impl Default for Assist {
+    fn default() -> Self {
+        Assist {
+            duration: 600,
+            attack_range: 15.0,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub struct Fighter {
     pub ty: FighterType,
     pub air_jumps: u64,
+    /// The fighter can use the Super action when the super meter is enabled and full
+    pub has_super: bool,
+    pub throws: Throws,
+    /// Alternate appearances chosen on the character select screen, in addition to the default appearance
+    pub costumes: Vec<Costume>,
 }
 
 impl Default for Fighter {
@@ -206,6 +318,84 @@ impl Default for Fighter {
         Fighter {
             ty: FighterType::default(),
             air_jumps: 1,
+            has_super: false,
+            throws: Throws::default(),
+            costumes: vec![],
+        }
+    }
+}
+
+impl Fighter {
+    /// Costume 0 is the default appearance, which is not in `costumes`
+    pub fn costume(&self, costume: usize) -> Option<&Costume> {
+        costume.checked_sub(1).and_then(|x| self.costumes.get(x))
+    }
+
+    /// The number of costumes including the default appearance
+    pub fn costume_count(&self) -> usize {
+        self.costumes.len() + 1
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct Costume {
+    pub name: String,
+    /// Folder in assets/textures of png files that replace the model's images with the same name, other images are unchanged
+    pub textures: String,
+    /// Used instead of the fighter's model when set, it must have the same animations
+    pub model: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct Throws {
+    pub up: Throw,
+    pub down: Throw,
+    pub forward: Throw,
+    pub back: Throw,
+}
+
+impl Default for Throws {
+    fn default() -> Self {
+        Throws {
+            up: Throw {
+                angle: 85.0,
+                ..Throw::default()
+            },
+            down: Throw {
+                angle: -90.0,
+                ..Throw::default()
+            },
+            forward: Throw {
+                angle: 30.0,
+                ..Throw::default()
+            },
+            back: Throw {
+                angle: 170.0,
+                ..Throw::default()
+            },
+        }
+    }
+}
+
+/// Knockback applied to the grabbed fighter when a throw action reaches `release_frame`.
+/// `angle` is in degrees relative to the direction the thrower faces.
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct Throw {
+    pub release_frame: u64,
+    pub angle: f32,
+    pub damage: f32,
+    pub bkb: f32,
+    pub kbg: f32,
+}
+
+impl Default for Throw {
+    fn default() -> Self {
+        Throw {
+            release_frame: 5,
+            angle: 0.0,
+            damage: 5.0,
+            bkb: 80.0,
+            kbg: 1.1,
         }
     }
 }
@@ -275,6 +465,12 @@ pub struct Shield {
     pub hp_max: f32,
     pub hp_regen: f32,
     pub hp_cost: f32,
+    /// Velocity away from another shield per unit the two shields overlap
+    pub shield_push: f32,
+    /// Velocity applied to both this fighter and another fighter's body, per unit the shield overlaps the body
+    pub body_push: f32,
+    /// The shield never pushes harder than this
+    pub push_max: f32,
 }
 
 impl Default for Shield {
@@ -290,6 +486,9 @@ impl Default for Shield {
             hp_max: 60.0,
             hp_regen: 0.1,
             hp_cost: 0.3,
+            shield_push: 0.05,
+            body_push: 0.03,
+            push_max: 1.0,
         }
     }
 }
@@ -312,6 +511,15 @@ pub struct ActionDef {
     /// Invariant: Must always have one or more elements
     pub frames: ContextVec<ActionFrame>,
     pub iasa: i64,
+    /// Rhai script run every frame of the action, after the entity's builtin logic. An action change made by the script takes priority over one made by the builtin logic.
+    pub frame_step_script: Option<String>,
+    /// Rhai script run when the action reaches its last frame, takes priority over the entity's builtin logic.
+    pub action_expired_script: Option<String>,
+    /// Overrides the entity's animation_blend_frames when changing into this action, 0 snaps straight to the new animation.
+    pub blend_frames: Option<u64>,
+    /// Animation frames played per frame of the action e.g. 1.5 plays a 60 frame animation over 40 frames.
+    /// Lets an animation be reused at a different speed without exporting the model again.
+    pub animation_speed: f32,
 }
 
 impl Default for ActionDef {
@@ -319,10 +527,47 @@ impl Default for ActionDef {
         ActionDef {
             iasa: 0,
             frames: ContextVec::from_vec(vec![ActionFrame::default()]),
+            frame_step_script: None,
+            action_expired_script: None,
+            blend_frames: None,
+            animation_speed: 1.0,
         }
     }
 }
 
+impl ActionDef {
+    /// The frame of the animation to display on the specified frame of the action
+    pub fn animation_frame(&self, frame: usize) -> f32 {
+        frame as f32 * self.animation_speed
+    }
+
+    /// The number of action frames needed to play an animation of `animation_len` frames at the animation_speed
+    pub fn frames_for_animation(&self, animation_len: usize) -> usize {
+        if self.animation_speed <= 0.0 {
+            return animation_len.max(1);
+        }
+        let last_frame = animation_len.saturating_sub(1) as f32 / self.animation_speed;
+        last_frame.round() as usize + 1
+    }
+
+    /// The frames each colbox index is a hitbox on, as inclusive ranges of consecutive frames, ordered by colbox index
+    pub fn hitbox_windows(&self) -> Vec<(usize, Vec<(usize, usize)>)> {
+        let mut windows: BTreeMap<usize, Vec<(usize, usize)>> = BTreeMap::new();
+        for (frame_i, frame) in self.frames.iter().enumerate() {
+            for (colbox_i, colbox) in frame.colboxes.iter().enumerate() {
+                if let CollisionBoxRole::Hit(_) = colbox.role {
+                    let ranges = windows.entry(colbox_i).or_default();
+                    match ranges.last_mut() {
+                        Some((_, end)) if *end + 1 == frame_i => *end = frame_i,
+                        _ => ranges.push((frame_i, frame_i)),
+                    }
+                }
+            }
+        }
+        windows.into_iter().collect()
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub struct ActionFrame {
     pub ecb: ECB,
@@ -339,6 +584,17 @@ pub struct ActionFrame {
     pub ledge_grab_box: Option<Rect>,
     pub item_grab_box: Option<Rect>,
     pub force_hitlist_reset: bool,
+    /// Freeform labels queried by gameplay systems and overlays e.g. "intangible", "autocancel", "armor".
+    /// New frame level properties can be added as tags without changing the package format.
+    pub tags: Vec<String>,
+    /// Fired once when the entity reaches this frame
+    pub events: Vec<FrameEvent>,
+}
+
+impl ActionFrame {
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|x| x == tag)
+    }
 }
 
 impl Default for ActionFrame {
@@ -357,10 +613,29 @@ impl Default for ActionFrame {
             ledge_grab_box: None,
             item_grab_box: None,
             force_hitlist_reset: false,
+            tags: vec![],
+            events: vec![],
         }
     }
 }
 
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub enum FrameEvent {
+    /// Displays the text on screen for a few seconds
+    Text(String),
+    /// Fires an event with this name, game modes such as the tutorial listen for them.
+    /// Every action also fires an event named after itself when it starts.
+    Named(String),
+    /// Plays the sound effect with this file name from the entity's folder in assets/audio/sfx
+    Sound(String),
+}
+
+impl Default for FrameEvent {
+    fn default() -> FrameEvent {
+        FrameEvent::Named(String::new())
+    }
+}
+
 impl ActionFrame {
     pub fn get_hitboxes(&self) -> Vec<&CollisionBox> {
         self.colboxes
@@ -375,6 +650,15 @@ impl ActionFrame {
             .filter(|x| matches!(x.role, CollisionBoxRole::Hurt(_)))
             .collect()
     }
+
+    /// Scales the radius of every colbox and its offset from the entity along with the ecb, used when the entity is scaled e.g. by the fighter size modifier
+    pub fn scale(&mut self, scale: f32) {
+        for colbox in self.colboxes.iter_mut() {
+            colbox.point = (colbox.point.0 * scale, colbox.point.1 * scale);
+            colbox.radius *= scale;
+        }
+        self.ecb.scale(scale);
+    }
 }
 
 #[derive(Default, Clone, Serialize, Deserialize, Node)]
@@ -396,6 +680,16 @@ pub struct ECB {
     pub bottom: f32,
 }
 
+impl ECB {
+    /// Scales the distance of each side from the entity
+    pub fn scale(&mut self, scale: f32) {
+        self.left *= scale;
+        self.right *= scale;
+        self.top *= scale;
+        self.bottom *= scale;
+    }
+}
+
 impl Default for ECB {
     fn default() -> ECB {
         ECB {
@@ -478,6 +772,9 @@ pub struct HitBox {
     pub enable_clang: bool,
     pub enable_rebound: bool,
     pub effect: HitboxEffect,
+    pub spark: HitSpark,
+    /// File name of the sound effect in the attacker's folder in assets/audio/sfx played on hit, instead of the hit spark's sound
+    pub sfx: Option<String>,
     pub enable_reverse_hit: bool, // if the defender is behind the attacker the direction is reversed.
                                   //pub team_funnel_angle: Option<f32>, // degrees to +- towards nearest teammate
 }
@@ -495,6 +792,8 @@ impl Default for HitBox {
             enable_reverse_hit: true,
             hitstun: HitStun::default(),
             effect: HitboxEffect::default(),
+            spark: HitSpark::default(),
+            sfx: None,
         }
     }
 }
@@ -519,6 +818,8 @@ pub enum HitboxEffect {
     Reverse,
     Stun,
     Freeze,
+    /// Applies a timed status effect to the entity that is hit
+    Status(StatusEffect),
     None,
 }
 
@@ -527,3 +828,170 @@ impl Default for HitboxEffect {
         HitboxEffect::None
     }
 }
+
+/// A timed modifier to the stats of an entity.
+/// Effects of the same kind stack, once `max_stacks` is reached applying another refreshes the oldest one instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Node)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    pub frames: u64,
+    pub max_stacks: u64,
+    /// Multiplies horizontal movement, knockback is unaffected
+    pub speed_mult: f32,
+    pub gravity_mult: f32,
+    pub damage_taken_mult: f32,
+    /// Dealt every frame, ignores every damage multiplier
+    pub damage_per_frame: f32,
+}
+
+impl Default for StatusEffect {
+    fn default() -> StatusEffect {
+        StatusEffect {
+            kind: StatusEffectKind::Poison,
+            frames: 300,
+            max_stacks: 3,
+            speed_mult: 1.0,
+            gravity_mult: 1.0,
+            damage_taken_mult: 1.0,
+            damage_per_frame: 0.02,
+        }
+    }
+}
+
+/// Identifies the effect for stacking and for its HUD icon, the stats modified are up to the `StatusEffect`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Node)]
+pub enum StatusEffectKind {
+    Poison,
+    Slow,
+    Haste,
+    Heavy,
+    Floaty,
+    Armor,
+}
+
+impl Default for StatusEffectKind {
+    fn default() -> StatusEffectKind {
+        StatusEffectKind::Poison
+    }
+}
+
+/// The spark and sound of a hitbox landing, communicating the type of attack
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Node)]
+pub enum HitSpark {
+    Blunt,
+    Slash,
+    Electric,
+    Flame,
+}
+
+impl Default for HitSpark {
+    fn default() -> HitSpark {
+        HitSpark::Blunt
+    }
+}
+
+#[test]
+fn action_drift_test() {
+    let mut entity = EntityDef::default();
+    entity
+        .actions
+        .push(String::from("Travel"), ActionDef::default());
+    entity
+        .actions
+        .push(String::from("Bounce"), ActionDef::default());
+
+    let drift = entity.action_drift();
+    assert_eq!(
+        drift.missing,
+        vec![String::from("Spawn"), String::from("Hit")]
+    );
+    assert_eq!(drift.unknown, vec![String::from("Bounce")]);
+
+    assert_eq!(entity.cleanup(), drift);
+    assert!(entity.action_drift().is_empty());
+}
+
+#[test]
+fn scale_test() {
+    let mut frame = ActionFrame::default();
+    frame.colboxes.push(CollisionBox {
+        point: (2.0, 4.0),
+        radius: 3.0,
+        role: CollisionBoxRole::Hurt(Default::default()),
+    });
+    frame.colboxes.push(CollisionBox {
+        point: (2.0, 4.0),
+        radius: 3.0,
+        role: CollisionBoxRole::Hit(Default::default()),
+    });
+    frame.scale(1.5);
+
+    assert_eq!(frame.colboxes[0].point, (3.0, 6.0));
+    assert_eq!(frame.colboxes[0].radius, 4.5);
+    assert_eq!(frame.colboxes[1].point, (3.0, 6.0));
+    assert_eq!(frame.colboxes[1].radius, 4.5);
+    assert_eq!(frame.ecb.top, 24.0);
+    assert_eq!(frame.ecb.left, -6.0);
+}
+
+#[test]
+fn simulation_lod_test() {
+    assert!(SimulationLod::Full.distant_step(7));
+    assert!(!SimulationLod::Sleep.distant_step(0));
+    assert!(SimulationLod::Reduced(4).distant_step(8));
+    assert!(!SimulationLod::Reduced(4).distant_step(9));
+    assert!(SimulationLod::Reduced(0).distant_step(9));
+}
+
+#[test]
+fn costume_test() {
+    let mut fighter = Fighter::default();
+    fighter.costumes.push(Costume {
+        name: "Red".into(),
+        textures: "TorielRed".into(),
+        model: None,
+    });
+    assert_eq!(fighter.costume_count(), 2);
+    assert!(fighter.costume(0).is_none());
+    assert_eq!(fighter.costume(1).unwrap().name, "Red");
+    assert!(fighter.costume(2).is_none());
+}
+
+#[test]
+fn animation_speed_test() {
+    let mut action = ActionDef::default();
+    assert_eq!(action.frames_for_animation(60), 60);
+    assert_eq!(action.animation_frame(10), 10.0);
+
+    action.animation_speed = 1.5;
+    assert_eq!(action.frames_for_animation(60), 40);
+    assert_eq!(action.animation_frame(10), 15.0);
+    assert_eq!(action.frames_for_animation(0), 1);
+}
+
+#[test]
+fn hitbox_windows_test() {
+    let hurtbox = CollisionBox::default();
+    let hitbox = CollisionBox {
+        role: CollisionBoxRole::Hit(HitBox::default()),
+        ..CollisionBox::default()
+    };
+    let mut action = ActionDef::default();
+    action.frames.clear();
+    for colboxes in [
+        vec![hurtbox.clone()],
+        vec![hitbox.clone(), hitbox.clone()],
+        vec![hitbox.clone(), hitbox.clone()],
+        vec![hurtbox.clone(), hitbox.clone()],
+        vec![hitbox.clone()],
+    ] {
+        action.frames.push(ActionFrame {
+            colboxes: ContextVec::from_vec(colboxes),
+            ..ActionFrame::default()
+        });
+    }
+    assert_eq!(
+        action.hitbox_windows(),
+        vec![(0, vec![(1, 2), (4, 4)]), (1, vec![(1, 3)])]
+    );
+}
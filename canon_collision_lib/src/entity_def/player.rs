@@ -86,6 +86,9 @@ pub enum PlayerAction {
     GrabbedIdleAir,
     GrabbedIdle,
     GrabbedEnd,
+    GrabbingWalk,
+    GrabbingJumpSquat,
+    GrabbingJump,
 
     // Throws
     Uthrow,
@@ -135,6 +138,9 @@ pub enum PlayerAction {
     NspecialGroundStart,
     NspecialAirStart,
 
+    // Super
+    Super,
+
     // Taunts
     TauntUp,
     TauntDown,
@@ -10,10 +10,4 @@ pub enum TorielAction {
 
     NspecialGroundStart,
     NspecialAirStart,
-
-    // Throws
-    Uthrow,
-    Dthrow,
-    Fthrow,
-    Bthrow,
 }
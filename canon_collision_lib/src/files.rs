@@ -1,5 +1,6 @@
 use std::fs;
 use std::fs::{DirBuilder, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use dirs_next;
@@ -8,12 +9,14 @@ use serde::ser::Serialize;
 use serde_cbor;
 use serde_json;
 
+use crate::package;
+
 pub fn build_version() -> String {
     String::from(env!("BUILD_VERSION"))
 }
 
 pub fn engine_version() -> u64 {
-    20
+    38
 }
 
 pub fn save_struct_json<T: Serialize>(filename: &Path, object: &T) {
@@ -50,6 +53,25 @@ pub fn save_struct_cbor<T: Serialize>(filename: &Path, object: &T) {
     serde_cbor::to_writer(file, object).unwrap();
 }
 
+/// Writes to a temporary file that is then renamed over the original,
+/// so the original is left intact if writing is interrupted.
+pub fn save_bytes_atomic(filename: &Path, bytes: &[u8]) -> Result<(), String> {
+    // ensure parent directories exists
+    DirBuilder::new()
+        .recursive(true)
+        .create(filename.parent().unwrap())
+        .map_err(|x| format!("{:?}", x))?;
+
+    let mut temp_name = filename.file_name().unwrap().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = filename.with_file_name(temp_name);
+
+    let mut file = File::create(&temp_path).map_err(|x| format!("{:?}", x))?;
+    file.write_all(bytes).map_err(|x| format!("{:?}", x))?;
+    file.sync_all().map_err(|x| format!("{:?}", x))?;
+    fs::rename(&temp_path, filename).map_err(|x| format!("{:?}", x))
+}
+
 pub fn load_cbor(filename: &Path) -> Result<serde_cbor::Value, String> {
     let file = File::open(filename).map_err(|x| format!("{:?}", x))?;
     serde_cbor::from_reader(&file).map_err(|x| format!("{:?}", x))
@@ -72,6 +94,11 @@ pub fn load_struct_bincode<T: DeserializeOwned>(filename: &Path) -> Result<T, St
     bincode::deserialize_from(file).map_err(|x| format!("{:?}", x))
 }
 
+/// Hashes the bincode serialization of the object, so floats are hashed by their exact bits
+pub fn hash_struct_bincode<T: Serialize>(object: &T) -> u64 {
+    package::fnv1a_hash(&bincode::serialize(object).unwrap())
+}
+
 pub fn load_file(filename: &Path) -> Result<String, String> {
     std::fs::read_to_string(&filename).map_err(|x| {
         format!(
@@ -1,5 +1,64 @@
+use cgmath::{Matrix4, Quaternion};
 use treeflection::{Node, NodeRunner, NodeToken};
 
+use std::f32::consts::PI;
+
+/// Rotates the point counter clockwise around the origin by `angle` radians
+pub fn rotate(point: (f32, f32), angle: f32) -> (f32, f32) {
+    let (x, y) = point;
+    (
+        x * angle.cos() - y * angle.sin(),
+        x * angle.sin() + y * angle.cos(),
+    )
+}
+
+/// Maps an angle from atan2, between -pi and pi, to between 0 and 2pi
+pub fn positive_angle(angle: f32) -> f32 {
+    angle + if angle < 0.0 { PI * 2.0 } else { 0.0 }
+}
+
+/// Converts the rotation of the matrix to a quaternion.
+/// Assumes the upper 3x3 of m is a pure rotation matrix (i.e. unscaled)
+/// Implemented as described here http://www.euclideanspace.com/maths/geometry/rotations/conversions/matrixToQuaternion/index.htm
+pub fn matrix_to_quaternion(m: &Matrix4<f32>) -> Quaternion<f32> {
+    // cgmath matrices are column major, so m.y.z is the element in row z and column y
+    let trace = m.x.x + m.y.y + m.z.z;
+
+    if trace > 0.0 {
+        let s = 0.5 / (trace + 1.0).sqrt();
+        Quaternion::new(
+            0.25 / s,
+            (m.y.z - m.z.y) * s,
+            (m.z.x - m.x.z) * s,
+            (m.x.y - m.y.x) * s,
+        )
+    } else if m.x.x > m.y.y && m.x.x > m.z.z {
+        let s = 2.0 * (1.0 + m.x.x - m.y.y - m.z.z).sqrt();
+        Quaternion::new(
+            (m.y.z - m.z.y) / s,
+            0.25 * s,
+            (m.y.x + m.x.y) / s,
+            (m.z.x + m.x.z) / s,
+        )
+    } else if m.y.y > m.z.z {
+        let s = 2.0 * (1.0 + m.y.y - m.x.x - m.z.z).sqrt();
+        Quaternion::new(
+            (m.z.x - m.x.z) / s,
+            (m.y.x + m.x.y) / s,
+            0.25 * s,
+            (m.z.y + m.y.z) / s,
+        )
+    } else {
+        let s = 2.0 * (1.0 + m.z.z - m.x.x - m.y.y).sqrt();
+        Quaternion::new(
+            (m.x.y - m.y.x) / s,
+            (m.z.x + m.x.z) / s,
+            (m.z.y + m.y.z) / s,
+            0.25 * s,
+        )
+    }
+}
+
 /// Checks if segment p1q1 intersects with segment p2q2
 /// Implemented as described here http://www.geeksforgeeks.org/check-if-two-given-line-segments-intersect/
 pub fn segments_intersect(p1: (f32, f32), q1: (f32, f32), p2: (f32, f32), q2: (f32, f32)) -> bool {
@@ -73,6 +132,18 @@ impl Rect {
         self.y1.max(self.y2)
     }
 
+    pub fn width(&self) -> f32 {
+        self.right() - self.left()
+    }
+
+    pub fn height(&self) -> f32 {
+        self.top() - self.bot()
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        ((self.x1 + self.x2) / 2.0, (self.y1 + self.y2) / 2.0)
+    }
+
     /// Returns true iff the passed point is within this Rect
     pub fn contains_point(&self, x: f32, y: f32) -> bool {
         ((x > self.x1 && x < self.x2) || (x > self.x2 && x < self.x1))
@@ -95,3 +166,40 @@ impl Rect {
             && self.top() > other_rect.bot()
     }
 }
+
+#[test]
+fn rotate_test() {
+    let (x, y) = rotate((1.0, 0.0), PI / 2.0);
+    assert!(x.abs() < 0.0001);
+    assert!((y - 1.0).abs() < 0.0001);
+
+    assert_eq!(positive_angle(1.0), 1.0);
+    assert!((positive_angle(-PI / 2.0) - PI * 3.0 / 2.0).abs() < 0.0001);
+}
+
+#[test]
+fn matrix_to_quaternion_test() {
+    use cgmath::{InnerSpace, Rad, Rotation3};
+
+    // covers every branch of the conversion
+    let rotations = [
+        Quaternion::from_angle_x(Rad(0.5)),
+        Quaternion::from_angle_x(Rad(3.0)),
+        Quaternion::from_angle_y(Rad(3.0)),
+        Quaternion::from_angle_z(Rad(3.0)),
+        Quaternion::from_axis_angle(cgmath::Vector3::new(1.0, 1.0, 0.0).normalize(), Rad(2.0)),
+    ];
+    for expected in rotations {
+        let actual = matrix_to_quaternion(&Matrix4::from(expected));
+        // q and -q are the same rotation
+        assert!(actual.dot(expected).abs() > 0.9999);
+    }
+}
+
+#[test]
+fn rect_test() {
+    let rect = Rect::from_tuples((4.0, -1.0), (-2.0, 3.0));
+    assert_eq!(rect.width(), 6.0);
+    assert_eq!(rect.height(), 4.0);
+    assert_eq!(rect.center(), (1.0, 1.0));
+}
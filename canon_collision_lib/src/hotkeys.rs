@@ -0,0 +1,174 @@
+use strum::IntoEnumIterator;
+use treeflection::{Node, NodeRunner, NodeToken};
+#[cfg(feature = "window")]
+use winit::event::VirtualKeyCode;
+#[cfg(feature = "window")]
+use winit_input_helper::WinitInputHelper;
+
+/// Declares the names of every key that can be bound to a hotkey, along with their winit key codes when built with the window feature
+macro_rules! bindable_keys {
+    ($(($name:literal, $key:ident)),* $(,)?) => {
+        const KEY_NAMES: &[&str] = &[$($name),*];
+        #[cfg(feature = "window")]
+        const KEYS: &[(&str, VirtualKeyCode)] = &[$(($name, VirtualKeyCode::$key)),*];
+    };
+}
+
+// Keys that can be bound to a hotkey, stored by name in the config file.
+#[rustfmt::skip]
+bindable_keys![
+    ("F1", F1), ("F2", F2), ("F3", F3), ("F4", F4),
+    ("F5", F5), ("F6", F6), ("F7", F7), ("F8", F8),
+    ("F9", F9), ("F10", F10), ("F11", F11), ("F12", F12),
+    ("A", A), ("B", B), ("C", C), ("D", D),
+    ("E", E), ("F", F), ("G", G), ("H", H),
+    ("I", I), ("J", J), ("K", K), ("L", L),
+    ("M", M), ("N", N), ("O", O), ("P", P),
+    ("Q", Q), ("R", R), ("S", S), ("T", T),
+    ("U", U), ("V", V), ("W", W), ("X", X),
+    ("Y", Y), ("Z", Z),
+    ("0", Key0), ("1", Key1), ("2", Key2), ("3", Key3),
+    ("4", Key4), ("5", Key5), ("6", Key6), ("7", Key7),
+    ("8", Key8), ("9", Key9),
+    ("Insert", Insert), ("Delete", Delete),
+    ("Home", Home), ("End", End),
+    ("PageUp", PageUp), ("PageDown", PageDown),
+    ("PrintScreen", Snapshot), ("ScrollLock", Scroll), ("Pause", Pause),
+    ("Backspace", Back),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug, EnumIter)]
+pub enum HotkeyAction {
+    Fullscreen,
+    Screenshot,
+    Pause,
+    Debug,
+    Rewind,
+    Minimap,
+}
+
+/// Keyboard shortcuts that work from anywhere in the game.
+/// Not active while the command line or the pause editor is open, as they have their own keybindings.
+#[derive(Clone, Serialize, Deserialize, Node)]
+#[serde(default)]
+pub struct Hotkeys {
+    pub fullscreen: String,
+    pub screenshot: String,
+    pub pause: String,
+    pub debug: String,
+    /// Held to rewind local games, when the rules allow it
+    pub rewind: String,
+    /// Cycles the configured zoom level of the minimap, used by players that have not picked their own from the pause menu
+    pub minimap: String,
+}
+
+impl Default for Hotkeys {
+    /// The defaults avoid the function keys, letters and numbers used by the editor, camera and replay controls
+    fn default() -> Hotkeys {
+        Hotkeys {
+            fullscreen: "PageUp".into(),
+            screenshot: "PageDown".into(),
+            pause: "P".into(),
+            debug: "Insert".into(),
+            rewind: "T".into(),
+            minimap: "O".into(),
+        }
+    }
+}
+
+impl Hotkeys {
+    pub fn get(&self, action: HotkeyAction) -> &str {
+        match action {
+            HotkeyAction::Fullscreen => &self.fullscreen,
+            HotkeyAction::Screenshot => &self.screenshot,
+            HotkeyAction::Pause => &self.pause,
+            HotkeyAction::Debug => &self.debug,
+            HotkeyAction::Rewind => &self.rewind,
+            HotkeyAction::Minimap => &self.minimap,
+        }
+    }
+
+    fn get_mut(&mut self, action: HotkeyAction) -> &mut String {
+        match action {
+            HotkeyAction::Fullscreen => &mut self.fullscreen,
+            HotkeyAction::Screenshot => &mut self.screenshot,
+            HotkeyAction::Pause => &mut self.pause,
+            HotkeyAction::Debug => &mut self.debug,
+            HotkeyAction::Rewind => &mut self.rewind,
+            HotkeyAction::Minimap => &mut self.minimap,
+        }
+    }
+
+    #[cfg(feature = "window")]
+    pub fn pressed(&self, action: HotkeyAction, os_input: &WinitInputHelper) -> bool {
+        KEYS.iter()
+            .find(|(name, _)| *name == self.get(action))
+            .map_or(false, |(_, key)| os_input.key_pressed_os(*key))
+    }
+
+    #[cfg(feature = "window")]
+    pub fn held(&self, action: HotkeyAction, os_input: &WinitInputHelper) -> bool {
+        KEYS.iter()
+            .find(|(name, _)| *name == self.get(action))
+            .map_or(false, |(_, key)| os_input.key_held(*key))
+    }
+
+    /// Binds the key to the action, fails if the key is already used by another action.
+    pub fn set(&mut self, action: HotkeyAction, key: &str) -> Result<(), String> {
+        if !KEY_NAMES.contains(&key) {
+            return Err(format!("{} is not a valid hotkey", key));
+        }
+        for other in HotkeyAction::iter() {
+            if other != action && self.get(other) == key {
+                return Err(format!("{} is already bound to {:?}", key, other));
+            }
+        }
+        *self.get_mut(action) = key.to_string();
+        Ok(())
+    }
+
+    /// Returns a description of every action bound to an invalid key or the same key as another action.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = vec![];
+        let actions: Vec<_> = HotkeyAction::iter().collect();
+        for (i, action) in actions.iter().enumerate() {
+            let key = self.get(*action);
+            if !KEY_NAMES.contains(&key) {
+                conflicts.push(format!("{:?} is bound to invalid key {}", action, key));
+            }
+            for other in &actions[i + 1..] {
+                if self.get(*other) == key {
+                    conflicts.push(format!(
+                        "{:?} and {:?} are both bound to {}",
+                        action, other, key
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+/// Returns the name of a bindable key that was pressed this frame
+#[cfg(feature = "window")]
+pub fn key_pressed(os_input: &WinitInputHelper) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(_, key)| os_input.key_pressed_os(*key))
+        .map(|(name, _)| *name)
+}
+
+#[test]
+fn hotkey_conflicts_test() {
+    let mut hotkeys = Hotkeys::default();
+    assert!(hotkeys.conflicts().is_empty());
+
+    assert!(hotkeys.set(HotkeyAction::Pause, "PageUp").is_err());
+    assert!(hotkeys.set(HotkeyAction::Pause, "NotAKey").is_err());
+    assert_eq!(hotkeys.pause, "P");
+
+    assert!(hotkeys.set(HotkeyAction::Pause, "F10").is_ok());
+    assert_eq!(hotkeys.pause, "F10");
+
+    hotkeys.debug = "F10".into();
+    assert_eq!(hotkeys.conflicts().len(), 1);
+}
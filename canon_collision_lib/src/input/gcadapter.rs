@@ -1,5 +1,5 @@
 use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, Sender};
 use std::thread;
 use std::time::Duration;
 
@@ -8,8 +8,16 @@ use rusb::{Context, DeviceHandle, Error, UsbContext};
 use super::filter;
 use super::state::{ControllerInput, Deadzone};
 
+/// Sent to the thread that communicates with the adapter
+enum AdapterMessage {
+    Rumble([bool; 4]),
+    ResetDeadzones,
+}
+
 pub struct GCAdapter {
     receiver: Receiver<[ControllerInput; 4]>,
+    sender: Sender<AdapterMessage>,
+    previous_rumble: [bool; 4],
     previous_inputs: [ControllerInput; 4],
 }
 
@@ -48,12 +56,17 @@ impl GCAdapter {
 
         adapter_handles
             .into_iter()
-            .map(|handle| GCAdapter {
-                receiver: run_in_thread(GCAdapterBackend {
+            .map(|handle| {
+                let (receiver, sender) = run_in_thread(GCAdapterBackend {
                     handle,
                     deadzones: Deadzone::empty4(),
-                }),
-                previous_inputs: Default::default(),
+                });
+                GCAdapter {
+                    receiver,
+                    sender,
+                    previous_rumble: [false; 4],
+                    previous_inputs: Default::default(),
+                }
             })
             .collect()
     }
@@ -72,6 +85,20 @@ impl GCAdapter {
         &self.previous_inputs
     }
 
+    /// The motor of a GC controller can only be switched on or off, so any strength above 0.0 switches it on
+    pub fn set_rumble(&mut self, strengths: [f32; 4]) {
+        let rumble = strengths.map(|x| x > 0.0);
+        if rumble != self.previous_rumble {
+            self.previous_rumble = rumble;
+            self.sender.send(AdapterMessage::Rumble(rumble)).ok();
+        }
+    }
+
+    /// Deadzones are recalibrated from the next input read of each port
+    pub fn reset_deadzones(&mut self) {
+        self.sender.send(AdapterMessage::ResetDeadzones).ok();
+    }
+
     fn handle_open_error(e: Error) {
         let access_solution = if cfg!(target_os = "linux") {
             r#":
@@ -110,14 +137,23 @@ impl GCAdapter {
     }
 }
 
-fn run_in_thread(mut backend: GCAdapterBackend) -> Receiver<[ControllerInput; 4]> {
+fn run_in_thread(
+    mut backend: GCAdapterBackend,
+) -> (Receiver<[ControllerInput; 4]>, Sender<AdapterMessage>) {
     let (input_tx, input_rx) = mpsc::channel();
+    let (message_tx, message_rx) = mpsc::channel();
     thread::spawn(move || loop {
+        for message in message_rx.try_iter() {
+            match message {
+                AdapterMessage::Rumble(rumble) => backend.write_rumble(rumble),
+                AdapterMessage::ResetDeadzones => backend.deadzones = Deadzone::empty4(),
+            }
+        }
         if input_tx.send(backend.read()).is_err() {
             return;
         }
     });
-    input_rx
+    (input_rx, message_tx)
 }
 
 struct GCAdapterBackend {
@@ -126,6 +162,22 @@ struct GCAdapterBackend {
 }
 
 impl GCAdapterBackend {
+    fn write_rumble(&mut self, rumble: [bool; 4]) {
+        let payload = [
+            0x11,
+            rumble[0] as u8,
+            rumble[1] as u8,
+            rumble[2] as u8,
+            rumble[3] as u8,
+        ];
+        if let Err(e) = self
+            .handle
+            .write_interrupt(0x2, &payload, Duration::new(1, 0))
+        {
+            warn!("GC adapter: Failed to set rumble: {}", e);
+        }
+    }
+
     /// Add 4 GC adapter controllers to inputs
     fn read(&mut self) -> [ControllerInput; 4] {
         let mut inputs = [ControllerInput::default(); 4];
@@ -1,6 +1,7 @@
 use std::f32;
+use std::time::Duration;
 
-use gilrs_core::{EvCode, EventType, Gamepad, Gilrs};
+use gilrs_core::{EvCode, EventType, FfDevice, Gamepad, Gilrs};
 use uuid::Uuid;
 
 use super::filter;
@@ -11,12 +12,15 @@ pub(crate) struct GenericController {
     pub index: usize,
     pub state: ControllerInput,
     pub deadzone: Deadzone,
+    /// None when the controller does not support force feedback
+    ff_device: Option<FfDevice>,
+    previous_rumble: f32,
 }
 
 impl GenericController {
     pub fn get_controllers(
         gilrs: &mut Gilrs,
-        existing_controllers: &[&GenericController],
+        existing_controllers: &[GenericController],
     ) -> Vec<GenericController> {
         let mut controllers = vec![];
         // find new generic controllers
@@ -37,6 +41,8 @@ impl GenericController {
                         index,
                         state,
                         deadzone: Deadzone::empty(),
+                        ff_device: gamepad.ff_device(),
+                        previous_rumble: 0.0,
                     });
                 }
             }
@@ -44,6 +50,16 @@ impl GenericController {
         controllers
     }
 
+    pub fn set_rumble(&mut self, strength: f32) {
+        if strength != self.previous_rumble {
+            self.previous_rumble = strength;
+            if let Some(ff_device) = &mut self.ff_device {
+                let magnitude = (strength.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+                ff_device.set_ff_state(magnitude, magnitude, Duration::from_millis(0));
+            }
+        }
+    }
+
     /// Add a single controller to inputs, reading from the passed gamepad
     pub fn read(
         &mut self,
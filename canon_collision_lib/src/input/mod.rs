@@ -1,35 +1,70 @@
+#[cfg(any(feature = "gc_adapter", feature = "gilrs", feature = "web_gamepad"))]
 mod filter;
+#[cfg(feature = "gc_adapter")]
 pub mod gcadapter;
+#[cfg(feature = "gilrs")]
 pub mod generic;
 pub mod maps;
+#[cfg(feature = "gilrs")]
+pub mod rebind;
 pub mod state;
+#[cfg(feature = "web_gamepad")]
+pub mod web;
 
+#[cfg(feature = "gc_adapter")]
 use gcadapter::GCAdapter;
+#[cfg(feature = "gilrs")]
 use generic::GenericController;
-use maps::ControllerMaps;
-use state::{Button, ControllerInput, Deadzone, PlayerInput, Stick, Trigger};
-
-use gilrs_core::{Event, Gilrs};
+#[cfg(feature = "gilrs")]
+use maps::{ControllerMap, ControllerMaps};
+#[cfg(feature = "gilrs")]
+use state::Deadzone;
+use state::{Button, ControllerInput, PlayerInput, Stick, Trigger};
+
+#[cfg(feature = "gilrs")]
+use gilrs_core::{Event, EventType, Gilrs};
+#[cfg(feature = "gc_adapter")]
 use rusb::Context;
 
+use crate::config::TriggerCurve;
 use crate::network::{Netplay, NetplayState};
 
-enum InputSource {
-    GCAdapter(GCAdapter),
-    GenericController(GenericController),
-}
-
 pub struct Input {
     // game past and (potentially) future inputs, frame 0 has index 2
     // structure: frames Vec<controllers Vec<ControllerInput>>
     game_inputs: Vec<Vec<ControllerInput>>,
     current_inputs: Vec<ControllerInput>, // inputs for this frame
-    prev_start: bool,
-    input_sources: Vec<InputSource>,
+    /// Inputs of frames skipped by a slowed down game, included in the next frame the game simulates
+    skipped_inputs: Vec<ControllerInput>,
+    // inputs of every peer including the local machine, index 0 is netplay frame 1
+    // inputs that have not been received yet are predicted by repeating the last confirmed input of that peer
+    // structure: peers Vec<frames Vec<controllers Vec<ControllerInput>>>
+    netplay_inputs: Vec<Vec<Vec<ControllerInput>>>,
+    // the number of frames at the start of each peers netplay_inputs that are confirmed, the rest are predicted
+    netplay_confirmed_frames: Vec<usize>,
+    // add to a game/menu frame to get the netplay frame
+    netplay_frame_offset: i64,
+    prev_inputs: Vec<ControllerInput>, // inputs for the previous frame
+    // local controllers are read from every GC adapter port and then from every generic controller
+    #[cfg(feature = "gc_adapter")]
+    gc_adapters: Vec<GCAdapter>,
+    #[cfg(feature = "gc_adapter")]
     _rusb_context: Context,
+    #[cfg(feature = "gilrs")]
+    generic_controllers: Vec<GenericController>,
+    #[cfg(feature = "gilrs")]
     gilrs: Gilrs,
+    #[cfg(feature = "gilrs")]
     controller_maps: ControllerMaps,
+    #[cfg(feature = "gilrs")]
     pub events: Vec<Event>,
+    /// The inputs of this controller replace the inputs of every other local controller, for demos and testing alone.
+    /// Applied before the inputs are recorded so replays stay in sync, never applied during netplay.
+    pub mirror_controller: Option<usize>,
+    /// Applied to the triggers of each local controller, indexed the same as the inputs read in `step`
+    trigger_curves: Vec<TriggerCurve>,
+    /// The trigger values of each local controller before its trigger curve was applied
+    raw_triggers: Vec<(f32, f32)>,
 }
 
 // In/Out is from perspective of computer
@@ -38,23 +73,34 @@ pub struct Input {
 
 impl Input {
     pub fn new() -> Input {
+        #[cfg(feature = "gc_adapter")]
         let mut _rusb_context = Context::new().unwrap();
-        let gilrs = Gilrs::new().unwrap();
-        let controller_maps = ControllerMaps::load();
-        let input_sources = GCAdapter::get_adapters(&mut _rusb_context)
-            .into_iter()
-            .map(InputSource::GCAdapter)
-            .collect();
+        #[cfg(feature = "gc_adapter")]
+        let gc_adapters = GCAdapter::get_adapters(&mut _rusb_context);
 
         Input {
             game_inputs: vec![],
             current_inputs: vec![],
-            events: vec![],
-            prev_start: false,
-            input_sources,
+            skipped_inputs: vec![],
+            netplay_inputs: vec![],
+            netplay_confirmed_frames: vec![],
+            netplay_frame_offset: 0,
+            prev_inputs: vec![],
+            #[cfg(feature = "gc_adapter")]
+            gc_adapters,
+            #[cfg(feature = "gc_adapter")]
             _rusb_context,
-            gilrs,
-            controller_maps,
+            #[cfg(feature = "gilrs")]
+            generic_controllers: vec![],
+            #[cfg(feature = "gilrs")]
+            gilrs: Gilrs::new().unwrap(),
+            #[cfg(feature = "gilrs")]
+            controller_maps: ControllerMaps::load(),
+            #[cfg(feature = "gilrs")]
+            events: vec![],
+            mirror_controller: None,
+            trigger_curves: vec![],
+            raw_triggers: vec![],
         }
     }
 
@@ -69,54 +115,54 @@ impl Input {
         // clear deadzones so they will be set at next read
         // TODO: uh why is this even like this, surely just implement deadzone reset on controller plugin (maybe it was for netplay???)
         if reset_deadzones {
-            for source in &mut self.input_sources {
-                match source {
-                    InputSource::GCAdapter(_) => {} // TODO: send message to thread or delete all manual reset_deadzone logic adapter.deadzones = Deadzone::empty4()
-                    InputSource::GenericController(controller) => {
-                        controller.deadzone = Deadzone::empty()
-                    }
-                }
+            #[cfg(feature = "gc_adapter")]
+            for adapter in &mut self.gc_adapters {
+                adapter.reset_deadzones();
             }
-        }
-
-        self.events.clear();
-        while let Some(ev) = self.gilrs.next_event() {
-            self.events.push(ev);
-        }
-        self.events.sort_by_key(|x| x.time);
-
-        let mut generic_controllers = vec![];
-        for input_source in &self.input_sources {
-            if let InputSource::GenericController(controller) = input_source {
-                generic_controllers.push(controller);
+            #[cfg(feature = "gilrs")]
+            for controller in &mut self.generic_controllers {
+                controller.deadzone = Deadzone::empty();
             }
         }
-        for controller in GenericController::get_controllers(&mut self.gilrs, &generic_controllers)
+
+        #[cfg(feature = "gilrs")]
         {
-            self.input_sources
-                .push(InputSource::GenericController(controller));
+            self.events.clear();
+            while let Some(ev) = self.gilrs.next_event() {
+                self.events.push(ev);
+            }
+            self.events.sort_by_key(|x| x.time);
+
+            let new_controllers =
+                GenericController::get_controllers(&mut self.gilrs, &self.generic_controllers);
+            self.generic_controllers.extend(new_controllers);
         }
 
         // read input from controllers
         let mut inputs: Vec<ControllerInput> = Vec::new();
-        for source in &mut self.input_sources {
-            match source {
-                InputSource::GCAdapter(adapter) => {
-                    inputs.extend_from_slice(adapter.get_inputs());
-                }
-                InputSource::GenericController(controller) => {
-                    let events = self
-                        .events
-                        .iter()
-                        .filter(|x| x.id == controller.index)
-                        .map(|x| &x.event)
-                        .cloned()
-                        .collect();
-                    let gamepad = &self.gilrs.gamepad(controller.index).unwrap(); // Old gamepads stick around forever so its fine to unwrap.
-                    let maps = &self.controller_maps.maps;
-                    inputs.push(controller.read(maps, events, gamepad));
-                }
-            }
+        #[cfg(feature = "gc_adapter")]
+        for adapter in &mut self.gc_adapters {
+            inputs.extend_from_slice(adapter.get_inputs());
+        }
+        #[cfg(feature = "gilrs")]
+        for controller in &mut self.generic_controllers {
+            let events = self
+                .events
+                .iter()
+                .filter(|x| x.id == controller.index)
+                .map(|x| &x.event)
+                .cloned()
+                .collect();
+            let gamepad = &self.gilrs.gamepad(controller.index).unwrap(); // Old gamepads stick around forever so its fine to unwrap.
+            let maps = &self.controller_maps.maps;
+            inputs.push(controller.read(maps, events, gamepad));
+        }
+
+        // Triggers are reshaped before being sent to peers, so that each peer only needs its own curves
+        self.raw_triggers = inputs.iter().map(|x| (x.l_trigger, x.r_trigger)).collect();
+        for (input, curve) in inputs.iter_mut().zip(&self.trigger_curves) {
+            input.l_trigger = curve.apply(input.l_trigger);
+            input.r_trigger = curve.apply(input.r_trigger);
         }
 
         if netplay.skip_frame() {
@@ -127,6 +173,13 @@ impl Input {
             netplay.send_controller_inputs(inputs.clone());
         }
 
+        // mirror inputs before appending AI inputs, so that CPUs keep their own inputs
+        if let NetplayState::Offline = netplay.state() {
+            if let Some(source) = self.mirror_controller.and_then(|x| inputs.get(x).cloned()) {
+                inputs.fill(source);
+            }
+        }
+
         // append AI inputs
         inputs.extend_from_slice(ai_inputs);
 
@@ -137,16 +190,149 @@ impl Input {
             }
         }
 
-        self.prev_start = self.current_inputs.iter().any(|x| x.start);
-        self.current_inputs = inputs;
+        self.prev_inputs = std::mem::replace(&mut self.current_inputs, inputs);
 
         debug!("step");
     }
 
+    /// Returns a message for each generic controller that was connected or disconnected during the last step
+    #[cfg(feature = "gilrs")]
+    pub fn connection_messages(&self) -> Vec<String> {
+        self.events
+            .iter()
+            .filter_map(|event| {
+                let name = self
+                    .gilrs
+                    .gamepad(event.id)
+                    .map(|x| x.name())
+                    .unwrap_or("Controller");
+                match event.event {
+                    EventType::Connected => Some(format!("{} connected", name)),
+                    EventType::Disconnected => Some(format!("{} disconnected", name)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Without the gilrs feature there are no generic controllers to connect
+    #[cfg(not(feature = "gilrs"))]
+    pub fn connection_messages(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// The name of each local controller, indexed the same as the inputs read in `step`
+    pub fn controller_names(&self) -> Vec<String> {
+        #[allow(unused_mut)] // Needed for builds without controller support
+        let mut names = vec![];
+        #[cfg(feature = "gc_adapter")]
+        for _ in &self.gc_adapters {
+            for port in 1..=4 {
+                names.push(format!("GC adapter port {}", port));
+            }
+        }
+        #[cfg(feature = "gilrs")]
+        for controller in &self.generic_controllers {
+            names.push(
+                self.gilrs
+                    .gamepad(controller.index)
+                    .map(|x| x.name().to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        names
+    }
+
+    /// The number of local controllers read from GC adapters, they come before the generic controllers
+    #[cfg(all(feature = "gilrs", feature = "gc_adapter"))]
+    fn gc_adapter_controllers(&self) -> usize {
+        self.gc_adapters.len() * 4
+    }
+
+    #[cfg(all(feature = "gilrs", not(feature = "gc_adapter")))]
+    fn gc_adapter_controllers(&self) -> usize {
+        0
+    }
+
+    /// Returns the gilrs index of the local controller, None if it is not a generic controller
+    #[cfg(feature = "gilrs")]
+    fn generic_gamepad_index(&self, controller: usize) -> Option<usize> {
+        controller
+            .checked_sub(self.gc_adapter_controllers())
+            .and_then(|i| self.generic_controllers.get(i))
+            .map(|x| x.index)
+    }
+
+    /// Recalibrates the deadzones of the local controller from the current position of its sticks and triggers.
+    /// The deadzones of every port on a GC adapter are recalibrated together.
+    #[allow(unused_variables)] // Needed for builds without controller support
+    pub fn reset_deadzone(&mut self, controller: usize) {
+        #[cfg(feature = "gc_adapter")]
+        if let Some(adapter) = self.gc_adapters.get_mut(controller / 4) {
+            adapter.reset_deadzones();
+        }
+        #[cfg(feature = "gilrs")]
+        if let Some(i) = controller.checked_sub(self.gc_adapter_controllers()) {
+            if let Some(generic) = self.generic_controllers.get_mut(i) {
+                generic.deadzone = Deadzone::empty();
+            }
+        }
+    }
+
+    /// Sets the trigger curve of each local controller, indexed the same as the inputs read in `step`.
+    /// Controllers without an entry use their triggers as is.
+    pub fn set_trigger_curves(&mut self, curves: &[TriggerCurve]) {
+        if self.trigger_curves != curves {
+            self.trigger_curves = curves.to_vec();
+        }
+    }
+
+    /// The (l, r) trigger values of the local controller before its trigger curve was applied
+    pub fn raw_triggers(&self, controller: usize) -> (f32, f32) {
+        self.raw_triggers
+            .get(controller)
+            .cloned()
+            .unwrap_or((0.0, 0.0))
+    }
+
+    /// Replaces the map of the same controller and saves all maps to disk
+    #[cfg(feature = "gilrs")]
+    pub fn save_controller_map(&mut self, map: ControllerMap) {
+        let maps = &mut self.controller_maps.maps;
+        maps.retain(|x| !(x.name == map.name && x.uuid == map.uuid && x.os == map.os));
+        maps.push(map);
+        self.controller_maps.save();
+    }
+
+    /// Sets the rumble strength of each local controller, indexed the same as the inputs read in `step`.
+    /// Controllers without an entry stop rumbling.
+    #[allow(unused_variables)] // Needed for builds without controller support
+    pub fn set_rumble(&mut self, strengths: &[f32]) {
+        let strength = |i: usize| strengths.get(i).cloned().unwrap_or(0.0);
+        #[cfg(feature = "gc_adapter")]
+        for (adapter_i, adapter) in self.gc_adapters.iter_mut().enumerate() {
+            let i = adapter_i * 4;
+            adapter.set_rumble([
+                strength(i),
+                strength(i + 1),
+                strength(i + 2),
+                strength(i + 3),
+            ]);
+        }
+        #[cfg(feature = "gilrs")]
+        {
+            let first = self.gc_adapter_controllers();
+            for (i, controller) in self.generic_controllers.iter_mut().enumerate() {
+                controller.set_rumble(strength(first + i));
+            }
+        }
+    }
+
     /// Reset the game input history
     pub fn reset_history(&mut self) {
         self.game_inputs.clear();
-        self.prev_start = false;
+        self.skipped_inputs.clear();
+        self.prev_inputs.clear();
     }
 
     /// Set the game input history
@@ -159,6 +345,11 @@ impl Input {
         self.game_inputs.clone()
     }
 
+    /// Borrow the game input history
+    pub fn history(&self) -> &[Vec<ControllerInput>] {
+        &self.game_inputs
+    }
+
     /// Call this once from the game update logic only
     /// Throws out all future history that may exist
     pub fn game_update(&mut self, frame: usize) {
@@ -166,35 +357,132 @@ impl Input {
             self.game_inputs.pop();
         }
 
-        self.game_inputs.push(self.current_inputs.clone());
+        let mut inputs = self.current_inputs.clone();
+        for (input, skipped) in inputs.iter_mut().zip(self.skipped_inputs.drain(..)) {
+            input.include_buttons(&skipped);
+        }
+        self.game_inputs.push(inputs);
+    }
+
+    /// Call this instead of `game_update` when the game does not simulate a frame this step, e.g. while in slow motion.
+    /// Buttons held during the skipped frame are held on the next frame the game simulates, so quick presses are not dropped.
+    pub fn game_skip(&mut self) {
+        if self.skipped_inputs.is_empty() {
+            self.skipped_inputs = self.current_inputs.clone();
+        } else {
+            for (skipped, input) in self.skipped_inputs.iter_mut().zip(&self.current_inputs) {
+                skipped.include_buttons(input);
+            }
+        }
+    }
+
+    /// Releases the buttons used to control a paused game on the frame, so the fighter of the controller that pressed them does not also use them
+    pub fn release_frame_controls(&mut self, frame: usize, controller: usize) {
+        if let Some(input) = self
+            .game_inputs
+            .get_mut(frame - 1)
+            .and_then(|x| x.get_mut(controller))
+        {
+            input.z = false;
+            input.r = false;
+            input.r_trigger = 0.0;
+        }
     }
 
     /// Call this once from netplay game/menu update logic only (instead of game_update)
-    pub fn netplay_update(&mut self) {
+    /// Returns the first frame that needs to be stepped/restepped.
+    /// Frames before it were stepped with inputs that have since been confirmed to be correct.
+    pub fn netplay_update(&mut self, netplay: &Netplay) -> usize {
         self.game_inputs.push(self.current_inputs.clone());
+        let current_frame = self.game_inputs.len();
+
+        let netplay_frame = netplay.frame();
+        if netplay_frame == 0 {
+            self.netplay_inputs.clear();
+            self.netplay_confirmed_frames.clear();
+            return current_frame;
+        }
+        self.netplay_frame_offset = netplay_frame as i64 - current_frame as i64;
+
+        let number_of_peers = netplay.number_of_peers();
+        self.netplay_inputs.resize(number_of_peers, vec![]);
+        self.netplay_confirmed_frames.resize(number_of_peers, 0);
+
+        let local_index = netplay.local_index();
+        let mut remote_inputs = netplay.confirmed_inputs.iter();
+        let mut mispredicted_frame = netplay_frame;
+        for peer in 0..number_of_peers {
+            let inputs = &mut self.netplay_inputs[peer];
+            let confirmed_frames = &mut self.netplay_confirmed_frames[peer];
+
+            // spectators have no local inputs, every peer is remote
+            if peer == local_index && !netplay.is_spectator() {
+                // matches the inputs sent to peers by `Netplay::send_controller_inputs`
+                let mut local_inputs = self.current_inputs.clone();
+                if let Some(players) = netplay.peer_players(peer) {
+                    local_inputs.resize(players, ControllerInput::default());
+                }
+                inputs.resize(netplay_frame - 1, vec![]);
+                inputs.push(local_inputs);
+                *confirmed_frames = netplay_frame;
+                continue;
+            }
+
+            // replace predictions with the inputs we have since received
+            let confirmed = remote_inputs.next().unwrap();
+            for (i, confirmed_input) in confirmed.iter().enumerate().skip(*confirmed_frames) {
+                match inputs.get_mut(i) {
+                    Some(input) => {
+                        if input != confirmed_input {
+                            mispredicted_frame = mispredicted_frame.min(i + 1);
+                            *input = confirmed_input.clone();
+                        }
+                    }
+                    None => inputs.push(confirmed_input.clone()),
+                }
+            }
+            *confirmed_frames = confirmed.len();
+
+            // predict the inputs we have not received yet, before any are received each of the peer's players is unplugged
+            let prediction = inputs[..*confirmed_frames]
+                .last()
+                .cloned()
+                .unwrap_or_else(|| {
+                    vec![ControllerInput::default(); netplay.peer_players(peer).unwrap_or(0)]
+                });
+            for (i, input) in inputs.iter_mut().enumerate().skip(*confirmed_frames) {
+                if *input != prediction {
+                    mispredicted_frame = mispredicted_frame.min(i + 1);
+                    *input = prediction.clone();
+                }
+            }
+            while inputs.len() < netplay_frame {
+                inputs.push(prediction.clone());
+            }
+        }
+
+        // An input is not used until input_delay frames after it was made
+        let first_frame =
+            mispredicted_frame as i64 + netplay.input_delay() as i64 - self.netplay_frame_offset;
+        first_frame.clamp(1, current_frame as i64) as usize
     }
 
     /// Return game inputs at specified index into history
     pub fn players_no_log(&self, frame: usize, netplay: &Netplay) -> Vec<PlayerInput> {
         let mut result_inputs: Vec<PlayerInput> = vec![];
 
-        let local_index = netplay.local_index();
-        let mut peer_offset = 0;
-        let peers_inputs = &netplay.confirmed_inputs;
-        for i in 0..netplay.number_of_peers() {
-            if i == local_index {
-                peer_offset = 1;
-
-                for i in 0..self.current_inputs.len() {
-                    let inputs = self.get_8frames_of_input(&self.game_inputs, i, frame as i64);
-                    result_inputs.push(Input::controller_inputs_to_player_input(inputs));
-                }
-            } else {
-                let peer_inputs = &peers_inputs[i - peer_offset];
+        if self.netplay_inputs.is_empty() {
+            for i in 0..self.current_inputs.len() {
+                let inputs = Input::get_8frames_of_input(&self.game_inputs, i, frame as i64);
+                result_inputs.push(Input::controller_inputs_to_player_input(inputs));
+            }
+        } else {
+            let netplay_frame =
+                frame as i64 + self.netplay_frame_offset - netplay.input_delay() as i64;
+            for peer_inputs in &self.netplay_inputs {
                 let num_controllers = peer_inputs.last().map_or(0, |x| x.len());
                 for i in 0..num_controllers {
-                    let inputs =
-                        self.get_8frames_of_input(&peer_inputs[..], i, netplay.frame() as i64);
+                    let inputs = Input::get_8frames_of_input(peer_inputs, i, netplay_frame);
                     result_inputs.push(Input::controller_inputs_to_player_input(inputs));
                 }
             }
@@ -218,6 +506,55 @@ impl Input {
         result_inputs
     }
 
+    /// Return the inputs at the specified index into `history`, which is structured the same as the game input history.
+    /// Unlike `players` this needs no controllers, the number of players is the number of controllers in the latest frame.
+    pub fn players_from_history(
+        history: &[Vec<ControllerInput>],
+        frame: usize,
+    ) -> Vec<PlayerInput> {
+        let num_controllers = history.last().map_or(0, |x| x.len());
+        (0..num_controllers)
+            .map(|i| {
+                let inputs = Input::get_8frames_of_input(history, i, frame as i64);
+                Input::controller_inputs_to_player_input(inputs)
+            })
+            .collect()
+    }
+
+    /// Returns the netplay peer that the controller of the players inputs belongs to and its index on that peer.
+    /// `controller` indexes the inputs returned by `players`, which lists every controller of each peer in turn.
+    /// Returns None when offline.
+    fn netplay_controller(&self, controller: usize) -> Option<(usize, usize)> {
+        let mut first_controller = 0;
+        for (peer, peer_inputs) in self.netplay_inputs.iter().enumerate() {
+            let num_controllers = peer_inputs.last().map_or(0, |x| x.len());
+            if controller < first_controller + num_controllers {
+                return Some((peer, controller - first_controller));
+            }
+            first_controller += num_controllers;
+        }
+        None
+    }
+
+    /// Returns the netplay peer that the controller of the players inputs belongs to, None when offline
+    pub fn netplay_peer(&self, controller: usize) -> Option<usize> {
+        self.netplay_controller(controller).map(|x| x.0)
+    }
+
+    /// Converts an index into the inputs returned by `players` to the index of the local controller it came from.
+    /// Returns None if the controller belongs to another netplay peer.
+    pub fn local_controller(&self, controller: usize, netplay: &Netplay) -> Option<usize> {
+        if self.netplay_inputs.is_empty() {
+            Some(controller)
+        } else if netplay.is_spectator() {
+            None
+        } else {
+            self.netplay_controller(controller)
+                .filter(|(peer, _)| *peer == netplay.local_index())
+                .map(|(_, local)| local)
+        }
+    }
+
     #[rustfmt::skip]
     fn controller_inputs_to_player_input(inputs: Vec<ControllerInput>) -> PlayerInput {
         if inputs[0].plugged_in {
@@ -255,7 +592,6 @@ impl Input {
     /// converts frames Vec<controllers Vec<ControllerInput>> into frames Vec<ControllerInput> for the specified controller_i
     /// Output must be 8 frames long, any missing frames due to either netplay lag or the game just starting are filled in
     fn get_8frames_of_input(
-        &self,
         game_inputs: &[Vec<ControllerInput>],
         controller_i: usize,
         frame: i64,
@@ -296,7 +632,33 @@ impl Input {
 
     /// Check for start button press
     pub fn start_pressed(&mut self) -> bool {
-        !self.prev_start && self.current_inputs.iter().any(|x| x.start)
+        !self.prev_inputs.iter().any(|x| x.start) && self.current_inputs.iter().any(|x| x.start)
+    }
+
+    /// The first controller to press start this frame
+    pub fn start_pressed_controller(&self) -> Option<usize> {
+        self.current_inputs
+            .iter()
+            .enumerate()
+            .position(|(i, x)| x.start && !self.prev_inputs.get(i).map_or(false, |x| x.start))
+    }
+
+    /// The input of every controller, built from this frame and the previous frame only
+    pub fn paused_players(&self) -> Vec<PlayerInput> {
+        self.current_inputs
+            .iter()
+            .enumerate()
+            .map(|(i, current)| {
+                let prev = self
+                    .prev_inputs
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(ControllerInput::empty);
+                let mut inputs = vec![*current];
+                inputs.extend(std::iter::repeat(prev).take(7));
+                Input::controller_inputs_to_player_input(inputs)
+            })
+            .collect()
     }
 
     /// button combination for quiting the game
@@ -307,3 +669,40 @@ impl Input {
             && self.start_pressed()
     }
 }
+
+#[test]
+fn players_from_history_test() {
+    let idle = ControllerInput {
+        plugged_in: true,
+        ..ControllerInput::empty()
+    };
+    let jump = ControllerInput { x: true, ..idle };
+    let history = vec![vec![idle, idle], vec![jump, idle], vec![jump, idle]];
+
+    assert!(Input::players_from_history(&[], 1).is_empty());
+    let players = Input::players_from_history(&history, 2);
+    assert_eq!(players.len(), 2);
+    assert!(players[0].x.press);
+    assert!(!players[1].x.value);
+    let players = Input::players_from_history(&history, 3);
+    assert!(players[0].x.value);
+    assert!(!players[0].x.press);
+}
+
+#[test]
+fn include_buttons_test() {
+    let mut input = ControllerInput {
+        plugged_in: true,
+        stick_x: 0.5,
+        ..ControllerInput::empty()
+    };
+    let skipped = ControllerInput {
+        a: true,
+        stick_x: -1.0,
+        ..ControllerInput::empty()
+    };
+    input.include_buttons(&skipped);
+    assert!(input.a);
+    assert!(!input.b);
+    assert_eq!(input.stick_x, 0.5);
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use gilrs_core::EventType;
+use uuid::Uuid;
+
+use super::generic::code_to_usize;
+use super::maps::{
+    AnalogDest, AnalogFilter, AnalogMap, ControllerMap, DigitalDest, DigitalFilter, DigitalMap, OS,
+};
+use super::Input;
+
+/// An axis is considered pushed once it is this far from its center, where -1.0 and 1.0 are its limits.
+const AXIS_PUSHED: f32 = 0.75;
+/// An axis must return within this distance of its center before it can be bound again.
+const AXIS_RELEASED: f32 = 0.5;
+
+#[derive(Clone, PartialEq)]
+pub enum RebindTarget {
+    Digital(DigitalDest),
+    Analog(AnalogDest),
+}
+
+impl RebindTarget {
+    pub fn all() -> Vec<RebindTarget> {
+        vec![
+            RebindTarget::Analog(AnalogDest::StickX),
+            RebindTarget::Analog(AnalogDest::StickY),
+            RebindTarget::Analog(AnalogDest::CStickX),
+            RebindTarget::Analog(AnalogDest::CStickY),
+            RebindTarget::Analog(AnalogDest::LTrigger),
+            RebindTarget::Analog(AnalogDest::RTrigger),
+            RebindTarget::Digital(DigitalDest::A),
+            RebindTarget::Digital(DigitalDest::B),
+            RebindTarget::Digital(DigitalDest::X),
+            RebindTarget::Digital(DigitalDest::Y),
+            RebindTarget::Digital(DigitalDest::Z),
+            RebindTarget::Digital(DigitalDest::L),
+            RebindTarget::Digital(DigitalDest::R),
+            RebindTarget::Digital(DigitalDest::Start),
+            RebindTarget::Digital(DigitalDest::Up),
+            RebindTarget::Digital(DigitalDest::Down),
+            RebindTarget::Digital(DigitalDest::Left),
+            RebindTarget::Digital(DigitalDest::Right),
+        ]
+    }
+
+    pub fn prompt(&self) -> &'static str {
+        match self {
+            RebindTarget::Analog(AnalogDest::StickX) => "Push the control stick fully right",
+            RebindTarget::Analog(AnalogDest::StickY) => "Push the control stick fully up",
+            RebindTarget::Analog(AnalogDest::CStickX) => "Push the C stick fully right",
+            RebindTarget::Analog(AnalogDest::CStickY) => "Push the C stick fully up",
+            RebindTarget::Analog(AnalogDest::LTrigger) => "Fully press the L trigger",
+            RebindTarget::Analog(AnalogDest::RTrigger) => "Fully press the R trigger",
+            RebindTarget::Digital(DigitalDest::A) => "Press A",
+            RebindTarget::Digital(DigitalDest::B) => "Press B",
+            RebindTarget::Digital(DigitalDest::X) => "Press X",
+            RebindTarget::Digital(DigitalDest::Y) => "Press Y",
+            RebindTarget::Digital(DigitalDest::Z) => "Press Z",
+            RebindTarget::Digital(DigitalDest::L) => "Press the L button",
+            RebindTarget::Digital(DigitalDest::R) => "Press the R button",
+            RebindTarget::Digital(DigitalDest::Start) => "Press start",
+            RebindTarget::Digital(DigitalDest::Up) => "Press d-pad up",
+            RebindTarget::Digital(DigitalDest::Down) => "Press d-pad down",
+            RebindTarget::Digital(DigitalDest::Left) => "Press d-pad left",
+            RebindTarget::Digital(DigitalDest::Right) => "Press d-pad right",
+        }
+    }
+
+    /// Triggers rest at one end of their axis instead of the center
+    fn is_trigger(&self) -> bool {
+        matches!(
+            self,
+            RebindTarget::Analog(AnalogDest::LTrigger) | RebindTarget::Analog(AnalogDest::RTrigger)
+        )
+    }
+}
+
+/// Builds a new `ControllerMap` for a generic controller by prompting for each input in turn.
+/// Only raw gilrs events of the controller being rebound are used, so its existing map does not get in the way.
+#[derive(Clone)]
+pub struct Rebind {
+    gamepad: usize,
+    map: ControllerMap,
+    targets: Vec<RebindTarget>,
+    /// Last value of each axis that has moved, from -1.0 to 1.0
+    axes: HashMap<usize, f32>,
+    /// Bound axes that must return to neutral before the next input is bound, along with whether they were bound as a trigger
+    held_axes: HashMap<usize, bool>,
+}
+
+impl Rebind {
+    /// Returns None if the controller is not a generic controller
+    pub fn new(input: &Input, controller: usize) -> Option<Rebind> {
+        let gamepad = input.generic_gamepad_index(controller)?;
+        let gamepad_info = input.gilrs.gamepad(gamepad)?;
+        let mut targets = RebindTarget::all();
+        targets.reverse();
+        Some(Rebind {
+            gamepad,
+            map: ControllerMap {
+                os: OS::get_current(),
+                uuid: Uuid::from_bytes(gamepad_info.uuid()),
+                name: gamepad_info.name().to_string(),
+                analog_maps: vec![],
+                digital_maps: vec![],
+            },
+            targets,
+            axes: HashMap::new(),
+            held_axes: HashMap::new(),
+        })
+    }
+
+    pub fn prompt(&self) -> &'static str {
+        self.targets.last().map(|x| x.prompt()).unwrap_or("")
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Skips the current input, leaving it unbound
+    pub fn skip(&mut self) {
+        self.targets.pop();
+    }
+
+    /// Call this once every frame, the events read by `Input::step` are used to bind the current input.
+    /// Once every input is bound the map is saved and replaces any existing map for the controller.
+    pub fn step(&mut self, input: &mut Input) {
+        let events: Vec<EventType> = input
+            .events
+            .iter()
+            .filter(|x| x.id == self.gamepad)
+            .map(|x| x.event)
+            .collect();
+        for event in events {
+            let target = match self.targets.last() {
+                Some(target) => target.clone(),
+                None => break,
+            };
+            let bound = match event {
+                EventType::ButtonPressed(code) if self.held_axes.is_empty() => {
+                    self.bind_button(&target, code_to_usize(&code));
+                    true
+                }
+                EventType::AxisValueChanged(value, code) => {
+                    let (min, max) = match input
+                        .gilrs
+                        .gamepad(self.gamepad)
+                        .and_then(|x| x.axis_info(code))
+                    {
+                        Some(info) => (info.min, info.max),
+                        None => continue,
+                    };
+                    let value = normalize_axis(value, min, max);
+                    let source = code_to_usize(&code);
+                    let rest = if target.is_trigger() { -1.0 } else { 0.0 };
+                    let previous = self.axes.insert(source, value).unwrap_or(rest);
+                    if let Some(trigger) = self.held_axes.get(&source) {
+                        if axis_neutral(*trigger, value) {
+                            self.held_axes.remove(&source);
+                        }
+                    }
+                    if self.held_axes.is_empty() && axis_pushed(&target, previous, value) {
+                        self.bind_axis(&target, source, min, max, value < 0.0);
+                        self.held_axes.insert(source, target.is_trigger());
+                        true
+                    } else {
+                        false
+                    }
+                }
+                _ => false,
+            };
+            if bound {
+                self.targets.pop();
+            }
+        }
+
+        if self.is_complete() {
+            input.save_controller_map(self.map.clone());
+        }
+    }
+
+    fn bind_button(&mut self, target: &RebindTarget, source: usize) {
+        match target {
+            RebindTarget::Digital(dest) => self.map.digital_maps.push(DigitalMap {
+                source,
+                dest: dest.clone(),
+                filter: DigitalFilter::default_digital(),
+            }),
+            RebindTarget::Analog(dest) => self.map.analog_maps.push(AnalogMap {
+                source,
+                dest: dest.clone(),
+                filter: AnalogFilter::default_digital(),
+            }),
+        }
+    }
+
+    fn bind_axis(&mut self, target: &RebindTarget, source: usize, min: i32, max: i32, flip: bool) {
+        match target {
+            RebindTarget::Digital(dest) => {
+                // true while the axis is pushed past the threshold in the same direction it was pushed now
+                let threshold = ((min + max) as f32 / 2.0
+                    + (max - min) as f32 / 2.0 * if flip { -AXIS_PUSHED } else { AXIS_PUSHED })
+                    as i32;
+                let filter = if flip {
+                    DigitalFilter::FromAnalog {
+                        min,
+                        max: threshold,
+                    }
+                } else {
+                    DigitalFilter::FromAnalog {
+                        min: threshold,
+                        max,
+                    }
+                };
+                self.map.digital_maps.push(DigitalMap {
+                    source,
+                    dest: dest.clone(),
+                    filter,
+                });
+            }
+            RebindTarget::Analog(dest) => self.map.analog_maps.push(AnalogMap {
+                source,
+                dest: dest.clone(),
+                filter: AnalogFilter::FromAnalog { min, max, flip },
+            }),
+        }
+    }
+}
+
+/// Maps the raw axis value from [min, max] to [-1.0, 1.0]
+fn normalize_axis(value: i32, min: i32, max: i32) -> f32 {
+    if max == min {
+        0.0
+    } else {
+        (value - min) as f32 / (max - min) as f32 * 2.0 - 1.0
+    }
+}
+
+/// An axis is bound when it is pushed from neutral to near its limit.
+/// Triggers must instead be pushed to their positive limit as they rest at their negative limit.
+fn axis_pushed(target: &RebindTarget, previous: f32, value: f32) -> bool {
+    let trigger = target.is_trigger();
+    axis_neutral(trigger, previous)
+        && if trigger {
+            value > AXIS_PUSHED
+        } else {
+            value.abs() > AXIS_PUSHED
+        }
+}
+
+/// Sticks are neutral near their center, triggers are neutral near their negative limit
+fn axis_neutral(trigger: bool, value: f32) -> bool {
+    if trigger {
+        value < -AXIS_RELEASED
+    } else {
+        value.abs() < AXIS_RELEASED
+    }
+}
+
+#[test]
+fn axis_pushed_test() {
+    let stick = RebindTarget::Analog(AnalogDest::StickX);
+    assert!(axis_pushed(&stick, 0.0, -0.9));
+    // a stick already held at its limit must be released before binding again
+    assert!(!axis_pushed(&stick, 0.8, 0.9));
+
+    let trigger = RebindTarget::Analog(AnalogDest::LTrigger);
+    assert!(!axis_pushed(&trigger, 0.0, -1.0));
+    assert!(axis_pushed(&trigger, -1.0, 1.0));
+    // a trigger still held from the previous binding must be released first
+    assert!(!axis_pushed(&trigger, 1.0, 1.0));
+    assert!(!axis_neutral(true, 0.0));
+    assert!(axis_neutral(true, -0.9));
+
+    assert_eq!(normalize_axis(255, 0, 255), 1.0);
+}
@@ -49,7 +49,7 @@ impl ControllerInput {
 }
 
 /// Internal input storage
-#[derive(Copy, Clone, Default, Serialize, Deserialize, Node)]
+#[derive(Copy, Clone, Default, PartialEq, Serialize, Deserialize, Node)]
 pub struct ControllerInput {
     pub plugged_in: bool,
 
@@ -102,6 +102,23 @@ impl ControllerInput {
             DigitalDest::L => self.l = value,
         }
     }
+
+    /// Holds every button held by `skipped`, an input that was never simulated, so its presses are not lost.
+    /// The analog values of self are kept as they are the most recent.
+    pub(crate) fn include_buttons(&mut self, skipped: &ControllerInput) {
+        self.a |= skipped.a;
+        self.b |= skipped.b;
+        self.x |= skipped.x;
+        self.y |= skipped.y;
+        self.left |= skipped.left;
+        self.right |= skipped.right;
+        self.down |= skipped.down;
+        self.up |= skipped.up;
+        self.start |= skipped.start;
+        self.z |= skipped.z;
+        self.r |= skipped.r;
+        self.l |= skipped.l;
+    }
 }
 
 /// External data access
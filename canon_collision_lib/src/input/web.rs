@@ -0,0 +1,128 @@
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton, GamepadMappingType};
+
+use super::filter;
+use super::state::{ControllerInput, Deadzone};
+
+/// Triggers pressed at least this far also press the digital L or R
+const TRIGGER_FULL_PRESS: f64 = 0.95;
+
+/// Reads gamepads through the Gamepad API of the browser, used by the web build in place of gilrs and the GC adapter.
+/// Only gamepads with the standard mapping are read, the browser has already mapped their buttons so no controller maps are needed.
+pub struct WebGamepads {
+    /// Indexed by the gamepad index assigned by the browser
+    deadzones: Vec<Deadzone>,
+}
+
+impl WebGamepads {
+    pub fn new() -> WebGamepads {
+        WebGamepads { deadzones: vec![] }
+    }
+
+    /// Returns the input of every gamepad slot of the browser.
+    /// Indexed by the gamepad index so that each gamepad keeps its controller when another gamepad disconnects.
+    pub fn read(&mut self) -> Vec<ControllerInput> {
+        let gamepads = match web_sys::window().map(|x| x.navigator().get_gamepads()) {
+            Some(Ok(gamepads)) => gamepads,
+            _ => return vec![],
+        };
+
+        self.deadzones
+            .resize_with(gamepads.length() as usize, Deadzone::empty);
+        gamepads
+            .iter()
+            .zip(self.deadzones.iter_mut())
+            .map(|(gamepad, deadzone)| match gamepad.dyn_into::<Gamepad>() {
+                Ok(gamepad)
+                    if gamepad.connected() && gamepad.mapping() == GamepadMappingType::Standard =>
+                {
+                    read_gamepad(&gamepad, deadzone)
+                }
+                _ => {
+                    *deadzone = Deadzone::empty();
+                    ControllerInput::default()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Button and axis indexes are from the standard mapping https://w3c.github.io/gamepad/#remapping
+fn read_gamepad(gamepad: &Gamepad, deadzone: &mut Deadzone) -> ControllerInput {
+    let buttons = gamepad.buttons();
+    let button = |index: u32| match buttons.get(index).dyn_into::<GamepadButton>() {
+        Ok(button) => (button.pressed(), button.value()),
+        Err(_) => (false, 0.0),
+    };
+    let pressed = |index: u32| button(index).0;
+    let axes = gamepad.axes();
+    let axis = |index: u32| axes.get(index).as_f64().unwrap_or(0.0) as f32;
+
+    let (_, l_value) = button(6);
+    let (_, r_value) = button(7);
+
+    // convert state floats to bytes, the browser uses positive y for down
+    let raw_stick_x = stick_to_byte(axis(0));
+    let raw_stick_y = stick_to_byte(-axis(1));
+    let raw_c_stick_x = stick_to_byte(axis(2));
+    let raw_c_stick_y = stick_to_byte(-axis(3));
+
+    let raw_l_trigger = trigger_to_byte(l_value);
+    let raw_r_trigger = trigger_to_byte(r_value);
+
+    // Only reset deadzone if controller was just plugged in
+    if !deadzone.plugged_in {
+        *deadzone = Deadzone {
+            plugged_in: true,
+            stick_x: raw_stick_x,
+            stick_y: raw_stick_y,
+            c_stick_x: raw_c_stick_x,
+            c_stick_y: raw_c_stick_y,
+            l_trigger: raw_l_trigger,
+            r_trigger: raw_r_trigger,
+        };
+    }
+
+    // convert bytes to result floats
+    let (stick_x, stick_y) = filter::stick_filter(
+        filter::stick_deadzone(raw_stick_x, deadzone.stick_x),
+        filter::stick_deadzone(raw_stick_y, deadzone.stick_y),
+    );
+    let (c_stick_x, c_stick_y) = filter::stick_filter(
+        filter::stick_deadzone(raw_c_stick_x, deadzone.c_stick_x),
+        filter::stick_deadzone(raw_c_stick_y, deadzone.c_stick_y),
+    );
+
+    ControllerInput {
+        plugged_in: true,
+
+        a: pressed(0),
+        b: pressed(1),
+        x: pressed(2),
+        y: pressed(3),
+        z: pressed(5),
+        start: pressed(9),
+        up: pressed(12),
+        down: pressed(13),
+        left: pressed(14),
+        right: pressed(15),
+        l: l_value >= TRIGGER_FULL_PRESS,
+        r: r_value >= TRIGGER_FULL_PRESS,
+
+        stick_x,
+        stick_y,
+        c_stick_x,
+        c_stick_y,
+        l_trigger: filter::trigger_filter(raw_l_trigger.saturating_sub(deadzone.l_trigger)),
+        r_trigger: filter::trigger_filter(raw_r_trigger.saturating_sub(deadzone.r_trigger)),
+    }
+}
+
+fn stick_to_byte(value: f32) -> u8 {
+    (value.min(1.0).max(-1.0) * 127.0 + 127.0) as u8
+}
+
+/// The browser reports triggers from 0.0 to 1.0, a fully pressed trigger must reach the 140 that `trigger_filter` treats as fully pressed
+fn trigger_to_byte(value: f64) -> u8 {
+    (value.min(1.0).max(0.0) * 255.0) as u8
+}
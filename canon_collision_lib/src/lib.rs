@@ -15,10 +15,15 @@ pub mod config;
 pub mod entity_def;
 pub mod files;
 pub mod geometry;
+pub mod hotkeys;
 pub mod input;
 pub mod logger;
 pub mod network;
 pub mod package;
 pub mod panic_handler;
 pub mod replays_files;
+pub mod schema;
 pub mod stage;
+pub mod theme;
+#[cfg(feature = "websocket")]
+pub mod websocket;
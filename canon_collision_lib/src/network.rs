@@ -1,60 +1,196 @@
+use crate::config::Config;
 use crate::files::build_version;
 use bincode;
 use rand;
 use rand::Rng;
 use treeflection::{Node, NodeRunner};
 
+use std::collections::HashMap;
 use std::io::Read;
 use std::io::Write;
-use std::net::{IpAddr, SocketAddr, TcpListener, UdpSocket};
+#[cfg(feature = "netplay")]
+use std::net::UdpSocket;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
 use std::str;
 use std::time::{Duration, Instant};
 
 use crate::input::state::ControllerInput;
 
 pub struct NetCommandLine {
-    listener: TcpListener,
+    listener: CommandListener,
+    /// When set, only commands sent with this token are run
+    token: Option<String>,
 }
 
+enum CommandListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+    /// Listening failed, so no commands are received
+    Disabled,
+}
+
+/// The command interface listens here when the configured address is invalid or unavailable
+pub const DEFAULT_COMMAND_ADDRESS: &str = "127.0.0.1:1613";
+
+/// How long the game waits on a connected client to send its command, the game is stalled while waiting
+const COMMAND_READ_TIMEOUT: Duration = Duration::from_millis(100);
+
 impl NetCommandLine {
-    pub fn new() -> NetCommandLine {
+    /// Listens for commands on the TCP `address`, or on the Unix domain socket at `socket_path` when set.
+    /// When `token` is set, commands without it are refused.
+    pub fn new(socket_path: Option<&str>, address: &str, token: Option<String>) -> NetCommandLine {
+        let listener = match socket_path {
+            #[cfg(unix)]
+            Some(path) => match NetCommandLine::bind_unix(path) {
+                Ok(listener) => CommandListener::Unix(listener),
+                Err(e) => {
+                    println!(
+                        "Failed to listen for commands on {}: {}, listening on TCP address {} instead",
+                        path, e, address
+                    );
+                    NetCommandLine::bind_tcp(address, token.is_some())
+                }
+            },
+            #[cfg(not(unix))]
+            Some(_) => {
+                println!(
+                    "command_socket is only supported on Unix, listening on TCP address {} instead",
+                    address
+                );
+                NetCommandLine::bind_tcp(address, token.is_some())
+            }
+            None => NetCommandLine::bind_tcp(address, token.is_some()),
+        };
+
+        NetCommandLine { listener, token }
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(path: &str) -> std::io::Result<UnixListener> {
+        // the socket file is left behind when the game closes, any other file at a mistyped path is left alone
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if metadata.file_type().is_socket() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }
+
+    fn bind_tcp(address: &str, has_token: bool) -> CommandListener {
+        let address: SocketAddr = match address.parse() {
+            Ok(address) => address,
+            Err(_) => {
+                println!(
+                    "command_address {:?} is not a valid address and port, listening on {} instead",
+                    address, DEFAULT_COMMAND_ADDRESS
+                );
+                DEFAULT_COMMAND_ADDRESS.parse().unwrap()
+            }
+        };
+        if !address.ip().is_loopback() && !has_token {
+            println!("Warning: the command interface is reachable from other machines without a command_token");
+        }
+
         // can fail when using scripts to quickly relaunch
-        let listener = match TcpListener::bind("127.0.0.1:1613") {
+        let listener = match TcpListener::bind(address) {
             Ok(listener) => listener,
             Err(_) => {
                 std::thread::sleep(std::time::Duration::from_millis(500));
-                TcpListener::bind("127.0.0.1:1613").unwrap()
+                match TcpListener::bind(address) {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        println!(
+                            "Failed to listen for commands on {}: {}, listening on {} instead",
+                            address, e, DEFAULT_COMMAND_ADDRESS
+                        );
+                        match TcpListener::bind(DEFAULT_COMMAND_ADDRESS) {
+                            Ok(listener) => listener,
+                            Err(e) => {
+                                println!(
+                                    "Failed to listen for commands on {}: {}, the command interface is disabled",
+                                    DEFAULT_COMMAND_ADDRESS, e
+                                );
+                                return CommandListener::Disabled;
+                            }
+                        }
+                    }
+                }
             }
         };
 
-        listener.set_nonblocking(true).unwrap();
-
-        NetCommandLine { listener }
+        match listener.set_nonblocking(true) {
+            Ok(()) => CommandListener::Tcp(listener),
+            Err(e) => {
+                println!(
+                    "Failed to listen for commands without blocking: {}, the command interface is disabled",
+                    e
+                );
+                CommandListener::Disabled
+            }
+        }
     }
 
     pub fn step<T>(&mut self, root_node: &mut T)
     where
         T: Node,
+    {
+        let token = self.token.as_deref();
+        match &self.listener {
+            CommandListener::Tcp(listener) => {
+                if let Ok((stream, _)) = listener.accept() {
+                    // a client that connects without sending a command must not hang the game
+                    if stream.set_nonblocking(false).is_ok()
+                        && stream.set_read_timeout(Some(COMMAND_READ_TIMEOUT)).is_ok()
+                    {
+                        NetCommandLine::respond(stream, root_node, token);
+                    }
+                }
+            }
+            #[cfg(unix)]
+            CommandListener::Unix(listener) => {
+                if let Ok((stream, _)) = listener.accept() {
+                    // a client that connects without sending a command must not hang the game
+                    if stream.set_nonblocking(false).is_ok()
+                        && stream.set_read_timeout(Some(COMMAND_READ_TIMEOUT)).is_ok()
+                    {
+                        NetCommandLine::respond(stream, root_node, token);
+                    }
+                }
+            }
+            CommandListener::Disabled => {}
+        }
+    }
+
+    fn respond<S, T>(mut stream: S, root_node: &mut T, token: Option<&str>)
+    where
+        S: Read + Write,
+        T: Node,
     {
         let mut buf = [0; 1024];
-        if let Ok((mut stream, _)) = self.listener.accept() {
-            match stream.read(&mut buf) {
-                Ok(amt) => {
-                    if amt > 1 {
-                        if let Ok(string) = str::from_utf8(&buf[1..amt]) {
-                            if buf[0] == 0x43 {
-                                // 'C'
-                                let out = NetCommandLine::run_inner(string, root_node);
-                                if let Err(e) = stream.write(out.as_bytes()) {
-                                    println!("command send failed {}", e);
-                                }
-                            }
+        match stream.read(&mut buf) {
+            Ok(amt) => {
+                if amt > 1 {
+                    if let Ok(string) = str::from_utf8(&buf[1..amt]) {
+                        let out = match command_authenticated(buf[0], string, token) {
+                            Ok(Some(command)) => NetCommandLine::run_inner(command, root_node),
+                            Ok(None) => return,
+                            Err(err) => err,
+                        };
+                        if let Err(e) = stream.write_all(out.as_bytes()) {
+                            println!("command send failed {}", e);
                         }
                     }
                 }
-                Err(e) => {
-                    println!("command receive failed {}", e);
-                }
+            }
+            Err(e) => {
+                println!("command receive failed {}", e);
             }
         }
     }
@@ -70,6 +206,70 @@ impl NetCommandLine {
     }
 }
 
+/// Formats a command to send to the command interface, authenticated with `token` when set
+pub fn command_message(command: &str, token: Option<&str>) -> String {
+    match token {
+        Some(token) => format!("A{}\n{}", token, command),
+        None => format!("C{}", command),
+    }
+}
+
+/// The address to connect to the command interface of a game on this machine listening on `address`.
+/// Listening on every interface e.g. `0.0.0.0:1613` is connected to through loopback.
+pub fn command_connect_address(address: &str) -> SocketAddr {
+    let mut address = address
+        .parse()
+        .unwrap_or_else(|_| DEFAULT_COMMAND_ADDRESS.parse().unwrap());
+    if let SocketAddr::V4(address) = &mut address {
+        if address.ip().is_unspecified() {
+            address.set_ip(Ipv4Addr::LOCALHOST);
+        }
+    }
+    if let SocketAddr::V6(address) = &mut address {
+        if address.ip().is_unspecified() {
+            address.set_ip(Ipv6Addr::LOCALHOST);
+        }
+    }
+    address
+}
+
+/// Returns the command of a message received by the command interface.
+/// Returns None for messages of an unknown type and an error when the token is missing or wrong.
+///
+/// Message Formats:
+///     'C' followed by the command
+///     'A' followed by the token, a newline and then the command
+fn command_authenticated<'a>(
+    ty: u8,
+    message: &'a str,
+    token: Option<&str>,
+) -> Result<Option<&'a str>, String> {
+    let (sent_token, command) = match ty {
+        b'C' => (None, message),
+        b'A' => match message.split_once('\n') {
+            Some((sent_token, command)) => (Some(sent_token), command),
+            None => return Err(String::from("Command is missing a newline after the token")),
+        },
+        _ => return Ok(None),
+    };
+    match token {
+        Some(token) if !sent_token.map_or(false, |sent| token_eq(sent, token)) => Err(
+            String::from("Command refused: missing or incorrect command token"),
+        ),
+        _ => Ok(Some(command)),
+    }
+}
+
+/// Compares every byte instead of returning at the first difference,
+/// so the response time does not reveal how much of a guessed token is correct.
+pub(crate) fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
 /*  Message Formats:
     Matchmaking Request:
         1 byte  - 0x00
@@ -91,54 +291,226 @@ impl NetCommandLine {
         1 byte  - 0x04
         n bytes - bincode serialized controller input data
 
+    Spectate Request:
+        1 byte  - 0x05
+        n bytes - bincode serialized SpectateRequest
+
+    Spectate Accept:
+        1 byte  - 0x06
+        n bytes - bincode serialized SpectateAccept
+
+    Spectator Input Message:
+        1 byte  - 0x07
+        n bytes - bincode serialized SpectatorInputs
+
+    Spectator Acknowledgement:
+        1 byte  - 0x08
+        n bytes - bincode serialized number of frames received by the spectator
+
+    Lobby Request:
+        1 byte  - 0x09
+        n bytes - bincode serialized LobbyRequest
+
+    Lobby Status:
+        1 byte  - 0x0A
+        n bytes - bincode serialized LobbyStatus
+
+    Lobby Leave:
+        1 byte - 0x0B
+
+    Rendezvous Request:
+        1 byte  - 0x0C
+        n bytes - bincode serialized RendezvousRequest
+
+    Rendezvous Response:
+        1 byte  - 0x0D
+        n bytes - bincode serialized RendezvousResponse
+
+    Hole Punch:
+        1 byte - 0x0E
+        1 byte - 1 if a hole punch from the receiver has been received, otherwise 0
+
     Disconnect notification:
         1 byte - 0xAA
 */
 
+/// Hosts matchmaking and lobbies
+const MATCHMAKING_SERVER: &str = "matchmaking.canoncollision.com:8413";
+
+/// Frames between each request sent to the matchmaking server while in a lobby.
+/// The server removes members it has not heard from in a while, so this also keeps the local machine in the lobby.
+const LOBBY_REQUEST_FRAMES: usize = 60;
+
+/// Most players a single machine can bring to a lobby
+const LOBBY_MAX_PLAYERS: u8 = 4;
+
+/// Most machines that can play together from a lobby, InitConnection only connects two peers
+const LOBBY_MAX_MEMBERS: usize = 2;
+
+/// Frames between each hole punch sent to the peer of a room
+const HOLE_PUNCH_FRAMES: usize = 5;
+
+/// Frames spent hole punching before falling back to the relay
+const HOLE_PUNCH_TIMEOUT_FRAMES: usize = 60 * 5;
+
+/// Number of frames of confirmed inputs a spectator buffers before simulating a frame.
+/// Absorbs jitter in the input stream so the spectator rarely has to wait on the network.
+const SPECTATOR_DELAY: usize = 10;
+
+/// Maximum number of frames sent to each spectator every frame.
+/// Spectators that fall behind catch up at this rate.
+const SPECTATOR_FRAMES_PER_STEP: usize = 8;
+
+/// Reason netplay disconnects when built without the netplay feature
+#[cfg(not(feature = "netplay"))]
+const NETPLAY_UNSUPPORTED: &str = "Netplay is not available in this build";
+
+/// The UDP socket used for netplay.
+/// Without the netplay feature no socket is bound and every send and receive fails.
+struct NetSocket {
+    #[cfg(feature = "netplay")]
+    socket: UdpSocket,
+}
+
+#[cfg(feature = "netplay")]
+impl NetSocket {
+    fn bind() -> NetSocket {
+        let socket = UdpSocket::bind("0.0.0.0:8413").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        NetSocket { socket }
+    }
+
+    fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> std::io::Result<usize> {
+        self.socket.send_to(buf, addr)
+    }
+
+    /// Returns Err if there is no packet waiting
+    fn recv_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf)
+    }
+}
+
+#[cfg(not(feature = "netplay"))]
+impl NetSocket {
+    fn bind() -> NetSocket {
+        NetSocket {}
+    }
+
+    fn send_to<A: ToSocketAddrs>(&self, _buf: &[u8], _addr: A) -> std::io::Result<usize> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            NETPLAY_UNSUPPORTED,
+        ))
+    }
+
+    fn recv_from(&self, _buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            NETPLAY_UNSUPPORTED,
+        ))
+    }
+}
+
 pub struct Netplay {
     // structure: peers Vec<frames Vec<controllers Vec<ControllerInput>>>
     // frame 0 has index 2
     pub confirmed_inputs: Vec<Vec<Vec<ControllerInput>>>,
     match_making_response: Option<MatchMakingResponse>,
+    lobby_status: Option<LobbyStatus>,
+    rendezvous_response: Option<RendezvousResponse>,
+    /// The frame the first hole punch was sent on
+    hole_punch_start_frame: Option<usize>,
+    /// A hole punch has been received from the peer
+    hole_punch_received: bool,
+    /// Packets sent by the peer are reaching it, so a direct connection works in both directions
+    hole_punch_acked: bool,
+    /// Most recent round trip time to each lobby member
+    lobby_pings: HashMap<SocketAddr, Duration>,
+    /// When each ping id was sent to the lobby members
+    lobby_pings_sent: HashMap<u8, Instant>,
     peers: Vec<SocketAddr>,
     seed: u64,
-    socket: UdpSocket,
+    socket: NetSocket,
     state: NetplayState,
     state_frame: usize,
     last_received_frame: usize,
+    skip_frame: bool,
     index: usize,
+    /// Input delay requested by the local config, sent to peers when initiating a connection
+    local_input_delay: usize,
+    /// Input delay used by every peer in the current session
+    input_delay: usize,
+    max_rollback_frames: usize,
+    /// Number of players on each machine in the session, indexed by peer including the local machine
+    peer_players: Vec<u8>,
     init_msgs: Vec<InitConnection>,
-    ping_msgs: Vec<u8>,
+    ping_msgs: Vec<(SocketAddr, u8)>,
     start_request_msgs: Vec<usize>,
     start_confirm_msgs: Vec<usize>,
     running_msgs: Vec<InputConfirm>,
+    /// Inputs sent by the local machine that a spectator may still need, index 0 is frame `local_inputs_trimmed + 1`
+    local_inputs: Vec<Vec<ControllerInput>>,
+    /// Number of frames removed from the front of `local_inputs` because every spectator has acknowledged them
+    local_inputs_trimmed: usize,
+    spectators: Vec<Spectator>,
+    spectate_request_msgs: Vec<(SocketAddr, SpectateRequest)>,
+    spectate_accept_msgs: Vec<(SocketAddr, SpectateAccept)>,
+    /// Peers that have refused to be spectated because they have already discarded the inputs of the first frames
+    spectate_refusals: Vec<SocketAddr>,
+    spectator_msgs: Vec<SpectatorInputs>,
 }
 
 impl Netplay {
     pub fn new() -> Netplay {
-        let socket = UdpSocket::bind("0.0.0.0:8413").unwrap();
-        socket.set_nonblocking(true).unwrap();
+        let socket = NetSocket::bind();
         Netplay {
             state: NetplayState::Offline,
             state_frame: 0,
             last_received_frame: 0,
+            skip_frame: false,
             confirmed_inputs: vec![],
             match_making_response: None,
+            lobby_status: None,
+            rendezvous_response: None,
+            hole_punch_start_frame: None,
+            hole_punch_received: false,
+            hole_punch_acked: false,
+            lobby_pings: HashMap::new(),
+            lobby_pings_sent: HashMap::new(),
             peers: vec![],
             seed: 0,
             index: 0,
+            local_input_delay: 0,
+            input_delay: 0,
+            max_rollback_frames: 0,
+            peer_players: vec![],
             init_msgs: vec![],
             ping_msgs: vec![],
             start_request_msgs: vec![],
             start_confirm_msgs: vec![],
             running_msgs: vec![],
+            local_inputs: vec![],
+            local_inputs_trimmed: 0,
+            spectators: vec![],
+            spectate_request_msgs: vec![],
+            spectate_accept_msgs: vec![],
+            spectate_refusals: vec![],
+            spectator_msgs: vec![],
             socket,
         }
     }
 
     /// Call this once every frame
-    pub fn step(&mut self) {
-        if !self.skip_frame() {
+    pub fn step(&mut self, config: &Config) {
+        #[cfg(not(feature = "netplay"))]
+        self.disconnect_with_reason(NETPLAY_UNSUPPORTED);
+
+        self.local_input_delay = config.netplay_input_delay;
+        self.max_rollback_frames = config.netplay_max_rollback_frames;
+
+        // Decide once per frame so that the input and game logic agree with state_frame on whether the frame was skipped
+        self.skip_frame = self.too_far_ahead();
+        if !self.skip_frame {
             self.state_frame += 1;
         }
 
@@ -147,6 +519,9 @@ impl Netplay {
             let mut buf = [0; 1024];
             if let Ok((_, addr)) = self.socket.recv_from(&mut buf) {
                 // returns Err if there is no packet waiting
+                if let NetplayState::Rendezvous { .. } = self.state {
+                    self.receive_rendezvous(addr, &buf);
+                }
                 match buf[0] {
                     0x00 => {
                         if let Ok(data) = bincode::deserialize(&buf[1..]) {
@@ -167,7 +542,7 @@ impl Netplay {
                     }
                     0x03 => {
                         if self.peers.contains(&addr) {
-                            self.ping_msgs.push(buf[1]);
+                            self.ping_msgs.push((addr, buf[1]));
                         }
                     }
                     0x04 => {
@@ -177,8 +552,63 @@ impl Netplay {
                             }
                         }
                     }
+                    0x05 => {
+                        if let NetplayState::Running = self.state {
+                            if let Ok(data) = bincode::deserialize(&buf[1..]) {
+                                self.spectate_request_msgs.push((addr, data));
+                            }
+                        }
+                    }
+                    0x06 => {
+                        if self.peers.contains(&addr) {
+                            if let Ok(data) = bincode::deserialize(&buf[1..]) {
+                                self.spectate_accept_msgs.push((addr, data));
+                            }
+                        }
+                    }
+                    0x07 => {
+                        if self.peers.contains(&addr) {
+                            if let Ok(data) = bincode::deserialize(&buf[1..]) {
+                                self.spectator_msgs.push(data);
+                            }
+                        }
+                    }
+                    0x08 => {
+                        if let Ok(frames) = bincode::deserialize::<usize>(&buf[1..]) {
+                            let state_frame = self.state_frame;
+                            if let Some(spectator) =
+                                self.spectators.iter_mut().find(|x| x.address == addr)
+                            {
+                                spectator.acked_frames = spectator.acked_frames.max(frames);
+                                spectator.last_received_frame = state_frame;
+                            }
+                        }
+                    }
+                    0x0A => {
+                        if let NetplayState::Lobby { .. } = self.state {
+                            if let Ok(data) = bincode::deserialize(&buf[1..]) {
+                                self.lobby_status = Some(data);
+                            }
+                        }
+                    }
+                    0x0D => {
+                        if let NetplayState::Rendezvous { .. } = self.state {
+                            if let Ok(data) = bincode::deserialize(&buf[1..]) {
+                                self.rendezvous_response = Some(data);
+                            }
+                        }
+                    }
+                    // handled by receive_rendezvous
+                    0x0E => {}
                     0xAA => {
-                        self.disconnect_with_reason("Peer disconnected");
+                        if self.spectators.iter().any(|x| x.address == addr) {
+                            self.spectators.retain(|x| x.address != addr);
+                        } else if let NetplayState::Lobby { .. } = self.state {
+                            // the lobby continues without them, the next lobby status removes them from the peers
+                            self.lobby_pings.remove(&addr);
+                        } else {
+                            self.disconnect_with_reason("Peer disconnected");
+                        }
                     }
                     _ => {
                         println!(
@@ -208,12 +638,9 @@ impl Netplay {
                     // Send a request every 10 seconds
                     let mut data = bincode::serialize(&request).unwrap();
                     data.insert(0, 0x00);
-                    if let Err(_) = self
-                        .socket
-                        .send_to(&data, "matchmaking.canoncollision.com:8413")
-                    {
+                    if self.socket.send_to(&data, MATCHMAKING_SERVER).is_err() {
                         self.disconnect_with_reason(
-                            "matchmaking.canoncollision.com:8413 is inaccessible",
+                            format!("{} is inaccessible", MATCHMAKING_SERVER).as_ref(),
                         );
                     }
                 }
@@ -229,9 +656,96 @@ impl Netplay {
                     self.set_state(NetplayState::InitConnection(InitConnection {
                         random: rand::thread_rng().gen::<u64>(),
                         build_version: request.build_version,
+                        input_delay: self.local_input_delay,
+                        players: 1,
                     }));
                 }
             }
+            NetplayState::Lobby { request } => {
+                if self.state_frame % LOBBY_REQUEST_FRAMES == 1 {
+                    self.send_lobby_request(&request);
+
+                    // ping every member, the id wraps around long after its previous ping has been answered
+                    let ping_id = (self.state_frame / LOBBY_REQUEST_FRAMES % 256) as u8;
+                    self.lobby_pings_sent.insert(ping_id, Instant::now());
+                    for peer in self.peers.iter() {
+                        self.socket.send_to(&[2, ping_id], peer).ok();
+                    }
+                }
+
+                for (address, ping_id) in self.ping_msgs.drain(..) {
+                    if let Some(sent) = self.lobby_pings_sent.get(&ping_id) {
+                        self.lobby_pings.insert(address, sent.elapsed());
+                    }
+                }
+
+                if let Some(status) = self.lobby_status.clone() {
+                    if status.you >= LOBBY_MAX_MEMBERS {
+                        // members that joined after the lobby filled up leave, so the first members can start
+                        self.disconnect_with_reason(
+                            format!(
+                                "The lobby is full, only {} machines can play together",
+                                LOBBY_MAX_MEMBERS
+                            )
+                            .as_ref(),
+                        );
+                    } else {
+                        let peers: Vec<SocketAddr> = status
+                            .members
+                            .iter()
+                            .take(LOBBY_MAX_MEMBERS)
+                            .enumerate()
+                            .filter(|(i, _)| *i != status.you)
+                            .map(|(_, member)| member.address)
+                            .collect();
+                        if peers != self.peers {
+                            self.confirmed_inputs = vec![vec![]; peers.len()];
+                            self.peers = peers;
+                        }
+
+                        if status.members.len() == LOBBY_MAX_MEMBERS
+                            && status.members.iter().all(|x| x.ready)
+                        {
+                            self.set_state(NetplayState::InitConnection(InitConnection {
+                                random: rand::thread_rng().gen::<u64>(),
+                                build_version: request.build_version,
+                                input_delay: self.local_input_delay,
+                                players: request.players,
+                            }));
+                        }
+                    }
+                }
+            }
+            NetplayState::Rendezvous { request } => {
+                if self.state_frame % 60 == 1 {
+                    // Send a request every second until the server finds the peer, the server needs a live request from both peers to relay
+                    let mut data = bincode::serialize(&request).unwrap();
+                    data.insert(0, 0x0C);
+                    if self.socket.send_to(&data, MATCHMAKING_SERVER).is_err() {
+                        self.disconnect_with_reason(
+                            format!("{} is inaccessible", MATCHMAKING_SERVER).as_ref(),
+                        );
+                    }
+                }
+
+                if let Some(response) = self.rendezvous_response.clone() {
+                    let start_frame = *self.hole_punch_start_frame.get_or_insert(self.state_frame);
+                    if self.hole_punch_acked {
+                        self.connect_rendezvous(response.peer, request.build_version);
+                    } else if self.state_frame - start_frame > HOLE_PUNCH_TIMEOUT_FRAMES {
+                        match response.relay {
+                            Some(relay) => self.connect_rendezvous(relay, request.build_version),
+                            None => self.disconnect_with_reason(
+                                "Could not connect to the peer directly and there is no relay available",
+                            ),
+                        }
+                    } else if (self.state_frame - start_frame) % HOLE_PUNCH_FRAMES == 0 {
+                        // Outgoing packets open a hole in the local NAT that the peer's packets can return through
+                        let acked = self.hole_punch_received as u8;
+                        self.socket.send_to(&[0x0E, acked], response.peer).ok();
+                    }
+                }
+            }
             NetplayState::InitConnection(local) => {
                 // send init
                 let mut data = bincode::serialize(&local).unwrap();
@@ -259,6 +773,15 @@ impl Netplay {
                         self.index = 1;
                         self.seed = init.random;
                     }
+
+                    // Every peer must use the same input delay or they will simulate different inputs on the same frame.
+                    self.input_delay = local.input_delay.max(init.input_delay);
+
+                    self.peer_players = if self.index == 0 {
+                        vec![local.players, init.players]
+                    } else {
+                        vec![init.players, local.players]
+                    };
                 }
             }
             NetplayState::PingTest {
@@ -283,7 +806,7 @@ impl Netplay {
                     pings[next_ping].time_sent = Some(Instant::now());
 
                     // record the time_received of received pings
-                    for ping_msg in self.ping_msgs.iter() {
+                    for (_, ping_msg) in self.ping_msgs.iter() {
                         pings[*ping_msg as usize].time_received = Some(Instant::now());
                     }
                     self.state = NetplayState::PingTest { local_init, pings };
@@ -340,13 +863,67 @@ impl Netplay {
                     }
                     to_delete.clear();
                 }
+
+                self.step_spectators();
+            }
+            NetplayState::Spectating { accepted: false } => {
+                // Send a request every second until the peer accepts
+                if self.state_frame % 60 == 1 {
+                    let request = SpectateRequest {
+                        build_version: build_version(),
+                    };
+                    let mut data = bincode::serialize(&request).unwrap();
+                    data.insert(0, 0x05);
+                    self.broadcast(&data, "spectate request");
+                }
+
+                // Every peer sends the inputs of every other peer, so any one peer that still has the first frames is enough
+                for (address, accept) in std::mem::take(&mut self.spectate_accept_msgs) {
+                    if accept.build_version != build_version() {
+                        self.disconnect_with_reason("Build versions did not match, ensure everyone is using the same Canon Collision build.");
+                        break;
+                    } else if accept.from_start {
+                        self.seed = accept.seed;
+                        self.input_delay = accept.input_delay;
+                        self.confirmed_inputs = vec![vec![]; accept.number_of_peers];
+                        self.set_state(NetplayState::Spectating { accepted: true });
+                        break;
+                    } else if !self.spectate_refusals.contains(&address) {
+                        self.spectate_refusals.push(address);
+                    }
+                }
+                if let NetplayState::Spectating { accepted: false } = self.state {
+                    if !self.spectate_refusals.is_empty()
+                        && self.spectate_refusals.len() >= self.peers.len()
+                    {
+                        self.disconnect_with_reason("This game can no longer be spectated, the inputs of its first frames have already been discarded by every player.");
+                    }
+                }
+            }
+            NetplayState::Spectating { accepted: true } => {
+                let mut received = self.confirmed_inputs.first().map_or(0, |x| x.len());
+                self.spectator_msgs.sort_by_key(|x| x.frame);
+                for msg in self.spectator_msgs.drain(..) {
+                    // Duplicates and frames after a missing frame are dropped, the peer resends every frame we have not acknowledged.
+                    if msg.frame == received + 1 && msg.inputs.len() == self.confirmed_inputs.len()
+                    {
+                        for (confirmed, inputs) in self.confirmed_inputs.iter_mut().zip(msg.inputs)
+                        {
+                            confirmed.push(inputs);
+                        }
+                        received += 1;
+                    }
+                }
+
+                let mut data = bincode::serialize(&received).unwrap();
+                data.insert(0, 0x08);
+                self.broadcast(&data, "spectator acknowledgement");
             }
         }
         debug!("state: {}", self.state.to_string());
         debug!("number_of_peers: {}", self.number_of_peers());
         debug!("local_index: {}", self.local_index());
         debug!("frame: {}", self.frame());
-        debug!("frames_to_step: {}", self.frames_to_step());
         debug!("skip_frame: {}", self.skip_frame());
     }
 
@@ -362,44 +939,70 @@ impl Netplay {
         }
     }
 
-    /// Returns the total number of peers including the local machine
+    /// Returns the total number of peers including the local machine.
+    /// When spectating this is the number of peers playing, the spectator is not included.
     pub fn number_of_peers(&self) -> usize {
-        self.peers.len() + 1
+        match &self.state {
+            NetplayState::Spectating { accepted: true } => self.confirmed_inputs.len(),
+            _ => self.peers.len() + 1,
+        }
     }
 
-    // TODO: Optimize by only starting from a frame where the inputs differ
-    /// Returns the number of frames that need to be stepped/restepped including the current frame
-    pub fn frames_to_step(&self) -> usize {
-        let input_frames = self
-            .confirmed_inputs
-            .iter()
-            .map(|x| x.len())
-            .min()
-            .unwrap_or(1);
+    /// Returns the number of players on the machine of `peer`, sent by each peer when connecting.
+    /// None when unknown, e.g. when spectating, where it is only known from the received inputs.
+    pub fn peer_players(&self, peer: usize) -> Option<usize> {
         match &self.state {
-            NetplayState::Running => self.state_frame.saturating_sub(input_frames).max(1),
-            _ => 1,
+            NetplayState::Running => self.peer_players.get(peer).map(|x| *x as usize),
+            _ => None,
         }
     }
 
+    /// Returns true if the local machine is watching a session without contributing inputs
+    pub fn is_spectator(&self) -> bool {
+        matches!(self.state, NetplayState::Spectating { .. })
+    }
+
     pub fn frame(&self) -> usize {
         match &self.state {
-            NetplayState::Running => self.state_frame,
+            NetplayState::Running | NetplayState::Spectating { accepted: true } => self.state_frame,
+            _ => 0,
+        }
+    }
+
+    /// Returns the number of frames between when an input is made and when it is used by the game.
+    /// Higher values reduce how often inputs need to be predicted and rolled back.
+    pub fn input_delay(&self) -> usize {
+        match &self.state {
+            NetplayState::Running | NetplayState::Spectating { accepted: true } => self.input_delay,
             _ => 0,
         }
     }
 
-    // TODO: take ping into account
     /// Returns true if the local machine should do nothing for a frame so that peers can catch up.
     pub fn skip_frame(&self) -> bool {
+        match &self.state {
+            NetplayState::Running | NetplayState::Spectating { accepted: true } => self.skip_frame,
+            _ => false,
+        }
+    }
+
+    // TODO: take ping into account
+    /// Returns true if simulating the next frame would require predicting more than max_rollback_frames frames of peer inputs
+    fn too_far_ahead(&self) -> bool {
         let input_frames = self
             .confirmed_inputs
             .iter()
             .map(|x| x.len())
             .min()
-            .unwrap_or(1);
+            .unwrap_or(0);
         match &self.state {
-            NetplayState::Running => self.state_frame > input_frames + 1,
+            NetplayState::Running => {
+                self.state_frame + 1 > input_frames + self.input_delay + self.max_rollback_frames
+            }
+            // spectators never predict inputs, instead they stay SPECTATOR_DELAY frames behind the confirmed inputs
+            NetplayState::Spectating { accepted: true } => {
+                self.state_frame + 1 + SPECTATOR_DELAY > input_frames + self.input_delay
+            }
             _ => false,
         }
     }
@@ -407,15 +1010,88 @@ impl Netplay {
     /// Return the seed used for this netplay session
     pub fn get_seed(&self) -> Option<u64> {
         match &self.state {
-            NetplayState::Running { .. } => Some(self.seed),
+            NetplayState::Running | NetplayState::Spectating { accepted: true } => Some(self.seed),
             _ => None,
         }
     }
 
+    /// Returns every member of the current lobby in the order they joined, empty when not in a lobby
+    pub fn lobby_members(&self) -> Vec<LobbyMemberStatus> {
+        match (&self.state, &self.lobby_status) {
+            (NetplayState::Lobby { .. }, Some(status)) => status
+                .members
+                .iter()
+                .enumerate()
+                .map(|(i, member)| LobbyMemberStatus {
+                    local: i == status.you,
+                    address: member.address,
+                    ping: self.lobby_pings.get(&member.address).cloned(),
+                    players: member.players,
+                    ready: member.ready,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The local machine is ready to start once every other member of the lobby is
+    pub fn set_lobby_ready(&mut self, ready: bool) {
+        if let NetplayState::Lobby { request } = &mut self.state {
+            request.ready = ready;
+            let request = request.clone();
+            self.send_lobby_request(&request);
+        }
+    }
+
+    /// Sets the number of players on the local machine, clamped between 1 and LOBBY_MAX_PLAYERS
+    pub fn set_lobby_players(&mut self, players: u8) {
+        if let NetplayState::Lobby { request } = &mut self.state {
+            request.players = players.clamp(1, LOBBY_MAX_PLAYERS);
+            let request = request.clone();
+            self.send_lobby_request(&request);
+        }
+    }
+
+    /// Records hole punches and any other packets received from the peer of a room
+    fn receive_rendezvous(&mut self, addr: SocketAddr, buf: &[u8]) {
+        if let Some(response) = &self.rendezvous_response {
+            if addr == response.peer {
+                if buf[0] == 0x0E {
+                    self.hole_punch_received = true;
+                    self.hole_punch_acked |= buf[1] == 1;
+                } else {
+                    // the peer only sends other packets after it has received our hole punches
+                    self.hole_punch_received = true;
+                    self.hole_punch_acked = true;
+                }
+            }
+        }
+    }
+
+    /// Starts connecting to the peer of a room at `address`, which is either the peer itself or a relay forwarding to it
+    fn connect_rendezvous(&mut self, address: SocketAddr, build_version: String) {
+        self.peers = vec![address];
+        self.confirmed_inputs = vec![vec![]];
+        self.set_state(NetplayState::InitConnection(InitConnection {
+            random: rand::thread_rng().gen::<u64>(),
+            build_version,
+            input_delay: self.local_input_delay,
+            players: 1,
+        }));
+    }
+
+    fn send_lobby_request(&mut self, request: &LobbyRequest) {
+        let mut data = bincode::serialize(request).unwrap();
+        data.insert(0, 0x09);
+        if self.socket.send_to(&data, MATCHMAKING_SERVER).is_err() {
+            self.disconnect_with_reason(format!("{} is inaccessible", MATCHMAKING_SERVER).as_ref());
+        }
+    }
+
     fn broadcast(&mut self, message: &[u8], message_name: &str) {
         let mut fail = false;
         for peer in self.peers.iter() {
-            if let Err(_) = self.socket.send_to(message, peer) {
+            if self.socket.send_to(message, peer).is_err() {
                 fail = true;
                 break;
             }
@@ -427,15 +1103,108 @@ impl Netplay {
         }
     }
 
+    /// Sends the confirmed inputs of every peer to spectators and accepts new spectators
+    fn step_spectators(&mut self) {
+        for (address, request) in std::mem::take(&mut self.spectate_request_msgs) {
+            // always reply so the spectator can report mismatched build versions
+            let accept = SpectateAccept {
+                build_version: build_version(),
+                seed: self.seed,
+                input_delay: self.input_delay,
+                number_of_peers: self.number_of_peers(),
+                from_start: self.local_inputs_trimmed == 0,
+            };
+            let mut data = bincode::serialize(&accept).unwrap();
+            data.insert(0, 0x06);
+            self.socket.send_to(&data, address).ok();
+
+            // A spectator simulates from the first frame, so it cannot join once those inputs are trimmed.
+            // The reply tells the spectator it was refused, so it can disconnect with a reason instead of waiting forever.
+            if request.build_version == build_version()
+                && self.local_inputs_trimmed == 0
+                && !self.spectators.iter().any(|x| x.address == address)
+            {
+                self.spectators.push(Spectator {
+                    address,
+                    acked_frames: 0,
+                    last_received_frame: self.state_frame,
+                });
+            }
+        }
+
+        let state_frame = self.state_frame;
+        self.spectators
+            .retain(|x| state_frame.saturating_sub(x.last_received_frame) <= 600);
+
+        // only frames where the inputs of every peer are confirmed can be sent
+        let confirmed_frames = self
+            .confirmed_inputs
+            .iter()
+            .map(|x| x.len())
+            .min()
+            .unwrap_or(0)
+            .min(self.local_inputs_trimmed + self.local_inputs.len());
+        for spectator in &self.spectators {
+            let end = confirmed_frames.min(spectator.acked_frames + SPECTATOR_FRAMES_PER_STEP);
+            for frame in spectator.acked_frames..end {
+                let mut remote_inputs = self.confirmed_inputs.iter();
+                let inputs = (0..self.number_of_peers())
+                    .map(|peer| {
+                        if peer == self.index {
+                            self.local_inputs[frame - self.local_inputs_trimmed].clone()
+                        } else {
+                            remote_inputs.next().unwrap()[frame].clone()
+                        }
+                    })
+                    .collect();
+                let spectator_inputs = SpectatorInputs {
+                    frame: frame + 1,
+                    inputs,
+                };
+                let mut data = bincode::serialize(&spectator_inputs).unwrap();
+                data.insert(0, 0x07);
+                // A spectator failing to receive inputs should not affect the players
+                self.socket.send_to(&data, spectator.address).ok();
+            }
+        }
+
+        // Frames every spectator has acknowledged will never be resent.
+        // Without spectators nothing is trimmed so a spectator can still join from the first frame.
+        if let Some(acked_frames) = self.spectators.iter().map(|x| x.acked_frames).min() {
+            let trim = acked_frames
+                .saturating_sub(self.local_inputs_trimmed)
+                .min(self.local_inputs.len());
+            self.local_inputs.drain(..trim);
+            self.local_inputs_trimmed += trim;
+        }
+    }
+
     fn clear(&mut self) {
         self.confirmed_inputs.clear();
         self.index = 0;
+        self.input_delay = 0;
+        self.skip_frame = false;
         self.init_msgs.clear();
         self.last_received_frame = 0;
         self.match_making_response = None;
+        self.lobby_status = None;
+        self.rendezvous_response = None;
+        self.hole_punch_start_frame = None;
+        self.hole_punch_received = false;
+        self.hole_punch_acked = false;
+        self.lobby_pings.clear();
+        self.lobby_pings_sent.clear();
         self.peers.clear();
+        self.peer_players.clear();
         self.ping_msgs.clear();
         self.running_msgs.clear();
+        self.local_inputs.clear();
+        self.local_inputs_trimmed = 0;
+        self.spectators.clear();
+        self.spectate_request_msgs.clear();
+        self.spectate_accept_msgs.clear();
+        self.spectate_refusals.clear();
+        self.spectator_msgs.clear();
         self.seed = 0;
         self.start_confirm_msgs.clear();
         self.start_request_msgs.clear();
@@ -449,6 +1218,8 @@ impl Netplay {
         self.set_state(NetplayState::InitConnection(InitConnection {
             random: rand::thread_rng().gen::<u64>(),
             build_version: build_version(),
+            input_delay: self.local_input_delay,
+            players: 1,
         }));
     }
 
@@ -462,6 +1233,43 @@ impl Netplay {
         self.set_state(NetplayState::MatchMaking { request });
     }
 
+    /// Connect to the peer that shares `room_code`, without either player needing to port forward.
+    /// The matchmaking server exchanges the public addresses of both peers so they can punch through their NATs,
+    /// if that fails the connection is relayed through the server instead.
+    pub fn connect_room_code(&mut self, room_code: String) {
+        self.clear();
+        let request = RendezvousRequest {
+            room_code,
+            build_version: build_version(),
+        };
+        self.set_state(NetplayState::Rendezvous { request });
+    }
+
+    /// Join the lobby named `room` on the matchmaking server, creating it if it does not exist.
+    /// The session starts once both members of the lobby are ready, machines joining a full lobby leave it.
+    pub fn join_lobby(&mut self, room: String) {
+        self.clear();
+        let request = LobbyRequest {
+            room,
+            build_version: build_version(),
+            players: 1,
+            ready: false,
+        };
+        self.set_state(NetplayState::Lobby { request });
+    }
+
+    /// Watch the session of the peer at `address` without contributing inputs.
+    /// The session is simulated from its start, so a spectator that joins late will be watching earlier parts of the session.
+    pub fn spectate(&mut self, address: IpAddr) {
+        self.clear();
+        self.peers.push(SocketAddr::new(address, 8413));
+        self.set_state(NetplayState::Spectating { accepted: false });
+    }
+
+    pub fn stop_spectating(&mut self) {
+        self.disconnect_with_reason("Stopped spectating");
+    }
+
     fn set_state(&mut self, state: NetplayState) {
         self.state = state;
         self.state_frame = 0;
@@ -472,9 +1280,13 @@ impl Netplay {
         match &self.state {
             NetplayState::Offline | NetplayState::Disconnected { .. } => {}
             _ => {
+                self.leave_lobby();
                 for peer in self.peers.iter() {
                     self.socket.send_to(&[0xAA], peer).ok();
                 }
+                for spectator in self.spectators.iter() {
+                    self.socket.send_to(&[0xAA], spectator.address).ok();
+                }
                 self.set_state(NetplayState::Disconnected {
                     reason: String::from(reason),
                 });
@@ -487,17 +1299,35 @@ impl Netplay {
         match &self.state {
             NetplayState::Offline => {}
             _ => {
+                self.leave_lobby();
                 for peer in self.peers.iter() {
                     self.socket.send_to(&[0xAA], peer).ok();
                 }
+                for spectator in self.spectators.iter() {
+                    self.socket.send_to(&[0xAA], spectator.address).ok();
+                }
                 self.set_state(NetplayState::Offline);
                 self.clear();
             }
         }
     }
 
-    pub fn send_controller_inputs(&mut self, inputs: Vec<ControllerInput>) {
+    /// Tells the matchmaking server to remove the local machine from its lobby immediately, instead of waiting for it to time out
+    fn leave_lobby(&mut self) {
+        if let NetplayState::Lobby { .. } = self.state {
+            self.socket.send_to(&[0x0B], MATCHMAKING_SERVER).ok();
+        }
+    }
+
+    pub fn send_controller_inputs(&mut self, mut inputs: Vec<ControllerInput>) {
         if let NetplayState::Running = &self.state {
+            // peers expect exactly as many controllers as the players we told them about
+            if let Some(players) = self.peer_players(self.index) {
+                inputs.resize(players, ControllerInput::default());
+            }
+            if self.state_frame == self.local_inputs_trimmed + self.local_inputs.len() + 1 {
+                self.local_inputs.push(inputs.clone());
+            }
             let input_confirm = InputConfirm {
                 frame: self.state_frame,
                 inputs,
@@ -512,6 +1342,9 @@ impl Netplay {
 
 /// State flow sequence:
 ///     Offline -> MatchMaking -> InitConnection -> Ping Test -> Running -> Disconnected -> Offline
+///     Offline -> Lobby -> InitConnection -> Ping Test -> Running -> Disconnected -> Offline
+///     Offline -> Rendezvous -> InitConnection -> Ping Test -> Running -> Disconnected -> Offline
+///     Offline -> Spectating -> Disconnected -> Offline
 #[derive(Clone)]
 pub enum NetplayState {
     Offline,
@@ -520,6 +1353,14 @@ pub enum NetplayState {
     MatchMaking {
         request: MatchMakingRequest,
     },
+    /// Waiting in a lobby for every member to ready up
+    Lobby {
+        request: LobbyRequest,
+    },
+    /// Waiting for the peer sharing the room code, then punching through NAT to it
+    Rendezvous {
+        request: RendezvousRequest,
+    },
     Disconnected {
         reason: String,
     },
@@ -527,6 +1368,11 @@ pub enum NetplayState {
         local_init: InitConnection,
         pings: [Ping; 255],
     },
+    /// Simulating the confirmed inputs of a session received from a peer.
+    /// `accepted` is false while waiting for the peer to accept the spectator.
+    Spectating {
+        accepted: bool,
+    },
 }
 
 impl NetplayState {
@@ -536,8 +1382,11 @@ impl NetplayState {
             NetplayState::Running => String::from("Running"),
             NetplayState::InitConnection(_) => String::from("InitConnection"),
             NetplayState::MatchMaking { .. } => String::from("MatchMaking"),
+            NetplayState::Lobby { .. } => String::from("Lobby"),
+            NetplayState::Rendezvous { .. } => String::from("Rendezvous"),
             NetplayState::Disconnected { .. } => String::from("Disconnected"),
             NetplayState::PingTest { .. } => String::from("PingTest"),
+            NetplayState::Spectating { .. } => String::from("Spectating"),
         }
     }
 }
@@ -554,10 +1403,67 @@ struct MatchMakingResponse {
     addresses: Vec<SocketAddr>,
 }
 
+/// Sent to the matchmaking server to join a lobby and keep the local machine's status in it up to date
+#[derive(Clone, Serialize)]
+pub struct LobbyRequest {
+    pub room: String,
+    build_version: String,
+    /// Number of players on the local machine
+    pub players: u8,
+    pub ready: bool,
+}
+
+/// Sent by the matchmaking server to every member of a lobby whenever it changes
+#[derive(Clone, Deserialize)]
+struct LobbyStatus {
+    /// Ordered by when they joined
+    members: Vec<LobbyMember>,
+    /// Index of the member receiving this status
+    you: usize,
+}
+
+#[derive(Clone, Deserialize)]
+struct LobbyMember {
+    /// The address the server received the member's requests from
+    address: SocketAddr,
+    players: u8,
+    ready: bool,
+}
+
+/// Sent to the matchmaking server to find the peer sharing the room code
+#[derive(Clone, Serialize)]
+pub struct RendezvousRequest {
+    pub room_code: String,
+    build_version: String,
+}
+
+/// Sent by the matchmaking server once both peers of a room have sent a RendezvousRequest
+#[derive(Clone, Deserialize)]
+struct RendezvousResponse {
+    /// The public address of the peer, as seen by the server
+    peer: SocketAddr,
+    /// Forwards packets to the peer when hole punching fails
+    relay: Option<SocketAddr>,
+}
+
+/// A member of the current lobby, as displayed to the local player
+pub struct LobbyMemberStatus {
+    /// This member is the local machine
+    pub local: bool,
+    pub address: SocketAddr,
+    /// Round trip time to the member, None until a ping is answered
+    pub ping: Option<Duration>,
+    pub players: u8,
+    pub ready: bool,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct InitConnection {
     build_version: String,
     random: u64,
+    input_delay: usize,
+    /// Number of players on the sending machine
+    players: u8,
 }
 
 #[derive(Clone, Default, Copy)]
@@ -571,3 +1477,56 @@ struct InputConfirm {
     inputs: Vec<ControllerInput>,
     frame: usize,
 }
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SpectateRequest {
+    build_version: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SpectateAccept {
+    build_version: String,
+    seed: u64,
+    input_delay: usize,
+    number_of_peers: usize,
+    /// false when the inputs of the first frames have been discarded and the game can no longer be spectated
+    from_start: bool,
+}
+
+/// The inputs of every peer for a single frame, ordered by peer index
+#[derive(Clone, Serialize, Deserialize)]
+struct SpectatorInputs {
+    inputs: Vec<Vec<ControllerInput>>,
+    frame: usize,
+}
+
+struct Spectator {
+    address: SocketAddr,
+    /// Every frame before this has been received by the spectator
+    acked_frames: usize,
+    last_received_frame: usize,
+}
+
+#[test]
+fn command_authenticated_test() {
+    let token = Some("secret");
+    assert_eq!(command_authenticated(b'C', "help", None), Ok(Some("help")));
+    assert_eq!(
+        command_authenticated(b'A', "x\nhelp", None),
+        Ok(Some("help"))
+    );
+    assert_eq!(
+        command_authenticated(b'A', "secret\nhelp", token),
+        Ok(Some("help"))
+    );
+    assert!(command_authenticated(b'A', "wrong\nhelp", token).is_err());
+    assert!(command_authenticated(b'A', "secreT\nhelp", token).is_err());
+    assert!(command_authenticated(b'A', "secret2\nhelp", token).is_err());
+    assert!(command_authenticated(b'C', "help", token).is_err());
+    assert_eq!(command_authenticated(b'X', "help", token), Ok(None));
+    assert_eq!(command_message("help", token), "Asecret\nhelp");
+    assert_eq!(
+        command_connect_address("0.0.0.0:1700"),
+        "127.0.0.1:1700".parse().unwrap()
+    );
+}
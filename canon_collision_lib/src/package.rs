@@ -1,22 +1,94 @@
 use std::collections::HashSet;
 use std::fs;
-use std::fs::File;
 
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "hot_reload")]
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
+#[cfg(feature = "hot_reload")]
+use hotwatch::{Event, Hotwatch};
+
+use serde::Serialize;
 use treeflection::{KeyedContextVec, Node, NodeRunner, NodeToken};
 
-use crate::entity_def::{ActionFrame, CollisionBox, CollisionBoxRole, EntityDef, EntityDefType};
+use crate::entity_def::{
+    ActionFrame, CollisionBox, CollisionBoxRole, EntityDef, EntityDefType, HitBox,
+};
 use crate::files;
+use crate::schema;
 use crate::stage::Stage;
+use crate::theme::Theme;
 
 /// Stores persistent that data that can be modified at runtime.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Package {
     pub stages: KeyedContextVec<Stage>, // TODO: Can just use a std map here
     pub entities: KeyedContextVec<EntityDef>,
+    /// Loaded from theme.json, it is not modified at runtime so it is never saved
+    pub theme: Theme,
     path: PathBuf,
     package_updates: Vec<PackageUpdate>,
+    #[serde(skip)]
+    save_status: Arc<Mutex<SaveStatus>>,
+    /// Hash of the entities and stages when they were last loaded or successfully saved
+    #[serde(skip)]
+    saved_hash: Arc<AtomicU64>,
+    /// Set when the package is reloaded from disc with changes, until taken by `take_reloaded`
+    #[serde(skip)]
+    reloaded: bool,
+    /// Entities with actions that are out of sync with the action enums fail to load instead of being fixed with a warning
+    #[serde(skip)]
+    strict_actions: bool,
+    /// Load from and save to the package cache, skipped by `--nocache`
+    #[serde(skip)]
+    use_cache: bool,
+}
+
+/// Every entity and stage of a package, deserialized ahead of time so that opening a large package only needs to hash its files.
+/// Stored in a single bincode file and rebuilt whenever the hashes of the package files no longer match.
+#[derive(Serialize, Deserialize)]
+struct PackageCache {
+    build_version: String,
+    /// The path relative to the package and the hash of every entity and stage file
+    hashes: Vec<(PathBuf, u64)>,
+    entities: Vec<(String, EntityDef)>,
+    stages: Vec<(String, Stage)>,
+}
+
+#[derive(Clone)]
+pub enum SaveStatus {
+    Idle,
+    Saving,
+    /// Contains the message to notify the user with
+    Completed(String),
+}
+
+impl Default for SaveStatus {
+    fn default() -> SaveStatus {
+        SaveStatus::Idle
+    }
+}
+
+/// Notices when the files of a package change on disc, created by `Package::watch`
+#[cfg(feature = "hot_reload")]
+pub struct PackageWatcher {
+    _hotwatch: Hotwatch,
+    rx: Receiver<()>,
+}
+
+#[cfg(feature = "hot_reload")]
+impl PackageWatcher {
+    /// Returns true if any file of the package changed since this was last called
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while self.rx.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
 }
 
 impl Default for Package {
@@ -30,21 +102,56 @@ impl Package {
         !self.package_updates.is_empty()
     }
 
-    /// Loads and returns the package with the specified name.
-    /// Returns None if the package doesnt exist or is broken.
-    pub fn open(path: PathBuf) -> Option<Package> {
+    /// Loads and returns the package at the specified path.
+    /// Returns an error if the package doesnt exist or is broken.
+    /// Entities with actions that are out of sync with the action enums are fixed with a warning, unless `strict_actions` is set in which case they fail to load.
+    /// When `use_cache` is set, the deserialized package is cached and reused until any of its files change.
+    pub fn open(path: PathBuf, strict_actions: bool, use_cache: bool) -> Result<Package, String> {
         let mut package = Package {
             path,
             stages: KeyedContextVec::new(),
             entities: KeyedContextVec::new(),
+            theme: Theme::default(),
             package_updates: vec![],
+            save_status: Default::default(),
+            saved_hash: Default::default(),
+            reloaded: false,
+            strict_actions,
+            use_cache,
         };
 
-        if package.load().is_ok() {
-            Some(package)
-        } else {
-            None
-        }
+        package.load()?;
+        Ok(package)
+    }
+
+    /// Creates a package from the contents of its entity and stage files, keyed by file name.
+    /// Used where the package cant be read from disc e.g. the web build, so the package has no path and is never cached or saved.
+    pub fn from_files(
+        entity_files: Vec<(String, Vec<u8>)>,
+        stage_files: Vec<(String, Vec<u8>)>,
+        theme: Theme,
+    ) -> Result<Package, String> {
+        let mut package = Package {
+            path: PathBuf::new(),
+            stages: KeyedContextVec::new(),
+            entities: KeyedContextVec::new(),
+            theme,
+            package_updates: vec![],
+            save_status: Default::default(),
+            saved_hash: Default::default(),
+            reloaded: false,
+            strict_actions: false,
+            use_cache: false,
+        };
+
+        let (entities, stages, _) = package.deserialize_files(entity_files, stage_files)?;
+        package.entities = KeyedContextVec::from_vec(entities);
+        package.stages = KeyedContextVec::from_vec(stages);
+        package
+            .saved_hash
+            .store(package.content_hash(), Ordering::Relaxed);
+        package.force_update_entire_package();
+        Ok(package)
     }
 
     pub fn find_package_in_parent_dirs() -> Option<PathBuf> {
@@ -61,90 +168,297 @@ impl Package {
     }
 
     pub fn generate_base(path: PathBuf) -> Package {
+        let mut entity = EntityDef::default();
+        entity.cleanup();
         let mut package = Package {
             path,
             stages: KeyedContextVec::from_vec(vec![(
                 String::from("base_stage.cbor"),
                 Stage::default(),
             )]),
-            entities: KeyedContextVec::from_vec(vec![(
-                String::from("base_fighter.cbor"),
-                EntityDef::default(),
-            )]),
+            entities: KeyedContextVec::from_vec(vec![(String::from("base_fighter.cbor"), entity)]),
+            theme: Theme::default(),
             package_updates: vec![],
+            save_status: Default::default(),
+            saved_hash: Default::default(),
+            reloaded: false,
+            strict_actions: false,
+            use_cache: false,
         };
-        package.save();
+        let files = package.serialize_files();
+        save_files(&package.path, &files).unwrap();
         package.load().unwrap();
         package
     }
 
-    // Write to a new folder first, in case there is a panic in between deleting and writing data.
-    // Then we delete the existing folder and rename the new one
+    /// Saves the package on a background thread so the game is not blocked.
+    /// The package is serialized immediately so changes made during the save are not included.
     pub fn save(&mut self) -> String {
-        // setup new directory to save to
-        let new_path = self.path.with_file_name("package_temp_path");
-        if let Err(_) = fs::create_dir(&new_path) {
-            if let Err(_) = fs::remove_dir_all(&new_path) {
-                return String::from("Save FAILED! Failed to delete an existing *_name_conflict_avoiding_temp_string folder");
-            }
-            if let Err(_) = fs::create_dir(&new_path) {
-                return String::from("Save FAILED! Failed to create *_name_conflict_avoiding_temp_string folder even after succesfully deleting an existing one");
-            }
+        if self.is_saving() {
+            return String::from("Save FAILED! A save is already in progress");
         }
+        *self.save_status.lock().unwrap() = SaveStatus::Saving;
 
-        // save all cbor files
-        for (key, fighter) in self.entities.key_value_iter() {
-            files::save_struct_cbor(&new_path.join("Entities").join(key), fighter);
+        let files = self.serialize_files();
+        let path = self.path.clone();
+        let save_status = self.save_status.clone();
+        let saved_hash = self.saved_hash.clone();
+        let hash = self.content_hash();
+        thread::spawn(move || {
+            let message = match save_files(&path, &files) {
+                Ok(()) => {
+                    saved_hash.store(hash, Ordering::Relaxed);
+                    String::from("Save completed successfully.")
+                }
+                Err(err) => format!("Save FAILED! {}", err),
+            };
+            *save_status.lock().unwrap() = SaveStatus::Completed(message);
+        });
+
+        String::from("Saving in the background...")
+    }
+
+    /// The entities or stages have been modified since they were loaded or saved
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.content_hash() != self.saved_hash.load(Ordering::Relaxed)
+    }
+
+    fn content_hash(&self) -> u64 {
+        files::hash_struct_bincode(&(&self.entities, &self.stages))
+    }
+
+    pub fn is_saving(&self) -> bool {
+        matches!(*self.save_status.lock().unwrap(), SaveStatus::Saving)
+    }
+
+    /// Returns the completion message of the last save, only once.
+    pub fn take_save_result(&mut self) -> Option<String> {
+        let mut save_status = self.save_status.lock().unwrap();
+        if let SaveStatus::Completed(message) = &*save_status {
+            let message = message.clone();
+            *save_status = SaveStatus::Idle;
+            Some(message)
+        } else {
+            None
         }
+    }
 
-        for (key, stage) in self.stages.key_value_iter() {
-            files::save_struct_cbor(&new_path.join("Stages").join(key), stage);
+    /// Returns true once after the package is reloaded from disc with changes
+    pub fn take_reloaded(&mut self) -> bool {
+        std::mem::take(&mut self.reloaded)
+    }
+
+    /// Reloads the package from disc, all unsaved changes are lost.
+    /// Returns the lines of `Package::diff` describing what the reload changed.
+    /// When the files on disc match the package, e.g. because the package just saved them, the package is left untouched.
+    pub fn reload(&mut self) -> Result<Vec<String>, String> {
+        let previous = self.clone();
+        self.load()?;
+        let diff = previous.diff(self);
+        if diff.is_empty() {
+            // keeps the renderer from being sent the entire package again
+            *self = previous;
+        } else {
+            self.reloaded = true;
         }
+        Ok(diff)
+    }
 
-        // replace old directory with new directory
-        fs::remove_dir_all(&self.path).ok();
-        if let Err(_) = fs::rename(new_path, &self.path) {
-            return String::from("Save FAILED! Failed to rename temp package");
+    /// Watches the entity and stage files of the package so that edits made outside of the game can be hot reloaded
+    #[cfg(feature = "hot_reload")]
+    pub fn watch(&self) -> Result<PackageWatcher, String> {
+        let (tx, rx) = mpsc::channel();
+        let mut hotwatch = Hotwatch::new().map_err(|x| x.to_string())?;
+        for dir in ["Entities", "Stages"] {
+            let tx = tx.clone();
+            hotwatch
+                .watch(self.path.join(dir), move |event: Event| {
+                    if let Event::Write(_) | Event::Create(_) | Event::Remove(_) = event {
+                        tx.send(()).ok();
+                    }
+                })
+                .map_err(|x| format!("Failed to watch the package {}: {}", dir, x))?;
         }
+        Ok(PackageWatcher {
+            _hotwatch: hotwatch,
+            rx,
+        })
+    }
 
-        String::from("Save completed successfully.")
+    /// Returns the path relative to the package and the cbor encoding of every entity and stage
+    fn serialize_files(&self) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files = vec![];
+        for (key, fighter) in self.entities.key_value_iter() {
+            files.push((
+                Path::new("Entities").join(key),
+                serde_cbor::to_vec(fighter).unwrap(),
+            ));
+        }
+        for (key, stage) in self.stages.key_value_iter() {
+            files.push((
+                Path::new("Stages").join(key),
+                serde_cbor::to_vec(stage).unwrap(),
+            ));
+        }
+        files
     }
 
     pub fn load(&mut self) -> Result<(), String> {
-        let mut entities = vec![];
-        if let Ok(dir) = fs::read_dir(self.path.join("Entities")) {
-            for path in dir {
-                let full_path = path.unwrap().path();
-                let key = full_path.file_name().unwrap().to_str().unwrap().to_string();
-
-                let reader = File::open(full_path).map_err(|x| format!("{:?}", x))?;
-                let mut entity: EntityDef =
-                    serde_cbor::from_reader(reader).map_err(|x| format!("{:?}", x))?;
-                entity.cleanup();
-                entities.push((key, entity));
+        self.theme = Theme::load(&self.path);
+
+        let entity_files = read_package_dir(&self.path.join("Entities"))?;
+        let stage_files = read_package_dir(&self.path.join("Stages"))?;
+
+        let hashes: Vec<(PathBuf, u64)> = entity_files
+            .iter()
+            .map(|(key, data)| (Path::new("Entities").join(key), fnv1a_hash(data)))
+            .chain(
+                stage_files
+                    .iter()
+                    .map(|(key, data)| (Path::new("Stages").join(key), fnv1a_hash(data))),
+            )
+            .collect();
+
+        if self.use_cache {
+            if let Ok(cache) = files::load_struct_bincode::<PackageCache>(&self.cache_path()) {
+                if cache.build_version == files::build_version() && cache.hashes == hashes {
+                    self.entities = KeyedContextVec::from_vec(cache.entities);
+                    self.stages = KeyedContextVec::from_vec(cache.stages);
+                    self.saved_hash
+                        .store(self.content_hash(), Ordering::Relaxed);
+                    self.force_update_entire_package();
+                    return Ok(());
+                }
             }
         }
-        entities.sort_by_key(|x| x.0.clone());
-        self.entities = KeyedContextVec::from_vec(entities);
 
-        let mut stages = vec![];
-        if let Ok(dir) = fs::read_dir(self.path.join("Stages")) {
-            for path in dir {
-                let full_path = path.unwrap().path();
-                let key = full_path.file_name().unwrap().to_str().unwrap().to_string();
-
-                let reader = File::open(full_path).map_err(|x| format!("{:?}", x))?;
-                let stage = serde_cbor::from_reader(reader).map_err(|x| format!("{:?}", x))?;
-                stages.push((key, stage));
+        let (mut entities, mut stages, fixed) =
+            self.deserialize_files(entity_files, stage_files)?;
+
+        // The cache must match the files on disk, so it is not written when the loaded entities differ from them
+        if self.use_cache && !fixed {
+            let cache = PackageCache {
+                build_version: files::build_version(),
+                hashes,
+                entities,
+                stages,
+            };
+            match bincode::serialize(&cache) {
+                Ok(bytes) => {
+                    if let Err(err) = files::save_bytes_atomic(&self.cache_path(), &bytes) {
+                        warn!("Failed to save the package cache: {}", err);
+                    }
+                }
+                Err(err) => warn!("Failed to serialize the package cache: {:?}", err),
             }
+            entities = cache.entities;
+            stages = cache.stages;
         }
-        stages.sort_by_key(|x| x.0.clone());
+
+        self.entities = KeyedContextVec::from_vec(entities);
         self.stages = KeyedContextVec::from_vec(stages);
+        self.saved_hash
+            .store(self.content_hash(), Ordering::Relaxed);
 
         self.force_update_entire_package();
         Ok(())
     }
 
+    /// Deserializes the raw entity and stage files of a package.
+    /// Also returns true when any entity had its actions fixed, in which case the entities no longer match their files.
+    #[allow(clippy::type_complexity)]
+    fn deserialize_files(
+        &self,
+        entity_files: Vec<(String, Vec<u8>)>,
+        stage_files: Vec<(String, Vec<u8>)>,
+    ) -> Result<(Vec<(String, EntityDef)>, Vec<(String, Stage)>, bool), String> {
+        let mut entities = vec![];
+        let mut drift_reports = vec![];
+        let mut fixed = false;
+        for (key, data) in entity_files {
+            let mut entity: EntityDef =
+                serde_cbor::from_slice(&data).map_err(|x| format!("{:?}", x))?;
+            let drift = entity.action_drift();
+            if !drift.is_empty() {
+                if self.strict_actions {
+                    drift_reports.push(drift.report(&key));
+                } else {
+                    entity.cleanup();
+                    fixed = true;
+                    warn!(
+                        "Fixed actions, save the package to keep the fix:\n{}",
+                        drift.report(&key)
+                    );
+                }
+            }
+            entities.push((key, entity));
+        }
+        if !drift_reports.is_empty() {
+            return Err(drift_reports.join("\n"));
+        }
+
+        let mut stages = vec![];
+        for (key, data) in stage_files {
+            let stage: Stage = serde_cbor::from_slice(&data).map_err(|x| format!("{:?}", x))?;
+            stages.push((key, stage));
+        }
+
+        Ok((entities, stages, fixed))
+    }
+
+    /// Each package gets its own cache file, named after the hash of the package path
+    fn cache_path(&self) -> PathBuf {
+        let path = fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        files::get_path().join("PackageCache").join(format!(
+            "{:016x}.bincode",
+            fnv1a_hash(path.to_string_lossy().as_bytes())
+        ))
+    }
+
+    /// Lines summarizing what changed between this package and `new`, so modders can confirm their edit loaded.
+    /// Empty when nothing changed.
+    pub fn diff(&self, new: &Package) -> Vec<String> {
+        let mut lines = vec![];
+        for (key, new_entity) in new.entities.key_value_iter() {
+            match self.entities.key_to_value(key) {
+                Some(old_entity) => {
+                    if !serialized_eq(old_entity, new_entity) {
+                        lines.push(format!("Entity changed: {}", key));
+                        lines.extend(entity_diff(old_entity, new_entity));
+                    }
+                }
+                None => lines.push(format!("Entity added: {}", key)),
+            }
+        }
+        for key in self.entities.keys() {
+            if !new.entities.contains_key(&key) {
+                lines.push(format!("Entity removed: {}", key));
+            }
+        }
+
+        for (key, new_stage) in new.stages.key_value_iter() {
+            match self.stages.key_to_value(key) {
+                Some(old_stage) => {
+                    if !serialized_eq(old_stage, new_stage) {
+                        lines.push(format!("Stage changed: {}", key));
+                    }
+                }
+                None => lines.push(format!("Stage added: {}", key)),
+            }
+        }
+        for key in self.stages.keys() {
+            if !new.stages.contains_key(&key) {
+                lines.push(format!("Stage removed: {}", key));
+            }
+        }
+
+        if !serialized_eq(&self.theme, &new.theme) {
+            lines.push(String::from("Theme changed"));
+        }
+
+        lines
+    }
+
     pub fn new_fighter_frame(&mut self, fighter: &str, action: &str, frame: usize) {
         let new_frame = {
             let action_frames = &self.entities[fighter].actions[action].frames;
@@ -314,6 +628,39 @@ impl Package {
         }
     }
 
+    /// Applies `modify` to every hitbox in `modified_colboxes`, colboxes with any other role are left untouched.
+    pub fn modify_fighter_hitboxes<F>(
+        &mut self,
+        fighter: &str,
+        action: &str,
+        frame: usize,
+        modified_colboxes: &HashSet<usize>,
+        modify: F,
+    ) where
+        F: Fn(&mut HitBox),
+    {
+        let fighter_frame = &mut self.entities[fighter].actions[action].frames[frame];
+        for i in modified_colboxes {
+            if let CollisionBoxRole::Hit(ref mut hitbox) = fighter_frame.colboxes[*i].role {
+                modify(hitbox);
+            }
+        }
+
+        self.package_updates
+            .push(PackageUpdate::DeleteFighterFrame {
+                fighter: fighter.to_string(),
+                action: action.to_string(),
+                frame_index: frame,
+            });
+        self.package_updates
+            .push(PackageUpdate::InsertFighterFrame {
+                fighter: fighter.to_string(),
+                action: action.to_string(),
+                frame_index: frame,
+                frame: fighter_frame.clone(),
+            });
+    }
+
     pub fn resize_fighter_colboxes(
         &mut self,
         fighter: &str,
@@ -519,6 +866,7 @@ Commands:
 *   help    - display this help
 *   save    - save changes to disc
 *   reload  - reload from disc, all changes are lost
+*   schema  - display every property with its type and documentation as JSON
 
 Accessors:
 *   .entities - KeyedContextVec
@@ -526,13 +874,18 @@ Accessors:
             ),
             NodeToken::Custom(action, _) => match action.as_ref() {
                 "save" => self.save(),
-                "reload" => {
-                    if let Err(err) = self.load() {
-                        err
-                    } else {
-                        String::from("Reload completed successfully.")
+                "schema" => schema::generate(&mut self.clone(), schema::NODE_DOCS),
+                "reload" => match self.reload() {
+                    Ok(diff) => {
+                        let mut lines = vec![String::from("Reload completed successfully.")];
+                        if diff.is_empty() {
+                            lines.push(String::from("No changes"));
+                        }
+                        lines.extend(diff);
+                        lines.join("\n")
                     }
-                }
+                    Err(err) => err,
+                },
                 _ => {
                     format!("Package cannot '{}'", action)
                 }
@@ -547,6 +900,59 @@ Accessors:
     }
 }
 
+/// Each file is written atomically, then files of deleted entities and stages are removed.
+fn save_files(package_path: &Path, files: &[(PathBuf, Vec<u8>)]) -> Result<(), String> {
+    for (path, bytes) in files {
+        files::save_bytes_atomic(&package_path.join(path), bytes)
+            .map_err(|err| format!("Failed to write {:?}: {}", path, err))?;
+    }
+
+    for dir in &["Entities", "Stages"] {
+        if let Ok(entries) = fs::read_dir(package_path.join(dir)) {
+            for entry in entries.flatten() {
+                let path = Path::new(dir).join(entry.file_name());
+                if !files.iter().any(|x| x.0 == path) {
+                    fs::remove_file(entry.path())
+                        .map_err(|err| format!("Failed to delete {:?}: {:?}", path, err))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn entity_diff(old: &EntityDef, new: &EntityDef) -> Vec<String> {
+    let mut lines = vec![];
+    for (key, new_action) in new.actions.key_value_iter() {
+        match old.actions.key_to_value(key) {
+            Some(old_action) => {
+                let old_frames = old_action.frames.len();
+                let new_frames = new_action.frames.len();
+                if old_frames != new_frames {
+                    lines.push(format!(
+                        "    {}: {} -> {} frames",
+                        key, old_frames, new_frames
+                    ));
+                } else if !serialized_eq(old_action, new_action) {
+                    lines.push(format!("    {}: modified", key));
+                }
+            }
+            None => lines.push(format!("    {}: added", key)),
+        }
+    }
+    for key in old.actions.keys() {
+        if !new.actions.contains_key(&key) {
+            lines.push(format!("    {}: removed", key));
+        }
+    }
+    lines
+}
+
+/// Compares values that do not implement PartialEq
+fn serialized_eq<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_cbor::to_vec(a).ok() == serde_cbor::to_vec(b).ok()
+}
+
 // Finer grained changes are used when speed is needed
 #[derive(Clone, Serialize, Deserialize)]
 pub enum PackageUpdate {
@@ -572,3 +978,64 @@ pub enum PackageUpdate {
         stage: Stage,
     },
 }
+
+/// Returns the key and contents of every file in the directory, sorted by key.
+/// A missing directory is treated as empty.
+fn read_package_dir(dir: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = vec![];
+    if let Ok(dir) = fs::read_dir(dir) {
+        for path in dir {
+            let full_path = path.unwrap().path();
+            let key = full_path.file_name().unwrap().to_str().unwrap().to_string();
+            if key.ends_with(".tmp") {
+                // left behind by an interrupted save
+                continue;
+            }
+            files.push((key, fs::read(full_path).map_err(|x| format!("{:?}", x))?));
+        }
+    }
+    files.sort_by_key(|x| x.0.clone());
+    Ok(files)
+}
+
+/// 64 bit FNV-1a, only used to detect changes so it doesnt need to be cryptographic
+pub(crate) fn fnv1a_hash(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[test]
+fn entity_diff_test() {
+    use crate::entity_def::ActionDef;
+
+    let mut old = EntityDef::default();
+    old.actions.push(String::from("Jab"), ActionDef::default());
+    old.actions
+        .push(String::from("Dtilt"), ActionDef::default());
+
+    let mut new = old.clone();
+    new.actions
+        .push(String::from("Utilt"), ActionDef::default());
+    new.actions[0].frames.push(ActionFrame::default());
+    new.actions[1].iasa = 5;
+
+    assert_eq!(
+        entity_diff(&old, &new),
+        vec![
+            String::from("    Jab: 1 -> 2 frames"),
+            String::from("    Dtilt: modified"),
+            String::from("    Utilt: added"),
+        ]
+    );
+}
+
+#[test]
+fn fnv1a_hash_test() {
+    assert_eq!(fnv1a_hash(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv1a_hash(b"a"), 0xaf63dc4c8601ec8c);
+    assert_ne!(fnv1a_hash(b"ab"), fnv1a_hash(b"ba"));
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use treeflection::{Node, NodeRunner};
+
+include!(concat!(env!("OUT_DIR"), "/node_docs.rs"));
+
+/// Protects against unbounded recursion if a node ever contains itself
+const MAX_DEPTH: usize = 32;
+
+/// Describes a node of the treeflection tree, generated by running `help` on every node.
+/// Only the first item of a collection is described as every item has the same schema.
+#[derive(Serialize)]
+pub struct SchemaNode {
+    #[serde(rename = "type")]
+    pub ty: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<String>,
+    /// Keyed by the accessor used to reach the child e.g. `.frames` or `[0]`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub children: BTreeMap<String, SchemaNode>,
+}
+
+/// Returns the schema of `root` and all of its children as JSON.
+/// `docs` are the doc comments collected by the build script, see `NODE_DOCS`.
+pub fn generate<T: Node>(root: &mut T, docs: &[(&str, &str, &str)]) -> String {
+    let schema = node_schema(root, "", String::from("Unknown"), None, docs, 0);
+    serde_json::to_string_pretty(&schema).unwrap()
+}
+
+fn node_schema<T: Node>(
+    root: &mut T,
+    path: &str,
+    fallback_ty: String,
+    doc: Option<String>,
+    docs: &[(&str, &str, &str)],
+    depth: usize,
+) -> SchemaNode {
+    let help = match NodeRunner::new(&format!("{}:help", path)) {
+        Ok(runner) => root.node_step(runner),
+        Err(_) => String::new(),
+    };
+    let help = parse_help(&help);
+
+    let ty = help.ty.unwrap_or(fallback_ty);
+    let doc = doc.or_else(|| find_doc(docs, &ty, ""));
+    let mut children = BTreeMap::new();
+    if depth < MAX_DEPTH {
+        for (accessor, child_ty) in help.accessors {
+            let child_doc = accessor
+                .strip_prefix('.')
+                .and_then(|member| find_doc(docs, &ty, member));
+            let child_path = format!("{}{}", path, accessor);
+            let child = node_schema(root, &child_path, child_ty, child_doc, docs, depth + 1);
+            children.insert(accessor, child);
+        }
+    }
+
+    SchemaNode {
+        ty,
+        doc,
+        commands: help.commands,
+        variants: help.variants,
+        children,
+    }
+}
+
+fn find_doc(docs: &[(&str, &str, &str)], ty: &str, member: &str) -> Option<String> {
+    docs.iter()
+        .find(|x| x.0 == ty && x.1 == member)
+        .map(|x| x.2.to_string())
+}
+
+#[derive(Default)]
+struct Help {
+    ty: Option<String>,
+    commands: Vec<String>,
+    variants: Vec<String>,
+    /// (accessor, type) the type is only known for struct and enum fields
+    accessors: Vec<(String, String)>,
+}
+
+/// Parses the output of the `help` command, which treeflection formats as a title followed by lists of `*   name - description`.
+/// Errors, such as indexing an empty vector, have no title and parse to an empty `Help`.
+fn parse_help(help: &str) -> Help {
+    let mut result = Help::default();
+    let mut lines = help.lines().map(|x| x.trim()).filter(|x| !x.is_empty());
+    result.ty = match lines.next().and_then(|x| x.strip_suffix(" Help")) {
+        Some(ty) => Some(ty.to_string()),
+        None => return result,
+    };
+
+    let mut section = "";
+    for line in lines {
+        let item = match line.strip_prefix('*') {
+            Some(item) => item.trim(),
+            None => {
+                section = line;
+                continue;
+            }
+        };
+        let mut split = item.splitn(2, " - ");
+        let name = split.next().unwrap_or("").trim();
+        let description = split.next().unwrap_or("").trim();
+        match section {
+            "Commands:" | "Actions:" => {
+                if let Some(command) = name.split_whitespace().next() {
+                    result.commands.push(command.to_string());
+                }
+            }
+            "Valid variants:" => result.variants.push(name.to_string()),
+            _ => {
+                // Only the first item of a collection is described, the context accessor and length would be duplicates
+                let accessor = match name {
+                    "[index]" | "[INDEX]" => String::from("[0]"),
+                    "[?]" | "[key]" | ".length" => continue,
+                    name if name.starts_with('.') || name.starts_with('[') => name.to_string(),
+                    name => format!(".{}", name),
+                };
+                if !result.accessors.iter().any(|x| x.0 == accessor) {
+                    result.accessors.push((accessor, description.to_string()));
+                }
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn parse_help_test() {
+    let help = parse_help(
+        r#"
+ActionFrame Help
+
+Actions:
+*   help  - display this help
+*   get   - display JSON
+
+Accessors:
+*   colboxes - ContextVec
+*   tags - Vec"#,
+    );
+    assert_eq!(help.ty.as_deref(), Some("ActionFrame"));
+    assert_eq!(help.commands, vec!["help", "get"]);
+    assert_eq!(
+        help.accessors,
+        vec![
+            (String::from(".colboxes"), String::from("ContextVec")),
+            (String::from(".tags"), String::from("Vec"))
+        ]
+    );
+
+    let help = parse_help("Used index 0 on an empty vector");
+    assert!(help.ty.is_none());
+}
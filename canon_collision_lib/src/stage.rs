@@ -1,8 +1,10 @@
 use crate::files::engine_version;
 use crate::geometry::Rect;
+#[cfg(feature = "window")]
 use winit_input_helper::WinitInputHelper;
 
 use treeflection::{ContextVec, Node, NodeRunner, NodeToken};
+#[cfg(feature = "window")]
 use winit::event::VirtualKeyCode;
 
 #[derive(Clone, Serialize, Deserialize, Node)]
@@ -12,8 +14,16 @@ pub struct Stage {
     pub surfaces: ContextVec<Surface>,
     pub blast: Rect,
     pub camera: Rect,
+    /// Width of the area visible when the camera is zoomed in as far as it can
+    pub camera_min_width: f32,
+    /// Width of the area visible when the camera is zoomed out as far as it can, the camera still stays within `camera`
+    pub camera_max_width: f32,
     pub spawn_points: ContextVec<SpawnPoint>,
     pub respawn_points: ContextVec<SpawnPoint>,
+    /// Items that can spawn on the floors of this stage while items are enabled
+    pub item_spawns: ContextVec<ItemSpawn>,
+    /// Average number of frames between item spawns at the medium item frequency, 0 disables item spawns
+    pub item_spawn_frames: u64,
 }
 
 impl Default for Stage {
@@ -31,6 +41,7 @@ impl Default for Stage {
                 traction: 1.0,
                 pass_through: false,
             }),
+            ..Surface::default()
         };
 
         let second_platform = Surface {
@@ -46,6 +57,7 @@ impl Default for Stage {
                 traction: 1.0,
                 pass_through: true,
             }),
+            ..Surface::default()
         };
 
         let blast = Rect {
@@ -114,8 +126,15 @@ impl Default for Stage {
             surfaces: ContextVec::from_vec(vec![main_platform, second_platform]),
             blast,
             camera,
+            camera_min_width: 100.0,
+            camera_max_width: 300.0,
             spawn_points,
             respawn_points,
+            item_spawns: ContextVec::from_vec(vec![ItemSpawn {
+                entity: "PerfectlyGenericObject.cbor".to_string(),
+                weight: 1,
+            }]),
+            item_spawn_frames: 600,
         }
     }
 }
@@ -145,6 +164,43 @@ impl Stage {
 
         FloorInfo { left_i, right_i }
     }
+
+    /// Picks an item from `item_spawns` where each item is `weight` times as likely as an item of weight 1.
+    /// `roll` is in the range [0.0, 1.0)
+    pub fn choose_item_spawn(&self, roll: f32) -> Option<&ItemSpawn> {
+        let total: u64 = self.item_spawns.iter().map(|x| x.weight).sum();
+        let mut remaining = (roll * total as f32) as u64;
+        for item_spawn in self.item_spawns.iter() {
+            if remaining < item_spawn.weight {
+                return Some(item_spawn);
+            }
+            remaining -= item_spawn.weight;
+        }
+        None
+    }
+
+    /// Moves every surface with a path to its position on the passed frame
+    pub fn step_surfaces(&mut self, frame: usize) {
+        for surface in self.surfaces.iter_mut() {
+            surface.step_path(frame);
+        }
+    }
+
+    /// Moves every surface from its authored position to its position once the passed frame has been stepped
+    pub fn apply_path_offsets(&mut self, frame: usize) {
+        for surface in self.surfaces.iter_mut() {
+            let (x, y) = surface.stepped_path_offset(frame);
+            surface.translate(x, y);
+        }
+    }
+
+    /// Moves every surface from its position once the passed frame has been stepped back to its authored position
+    pub fn remove_path_offsets(&mut self, frame: usize) {
+        for surface in self.surfaces.iter_mut() {
+            let (x, y) = surface.stepped_path_offset(frame);
+            surface.translate(-x, -y);
+        }
+    }
 }
 
 fn f32_equal(a: f32, b: f32) -> bool {
@@ -167,6 +223,17 @@ pub struct Surface {
     pub wall: bool,
     pub ceiling: bool,
     pub floor: Option<Floor>,
+    /// Keyframed offsets from the authored position, the path loops after the last keyframe.
+    /// Surfaces without keyframes are static.
+    pub path: ContextVec<PathKeyframe>,
+    /// Damages and launches fighters standing on the surface
+    pub hazard: Option<Hazard>,
+    /// Distance moved during the last step, entities leaving the surface inherit this velocity.
+    /// Recalculated every step so it is not saved.
+    #[serde(skip)]
+    pub vel_x: f32,
+    #[serde(skip)]
+    pub vel_y: f32,
 }
 
 // TODO: coloring
@@ -174,6 +241,33 @@ pub struct Surface {
 // g = if ceiling { 0.5 } else { 0.0 }
 // b = if wall { 0.5 } else { 0.0 }
 
+/// The surface is offset by (x, y) on `frame`, the offset is linearly interpolated between keyframes
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct PathKeyframe {
+    pub frame: u64,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize, Node)]
+pub struct Hazard {
+    pub damage: f32,
+    /// Knockback the fighter is launched with regardless of their damage
+    pub knockback: f32,
+    /// Launch angle in degrees, 90 launches straight up
+    pub angle: f32,
+}
+
+impl Default for Hazard {
+    fn default() -> Hazard {
+        Hazard {
+            damage: 10.0,
+            knockback: 100.0,
+            angle: 90.0,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Node)]
 pub struct Floor {
     pub traction: f32,
@@ -220,7 +314,67 @@ impl Surface {
             floor,
             grab1: false,
             grab2: false,
+            path: ContextVec::new(),
+            hazard: None,
+            vel_x: 0.0,
+            vel_y: 0.0,
+        }
+    }
+
+    /// Returns the offset from the authored position at the passed frame
+    pub fn path_offset(&self, frame: usize) -> (f32, f32) {
+        let (first, last) = match (self.path.first(), self.path.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return (0.0, 0.0),
+        };
+
+        let frame = if last.frame > 0 {
+            frame as u64 % last.frame
+        } else {
+            0
+        };
+        if frame <= first.frame {
+            return (first.x, first.y);
+        }
+
+        for window in self.path.windows(2) {
+            let (start, end) = (&window[0], &window[1]);
+            if frame >= start.frame && frame < end.frame {
+                let t = (frame - start.frame) as f32 / (end.frame - start.frame) as f32;
+                return (
+                    start.x + (end.x - start.x) * t,
+                    start.y + (end.y - start.y) * t,
+                );
+            }
         }
+        (last.x, last.y)
+    }
+
+    /// Returns the offset from the authored position once the passed frame has been stepped.
+    /// Frame 0 is before the first step, where the surface is at its authored position.
+    fn stepped_path_offset(&self, frame: usize) -> (f32, f32) {
+        if frame == 0 {
+            (0.0, 0.0)
+        } else {
+            self.path_offset(frame)
+        }
+    }
+
+    /// Moves the surface from its position on the path at the previous frame to its position at the passed frame.
+    /// Only the movement is applied so the points do not need to store the offset, frames must be stepped in order starting from 1.
+    pub fn step_path(&mut self, frame: usize) {
+        let (path_x, path_y) = self.stepped_path_offset(frame);
+        let (prev_x, prev_y) = self.stepped_path_offset(frame.saturating_sub(1));
+        self.vel_x = path_x - prev_x;
+        self.vel_y = path_y - prev_y;
+        self.translate(self.vel_x, self.vel_y);
+    }
+
+    fn translate(&mut self, x: f32, y: f32) {
+        self.x1 += x;
+        self.x2 += x;
+        self.y1 += y;
+        self.y2 += y;
     }
 
     pub fn is_pass_through(&self) -> bool {
@@ -349,6 +503,13 @@ impl Surface {
     }
 }
 
+#[derive(Clone, Default, Serialize, Deserialize, Node)]
+pub struct ItemSpawn {
+    /// Key of the item in the package entities
+    pub entity: String,
+    pub weight: u64,
+}
+
 #[derive(Clone, Default, Serialize, Deserialize, Node)]
 pub struct SpawnPoint {
     pub x: f32,
@@ -376,6 +537,7 @@ pub struct DebugStage {
 }
 
 impl DebugStage {
+    #[cfg(feature = "window")]
     pub fn step(&mut self, os_input: &WinitInputHelper) {
         if os_input.key_pressed_os(VirtualKeyCode::F1) {
             self.blast = !self.blast;
@@ -462,3 +624,74 @@ impl Default for RenderStageMode {
         RenderStageMode::Normal
     }
 }
+
+#[test]
+fn surface_path_test() {
+    let mut surface = Surface::new(-10.0, 0.0, 10.0, 0.0, true, false, false);
+    surface.path = ContextVec::from_vec(vec![
+        PathKeyframe {
+            frame: 0,
+            x: 0.0,
+            y: 0.0,
+        },
+        PathKeyframe {
+            frame: 4,
+            x: 20.0,
+            y: 0.0,
+        },
+        PathKeyframe {
+            frame: 8,
+            x: 0.0,
+            y: 0.0,
+        },
+    ]);
+
+    for frame in 1..=2 {
+        surface.step_path(frame);
+    }
+    assert_eq!(surface.p1(), (0.0, 0.0));
+    assert_eq!(surface.vel_x, 5.0);
+
+    let mut stage = Stage {
+        surfaces: ContextVec::from_vec(vec![surface.clone()]),
+        ..Stage::default()
+    };
+    stage.remove_path_offsets(2);
+    assert_eq!(stage.surfaces[0].p1(), (-10.0, 0.0));
+    stage.apply_path_offsets(2);
+    assert_eq!(stage.surfaces[0].p1(), (0.0, 0.0));
+
+    for frame in 3..=6 {
+        surface.step_path(frame);
+    }
+    assert_eq!(surface.p1(), (0.0, 0.0));
+
+    for frame in 7..=8 {
+        surface.step_path(frame);
+    }
+    assert_eq!(surface.p1(), (-10.0, 0.0));
+    assert_eq!(surface.vel_x, -5.0);
+}
+
+#[test]
+fn choose_item_spawn_test() {
+    let mut stage = Stage {
+        item_spawns: ContextVec::from_vec(vec![
+            ItemSpawn {
+                entity: "a".to_string(),
+                weight: 1,
+            },
+            ItemSpawn {
+                entity: "b".to_string(),
+                weight: 3,
+            },
+        ]),
+        ..Stage::default()
+    };
+    assert_eq!(stage.choose_item_spawn(0.0).unwrap().entity, "a");
+    assert_eq!(stage.choose_item_spawn(0.3).unwrap().entity, "b");
+    assert_eq!(stage.choose_item_spawn(0.99).unwrap().entity, "b");
+
+    stage.item_spawns = ContextVec::new();
+    assert!(stage.choose_item_spawn(0.5).is_none());
+}
@@ -0,0 +1,56 @@
+use crate::files;
+
+use std::path::Path;
+
+/// The look of the menus.
+/// A package can ship its own in theme.json so that total conversion mods feel cohesive.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Displayed on the main menu and as the window title
+    pub title: String,
+    /// Color the menus are cleared to
+    pub background_color: [f32; 4],
+    pub text_color: [f32; 4],
+    /// Color of the currently selected menu option
+    pub selected_color: [f32; 4],
+    /// File name of a font in the assets fonts directory e.g. `Title.ttf`, used for all menu text.
+    /// The default font is used when None or the font fails to load.
+    pub font: Option<String>,
+    /// Name of a model in the assets models directory to display behind the character select screen
+    pub css_background_model: Option<String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            title: String::from("Canon Collision"),
+            background_color: [0.0, 0.0, 0.0, 1.0],
+            text_color: [1.0, 1.0, 1.0, 1.0],
+            selected_color: [1.0, 1.0, 1.0, 1.0],
+            font: None,
+            css_background_model: None,
+        }
+    }
+}
+
+impl Theme {
+    /// Loads theme.json from the package directory.
+    /// Packages without a theme and broken themes use the default theme.
+    pub fn load(package_path: &Path) -> Theme {
+        let path = package_path.join("theme.json");
+        if !path.exists() {
+            return Theme::default();
+        }
+        match files::load_struct_json(&path) {
+            Ok(theme) => theme,
+            Err(err) => {
+                warn!(
+                    "Failed to load theme.json, using the default theme: {}",
+                    err
+                );
+                Theme::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::network::token_eq;
+
+use serde::Serialize;
+use treeflection::{Node, NodeRunner};
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::str;
+
+/// Appended to the key sent by the client to generate the handshake response
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Clients that send more than this without completing a message are disconnected
+const MAX_BUFFER_LEN: usize = 1024 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Exposes the command interface over WebSocket so overlays and browser based tools can use it.
+///
+/// Clients send text messages containing JSON e.g. `{"command": "package.entities[0].name:get", "token": "SECRET", "id": 1}`
+/// where `token` is only needed when the command_token is set and `id` is optionally echoed back in the response.
+/// Responses and game events are sent as JSON with a `type` field:
+///     `{"type": "CommandOutput", "id": 1, "output": "..."}`
+///     `{"type": "Error", "id": 1, "message": "..."}`
+///     plus any events passed to `send_events`
+pub struct WebSocketCommandLine {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    /// When set, only commands sent with this token are run
+    token: Option<String>,
+}
+
+struct Client {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    handshake_complete: bool,
+    /// Commands received since the last call to `run_commands`
+    pending: Vec<CommandRequest>,
+}
+
+#[derive(Deserialize)]
+struct CommandRequest {
+    command: String,
+    token: Option<String>,
+    id: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Response<'a> {
+    CommandOutput { id: Option<u64>, output: &'a str },
+    Error { id: Option<u64>, message: &'a str },
+}
+
+impl WebSocketCommandLine {
+    /// Listens for WebSocket connections on `address`, returns None when it cant be listened on.
+    /// When `token` is set, commands without it are refused.
+    pub fn new(address: &str, token: Option<String>) -> Option<WebSocketCommandLine> {
+        let address: SocketAddr = match address.parse() {
+            Ok(address) => address,
+            Err(_) => {
+                println!(
+                    "websocket_address {:?} is not a valid address and port",
+                    address
+                );
+                return None;
+            }
+        };
+        if !address.ip().is_loopback() && token.is_none() {
+            println!("Warning: the websocket command interface is reachable from other machines without a command_token");
+        }
+
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                println!("Failed to listen for websockets on {}: {}", address, e);
+                return None;
+            }
+        };
+        listener.set_nonblocking(true).unwrap();
+
+        Some(WebSocketCommandLine {
+            listener,
+            clients: vec![],
+            token,
+        })
+    }
+
+    /// Accepts new connections and receives messages from connected clients.
+    /// Commands that could not be run since the previous step, because no local game was running, are refused.
+    pub fn step(&mut self) {
+        for client in &mut self.clients {
+            for request in std::mem::take(&mut client.pending) {
+                let response = Response::Error {
+                    id: request.id,
+                    message: "Commands can only be run during a local game",
+                };
+                client.send_json(&response);
+            }
+        }
+
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                stream.set_nodelay(true).ok();
+                self.clients.push(Client {
+                    stream,
+                    buffer: vec![],
+                    handshake_complete: false,
+                    pending: vec![],
+                });
+            }
+        }
+
+        self.clients = std::mem::take(&mut self.clients)
+            .into_iter()
+            .filter_map(|mut x| x.receive().then(|| x))
+            .collect();
+    }
+
+    /// Runs the commands received by the last step
+    pub fn run_commands<T>(&mut self, root_node: &mut T)
+    where
+        T: Node,
+    {
+        for client in &mut self.clients {
+            for request in std::mem::take(&mut client.pending) {
+                let authenticated = match (&self.token, &request.token) {
+                    (Some(token), Some(sent)) => token_eq(sent, token),
+                    (Some(_), None) => false,
+                    (None, _) => true,
+                };
+                if !authenticated {
+                    let response = Response::Error {
+                        id: request.id,
+                        message: "Incorrect command token",
+                    };
+                    client.send_json(&response);
+                    continue;
+                }
+
+                let output = match NodeRunner::new(&request.command) {
+                    Ok(runner) => root_node.node_step(runner),
+                    Err(msg) => msg,
+                };
+                let response = Response::CommandOutput {
+                    id: request.id,
+                    output: &output,
+                };
+                client.send_json(&response);
+            }
+        }
+    }
+
+    /// Sends each event to every connected client
+    pub fn send_events<E>(&mut self, events: &[E])
+    where
+        E: Serialize,
+    {
+        for event in events {
+            for client in &mut self.clients {
+                client.send_json(event);
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Reads everything available on the stream, returns false when the client should be disconnected
+    fn receive(&mut self) -> bool {
+        let mut buf = [0; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(amt) => self.buffer.extend_from_slice(&buf[..amt]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => return false,
+            }
+            if self.buffer.len() > MAX_BUFFER_LEN {
+                return false;
+            }
+        }
+
+        if !self.handshake_complete {
+            let end = match self.buffer.windows(4).position(|x| x == b"\r\n\r\n") {
+                Some(end) => end + 4,
+                None => return true,
+            };
+            let request = String::from_utf8_lossy(&self.buffer[..end]).into_owned();
+            self.buffer.drain(..end);
+            match handshake_response(&request) {
+                Some(response) => {
+                    if self.stream.write_all(response.as_bytes()).is_err() {
+                        return false;
+                    }
+                    self.handshake_complete = true;
+                }
+                None => {
+                    self.stream
+                        .write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n")
+                        .ok();
+                    return false;
+                }
+            }
+        }
+
+        while let Some((frame, len)) = parse_frame(&self.buffer) {
+            self.buffer.drain(..len);
+            match frame.opcode {
+                OPCODE_TEXT if frame.fin => {
+                    let request = str::from_utf8(&frame.payload)
+                        .map_err(|x| x.to_string())
+                        .and_then(|x| serde_json::from_str(x).map_err(|x| x.to_string()));
+                    match request {
+                        Ok(request) => self.pending.push(request),
+                        Err(err) => {
+                            let message = format!("Invalid command message: {}", err);
+                            self.send_json(&Response::Error {
+                                id: None,
+                                message: &message,
+                            });
+                        }
+                    }
+                }
+                // Messages are small enough that clients have no reason to fragment them
+                OPCODE_TEXT | OPCODE_CONTINUATION => return false,
+                OPCODE_CLOSE => {
+                    self.send_frame(OPCODE_CLOSE, &[]);
+                    return false;
+                }
+                OPCODE_PING => self.send_frame(OPCODE_PONG, &frame.payload),
+                _ => {}
+            }
+        }
+        true
+    }
+
+    fn send_json<E>(&mut self, message: &E)
+    where
+        E: Serialize,
+    {
+        if self.handshake_complete {
+            let json = serde_json::to_string(message).unwrap();
+            self.send_frame(OPCODE_TEXT, json.as_bytes());
+        }
+    }
+
+    fn send_frame(&mut self, opcode: u8, payload: &[u8]) {
+        // a failed write closes the stream, which is then noticed by the next receive
+        if self
+            .stream
+            .write_all(&encode_frame(opcode, payload))
+            .is_err()
+        {
+            self.stream.shutdown(Shutdown::Both).ok();
+        }
+    }
+}
+
+/// Returns the response accepting an HTTP upgrade request, None when it is not a WebSocket handshake
+fn handshake_response(request: &str) -> Option<String> {
+    let key = request.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case("Sec-WebSocket-Key") {
+            Some(value.trim())
+        } else {
+            None
+        }
+    })?;
+    Some(format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    ))
+}
+
+fn accept_key(key: &str) -> String {
+    let digest = sha1_smol::Sha1::from(format!("{}{}", key, HANDSHAKE_GUID)).digest();
+    base64::encode(digest.bytes())
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Returns the first frame in `buffer` along with its length in bytes, None when the frame has not been fully received
+fn parse_frame(buffer: &[u8]) -> Option<(Frame, usize)> {
+    let fin = buffer.first()? & 0x80 != 0;
+    let opcode = buffer[0] & 0x0F;
+    let masked = buffer.get(1)? & 0x80 != 0;
+    let (payload_len, mut offset) = match buffer[1] & 0x7F {
+        126 => (
+            u16::from_be_bytes(buffer.get(2..4)?.try_into().unwrap()) as usize,
+            4,
+        ),
+        127 => (
+            u64::from_be_bytes(buffer.get(2..10)?.try_into().unwrap()) as usize,
+            10,
+        ),
+        len => (len as usize, 2),
+    };
+
+    let mask = if masked {
+        let mask: [u8; 4] = buffer.get(offset..offset + 4)?.try_into().unwrap();
+        offset += 4;
+        mask
+    } else {
+        [0; 4]
+    };
+
+    let payload = buffer
+        .get(offset..offset.checked_add(payload_len)?)?
+        .iter()
+        .enumerate()
+        .map(|(i, x)| x ^ mask[i % 4])
+        .collect();
+    let frame = Frame {
+        fin,
+        opcode,
+        payload,
+    };
+    Some((frame, offset + payload_len))
+}
+
+/// Encodes an unmasked and unfragmented frame, as sent by servers
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    if payload.len() < 126 {
+        frame.push(payload.len() as u8);
+    } else if payload.len() <= u16::MAX as usize {
+        frame.push(126);
+        frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[test]
+fn websocket_frame_test() {
+    // example from RFC 6455
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+
+    // a masked "Hello" from RFC 6455
+    let masked = [
+        0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+    ];
+    let (frame, len) = parse_frame(&masked).unwrap();
+    assert!(frame.fin);
+    assert_eq!(frame.opcode, OPCODE_TEXT);
+    assert_eq!(frame.payload, b"Hello");
+    assert_eq!(len, masked.len());
+    assert!(parse_frame(&masked[..6]).is_none());
+
+    let payload = vec![b'a'; 300];
+    let encoded = encode_frame(OPCODE_TEXT, &payload);
+    let (frame, len) = parse_frame(&encoded).unwrap();
+    assert_eq!(frame.payload, payload);
+    assert_eq!(len, encoded.len());
+}
@@ -0,0 +1,114 @@
+use canon_collision::simulation::{CollisionBox, CollisionBoxRole, Stage};
+use canon_collision_lib::stage::Surface;
+
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+/// Fraction of the canvas left empty on each side of the stage camera area
+const MARGIN: f64 = 0.05;
+/// Matches the thickness of surfaces in the wgpu renderer, in world units
+const SURFACE_WIDTH: f64 = 0.5;
+
+/// Draws the surfaces of the stage and every colbox, fitting the camera area of the stage to the canvas.
+/// Colors match the hitbox renderer of the game.
+pub fn render(
+    context: &CanvasRenderingContext2d,
+    stage: &Stage,
+    colboxes: &[CollisionBox],
+) -> Result<(), JsValue> {
+    let (width, height) = match context.canvas() {
+        Some(canvas) => (canvas.width() as f64, canvas.height() as f64),
+        None => return Ok(()),
+    };
+
+    context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)?;
+    context.set_fill_style(&JsValue::from_str("black"));
+    context.fill_rect(0.0, 0.0, width, height);
+
+    // world space has y pointing up, so the y axis is flipped
+    let camera = &stage.camera;
+    let (left, right) = min_max(camera.x1, camera.x2);
+    let (bottom, top) = min_max(camera.y1, camera.y2);
+    let scale = (width / (right - left).max(1.0)).min(height / (top - bottom).max(1.0))
+        * (1.0 - MARGIN * 2.0);
+    context.set_transform(
+        scale,
+        0.0,
+        0.0,
+        -scale,
+        width / 2.0 - (left + right) / 2.0 * scale,
+        height / 2.0 + (bottom + top) / 2.0 * scale,
+    )?;
+
+    context.set_line_width(SURFACE_WIDTH);
+    for surface in stage.surfaces.iter() {
+        context.set_stroke_style(&JsValue::from_str(&css_color(surface_color(surface))));
+        context.begin_path();
+        context.move_to(surface.x1 as f64, surface.y1 as f64);
+        context.line_to(surface.x2 as f64, surface.y2 as f64);
+        context.stroke();
+    }
+
+    // draw hurtboxes first so the boxes that act on them are drawn on top
+    let (hurtboxes, other_colboxes): (Vec<_>, Vec<_>) = colboxes
+        .iter()
+        .partition(|x| matches!(x.role, CollisionBoxRole::Hurt(_)));
+    for colbox in hurtboxes.into_iter().chain(other_colboxes) {
+        context.set_fill_style(&JsValue::from_str(&css_color(colbox_color(&colbox.role))));
+        context.begin_path();
+        context.arc(
+            colbox.point.0 as f64,
+            colbox.point.1 as f64,
+            colbox.radius as f64,
+            0.0,
+            std::f64::consts::TAU,
+        )?;
+        context.fill();
+    }
+
+    Ok(())
+}
+
+fn min_max(a: f32, b: f32) -> (f64, f64) {
+    (a.min(b) as f64, a.max(b) as f64)
+}
+
+/// Same as `Buffers::new_surfaces` in the wgpu renderer
+fn surface_color(surface: &Surface) -> [f32; 4] {
+    let r = if surface.is_pass_through() {
+        0.4
+    } else if surface.floor.is_some() {
+        0.6
+    } else {
+        0.0
+    };
+    let g = if surface.ceiling { 0.5 } else { 0.0 };
+    let b = if surface.wall { 0.5 } else { 0.0 };
+    if surface.hazard.is_some() {
+        [1.0, 0.0, 0.0, 1.0]
+    } else {
+        [1.0 - g - b, 1.0 - r - b, 1.0 - r - g, 1.0]
+    }
+}
+
+/// Same as hitbox.wgsl, hurtboxes are drawn without the edge in the color of their fighter
+fn colbox_color(role: &CollisionBoxRole) -> [f32; 4] {
+    match role {
+        CollisionBoxRole::Hurt(_) => [0.9, 0.9, 0.9, 1.0],
+        CollisionBoxRole::Hit(_) => [1.0, 0.0, 0.0, 1.0],
+        CollisionBoxRole::Grab => [0.76, 0.106, 0.843, 1.0],
+        CollisionBoxRole::Invincible => [0.0, 0.64, 0.0, 1.0],
+        CollisionBoxRole::Reflect => [0.8, 0.8, 0.8, 1.0],
+        CollisionBoxRole::Absorb => [0.0, 0.0, 1.0, 1.0],
+    }
+}
+
+fn css_color(color: [f32; 4]) -> String {
+    format!(
+        "rgba({}, {}, {}, {})",
+        (color[0] * 255.0) as u8,
+        (color[1] * 255.0) as u8,
+        (color[2] * 255.0) as u8,
+        color[3]
+    )
+}
@@ -0,0 +1,159 @@
+//! Views replays and plays games in the browser, drawing only the stage surfaces and colboxes to a canvas.
+//! Built for wasm32-unknown-unknown, see compiling.md.
+
+mod canvas;
+
+use canon_collision::replays;
+use canon_collision::simulation::{PlayerSetup, Rules, Simulation};
+use canon_collision_lib::input::web::WebGamepads;
+use canon_collision_lib::package::Package;
+use canon_collision_lib::theme::Theme;
+
+use std::path::Path;
+
+use wasm_bindgen::prelude::*;
+use web_sys::CanvasRenderingContext2d;
+
+#[wasm_bindgen]
+pub struct Viewer {
+    entity_files: Vec<(String, Vec<u8>)>,
+    stage_files: Vec<(String, Vec<u8>)>,
+    theme: Theme,
+    simulation: Option<Simulation>,
+    gamepads: WebGamepads,
+}
+
+#[wasm_bindgen]
+impl Viewer {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Viewer {
+        console_error_panic_hook::set_once();
+        Viewer {
+            entity_files: vec![],
+            stage_files: vec![],
+            theme: Theme::default(),
+            simulation: None,
+            gamepads: WebGamepads::new(),
+        }
+    }
+
+    /// Adds a file of the package, `path` ends with the directory the file is in e.g. `package/Entities/base_fighter.cbor`.
+    /// Files outside of the Entities and Stages directories are ignored, except for theme.json.
+    pub fn add_package_file(&mut self, path: &str, bytes: Vec<u8>) -> Result<(), JsValue> {
+        let path = Path::new(path);
+        let key = match path.file_name().and_then(|x| x.to_str()) {
+            Some(key) => key.to_string(),
+            None => return Ok(()),
+        };
+        let dir = path
+            .parent()
+            .and_then(|x| x.file_name())
+            .and_then(|x| x.to_str());
+
+        if key.ends_with(".tmp") {
+            // left behind by an interrupted save
+            return Ok(());
+        }
+        match dir {
+            Some("Entities") => self.entity_files.push((key, bytes)),
+            Some("Stages") => self.stage_files.push((key, bytes)),
+            _ if key == "theme.json" => {
+                self.theme = serde_json::from_slice(&bytes)
+                    .map_err(|x| JsValue::from_str(&format!("Failed to load theme.json: {}", x)))?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Resimulates a replay of a game played with the added package
+    pub fn load_replay(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let replay = replays::load_replay_bytes(bytes).map_err(|x| JsValue::from_str(&x))?;
+        let simulation =
+            Simulation::from_replay(self.package()?, &replay).map_err(|x| JsValue::from_str(&x))?;
+        self.simulation = Some(simulation);
+        Ok(())
+    }
+
+    /// Starts a game on `stage` where every gamepad slot up to the last connected gamepad controls a player using `fighter`.
+    /// `stage` and `fighter` are file names from the package e.g. `base_stage.cbor`.
+    pub fn play(&mut self, stage: &str, fighter: &str, seed: u32) -> Result<(), JsValue> {
+        let package = self.package()?;
+        let stage = stage.to_string();
+        let fighter = fighter.to_string();
+        if !package.stages.contains_key(&stage) {
+            return Err(JsValue::from_str(&format!(
+                "Package does not contain the stage '{}'",
+                stage
+            )));
+        }
+        if !package.entities.contains_key(&fighter) {
+            return Err(JsValue::from_str(&format!(
+                "Package does not contain the fighter '{}'",
+                fighter
+            )));
+        }
+
+        let players = self
+            .gamepads
+            .read()
+            .iter()
+            .rposition(|x| x.plugged_in)
+            .map_or(1, |x| x + 1);
+        let players = (0..players)
+            .map(|team| PlayerSetup {
+                fighter: fighter.clone(),
+                team,
+                ..PlayerSetup::default()
+            })
+            .collect();
+        self.simulation = Some(Simulation::new(
+            package,
+            players,
+            stage,
+            Rules::default(),
+            seed as u64,
+        ));
+        Ok(())
+    }
+
+    /// Simulates the next frame, once a replay runs out of recorded inputs the gamepads take over
+    pub fn step(&mut self) {
+        if let Some(simulation) = &mut self.simulation {
+            simulation.set_inputs(self.gamepads.read());
+            simulation.step();
+        }
+    }
+
+    /// The number of frames stepped so far
+    pub fn frame(&self) -> usize {
+        self.simulation.as_ref().map_or(0, |x| x.frame())
+    }
+
+    /// True once the rules have decided the winner
+    pub fn finished(&self) -> bool {
+        self.simulation.as_ref().map_or(false, |x| x.finished())
+    }
+
+    /// Draws the current frame to the canvas of `context`
+    pub fn render(&self, context: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        match &self.simulation {
+            Some(simulation) => canvas::render(context, simulation.stage(), &simulation.colboxes()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Viewer {
+    /// Each game gets its own copy of the package, as the simulation takes ownership of it
+    fn package(&self) -> Result<Package, JsValue> {
+        let mut entity_files = self.entity_files.clone();
+        let mut stage_files = self.stage_files.clone();
+        // match the order of `Package::open`
+        entity_files.sort_by_key(|x| x.0.clone());
+        stage_files.sort_by_key(|x| x.0.clone());
+        Package::from_files(entity_files, stage_files, self.theme.clone())
+            .map_err(|x| JsValue::from_str(&x))
+    }
+}
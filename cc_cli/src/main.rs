@@ -2,6 +2,10 @@ use std::env;
 use std::io::Read;
 use std::io::Write;
 use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use serde_json::Value;
 
 fn main() {
     std::process::exit(main_main());
@@ -10,22 +14,141 @@ fn main() {
 fn main_main() -> i32 {
     let mut args = env::args();
     args.next();
-    let out_vec: Vec<String> = args.collect();
-    let out: String = format!("C{}", out_vec.join(" "));
+    let mut out_vec: Vec<String> = args.collect();
+
+    // Options come before the command:
+    // `--socket PATH` connects to the Unix domain socket set by command_socket in the game's config instead of TCP
+    // `--address HOST:PORT` connects to the command_address set in the game's config instead of 127.0.0.1:1613
+    // `--token TOKEN` authenticates with the command_token set in the game's config
+    let mut connection = Connection {
+        socket: None,
+        address: String::from("127.0.0.1:1613"),
+        token: None,
+    };
+    while let Some(option) = out_vec
+        .first()
+        .filter(|x| ["--socket", "--address", "--token"].contains(&x.as_str()))
+        .cloned()
+    {
+        if out_vec.len() < 2 {
+            println!("{} requires a value", option);
+            return 1;
+        }
+        let value = out_vec.remove(1);
+        out_vec.remove(0);
+        match option.as_str() {
+            "--socket" => connection.socket = Some(value),
+            "--address" => connection.address = value,
+            _ => connection.token = Some(value),
+        }
+    }
 
-    match TcpStream::connect("127.0.0.1:1613") {
-        Ok(mut stream) => {
-            stream.write_all(out.as_bytes()).unwrap();
+    // `cc_cli schema [PATH]` displays the schema as a tree, optionally only the properties under PATH e.g. `package.entities[0]`
+    if out_vec.first().map(|x| x.as_ref()) == Some("schema") {
+        return match send(":schema", &connection) {
+            Some(result) => print_schema(&result, out_vec.get(1).map_or("", |x| x.as_ref())),
+            None => 1,
+        };
+    }
 
-            let mut result = String::new();
-            if stream.read_to_string(&mut result).is_ok() {
-                println!("{}", result);
-            }
+    let out = out_vec.join(" ");
+    match send(&out, &connection) {
+        Some(result) => {
+            println!("{}", result);
             0
         }
+        None => 1,
+    }
+}
+
+struct Connection {
+    socket: Option<String>,
+    address: String,
+    token: Option<String>,
+}
+
+fn send(command: &str, connection: &Connection) -> Option<String> {
+    let message = match &connection.token {
+        Some(token) => format!("A{}\n{}", token, command),
+        None => format!("C{}", command),
+    };
+    let result = match &connection.socket {
+        #[cfg(unix)]
+        Some(path) => UnixStream::connect(path).map(|stream| send_stream(stream, &message)),
+        #[cfg(not(unix))]
+        Some(_) => {
+            println!("--socket is only supported on Unix");
+            return None;
+        }
+        None => TcpStream::connect(connection.address.as_str())
+            .map(|stream| send_stream(stream, &message)),
+    };
+
+    match result {
+        Ok(result) => Some(result),
         Err(e) => {
             println!("Could not connect to Canon Collision host: {}", e);
+            None
+        }
+    }
+}
+
+fn send_stream<S: Read + Write>(mut stream: S, message: &str) -> String {
+    stream.write_all(message.as_bytes()).unwrap();
+
+    let mut result = String::new();
+    stream.read_to_string(&mut result).ok();
+    result
+}
+
+fn print_schema(result: &str, filter: &str) -> i32 {
+    match serde_json::from_str::<Value>(result) {
+        Ok(schema) => {
+            let filter = if filter.starts_with('.') || filter.starts_with('[') || filter.is_empty()
+            {
+                filter.to_string()
+            } else {
+                format!(".{}", filter)
+            };
+            print_schema_node(&schema, "", &filter, 0);
+            0
+        }
+        Err(_) => {
+            // The host responded with an error message instead of a schema
+            println!("{}", result);
             1
         }
     }
 }
+
+/// Prints the node at `path` if it is under `filter` and then recurses into its children.
+fn print_schema_node(node: &Value, path: &str, filter: &str, depth: usize) {
+    let ty = node["type"].as_str().unwrap_or("Unknown");
+    let depth = if path.starts_with(filter) {
+        let name = if path.is_empty() { "root" } else { path };
+        let indent = "    ".repeat(depth);
+        println!("{}{}: {}", indent, name, ty);
+        if let Some(doc) = node["doc"].as_str() {
+            for line in doc.lines() {
+                println!("{}    /// {}", indent, line);
+            }
+        }
+        if let Some(variants) = node["variants"].as_array() {
+            let variants: Vec<&str> = variants.iter().filter_map(|x| x.as_str()).collect();
+            println!("{}    variants: {}", indent, variants.join(", "));
+        }
+        depth + 1
+    } else {
+        depth
+    };
+
+    if let Some(children) = node["children"].as_object() {
+        for (accessor, child) in children {
+            let child_path = format!("{}{}", path, accessor);
+            // only descend into nodes that are on the way to or under the filter
+            if child_path.starts_with(filter) || filter.starts_with(&child_path) {
+                print_schema_node(child, &child_path, filter, depth);
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use canon_collision_lib::entity_def::{
+    ActionDef, ActionFrame, CollisionBox, CollisionBoxRole, EntityDef,
+};
+use serde::Serialize;
+use serde_cbor::value::{to_value, Value};
+
+/// Lines describing every frame data change from `old` to `new`, grouped by entity
+pub fn package_diff(old: &[(String, EntityDef)], new: &[(String, EntityDef)]) -> Vec<String> {
+    let mut lines = vec![];
+    for (key, new_entity) in new {
+        match old.iter().find(|x| &x.0 == key) {
+            Some((_, old_entity)) => {
+                let changes = entity_diff(old_entity, new_entity);
+                if !changes.is_empty() {
+                    lines.push(format!("{}:", key));
+                    lines.extend(changes.into_iter().map(|x| format!("    {}", x)));
+                }
+            }
+            None => lines.push(format!("{}: added", key)),
+        }
+    }
+    for (key, _) in old {
+        if !new.iter().any(|x| &x.0 == key) {
+            lines.push(format!("{}: removed", key));
+        }
+    }
+    lines
+}
+
+fn entity_diff(old: &EntityDef, new: &EntityDef) -> Vec<String> {
+    let mut lines = fields_diff("", old, new, &["engine_version", "actions"]);
+    for (key, new_action) in new.actions.key_value_iter() {
+        match old.actions.key_to_value(key) {
+            Some(old_action) => lines.extend(action_diff(key, old_action, new_action)),
+            None => lines.push(format!("{}: added", key)),
+        }
+    }
+    for key in old.actions.keys() {
+        if !new.actions.contains_key(&key) {
+            lines.push(format!("{}: removed", key));
+        }
+    }
+    lines
+}
+
+fn action_diff(name: &str, old: &ActionDef, new: &ActionDef) -> Vec<String> {
+    let mut lines = vec![];
+    if old.frames.len() != new.frames.len() {
+        lines.push(format!(
+            "{}: {} -> {} frames",
+            name,
+            old.frames.len(),
+            new.frames.len()
+        ));
+    }
+    if old.iasa != new.iasa {
+        lines.push(format!("{}: iasa {} -> {}", name, old.iasa, new.iasa));
+    }
+    if old.frame_step_script != new.frame_step_script
+        || old.action_expired_script != new.action_expired_script
+    {
+        lines.push(format!("{}: scripts modified", name));
+    }
+    for (i, (old_frame, new_frame)) in old.frames.iter().zip(new.frames.iter()).enumerate() {
+        lines.extend(frame_diff(
+            &format!("{} frame {}", name, i),
+            old_frame,
+            new_frame,
+        ));
+    }
+    lines
+}
+
+/// Only the hitboxes and tags are compared, as the rest of the frame rarely affects balance
+fn frame_diff(location: &str, old: &ActionFrame, new: &ActionFrame) -> Vec<String> {
+    let mut lines = vec![];
+    let old_hitboxes = old.get_hitboxes();
+    let new_hitboxes = new.get_hitboxes();
+    for (i, new_hitbox) in new_hitboxes.iter().enumerate() {
+        let location = format!("{} hitbox {}", location, i);
+        match old_hitboxes.get(i) {
+            Some(old_hitbox) => lines.extend(hitbox_diff(&location, old_hitbox, new_hitbox)),
+            None => lines.push(format!("{}: added", location)),
+        }
+    }
+    for i in new_hitboxes.len()..old_hitboxes.len() {
+        lines.push(format!("{} hitbox {}: removed", location, i));
+    }
+
+    if old.tags != new.tags {
+        lines.push(format!(
+            "{}: tags [{}] -> [{}]",
+            location,
+            old.tags.join(", "),
+            new.tags.join(", ")
+        ));
+    }
+    lines
+}
+
+fn hitbox_diff(location: &str, old: &CollisionBox, new: &CollisionBox) -> Vec<String> {
+    let mut lines = vec![];
+    if old.radius != new.radius {
+        lines.push(format!(
+            "{}: radius {} -> {}",
+            location, old.radius, new.radius
+        ));
+    }
+    match (&old.role, &new.role) {
+        (CollisionBoxRole::Hit(old_hit), CollisionBoxRole::Hit(new_hit)) => {
+            lines.extend(fields_diff(location, old_hit, new_hit, &[]))
+        }
+        (CollisionBoxRole::Hit(_), _) => lines.push(format!("{}: hit -> grab", location)),
+        (_, CollisionBoxRole::Hit(_)) => lines.push(format!("{}: grab -> hit", location)),
+        _ => {}
+    }
+    lines
+}
+
+/// Compares every field of `old` and `new` except those named in `skip`.
+/// Going through serde means new fields are picked up without changes here.
+fn fields_diff<T: Serialize>(location: &str, old: &T, new: &T, skip: &[&str]) -> Vec<String> {
+    let prefix = if location.is_empty() {
+        String::new()
+    } else {
+        format!("{}: ", location)
+    };
+
+    let mut lines = vec![];
+    if let (Ok(Value::Map(old)), Ok(Value::Map(new))) = (to_value(old), to_value(new)) {
+        for (key, new_value) in &new {
+            if let (Value::Text(name), Some(old_value)) = (key, old.get(key)) {
+                if old_value != new_value && !skip.contains(&name.as_str()) {
+                    lines.push(format!(
+                        "{}{} {} -> {}",
+                        prefix,
+                        name,
+                        display(old_value),
+                        display(new_value)
+                    ));
+                }
+            }
+        }
+    }
+    lines
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("None"),
+        Value::Bool(value) => value.to_string(),
+        Value::Integer(value) => value.to_string(),
+        // every float in the package is an f32, displaying it as an f64 would show rounding errors
+        Value::Float(value) => (*value as f32).to_string(),
+        Value::Text(value) => value.clone(),
+        Value::Array(values) => {
+            let values: Vec<_> = values.iter().map(display).collect();
+            format!("[{}]", values.join(", "))
+        }
+        // enum variants with data
+        Value::Map(map) if map.len() == 1 => {
+            let (key, value) = map.iter().next().unwrap();
+            format!("{}({})", display(key), display(value))
+        }
+        Value::Map(map) => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(key, value)| format!("{}: {}", display(key), display(value)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        _ => String::from("?"),
+    }
+}
+
+#[test]
+fn package_diff_test() {
+    use canon_collision_lib::entity_def::HitBox;
+
+    let mut frame = ActionFrame::default();
+    frame.colboxes.push(CollisionBox {
+        role: CollisionBoxRole::Hit(HitBox::default()),
+        ..CollisionBox::default()
+    });
+    let mut action = ActionDef::default();
+    action.frames.push(frame);
+    let mut old = EntityDef::default();
+    old.actions.push(String::from("Jab"), action);
+
+    let mut new = old.clone();
+    new.weight = 1.1;
+    new.actions[0].frames.push(ActionFrame::default());
+    if let CollisionBoxRole::Hit(hitbox) = &mut new.actions[0].frames[1].colboxes[0].role {
+        hitbox.damage = 8.0;
+    }
+    new.actions[0].frames[1]
+        .tags
+        .push(String::from("intangible"));
+
+    let old = vec![(String::from("Toriel.cbor"), old)];
+    let new = vec![(String::from("Toriel.cbor"), new)];
+    assert_eq!(
+        package_diff(&old, &new),
+        vec![
+            String::from("Toriel.cbor:"),
+            format!("    weight {} -> 1.1", EntityDef::default().weight),
+            String::from("    Jab: 2 -> 3 frames"),
+            String::from("    Jab frame 1 hitbox 0: damage 6 -> 8"),
+            String::from("    Jab frame 1: tags [] -> [intangible]"),
+        ]
+    );
+    assert!(package_diff(&old, &old).is_empty());
+}
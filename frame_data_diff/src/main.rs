@@ -0,0 +1,125 @@
+mod diff;
+
+use canon_collision_lib::entity_def::EntityDef;
+use canon_collision_lib::package::Package;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lists the frame data changes between two versions of a package, to be used as a starting point for balance patch notes.
+///
+/// Each version is either a path to a package or `git:REF` to use the package as committed at REF.
+/// The new version defaults to the package in the current directory or any of its parent directories.
+/// e.g. `frame_data_diff git:HEAD~5` or `frame_data_diff old/package new/package`
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.len() > 2 {
+        println!("Usage: frame_data_diff OLD [NEW]");
+        println!("OLD and NEW are either a path to a package or git:REF to use the package committed at REF.");
+        println!("NEW defaults to the package in the current directory or any of its parent directories.");
+        return;
+    }
+
+    let current = Package::find_package_in_parent_dirs();
+    let old = match load_entities(&args[0], current.as_deref()) {
+        Ok(entities) => entities,
+        Err(err) => {
+            println!("Could not load {}: {}", args[0], err);
+            return;
+        }
+    };
+    let new_source = args.get(1).map_or("current package", |x| x.as_ref());
+    let new = match args.get(1) {
+        Some(source) => load_entities(source, current.as_deref()),
+        None => match &current {
+            Some(path) => load_entities_dir(path),
+            None => Err(String::from(
+                "Could not find package in current directory or any of its parent directories.",
+            )),
+        },
+    };
+    let new = match new {
+        Ok(entities) => entities,
+        Err(err) => {
+            println!("Could not load {}: {}", new_source, err);
+            return;
+        }
+    };
+
+    println!("Frame data changes from {} to {}:", args[0], new_source);
+    let lines = diff::package_diff(&old, &new);
+    if lines.is_empty() {
+        println!("No changes");
+    }
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+fn load_entities(source: &str, current: Option<&Path>) -> Result<Vec<(String, EntityDef)>, String> {
+    if let Some(git_ref) = source.strip_prefix("git:") {
+        match current {
+            Some(path) => load_entities_git(path, git_ref),
+            None => Err(String::from(
+                "Loading from git requires a package in the current directory or any of its parent directories.",
+            )),
+        }
+    } else {
+        load_entities_dir(&PathBuf::from(source))
+    }
+}
+
+fn load_entities_dir(path: &Path) -> Result<Vec<(String, EntityDef)>, String> {
+    // old packages may predate actions that have since been added, they are fixed on load which matches loading from git
+    let package = Package::open(path.to_path_buf(), false, false).map_err(|x| {
+        format!("Not a package or the package is from another engine version, run package_upgrader on it first. {}", x)
+    })?;
+    Ok(package
+        .entities
+        .key_value_iter()
+        .map(|(key, entity)| (key.clone(), entity.clone()))
+        .collect())
+}
+
+/// Loads the entities of the package at `package_path` as they were committed at `git_ref`
+fn load_entities_git(
+    package_path: &Path,
+    git_ref: &str,
+) -> Result<Vec<(String, EntityDef)>, String> {
+    let files = git(
+        package_path,
+        &["ls-tree", "--name-only", git_ref, "Entities/"],
+    )?;
+    let mut entities = vec![];
+    for file in String::from_utf8_lossy(&files).lines() {
+        let key = file.trim_start_matches("Entities/").to_string();
+        // --filters runs git lfs so we get the file instead of its pointer
+        let data = git(
+            package_path,
+            &["cat-file", "--filters", &format!("{}:./{}", git_ref, file)],
+        )?;
+        let mut entity: EntityDef = serde_cbor::from_slice(&data).map_err(|x| {
+            format!(
+                "{} is from another engine version, check out {} and run package_upgrader on it first. {}",
+                key, git_ref, x
+            )
+        })?;
+        entity.cleanup();
+        entities.push((key, entity));
+    }
+    entities.sort_by_key(|x| x.0.clone());
+    Ok(entities)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|x| format!("Failed to run git: {}", x))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
@@ -17,6 +17,12 @@ pub fn cli() -> CLIResults {
         "Delete any existing hitboxes on the generated actions",
     );
     opts.optflag("r", "resize", "Resize generated action length");
+    opts.optopt(
+        "s",
+        "scale",
+        "Scale the radius and offset of the generated hurtboxes, for fighters scaled from their model",
+        "FACTOR",
+    );
     opts.reqopt("f", "fighter", "Use the fighter specified", "NAME");
     opts.optopt(
         "a",
@@ -39,6 +45,17 @@ pub fn cli() -> CLIResults {
     results.resize = matches.opt_present("r");
     results.fighter_name = matches.opt_str("f");
 
+    if let Some(scale) = matches.opt_str("s") {
+        match scale.parse() {
+            Ok(scale) => results.scale = scale,
+            Err(_) => {
+                print_usage(program, opts);
+                results.fighter_name = None;
+                return results;
+            }
+        }
+    }
+
     if let Some(fighter_names) = matches.opt_str("a") {
         for fighter_name in fighter_names.split(',') {
             results.action_names.push(fighter_name.to_string());
@@ -53,6 +70,7 @@ pub struct CLIResults {
     pub action_names: Vec<String>,
     pub delete_hitboxes: bool,
     pub resize: bool,
+    pub scale: f32,
 }
 
 impl CLIResults {
@@ -62,6 +80,7 @@ impl CLIResults {
             action_names: vec![],
             delete_hitboxes: false,
             resize: false,
+            scale: 1.0,
         }
     }
 }
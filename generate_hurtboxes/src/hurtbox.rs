@@ -46,6 +46,10 @@ pub struct HurtBox {
     pub bone_length: f32,
     /// Radius of the hurtbox
     pub radius: f32,
+    /// Overrides the radius of each hurtbox placed along the bone, starting from the bone's origin.
+    /// Hurtboxes without an override use `radius`, e.g. to taper a limb towards its end:
+    /// `HurtBox { radii: vec!(1.2, 1.1), ..HurtBox::new("Arm.L", 4.0, 1.0, 0.0, 0.0, 0.0) }`
+    pub radii: Vec<f32>,
     /// Offset of the hurtbox from the bone, in bone space
     pub offset: Vector3<f32>,
 }
@@ -63,7 +67,13 @@ impl HurtBox {
             bone: bone.into(),
             bone_length,
             radius,
+            radii: vec![],
             offset: Vector3::new(offset_x, offset_y, offset_z),
         }
     }
+
+    /// The radius of the i'th hurtbox placed along the bone
+    pub fn radius_at(&self, i: usize) -> f32 {
+        self.radii.get(i).cloned().unwrap_or(self.radius)
+    }
 }
@@ -10,12 +10,13 @@ use canon_collision_lib::assets::Assets;
 use canon_collision_lib::entity_def::{
     ActionDef, ActionFrame, CollisionBox, CollisionBoxRole, ItemHold,
 };
+use canon_collision_lib::geometry;
 use canon_collision_lib::package::Package;
 use cli::CLIResults;
 use hurtbox::HurtBox;
 use model::{Animation, Joint, Model3D};
 
-use cgmath::{Matrix4, Point3, Rad, SquareMatrix, Transform, Vector3, VectorSpace};
+use cgmath::{InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Transform, Vector3, VectorSpace};
 use std::f32;
 
 fn main() {
@@ -25,11 +26,12 @@ fn main() {
 
     if let Some(fighter_key) = &cli.fighter_name {
         let mut package = if let Some(path) = Package::find_package_in_parent_dirs() {
-            if let Some(package) = Package::open(path) {
-                package
-            } else {
-                println!("Could not load package");
-                return;
+            match Package::open(path, false, true) {
+                Ok(package) => package,
+                Err(err) => {
+                    println!("Could not load package: {}", err);
+                    return;
+                }
             }
         } else {
             println!(
@@ -88,7 +90,7 @@ fn regenerate_action(
     hurtboxes: &[HurtBox],
 ) {
     if cli.resize {
-        let frames = animation.len().max(1);
+        let frames = action.frames_for_animation(animation.len());
         while action.frames.len() > frames {
             action.frames.pop();
         }
@@ -109,9 +111,10 @@ fn regenerate_action(
         }
     }
 
+    let animation_speed = action.animation_speed;
     for (i, frame) in action.frames.iter_mut().enumerate() {
         let mut root_joint = root_joint.clone();
-        let animation_frame = i as f32;
+        let animation_frame = i as f32 * animation_speed;
         animation::set_animated_joints(
             animation,
             animation_frame,
@@ -119,27 +122,30 @@ fn regenerate_action(
             Matrix4::identity(),
         );
         for hurtbox in hurtboxes {
-            generate_hurtbox(frame, &root_joint, hurtbox);
+            generate_hurtbox(frame, &root_joint, hurtbox, cli.scale);
         }
 
         generate_item_hold(frame, &root_joint, "Hand.R");
     }
 }
 
-fn generate_hurtbox(frame: &mut ActionFrame, root_joint: &Joint, hurtbox: &HurtBox) {
+/// The radius and offset of the generated hurtboxes are scaled by both the scale of the bone and `scale`
+fn generate_hurtbox(frame: &mut ActionFrame, root_joint: &Joint, hurtbox: &HurtBox, scale: f32) {
     for child in &root_joint.children {
-        generate_hurtbox(frame, child, hurtbox);
+        generate_hurtbox(frame, child, hurtbox, scale);
     }
 
     if root_joint.name == hurtbox.bone {
         let role = CollisionBoxRole::Hurt(Default::default());
-        let radius = hurtbox.radius; // TODO: Multiply by scale of transform
 
-        let count = (hurtbox.bone_length / radius) as usize;
+        let count = (hurtbox.bone_length / hurtbox.radius) as usize;
         let transform = &root_joint.transform;
+        // assumes the bone is scaled uniformly
+        let bone_scale = transform.transform_vector(Vector3::unit_y()).magnitude();
         let o = &hurtbox.offset;
-        let point1 = transform.transform_point(Point3::new(o.x, o.y, o.z));
-        let point2 = transform.transform_point(Point3::new(o.x, o.y + hurtbox.bone_length, o.z));
+        let point1 = transform.transform_point(Point3::new(o.x, o.y, o.z)) * scale;
+        let point2 =
+            transform.transform_point(Point3::new(o.x, o.y + hurtbox.bone_length, o.z)) * scale;
 
         if count > 1 {
             for i in 0..count {
@@ -150,7 +156,7 @@ fn generate_hurtbox(frame: &mut ActionFrame, root_joint: &Joint, hurtbox: &HurtB
                 let role = role.clone();
                 frame.colboxes.push(CollisionBox {
                     point,
-                    radius,
+                    radius: hurtbox.radius_at(i) * bone_scale * scale,
                     role,
                 });
             }
@@ -158,7 +164,7 @@ fn generate_hurtbox(frame: &mut ActionFrame, root_joint: &Joint, hurtbox: &HurtB
             let point = (point1.z, point1.y);
             frame.colboxes.push(CollisionBox {
                 point,
-                radius,
+                radius: hurtbox.radius_at(0) * bone_scale * scale,
                 role,
             });
         }
@@ -173,63 +179,18 @@ fn generate_item_hold(frame: &mut ActionFrame, root_joint: &Joint, bone_name: &s
     if root_joint.name == bone_name {
         let transform = Matrix4::from_angle_y(Rad(f32::consts::PI / 2.0)) * &root_joint.transform;
         let point = transform.transform_point(Point3::new(0.0, 0.0, 0.0));
-        let quaternion = matrix_to_quaternion(&transform);
+        let quaternion = geometry::matrix_to_quaternion(&transform);
 
         if frame.item_hold.is_some() {
             frame.item_hold = Some(ItemHold {
                 translation_x: point.x,
                 translation_y: point.y,
                 translation_z: point.z,
-                quaternion_x: quaternion.1,
-                quaternion_y: quaternion.2,
-                quaternion_z: quaternion.3,
-                quaternion_rotation: -quaternion.0, // No idea why this needs to be inverted but it makes everything work ¯\_(ツ)_/¯
+                quaternion_x: quaternion.v.x,
+                quaternion_y: quaternion.v.y,
+                quaternion_z: quaternion.v.z,
+                quaternion_rotation: quaternion.s,
             });
         }
     }
 }
-
-/// copied from three.js
-/// which copied from http://www.euclideanspace.com/maths/geometry/rotations/conversions/matrixToQuaternion/index.htm
-/// assumes the upper 3x3 of m is a pure rotation matrix (i.e, unscaled)
-fn matrix_to_quaternion(m: &Matrix4<f32>) -> (f32, f32, f32, f32) {
-    let trace = m.x.x + m.y.y + m.z.z;
-
-    if trace > 0.0 {
-        let s = 0.5 / (trace + 1.0).sqrt();
-
-        (
-            0.25 / s,
-            (m.z.y - m.y.z) * s,
-            (m.x.z - m.z.x) * s,
-            (m.y.x - m.x.y) * s,
-        )
-    } else if m.x.x > m.y.y && m.x.x > m.z.z {
-        let s = 2.0 * (1.0 + m.x.x - m.y.y - m.z.z).sqrt();
-
-        (
-            (m.z.y - m.y.z) / s,
-            0.25 * s,
-            (m.x.y + m.y.x) / s,
-            (m.x.z + m.z.x) / s,
-        )
-    } else if m.y.y > m.z.z {
-        let s = 2.0 * (1.0 + m.y.y - m.x.x - m.z.z).sqrt();
-
-        (
-            (m.x.z - m.z.x) / s,
-            (m.x.y + m.y.x) / s,
-            0.25 * s,
-            (m.y.z + m.z.y) / s,
-        )
-    } else {
-        let s = 2.0 * (1.0 + m.z.z - m.x.x - m.y.y).sqrt();
-
-        (
-            (m.y.x - m.x.y) / s,
-            (m.x.z + m.z.x) / s,
-            (m.y.z + m.z.y) / s,
-            0.25 * s,
-        )
-    }
-}
@@ -212,8 +212,14 @@ pub struct Animation {
 }
 
 impl Animation {
+    /// The number of frames until the last keyframe of any channel, played at 60fps
     pub fn len(&self) -> usize {
-        unimplemented!()
+        let last_input = self
+            .channels
+            .iter()
+            .filter_map(|x| x.inputs.last())
+            .fold(0.0, |a: f32, b| a.max(*b));
+        (last_input * 60.0).round() as usize + 1
     }
 }
 
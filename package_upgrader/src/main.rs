@@ -2,9 +2,10 @@ use serde_cbor::{value, Value};
 use strum::IntoEnumIterator;
 
 use canon_collision_lib::entity_def::player::PlayerAction;
-use canon_collision_lib::entity_def::EntityDef;
+use canon_collision_lib::entity_def::{ActionDef, EntityDef};
 use canon_collision_lib::files::{engine_version, load_cbor, save_struct_cbor};
 use canon_collision_lib::package::Package;
+use canon_collision_lib::stage::Stage;
 
 use std::collections::BTreeMap;
 use std::fs;
@@ -39,6 +40,12 @@ fn main() {
                 upgrade_to_latest_entity(&full_path, dry_run);
             }
         }
+        if let Ok(dir) = fs::read_dir(package_path.join("Stages")) {
+            for path in dir {
+                let full_path = path.unwrap().path();
+                upgrade_to_latest_stage(&full_path, dry_run);
+            }
+        }
     } else {
         println!("Could not find package in current directory or any of its parent directories.");
     }
@@ -81,6 +88,47 @@ fn new_object(entries: Vec<(&str, Value)>) -> Value {
     Value::Map(map)
 }
 
+/// Appends the actions to a fighter that does not have them yet, each as a copy of the `base` action.
+/// Falls back to an empty action when the fighter does not have the `base` action either.
+fn add_fighter_actions(entity: &mut Value, names: &[&str], base: &str) {
+    if let Value::Map(entity) = entity {
+        let is_fighter = matches!(
+            entity.get(&Value::Text("ty".into())),
+            Some(Value::Map(entity_type)) if entity_type.contains_key(&Value::Text("Fighter".into()))
+        );
+        if !is_fighter {
+            return;
+        }
+        if let Some(Value::Map(actions)) = entity.get_mut(&Value::Text("actions".into())) {
+            let keys = match actions.get(&Value::Text("keys".into())) {
+                Some(Value::Array(keys)) => keys.clone(),
+                _ => return,
+            };
+            let vector = match actions.get_mut(&Value::Text("vector".into())) {
+                Some(Value::Array(vector)) => vector,
+                _ => return,
+            };
+            let base_action = keys
+                .iter()
+                .position(|x| x == &Value::Text(base.into()))
+                .and_then(|i| vector.get(i).cloned())
+                .unwrap_or_else(|| value::to_value(ActionDef::default()).unwrap());
+
+            let mut new_keys = vec![];
+            for name in names {
+                let key = Value::Text(name.to_string());
+                if !keys.contains(&key) {
+                    vector.push(base_action.clone());
+                    new_keys.push(key);
+                }
+            }
+            if let Some(Value::Array(keys)) = actions.get_mut(&Value::Text("keys".into())) {
+                keys.extend(new_keys);
+            }
+        }
+    }
+}
+
 fn upgrade_to_latest_entity(path: &Path, dry_run: bool) {
     let file_name = path.file_name().unwrap().to_str().unwrap();
     let mut entity = load_cbor(path).unwrap();
@@ -93,6 +141,20 @@ fn upgrade_to_latest_entity(path: &Path, dry_run: bool) {
     } else if entity_engine_version < engine_version() {
         for upgrade_from in entity_engine_version..engine_version() {
             match upgrade_from {
+                36 => upgrade_entity36(&mut entity),
+                35 => upgrade_entity35(&mut entity),
+                34 => upgrade_entity34(&mut entity),
+                33 => upgrade_entity33(&mut entity),
+                32 => upgrade_entity32(&mut entity),
+                31 => upgrade_entity31(&mut entity),
+                30 => upgrade_entity30(&mut entity),
+                29 => upgrade_entity29(&mut entity),
+                28 => upgrade_entity28(&mut entity),
+                27 => upgrade_entity27(&mut entity),
+                25 => upgrade_entity25(&mut entity),
+                23 => upgrade_entity23(&mut entity),
+                22 => upgrade_entity22(&mut entity),
+                20 => upgrade_entity20(&mut entity),
                 19 => upgrade_entity19(&mut entity),
                 18 => upgrade_entity18(&mut entity, file_name),
                 17 => upgrade_entity17(&mut entity),
@@ -120,6 +182,341 @@ fn upgrade_to_latest_entity(path: &Path, dry_run: bool) {
     );
 }
 
+fn upgrade_to_latest_stage(path: &Path, dry_run: bool) {
+    let mut stage = load_cbor(path).unwrap();
+    let stage_engine_version = get_engine_version(&stage);
+    if stage_engine_version > engine_version() {
+        panic!(
+            "Stage: {} is newer than this version of Canon Collision.",
+            path.file_name().unwrap().to_str().unwrap()
+        );
+    } else if stage_engine_version < engine_version() {
+        for upgrade_from in stage_engine_version..engine_version() {
+            match upgrade_from {
+                37 => upgrade_stage37(&mut stage),
+                26 => upgrade_stage26(&mut stage),
+                24 => upgrade_stage24(&mut stage),
+                21 => upgrade_stage21(&mut stage),
+                _ => {}
+            }
+        }
+        upgrade_engine_version(&mut stage);
+    }
+
+    // convert to Stage to ensure result is deserializable before writing to disk
+    let stage: Stage = value::from_value(stage).unwrap();
+
+    if dry_run {
+        print!("dry run: ");
+    } else {
+        save_struct_cbor(path, &stage);
+    }
+
+    println!(
+        "Upgraded stage from version {} to version {}.",
+        stage_engine_version,
+        engine_version()
+    );
+}
+
+fn upgrade_stage37(stage: &mut Value) {
+    if let Value::Map(stage) = stage {
+        if let Some(Value::Array(surfaces)) = stage.get_mut(&Value::Text("surfaces".into())) {
+            for surface in surfaces {
+                if let Value::Map(surface) = surface {
+                    surface.insert(Value::Text("hazard".into()), Value::Null);
+                    // the position along the path is no longer stored,
+                    // the points included it so take it back out to get the authored position
+                    for (point, offset) in [
+                        ("x1", "path_x"),
+                        ("x2", "path_x"),
+                        ("y1", "path_y"),
+                        ("y2", "path_y"),
+                    ] {
+                        if let (Some(Value::Float(offset)), Some(Value::Float(point))) = (
+                            surface.get(&Value::Text(offset.into())).cloned(),
+                            surface.get_mut(&Value::Text(point.into())),
+                        ) {
+                            *point -= offset;
+                        }
+                    }
+                    for member in ["path_x", "path_y", "vel_x", "vel_y"] {
+                        surface.remove(&Value::Text(member.into()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_stage26(stage: &mut Value) {
+    if let Value::Map(stage) = stage {
+        // The camera could previously zoom out to the entire camera area
+        let mut camera_width = 300.0;
+        if let Some(Value::Map(camera)) = stage.get(&Value::Text("camera".into())) {
+            if let (Some(Value::Float(x1)), Some(Value::Float(x2))) = (
+                camera.get(&Value::Text("x1".into())),
+                camera.get(&Value::Text("x2".into())),
+            ) {
+                camera_width = (x2 - x1).abs();
+            }
+        }
+        stage.insert(Value::Text("camera_min_width".into()), Value::Float(100.0));
+        stage.insert(
+            Value::Text("camera_max_width".into()),
+            Value::Float(camera_width),
+        );
+    }
+}
+
+fn upgrade_stage24(stage: &mut Value) {
+    if let Value::Map(stage) = stage {
+        stage.insert(Value::Text("item_spawns".into()), Value::Array(vec![]));
+        stage.insert(Value::Text("item_spawn_frames".into()), Value::Integer(0));
+    }
+}
+
+fn upgrade_stage21(stage: &mut Value) {
+    if let Value::Map(stage) = stage {
+        if let Some(Value::Array(surfaces)) = stage.get_mut(&Value::Text("surfaces".into())) {
+            for surface in surfaces {
+                if let Value::Map(surface) = surface {
+                    surface.insert(Value::Text("path".into()), Value::Array(vec![]));
+                    for member in ["path_x", "path_y", "vel_x", "vel_y"] {
+                        surface.insert(Value::Text(member.into()), Value::Float(0.0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_entity36(entity: &mut Value) {
+    // fighters missing a Super action get a copy of the forward smash, has_super is left for the author to enable
+    add_fighter_actions(entity, &["Super"], "Fsmash");
+}
+
+fn upgrade_entity35(entity: &mut Value) {
+    // grabbing keeps the grabbed fighter in the same place while carrying them
+    add_fighter_actions(
+        entity,
+        &["GrabbingWalk", "GrabbingJumpSquat", "GrabbingJump"],
+        "GrabbingIdle",
+    );
+}
+
+fn upgrade_entity34(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(actions) = entity.get_mut(&Value::Text("actions".into())) {
+            if let Some(actions) = get_vec(actions, "vector") {
+                for action in actions {
+                    if let Value::Map(action) = action {
+                        action.insert(Value::Text("animation_speed".into()), Value::Float(1.0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_entity33(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(Value::Map(entity_type)) = entity.get_mut(&Value::Text("ty".into())) {
+            if let Some(Value::Map(fighter)) = entity_type.get_mut(&Value::Text("Fighter".into())) {
+                fighter.insert(Value::Text("costumes".into()), Value::Array(vec![]));
+            }
+        }
+    }
+}
+
+fn upgrade_entity32(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        entity.insert(
+            Value::Text("simulation_lod".into()),
+            Value::Text("Full".into()),
+        );
+    }
+}
+
+fn upgrade_entity31(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(actions) = entity.get_mut(&Value::Text("actions".into())) {
+            if let Some(actions) = get_vec(actions, "vector") {
+                for action in actions {
+                    if let Some(frames) = get_vec(action, "frames") {
+                        for frame in frames {
+                            if let Some(colboxes) = get_vec(frame, "colboxes") {
+                                for colbox in colboxes {
+                                    if let Value::Map(colbox) = colbox {
+                                        if let Some(Value::Map(role)) =
+                                            colbox.get_mut(&Value::Text("role".into()))
+                                        {
+                                            if let Some(Value::Map(hitbox)) =
+                                                role.get_mut(&Value::Text("Hit".into()))
+                                            {
+                                                hitbox
+                                                    .insert(Value::Text("sfx".into()), Value::Null);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_entity30(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        entity.insert(
+            Value::Text("animation_blend_frames".into()),
+            Value::Integer(4),
+        );
+        if let Some(actions) = entity.get_mut(&Value::Text("actions".into())) {
+            if let Some(actions) = get_vec(actions, "vector") {
+                for action in actions {
+                    if let Value::Map(action) = action {
+                        action.insert(Value::Text("blend_frames".into()), Value::Null);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_entity29(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(actions) = entity.get_mut(&Value::Text("actions".into())) {
+            if let Some(actions) = get_vec(actions, "vector") {
+                for action in actions {
+                    if let Some(frames) = get_vec(action, "frames") {
+                        for frame in frames {
+                            if let Some(colboxes) = get_vec(frame, "colboxes") {
+                                for colbox in colboxes {
+                                    if let Value::Map(colbox) = colbox {
+                                        if let Some(Value::Map(role)) =
+                                            colbox.get_mut(&Value::Text("role".into()))
+                                        {
+                                            if let Some(Value::Map(hitbox)) =
+                                                role.get_mut(&Value::Text("Hit".into()))
+                                            {
+                                                hitbox.insert(
+                                                    Value::Text("spark".into()),
+                                                    Value::Text("Blunt".into()),
+                                                );
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_entity28(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(actions) = entity.get_mut(&Value::Text("actions".into())) {
+            if let Some(actions) = get_vec(actions, "vector") {
+                for action in actions {
+                    if let Some(frames) = get_vec(action, "frames") {
+                        for frame in frames {
+                            if let Value::Map(frame) = frame {
+                                frame.insert(Value::Text("events".into()), Value::Array(vec![]));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_entity27(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(Value::Map(shield)) = entity.get_mut(&Value::Text("shield".into())) {
+            shield.insert(Value::Text("shield_push".into()), Value::Float(0.05));
+            shield.insert(Value::Text("body_push".into()), Value::Float(0.03));
+            shield.insert(Value::Text("push_max".into()), Value::Float(1.0));
+        }
+    }
+}
+
+fn upgrade_entity25(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(Value::Map(entity_type)) = entity.get_mut(&Value::Text("ty".into())) {
+            if let Some(Value::Map(fighter)) = entity_type.get_mut(&Value::Text("Fighter".into())) {
+                // previously every fighter used the throws hardcoded for Toriel
+                let throw = |angle| {
+                    new_object(vec![
+                        ("release_frame", Value::Integer(5)),
+                        ("angle", Value::Float(angle)),
+                        ("damage", Value::Float(5.0)),
+                        ("bkb", Value::Float(80.0)),
+                        ("kbg", Value::Float(1.1)),
+                    ])
+                };
+                let throws = new_object(vec![
+                    ("up", throw(85.0)),
+                    ("down", throw(-90.0)),
+                    ("forward", throw(30.0)),
+                    ("back", throw(170.0)),
+                ]);
+                fighter.insert(Value::Text("throws".into()), throws);
+            }
+        }
+    }
+}
+
+fn upgrade_entity23(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(actions) = entity.get_mut(&Value::Text("actions".into())) {
+            if let Some(actions) = get_vec(actions, "vector") {
+                for action in actions {
+                    if let Some(frames) = get_vec(action, "frames") {
+                        for frame in frames {
+                            if let Value::Map(frame) = frame {
+                                frame.insert(Value::Text("tags".into()), Value::Array(vec![]));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn upgrade_entity22(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(Value::Map(entity_type)) = entity.get_mut(&Value::Text("ty".into())) {
+            if let Some(Value::Map(fighter)) = entity_type.get_mut(&Value::Text("Fighter".into())) {
+                fighter.insert(Value::Text("has_super".into()), Value::Bool(false));
+            }
+        }
+    }
+}
+
+fn upgrade_entity20(entity: &mut Value) {
+    if let Value::Map(entity) = entity {
+        if let Some(actions) = entity.get_mut(&Value::Text("actions".into())) {
+            if let Some(actions) = get_vec(actions, "vector") {
+                for action in actions {
+                    if let Value::Map(action) = action {
+                        action.insert(Value::Text("frame_step_script".into()), Value::Null);
+                        action.insert(Value::Text("action_expired_script".into()), Value::Null);
+                    }
+                }
+            }
+        }
+    }
+}
+
 fn upgrade_entity19(entity: &mut Value) {
     if let Value::Map(entity) = entity {
         entity.insert(Value::Text("css_action".into()), Value::Text("Idle".into()));